@@ -0,0 +1,638 @@
+//! `.aseprite`/`.ase` files, as described in
+//! <https://github.com/aseprite/aseprite/blob/main/docs/ase-file-specs.md>
+
+use log::warn;
+use tubereng_asset::{AssetError, Result};
+use tubereng_image::{Image, ImageMeta};
+use tubereng_renderer::texture;
+
+use crate::{Aseprite, AtlasImage, Direction, Frame, Tag};
+
+const FILE_MAGIC: u16 = 0xA5E0;
+const FRAME_MAGIC: u16 = 0xF1FA;
+const HEADER_SIZE: usize = 128;
+const FRAME_HEADER_SIZE: usize = 16;
+const CHUNK_HEADER_SIZE: usize = 6;
+
+const OLD_PALETTE_CHUNK: u16 = 0x0004;
+const LAYER_CHUNK: u16 = 0x2004;
+const CEL_CHUNK: u16 = 0x2005;
+const TAGS_CHUNK: u16 = 0x2018;
+const PALETTE_CHUNK: u16 = 0x2019;
+
+const LAYER_VISIBLE: u16 = 1;
+const LAYER_OPACITY_VALID: u32 = 1;
+
+const RAW_CEL: u16 = 0;
+const LINKED_CEL: u16 = 1;
+const COMPRESSED_CEL: u16 = 2;
+
+const NAMED_PALETTE_ENTRY: u16 = 1;
+const MAX_PALETTE_INDEX: usize = 0xFFFF;
+
+pub(crate) fn is_aseprite_file(file_content: &[u8]) -> bool {
+    file_content.len() >= HEADER_SIZE && file_content[4..6] == FILE_MAGIC.to_le_bytes()
+}
+
+/// Little endian reader of the fields of a file, failing on truncated data
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8]> {
+        if count > self.data.len() {
+            return Err(AssetError::AsepriteDeserializationFailed);
+        }
+        let (bytes, rest) = self.data.split_at(count);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn word(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn short(&mut self) -> Result<i16> {
+        let bytes = self.bytes(2)?;
+        Ok(i16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn dword(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let length = self.word()?;
+        Ok(String::from_utf8_lossy(self.bytes(length.into())?).into_owned())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ColorDepth {
+    Rgba,
+    Grayscale,
+    Indexed { transparent_index: u8 },
+}
+
+impl ColorDepth {
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            ColorDepth::Rgba => 4,
+            ColorDepth::Grayscale => 2,
+            ColorDepth::Indexed { .. } => 1,
+        }
+    }
+}
+
+struct Layer {
+    visible: bool,
+    opacity: u8,
+}
+
+#[derive(Clone)]
+struct Cel {
+    layer: usize,
+    x: i32,
+    y: i32,
+    opacity: u8,
+    width: usize,
+    height: usize,
+    /// RGBA pixels, row by row
+    pixels: Vec<u8>,
+}
+
+struct Document {
+    width: usize,
+    height: usize,
+    color_depth: ColorDepth,
+    layer_opacity_valid: bool,
+    palette: Vec<[u8; 4]>,
+    layers: Vec<Layer>,
+    /// Visibility of the last layer of each level of the layer hierarchy
+    layer_ancestors: Vec<bool>,
+    frames: Vec<(f32, Vec<Cel>)>,
+    tags: Vec<Tag>,
+}
+
+pub(crate) fn load(file_content: &[u8], meta: &ImageMeta) -> Result<Aseprite> {
+    let mut header = Reader {
+        data: file_content.get(..HEADER_SIZE).unwrap_or_default(),
+    };
+    header.dword()?;
+    if header.word()? != FILE_MAGIC {
+        return Err(AssetError::AsepriteDeserializationFailed);
+    }
+    let frame_count = header.word()?;
+    let width = header.word()?.into();
+    let height = header.word()?.into();
+    let color_depth = header.word()?;
+    let flags = header.dword()?;
+    header.bytes(10)?;
+    let transparent_index = header.byte()?;
+    let color_depth = match color_depth {
+        32 => ColorDepth::Rgba,
+        16 => ColorDepth::Grayscale,
+        8 => ColorDepth::Indexed { transparent_index },
+        _ => return Err(AssetError::AsepriteDeserializationFailed),
+    };
+
+    let mut document = Document {
+        width,
+        height,
+        color_depth,
+        layer_opacity_valid: flags & LAYER_OPACITY_VALID != 0,
+        palette: vec![],
+        layers: vec![],
+        layer_ancestors: vec![],
+        frames: vec![],
+        tags: vec![],
+    };
+    let mut reader = Reader {
+        data: &file_content[HEADER_SIZE..],
+    };
+    for _ in 0..frame_count {
+        let frame_size = reader.dword()? as usize;
+        if frame_size < FRAME_HEADER_SIZE {
+            return Err(AssetError::AsepriteDeserializationFailed);
+        }
+        let frame_data = reader.bytes(frame_size - 4)?;
+        document.read_frame(frame_data)?;
+    }
+    Ok(document.into_aseprite(meta))
+}
+
+impl Document {
+    fn read_frame(&mut self, frame_data: &[u8]) -> Result<()> {
+        let mut reader = Reader { data: frame_data };
+        if reader.word()? != FRAME_MAGIC {
+            return Err(AssetError::AsepriteDeserializationFailed);
+        }
+        let old_chunk_count = reader.word()?;
+        let duration = f32::from(reader.word()?) / 1000.0;
+        reader.bytes(2)?;
+        let chunk_count = match reader.dword()? {
+            0 => u32::from(old_chunk_count),
+            chunk_count => chunk_count,
+        };
+
+        self.frames.push((duration, vec![]));
+        for _ in 0..chunk_count {
+            let chunk_size = reader.dword()? as usize;
+            let chunk_type = reader.word()?;
+            let chunk_data = reader.bytes(chunk_size.saturating_sub(CHUNK_HEADER_SIZE))?;
+            let mut chunk = Reader { data: chunk_data };
+            match chunk_type {
+                OLD_PALETTE_CHUNK => self.read_old_palette(&mut chunk)?,
+                PALETTE_CHUNK => self.read_palette(&mut chunk)?,
+                LAYER_CHUNK => self.read_layer(&mut chunk)?,
+                CEL_CHUNK => self.read_cel(&mut chunk)?,
+                TAGS_CHUNK => self.read_tags(&mut chunk)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn palette_entry(&mut self, index: usize) -> &mut [u8; 4] {
+        if index >= self.palette.len() {
+            self.palette.resize(index + 1, [0, 0, 0, 255]);
+        }
+        &mut self.palette[index]
+    }
+
+    fn read_old_palette(&mut self, chunk: &mut Reader) -> Result<()> {
+        let mut index = 0;
+        for _ in 0..chunk.word()? {
+            index += usize::from(chunk.byte()?);
+            let color_count = match chunk.byte()? {
+                0 => 256,
+                color_count => usize::from(color_count),
+            };
+            for _ in 0..color_count {
+                let [r, g, b] = [chunk.byte()?, chunk.byte()?, chunk.byte()?];
+                *self.palette_entry(index) = [r, g, b, 255];
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_palette(&mut self, chunk: &mut Reader) -> Result<()> {
+        chunk.dword()?;
+        let first = chunk.dword()? as usize;
+        let last = chunk.dword()? as usize;
+        chunk.bytes(8)?;
+        if last > MAX_PALETTE_INDEX {
+            return Err(AssetError::AsepriteDeserializationFailed);
+        }
+        for index in first..=last {
+            let flags = chunk.word()?;
+            let rgba = chunk.bytes(4)?;
+            *self.palette_entry(index) = [rgba[0], rgba[1], rgba[2], rgba[3]];
+            if flags & NAMED_PALETTE_ENTRY != 0 {
+                chunk.string()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_layer(&mut self, chunk: &mut Reader) -> Result<()> {
+        let flags = chunk.word()?;
+        chunk.word()?;
+        let child_level = usize::from(chunk.word()?);
+        chunk.bytes(6)?;
+        let opacity = chunk.byte()?;
+
+        // A layer is hidden if one of the groups containing it is hidden
+        self.layer_ancestors.truncate(child_level);
+        let parent_visible = self.layer_ancestors.last().copied().unwrap_or(true);
+        let visible = parent_visible && flags & LAYER_VISIBLE != 0;
+        self.layer_ancestors.push(visible);
+
+        self.layers.push(Layer {
+            visible,
+            opacity: if self.layer_opacity_valid {
+                opacity
+            } else {
+                255
+            },
+        });
+        Ok(())
+    }
+
+    fn read_cel(&mut self, chunk: &mut Reader) -> Result<()> {
+        let layer = usize::from(chunk.word()?);
+        let x = i32::from(chunk.short()?);
+        let y = i32::from(chunk.short()?);
+        let opacity = chunk.byte()?;
+        let cel_type = chunk.word()?;
+        chunk.bytes(7)?;
+
+        let cel = match cel_type {
+            LINKED_CEL => {
+                let frame = usize::from(chunk.word()?);
+                self.frames
+                    .get(frame)
+                    .and_then(|(_, cels)| cels.iter().find(|cel| cel.layer == layer))
+                    .cloned()
+                    .ok_or(AssetError::AsepriteDeserializationFailed)?
+            }
+            RAW_CEL | COMPRESSED_CEL => {
+                let width = usize::from(chunk.word()?);
+                let height = usize::from(chunk.word()?);
+                let decompressed;
+                let data = if cel_type == COMPRESSED_CEL {
+                    decompressed = miniz_oxide::inflate::decompress_to_vec_zlib(chunk.data)
+                        .map_err(|_| AssetError::AsepriteDeserializationFailed)?;
+                    &decompressed[..]
+                } else {
+                    chunk.data
+                };
+                let pixel_count = width * height;
+                let data = data
+                    .get(..pixel_count * self.color_depth.bytes_per_pixel())
+                    .ok_or(AssetError::AsepriteDeserializationFailed)?;
+                Cel {
+                    layer,
+                    x,
+                    y,
+                    opacity,
+                    width,
+                    height,
+                    pixels: self.to_rgba(data),
+                }
+            }
+            _ => {
+                warn!("Skipping the tilemap cel of layer {layer}, tilemaps are not supported");
+                return Ok(());
+            }
+        };
+        if let Some((_, cels)) = self.frames.last_mut() {
+            cels.push(cel);
+        }
+        Ok(())
+    }
+
+    fn read_tags(&mut self, chunk: &mut Reader) -> Result<()> {
+        let tag_count = chunk.word()?;
+        chunk.bytes(8)?;
+        for _ in 0..tag_count {
+            let from = usize::from(chunk.word()?);
+            let to = usize::from(chunk.word()?);
+            let direction = match chunk.byte()? {
+                0 => Direction::Forward,
+                1 => Direction::Reverse,
+                2 => Direction::PingPong,
+                3 => Direction::PingPongReverse,
+                _ => return Err(AssetError::AsepriteDeserializationFailed),
+            };
+            chunk.bytes(12)?;
+            let name = chunk.string()?;
+            self.tags.push(Tag {
+                name,
+                from,
+                to,
+                direction,
+            });
+        }
+        Ok(())
+    }
+
+    fn to_rgba(&self, data: &[u8]) -> Vec<u8> {
+        match self.color_depth {
+            ColorDepth::Rgba => data.to_vec(),
+            ColorDepth::Grayscale => data
+                .chunks_exact(2)
+                .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+                .collect(),
+            ColorDepth::Indexed { transparent_index } => data
+                .iter()
+                .flat_map(|&index| {
+                    if index == transparent_index {
+                        [0; 4]
+                    } else {
+                        self.palette
+                            .get(usize::from(index))
+                            .copied()
+                            .unwrap_or([0; 4])
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Flattens the visible layers of each frame, and packs the frames in a
+    /// grid
+    fn into_aseprite(self, meta: &ImageMeta) -> Aseprite {
+        let frame_count = self.frames.len();
+        let columns = (1..=frame_count.max(1))
+            .find(|c| c * c >= frame_count)
+            .unwrap_or(1);
+        let rows = frame_count.div_ceil(columns).max(1);
+        let atlas_width = columns * self.width;
+        let atlas_height = rows * self.height;
+        let mut atlas = vec![0; atlas_width * atlas_height * 4];
+
+        let mut frames = Vec::with_capacity(frame_count);
+        for (i, (duration, cels)) in self.frames.iter().enumerate() {
+            let origin_x = (i % columns) * self.width;
+            let origin_y = (i / columns) * self.height;
+            let mut cels = cels.iter().collect::<Vec<_>>();
+            cels.sort_by_key(|cel| cel.layer);
+            for cel in cels {
+                let Some(layer) = self.layers.get(cel.layer) else {
+                    continue;
+                };
+                if !layer.visible {
+                    continue;
+                }
+                let opacity = f32::from(cel.opacity) / 255.0 * f32::from(layer.opacity) / 255.0;
+                self.draw_cel(cel, opacity, &mut atlas, atlas_width, origin_x, origin_y);
+            }
+
+            // The atlas is at most 65535 frames of 65535 pixels wide
+            #[allow(clippy::cast_precision_loss)]
+            frames.push(Frame {
+                rect: texture::Rect::new(
+                    origin_x as f32,
+                    origin_y as f32,
+                    self.width as f32,
+                    self.height as f32,
+                ),
+                duration: *duration,
+            });
+        }
+
+        // The atlas dimensions are bounded by the u16 sprite size and frame
+        // count
+        #[allow(clippy::cast_possible_truncation)]
+        let atlas = Image::from_rgba8(
+            atlas_width as u32,
+            atlas_height as u32,
+            atlas,
+            meta.premultiply_alpha,
+        );
+        Aseprite {
+            atlas: AtlasImage::Embedded(atlas),
+            frames,
+            tags: self.tags,
+        }
+    }
+
+    /// Blends a cel over a frame of the atlas, with the normal blend mode
+    fn draw_cel(
+        &self,
+        cel: &Cel,
+        opacity: f32,
+        atlas: &mut [u8],
+        atlas_width: usize,
+        origin_x: usize,
+        origin_y: usize,
+    ) {
+        for cel_y in 0..cel.height {
+            let Some(y) = offset(cel.y, cel_y).filter(|&y| y < self.height) else {
+                continue;
+            };
+            for cel_x in 0..cel.width {
+                let Some(x) = offset(cel.x, cel_x).filter(|&x| x < self.width) else {
+                    continue;
+                };
+                let source = &cel.pixels[(cel_y * cel.width + cel_x) * 4..][..4];
+                let target = (origin_y + y) * atlas_width + origin_x + x;
+                blend(source, opacity, &mut atlas[target * 4..][..4]);
+            }
+        }
+    }
+}
+
+fn offset(origin: i32, offset: usize) -> Option<usize> {
+    usize::try_from(i64::from(origin) + i64::try_from(offset).ok()?).ok()
+}
+
+fn blend(source: &[u8], opacity: f32, target: &mut [u8]) {
+    let source_alpha = f32::from(source[3]) / 255.0 * opacity;
+    let target_alpha = f32::from(target[3]) / 255.0;
+    let alpha = source_alpha + target_alpha * (1.0 - source_alpha);
+    if alpha <= 0.0 {
+        return;
+    }
+    for channel in 0..3 {
+        let color = (f32::from(source[channel]) * source_alpha
+            + f32::from(target[channel]) * target_alpha * (1.0 - source_alpha))
+            / alpha;
+        // The blended channels stay in [0, 255]
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let color = color.round() as u8;
+        target[channel] = color;
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let alpha = (alpha * 255.0).round() as u8;
+    target[3] = alpha;
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_asset::AssetLoader;
+
+    use super::*;
+    use crate::AsepriteLoader;
+
+    fn size(length: usize) -> [u8; 4] {
+        u32::try_from(length).unwrap().to_le_bytes()
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut data = u16::try_from(value.len()).unwrap().to_le_bytes().to_vec();
+        data.extend(value.as_bytes());
+        data
+    }
+
+    fn chunk(chunk_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut chunk = size(data.len() + CHUNK_HEADER_SIZE).to_vec();
+        chunk.extend(chunk_type.to_le_bytes());
+        chunk.extend(data);
+        chunk
+    }
+
+    fn frame(duration_ms: u16, chunks: &[Vec<u8>]) -> Vec<u8> {
+        let chunk_count = chunks.len();
+        let chunks = chunks.concat();
+        let mut frame = size(chunks.len() + FRAME_HEADER_SIZE).to_vec();
+        frame.extend(FRAME_MAGIC.to_le_bytes());
+        frame.extend(0xFFFF_u16.to_le_bytes());
+        frame.extend(duration_ms.to_le_bytes());
+        frame.extend([0; 2]);
+        frame.extend(size(chunk_count));
+        frame.extend(chunks);
+        frame
+    }
+
+    fn layer(flags: u16, opacity: u8, name: &str) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend(flags.to_le_bytes());
+        data.extend([0; 10]);
+        data.push(opacity);
+        data.extend([0; 3]);
+        data.extend(string(name));
+        chunk(LAYER_CHUNK, &data)
+    }
+
+    fn cel(layer: u16, x: i16, y: i16, cel_type: u16, content: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend(layer.to_le_bytes());
+        data.extend(x.to_le_bytes());
+        data.extend(y.to_le_bytes());
+        data.push(255);
+        data.extend(cel_type.to_le_bytes());
+        data.extend([0; 7]);
+        data.extend(content);
+        chunk(CEL_CHUNK, &data)
+    }
+
+    fn image_cel(width: u16, height: u16, pixels: &[u8]) -> Vec<u8> {
+        let mut content = width.to_le_bytes().to_vec();
+        content.extend(height.to_le_bytes());
+        content.extend(pixels);
+        content
+    }
+
+    fn tags(name: &str, from: u16, to: u16, direction: u8) -> Vec<u8> {
+        let mut data = 1_u16.to_le_bytes().to_vec();
+        data.extend([0; 8]);
+        data.extend(from.to_le_bytes());
+        data.extend(to.to_le_bytes());
+        data.push(direction);
+        data.extend([0; 12]);
+        data.extend(string(name));
+        chunk(TAGS_CHUNK, &data)
+    }
+
+    /// 2x2 sprite of 3 layers and 2 frames
+    fn sprite() -> Vec<u8> {
+        let red = [255, 0, 0, 255].repeat(4);
+        let green = [0, 255, 0, 255].repeat(4);
+        let blue = miniz_oxide::deflate::compress_to_vec_zlib(&[0, 0, 255, 255], 6);
+        let frames = [
+            frame(
+                100,
+                &[
+                    layer(LAYER_VISIBLE, 255, "background"),
+                    layer(0, 255, "hidden"),
+                    layer(LAYER_VISIBLE, 128, "overlay"),
+                    tags("blink", 0, 1, 2),
+                    cel(0, 0, 0, RAW_CEL, &image_cel(2, 2, &red)),
+                    cel(1, 0, 0, RAW_CEL, &image_cel(2, 2, &green)),
+                    cel(2, 1, 1, COMPRESSED_CEL, &image_cel(1, 1, &blue)),
+                ],
+            ),
+            frame(250, &[cel(0, 0, 0, LINKED_CEL, &0_u16.to_le_bytes())]),
+        ];
+
+        let frames = frames.concat();
+        let mut file = size(frames.len() + HEADER_SIZE).to_vec();
+        file.extend(FILE_MAGIC.to_le_bytes());
+        file.extend(2_u16.to_le_bytes());
+        file.extend(2_u16.to_le_bytes());
+        file.extend(2_u16.to_le_bytes());
+        file.extend(32_u16.to_le_bytes());
+        file.extend(LAYER_OPACITY_VALID.to_le_bytes());
+        file.resize(HEADER_SIZE, 0);
+        file.extend(frames);
+        file
+    }
+
+    #[test]
+    fn load_aseprite_file() {
+        let aseprite = AsepriteLoader::load(&sprite()).unwrap();
+        assert_eq!(
+            aseprite.frames,
+            vec![
+                Frame {
+                    rect: texture::Rect::new(0.0, 0.0, 2.0, 2.0),
+                    duration: 0.1,
+                },
+                Frame {
+                    rect: texture::Rect::new(2.0, 0.0, 2.0, 2.0),
+                    duration: 0.25,
+                },
+            ]
+        );
+        assert_eq!(
+            aseprite.tags,
+            vec![Tag {
+                name: "blink".into(),
+                from: 0,
+                to: 1,
+                direction: Direction::PingPong,
+            }]
+        );
+
+        let AtlasImage::Embedded(atlas) = &aseprite.atlas else {
+            panic!("the atlas of an .aseprite file is embedded");
+        };
+        assert_eq!((atlas.width(), atlas.height()), (4, 2));
+        let pixel = |x: usize, y: usize| &atlas.data()[(y * 4 + x) * 4..][..4];
+        // The hidden layer isn't drawn, and the overlay is half transparent
+        assert_eq!(pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(1, 1), [127, 0, 128, 255]);
+        // The linked cel of the second frame only has the background
+        assert_eq!(pixel(2, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(3, 1), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let file = sprite();
+        assert!(matches!(
+            AsepriteLoader::load(&file[..file.len() - 4]),
+            Err(AssetError::AsepriteDeserializationFailed)
+        ));
+    }
+}
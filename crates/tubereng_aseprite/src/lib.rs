@@ -0,0 +1,356 @@
+#![warn(clippy::pedantic)]
+
+//! Aseprite importer, loading `.aseprite`/`.ase` files and the JSON data
+//! exported by Aseprite alongside a sprite sheet.
+//!
+//! Both produce a texture atlas with the rects and durations of the frames
+//! and the animation tags, ready to be played by an
+//! [`AnimatedSprite`](tubereng_renderer::sprite::AnimatedSprite) with
+//! [`Aseprite::animated_sprite`].
+
+use tubereng_asset::{json::Value, Asset, AssetError, AssetLoader, Result};
+use tubereng_image::{Image, ImageMeta};
+use tubereng_renderer::{
+    sprite::{AnimatedSprite, AnimationState},
+    texture,
+};
+
+mod binary;
+
+/// Sprite sheet loaded from an Aseprite file or JSON export
+pub struct Aseprite {
+    pub atlas: AtlasImage,
+    pub frames: Vec<Frame>,
+    pub tags: Vec<Tag>,
+}
+
+/// Texture atlas of the frames of an [`Aseprite`] asset
+pub enum AtlasImage {
+    /// Frames composited from the layers of an `.aseprite` file
+    Embedded(Image),
+    /// Path of the sprite sheet of a JSON export, relative to the JSON file
+    Path(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Area of the frame in the texture atlas, in pixels
+    pub rect: texture::Rect,
+    /// Duration of the frame in seconds
+    pub duration: f32,
+}
+
+/// Named range of frames, played as an animation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    /// Index of the first frame of the tag
+    pub from: usize,
+    /// Index of the last frame of the tag, included
+    pub to: usize,
+    pub direction: Direction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Forward,
+    Reverse,
+    /// Forward then backward, without repeating the first and last frames
+    PingPong,
+    /// Backward then forward, without repeating the first and last frames
+    PingPongReverse,
+}
+
+impl Tag {
+    /// Returns the indices of the frames of one loop of the animation
+    #[must_use]
+    pub fn frame_indices(&self) -> Vec<usize> {
+        let forward = self.from..=self.to;
+        let inner = self.from + 1..self.to;
+        match self.direction {
+            Direction::Forward => forward.collect(),
+            Direction::Reverse => forward.rev().collect(),
+            Direction::PingPong => forward.chain(inner.rev()).collect(),
+            Direction::PingPongReverse => forward.rev().chain(inner).collect(),
+        }
+    }
+}
+
+/// Resolves a path relative to the path of an Aseprite JSON export, e.g.
+/// `hero.png` next to `sprites/hero.json` is `sprites/hero.png`
+#[must_use]
+pub fn resolve_path(file_path: &str, relative_path: &str) -> String {
+    match file_path.rfind('/') {
+        Some(separator) => format!("{}{relative_path}", &file_path[..=separator]),
+        None => relative_path.to_string(),
+    }
+}
+
+impl Aseprite {
+    /// Returns the animations of the tags, named after them, or a single
+    /// unnamed animation of all the frames if there is no tag
+    #[must_use]
+    pub fn animation_state(&self) -> AnimationState {
+        let frame_lists = if self.tags.is_empty() {
+            vec![(0..self.frames.len()).collect::<Vec<_>>()]
+        } else {
+            self.tags.iter().map(Tag::frame_indices).collect()
+        };
+        AnimationState {
+            animations: frame_lists
+                .iter()
+                .map(|frames| {
+                    frames
+                        .iter()
+                        .map(|&i| self.frames[i].rect.clone())
+                        .collect()
+                })
+                .collect(),
+            animation_names: self.tags.iter().map(|tag| tag.name.clone()).collect(),
+            frame_durations: frame_lists
+                .iter()
+                .map(|frames| frames.iter().map(|&i| self.frames[i].duration).collect())
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Returns an animated sprite playing the first animation, the atlas
+    /// having been uploaded as `texture_atlas`
+    #[must_use]
+    pub fn animated_sprite(&self, texture_atlas: texture::Id) -> AnimatedSprite {
+        AnimatedSprite {
+            texture_atlas,
+            animation: self.animation_state(),
+        }
+    }
+}
+
+impl Asset for Aseprite {
+    type Loader = AsepriteLoader;
+}
+
+/// Loads `.aseprite`/`.ase` files, whose visible layers are flattened in an
+/// embedded atlas, and the JSON exported by Aseprite along with a sprite
+/// sheet, either as an array or a hash of frames.
+///
+/// The `.meta` file of an `.aseprite` file holds the [`ImageMeta`] of its
+/// atlas.
+pub struct AsepriteLoader;
+impl AssetLoader<Aseprite> for AsepriteLoader {
+    fn load(file_content: &[u8]) -> Result<Aseprite> {
+        Self::load_with_meta(file_content, None)
+    }
+
+    fn load_with_meta(file_content: &[u8], meta: Option<&[u8]>) -> Result<Aseprite> {
+        let aseprite = if binary::is_aseprite_file(file_content) {
+            binary::load(file_content, &ImageMeta::parse(meta)?)?
+        } else {
+            load_json(file_content)?
+        };
+        if aseprite
+            .tags
+            .iter()
+            .any(|tag| tag.from > tag.to || tag.to >= aseprite.frames.len())
+        {
+            return Err(AssetError::AsepriteDeserializationFailed);
+        }
+        Ok(aseprite)
+    }
+}
+
+fn load_json(file_content: &[u8]) -> Result<Aseprite> {
+    let source =
+        std::str::from_utf8(file_content).map_err(|_| AssetError::AsepriteDeserializationFailed)?;
+    let document = Value::parse(source)?;
+    let frames = document
+        .get("frames")
+        .ok_or(AssetError::AsepriteDeserializationFailed)?;
+    let frames = match frames {
+        Value::Array(frames) => frames.iter().map(parse_json_frame).collect(),
+        Value::Object(frames) => frames
+            .iter()
+            .map(|(_, frame)| parse_json_frame(frame))
+            .collect(),
+        _ => Err(AssetError::AsepriteDeserializationFailed),
+    }?;
+
+    let meta = document.get("meta");
+    let image = meta
+        .and_then(|meta| meta.get("image"))
+        .and_then(Value::as_str)
+        .ok_or(AssetError::AsepriteDeserializationFailed)?;
+    let tags = meta
+        .and_then(|meta| meta.get("frameTags"))
+        .map_or(&[][..], Value::items)
+        .iter()
+        .map(parse_json_tag)
+        .collect::<Result<_>>()?;
+    Ok(Aseprite {
+        atlas: AtlasImage::Path(image.to_string()),
+        frames,
+        tags,
+    })
+}
+
+fn parse_json_frame(frame: &Value) -> Result<Frame> {
+    let rect = frame
+        .get("frame")
+        .ok_or(AssetError::AsepriteDeserializationFailed)?;
+    let field = |key| {
+        rect.get(key)
+            .and_then(Value::as_f32)
+            .ok_or(AssetError::AsepriteDeserializationFailed)
+    };
+    Ok(Frame {
+        rect: texture::Rect::new(field("x")?, field("y")?, field("w")?, field("h")?),
+        duration: frame
+            .get("duration")
+            .and_then(Value::as_f32)
+            .ok_or(AssetError::AsepriteDeserializationFailed)?
+            / 1000.0,
+    })
+}
+
+fn parse_json_tag(tag: &Value) -> Result<Tag> {
+    let index = |key| {
+        tag.get(key)
+            .and_then(Value::as_usize)
+            .ok_or(AssetError::AsepriteDeserializationFailed)
+    };
+    let direction = match tag.get("direction").and_then(Value::as_str) {
+        Some("forward") | None => Direction::Forward,
+        Some("reverse") => Direction::Reverse,
+        Some("pingpong") => Direction::PingPong,
+        Some("pingpong_reverse") => Direction::PingPongReverse,
+        Some(_) => return Err(AssetError::AsepriteDeserializationFailed),
+    };
+    Ok(Tag {
+        name: tag
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or(AssetError::AsepriteDeserializationFailed)?
+            .to_string(),
+        from: index("from")?,
+        to: index("to")?,
+        direction,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH_EXPORT: &str = r##"{
+        "frames": {
+            "hero 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 16, "h": 24 }, "duration": 100 },
+            "hero 1.aseprite": { "frame": { "x": 16, "y": 0, "w": 16, "h": 24 }, "duration": 150 },
+            "hero 2.aseprite": { "frame": { "x": 32, "y": 0, "w": 16, "h": 24 }, "duration": 100 },
+            "hero 3.aseprite": { "frame": { "x": 48, "y": 0, "w": 16, "h": 24 }, "duration": 200 }
+        },
+        "meta": {
+            "app": "https://www.aseprite.org/",
+            "image": "hero.png",
+            "size": { "w": 64, "h": 24 },
+            "frameTags": [
+                { "name": "idle", "from": 0, "to": 0, "direction": "forward", "color": "#000000ff" },
+                { "name": "walk", "from": 1, "to": 3, "direction": "pingpong", "color": "#000000ff" }
+            ]
+        }
+    }"##;
+
+    #[test]
+    fn load_json_hash_export() {
+        let aseprite = AsepriteLoader::load(HASH_EXPORT.as_bytes()).unwrap();
+        assert!(matches!(&aseprite.atlas, AtlasImage::Path(path) if path == "hero.png"));
+        assert_eq!(aseprite.frames.len(), 4);
+        assert_eq!(
+            aseprite.frames[1],
+            Frame {
+                rect: texture::Rect::new(16.0, 0.0, 16.0, 24.0),
+                duration: 0.15,
+            }
+        );
+        assert_eq!(
+            aseprite.tags[1],
+            Tag {
+                name: "walk".into(),
+                from: 1,
+                to: 3,
+                direction: Direction::PingPong,
+            }
+        );
+        assert_eq!(
+            resolve_path("sprites/hero.json", "hero.png"),
+            "sprites/hero.png"
+        );
+    }
+
+    #[test]
+    fn load_json_array_export() {
+        let source = r#"{
+            "frames": [
+                { "filename": "a", "frame": { "x": 0, "y": 8, "w": 8, "h": 8 }, "duration": 50 },
+                { "filename": "b", "frame": { "x": 8, "y": 8, "w": 8, "h": 8 }, "duration": 50 }
+            ],
+            "meta": { "image": "sheet.png" }
+        }"#;
+        let aseprite = AsepriteLoader::load(source.as_bytes()).unwrap();
+        assert_eq!(
+            aseprite.frames[1].rect,
+            texture::Rect::new(8.0, 8.0, 8.0, 8.0)
+        );
+        assert!(aseprite.tags.is_empty());
+
+        let animation = aseprite.animation_state();
+        assert_eq!(animation.animations.len(), 1);
+        assert_eq!(animation.animations[0].len(), 2);
+        assert!(animation.animation_names.is_empty());
+    }
+
+    #[test]
+    fn tags_out_of_range_are_rejected() {
+        let source = r#"{
+            "frames": [{ "frame": { "x": 0, "y": 0, "w": 8, "h": 8 }, "duration": 50 }],
+            "meta": { "image": "sheet.png", "frameTags": [{ "name": "a", "from": 0, "to": 1 }] }
+        }"#;
+        assert!(matches!(
+            AsepriteLoader::load(source.as_bytes()),
+            Err(AssetError::AsepriteDeserializationFailed)
+        ));
+    }
+
+    #[test]
+    fn tags_become_named_animations() {
+        let aseprite = AsepriteLoader::load(HASH_EXPORT.as_bytes()).unwrap();
+        let mut animation = aseprite.animation_state();
+        assert_eq!(animation.animation_names, vec!["idle", "walk"]);
+        assert_eq!(animation.animations[0].len(), 1);
+        // The ping-pong loop doesn't repeat its first and last frames
+        assert_eq!(
+            animation.animations[1],
+            [1, 2, 3, 2].map(|i| aseprite.frames[i].rect.clone())
+        );
+        assert_eq!(animation.frame_durations[1], vec![0.15, 0.1, 0.2, 0.1]);
+
+        assert!(animation.play("walk"));
+        assert!((animation.frame_duration() - 0.15).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn frame_indices_follow_the_direction() {
+        let tag = |direction| Tag {
+            name: String::new(),
+            from: 2,
+            to: 4,
+            direction,
+        };
+        assert_eq!(tag(Direction::Forward).frame_indices(), vec![2, 3, 4]);
+        assert_eq!(tag(Direction::Reverse).frame_indices(), vec![4, 3, 2]);
+        assert_eq!(tag(Direction::PingPong).frame_indices(), vec![2, 3, 4, 3]);
+        assert_eq!(
+            tag(Direction::PingPongReverse).frame_indices(),
+            vec![4, 3, 2, 3]
+        );
+    }
+}
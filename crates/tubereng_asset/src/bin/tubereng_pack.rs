@@ -0,0 +1,25 @@
+//! Packs an asset directory into an archive read with `ArchiveFs`
+//!
+//! Usage: `tubereng_pack <asset directory> <archive path>`
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() -> std::process::ExitCode {
+    use std::process::ExitCode;
+    use tubereng_asset::pack::Packer;
+
+    let arguments = std::env::args().skip(1).collect::<Vec<_>>();
+    let [source_directory, archive_path] = arguments.as_slice() else {
+        eprintln!("Usage: tubereng_pack <asset directory> <archive path>");
+        return ExitCode::FAILURE;
+    };
+    match Packer::new().archive_directory(std::path::Path::new(source_directory), archive_path) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Couldn't archive {source_directory}: {error:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {}
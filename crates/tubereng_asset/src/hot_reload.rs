@@ -0,0 +1,130 @@
+//! Detection of the modified asset files, so that the assets can be reloaded
+//! while the application runs, see [`crate::AssetStore::reload_modified`].
+//!
+//! The modification times of the files are polled, which works on any file
+//! system without a platform notification API. The notify crate isn't
+//! available to the workspace; on Linux, inotify could be used through libc
+//! to get notified of the modifications instead of polling.
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Minimum time between two checks of the watched files
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+struct WatchedFile {
+    asset_path: String,
+    /// `None` while the file doesn't exist
+    modified_time: Option<SystemTime>,
+}
+
+/// Watches the files of the loaded assets for modifications
+pub struct FileWatcher {
+    files: HashMap<PathBuf, WatchedFile>,
+    last_poll: Option<Instant>,
+}
+
+impl FileWatcher {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+            last_poll: None,
+        }
+    }
+
+    /// Watches a file of the asset at `asset_path`, which may not exist yet,
+    /// e.g. its `.meta` file. Watching a file twice has no effect.
+    pub fn watch(&mut self, asset_path: &str, file_path: &str) {
+        let file_path = PathBuf::from(file_path);
+        if self.files.contains_key(&file_path) {
+            return;
+        }
+        let modified_time = modified_time(&file_path);
+        self.files.insert(
+            file_path,
+            WatchedFile {
+                asset_path: asset_path.to_string(),
+                modified_time,
+            },
+        );
+    }
+
+    /// Returns the paths of the assets whose files have been modified,
+    /// created or deleted since the last check. The files are checked at
+    /// most every [`POLL_INTERVAL`].
+    pub fn poll(&mut self) -> Vec<String> {
+        let now = Instant::now();
+        if self
+            .last_poll
+            .is_some_and(|last_poll| now.duration_since(last_poll) < POLL_INTERVAL)
+        {
+            return vec![];
+        }
+        self.last_poll = Some(now);
+        self.check()
+    }
+
+    /// Checks the watched files, regardless of the poll interval
+    fn check(&mut self) -> Vec<String> {
+        let mut modified_asset_paths = vec![];
+        for (file_path, watched_file) in &mut self.files {
+            let modified_time = modified_time(file_path);
+            if modified_time == watched_file.modified_time {
+                continue;
+            }
+            watched_file.modified_time = modified_time;
+            if !modified_asset_paths.contains(&watched_file.asset_path) {
+                modified_asset_paths.push(watched_file.asset_path.clone());
+            }
+        }
+        modified_asset_paths
+    }
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn modified_time(file_path: &PathBuf) -> Option<SystemTime> {
+    std::fs::metadata(file_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_watcher_detects_modifications() {
+        let directory =
+            std::env::temp_dir().join(format!("tubereng_hot_reload_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let file_path = directory.join("sprite.png");
+        let meta_path = directory.join("sprite.png.meta");
+        std::fs::write(&file_path, [0]).unwrap();
+
+        let mut watcher = FileWatcher::new();
+        watcher.watch("sprite.png", file_path.to_str().unwrap());
+        watcher.watch("sprite.png", meta_path.to_str().unwrap());
+        assert!(watcher.poll().is_empty());
+
+        let file = std::fs::File::options()
+            .write(true)
+            .open(&file_path)
+            .unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        std::fs::write(&meta_path, "()").unwrap();
+        assert_eq!(watcher.check(), ["sprite.png"]);
+        assert!(watcher.check().is_empty());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,347 @@
+//! Minimal JSON parser for the loaders of JSON based formats, e.g. glTF,
+//! keeping the parsed values as a tree queried by the loaders.
+
+use indexmap::IndexMap;
+
+use crate::{AssetError, Result};
+
+/// Maximum nesting of the arrays and objects of a document, so that a
+/// malicious document can't overflow the stack of the recursive parser
+pub const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Members of an object, in the order of the document. The last value
+    /// of a duplicated key is kept.
+    Object(IndexMap<String, Value>),
+}
+
+impl Value {
+    /// Parses a JSON document
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the document isn't valid JSON
+    pub fn parse(source: &str) -> Result<Value> {
+        let mut parser = Parser {
+            rest: source,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if !parser.rest.is_empty() {
+            return Err(AssetError::JsonDeserializationFailed);
+        }
+        Ok(value)
+    }
+
+    /// Returns the member `key` of an object, `None` if missing or if the
+    /// value isn't an object
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.get(key),
+            _ => None,
+        }
+    }
+
+    /// Returns the items of an array, an empty slice if the value isn't an
+    /// array
+    #[must_use]
+    pub fn items(&self) -> &[Value] {
+        match self {
+            Value::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    /// Returns the members of an object in the order of the document, none
+    /// if the value isn't an object
+    pub fn members(&self) -> impl Iterator<Item = (&String, &Value)> {
+        match self {
+            Value::Object(members) => Some(members.iter()),
+            _ => None,
+        }
+        .into_iter()
+        .flatten()
+    }
+
+    #[must_use]
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            #[allow(clippy::cast_possible_truncation)] // glTF values are single precision
+            Value::Number(number) => Some(*number as f32),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)] // Checked above
+            Value::Number(number) if number.fract() == 0.0 && *number >= 0.0 => {
+                Some(*number as usize)
+            }
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the array of numbers as a fixed size array
+    #[must_use]
+    pub fn as_f32_array<const N: usize>(&self) -> Option<[f32; N]> {
+        let items = self.items();
+        if items.len() != N {
+            return None;
+        }
+        let mut array = [0.0; N];
+        for (element, item) in array.iter_mut().zip(items) {
+            *element = item.as_f32()?;
+        }
+        Some(array)
+    }
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+    /// Number of arrays and objects the parser is in
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> Result<Value> {
+        self.skip_whitespace();
+        match self.rest.chars().next() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            Some('n') => self.keyword("null", Value::Null),
+            _ => self.number(),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value> {
+        let mut members = IndexMap::new();
+        self.parse_delimited('{', '}', |parser| {
+            parser.skip_whitespace();
+            let key = parser.string()?;
+            parser.expect(':')?;
+            members.insert(key, parser.value()?);
+            Ok(())
+        })?;
+        Ok(Value::Object(members))
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        let mut items = vec![];
+        self.parse_delimited('[', ']', |parser| {
+            items.push(parser.value()?);
+            Ok(())
+        })?;
+        Ok(Value::Array(items))
+    }
+
+    /// Parses the comma separated items between `open` and `close`
+    fn parse_delimited(
+        &mut self,
+        open: char,
+        close: char,
+        mut parse_item: impl FnMut(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        self.expect(open)?;
+        if self.depth == MAX_DEPTH {
+            return Err(AssetError::JsonDeserializationFailed);
+        }
+        self.depth += 1;
+        if !self.consume(close) {
+            loop {
+                parse_item(self)?;
+                if !self.consume(',') {
+                    self.expect(close)?;
+                    break;
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let mut rest = self
+            .rest
+            .strip_prefix('"')
+            .ok_or(AssetError::JsonDeserializationFailed)?
+            .chars();
+        let mut string = String::new();
+        loop {
+            match rest.next().ok_or(AssetError::JsonDeserializationFailed)? {
+                '"' => break,
+                '\\' => {
+                    let escaped = match rest.next().ok_or(AssetError::JsonDeserializationFailed)? {
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => unicode_escape(&mut rest)?,
+                        c @ ('"' | '\\' | '/') => c,
+                        _ => return Err(AssetError::JsonDeserializationFailed),
+                    };
+                    string.push(escaped);
+                }
+                c => string.push(c),
+            }
+        }
+        self.rest = rest.as_str();
+        Ok(string)
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let length = self
+            .rest
+            .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            .unwrap_or(self.rest.len());
+        let number = self.rest[..length]
+            .parse()
+            .map_err(|_| AssetError::JsonDeserializationFailed)?;
+        self.rest = &self.rest[length..];
+        Ok(Value::Number(number))
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Value) -> Result<Value> {
+        self.rest = self
+            .rest
+            .strip_prefix(keyword)
+            .ok_or(AssetError::JsonDeserializationFailed)?;
+        Ok(value)
+    }
+
+    fn expect(&mut self, token: char) -> Result<()> {
+        if self.consume(token) {
+            Ok(())
+        } else {
+            Err(AssetError::JsonDeserializationFailed)
+        }
+    }
+
+    fn consume(&mut self, token: char) -> bool {
+        self.skip_whitespace();
+        let Some(rest) = self.rest.strip_prefix(token) else {
+            return false;
+        };
+        self.rest = rest;
+        true
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+}
+
+/// Parses the 4 hexadecimal digits following `\u`, and the low surrogate
+/// escape following a high surrogate
+fn unicode_escape(rest: &mut std::str::Chars) -> Result<char> {
+    let high = code_unit(rest).ok_or(AssetError::JsonDeserializationFailed)?;
+    let code_point = if (0xD800..0xDC00).contains(&high) {
+        if !rest.as_str().starts_with("\\u") {
+            return Err(AssetError::JsonDeserializationFailed);
+        }
+        rest.nth(1);
+        let low = code_unit(rest)
+            .filter(|low| (0xDC00..0xE000).contains(low))
+            .ok_or(AssetError::JsonDeserializationFailed)?;
+        0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+    } else {
+        high
+    };
+    char::from_u32(code_point).ok_or(AssetError::JsonDeserializationFailed)
+}
+
+fn code_unit(rest: &mut std::str::Chars) -> Option<u32> {
+    let code_unit = u32::from_str_radix(rest.as_str().get(..4)?, 16).ok()?;
+    rest.nth(3);
+    Some(code_unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_values() {
+        let value = Value::parse(
+            r#" { "asset": {"version": "2.0"}, "values": [1, -2.5e1, true, null, "\u00e9\ud83d\ude00\n"], "empty": [] } "#,
+        )
+        .unwrap();
+        assert_eq!(
+            value.get("asset").and_then(|asset| asset.get("version")),
+            Some(&Value::String("2.0".to_string()))
+        );
+        let values = value.get("values").unwrap().items();
+        assert_eq!(values[0].as_usize(), Some(1));
+        assert_eq!(values[1].as_f32(), Some(-25.0));
+        assert_eq!(values[1].as_usize(), None);
+        assert_eq!(values[2].as_bool(), Some(true));
+        assert_eq!(values[3], Value::Null);
+        assert_eq!(values[4].as_str(), Some("é😀\n"));
+        assert!(value.get("empty").unwrap().items().is_empty());
+        assert!(value.get("missing").is_none());
+        let names = value
+            .members()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["asset", "values", "empty"]);
+    }
+
+    #[test]
+    fn invalid_documents_are_rejected() {
+        for source in [
+            "{",
+            "[1,]",
+            "{\"a\" 1}",
+            "tru",
+            "\"\\x\"",
+            "[1] 2",
+            "\"\\ud83d\"",
+        ] {
+            assert!(Value::parse(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Value::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Value::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(Value::parse(&"{\"a\":".repeat(100_000)).is_err());
+    }
+
+    #[test]
+    fn duplicated_keys_keep_the_last_value() {
+        let value = Value::parse(r#"{"a": 1, "b": 2, "a": 3}"#).unwrap();
+        assert_eq!(value.get("a").and_then(Value::as_usize), Some(3));
+        assert_eq!(value.members().count(), 2);
+    }
+}
@@ -1,10 +1,25 @@
 #![warn(clippy::pedantic)]
 
 use log::warn;
-use std::{any::Any, hash::Hasher, marker::PhantomData, path::PathBuf};
+use std::{
+    any::Any,
+    hash::Hasher,
+    marker::PhantomData,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+};
 
 use vfs::VirtualFileSystem;
 
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+pub mod hot_reload;
+pub mod json;
+pub mod pack;
+pub mod scene;
+pub mod settings;
 pub mod vfs;
 pub type Result<T> = std::result::Result<T, AssetError>;
 
@@ -12,35 +27,94 @@ pub type Result<T> = std::result::Result<T, AssetError>;
 pub enum AssetError {
     PathCanonicalizationFailed,
     ImageDecodingFailed,
+    /// The file, or the entry of a pack, archive or embedded directory,
+    /// doesn't exist
+    NotFound,
     ReadFailed,
+    WriteFailed,
+    LocalStorageUnavailable,
     AssetPathIsInvalidUTF8,
     ExecutablePathAcquisitionFailed(std::io::Error),
+    SettingsSerializationFailed,
+    SettingsDeserializationFailed,
+    SettingsMigrationFailed {
+        from_version: u32,
+    },
+    MetaDeserializationFailed,
+    UnsupportedTextureContainer,
+    PackManifestSerializationFailed,
+    PackManifestDeserializationFailed,
+    PackIntegrityCheckFailed {
+        path: String,
+    },
+    SceneDeserializationFailed,
+    ArchiveIndexSerializationFailed,
+    InvalidArchive,
+    GltfDeserializationFailed,
+    /// The buffers of a glTF asset must be embedded, as in `.glb` files
+    UnsupportedGltfBuffer {
+        uri: String,
+    },
+    /// Invalid statement of a Wavefront `.obj` or `.mtl` file
+    ObjDeserializationFailed {
+        line: usize,
+    },
+    FontDecodingFailed,
+    JsonDeserializationFailed,
+    AsepriteDeserializationFailed,
+    LdtkDeserializationFailed,
+    /// The file is being fetched asynchronously, e.g. by the `WebFs` of the
+    /// web builds, and can be read again once received
+    Pending,
 }
 
-#[derive(Debug)]
+/// Handle to an asset stored in an [`AssetStore`]. The asset is unloaded by
+/// [`AssetStore::free_unused`] once all the clones of its handle have been
+/// dropped.
 pub struct AssetHandle<T> {
+    inner: Arc<HandleInner>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+struct HandleInner {
     id: usize,
-    _marker: PhantomData<T>,
+    dropped: Sender<usize>,
+}
+
+impl Drop for HandleInner {
+    fn drop(&mut self) {
+        // The store may have been dropped before the handle
+        let _ = self.dropped.send(self.id);
+    }
 }
 
 impl<T> AssetHandle<T> {
     #[must_use]
     pub fn id(&self) -> usize {
-        self.id
+        self.inner.id
+    }
+}
+
+impl<T> std::fmt::Debug for AssetHandle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AssetHandle")
+            .field("id", &self.inner.id)
+            .finish()
     }
 }
 
 impl<T> Clone for AssetHandle<T> {
     fn clone(&self) -> Self {
-        *self
+        Self {
+            inner: Arc::clone(&self.inner),
+            _marker: PhantomData,
+        }
     }
 }
 
-impl<T> Copy for AssetHandle<T> {}
-
 impl<T> PartialEq for AssetHandle<T> {
     fn eq(&self, other: &Self) -> bool {
-        self.id == other.id
+        self.inner.id == other.inner.id
     }
 }
 
@@ -48,23 +122,29 @@ impl<T> Eq for AssetHandle<T> {}
 
 impl<T> std::hash::Hash for AssetHandle<T> {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.id.hash(state);
+        self.inner.id.hash(state);
     }
 }
 
-impl<T: 'static> AssetHandle<T> {
-    #[must_use]
-    fn new(id: usize) -> Self {
-        Self {
-            id,
-            _marker: PhantomData,
-        }
-    }
-}
+/// Loads an asset again from its path, see [`AssetStore::reload`]
+type Reloader = fn(&AssetStore, &str) -> Result<Box<dyn Any>>;
 
 pub struct AssetStore {
     fs: Box<dyn VirtualFileSystem>,
-    assets: Vec<Box<dyn Any>>,
+    /// Stored assets by id, `None` once unloaded
+    assets: Vec<Option<Box<dyn Any>>>,
+    /// Paths the stored assets have been loaded from, `None` for the assets
+    /// stored directly
+    sources: Vec<Option<(String, Reloader)>>,
+    /// Ids of the unloaded assets, reused by the next stored assets
+    free_ids: Vec<usize>,
+    /// Ids of the assets loaded with [`AssetStore::load`] whose files were
+    /// still being fetched, see [`AssetStore::load_pending`]
+    pending_ids: Vec<usize>,
+    dropped_handles: Receiver<usize>,
+    dropped_handle_sender: Sender<usize>,
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    watcher: std::cell::RefCell<hot_reload::FileWatcher>,
 }
 impl AssetStore {
     #[must_use]
@@ -72,13 +152,23 @@ impl AssetStore {
     where
         FS: VirtualFileSystem + 'static,
     {
+        let (dropped_handle_sender, dropped_handles) = mpsc::channel();
         Self {
             fs: Box::new(fs),
             assets: vec![],
+            sources: vec![],
+            free_ids: vec![],
+            pending_ids: vec![],
+            dropped_handles,
+            dropped_handle_sender,
+            #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+            watcher: std::cell::RefCell::new(hot_reload::FileWatcher::new()),
         }
     }
 
-    /// Loads an asset using an asset path and returns the asset without storing it
+    /// Loads an asset using an asset path and returns the asset without storing it.
+    /// If a `<asset_path>.meta` file exists next to the asset, its content is
+    /// given to the asset loader.
     ///
     /// # Errors
     ///
@@ -106,15 +196,30 @@ impl AssetStore {
         let mut resolved_asset_path = PathBuf::new();
 
         resolved_asset_path.push(asset_path);
-        let bytes = self.fs.read_bytes(
-            resolved_asset_path
-                .to_str()
-                .ok_or(AssetError::AssetPathIsInvalidUTF8)?,
-        )?;
-        A::Loader::load(&bytes)
+        let resolved_asset_path = resolved_asset_path
+            .to_str()
+            .ok_or(AssetError::AssetPathIsInvalidUTF8)?;
+        let meta_path = format!("{resolved_asset_path}.meta");
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        {
+            let mut watcher = self.watcher.borrow_mut();
+            watcher.watch(asset_path, resolved_asset_path);
+            watcher.watch(asset_path, &meta_path);
+        }
+        // Both files are read before returning, so that they are fetched
+        // together when the file system fetches them asynchronously
+        let bytes = self.fs.read_bytes(resolved_asset_path);
+        let meta = self.fs.read_bytes(&meta_path);
+        let bytes = bytes?;
+        if let Err(AssetError::Pending) = meta {
+            return Err(AssetError::Pending);
+        }
+        A::Loader::load_with_meta(&bytes, meta.ok().as_deref())
     }
 
-    /// Loads an asset using an asset path
+    /// Loads an asset using an asset path. If its file is still being
+    /// fetched, the returned handle is pending: the asset is missing from the
+    /// store until [`AssetStore::load_pending`] loads it.
     ///
     /// # Errors
     ///
@@ -124,21 +229,163 @@ impl AssetStore {
     where
         A: 'static + Asset,
     {
-        Ok(self.store(self.load_without_storing(asset_path)?))
+        let (handle, asset_id) = match self.load_without_storing::<A>(asset_path) {
+            Ok(asset) => {
+                let handle = self.store(asset);
+                let asset_id = handle.id();
+                (handle, asset_id)
+            }
+            Err(AssetError::Pending) => {
+                let asset_id = self.allocate_id(None);
+                self.pending_ids.push(asset_id);
+                (self.handle(asset_id), asset_id)
+            }
+            Err(error) => return Err(error),
+        };
+        self.sources[asset_id] = Some((asset_path.to_string(), |asset_store, asset_path| {
+            Ok(Box::new(asset_store.load_without_storing::<A>(asset_path)?))
+        }));
+        Ok(handle)
+    }
+
+    /// Loads the assets whose files were still being fetched when loaded with
+    /// [`AssetStore::load`], returning the ids of the ones loaded. The assets
+    /// failing to load are dropped from the pending ones, their handles
+    /// staying empty.
+    pub fn load_pending(&mut self) -> Vec<usize> {
+        let mut loaded_ids = vec![];
+        for asset_id in std::mem::take(&mut self.pending_ids) {
+            let Some((asset_path, reloader)) = self.sources[asset_id].clone() else {
+                continue;
+            };
+            match reloader(self, &asset_path) {
+                Ok(asset) => {
+                    self.assets[asset_id] = Some(asset);
+                    loaded_ids.push(asset_id);
+                }
+                Err(AssetError::Pending) => self.pending_ids.push(asset_id),
+                Err(error) => warn!("Failed to load asset {asset_path}: {error:?}"),
+            }
+        }
+        loaded_ids
     }
 
+    /// Returns `true` if the asset of the handle is still being fetched, see
+    /// [`AssetStore::load`]
+    #[must_use]
+    pub fn is_pending<T>(&self, handle: &AssetHandle<T>) -> bool {
+        self.pending_ids.contains(&handle.id())
+    }
+
+    /// Stores an asset, the ids of unloaded assets being reused
     pub fn store<A>(&mut self, asset: A) -> AssetHandle<A>
     where
         A: 'static + Asset,
     {
-        let asset_id = self.assets.len();
-        self.assets.push(Box::new(asset));
-        AssetHandle::new(asset_id)
+        let asset_id = self.allocate_id(Some(Box::new(asset)));
+        self.handle(asset_id)
+    }
+
+    /// Allocates an id for an asset, `None` for a pending one
+    fn allocate_id(&mut self, asset: Option<Box<dyn Any>>) -> usize {
+        if let Some(asset_id) = self.free_ids.pop() {
+            self.assets[asset_id] = asset;
+            self.sources[asset_id] = None;
+            asset_id
+        } else {
+            self.assets.push(asset);
+            self.sources.push(None);
+            self.assets.len() - 1
+        }
+    }
+
+    fn handle<A>(&self, asset_id: usize) -> AssetHandle<A> {
+        AssetHandle {
+            inner: Arc::new(HandleInner {
+                id: asset_id,
+                dropped: self.dropped_handle_sender.clone(),
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Unloads the assets whose handles have all been dropped, e.g. after a
+    /// level transition, returning their ids so that the resources created
+    /// from them, such as textures, can be released as well
+    pub fn free_unused(&mut self) -> Vec<usize> {
+        let unloaded_ids = self.dropped_handles.try_iter().collect::<Vec<_>>();
+        for &asset_id in &unloaded_ids {
+            self.assets[asset_id] = None;
+            self.sources[asset_id] = None;
+            self.free_ids.push(asset_id);
+        }
+        self.pending_ids
+            .retain(|pending_id| !unloaded_ids.contains(pending_id));
+        unloaded_ids
+    }
+
+    /// Number of stored assets, the unloaded ones excluded
+    #[must_use]
+    pub fn asset_count(&self) -> usize {
+        self.assets.iter().filter(|asset| asset.is_some()).count()
     }
 
+    /// Returns the path an asset has been loaded from with
+    /// [`AssetStore::load`], `None` if it has been stored directly
     #[must_use]
-    pub fn get<T: 'static>(&self, handle: AssetHandle<T>) -> Option<&T> {
-        self.assets.get(handle.id)?.downcast_ref()
+    pub fn path<T>(&self, handle: &AssetHandle<T>) -> Option<&str> {
+        self.sources[handle.id()]
+            .as_ref()
+            .map(|(path, _)| path.as_str())
+    }
+
+    /// Loads again the assets loaded from a path with [`AssetStore::load`],
+    /// e.g. after the file has been modified. Their handles stay valid.
+    /// Returns the number of reloaded assets.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an asset cannot be loaded, the
+    /// asset then keeping its previous content
+    pub fn reload(&mut self, asset_path: &str) -> Result<usize> {
+        let mut reloaded_count = 0;
+        for asset_id in 0..self.assets.len() {
+            let Some((_, reloader)) = self.sources[asset_id]
+                .as_ref()
+                .filter(|(source_path, _)| source_path == asset_path)
+            else {
+                continue;
+            };
+            self.assets[asset_id] = Some(reloader(self, asset_path)?);
+            reloaded_count += 1;
+        }
+        Ok(reloaded_count)
+    }
+
+    /// Returns the paths of the assets loaded by this store whose file or
+    /// `.meta` file has been modified since the last call, after reloading
+    /// the stored ones, see [`AssetStore::reload`]. The assets depending on
+    /// them, e.g. the textures uploaded from an image, are to be reloaded by
+    /// the caller.
+    ///
+    /// The files are checked at most every
+    /// [`hot_reload::POLL_INTERVAL`], an empty list being returned in
+    /// between.
+    #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+    pub fn reload_modified(&mut self) -> Vec<String> {
+        let modified_paths = self.watcher.borrow_mut().poll();
+        for asset_path in &modified_paths {
+            log::info!("Reloading modified asset {asset_path}");
+            if let Err(error) = self.reload(asset_path) {
+                warn!("Failed to reload asset {asset_path}: {error:?}");
+            }
+        }
+        modified_paths
+    }
+
+    #[must_use]
+    pub fn get<T: 'static>(&self, handle: &AssetHandle<T>) -> Option<&T> {
+        self.assets.get(handle.id())?.as_ref()?.downcast_ref()
     }
 }
 
@@ -153,6 +400,16 @@ pub trait AssetLoader<T> {
     ///
     /// This function will return an error if the the asset cannot be loaded
     fn load(file_content: &[u8]) -> Result<T>;
+
+    /// Loads an asset using the content of its `.meta` file, if any
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the the asset cannot be loaded,
+    /// or if the meta file is invalid
+    fn load_with_meta(file_content: &[u8], _meta: Option<&[u8]>) -> Result<T> {
+        Self::load(file_content)
+    }
 }
 
 #[cfg(test)]
@@ -183,7 +440,40 @@ mod tests {
         let fs = MockFS;
         let mut asset_store = AssetStore::new(fs);
         let asset_handle = asset_store.load::<Text>("test.txt")?;
-        assert_eq!(asset_handle.id, 0);
+        assert_eq!(asset_handle.id(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn asset_store_reload() -> Result<()> {
+        struct Counter(usize);
+        impl Asset for Counter {
+            type Loader = CounterLoader;
+        }
+        struct CounterLoader;
+        impl AssetLoader<Counter> for CounterLoader {
+            fn load(file_content: &[u8]) -> Result<Counter> {
+                Ok(Counter(file_content.len()))
+            }
+        }
+        struct GrowingFS(std::cell::Cell<usize>);
+        impl VirtualFileSystem for GrowingFS {
+            fn read_bytes(&self, path: &str) -> Result<Vec<u8>> {
+                if std::path::Path::new(path).extension() == Some("meta".as_ref()) {
+                    return Err(AssetError::ReadFailed);
+                }
+                self.0.set(self.0.get() + 1);
+                Ok(vec![0; self.0.get()])
+            }
+        }
+
+        let mut asset_store = AssetStore::new(GrowingFS(std::cell::Cell::new(0)));
+        let handle = asset_store.load::<Counter>("counter.bin")?;
+        let stored = asset_store.store(Counter(0));
+        assert_eq!(asset_store.reload("counter.bin")?, 1);
+        assert_eq!(asset_store.get(&handle).unwrap().0, 2);
+        assert_eq!(asset_store.get(&stored).unwrap().0, 0);
+        assert_eq!(asset_store.reload("other.bin")?, 0);
         Ok(())
     }
 
@@ -192,8 +482,64 @@ mod tests {
         let fs = MockFS;
         let mut asset_store = AssetStore::new(fs);
         let asset_handle = asset_store.load::<Text>("test.txt")?;
-        let asset = asset_store.get(asset_handle).unwrap();
+        let asset = asset_store.get(&asset_handle).unwrap();
         assert_eq!(&asset.0, "cheh");
         Ok(())
     }
+
+    #[test]
+    fn assets_are_unloaded_with_their_last_handle() -> Result<()> {
+        let mut asset_store = AssetStore::new(MockFS);
+        let handle = asset_store.load::<Text>("test.txt")?;
+        let other = asset_store.load::<Text>("other.txt")?;
+        let clone = handle.clone();
+
+        drop(handle);
+        assert!(asset_store.free_unused().is_empty());
+        assert!(asset_store.get(&clone).is_some());
+
+        drop(clone);
+        assert_eq!(asset_store.free_unused(), [0]);
+        assert_eq!(asset_store.asset_count(), 1);
+        assert_eq!(asset_store.path(&other), Some("other.txt"));
+
+        // The id of the unloaded asset is reused
+        let reloaded = asset_store.load::<Text>("test.txt")?;
+        assert_eq!(reloaded.id(), 0);
+        assert_eq!(asset_store.asset_count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn pending_assets_are_loaded_once_fetched() -> Result<()> {
+        struct FetchingFS(std::cell::Cell<bool>);
+        impl VirtualFileSystem for FetchingFS {
+            fn read_bytes(&self, _path: &str) -> Result<Vec<u8>> {
+                if self.0.get() {
+                    Ok(vec![])
+                } else {
+                    Err(AssetError::Pending)
+                }
+            }
+        }
+
+        let mut asset_store = AssetStore::new(FetchingFS(std::cell::Cell::new(false)));
+        let handle = asset_store.load::<Text>("test.txt")?;
+        assert!(asset_store.is_pending(&handle));
+        assert!(asset_store.get(&handle).is_none());
+        assert!(asset_store.load_pending().is_empty());
+
+        asset_store.fs = Box::new(FetchingFS(std::cell::Cell::new(true)));
+        assert_eq!(asset_store.load_pending(), [handle.id()]);
+        assert!(!asset_store.is_pending(&handle));
+        assert_eq!(&asset_store.get(&handle).unwrap().0, "cheh");
+
+        // A pending asset whose handles are dropped isn't loaded
+        asset_store.fs = Box::new(FetchingFS(std::cell::Cell::new(false)));
+        drop(asset_store.load::<Text>("other.txt")?);
+        assert_eq!(asset_store.free_unused(), [1]);
+        asset_store.fs = Box::new(FetchingFS(std::cell::Cell::new(true)));
+        assert!(asset_store.load_pending().is_empty());
+        Ok(())
+    }
 }
@@ -0,0 +1,550 @@
+//! Packing of assets into a single file, along with a manifest describing
+//! its entries.
+//!
+//! The manifest records the hash of the source of every entry, so that
+//! packing again only processes the entries whose source has changed, and
+//! the hash of the packed data, checked when the pack is opened with
+//! [`crate::vfs::pack::PackFileSystem`]. Comparing the manifests of two
+//! versions of a pack gives the entries to ship in a patch.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AssetError, Result};
+
+/// Version of the manifest format
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Returns the 64-bit FNV-1a hash of `bytes`, stable across platforms and
+/// builds
+#[must_use]
+pub fn content_hash(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Hash of the source file the entry has been packed from
+    pub source_hash: u64,
+    /// Hash of the packed data
+    pub hash: u64,
+    pub offset: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackManifest {
+    pub version: u32,
+    /// Entries by path relative to the asset directory
+    pub entries: BTreeMap<String, ManifestEntry>,
+}
+
+impl PackManifest {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Parses a manifest written with [`PackManifest::to_bytes`]
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if the manifest is invalid or has been
+    /// written with another version of the format
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let manifest: Self = ron::de::from_bytes(bytes)
+            .map_err(|_| AssetError::PackManifestDeserializationFailed)?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(AssetError::PackManifestDeserializationFailed);
+        }
+        Ok(manifest)
+    }
+
+    /// # Errors
+    ///
+    /// An error will be returned if the manifest cannot be serialized
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map(String::into_bytes)
+            .map_err(|_| AssetError::PackManifestSerializationFailed)
+    }
+
+    /// Returns the packed data of the entry at `path`
+    #[must_use]
+    pub fn entry_data<'a>(&self, path: &str, data: &'a [u8]) -> Option<&'a [u8]> {
+        let entry = self.entries.get(path)?;
+        let start = usize::try_from(entry.offset).ok()?;
+        let end = start.checked_add(usize::try_from(entry.size).ok()?)?;
+        data.get(start..end)
+    }
+
+    /// Checks that the data of every entry is in the pack and matches its
+    /// hash
+    ///
+    /// # Errors
+    ///
+    /// An error naming the first invalid entry will be returned
+    pub fn validate(&self, data: &[u8]) -> Result<()> {
+        for (path, entry) in &self.entries {
+            let is_valid = self
+                .entry_data(path, data)
+                .is_some_and(|entry_data| content_hash(entry_data) == entry.hash);
+            if !is_valid {
+                return Err(AssetError::PackIntegrityCheckFailed { path: path.clone() });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the entries added, changed and removed in `newer`
+    #[must_use]
+    pub fn diff(&self, newer: &PackManifest) -> PackDiff {
+        let mut diff = PackDiff::default();
+        for (path, entry) in &newer.entries {
+            match self.entries.get(path) {
+                None => diff.added.push(path.clone()),
+                Some(previous) if previous.hash != entry.hash => diff.changed.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        diff.removed = self
+            .entries
+            .keys()
+            .filter(|path| !newer.entries.contains_key(*path))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+impl Default for PackManifest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PackDiff {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl PackDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Entries processed or reused by a [`Packer::build`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PackReport {
+    pub rebuilt: Vec<String>,
+    pub reused: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// A pack and its manifest
+#[derive(Debug, Clone)]
+pub struct Pack {
+    pub data: Vec<u8>,
+    pub manifest: PackManifest,
+}
+
+type Processor = Box<dyn Fn(&str, &[u8]) -> Vec<u8>>;
+
+/// Builds packs from source files, optionally processing them, e.g. to
+/// compress textures
+#[derive(Default)]
+pub struct Packer {
+    processor: Option<Processor>,
+}
+
+impl Packer {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the function turning the content of a source file into the data
+    /// to pack, given its path
+    #[must_use]
+    pub fn with_processor<F>(mut self, processor: F) -> Self
+    where
+        F: 'static + Fn(&str, &[u8]) -> Vec<u8>,
+    {
+        self.processor = Some(Box::new(processor));
+        self
+    }
+
+    /// Packs the given `(path, content)` sources. The entries of `previous`
+    /// whose source hasn't changed are reused without being processed again.
+    #[must_use]
+    pub fn build<I>(&self, sources: I, previous: Option<&Pack>) -> (Pack, PackReport)
+    where
+        I: IntoIterator<Item = (String, Vec<u8>)>,
+    {
+        let mut pack = Pack {
+            data: vec![],
+            manifest: PackManifest::new(),
+        };
+        let mut report = PackReport::default();
+        let mut sources = sources.into_iter().collect::<Vec<_>>();
+        sources.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (path, content) in sources {
+            let source_hash = content_hash(&content);
+            let previous_data = previous.and_then(|previous| {
+                let entry = previous.manifest.entries.get(&path)?;
+                if entry.source_hash != source_hash {
+                    return None;
+                }
+                previous.manifest.entry_data(&path, &previous.data)
+            });
+            let processed;
+            let data = if let Some(previous_data) = previous_data {
+                report.reused.push(path.clone());
+                previous_data
+            } else {
+                report.rebuilt.push(path.clone());
+                processed = match &self.processor {
+                    Some(processor) => processor(&path, &content),
+                    None => content,
+                };
+                &processed
+            };
+
+            pack.manifest.entries.insert(
+                path,
+                ManifestEntry {
+                    source_hash,
+                    hash: content_hash(data),
+                    offset: pack.data.len() as u64,
+                    size: data.len() as u64,
+                },
+            );
+            pack.data.extend_from_slice(data);
+        }
+
+        if let Some(previous) = previous {
+            report.removed = previous
+                .manifest
+                .entries
+                .keys()
+                .filter(|path| !pack.manifest.entries.contains_key(*path))
+                .cloned()
+                .collect();
+        }
+        (pack, report)
+    }
+
+    /// Packs the files of `source_directory` into `pack_path`, writing the
+    /// manifest next to it in `<pack_path>.manifest`. The entries of the pack
+    /// already at `pack_path`, if any, are reused when their source hasn't
+    /// changed.
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if the source directory cannot be read, or
+    /// if the pack or its manifest cannot be written
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn pack_directory(
+        &self,
+        source_directory: &std::path::Path,
+        pack_path: &str,
+    ) -> Result<PackReport> {
+        use crate::vfs::{filesystem::FileSystem, VirtualFileSystem, WritableFileSystem};
+
+        let fs = FileSystem;
+        let previous = fs
+            .read_bytes(&manifest_path(pack_path))
+            .and_then(|manifest| PackManifest::from_bytes(&manifest))
+            .and_then(|manifest| {
+                Ok(Pack {
+                    data: fs.read_bytes(pack_path)?,
+                    manifest,
+                })
+            })
+            .ok();
+
+        let mut sources = vec![];
+        collect_sources(source_directory, "", &mut sources)?;
+        let (pack, report) = self.build(sources, previous.as_ref());
+        fs.write_bytes(pack_path, &pack.data)?;
+        fs.write_bytes(&manifest_path(pack_path), &pack.manifest.to_bytes()?)?;
+        Ok(report)
+    }
+
+    /// Builds an archive read with [`crate::vfs::archive::ArchiveFs`] from
+    /// the given `(path, content)` sources, processing them
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if the index of the archive cannot be
+    /// serialized
+    pub fn build_archive<I>(&self, sources: I) -> Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = (String, Vec<u8>)>,
+    {
+        let mut sources = sources.into_iter().collect::<Vec<_>>();
+        sources.sort_by(|(a, _), (b, _)| a.cmp(b));
+        crate::vfs::archive::build_archive(sources.into_iter().map(|(path, content)| {
+            let content = match &self.processor {
+                Some(processor) => processor(&path, &content),
+                None => content,
+            };
+            (path, content)
+        }))
+    }
+
+    /// Builds an archive of the files of `source_directory` at
+    /// `archive_path`, see [`Packer::build_archive`]
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if the source directory cannot be read, or
+    /// if the archive cannot be written
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn archive_directory(
+        &self,
+        source_directory: &std::path::Path,
+        archive_path: &str,
+    ) -> Result<()> {
+        use crate::vfs::{filesystem::FileSystem, WritableFileSystem};
+
+        let mut sources = vec![];
+        collect_sources(source_directory, "", &mut sources)?;
+        FileSystem.write_bytes(archive_path, &self.build_archive(sources)?)
+    }
+
+    /// Builds a patch from `previous` to `pack`: a pack holding the entries
+    /// added or changed since `previous`, along with the full diff
+    #[must_use]
+    pub fn patch(previous: &PackManifest, pack: &Pack) -> (Pack, PackDiff) {
+        let diff = previous.diff(&pack.manifest);
+        let mut patch = Pack {
+            data: vec![],
+            manifest: PackManifest::new(),
+        };
+        for path in diff.added.iter().chain(&diff.changed) {
+            let (Some(entry), Some(data)) = (
+                pack.manifest.entries.get(path),
+                pack.manifest.entry_data(path, &pack.data),
+            ) else {
+                continue;
+            };
+            patch.manifest.entries.insert(
+                path.clone(),
+                ManifestEntry {
+                    offset: patch.data.len() as u64,
+                    ..entry.clone()
+                },
+            );
+            patch.data.extend_from_slice(data);
+        }
+        (patch, diff)
+    }
+}
+
+/// Path of the manifest of the pack at `pack_path`
+#[must_use]
+pub fn manifest_path(pack_path: &str) -> String {
+    format!("{pack_path}.manifest")
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn collect_sources(
+    directory: &std::path::Path,
+    prefix: &str,
+    sources: &mut Vec<(String, Vec<u8>)>,
+) -> Result<()> {
+    let mut files = vec![];
+    collect_files(directory, prefix, &mut files)?;
+    for (relative_path, path) in files {
+        let content = std::fs::read(&path).map_err(|_| AssetError::ReadFailed)?;
+        sources.push((relative_path, content));
+    }
+    Ok(())
+}
+
+/// Collects the `(relative path, path)` of the files of `directory` and of
+/// its subdirectories
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn collect_files(
+    directory: &std::path::Path,
+    prefix: &str,
+    files: &mut Vec<(String, std::path::PathBuf)>,
+) -> Result<()> {
+    let entries = std::fs::read_dir(directory).map_err(|_| AssetError::ReadFailed)?;
+    for entry in entries {
+        let path = entry.map_err(|_| AssetError::ReadFailed)?.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(AssetError::AssetPathIsInvalidUTF8)?;
+        let relative_path = format!("{prefix}{name}");
+        if path.is_dir() {
+            collect_files(&path, &format!("{relative_path}/"), files)?;
+        } else {
+            files.push((relative_path, path));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    fn sources(entries: &[(&str, &str)]) -> Vec<(String, Vec<u8>)> {
+        entries
+            .iter()
+            .map(|(path, content)| ((*path).to_string(), content.as_bytes().to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn only_changed_entries_are_rebuilt() {
+        let processed = Rc::new(RefCell::new(vec![]));
+        let packer = Packer::new().with_processor({
+            let processed = Rc::clone(&processed);
+            move |path, content| {
+                processed.borrow_mut().push(path.to_string());
+                content.to_ascii_uppercase()
+            }
+        });
+
+        let (pack, report) = packer.build(sources(&[("a.txt", "a"), ("b.txt", "b")]), None);
+        assert_eq!(report.rebuilt, ["a.txt", "b.txt"]);
+        assert_eq!(
+            pack.manifest.entry_data("b.txt", &pack.data),
+            Some(&b"B"[..])
+        );
+
+        processed.borrow_mut().clear();
+        let (pack, report) = packer.build(
+            sources(&[("a.txt", "a"), ("b.txt", "bb"), ("c.txt", "c")]),
+            Some(&pack),
+        );
+        assert_eq!(*processed.borrow(), ["b.txt", "c.txt"]);
+        assert_eq!(report.reused, ["a.txt"]);
+        assert_eq!(report.rebuilt, ["b.txt", "c.txt"]);
+        assert_eq!(
+            pack.manifest.entry_data("a.txt", &pack.data),
+            Some(&b"A"[..])
+        );
+        assert_eq!(
+            pack.manifest.entry_data("b.txt", &pack.data),
+            Some(&b"BB"[..])
+        );
+        assert!(pack.manifest.validate(&pack.data).is_ok());
+
+        let (_, report) = packer.build(sources(&[("a.txt", "a")]), Some(&pack));
+        assert_eq!(report.removed, ["b.txt", "c.txt"]);
+    }
+
+    #[test]
+    fn directories_are_packed_incrementally() -> Result<()> {
+        let directory = std::env::temp_dir().join(format!("tubereng_pack_{}", std::process::id()));
+        let source_directory = directory.join("assets");
+        std::fs::create_dir_all(source_directory.join("sprites")).unwrap();
+        std::fs::write(source_directory.join("a.txt"), "a").unwrap();
+        std::fs::write(source_directory.join("sprites/b.txt"), "b").unwrap();
+        let pack_path = directory.join("assets.pack");
+        let pack_path = pack_path.to_str().unwrap();
+
+        let packer = Packer::new();
+        let report = packer.pack_directory(&source_directory, pack_path)?;
+        assert_eq!(report.rebuilt, ["a.txt", "sprites/b.txt"]);
+        std::fs::write(source_directory.join("a.txt"), "aa").unwrap();
+        let report = packer.pack_directory(&source_directory, pack_path)?;
+        assert_eq!(report.rebuilt, ["a.txt"]);
+        assert_eq!(report.reused, ["sprites/b.txt"]);
+
+        let fs = crate::vfs::pack::PackFileSystem::open(pack_path)?;
+        assert_eq!(
+            crate::vfs::VirtualFileSystem::read_bytes(&fs, "a.txt")?,
+            b"aa"
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn directories_are_archived() -> Result<()> {
+        let directory =
+            std::env::temp_dir().join(format!("tubereng_archive_{}", std::process::id()));
+        let source_directory = directory.join("assets");
+        std::fs::create_dir_all(source_directory.join("sprites")).unwrap();
+        std::fs::write(source_directory.join("sprites/a.txt"), "a").unwrap();
+        let archive_path = directory.join("assets.tuba");
+        let archive_path = archive_path.to_str().unwrap();
+
+        Packer::new()
+            .with_processor(|_, content| content.to_ascii_uppercase())
+            .archive_directory(&source_directory, archive_path)?;
+
+        let fs = crate::vfs::archive::ArchiveFs::open(archive_path)?;
+        assert_eq!(
+            crate::vfs::VirtualFileSystem::read_bytes(&fs, "sprites/a.txt")?,
+            b"A"
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn corrupted_packs_fail_validation() {
+        let (mut pack, _) = Packer::new().build(sources(&[("a.txt", "a"), ("b.txt", "b")]), None);
+        pack.data[1] = b'x';
+        assert!(matches!(
+            pack.manifest.validate(&pack.data),
+            Err(AssetError::PackIntegrityCheckFailed { path }) if path == "b.txt"
+        ));
+        pack.data.truncate(1);
+        assert!(pack.manifest.validate(&pack.data).is_err());
+    }
+
+    #[test]
+    fn manifest_round_trip() -> Result<()> {
+        let (pack, _) = Packer::new().build(sources(&[("dir/a.txt", "a")]), None);
+        let manifest = PackManifest::from_bytes(&pack.manifest.to_bytes()?)?;
+        assert_eq!(manifest, pack.manifest);
+        Ok(())
+    }
+
+    #[test]
+    fn patch_holds_added_and_changed_entries() {
+        let packer = Packer::new();
+        let (previous, _) = packer.build(
+            sources(&[("a.txt", "a"), ("b.txt", "b"), ("c.txt", "c")]),
+            None,
+        );
+        let (pack, _) = packer.build(
+            sources(&[("a.txt", "a"), ("b.txt", "bb"), ("d.txt", "d")]),
+            Some(&previous),
+        );
+
+        let (patch, diff) = Packer::patch(&previous.manifest, &pack);
+        assert_eq!(diff.added, ["d.txt"]);
+        assert_eq!(diff.changed, ["b.txt"]);
+        assert_eq!(diff.removed, ["c.txt"]);
+        assert_eq!(
+            patch.manifest.entries.keys().collect::<Vec<_>>(),
+            ["b.txt", "d.txt"]
+        );
+        assert!(patch.manifest.validate(&patch.data).is_ok());
+        assert!(pack.manifest.diff(&pack.manifest).is_empty());
+    }
+}
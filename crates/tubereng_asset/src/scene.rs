@@ -0,0 +1,265 @@
+use serde::{de::IgnoredAny, Deserialize};
+
+use crate::{Asset, AssetError, AssetLoader, Result};
+
+/// Entities authored as data in a RON file, spawned in the ECS with
+/// the `SceneCommands::spawn_scene` command of the engine:
+/// ```ron
+/// Scene(
+///     entities: [
+///         (
+///             name: "player",
+///             components: {
+///                 "Transform": (translation: (x: 0.0, y: 515.0, z: 0.0)),
+///                 "Player": (speed: 200.0),
+///             },
+///         ),
+///         (
+///             parent: "player",
+///             components: {
+///                 "Sprite": (texture: "player.png"),
+///             },
+///         ),
+///     ],
+/// )
+/// ```
+///
+/// Components are identified by the name they are registered with in the
+/// ECS. Their values are kept as RON and only deserialized when the scene is
+/// spawned, as their types aren't known to the asset loader.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Scene {
+    pub entities: Vec<SceneEntity>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SceneEntity {
+    /// Name used by the other entities of the scene to refer to this entity
+    pub name: Option<String>,
+    /// Name of the parent entity in the scene
+    pub parent: Option<String>,
+    /// Registered name and RON value of each component
+    pub components: Vec<(String, String)>,
+}
+
+impl Scene {
+    /// Parses a scene from its RON representation
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the scene is malformed
+    pub fn from_ron(source: &str) -> Result<Self> {
+        let mut parser = Parser { rest: source };
+        let scene = parser.scene()?;
+        parser.skip_whitespace();
+        if !parser.rest.is_empty() {
+            return Err(AssetError::SceneDeserializationFailed);
+        }
+        Ok(scene)
+    }
+}
+
+impl Asset for Scene {
+    type Loader = SceneLoader;
+}
+
+pub struct SceneLoader;
+impl AssetLoader<Scene> for SceneLoader {
+    fn load(file_content: &[u8]) -> Result<Scene> {
+        let source = std::str::from_utf8(file_content)
+            .map_err(|_| AssetError::SceneDeserializationFailed)?;
+        Scene::from_ron(source)
+    }
+}
+
+/// Parser of the structure of scene files. The values are parsed by a RON
+/// deserializer started at their position, so that the source of component
+/// values can be kept as is.
+///
+/// Deriving `Deserialize` for the scene would need a raw value type, which
+/// ron 0.8 doesn't have. Its `Value` drops the names of enum variants and
+/// structs, so it can't hold the components either.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn scene(&mut self) -> Result<Scene> {
+        self.consume("Scene");
+        let mut scene = Scene::default();
+        self.parse_delimited('(', ')', |parser| {
+            parser.expect_field("entities")?;
+            parser.parse_delimited('[', ']', |parser| {
+                scene.entities.push(parser.entity()?);
+                Ok(())
+            })
+        })?;
+        Ok(scene)
+    }
+
+    fn entity(&mut self) -> Result<SceneEntity> {
+        let mut entity = SceneEntity::default();
+        self.parse_delimited('(', ')', |parser| {
+            if parser.consume_field("name") {
+                entity.name = Some(parser.value()?);
+            } else if parser.consume_field("parent") {
+                entity.parent = Some(parser.value()?);
+            } else {
+                parser.expect_field("components")?;
+                parser.parse_delimited('{', '}', |parser| {
+                    let name = parser.value()?;
+                    parser.expect(":")?;
+                    let value = parser.raw_value()?;
+                    entity.components.push((name, value.to_string()));
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })?;
+        Ok(entity)
+    }
+
+    /// Parses the comma separated items between `open` and `close`, a
+    /// trailing comma being allowed
+    fn parse_delimited(
+        &mut self,
+        open: char,
+        close: char,
+        mut parse_item: impl FnMut(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        self.expect(open.encode_utf8(&mut [0; 4]))?;
+        let close = close.encode_utf8(&mut [0; 4]).to_string();
+        loop {
+            if self.consume(&close) {
+                return Ok(());
+            }
+            parse_item(self)?;
+            if !self.consume(",") {
+                return self.expect(&close);
+            }
+        }
+    }
+
+    fn value<T: for<'de> Deserialize<'de>>(&mut self) -> Result<T> {
+        let mut deserializer = ron::Deserializer::from_str(self.rest)
+            .map_err(|_| AssetError::SceneDeserializationFailed)?;
+        let value = T::deserialize(&mut deserializer)
+            .map_err(|_| AssetError::SceneDeserializationFailed)?;
+        let consumed = self.rest.len() - deserializer.remainder().len();
+        self.rest = &self.rest[consumed..];
+        Ok(value)
+    }
+
+    fn raw_value(&mut self) -> Result<&'a str> {
+        self.skip_whitespace();
+        let source = self.rest;
+        self.value::<IgnoredAny>()?;
+        Ok(source[..source.len() - self.rest.len()].trim_end())
+    }
+
+    fn consume_field(&mut self, name: &str) -> bool {
+        let rest = self.rest;
+        if self.consume(name) && self.consume(":") {
+            return true;
+        }
+        self.rest = rest;
+        false
+    }
+
+    fn expect_field(&mut self, name: &str) -> Result<()> {
+        if self.consume_field(name) {
+            Ok(())
+        } else {
+            Err(AssetError::SceneDeserializationFailed)
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.consume(token) {
+            Ok(())
+        } else {
+            Err(AssetError::SceneDeserializationFailed)
+        }
+    }
+
+    fn consume(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        let Some(rest) = self.rest.strip_prefix(token) else {
+            return false;
+        };
+        self.rest = rest;
+        true
+    }
+
+    /// Skips whitespace and comments
+    fn skip_whitespace(&mut self) {
+        loop {
+            self.rest = self.rest.trim_start();
+            if let Some(rest) = self.rest.strip_prefix("//") {
+                self.rest = rest.find('\n').map_or("", |end| &rest[end..]);
+            } else if let Some(rest) = self.rest.strip_prefix("/*") {
+                self.rest = rest.find("*/").map_or("", |end| &rest[end + 2..]);
+            } else {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scene_from_ron() -> Result<()> {
+        let scene = Scene::from_ron(
+            r#"
+            // The player and its sprite
+            Scene(
+                entities: [
+                    (
+                        name: "player",
+                        components: {
+                            "Transform": (translation: (x: 1.0, y: 2.0, z: 0.0)),
+                            "State": Jumping(height: 3.0), /* inline comment */
+                            "Tag": Player,
+                        },
+                    ),
+                    (parent: "player", components: {"Sprite": (texture: "player.png")})
+                ],
+            )
+            "#,
+        )?;
+
+        assert_eq!(
+            scene.entities,
+            [
+                SceneEntity {
+                    name: Some("player".into()),
+                    parent: None,
+                    components: vec![
+                        (
+                            "Transform".into(),
+                            "(translation: (x: 1.0, y: 2.0, z: 0.0))".into()
+                        ),
+                        ("State".into(), "Jumping(height: 3.0)".into()),
+                        ("Tag".into(), "Player".into()),
+                    ],
+                },
+                SceneEntity {
+                    name: None,
+                    parent: Some("player".into()),
+                    components: vec![("Sprite".into(), r#"(texture: "player.png")"#.into())],
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn scene_from_ron_malformed() {
+        assert!(Scene::from_ron("Scene(entities: [(components: {\"A\": ()})]").is_err());
+        assert!(Scene::from_ron("Scene(entities: [(unknown: 1)])").is_err());
+        assert!(Scene::from_ron("Scene(entities: []) trailing").is_err());
+    }
+}
@@ -0,0 +1,273 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{vfs::WritableFileSystem, AssetError, Result};
+
+/// Settings that can be persisted with a [`SettingsStore`].
+///
+/// The schema of the settings is versioned: when settings saved by an older
+/// version of the game are loaded, [`Settings::migrate`] is called once per
+/// version step until the stored value reaches [`Settings::VERSION`].
+pub trait Settings: Serialize + DeserializeOwned {
+    /// Current version of the settings schema
+    const VERSION: u32;
+
+    /// Migrates a settings value stored with the `from_version` schema to the
+    /// `from_version + 1` schema
+    ///
+    /// # Errors
+    ///
+    /// The default implementation doesn't support any migration and always
+    /// returns an error
+    fn migrate(from_version: u32, _value: ron::Value) -> Result<ron::Value> {
+        Err(AssetError::SettingsMigrationFailed { from_version })
+    }
+}
+
+#[derive(Serialize)]
+struct StoredSettings<'a, S> {
+    version: u32,
+    settings: &'a S,
+}
+
+#[derive(Deserialize)]
+struct StoredSettingsValue {
+    version: u32,
+    settings: ron::Value,
+}
+
+/// Persists settings using the writable file system of the platform: files
+/// in the user configuration directory on native, the browser's local storage
+/// on wasm.
+pub struct SettingsStore {
+    fs: Box<dyn WritableFileSystem>,
+    root: String,
+}
+
+impl SettingsStore {
+    /// Creates a settings store using the writable file system of the current
+    /// platform, with the settings of the application stored under
+    /// `application_name`
+    #[must_use]
+    pub fn new(application_name: &str) -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let mut root = user_config_directory();
+            root.push(application_name);
+            Self::with_file_system(crate::vfs::filesystem::FileSystem, &root.to_string_lossy())
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            Self::with_file_system(crate::vfs::local_storage::LocalStorage, application_name)
+        }
+    }
+
+    /// Creates a settings store using the given file system, with settings
+    /// stored under `root`
+    #[must_use]
+    pub fn with_file_system<FS>(fs: FS, root: &str) -> Self
+    where
+        FS: 'static + WritableFileSystem,
+    {
+        Self {
+            fs: Box::new(fs),
+            root: root.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Saves settings under the given name
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the settings cannot be serialized or written
+    pub fn save<S: Settings>(&self, name: &str, settings: &S) -> Result<()> {
+        let content = ron::ser::to_string_pretty(
+            &StoredSettings {
+                version: S::VERSION,
+                settings,
+            },
+            ron::ser::PrettyConfig::default(),
+        )
+        .map_err(|_| AssetError::SettingsSerializationFailed)?;
+        self.fs.write_bytes(&self.path_of(name), content.as_bytes())
+    }
+
+    /// Loads the settings saved under the given name, migrating them if they
+    /// were saved with an older schema version. Returns `None` if no settings
+    /// were saved under this name.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the settings cannot be read, deserialized or
+    /// migrated
+    pub fn load<S: Settings>(&self, name: &str) -> Result<Option<S>> {
+        let content = match self.fs.read_bytes(&self.path_of(name)) {
+            Ok(content) => content,
+            Err(AssetError::NotFound) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let content =
+            std::str::from_utf8(&content).map_err(|_| AssetError::SettingsDeserializationFailed)?;
+        let stored: StoredSettingsValue =
+            ron::from_str(content).map_err(|_| AssetError::SettingsDeserializationFailed)?;
+
+        if stored.version > S::VERSION {
+            return Err(AssetError::SettingsDeserializationFailed);
+        }
+
+        let mut value = stored.settings;
+        for version in stored.version..S::VERSION {
+            value = S::migrate(version, value)?;
+        }
+
+        value
+            .into_rust()
+            .map(Some)
+            .map_err(|_| AssetError::SettingsDeserializationFailed)
+    }
+
+    fn path_of(&self, name: &str) -> String {
+        format!("{}/{name}.ron", self.root)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn user_config_directory() -> std::path::PathBuf {
+    use std::{env, path::PathBuf};
+
+    let home = || env::var_os("HOME").map(PathBuf::from);
+    let directory = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home().map(|home| home.join(".config")))
+    };
+
+    directory.unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+    use crate::vfs::VirtualFileSystem;
+
+    use super::*;
+
+    #[derive(Default, Clone)]
+    struct MemoryFS {
+        files: Rc<RefCell<HashMap<String, Vec<u8>>>>,
+    }
+
+    impl VirtualFileSystem for MemoryFS {
+        fn read_bytes(&self, path: &str) -> Result<Vec<u8>> {
+            self.files
+                .borrow()
+                .get(path)
+                .cloned()
+                .ok_or(AssetError::NotFound)
+        }
+    }
+
+    impl WritableFileSystem for MemoryFS {
+        fn write_bytes(&self, path: &str, bytes: &[u8]) -> Result<()> {
+            self.files
+                .borrow_mut()
+                .insert(path.to_string(), bytes.to_vec());
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct AudioSettingsV1 {
+        volume: u32,
+    }
+
+    impl Settings for AudioSettingsV1 {
+        const VERSION: u32 = 1;
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct AudioSettingsV2 {
+        master_volume: f32,
+        muted: bool,
+    }
+
+    impl Settings for AudioSettingsV2 {
+        const VERSION: u32 = 2;
+
+        fn migrate(from_version: u32, value: ron::Value) -> Result<ron::Value> {
+            let ron::Value::Map(map) = value else {
+                return Err(AssetError::SettingsMigrationFailed { from_version });
+            };
+            let volume = map
+                .iter()
+                .find(|(key, _)| **key == ron::Value::String("volume".into()))
+                .and_then(|(_, volume)| volume.clone().into_rust::<u32>().ok())
+                .ok_or(AssetError::SettingsMigrationFailed { from_version })?;
+
+            let mut migrated = ron::Map::new();
+            migrated.insert(
+                ron::Value::String("master_volume".into()),
+                ron::Value::Number(ron::Number::from(f64::from(volume) / 100.0)),
+            );
+            migrated.insert(ron::Value::String("muted".into()), ron::Value::Bool(false));
+            Ok(ron::Value::Map(migrated))
+        }
+    }
+
+    #[test]
+    fn settings_store_round_trip() -> Result<()> {
+        let store = SettingsStore::with_file_system(MemoryFS::default(), "game");
+        store.save("audio", &AudioSettingsV1 { volume: 80 })?;
+        let loaded = store.load::<AudioSettingsV1>("audio")?;
+        assert_eq!(loaded, Some(AudioSettingsV1 { volume: 80 }));
+        Ok(())
+    }
+
+    #[test]
+    fn settings_store_missing_settings() -> Result<()> {
+        let store = SettingsStore::with_file_system(MemoryFS::default(), "game");
+        assert_eq!(store.load::<AudioSettingsV1>("audio")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn settings_store_read_errors_are_returned() {
+        struct UnreadableFS;
+        impl VirtualFileSystem for UnreadableFS {
+            fn read_bytes(&self, _path: &str) -> Result<Vec<u8>> {
+                Err(AssetError::ReadFailed)
+            }
+        }
+        impl WritableFileSystem for UnreadableFS {
+            fn write_bytes(&self, _path: &str, _bytes: &[u8]) -> Result<()> {
+                Ok(())
+            }
+        }
+
+        let store = SettingsStore::with_file_system(UnreadableFS, "game");
+        assert!(matches!(
+            store.load::<AudioSettingsV1>("audio"),
+            Err(AssetError::ReadFailed)
+        ));
+    }
+
+    #[test]
+    fn settings_store_migrates_older_settings() -> Result<()> {
+        let store = SettingsStore::with_file_system(MemoryFS::default(), "game");
+        store.save("audio", &AudioSettingsV1 { volume: 50 })?;
+
+        let loaded = store.load::<AudioSettingsV2>("audio")?;
+        assert_eq!(
+            loaded,
+            Some(AudioSettingsV2 {
+                master_volume: 0.5,
+                muted: false,
+            })
+        );
+        Ok(())
+    }
+}
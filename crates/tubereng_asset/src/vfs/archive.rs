@@ -0,0 +1,222 @@
+//! Archives holding the assets in a single compressed file, built with
+//! [`crate::pack::Packer::build_archive`] or the `tubereng_pack` utility.
+//!
+//! An archive starts with [`ARCHIVE_MAGIC`], the version of the format and
+//! the size of its index, followed by the index and the data of the entries.
+//! Each entry is compressed with deflate, unless it doesn't get smaller, e.g.
+//! for an already compressed PNG file.
+
+use std::collections::BTreeMap;
+
+use log::trace;
+use serde::{Deserialize, Serialize};
+
+use super::VirtualFileSystem;
+use crate::{pack::content_hash, AssetError, Result};
+
+/// Bytes an archive starts with
+pub const ARCHIVE_MAGIC: &[u8; 4] = b"TUBA";
+/// Version of the archive format
+pub const ARCHIVE_VERSION: u32 = 1;
+
+const HEADER_SIZE: usize = 16;
+const COMPRESSION_LEVEL: u8 = 6;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    /// Offset of the entry from the end of the index
+    pub offset: u64,
+    /// Size of the entry in the archive
+    pub size: u64,
+    pub uncompressed_size: u64,
+    pub compressed: bool,
+    /// Hash of the uncompressed data
+    pub hash: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveIndex {
+    /// Entries by path relative to the asset directory
+    pub entries: BTreeMap<String, ArchiveEntry>,
+}
+
+/// Builds an archive from `(path, content)` sources
+///
+/// # Errors
+///
+/// An error will be returned if the index cannot be serialized
+pub fn build_archive<I>(sources: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = (String, Vec<u8>)>,
+{
+    let mut index = ArchiveIndex::default();
+    let mut data = vec![];
+    for (path, content) in sources {
+        let compressed = miniz_oxide::deflate::compress_to_vec(&content, COMPRESSION_LEVEL);
+        let is_compressed = compressed.len() < content.len();
+        let packed = if is_compressed { &compressed } else { &content };
+        index.entries.insert(
+            path,
+            ArchiveEntry {
+                offset: data.len() as u64,
+                size: packed.len() as u64,
+                uncompressed_size: content.len() as u64,
+                compressed: is_compressed,
+                hash: content_hash(&content),
+            },
+        );
+        data.extend_from_slice(packed);
+    }
+
+    let index = ron::ser::to_string(&index)
+        .map_err(|_| AssetError::ArchiveIndexSerializationFailed)?
+        .into_bytes();
+    let mut archive = Vec::with_capacity(HEADER_SIZE + index.len() + data.len());
+    archive.extend_from_slice(ARCHIVE_MAGIC);
+    archive.extend_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+    archive.extend_from_slice(&(index.len() as u64).to_le_bytes());
+    archive.extend_from_slice(&index);
+    archive.extend_from_slice(&data);
+    Ok(archive)
+}
+
+/// Reads the assets from an archive, e.g. so that a shipped game doesn't
+/// expose its assets as loose files. The entries are decompressed and checked
+/// against their hash when they are read.
+pub struct ArchiveFs {
+    archive: Vec<u8>,
+    index: ArchiveIndex,
+    data_offset: usize,
+}
+
+impl ArchiveFs {
+    /// # Errors
+    ///
+    /// An error will be returned if the archive header or its index is
+    /// invalid
+    pub fn new(archive: Vec<u8>) -> Result<Self> {
+        let header = archive
+            .first_chunk::<HEADER_SIZE>()
+            .ok_or(AssetError::InvalidArchive)?;
+        let (magic, header) = header.split_at(ARCHIVE_MAGIC.len());
+        let (version, index_size) = header.split_at(4);
+        let version = version.try_into().ok().map(u32::from_le_bytes);
+        if magic != ARCHIVE_MAGIC || version != Some(ARCHIVE_VERSION) {
+            return Err(AssetError::InvalidArchive);
+        }
+        let index_size = index_size
+            .try_into()
+            .ok()
+            .and_then(|index_size| usize::try_from(u64::from_le_bytes(index_size)).ok())
+            .ok_or(AssetError::InvalidArchive)?;
+        let data_offset = HEADER_SIZE
+            .checked_add(index_size)
+            .ok_or(AssetError::InvalidArchive)?;
+        let index = archive
+            .get(HEADER_SIZE..data_offset)
+            .ok_or(AssetError::InvalidArchive)?;
+        let index = ron::de::from_bytes(index).map_err(|_| AssetError::InvalidArchive)?;
+        Ok(Self {
+            archive,
+            index,
+            data_offset,
+        })
+    }
+
+    /// Opens the archive at `archive_path`
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if the archive cannot be read or is invalid
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(archive_path: &str) -> Result<Self> {
+        Self::new(std::fs::read(archive_path).map_err(|_| AssetError::ReadFailed)?)
+    }
+
+    #[must_use]
+    pub fn index(&self) -> &ArchiveIndex {
+        &self.index
+    }
+
+    fn entry_data(&self, entry: &ArchiveEntry) -> Option<&[u8]> {
+        let start = self
+            .data_offset
+            .checked_add(usize::try_from(entry.offset).ok()?)?;
+        let end = start.checked_add(usize::try_from(entry.size).ok()?)?;
+        self.archive.get(start..end)
+    }
+}
+
+impl VirtualFileSystem for ArchiveFs {
+    /// Reads the entry at the given path, see [`PackFileSystem`] for the
+    /// accepted paths
+    ///
+    /// [`PackFileSystem`]: super::pack::PackFileSystem
+    fn read_bytes(&self, path: &str) -> Result<Vec<u8>> {
+        trace!("Reading bytes from {path} in archive");
+        let entry_path =
+            super::entry_path(&self.index.entries, path).ok_or(AssetError::NotFound)?;
+        let entry = &self.index.entries[entry_path];
+        let data = self.entry_data(entry).ok_or(AssetError::InvalidArchive)?;
+        let data = if entry.compressed {
+            let limit =
+                usize::try_from(entry.uncompressed_size).map_err(|_| AssetError::InvalidArchive)?;
+            miniz_oxide::inflate::decompress_to_vec_with_limit(data, limit)
+                .map_err(|_| AssetError::InvalidArchive)?
+        } else {
+            data.to_vec()
+        };
+        if content_hash(&data) != entry.hash {
+            return Err(AssetError::PackIntegrityCheckFailed {
+                path: entry_path.to_string(),
+            });
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive() -> Vec<u8> {
+        build_archive([
+            ("sprites/player.png".to_string(), b"player".to_vec()),
+            ("level.ron".to_string(), "tile ".repeat(100).into_bytes()),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn entries_are_compressed_and_read_back() -> Result<()> {
+        let fs = ArchiveFs::new(archive())?;
+        let level = &fs.index().entries["level.ron"];
+        assert!(level.compressed);
+        assert!(level.size < level.uncompressed_size);
+        assert!(!fs.index().entries["sprites/player.png"].compressed);
+
+        assert_eq!(fs.read_bytes("level.ron")?, "tile ".repeat(100).as_bytes());
+        assert_eq!(fs.read_bytes("/game/assets/sprites/player.png")?, b"player");
+        assert!(fs.read_bytes("missing.png").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_archives_are_rejected() {
+        assert!(matches!(
+            ArchiveFs::new(b"TUBA".to_vec()),
+            Err(AssetError::InvalidArchive)
+        ));
+        let mut corrupted = archive();
+        corrupted[0] = b'X';
+        assert!(ArchiveFs::new(corrupted).is_err());
+
+        let mut corrupted = archive();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xFF;
+        let fs = ArchiveFs::new(corrupted).unwrap();
+        // The last entry is the compressed level
+        assert!(fs.read_bytes("level.ron").is_err());
+        assert_eq!(fs.read_bytes("sprites/player.png").unwrap(), b"player");
+    }
+}
@@ -0,0 +1,131 @@
+//! Assets bundled into the binary at compile time, e.g. for small games or
+//! wasm builds, read with the same paths as from the asset directory.
+//!
+//! The list of the assets is generated by the build script of the
+//! application with [`generate_embedded_assets`]:
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     tubereng_asset::vfs::embedded::generate_embedded_assets(
+//!         "assets".as_ref(),
+//!         &std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("assets.rs"),
+//!     )
+//!     .unwrap();
+//! }
+//!
+//! // main.rs
+//! static ASSETS: EmbeddedAssets = include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+//! let fs = EmbeddedFs::new(ASSETS);
+//! ```
+
+use std::collections::BTreeMap;
+
+use log::trace;
+
+use super::VirtualFileSystem;
+use crate::{AssetError, Result};
+
+/// `(path, content)` of the embedded assets, the paths being relative to the
+/// asset directory
+pub type EmbeddedAssets = &'static [(&'static str, &'static [u8])];
+
+/// Reads the assets embedded in the binary
+pub struct EmbeddedFs {
+    files: BTreeMap<String, &'static [u8]>,
+}
+
+impl EmbeddedFs {
+    #[must_use]
+    pub fn new(assets: EmbeddedAssets) -> Self {
+        Self {
+            files: assets
+                .iter()
+                .map(|&(path, content)| (path.to_string(), content))
+                .collect(),
+        }
+    }
+}
+
+impl VirtualFileSystem for EmbeddedFs {
+    /// Reads the asset at the given path, see [`PackFileSystem`] for the
+    /// accepted paths
+    ///
+    /// [`PackFileSystem`]: super::pack::PackFileSystem
+    fn read_bytes(&self, path: &str) -> Result<Vec<u8>> {
+        trace!("Reading embedded bytes from {path}");
+        super::entry_path(&self.files, path)
+            .map(|entry_path| self.files[entry_path].to_vec())
+            .ok_or(AssetError::NotFound)
+    }
+}
+
+/// Writes to `output_path` the [`EmbeddedAssets`] expression embedding the
+/// files of `source_directory` with `include_bytes!`, to be included by the
+/// application. Meant to be called from a build script, it prints the
+/// directive rebuilding the application when the assets change.
+///
+/// # Errors
+///
+/// An error will be returned if the source directory cannot be read, or if
+/// the output file cannot be written
+#[cfg(not(target_arch = "wasm32"))]
+pub fn generate_embedded_assets(
+    source_directory: &std::path::Path,
+    output_path: &std::path::Path,
+) -> Result<()> {
+    use std::fmt::Write;
+
+    let source_directory = source_directory
+        .canonicalize()
+        .map_err(|_| AssetError::PathCanonicalizationFailed)?;
+    let mut files = vec![];
+    crate::pack::collect_files(&source_directory, "", &mut files)?;
+    files.sort();
+
+    let mut source = String::from("&[\n");
+    for (relative_path, path) in files {
+        let path = path.to_str().ok_or(AssetError::AssetPathIsInvalidUTF8)?;
+        writeln!(
+            source,
+            "    ({relative_path:?}, include_bytes!({path:?}).as_slice()),"
+        )
+        .map_err(|_| AssetError::WriteFailed)?;
+    }
+    source.push_str("]\n");
+    std::fs::write(output_path, source).map_err(|_| AssetError::WriteFailed)?;
+    println!("cargo:rerun-if-changed={}", source_directory.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static ASSETS: EmbeddedAssets = &[("sprites/player.png", b"player"), ("level.ron", b"()")];
+
+    #[test]
+    fn embedded_assets_are_read_by_path() -> Result<()> {
+        let fs = EmbeddedFs::new(ASSETS);
+        assert_eq!(fs.read_bytes("sprites/player.png")?, b"player");
+        assert_eq!(fs.read_bytes("/game/assets/level.ron")?, b"()");
+        assert!(fs.read_bytes("missing.png").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn generated_assets_list_embeds_the_files() {
+        let directory =
+            std::env::temp_dir().join(format!("tubereng_embedded_{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("assets/sprites")).unwrap();
+        std::fs::write(directory.join("assets/sprites/a.png"), "a").unwrap();
+        let output_path = directory.join("assets.rs");
+
+        generate_embedded_assets(&directory.join("assets"), &output_path).unwrap();
+
+        let source = std::fs::read_to_string(&output_path).unwrap();
+        assert!(source.starts_with("&[\n    (\"sprites/a.png\", include_bytes!("));
+        assert!(source.contains("a.png\").as_slice()),"));
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
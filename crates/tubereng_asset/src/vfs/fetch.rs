@@ -0,0 +1,115 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use log::trace;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+use super::VirtualFileSystem;
+use crate::{AssetError, Result};
+
+/// Fetches the assets over HTTP, relative to the URL of the page, so that a
+/// browser build loads them with the same paths as a native build.
+///
+/// The fetched assets are cached. Assets preloaded with [`WebFs::preload`]
+/// are fetched before the engine starts, the other ones are fetched
+/// asynchronously when they are first read, [`AssetError::Pending`] being
+/// returned until they are received, see [`crate::AssetStore::load`].
+#[derive(Clone)]
+pub struct WebFs {
+    asset_directory: String,
+    cache: Rc<RefCell<HashMap<String, CachedFile>>>,
+}
+
+enum CachedFile {
+    Fetching,
+    Fetched(Vec<u8>),
+    /// The file doesn't exist or couldn't be fetched, e.g. a missing `.meta`
+    /// file
+    Failed,
+}
+
+impl WebFs {
+    /// Fetches the assets from `asset_directory`, relative to the URL of the
+    /// page, e.g. `"assets"`
+    #[must_use]
+    pub fn new(asset_directory: &str) -> Self {
+        Self {
+            asset_directory: asset_directory.trim_end_matches('/').to_string(),
+            cache: Rc::default(),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.asset_directory, path.trim_start_matches('/'))
+    }
+
+    /// Fetches assets and their `.meta` files, if any, into the cache
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if an asset cannot be fetched
+    pub async fn preload(&self, paths: &[&str]) -> Result<()> {
+        for path in paths {
+            let bytes = fetch(&self.url(path)).await?;
+            self.cache
+                .borrow_mut()
+                .insert((*path).to_string(), CachedFile::Fetched(bytes));
+            let meta_path = format!("{path}.meta");
+            let meta = fetch(&self.url(&meta_path))
+                .await
+                .map_or(CachedFile::Failed, CachedFile::Fetched);
+            self.cache.borrow_mut().insert(meta_path, meta);
+        }
+        Ok(())
+    }
+
+    /// Drops the cached assets, which are fetched again when read
+    pub fn clear_cache(&self) {
+        self.cache.borrow_mut().clear();
+    }
+}
+
+impl VirtualFileSystem for WebFs {
+    fn read_bytes(&self, path: &str) -> Result<Vec<u8>> {
+        match self.cache.borrow().get(path) {
+            Some(CachedFile::Fetched(bytes)) => return Ok(bytes.clone()),
+            Some(CachedFile::Fetching) => return Err(AssetError::Pending),
+            Some(CachedFile::Failed) => return Err(AssetError::ReadFailed),
+            None => {}
+        }
+        trace!("Fetching {path}");
+        self.cache
+            .borrow_mut()
+            .insert(path.to_string(), CachedFile::Fetching);
+        let url = self.url(path);
+        let path = path.to_string();
+        let cache = Rc::clone(&self.cache);
+        spawn_local(async move {
+            let file = fetch(&url)
+                .await
+                .map_or(CachedFile::Failed, CachedFile::Fetched);
+            cache.borrow_mut().insert(path, file);
+        });
+        Err(AssetError::Pending)
+    }
+}
+
+async fn fetch(url: &str) -> Result<Vec<u8>> {
+    let window = web_sys::window().ok_or(AssetError::ReadFailed)?;
+    let response = JsFuture::from(window.fetch_with_str(url))
+        .await
+        .map_err(|_| AssetError::ReadFailed)?
+        .dyn_into::<web_sys::Response>()
+        .map_err(|_| AssetError::ReadFailed)?;
+    if !response.ok() {
+        return Err(AssetError::ReadFailed);
+    }
+    let buffer = JsFuture::from(
+        response
+            .array_buffer()
+            .map_err(|_| AssetError::ReadFailed)?,
+    )
+    .await
+    .map_err(|_| AssetError::ReadFailed)?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
@@ -1,12 +1,25 @@
 use log::trace;
 
-use super::VirtualFileSystem;
+use super::{VirtualFileSystem, WritableFileSystem};
 use crate::{AssetError, Result};
 
 pub struct FileSystem;
 impl VirtualFileSystem for FileSystem {
     fn read_bytes(&self, path: &str) -> Result<Vec<u8>> {
-        trace!("Reading bytes from {}", path);
-        std::fs::read(path).map_err(|_| AssetError::ReadFailed)
+        trace!("Reading bytes from {path}");
+        std::fs::read(path).map_err(|error| match error.kind() {
+            std::io::ErrorKind::NotFound => AssetError::NotFound,
+            _ => AssetError::ReadFailed,
+        })
+    }
+}
+
+impl WritableFileSystem for FileSystem {
+    fn write_bytes(&self, path: &str, bytes: &[u8]) -> Result<()> {
+        trace!("Writing bytes to {path}");
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(parent).map_err(|_| AssetError::WriteFailed)?;
+        }
+        std::fs::write(path, bytes).map_err(|_| AssetError::WriteFailed)
     }
 }
@@ -0,0 +1,34 @@
+use crate::{AssetError, Result};
+
+use super::{VirtualFileSystem, WritableFileSystem};
+
+/// File system backed by the `localStorage` of the browser, files are stored
+/// as UTF-8 strings keyed by their path
+pub struct LocalStorage;
+
+impl LocalStorage {
+    fn storage() -> Result<web_sys::Storage> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or(AssetError::LocalStorageUnavailable)
+    }
+}
+
+impl VirtualFileSystem for LocalStorage {
+    fn read_bytes(&self, path: &str) -> Result<Vec<u8>> {
+        Self::storage()?
+            .get_item(path)
+            .map_err(|_| AssetError::ReadFailed)?
+            .map(String::into_bytes)
+            .ok_or(AssetError::NotFound)
+    }
+}
+
+impl WritableFileSystem for LocalStorage {
+    fn write_bytes(&self, path: &str, bytes: &[u8]) -> Result<()> {
+        let content = std::str::from_utf8(bytes).map_err(|_| AssetError::WriteFailed)?;
+        Self::storage()?
+            .set_item(path, content)
+            .map_err(|_| AssetError::WriteFailed)
+    }
+}
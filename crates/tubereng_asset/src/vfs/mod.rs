@@ -1,7 +1,16 @@
+use std::collections::BTreeMap;
+
 use crate::Result;
 
+pub mod archive;
+pub mod embedded;
 pub mod filesystem;
+pub mod pack;
 
+#[cfg(target_arch = "wasm32")]
+pub mod fetch;
+#[cfg(target_arch = "wasm32")]
+pub mod local_storage;
 #[cfg(target_arch = "wasm32")]
 pub mod web;
 
@@ -12,3 +21,32 @@ pub trait VirtualFileSystem {
     /// An error will be returned if the file cannot be read
     fn read_bytes(&self, path: &str) -> Result<Vec<u8>>;
 }
+
+/// A file system that can also be written to, used to persist data such as
+/// settings
+pub trait WritableFileSystem: VirtualFileSystem {
+    /// Writes the given bytes to the file at the given path, replacing its
+    /// content if it already exists
+    ///
+    /// # Errors
+    /// An error will be returned if the file cannot be written
+    fn write_bytes(&self, path: &str, bytes: &[u8]) -> Result<()>;
+}
+
+/// Returns the path of the entry of a pack or an archive read at `path`,
+/// relative to the asset directory. Paths resolved by the asset store, ending
+/// with the path of an entry, are accepted as well, the longest matching
+/// entry path is used.
+pub(crate) fn entry_path<'a, E>(entries: &'a BTreeMap<String, E>, path: &str) -> Option<&'a str> {
+    if let Some((entry_path, _)) = entries.get_key_value(path) {
+        return Some(entry_path);
+    }
+    entries
+        .keys()
+        .map(String::as_str)
+        .filter(|entry_path| {
+            path.strip_suffix(entry_path)
+                .is_some_and(|prefix| prefix.ends_with('/'))
+        })
+        .max_by_key(|entry_path| entry_path.len())
+}
@@ -0,0 +1,103 @@
+use log::trace;
+
+use super::VirtualFileSystem;
+use crate::{
+    pack::{Pack, PackManifest},
+    AssetError, Result,
+};
+
+/// Reads the assets from a pack built with [`crate::pack::Packer`], whose
+/// integrity is checked against its manifest when it is opened
+pub struct PackFileSystem {
+    pack: Pack,
+}
+
+impl PackFileSystem {
+    /// # Errors
+    ///
+    /// An error will be returned if the manifest is invalid or if an entry
+    /// doesn't match its hash
+    pub fn new(data: Vec<u8>, manifest: &[u8]) -> Result<Self> {
+        let manifest = PackManifest::from_bytes(manifest)?;
+        manifest.validate(&data)?;
+        Ok(Self {
+            pack: Pack { data, manifest },
+        })
+    }
+
+    /// Opens the pack at `pack_path` and its manifest, see
+    /// [`crate::pack::Packer::pack_directory`]
+    ///
+    /// # Errors
+    ///
+    /// An error will be returned if the pack or its manifest cannot be read,
+    /// or if the pack is invalid
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(pack_path: &str) -> Result<Self> {
+        let data = std::fs::read(pack_path).map_err(|_| AssetError::ReadFailed)?;
+        let manifest = std::fs::read(crate::pack::manifest_path(pack_path))
+            .map_err(|_| AssetError::ReadFailed)?;
+        Self::new(data, &manifest)
+    }
+
+    #[must_use]
+    pub fn manifest(&self) -> &PackManifest {
+        &self.pack.manifest
+    }
+}
+
+impl VirtualFileSystem for PackFileSystem {
+    /// Reads the entry at the given path, relative to the asset directory.
+    /// Paths resolved by the asset store, ending with the path of an entry,
+    /// are accepted as well, the longest matching entry path is used.
+    fn read_bytes(&self, path: &str) -> Result<Vec<u8>> {
+        trace!("Reading bytes from {path} in pack");
+        let manifest = &self.pack.manifest;
+        super::entry_path(&manifest.entries, path)
+            .and_then(|entry_path| manifest.entry_data(entry_path, &self.pack.data))
+            .map(<[u8]>::to_vec)
+            .ok_or(AssetError::ReadFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pack::Packer;
+
+    use super::*;
+
+    fn pack() -> Pack {
+        Packer::new()
+            .build(
+                [
+                    ("sprites/player.png".to_string(), b"player".to_vec()),
+                    ("player.png".to_string(), b"other".to_vec()),
+                ],
+                None,
+            )
+            .0
+    }
+
+    #[test]
+    fn entries_are_read_by_path() -> Result<()> {
+        let pack = pack();
+        let fs = PackFileSystem::new(pack.data, &pack.manifest.to_bytes()?)?;
+        assert_eq!(fs.read_bytes("sprites/player.png")?, b"player");
+        assert_eq!(fs.read_bytes("/game/assets/sprites/player.png")?, b"player");
+        assert_eq!(fs.read_bytes("player.png")?, b"other");
+        assert!(fs.read_bytes("layer.png").is_err());
+        assert!(fs.read_bytes("missing.png").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn corrupted_packs_are_rejected() -> Result<()> {
+        let mut pack = pack();
+        pack.data[0] = 0;
+        assert!(matches!(
+            PackFileSystem::new(pack.data, &pack.manifest.to_bytes()?),
+            Err(AssetError::PackIntegrityCheckFailed { .. })
+        ));
+        Ok(())
+    }
+}
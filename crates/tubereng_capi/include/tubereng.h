@@ -0,0 +1,101 @@
+/* C API of the tubereng engine, implemented by the tubereng_capi crate. */
+#ifndef TUBERENG_H
+#define TUBERENG_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct TuberEngine TuberEngine;
+
+typedef enum TuberStatus {
+    TUBER_STATUS_OK = 0,
+    TUBER_STATUS_NULL_POINTER,
+    TUBER_STATUS_INVALID_UTF8,
+    TUBER_STATUS_UNKNOWN_COMPONENT,
+    TUBER_STATUS_SIZE_MISMATCH,
+    TUBER_STATUS_MISSING_COMPONENT,
+    TUBER_STATUS_UNKNOWN_KEY,
+    TUBER_STATUS_UNKNOWN_BUTTON,
+    TUBER_STATUS_NO_HEADLESS_FRAME,
+    TUBER_STATUS_BUFFER_TOO_SMALL,
+    TUBER_STATUS_UNKNOWN_ENTITY,
+} TuberStatus;
+
+typedef enum TuberKey {
+    TUBER_KEY_ESCAPE = 0,
+    TUBER_KEY_RETURN,
+    TUBER_KEY_LSHIFT,
+    TUBER_KEY_RSHIFT,
+    TUBER_KEY_LCONTROL,
+    TUBER_KEY_RCONTROL,
+    TUBER_KEY_BACKSPACE,
+    TUBER_KEY_SPACE,
+    TUBER_KEY_ARROW_UP,
+    TUBER_KEY_ARROW_DOWN,
+    TUBER_KEY_ARROW_LEFT,
+    TUBER_KEY_ARROW_RIGHT,
+    /* Letters follow in alphabetical order */
+    TUBER_KEY_A,
+    TUBER_KEY_Z = TUBER_KEY_A + 25,
+    TUBER_KEY_UNKNOWN,
+    TUBER_KEY_LALT,
+    TUBER_KEY_RALT,
+    TUBER_KEY_LSUPER,
+    TUBER_KEY_RSUPER,
+} TuberKey;
+
+typedef enum TuberMouseButton {
+    TUBER_MOUSE_BUTTON_LEFT = 0,
+    TUBER_MOUSE_BUTTON_MIDDLE,
+    TUBER_MOUSE_BUTTON_RIGHT,
+    TUBER_MOUSE_BUTTON_UNKNOWN,
+} TuberMouseButton;
+
+/* Data of the "Transform" component */
+typedef struct TuberTransform {
+    float translation[3];
+    float scale[3];
+    /* x, y, z, w */
+    float rotation[4];
+} TuberTransform;
+
+/* Engine reading its assets relative to the working directory, a null title
+ * keeps the default one. Returns null if the title isn't valid UTF-8. */
+TuberEngine *tuber_engine_new(const char *application_title);
+void tuber_engine_free(TuberEngine *engine);
+TuberStatus tuber_engine_update(TuberEngine *engine, float delta_time);
+
+/* Returns false if no graphics adapter is available */
+bool tuber_engine_init_headless_graphics(TuberEngine *engine, uint32_t width, uint32_t height);
+/* Copies the last frame as tightly packed RGBA8 rows, width and height may be
+ * null */
+TuberStatus tuber_engine_read_headless_frame(const TuberEngine *engine, uint8_t *pixels,
+                                             size_t pixels_len, uint32_t *width,
+                                             uint32_t *height);
+
+TuberStatus tuber_entity_spawn(TuberEngine *engine, uint64_t *entity_id);
+TuberStatus tuber_entity_delete(TuberEngine *engine, uint64_t entity_id);
+
+/* Returns 0 if no component is registered under name */
+size_t tuber_component_size(const TuberEngine *engine, const char *name);
+TuberStatus tuber_component_get(const TuberEngine *engine, uint64_t entity_id, const char *name,
+                                void *data, size_t size);
+TuberStatus tuber_component_set(TuberEngine *engine, uint64_t entity_id, const char *name,
+                                const void *data, size_t size);
+TuberStatus tuber_component_remove(TuberEngine *engine, uint64_t entity_id, const char *name);
+
+TuberStatus tuber_input_key(TuberEngine *engine, uint32_t key, bool pressed);
+TuberStatus tuber_input_mouse_button(TuberEngine *engine, uint32_t button, bool pressed);
+TuberStatus tuber_input_cursor_moved(TuberEngine *engine, double x, double y);
+TuberStatus tuber_input_mouse_motion(TuberEngine *engine, double dx, double dy);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* TUBERENG_H */
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+
+use tubereng_core::Transform;
+use tubereng_ecs::{Ecs, EntityId};
+use tubereng_math::{quaternion::Quaternion, vector::Vector3f};
+
+/// Component exposed to C hosts through a plain data representation
+pub trait CComponent: 'static + Sized {
+    /// `#[repr(C)]` layout of the component, declared in `tubereng.h`
+    type Repr: bytemuck::Pod;
+
+    fn to_repr(&self) -> Self::Repr;
+    fn from_repr(repr: Self::Repr) -> Self;
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TuberTransform {
+    pub translation: [f32; 3],
+    pub scale: [f32; 3],
+    /// x, y, z, w
+    pub rotation: [f32; 4],
+}
+
+impl CComponent for Transform {
+    type Repr = TuberTransform;
+
+    fn to_repr(&self) -> TuberTransform {
+        let axis = self.rotation.vector_part();
+        TuberTransform {
+            translation: [self.translation.x, self.translation.y, self.translation.z],
+            scale: [self.scale.x, self.scale.y, self.scale.z],
+            rotation: [axis.x, axis.y, axis.z, self.rotation.scalar_part()],
+        }
+    }
+
+    fn from_repr(repr: TuberTransform) -> Self {
+        let [x, y, z, w] = repr.rotation;
+        Self {
+            translation: Vector3f::new(
+                repr.translation[0],
+                repr.translation[1],
+                repr.translation[2],
+            ),
+            scale: Vector3f::new(repr.scale[0], repr.scale[1], repr.scale[2]),
+            rotation: Quaternion::new(w, Vector3f::new(x, y, z)),
+        }
+    }
+}
+
+struct Entry {
+    size: usize,
+    read: fn(&Ecs, EntityId, &mut [u8]) -> bool,
+    write: fn(&mut Ecs, EntityId, &[u8]),
+    remove: fn(&mut Ecs, EntityId),
+}
+
+/// Components accessible by name from C hosts. `Transform` is registered by
+/// default.
+pub struct ComponentRegistry {
+    entries: HashMap<String, Entry>,
+}
+
+impl ComponentRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        let mut registry = Self {
+            entries: HashMap::new(),
+        };
+        registry.register::<Transform>("Transform");
+        registry
+    }
+
+    /// Registers a component under `name`, replacing the component previously
+    /// registered under that name
+    pub fn register<C: CComponent>(&mut self, name: &str) {
+        self.entries.insert(
+            name.to_string(),
+            Entry {
+                size: std::mem::size_of::<C::Repr>(),
+                read: read_component::<C>,
+                write: write_component::<C>,
+                remove: |ecs, entity_id| ecs.remove_component::<C>(entity_id),
+            },
+        );
+    }
+
+    /// Returns the size of the representation of a component, `None` if no
+    /// component is registered under `name`
+    #[must_use]
+    pub fn size(&self, name: &str) -> Option<usize> {
+        self.entries.get(name).map(|entry| entry.size)
+    }
+
+    pub(crate) fn read(
+        &self,
+        ecs: &Ecs,
+        name: &str,
+        entity_id: EntityId,
+        out: &mut [u8],
+    ) -> Result<(), AccessError> {
+        let entry = self.entry(name, out.len())?;
+        if (entry.read)(ecs, entity_id, out) {
+            Ok(())
+        } else {
+            Err(AccessError::MissingComponent)
+        }
+    }
+
+    pub(crate) fn write(
+        &self,
+        ecs: &mut Ecs,
+        name: &str,
+        entity_id: EntityId,
+        data: &[u8],
+    ) -> Result<(), AccessError> {
+        let entry = self.entry(name, data.len())?;
+        (entry.write)(ecs, entity_id, data);
+        Ok(())
+    }
+
+    pub(crate) fn remove(
+        &self,
+        ecs: &mut Ecs,
+        name: &str,
+        entity_id: EntityId,
+    ) -> Result<(), AccessError> {
+        let entry = self
+            .entries
+            .get(name)
+            .ok_or(AccessError::UnknownComponent)?;
+        (entry.remove)(ecs, entity_id);
+        Ok(())
+    }
+
+    fn entry(&self, name: &str, size: usize) -> Result<&Entry, AccessError> {
+        let entry = self
+            .entries
+            .get(name)
+            .ok_or(AccessError::UnknownComponent)?;
+        if entry.size != size {
+            return Err(AccessError::SizeMismatch);
+        }
+        Ok(entry)
+    }
+}
+
+impl Default for ComponentRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AccessError {
+    UnknownComponent,
+    SizeMismatch,
+    MissingComponent,
+}
+
+fn read_component<C: CComponent>(ecs: &Ecs, entity_id: EntityId, out: &mut [u8]) -> bool {
+    let Some(component) = ecs.component::<C>(entity_id) else {
+        return false;
+    };
+    out.copy_from_slice(bytemuck::bytes_of(&component.to_repr()));
+    true
+}
+
+fn write_component<C: CComponent>(ecs: &mut Ecs, entity_id: EntityId, data: &[u8]) {
+    let component = C::from_repr(bytemuck::pod_read_unaligned(data));
+    if let Some(mut existing) = ecs.component_mut::<C>(entity_id) {
+        *existing = component;
+        return;
+    }
+    ecs.insert_component(entity_id, component);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_round_trips_through_its_representation() {
+        let repr = TuberTransform {
+            translation: [1.0, 2.0, 3.0],
+            scale: [2.0; 3],
+            rotation: [0.0, 0.0, 1.0, 0.0],
+        };
+        assert_eq!(Transform::from_repr(repr).to_repr(), repr);
+    }
+
+    #[test]
+    fn accesses_are_checked_against_the_registry() {
+        let registry = ComponentRegistry::new();
+        let mut ecs = Ecs::new();
+        let entity_id = ecs.insert(());
+        let size = registry.size("Transform").unwrap();
+
+        let mut out = vec![0; size];
+        assert_eq!(
+            registry.read(&ecs, "Transform", entity_id, &mut out),
+            Err(AccessError::MissingComponent)
+        );
+        assert_eq!(
+            registry.read(&ecs, "Velocity", entity_id, &mut out),
+            Err(AccessError::UnknownComponent)
+        );
+        assert_eq!(
+            registry.write(&mut ecs, "Transform", entity_id, &out[1..]),
+            Err(AccessError::SizeMismatch)
+        );
+
+        let data = bytemuck::bytes_of(&Transform::default().to_repr()).to_vec();
+        registry
+            .write(&mut ecs, "Transform", entity_id, &data)
+            .unwrap();
+        registry
+            .read(&ecs, "Transform", entity_id, &mut out)
+            .unwrap();
+        assert_eq!(out, data);
+        registry.remove(&mut ecs, "Transform", entity_id).unwrap();
+        assert!(ecs.component::<Transform>(entity_id).is_none());
+    }
+}
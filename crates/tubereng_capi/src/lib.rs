@@ -0,0 +1,626 @@
+#![warn(clippy::pedantic)]
+
+//! C API embedding the engine in non-Rust hosts, declared in
+//! `include/tubereng.h`.
+//!
+//! The API covers a stable subset of the engine: its creation, stepping,
+//! headless rendering, the entities and the components registered in a
+//! [`ComponentRegistry`], and input injection. Engines configured from Rust,
+//! e.g. with systems and extra components, are handed to the host with
+//! [`TuberEngine::into_raw`].
+//!
+//! Every function taking a `TuberEngine` pointer expects a pointer returned by
+//! `tuber_engine_new` or [`TuberEngine::into_raw`] that hasn't been freed, and
+//! must not be called concurrently on the same engine.
+
+use std::ffi::{c_char, CStr};
+
+use tubereng_asset::vfs::filesystem::FileSystem;
+use tubereng_ecs::EntityId;
+use tubereng_engine::Engine;
+use tubereng_input::{keyboard::Key, mouse::Button, Input};
+
+pub mod component;
+
+use component::AccessError;
+pub use component::{CComponent, ComponentRegistry, TuberTransform};
+
+/// Engine handed to a C host, along with the components it can access
+pub struct TuberEngine {
+    engine: Engine,
+    components: ComponentRegistry,
+}
+
+impl TuberEngine {
+    #[must_use]
+    pub fn new(engine: Engine) -> Self {
+        Self {
+            engine,
+            components: ComponentRegistry::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    pub fn engine_mut(&mut self) -> &mut Engine {
+        &mut self.engine
+    }
+
+    pub fn components_mut(&mut self) -> &mut ComponentRegistry {
+        &mut self.components
+    }
+
+    /// Moves the engine to the heap, it is then owned by the host which frees
+    /// it with `tuber_engine_free`
+    #[must_use]
+    pub fn into_raw(self) -> *mut TuberEngine {
+        Box::into_raw(Box::new(self))
+    }
+}
+
+/// Result of the fallible functions of the C API
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TuberStatus {
+    Ok = 0,
+    NullPointer,
+    InvalidUtf8,
+    UnknownComponent,
+    /// The size of the component data doesn't match the registered component
+    SizeMismatch,
+    MissingComponent,
+    UnknownKey,
+    UnknownButton,
+    /// The graphics haven't been initialized headlessly
+    NoHeadlessFrame,
+    BufferTooSmall,
+    /// The entity doesn't exist or has been deleted
+    UnknownEntity,
+}
+
+impl From<AccessError> for TuberStatus {
+    fn from(error: AccessError) -> Self {
+        match error {
+            AccessError::UnknownComponent => TuberStatus::UnknownComponent,
+            AccessError::SizeMismatch => TuberStatus::SizeMismatch,
+            AccessError::MissingComponent => TuberStatus::MissingComponent,
+        }
+    }
+}
+
+impl<T> From<Result<T, AccessError>> for TuberStatus {
+    fn from(result: Result<T, AccessError>) -> Self {
+        result.map_or_else(TuberStatus::from, |_| TuberStatus::Ok)
+    }
+}
+
+/// Creates an engine reading its assets from the filesystem, relative to the
+/// working directory. A null `application_title` keeps the default title.
+///
+/// The title is leaked as the engine keeps it for its whole lifetime.
+///
+/// # Safety
+///
+/// `application_title` must be null or a valid nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn tuber_engine_new(application_title: *const c_char) -> *mut TuberEngine {
+    let mut builder = Engine::builder();
+    if !application_title.is_null() {
+        // SAFETY: The caller guarantees the string is valid
+        let Ok(title) = unsafe { CStr::from_ptr(application_title) }.to_str() else {
+            return std::ptr::null_mut();
+        };
+        builder.with_application_title(Box::leak(title.to_string().into_boxed_str()));
+    }
+    TuberEngine::new(builder.build(FileSystem)).into_raw()
+}
+
+/// Frees an engine, does nothing if `engine` is null
+///
+/// # Safety
+///
+/// See the [crate documentation](crate), `engine` must not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn tuber_engine_free(engine: *mut TuberEngine) {
+    if !engine.is_null() {
+        // SAFETY: The engine has been allocated by `TuberEngine::into_raw`
+        drop(unsafe { Box::from_raw(engine) });
+    }
+}
+
+/// Runs a frame of the engine
+///
+/// # Safety
+///
+/// See the [crate documentation](crate)
+#[no_mangle]
+pub unsafe extern "C" fn tuber_engine_update(
+    engine: *mut TuberEngine,
+    delta_time: f32,
+) -> TuberStatus {
+    // SAFETY: The caller guarantees the pointer is valid
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        return TuberStatus::NullPointer;
+    };
+    engine.engine.update(delta_time);
+    TuberStatus::Ok
+}
+
+/// Initializes the renderer without a window, blocking until it is ready.
+/// Returns false if no graphics adapter is available.
+///
+/// # Safety
+///
+/// See the [crate documentation](crate)
+#[no_mangle]
+pub unsafe extern "C" fn tuber_engine_init_headless_graphics(
+    engine: *mut TuberEngine,
+    width: u32,
+    height: u32,
+) -> bool {
+    // SAFETY: The caller guarantees the pointer is valid
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        return false;
+    };
+    pollster::block_on(engine.engine.init_headless_graphics(width, height))
+}
+
+/// Copies the last frame rendered headlessly into `pixels` as tightly packed
+/// RGBA8 rows, `pixels_len` being the size of the buffer in bytes
+///
+/// # Safety
+///
+/// See the [crate documentation](crate), `pixels` must be valid for writes of
+/// `pixels_len` bytes, `width` and `height` must be null or valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn tuber_engine_read_headless_frame(
+    engine: *const TuberEngine,
+    pixels: *mut u8,
+    pixels_len: usize,
+    width: *mut u32,
+    height: *mut u32,
+) -> TuberStatus {
+    // SAFETY: The caller guarantees the pointer is valid
+    let Some(engine) = (unsafe { engine.as_ref() }) else {
+        return TuberStatus::NullPointer;
+    };
+    if pixels.is_null() {
+        return TuberStatus::NullPointer;
+    }
+    let Some((frame_width, frame_height, frame)) = engine.engine.read_headless_frame() else {
+        return TuberStatus::NoHeadlessFrame;
+    };
+    // SAFETY: The caller guarantees the pointers are null or valid
+    unsafe {
+        if let Some(width) = width.as_mut() {
+            *width = frame_width;
+        }
+        if let Some(height) = height.as_mut() {
+            *height = frame_height;
+        }
+    }
+    if pixels_len < frame.len() {
+        return TuberStatus::BufferTooSmall;
+    }
+    // SAFETY: The caller guarantees the buffer is large enough
+    unsafe { std::slice::from_raw_parts_mut(pixels, frame.len()) }.copy_from_slice(&frame);
+    TuberStatus::Ok
+}
+
+/// Creates an entity without components, returns its id in `entity_id`
+///
+/// # Safety
+///
+/// See the [crate documentation](crate), `entity_id` must be valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn tuber_entity_spawn(
+    engine: *mut TuberEngine,
+    entity_id: *mut u64,
+) -> TuberStatus {
+    // SAFETY: The caller guarantees the pointers are valid
+    let (Some(engine), Some(entity_id)) =
+        (unsafe { engine.as_mut() }, unsafe { entity_id.as_mut() })
+    else {
+        return TuberStatus::NullPointer;
+    };
+    *entity_id = engine.engine.ecs_mut().insert(()) as u64;
+    TuberStatus::Ok
+}
+
+/// Deletes an entity along with its components, returns
+/// [`TuberStatus::UnknownEntity`] if it doesn't exist
+///
+/// # Safety
+///
+/// See the [crate documentation](crate)
+#[no_mangle]
+pub unsafe extern "C" fn tuber_entity_delete(
+    engine: *mut TuberEngine,
+    entity_id: u64,
+) -> TuberStatus {
+    // SAFETY: The caller guarantees the pointer is valid
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        return TuberStatus::NullPointer;
+    };
+    let ecs = engine.engine.ecs_mut();
+    let entity_id = to_entity_id(entity_id);
+    if !ecs.contains_entity(entity_id) {
+        return TuberStatus::UnknownEntity;
+    }
+    ecs.delete(entity_id);
+    TuberStatus::Ok
+}
+
+/// Returns the size in bytes of the data of a registered component, 0 if no
+/// component is registered under `name`
+///
+/// # Safety
+///
+/// See the [crate documentation](crate), `name` must be a valid nul-terminated
+/// string
+#[no_mangle]
+pub unsafe extern "C" fn tuber_component_size(
+    engine: *const TuberEngine,
+    name: *const c_char,
+) -> usize {
+    // SAFETY: The caller guarantees the pointers are valid
+    let (Some(engine), Ok(name)) = (unsafe { engine.as_ref() }, unsafe { to_str(name) }) else {
+        return 0;
+    };
+    engine.components.size(name).unwrap_or(0)
+}
+
+/// Copies the data of a component of an entity into `data`, `size` being the
+/// size returned by `tuber_component_size`
+///
+/// # Safety
+///
+/// See the [crate documentation](crate), `name` must be a valid nul-terminated
+/// string and `data` must be valid for writes of `size` bytes
+#[no_mangle]
+pub unsafe extern "C" fn tuber_component_get(
+    engine: *const TuberEngine,
+    entity_id: u64,
+    name: *const c_char,
+    data: *mut u8,
+    size: usize,
+) -> TuberStatus {
+    // SAFETY: The caller guarantees the pointer is valid
+    let Some(engine) = (unsafe { engine.as_ref() }) else {
+        return TuberStatus::NullPointer;
+    };
+    // SAFETY: The caller guarantees the string is valid
+    let name = match unsafe { to_str(name) } {
+        Ok(name) => name,
+        Err(status) => return status,
+    };
+    if data.is_null() {
+        return TuberStatus::NullPointer;
+    }
+    // SAFETY: The caller guarantees the buffer is valid
+    let data = unsafe { std::slice::from_raw_parts_mut(data, size) };
+    engine
+        .components
+        .read(engine.engine.ecs(), name, to_entity_id(entity_id), data)
+        .into()
+}
+
+/// Sets a component of an entity from its data, `size` being the size
+/// returned by `tuber_component_size`. The component is added to the entity
+/// if it doesn't have it yet.
+///
+/// # Safety
+///
+/// See the [crate documentation](crate), `name` must be a valid nul-terminated
+/// string and `data` must be valid for reads of `size` bytes
+#[no_mangle]
+pub unsafe extern "C" fn tuber_component_set(
+    engine: *mut TuberEngine,
+    entity_id: u64,
+    name: *const c_char,
+    data: *const u8,
+    size: usize,
+) -> TuberStatus {
+    // SAFETY: The caller guarantees the pointer is valid
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        return TuberStatus::NullPointer;
+    };
+    // SAFETY: The caller guarantees the string is valid
+    let name = match unsafe { to_str(name) } {
+        Ok(name) => name,
+        Err(status) => return status,
+    };
+    if data.is_null() {
+        return TuberStatus::NullPointer;
+    }
+    // SAFETY: The caller guarantees the buffer is valid
+    let data = unsafe { std::slice::from_raw_parts(data, size) };
+    engine
+        .components
+        .write(engine.engine.ecs_mut(), name, to_entity_id(entity_id), data)
+        .into()
+}
+
+/// Removes a component from an entity
+///
+/// # Safety
+///
+/// See the [crate documentation](crate), `name` must be a valid nul-terminated
+/// string
+#[no_mangle]
+pub unsafe extern "C" fn tuber_component_remove(
+    engine: *mut TuberEngine,
+    entity_id: u64,
+    name: *const c_char,
+) -> TuberStatus {
+    // SAFETY: The caller guarantees the pointer is valid
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        return TuberStatus::NullPointer;
+    };
+    // SAFETY: The caller guarantees the string is valid
+    let name = match unsafe { to_str(name) } {
+        Ok(name) => name,
+        Err(status) => return status,
+    };
+    engine
+        .components
+        .remove(engine.engine.ecs_mut(), name, to_entity_id(entity_id))
+        .into()
+}
+
+/// Injects a key press or release, `key` being a `TuberKey` value
+///
+/// # Safety
+///
+/// See the [crate documentation](crate)
+#[no_mangle]
+pub unsafe extern "C" fn tuber_input_key(
+    engine: *mut TuberEngine,
+    key: u32,
+    pressed: bool,
+) -> TuberStatus {
+    let Some(&key) = Key::ALL.get(key as usize) else {
+        return TuberStatus::UnknownKey;
+    };
+    // SAFETY: The caller guarantees the pointer is valid
+    unsafe {
+        on_input(
+            engine,
+            if pressed {
+                Input::KeyDown(key)
+            } else {
+                Input::KeyUp(key)
+            },
+        )
+    }
+}
+
+/// Injects a mouse button press or release, `button` being a
+/// `TuberMouseButton` value
+///
+/// # Safety
+///
+/// See the [crate documentation](crate)
+#[no_mangle]
+pub unsafe extern "C" fn tuber_input_mouse_button(
+    engine: *mut TuberEngine,
+    button: u32,
+    pressed: bool,
+) -> TuberStatus {
+    let Some(&button) = Button::ALL.get(button as usize) else {
+        return TuberStatus::UnknownButton;
+    };
+    // SAFETY: The caller guarantees the pointer is valid
+    unsafe {
+        on_input(
+            engine,
+            if pressed {
+                Input::MouseButtonDown(button)
+            } else {
+                Input::MouseButtonUp(button)
+            },
+        )
+    }
+}
+
+/// Injects the position of the cursor in the window, in pixels
+///
+/// # Safety
+///
+/// See the [crate documentation](crate)
+#[no_mangle]
+pub unsafe extern "C" fn tuber_input_cursor_moved(
+    engine: *mut TuberEngine,
+    x: f64,
+    y: f64,
+) -> TuberStatus {
+    // SAFETY: The caller guarantees the pointer is valid
+    unsafe { on_input(engine, Input::CursorMoved((x, y))) }
+}
+
+/// Injects a relative motion of the mouse
+///
+/// # Safety
+///
+/// See the [crate documentation](crate)
+#[no_mangle]
+pub unsafe extern "C" fn tuber_input_mouse_motion(
+    engine: *mut TuberEngine,
+    dx: f64,
+    dy: f64,
+) -> TuberStatus {
+    // SAFETY: The caller guarantees the pointer is valid
+    unsafe { on_input(engine, Input::MouseMotion((dx, dy))) }
+}
+
+unsafe fn on_input(engine: *mut TuberEngine, input: Input) -> TuberStatus {
+    // SAFETY: The caller guarantees the pointer is valid
+    let Some(engine) = (unsafe { engine.as_mut() }) else {
+        return TuberStatus::NullPointer;
+    };
+    engine.engine.on_input(input);
+    TuberStatus::Ok
+}
+
+unsafe fn to_str<'a>(string: *const c_char) -> Result<&'a str, TuberStatus> {
+    if string.is_null() {
+        return Err(TuberStatus::NullPointer);
+    }
+    // SAFETY: The caller guarantees the string is valid
+    unsafe { CStr::from_ptr(string) }
+        .to_str()
+        .map_err(|_| TuberStatus::InvalidUtf8)
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn to_entity_id(entity_id: u64) -> EntityId {
+    entity_id as EntityId
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_input::InputState;
+
+    use super::*;
+
+    const TRANSFORM: &CStr = c"Transform";
+
+    #[test]
+    fn components_are_accessed_by_name() {
+        unsafe {
+            let engine = tuber_engine_new(c"C host".as_ptr());
+            assert_eq!((*engine).engine().application_title(), "C host");
+            let mut entity_id = 0;
+            assert_eq!(
+                tuber_entity_spawn(engine, &raw mut entity_id),
+                TuberStatus::Ok
+            );
+
+            let size = tuber_component_size(engine, TRANSFORM.as_ptr());
+            assert_eq!(size, std::mem::size_of::<TuberTransform>());
+            let transform = TuberTransform {
+                translation: [1.0, 2.0, 3.0],
+                scale: [1.0; 3],
+                rotation: [0.0, 0.0, 0.0, 1.0],
+            };
+            assert_eq!(
+                tuber_component_set(
+                    engine,
+                    entity_id,
+                    TRANSFORM.as_ptr(),
+                    bytemuck::bytes_of(&transform).as_ptr(),
+                    size
+                ),
+                TuberStatus::Ok
+            );
+            assert_eq!(tuber_engine_update(engine, 0.016), TuberStatus::Ok);
+
+            let mut read = TuberTransform::default();
+            assert_eq!(
+                tuber_component_get(
+                    engine,
+                    entity_id,
+                    TRANSFORM.as_ptr(),
+                    bytemuck::bytes_of_mut(&mut read).as_mut_ptr(),
+                    size
+                ),
+                TuberStatus::Ok
+            );
+            assert_eq!(read, transform);
+            assert_eq!(
+                tuber_component_get(
+                    engine,
+                    entity_id,
+                    c"Velocity".as_ptr(),
+                    bytemuck::bytes_of_mut(&mut read).as_mut_ptr(),
+                    size
+                ),
+                TuberStatus::UnknownComponent
+            );
+
+            assert_eq!(tuber_entity_delete(engine, entity_id), TuberStatus::Ok);
+            assert_eq!(
+                tuber_component_get(
+                    engine,
+                    entity_id,
+                    TRANSFORM.as_ptr(),
+                    bytemuck::bytes_of_mut(&mut read).as_mut_ptr(),
+                    size
+                ),
+                TuberStatus::MissingComponent
+            );
+            tuber_engine_free(engine);
+        }
+    }
+
+    #[test]
+    fn inputs_are_injected() {
+        unsafe {
+            let engine = tuber_engine_new(std::ptr::null());
+            assert_eq!(
+                tuber_input_key(engine, Key::Space as u32, true),
+                TuberStatus::Ok
+            );
+            assert_eq!(
+                tuber_input_mouse_button(engine, Button::Left as u32, true),
+                TuberStatus::Ok
+            );
+            assert_eq!(
+                tuber_input_cursor_moved(engine, 10.0, 20.0),
+                TuberStatus::Ok
+            );
+            assert_eq!(tuber_input_key(engine, 1000, true), TuberStatus::UnknownKey);
+            {
+                let input_state = (*engine).engine().ecs().resource::<InputState>().unwrap();
+                assert!(input_state.keyboard.is_key_down(Key::Space));
+                assert!(input_state.mouse.is_button_down(Button::Left));
+                assert_eq!(input_state.mouse.position(), &(10.0, 20.0));
+            }
+            tuber_engine_free(engine);
+        }
+    }
+
+    #[test]
+    fn unknown_entities_are_not_deleted() {
+        unsafe {
+            let engine = tuber_engine_new(std::ptr::null());
+            assert_eq!(tuber_entity_delete(engine, 12), TuberStatus::UnknownEntity);
+
+            let mut entity_id = 0;
+            assert_eq!(
+                tuber_entity_spawn(engine, &raw mut entity_id),
+                TuberStatus::Ok
+            );
+            assert_eq!(tuber_entity_delete(engine, entity_id), TuberStatus::Ok);
+            assert_eq!(
+                tuber_entity_delete(engine, entity_id),
+                TuberStatus::UnknownEntity
+            );
+            assert_eq!(tuber_engine_update(engine, 0.016), TuberStatus::Ok);
+
+            let (mut first, mut second) = (0, 0);
+            tuber_entity_spawn(engine, &raw mut first);
+            tuber_entity_spawn(engine, &raw mut second);
+            assert_ne!(first, second);
+            tuber_engine_free(engine);
+        }
+    }
+
+    #[test]
+    fn null_engines_are_rejected() {
+        unsafe {
+            assert_eq!(
+                tuber_engine_update(std::ptr::null_mut(), 0.016),
+                TuberStatus::NullPointer
+            );
+            assert_eq!(
+                tuber_component_size(std::ptr::null(), TRANSFORM.as_ptr()),
+                0
+            );
+            tuber_engine_free(std::ptr::null_mut());
+        }
+    }
+}
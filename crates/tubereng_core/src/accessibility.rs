@@ -0,0 +1,104 @@
+/// Range the UI scale factor is clamped to
+pub const UI_SCALE_RANGE: std::ops::RangeInclusive<f32> = 0.5..=4.0;
+
+/// How urgently an announcement is read by screen readers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Politeness {
+    /// Read once the screen reader is idle, e.g. when a menu is opened
+    #[default]
+    Polite,
+    /// Read immediately, interrupting the current speech
+    Assertive,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    pub text: String,
+    pub politeness: Politeness,
+}
+
+/// Resource holding the accessibility settings of the application and the
+/// announcements to forward to screen readers.
+///
+/// The UI scale factor is honored by the UI layout, each UI point being drawn
+/// with `ui_scale` pixels. Announcements are forwarded by the runner at the
+/// end of the frame they have been made in, to ARIA live regions on wasm32.
+/// Native runners only log them.
+#[derive(Debug)]
+pub struct Accessibility {
+    ui_scale: f32,
+    announcements: Vec<Announcement>,
+}
+
+impl Accessibility {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            ui_scale: 1.0,
+            announcements: vec![],
+        }
+    }
+
+    #[must_use]
+    pub fn ui_scale(&self) -> f32 {
+        self.ui_scale
+    }
+
+    /// Sets the UI scale factor, clamped to [`UI_SCALE_RANGE`]
+    pub fn set_ui_scale(&mut self, ui_scale: f32) {
+        self.ui_scale = ui_scale.clamp(*UI_SCALE_RANGE.start(), *UI_SCALE_RANGE.end());
+    }
+
+    /// Queues a text to be read by screen readers, e.g. the title of a menu
+    /// which has just been opened
+    pub fn announce(&mut self, text: impl Into<String>, politeness: Politeness) {
+        self.announcements.push(Announcement {
+            text: text.into(),
+            politeness,
+        });
+    }
+
+    /// Returns the queued announcements, in the order they have been made
+    pub fn drain_announcements(&mut self) -> impl Iterator<Item = Announcement> + '_ {
+        self.announcements.drain(..)
+    }
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_scale_is_clamped() {
+        let mut accessibility = Accessibility::new();
+        accessibility.set_ui_scale(1.5);
+        assert!((accessibility.ui_scale() - 1.5).abs() < f32::EPSILON);
+        accessibility.set_ui_scale(0.0);
+        assert!((accessibility.ui_scale() - 0.5).abs() < f32::EPSILON);
+        accessibility.set_ui_scale(10.0);
+        assert!((accessibility.ui_scale() - 4.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn announcements_are_drained_in_order() {
+        let mut accessibility = Accessibility::new();
+        accessibility.announce("Options", Politeness::Polite);
+        accessibility.announce("Game saved", Politeness::Assertive);
+        let announcements = accessibility.drain_announcements().collect::<Vec<_>>();
+        assert_eq!(
+            announcements
+                .iter()
+                .map(|announcement| announcement.text.as_str())
+                .collect::<Vec<_>>(),
+            ["Options", "Game saved"]
+        );
+        assert_eq!(announcements[1].politeness, Politeness::Assertive);
+        assert_eq!(accessibility.drain_announcements().count(), 0);
+    }
+}
@@ -0,0 +1,102 @@
+use bumpalo::Bump;
+
+/// Vec allocated in a [`FrameArena`]
+pub type ArenaVec<'arena, T> = bumpalo::collections::Vec<'arena, T>;
+
+/// Bump allocator for transient collections (draw lists, culling results,
+/// work queues...) that only live for the frame they are created in.
+///
+/// Allocations are freed all at once when the arena is reset at the start of
+/// each frame, the memory of the arena being reused from one frame to the
+/// next. Values allocated with [`FrameArena::alloc`] and the slice helpers are
+/// never dropped, so they shouldn't own resources. [`ArenaVec`] drops its
+/// elements as usual.
+#[derive(Default)]
+pub struct FrameArena {
+    bump: Bump,
+}
+
+impl FrameArena {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bump: Bump::with_capacity(capacity),
+        }
+    }
+
+    #[must_use]
+    pub fn vec<T>(&self) -> ArenaVec<'_, T> {
+        ArenaVec::new_in(&self.bump)
+    }
+
+    #[must_use]
+    pub fn vec_with_capacity<T>(&self, capacity: usize) -> ArenaVec<'_, T> {
+        ArenaVec::with_capacity_in(capacity, &self.bump)
+    }
+
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        self.bump.alloc(value)
+    }
+
+    pub fn alloc_slice_copy<T: Copy>(&self, slice: &[T]) -> &mut [T] {
+        self.bump.alloc_slice_copy(slice)
+    }
+
+    pub fn alloc_slice_fill_iter<T, I>(&self, iter: I) -> &mut [T]
+    where
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        self.bump.alloc_slice_fill_iter(iter)
+    }
+
+    /// Size of the memory owned by the arena, allocated or not
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.bump.allocated_bytes()
+    }
+
+    /// Frees every allocation, keeping the largest chunk of memory of the
+    /// arena for the next frame
+    pub fn reset(&mut self) {
+        self.bump.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arena_collections() {
+        let arena = FrameArena::with_capacity(1024);
+        let mut entities = arena.vec();
+        entities.extend([3, 1, 2]);
+        entities.sort_unstable();
+        assert_eq!(entities.as_slice(), [1, 2, 3]);
+
+        let squares = arena.alloc_slice_fill_iter(entities.iter().map(|e| e * e));
+        assert_eq!(squares, [1, 4, 9]);
+        assert_eq!(arena.alloc_slice_copy(&[5u8, 6]), [5, 6]);
+    }
+
+    #[test]
+    fn arena_memory_is_reused_after_reset() {
+        let mut arena = FrameArena::new();
+        for _ in 0..2 {
+            let mut values = arena.vec_with_capacity(256);
+            values.extend(0..256u32);
+        }
+        let capacity = arena.capacity();
+
+        arena.reset();
+        let mut values = arena.vec_with_capacity(256);
+        values.extend(0..256u32);
+        assert!(arena.capacity() <= capacity);
+    }
+}
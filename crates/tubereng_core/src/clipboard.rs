@@ -0,0 +1,98 @@
+/// Access to the clipboard of the platform, installed by the runner
+pub trait ClipboardBackend {
+    /// Returns the text held by the clipboard, `None` if it's empty, doesn't
+    /// hold text or can't be read
+    fn get_text(&mut self) -> Option<String>;
+    fn set_text(&mut self, text: &str);
+}
+
+/// Clipboard only shared within the application, used when the platform
+/// clipboard isn't available, e.g. without a window
+#[derive(Debug, Default)]
+pub struct LocalClipboard {
+    text: Option<String>,
+}
+
+impl ClipboardBackend for LocalClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        self.text.clone()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.text = Some(text.to_string());
+    }
+}
+
+/// Resource through which systems copy and paste text, e.g. in a text field
+/// or a dev console. It's backed by the platform clipboard once the runner
+/// installs its [`ClipboardBackend`], by a [`LocalClipboard`] until then.
+pub struct Clipboard {
+    backend: Box<dyn ClipboardBackend>,
+}
+
+impl Clipboard {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_backend(LocalClipboard::default())
+    }
+
+    #[must_use]
+    pub fn with_backend(backend: impl ClipboardBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: impl ClipboardBackend + 'static) {
+        self.backend = Box::new(backend);
+    }
+
+    /// Returns the text held by the clipboard, see
+    /// [`ClipboardBackend::get_text`]
+    pub fn get_text(&mut self) -> Option<String> {
+        self.backend.get_text()
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.backend.set_text(text);
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    struct SharedClipboard(Rc<RefCell<String>>);
+
+    impl ClipboardBackend for SharedClipboard {
+        fn get_text(&mut self) -> Option<String> {
+            Some(self.0.borrow().clone())
+        }
+
+        fn set_text(&mut self, text: &str) {
+            *self.0.borrow_mut() = text.to_string();
+        }
+    }
+
+    #[test]
+    fn clipboard_backend() {
+        let mut clipboard = Clipboard::new();
+        assert_eq!(clipboard.get_text(), None);
+        clipboard.set_text("copied");
+        assert_eq!(clipboard.get_text().as_deref(), Some("copied"));
+
+        let platform_text = Rc::new(RefCell::new("pasted".to_string()));
+        clipboard.set_backend(SharedClipboard(Rc::clone(&platform_text)));
+        assert_eq!(clipboard.get_text().as_deref(), Some("pasted"));
+        clipboard.set_text("copied");
+        assert_eq!(*platform_text.borrow(), "copied");
+    }
+}
@@ -0,0 +1,23 @@
+/// Resource through which systems request the application to exit, e.g. from
+/// a "Quit" menu entry. The runner checks it at the end of each frame and
+/// stops once the frame is complete.
+#[derive(Debug, Default)]
+pub struct AppExit {
+    requested: bool,
+}
+
+impl AppExit {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    #[must_use]
+    pub fn is_requested(&self) -> bool {
+        self.requested
+    }
+}
@@ -0,0 +1,66 @@
+use crate::Transform;
+
+/// Component rendering an entity simulated in the `FixedUpdate` stage
+/// between its transforms of the last two fixed steps, so that its motion
+/// stays smooth when the frame rate differs from the simulation rate.
+///
+/// The rendered transform lags behind the simulated one by up to a step, the
+/// interpolation factor being `FixedTime::alpha`. An entity moved outside of
+/// the fixed steps, e.g. teleported, should be [`InterpolatedTransform::reset`].
+#[derive(Debug, Clone, Default)]
+pub struct InterpolatedTransform {
+    previous: Option<Transform>,
+}
+
+impl InterpolatedTransform {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the transform of the entity before a fixed step
+    pub fn record(&mut self, transform: &Transform) {
+        self.previous = Some(transform.clone());
+    }
+
+    /// Renders the current transform of the entity until the next fixed step
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Returns the transform to render, between the transform recorded
+    /// before the last fixed step and the `current` one
+    #[must_use]
+    pub fn interpolate(&self, current: &Transform, alpha: f32) -> Transform {
+        match &self.previous {
+            Some(previous) => previous.interpolate(current, alpha),
+            None => current.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_math::vector::Vector3f;
+
+    use super::*;
+
+    fn at(x: f32) -> Transform {
+        Transform {
+            translation: Vector3f::new(x, 0.0, 0.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn interpolated_transform() {
+        let mut interpolated = InterpolatedTransform::new();
+        assert!((interpolated.interpolate(&at(4.0), 0.5).translation.x - 4.0).abs() < 1e-6);
+
+        interpolated.record(&at(2.0));
+        assert!((interpolated.interpolate(&at(4.0), 0.25).translation.x - 2.5).abs() < 1e-6);
+
+        interpolated.reset();
+        assert!((interpolated.interpolate(&at(4.0), 0.25).translation.x - 4.0).abs() < 1e-6);
+    }
+}
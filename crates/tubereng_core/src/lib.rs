@@ -2,15 +2,26 @@
 
 use std::collections::HashMap;
 
+use serde::{Deserialize, Serialize};
+
 use tubereng_math::{
     matrix::{Identity, Matrix4f},
     quaternion::Quaternion,
     vector::Vector3f,
 };
 
-pub struct DeltaTime(pub f32);
+pub mod accessibility;
+pub mod arena;
+pub mod clipboard;
+pub mod exit;
+pub mod interpolation;
+pub mod scheduler;
+pub mod time;
+pub mod verlet;
 
-#[derive(Debug, Clone)]
+/// Fields missing from a serialized transform take their default value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Transform {
     pub translation: Vector3f,
     pub scale: Vector3f,
@@ -24,6 +35,17 @@ impl Transform {
             * Matrix4f::new_translation(&self.translation)
             * self.rotation.rotation_matrix()
     }
+
+    /// Interpolates between this transform, at `alpha` 0, and another one,
+    /// at `alpha` 1
+    #[must_use]
+    pub fn interpolate(&self, other: &Transform, alpha: f32) -> Transform {
+        Transform {
+            translation: self.translation + (other.translation - self.translation) * alpha,
+            scale: self.scale + (other.scale - self.scale) * alpha,
+            rotation: self.rotation.nlerp(&other.rotation, alpha),
+        }
+    }
 }
 
 impl From<Matrix4f> for Transform {
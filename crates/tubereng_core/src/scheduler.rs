@@ -0,0 +1,282 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Time spent running jobs each frame by default
+pub const DEFAULT_FRAME_BUDGET: Duration = Duration::from_millis(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobId(u64);
+
+/// Result of a step of a job
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobStep {
+    /// The job has work left, with its progress between 0 and 1
+    Progress(f32),
+    Done,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobEventKind {
+    Progress(f32),
+    Completed,
+}
+
+/// Event emitted by the [`JobScheduler`] when a job ran during the frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobEvent {
+    pub id: JobId,
+    pub name: String,
+    pub kind: JobEventKind,
+}
+
+struct Job {
+    id: JobId,
+    name: String,
+    step: Box<dyn FnMut() -> JobStep>,
+    progress: f32,
+}
+
+/// Resource running queued low priority jobs, e.g. navmesh baking, atlas
+/// packing or save serialization, on the main thread for at most a time
+/// budget per frame so that long computations don't cause hitches.
+///
+/// A job is a closure doing a small slice of the work each time it is
+/// called. Jobs run one after the other in the order they were spawned, the
+/// current job being called until it is done or the budget is spent. At
+/// least one step runs each frame so that jobs progress with a zero budget.
+pub struct JobScheduler {
+    frame_budget: Duration,
+    jobs: VecDeque<Job>,
+    events: Vec<JobEvent>,
+    next_id: u64,
+}
+
+impl JobScheduler {
+    #[must_use]
+    pub fn new(frame_budget: Duration) -> Self {
+        Self {
+            frame_budget,
+            jobs: VecDeque::new(),
+            events: vec![],
+            next_id: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn frame_budget(&self) -> Duration {
+        self.frame_budget
+    }
+
+    pub fn set_frame_budget(&mut self, frame_budget: Duration) {
+        self.frame_budget = frame_budget;
+    }
+
+    /// Queues a job, `step` being called until it returns [`JobStep::Done`]
+    pub fn spawn<F>(&mut self, name: &str, step: F) -> JobId
+    where
+        F: 'static + FnMut() -> JobStep,
+    {
+        let id = JobId(self.next_id);
+        self.next_id += 1;
+        self.jobs.push_back(Job {
+            id,
+            name: name.to_string(),
+            step: Box::new(step),
+            progress: 0.0,
+        });
+        id
+    }
+
+    /// Removes a queued job. Returns false if the job is done or unknown.
+    pub fn cancel(&mut self, id: JobId) -> bool {
+        let Some(index) = self.jobs.iter().position(|job| job.id == id) else {
+            return false;
+        };
+        self.jobs.remove(index);
+        true
+    }
+
+    /// Returns the progress of a queued job, `None` if it is done or unknown
+    #[must_use]
+    pub fn progress(&self, id: JobId) -> Option<f32> {
+        self.jobs
+            .iter()
+            .find(|job| job.id == id)
+            .map(|job| job.progress)
+    }
+
+    #[must_use]
+    pub fn pending_count(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Returns the events emitted during the last frame
+    pub fn events(&self) -> impl Iterator<Item = &JobEvent> {
+        self.events.iter()
+    }
+
+    /// Runs the queued jobs for at most the frame budget, replacing the events
+    /// of the previous frame
+    pub fn run(&mut self) {
+        let start = Instant::now();
+        self.run_with_clock(|| start.elapsed());
+    }
+
+    /// Runs the queued jobs until `elapsed` goes past the frame budget
+    fn run_with_clock(&mut self, mut elapsed: impl FnMut() -> Duration) {
+        self.events.clear();
+        let mut last_progress_event = None;
+        let mut ran_a_step = false;
+        while let Some(job) = self.jobs.front_mut() {
+            if ran_a_step && elapsed() >= self.frame_budget {
+                break;
+            }
+            ran_a_step = true;
+
+            match (job.step)() {
+                JobStep::Progress(progress) => {
+                    job.progress = progress.clamp(0.0, 1.0);
+                    last_progress_event = Some(job.id);
+                }
+                JobStep::Done => {
+                    // SAFETY: The job is the front of the queue
+                    let job = unsafe { self.jobs.pop_front().unwrap_unchecked() };
+                    self.events.push(JobEvent {
+                        id: job.id,
+                        name: job.name,
+                        kind: JobEventKind::Completed,
+                    });
+                    last_progress_event = None;
+                }
+            }
+        }
+
+        // Only the latest progress of the job left unfinished is reported
+        if let Some(job) =
+            last_progress_event.and_then(|id| self.jobs.iter().find(|job| job.id == id))
+        {
+            self.events.push(JobEvent {
+                id: job.id,
+                name: job.name.clone(),
+                kind: JobEventKind::Progress(job.progress),
+            });
+        }
+    }
+}
+
+impl Default for JobScheduler {
+    fn default() -> Self {
+        Self::new(DEFAULT_FRAME_BUDGET)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    /// Job counting its steps, done after `steps` steps
+    fn counting_job(steps: u32) -> (Rc<Cell<u32>>, impl FnMut() -> JobStep) {
+        let count = Rc::new(Cell::new(0));
+        let job_count = count.clone();
+        (count, move || {
+            job_count.set(job_count.get() + 1);
+            if job_count.get() == steps {
+                JobStep::Done
+            } else {
+                #[allow(clippy::cast_precision_loss)]
+                JobStep::Progress(job_count.get() as f32 / steps as f32)
+            }
+        })
+    }
+
+    /// Clock advancing by 1ms each time it is read
+    fn clock() -> impl FnMut() -> Duration {
+        let mut elapsed = Duration::ZERO;
+        move || {
+            elapsed += Duration::from_millis(1);
+            elapsed
+        }
+    }
+
+    #[test]
+    fn jobs_run_within_the_frame_budget() {
+        let mut scheduler = JobScheduler::new(Duration::from_millis(3));
+        let (count, job) = counting_job(10);
+        let id = scheduler.spawn("bake navmesh", job);
+
+        scheduler.run_with_clock(clock());
+        assert_eq!(count.get(), 3);
+        assert_eq!(scheduler.progress(id), Some(0.3));
+        assert_eq!(
+            scheduler.events().collect::<Vec<_>>(),
+            [&JobEvent {
+                id,
+                name: "bake navmesh".to_string(),
+                kind: JobEventKind::Progress(0.3),
+            }]
+        );
+
+        scheduler.run_with_clock(clock());
+        scheduler.run_with_clock(clock());
+        scheduler.run_with_clock(clock());
+        assert_eq!(count.get(), 10);
+        assert_eq!(scheduler.pending_count(), 0);
+        assert_eq!(scheduler.progress(id), None);
+        assert!(scheduler
+            .events()
+            .any(|event| event.id == id && event.kind == JobEventKind::Completed));
+    }
+
+    #[test]
+    fn jobs_run_in_order() {
+        let mut scheduler = JobScheduler::new(Duration::from_millis(3));
+        let (first_count, first_job) = counting_job(2);
+        let (second_count, second_job) = counting_job(5);
+        let first = scheduler.spawn("first", first_job);
+        let second = scheduler.spawn("second", second_job);
+
+        scheduler.run_with_clock(clock());
+        assert_eq!(first_count.get(), 2);
+        assert_eq!(second_count.get(), 1);
+        let kinds = scheduler
+            .events()
+            .map(|event| (event.id, event.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                (first, JobEventKind::Completed),
+                (second, JobEventKind::Progress(0.2))
+            ]
+        );
+    }
+
+    #[test]
+    fn a_step_runs_each_frame_with_a_zero_budget() {
+        let mut scheduler = JobScheduler::new(Duration::ZERO);
+        let (count, job) = counting_job(10);
+        scheduler.spawn("save", job);
+        scheduler.run();
+        scheduler.run();
+        assert_eq!(count.get(), 2);
+    }
+
+    #[test]
+    fn cancelled_jobs_dont_run() {
+        let mut scheduler = JobScheduler::default();
+        let (count, job) = counting_job(10);
+        let id = scheduler.spawn("pack atlas", job);
+        assert!(scheduler.cancel(id));
+        assert!(!scheduler.cancel(id));
+        scheduler.run();
+        assert_eq!(count.get(), 0);
+        assert_eq!(scheduler.events().count(), 0);
+    }
+}
@@ -0,0 +1,365 @@
+/// Resource tracking the time of the frames, advanced by the engine before
+/// the systems of a world run.
+///
+/// The delta time is multiplied by the time scale, so that slow motion or
+/// pausing the game only requires changing the scale, the systems that must
+/// keep running at real time, e.g. the UI, using the raw delta time instead.
+#[derive(Debug, Clone)]
+pub struct Time {
+    raw_delta: f32,
+    delta: f32,
+    scale: f32,
+    paused: bool,
+    raw_elapsed: f64,
+    elapsed: f64,
+    frame_count: u64,
+}
+
+impl Time {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            raw_delta: 0.0,
+            delta: 0.0,
+            scale: 1.0,
+            paused: false,
+            raw_elapsed: 0.0,
+            elapsed: 0.0,
+            frame_count: 0,
+        }
+    }
+
+    /// Returns the time after a single frame lasting `raw_delta` seconds,
+    /// e.g. to run systems outside of the engine
+    #[must_use]
+    pub fn with_delta(raw_delta: f32) -> Self {
+        let mut time = Self::new();
+        time.advance(raw_delta);
+        time
+    }
+
+    /// Starts a new frame lasting `raw_delta` seconds of real time
+    pub fn advance(&mut self, raw_delta: f32) {
+        self.raw_delta = raw_delta.max(0.0);
+        self.delta = if self.paused {
+            0.0
+        } else {
+            self.raw_delta * self.scale
+        };
+        self.raw_elapsed += f64::from(self.raw_delta);
+        self.elapsed += f64::from(self.delta);
+        self.frame_count += 1;
+    }
+
+    /// Scaled duration of the current frame in seconds, 0 while paused
+    #[must_use]
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// Real duration of the current frame in seconds, regardless of the time
+    /// scale and of pauses
+    #[must_use]
+    pub fn raw_delta(&self) -> f32 {
+        self.raw_delta
+    }
+
+    #[must_use]
+    pub fn time_scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Sets the speed of the scaled time, e.g. 0.5 for slow motion. Applies
+    /// from the next frame.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the time scale is negative
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        assert!(time_scale >= 0.0, "The time scale can't be negative");
+        self.scale = time_scale;
+    }
+
+    /// Stops the scaled time from the next frame, keeping the time scale
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    #[must_use]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Scaled time elapsed since the first frame, in seconds
+    #[must_use]
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Real time elapsed since the first frame, in seconds
+    #[must_use]
+    pub fn raw_elapsed(&self) -> f64 {
+        self.raw_elapsed
+    }
+
+    /// Number of frames started, including the current one
+    #[must_use]
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Time step of the systems of the `FixedUpdate` stage, in seconds
+pub const DEFAULT_FIXED_TIMESTEP: f32 = 1.0 / 60.0;
+
+/// Number of fixed steps a frame runs at most, so that a slow frame doesn't
+/// make the next ones even slower
+pub const DEFAULT_MAX_STEPS_PER_FRAME: u32 = 8;
+
+/// Resource driving the `FixedUpdate` stage, which runs zero or more times per
+/// frame so that the time simulated by its systems advances by `timestep`
+/// at each run, regardless of the frame rate.
+///
+/// The delta time of each frame is accumulated, a run of the stage consuming
+/// `timestep` of the accumulated time.
+#[derive(Debug, Clone)]
+pub struct FixedTime {
+    timestep: f32,
+    accumulator: f32,
+    max_steps_per_frame: u32,
+}
+
+impl FixedTime {
+    /// # Panics
+    ///
+    /// Will panic if the timestep isn't positive
+    #[must_use]
+    pub fn new(timestep: f32) -> Self {
+        assert!(timestep > 0.0, "The fixed timestep must be positive");
+        Self {
+            timestep,
+            accumulator: 0.0,
+            max_steps_per_frame: DEFAULT_MAX_STEPS_PER_FRAME,
+        }
+    }
+
+    /// Time simulated by each run of the `FixedUpdate` stage, in seconds
+    #[must_use]
+    pub fn timestep(&self) -> f32 {
+        self.timestep
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the timestep isn't positive
+    pub fn set_timestep(&mut self, timestep: f32) {
+        assert!(timestep > 0.0, "The fixed timestep must be positive");
+        self.timestep = timestep;
+    }
+
+    #[must_use]
+    pub fn max_steps_per_frame(&self) -> u32 {
+        self.max_steps_per_frame
+    }
+
+    pub fn set_max_steps_per_frame(&mut self, max_steps_per_frame: u32) {
+        self.max_steps_per_frame = max_steps_per_frame;
+    }
+
+    /// Accumulated time not simulated yet, less than `timestep` once the
+    /// stage has run
+    #[must_use]
+    pub fn overstep(&self) -> f32 {
+        self.accumulator
+    }
+
+    /// Fraction of a step accumulated but not simulated yet, between 0 and 1,
+    /// used to interpolate the rendering between the last two steps
+    #[must_use]
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.timestep).min(1.0)
+    }
+
+    /// Accumulates the delta time of a frame. Time beyond
+    /// `max_steps_per_frame` steps is dropped.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn accumulate(&mut self, delta_time: f32) {
+        let max_accumulator = self.timestep * self.max_steps_per_frame as f32;
+        self.accumulator = (self.accumulator + delta_time.max(0.0)).min(max_accumulator);
+    }
+
+    /// Consumes a step of the accumulated time, returns `false` if less than
+    /// a step has been accumulated
+    pub fn expend(&mut self) -> bool {
+        if self.accumulator < self.timestep {
+            return false;
+        }
+        self.accumulator -= self.timestep;
+        true
+    }
+}
+
+impl Default for FixedTime {
+    fn default() -> Self {
+        Self::new(DEFAULT_FIXED_TIMESTEP)
+    }
+}
+
+/// Weight of the last frame in the average frame time of [`FrameTiming`]
+const FRAME_TIME_SMOOTHING: f32 = 0.1;
+
+/// Resource measuring the actual timing of the frames, the time spent
+/// updating the engine and the time spent waiting to honor the frame rate
+/// limit being reported by the runner.
+#[derive(Debug, Clone, Default)]
+#[allow(clippy::struct_field_names)]
+pub struct FrameTiming {
+    frame_time: f32,
+    average_frame_time: f32,
+    work_time: f32,
+    wait_time: f32,
+}
+
+impl FrameTiming {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the delta time of a frame, averaged with the previous frames
+    pub fn record_frame(&mut self, frame_time: f32) {
+        self.frame_time = frame_time;
+        self.average_frame_time = if self.average_frame_time > 0.0 {
+            self.average_frame_time + (frame_time - self.average_frame_time) * FRAME_TIME_SMOOTHING
+        } else {
+            frame_time
+        };
+    }
+
+    /// Records the time spent updating the engine and the time spent waiting
+    /// before the last frame
+    pub fn record_pacing(&mut self, work_time: f32, wait_time: f32) {
+        self.work_time = work_time;
+        self.wait_time = wait_time;
+    }
+
+    /// Time between the start of the last frame and the start of the
+    /// previous one, in seconds
+    #[must_use]
+    pub fn frame_time(&self) -> f32 {
+        self.frame_time
+    }
+
+    /// Exponential moving average of the frame time, in seconds
+    #[must_use]
+    pub fn average_frame_time(&self) -> f32 {
+        self.average_frame_time
+    }
+
+    /// Frames per second computed from the average frame time, 0 before the
+    /// first frame
+    #[must_use]
+    pub fn frame_rate(&self) -> f32 {
+        if self.average_frame_time > 0.0 {
+            1.0 / self.average_frame_time
+        } else {
+            0.0
+        }
+    }
+
+    /// Time spent updating the engine during the last frame, in seconds
+    #[must_use]
+    pub fn work_time(&self) -> f32 {
+        self.work_time
+    }
+
+    /// Time spent waiting before the last frame to honor the frame rate
+    /// limit, in seconds
+    #[must_use]
+    pub fn wait_time(&self) -> f32 {
+        self.wait_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step_count(fixed_time: &mut FixedTime) -> u32 {
+        let mut count = 0;
+        while fixed_time.expend() {
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn fixed_time_steps() {
+        let mut fixed_time = FixedTime::new(0.25);
+
+        fixed_time.accumulate(0.6);
+        assert_eq!(step_count(&mut fixed_time), 2);
+        assert!((fixed_time.overstep() - 0.1).abs() < 1e-6);
+
+        fixed_time.accumulate(0.1);
+        assert_eq!(step_count(&mut fixed_time), 0);
+
+        fixed_time.accumulate(0.05);
+        assert_eq!(step_count(&mut fixed_time), 1);
+    }
+
+    #[test]
+    fn fixed_time_max_steps_per_frame() {
+        let mut fixed_time = FixedTime::new(0.25);
+        fixed_time.set_max_steps_per_frame(3);
+
+        fixed_time.accumulate(10.0);
+
+        assert_eq!(step_count(&mut fixed_time), 3);
+        assert!(fixed_time.overstep().abs() < 1e-6);
+    }
+
+    #[test]
+    fn frame_timing_average() {
+        let mut frame_timing = FrameTiming::new();
+        assert!(frame_timing.frame_rate().abs() < 1e-6);
+
+        frame_timing.record_frame(0.02);
+        assert!((frame_timing.frame_rate() - 50.0).abs() < 1e-3);
+
+        frame_timing.record_frame(0.03);
+        assert!((frame_timing.frame_time() - 0.03).abs() < 1e-6);
+        assert!((frame_timing.average_frame_time() - 0.021).abs() < 1e-6);
+    }
+
+    #[test]
+    fn time_scale_and_pause() {
+        let mut time = Time::new();
+        time.advance(0.5);
+        time.set_time_scale(0.5);
+        time.advance(0.5);
+        assert!((time.delta() - 0.25).abs() < 1e-6);
+        assert!((time.raw_delta() - 0.5).abs() < 1e-6);
+
+        time.pause();
+        time.advance(0.5);
+        assert!(time.delta().abs() < 1e-6);
+        time.resume();
+        time.advance(0.5);
+
+        assert!((time.delta() - 0.25).abs() < 1e-6);
+        assert!((time.elapsed() - 1.0).abs() < 1e-6);
+        assert!((time.raw_elapsed() - 2.0).abs() < 1e-6);
+        assert_eq!(time.frame_count(), 4);
+    }
+}
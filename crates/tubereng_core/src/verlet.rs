@@ -0,0 +1,228 @@
+use tubereng_math::vector::Vector3f;
+
+#[derive(Debug, Clone)]
+pub struct Point {
+    pub position: Vector3f,
+    previous_position: Vector3f,
+    /// Pinned points aren't moved by the simulation, they can be moved
+    /// manually to attach the body to something
+    pub pinned: bool,
+}
+
+impl Point {
+    #[must_use]
+    pub fn new(position: Vector3f) -> Self {
+        Self {
+            position,
+            previous_position: position,
+            pinned: false,
+        }
+    }
+}
+
+/// Keeps two points of a body at a given distance
+#[derive(Debug, Clone)]
+pub struct DistanceConstraint {
+    pub a: usize,
+    pub b: usize,
+    pub length: f32,
+    /// Fraction of the error corrected at each iteration, between 0 (loose)
+    /// and 1 (rigid)
+    pub stiffness: f32,
+}
+
+/// A set of points linked by distance constraints simulated with verlet
+/// integration, to be used for ropes, chains, bridges or cloth
+#[derive(Debug, Clone)]
+pub struct VerletBody {
+    pub points: Vec<Point>,
+    pub constraints: Vec<DistanceConstraint>,
+    pub gravity: Vector3f,
+    /// Fraction of the velocity of the points kept from one step to the next
+    pub damping: f32,
+    /// Number of times the constraints are solved at each step, more
+    /// iterations make the body stiffer
+    pub iterations: u32,
+}
+
+impl VerletBody {
+    /// Gravity in pixels per second squared, pointing down the Y axis as with
+    /// 2D cameras
+    pub const DEFAULT_GRAVITY: Vector3f = Vector3f {
+        x: 0.0,
+        y: 980.0,
+        z: 0.0,
+    };
+
+    #[must_use]
+    pub fn new(points: Vec<Point>, constraints: Vec<DistanceConstraint>) -> Self {
+        Self {
+            points,
+            constraints,
+            gravity: Self::DEFAULT_GRAVITY,
+            damping: 0.99,
+            iterations: 8,
+        }
+    }
+
+    /// Creates a rope of `segment_count` segments going from `start` to
+    /// `end`, with its first point pinned
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `segment_count` is 0
+    #[must_use]
+    pub fn rope(start: Vector3f, end: Vector3f, segment_count: usize) -> Self {
+        assert!(segment_count > 0, "A rope needs at least one segment");
+        #[allow(clippy::cast_precision_loss)]
+        let segment = (end - start) / segment_count as f32;
+        #[allow(clippy::cast_precision_loss)]
+        let mut points = (0..=segment_count)
+            .map(|i| Point::new(start + segment * i as f32))
+            .collect::<Vec<_>>();
+        points[0].pinned = true;
+
+        let constraints = (0..segment_count)
+            .map(|i| DistanceConstraint {
+                a: i,
+                b: i + 1,
+                length: segment.norm(),
+                stiffness: 1.0,
+            })
+            .collect();
+        Self::new(points, constraints)
+    }
+
+    /// Creates a grid of `columns` x `rows` points spaced by `spacing` in the
+    /// XY plane, with its top row pinned, to be used for cloth or capes
+    #[must_use]
+    pub fn grid(origin: Vector3f, columns: usize, rows: usize, spacing: f32) -> Self {
+        #[allow(clippy::cast_precision_loss)]
+        let points = (0..rows)
+            .flat_map(|row| {
+                (0..columns).map(move |column| {
+                    let mut point = Point::new(
+                        origin + Vector3f::new(column as f32 * spacing, row as f32 * spacing, 0.0),
+                    );
+                    point.pinned = row == 0;
+                    point
+                })
+            })
+            .collect();
+
+        let mut constraints = vec![];
+        for row in 0..rows {
+            for column in 0..columns {
+                let index = row * columns + column;
+                if column + 1 < columns {
+                    constraints.push(DistanceConstraint {
+                        a: index,
+                        b: index + 1,
+                        length: spacing,
+                        stiffness: 1.0,
+                    });
+                }
+                if row + 1 < rows {
+                    constraints.push(DistanceConstraint {
+                        a: index,
+                        b: index + columns,
+                        length: spacing,
+                        stiffness: 1.0,
+                    });
+                }
+            }
+        }
+        Self::new(points, constraints)
+    }
+
+    /// Advances the simulation by `delta_time` seconds
+    pub fn step(&mut self, delta_time: f32) {
+        self.step_with_collisions(delta_time, |_| {});
+    }
+
+    /// Advances the simulation by `delta_time` seconds, `collide` is called on
+    /// the position of every free point after each constraint iteration to
+    /// push it out of obstacles
+    pub fn step_with_collisions<F>(&mut self, delta_time: f32, mut collide: F)
+    where
+        F: FnMut(&mut Vector3f),
+    {
+        let acceleration = self.gravity * delta_time * delta_time;
+        for point in self.points.iter_mut().filter(|point| !point.pinned) {
+            let velocity = (point.position - point.previous_position) * self.damping;
+            point.previous_position = point.position;
+            point.position += velocity + acceleration;
+        }
+
+        for _ in 0..self.iterations {
+            for constraint in &self.constraints {
+                solve_constraint(&mut self.points, constraint);
+            }
+            for point in self.points.iter_mut().filter(|point| !point.pinned) {
+                collide(&mut point.position);
+            }
+        }
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Vector3f> + '_ {
+        self.points.iter().map(|point| point.position)
+    }
+}
+
+fn solve_constraint(points: &mut [Point], constraint: &DistanceConstraint) {
+    let a = &points[constraint.a];
+    let b = &points[constraint.b];
+    let (a_weight, b_weight) = match (a.pinned, b.pinned) {
+        (true, true) => return,
+        (true, false) => (0.0, 1.0),
+        (false, true) => (1.0, 0.0),
+        (false, false) => (0.5, 0.5),
+    };
+
+    let delta = b.position - a.position;
+    let distance = delta.norm();
+    if distance <= f32::EPSILON {
+        return;
+    }
+    let correction = delta * ((distance - constraint.length) / distance * constraint.stiffness);
+    points[constraint.a].position += correction * a_weight;
+    points[constraint.b].position -= correction * b_weight;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rope_hangs_from_its_pinned_point() {
+        let mut rope = VerletBody::rope(
+            Vector3f::new(0.0, 0.0, 0.0),
+            Vector3f::new(100.0, 0.0, 0.0),
+            10,
+        );
+        for _ in 0..600 {
+            rope.step(1.0 / 60.0);
+        }
+
+        let positions = rope.positions().collect::<Vec<_>>();
+        assert_eq!(positions[0], Vector3f::new(0.0, 0.0, 0.0));
+        // The rope has fallen and stays close to its length
+        let end = positions[10];
+        assert!(end.y > 95.0);
+        assert!(end.norm() < 105.0);
+    }
+
+    #[test]
+    fn collisions_push_points_out_of_obstacles() {
+        let mut rope = VerletBody::rope(
+            Vector3f::new(0.0, 0.0, 0.0),
+            Vector3f::new(2.0, 0.0, 0.0),
+            2,
+        );
+        for _ in 0..120 {
+            rope.step_with_collisions(1.0 / 60.0, |position| position.y = position.y.min(0.5));
+        }
+
+        assert!(rope.positions().all(|position| position.y <= 0.5));
+    }
+}
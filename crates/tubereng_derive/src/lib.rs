@@ -0,0 +1,176 @@
+#![warn(clippy::pedantic)]
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use proc_macro_crate::{crate_name, FoundCrate};
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident};
+
+/// Returns the path to `tubereng_ecs`, either used directly or through the
+/// `tubereng` facade crate
+fn ecs_crate_path() -> proc_macro2::TokenStream {
+    match crate_name("tubereng_ecs") {
+        Ok(FoundCrate::Itself) => quote!(crate),
+        Ok(FoundCrate::Name(name)) => {
+            let name = Ident::new(&name, Span::call_site());
+            quote!(::#name)
+        }
+        Err(_) => {
+            if let Ok(FoundCrate::Name(name)) = crate_name("tubereng") {
+                let name = Ident::new(&name, Span::call_site());
+                quote!(::#name::ecs)
+            } else {
+                quote!(::tubereng::ecs)
+            }
+        }
+    }
+}
+
+#[proc_macro_derive(MemoryUsage)]
+pub fn derive_memory_usage(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let ecs = ecs_crate_path();
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = field_accessors(&data.fields);
+            quote!(0usize #(+ #ecs::memory::MemoryUsage::heap_size(&self.#fields))*)
+        }
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let variant_name = &variant.ident;
+                let bindings = (0..variant.fields.len())
+                    .map(|i| Ident::new(&format!("field_{i}"), Span::call_site()))
+                    .collect::<Vec<_>>();
+                let pattern = match &variant.fields {
+                    Fields::Named(fields) => {
+                        let names = fields.named.iter().map(|f| &f.ident);
+                        quote!({ #(#names: #bindings),* })
+                    }
+                    Fields::Unnamed(_) => quote!(( #(#bindings),* )),
+                    Fields::Unit => quote!(),
+                };
+                quote! {
+                    Self::#variant_name #pattern => 0usize #(+ #ecs::memory::MemoryUsage::heap_size(#bindings))*
+                }
+            });
+            quote! {
+                match self {
+                    #(#arms,)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(name, "MemoryUsage cannot be derived for unions")
+                .to_compile_error()
+                .into();
+        }
+    };
+
+    quote! {
+        impl #impl_generics #ecs::memory::MemoryUsage for #name #type_generics #where_clause {
+            fn heap_size(&self) -> usize {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+/// Implements `EntityDefinition` for a struct, each field being inserted as a
+/// component of the entity. Fields marked with `#[bundle]` are bundles
+/// themselves, their components being inserted instead.
+#[proc_macro_derive(Bundle, attributes(bundle))]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let ecs = ecs_crate_path();
+    let name = &input.ident;
+
+    let Data::Struct(data) = &input.data else {
+        return syn::Error::new_spanned(name, "Bundle can only be derived for structs")
+            .to_compile_error()
+            .into();
+    };
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    let mut reserves = vec![];
+    let writes = data
+        .fields
+        .iter()
+        .zip(field_accessors(&data.fields))
+        .map(|(field, accessor)| {
+            let ty = &field.ty;
+            if field
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("bundle"))
+            {
+                where_clause
+                    .predicates
+                    .push(syn::parse_quote!(#ty: #ecs::EntityDefinition));
+                reserves.push(quote! {
+                    <#ty as #ecs::EntityDefinition>::reserve_component_stores(
+                        component_stores,
+                        capacity,
+                    );
+                });
+                quote! {
+                    #ecs::EntityDefinition::write_into_component_stores(
+                        self.#accessor,
+                        entity_id,
+                        component_stores,
+                    );
+                }
+            } else {
+                where_clause
+                    .predicates
+                    .push(syn::parse_quote!(#ty: 'static + ::std::fmt::Debug));
+                reserves.push(quote! {
+                    #ecs::reserve_component::<#ty>(component_stores, capacity);
+                });
+                quote! {
+                    #ecs::store_component(component_stores, entity_id, self.#accessor);
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics #ecs::EntityDefinition for #name #type_generics #where_clause {
+            fn write_into_component_stores(
+                self,
+                entity_id: #ecs::EntityId,
+                component_stores: &mut #ecs::ComponentStores,
+            ) {
+                #(#writes)*
+            }
+
+            fn reserve_component_stores(
+                component_stores: &mut #ecs::ComponentStores,
+                capacity: usize,
+            ) {
+                #(#reserves)*
+            }
+        }
+    }
+    .into()
+}
+
+fn field_accessors(fields: &Fields) -> Vec<proc_macro2::TokenStream> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            if let Some(ident) = &field.ident {
+                quote!(#ident)
+            } else {
+                let index = syn::Index::from(i);
+                quote!(#index)
+            }
+        })
+        .collect()
+}
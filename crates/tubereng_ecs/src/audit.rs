@@ -0,0 +1,39 @@
+use std::cell::Cell;
+
+/// Record of the last mutable access to a component instance, kept when the
+/// component audit is enabled with [`crate::Ecs::set_component_audit_enabled`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentWrite {
+    /// Name of the system that mutably dereferenced the component
+    pub system_name: &'static str,
+    /// Index of the frame, i.e. of the schedule run, during which the write
+    /// happened
+    pub frame: u64,
+}
+
+thread_local! {
+    static CURRENT_WRITER: Cell<Option<ComponentWrite>> = const { Cell::new(None) };
+}
+
+/// Returns the write that should be recorded for components mutably accessed
+/// right now, if the audit is enabled and a system is running
+pub(crate) fn current_writer() -> Option<ComponentWrite> {
+    CURRENT_WRITER.with(Cell::get)
+}
+
+/// Marks a system as the writer of the components it mutably accesses until
+/// the guard is dropped
+pub(crate) struct WriterGuard;
+
+impl WriterGuard {
+    pub(crate) fn new(writer: ComponentWrite) -> Self {
+        CURRENT_WRITER.with(|current_writer| current_writer.set(Some(writer)));
+        Self
+    }
+}
+
+impl Drop for WriterGuard {
+    fn drop(&mut self) {
+        CURRENT_WRITER.with(|current_writer| current_writer.set(None));
+    }
+}
@@ -0,0 +1,227 @@
+use std::any::TypeId;
+
+use crate::{
+    commands::{Command, CommandQueue},
+    component_store::ComponentStore,
+    relationship::ChildOf,
+    Ecs, EntityId, Storage,
+};
+
+/// Copies the component of an entity to another entity
+pub(crate) type CloneComponentFn = fn(&mut ComponentStore, EntityId, EntityId);
+
+impl Storage {
+    /// Registers a component type so that it is copied by
+    /// [`Storage::clone_entity`]
+    pub fn register_cloneable_component<C: 'static + Clone>(&mut self) {
+        self.component_clone_fns
+            .insert(TypeId::of::<C>(), |component_store, entity_id, clone| {
+                if let Some(component) = component_store.get::<C>(entity_id).cloned() {
+                    component_store.store(clone, component);
+                }
+            });
+    }
+
+    /// Inserts a copy of an entity with its cloneable components and its
+    /// tags, having the same parents as the entity. The components that
+    /// haven't been registered with [`Storage::register_cloneable_component`]
+    /// aren't copied, and the copy of a missing entity has no component.
+    pub fn clone_entity(&mut self, entity_id: EntityId) -> EntityId {
+        let clone = self.clone_components(entity_id);
+        let parents = self
+            .relationships
+            .get::<ChildOf>()
+            .and_then(|child_of| child_of.targets(entity_id))
+            .map(|parents| parents.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for parent in parents {
+            self.insert_relationship::<ChildOf>(clone, parent);
+        }
+        clone
+    }
+
+    /// Clones an entity like [`Storage::clone_entity`] along with its
+    /// descendants, the copies of the descendants being children of the
+    /// copies of their parents
+    pub fn clone_entity_tree(&mut self, entity_id: EntityId) -> EntityId {
+        let clone = self.clone_entity(entity_id);
+        self.clone_descendants(entity_id, clone);
+        clone
+    }
+
+    /// Clones the descendants of `entity_id` as descendants of `clone`
+    fn clone_descendants(&mut self, entity_id: EntityId, clone: EntityId) {
+        let mut children = self
+            .relationships
+            .get::<ChildOf>()
+            .and_then(|child_of| child_of.sources(entity_id))
+            .map(|children| children.iter().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        children.sort_unstable();
+        for child in children {
+            let child_clone = self.clone_components(child);
+            self.insert_relationship::<ChildOf>(child_clone, clone);
+            self.clone_descendants(child, child_clone);
+        }
+    }
+
+    fn clone_components(&mut self, entity_id: EntityId) -> EntityId {
+        let clone = self.allocate_entity();
+        if !self.contains_entity(entity_id) {
+            return clone;
+        }
+
+        for (type_id, component_store) in &mut self.component_stores {
+            if let Some(clone_fn) = self.component_clone_fns.get(type_id) {
+                clone_fn(component_store, entity_id, clone);
+            }
+        }
+        let tags = self.tags(entity_id).collect::<Vec<_>>();
+        for tag in tags {
+            self.add_tag(clone, tag);
+        }
+        clone
+    }
+}
+
+impl Ecs {
+    /// See [`Storage::register_cloneable_component`]
+    pub fn register_cloneable_component<C: 'static + Clone>(&mut self) {
+        self.storage.register_cloneable_component::<C>();
+    }
+
+    /// See [`Storage::clone_entity`]
+    pub fn clone_entity(&mut self, entity_id: EntityId) -> EntityId {
+        self.storage.clone_entity(entity_id)
+    }
+
+    /// See [`Storage::clone_entity_tree`]
+    pub fn clone_entity_tree(&mut self, entity_id: EntityId) -> EntityId {
+        self.storage.clone_entity_tree(entity_id)
+    }
+}
+
+impl CommandQueue {
+    /// See [`Storage::clone_entity`]
+    pub fn clone_entity(&self, entity_id: EntityId) -> EntityId {
+        self.push_command(CloneEntity { entity_id });
+        self.compute_next_entity_id()
+    }
+
+    /// See [`Storage::clone_entity_tree`]. Only the id of the copy of the
+    /// entity is returned, its descendants being copied after the other
+    /// commands of the queue.
+    pub fn clone_entity_tree(&self, entity_id: EntityId) -> EntityId {
+        let clone = self.clone_entity(entity_id);
+        self.push_deferred_command(CloneDescendants { entity_id, clone });
+        clone
+    }
+}
+
+pub struct CloneEntity {
+    entity_id: EntityId,
+}
+
+impl Command for CloneEntity {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.clone_entity(self.entity_id);
+    }
+}
+
+pub struct CloneDescendants {
+    entity_id: EntityId,
+    clone: EntityId,
+}
+
+impl Command for CloneDescendants {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.storage.clone_descendants(self.entity_id, self.clone);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag::Tag;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Health(i32);
+
+    #[derive(Debug, PartialEq)]
+    struct Unique;
+
+    fn clone_ecs() -> Ecs {
+        let mut ecs = Ecs::new();
+        ecs.register_cloneable_component::<Health>();
+        ecs.define_relationship::<ChildOf>();
+        ecs
+    }
+
+    #[test]
+    fn clone_entity() {
+        let mut ecs = clone_ecs();
+        let parent = ecs.insert(());
+        let entity = ecs.insert((Health(3), Unique));
+        let child = ecs.insert((Health(1),));
+        ecs.insert_relationship::<ChildOf>(entity, parent);
+        ecs.insert_relationship::<ChildOf>(child, entity);
+        ecs.add_tag(entity, Tag::new("enemy"));
+
+        let clone = ecs.clone_entity(entity);
+
+        assert_eq!(ecs.entity_count(), 4);
+        assert_eq!(ecs.component::<Health>(clone), Some(&Health(3)));
+        assert!(ecs.component::<Unique>(clone).is_none());
+        assert!(ecs.has_tag(clone, Tag::new("enemy")));
+        let child_of = ecs.relationship::<ChildOf>().unwrap();
+        assert!(child_of.contains(clone, parent));
+        assert!(child_of.sources(clone).is_none());
+    }
+
+    #[test]
+    fn clone_entity_tree() {
+        let mut ecs = clone_ecs();
+        let root = ecs.insert((Health(3),));
+        let child = ecs.insert((Health(2),));
+        let grandchild = ecs.insert((Health(1),));
+        ecs.insert_relationship::<ChildOf>(child, root);
+        ecs.insert_relationship::<ChildOf>(grandchild, child);
+
+        let clone = ecs.clone_entity_tree(root);
+
+        assert_eq!(ecs.entity_count(), 6);
+        let child_of = ecs.relationship::<ChildOf>().unwrap();
+        let child_clone = *child_of.sources(clone).unwrap().iter().next().unwrap();
+        let grandchild_clone = *child_of
+            .sources(child_clone)
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+        assert_eq!(ecs.component::<Health>(clone), Some(&Health(3)));
+        assert_eq!(ecs.component::<Health>(child_clone), Some(&Health(2)));
+        assert_eq!(ecs.component::<Health>(grandchild_clone), Some(&Health(1)));
+        assert_eq!(child_of.sources(root).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn command_queue_clone_entity_tree() {
+        let mut ecs = clone_ecs();
+        let root = ecs.insert((Health(3),));
+        let child = ecs.insert((Health(2),));
+        ecs.insert_relationship::<ChildOf>(child, root);
+        // Synchronizes the entity ids allocated by the command queue
+        ecs.run_systems();
+
+        let clone = ecs.command_queue().clone_entity_tree(root);
+        let other = ecs.command_queue().insert((Unique,));
+        ecs.run_systems();
+
+        assert_eq!((clone, other), (2, 3));
+        assert_eq!(ecs.component::<Health>(clone), Some(&Health(3)));
+        assert_eq!(ecs.component::<Unique>(other), Some(&Unique));
+        let child_of = ecs.relationship::<ChildOf>().unwrap();
+        assert!(child_of.contains(4, clone));
+        assert_eq!(ecs.component::<Health>(4), Some(&Health(2)));
+    }
+}
@@ -7,6 +7,7 @@ use std::{
 
 use crate::{
     system::{self, System},
+    tag::Tag,
     Ecs, EntityDefinition, EntityId,
 };
 
@@ -15,6 +16,7 @@ pub struct CommandQueue {
     next_entity_id: usize,
     deleted_entities: Vec<EntityId>,
     commands: RefCell<Vec<Box<dyn Command>>>,
+    deferred_commands: RefCell<Vec<Box<dyn Command>>>,
 }
 impl CommandQueue {
     #[must_use]
@@ -24,12 +26,15 @@ impl CommandQueue {
             next_entity_id,
             deleted_entities: deleted_entities.to_vec(),
             commands: RefCell::new(vec![]),
+            deferred_commands: RefCell::new(vec![]),
         }
     }
-    fn compute_next_entity_id(&self) -> EntityId {
+    pub(crate) fn compute_next_entity_id(&self) -> EntityId {
         let allocated_entity_count = self.allocated_entity_count.load(atomic::Ordering::Relaxed);
+        // Deleted entity ids are reused last deleted first, see
+        // `Storage::allocate_entity`
         let id = if allocated_entity_count < self.deleted_entities.len() {
-            self.deleted_entities[allocated_entity_count]
+            self.deleted_entities[self.deleted_entities.len() - 1 - allocated_entity_count]
         } else {
             self.next_entity_id + allocated_entity_count - self.deleted_entities.len()
         };
@@ -46,10 +51,32 @@ impl CommandQueue {
         self.compute_next_entity_id()
     }
 
+    /// Inserts entities sharing the same definition with a single command,
+    /// see [`Ecs::insert_batch`]. Returns the ids of the entities in the order
+    /// of the batch.
+    pub fn insert_batch<ED, I>(&self, entity_definitions: I) -> Vec<EntityId>
+    where
+        ED: 'static + EntityDefinition,
+        I: IntoIterator<Item = ED>,
+    {
+        let entity_definitions = entity_definitions.into_iter().collect::<Vec<_>>();
+        let entity_ids = entity_definitions
+            .iter()
+            .map(|_| self.compute_next_entity_id())
+            .collect();
+        self.push_command(InsertBatch {
+            entity_definitions: Some(entity_definitions),
+        });
+        entity_ids
+    }
+
+    /// Adds a component to an entity, replacing the component of the same
+    /// type if present. Ignored if the entity is deleted by then.
     pub fn insert_component<C: 'static>(&self, entity_id: EntityId, component: C) {
         self.push_command(InsertComponent::new(entity_id, component));
     }
 
+    /// Removes a component from an entity, if present
     pub fn remove_component<C: 'static>(&self, entity_id: EntityId) {
         self.push_command(RemoveComponent::<C>::new(entity_id));
     }
@@ -65,10 +92,27 @@ impl CommandQueue {
         self.push_command(InsertResource::new(resource));
     }
 
+    /// Removes a resource, dropping it
+    pub fn remove_resource<R: 'static>(&self) {
+        self.push_command(RemoveResource::<R>::new());
+    }
+
     pub fn insert_relationship<R: 'static>(&self, source: EntityId, target: EntityId) {
         self.push_command(InsertRelationship::<R>::new(source, target));
     }
 
+    pub fn remove_relationship<R: 'static>(&self, source: EntityId, target: EntityId) {
+        self.push_command(RemoveRelationship::<R>::new(source, target));
+    }
+
+    pub fn add_tag(&self, entity_id: EntityId, tag: Tag) {
+        self.push_command(AddTag { entity_id, tag });
+    }
+
+    pub fn remove_tag(&self, entity_id: EntityId, tag: Tag) {
+        self.push_command(RemoveTag { entity_id, tag });
+    }
+
     pub fn register_system<S, F, A>(&self, _stage: &S, system: F)
     where
         S: 'static,
@@ -77,12 +121,23 @@ impl CommandQueue {
         self.push_command(RegisterSystem::<S>::new::<S, _, _>(system));
     }
 
-    fn push_command<C>(&self, command: C)
+    pub(crate) fn push_command<C>(&self, command: C)
     where
         C: 'static + Command,
     {
         self.commands.borrow_mut().push(Box::new(command));
     }
+
+    /// Pushes a command applied after the other commands of the queue. Used
+    /// by the commands inserting a number of entities the queue can't know,
+    /// so that the ids returned by the queue for the other entities stay
+    /// valid.
+    pub fn push_deferred_command<C>(&self, command: C)
+    where
+        C: 'static + Command,
+    {
+        self.deferred_commands.borrow_mut().push(Box::new(command));
+    }
 }
 
 impl IntoIterator for CommandQueue {
@@ -90,7 +145,9 @@ impl IntoIterator for CommandQueue {
     type IntoIter = IntoIter<Box<dyn Command>>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.commands.into_inner().into_iter()
+        let mut commands = self.commands.into_inner();
+        commands.append(&mut self.deferred_commands.into_inner());
+        commands.into_iter()
     }
 }
 
@@ -119,6 +176,19 @@ impl Command for InsertEntity {
     }
 }
 
+pub struct InsertBatch<ED> {
+    entity_definitions: Option<Vec<ED>>,
+}
+
+impl<ED> Command for InsertBatch<ED>
+where
+    ED: 'static + EntityDefinition,
+{
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.insert_batch(self.entity_definitions.take().unwrap());
+    }
+}
+
 pub struct InsertComponent<C> {
     entity_id: EntityId,
     component: Option<C>,
@@ -204,6 +274,43 @@ where
     }
 }
 
+pub struct RemoveResource<R>
+where
+    R: 'static,
+{
+    _marker: PhantomData<R>,
+}
+
+impl<R> RemoveResource<R>
+where
+    R: 'static,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R> Default for RemoveResource<R>
+where
+    R: 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R> Command for RemoveResource<R>
+where
+    R: 'static,
+{
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.remove_resource::<R>();
+    }
+}
+
 pub struct InsertRelationship<R>
 where
     R: 'static,
@@ -233,6 +340,57 @@ where
     }
 }
 
+pub struct RemoveRelationship<R>
+where
+    R: 'static,
+{
+    source: EntityId,
+    target: EntityId,
+    _marker: PhantomData<R>,
+}
+
+impl<R> RemoveRelationship<R> {
+    #[must_use]
+    pub fn new(source: EntityId, target: EntityId) -> Self {
+        Self {
+            source,
+            target,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R> Command for RemoveRelationship<R>
+where
+    R: 'static,
+{
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.remove_relationship::<R>(self.source, self.target);
+    }
+}
+
+pub struct AddTag {
+    entity_id: EntityId,
+    tag: Tag,
+}
+
+impl Command for AddTag {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.add_tag(self.entity_id, self.tag);
+    }
+}
+
+pub struct RemoveTag {
+    entity_id: EntityId,
+    tag: Tag,
+}
+
+impl Command for RemoveTag {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.remove_tag(self.entity_id, self.tag);
+    }
+}
+
 pub struct RegisterSystem<S> {
     system: Option<System>,
     _marker: PhantomData<S>,
@@ -1,17 +1,25 @@
 use std::{
     alloc::Layout,
     cell::{RefCell, UnsafeCell},
+    collections::HashMap,
     ptr::NonNull,
 };
 
-use crate::{bitset::BitSet, EntityId, MAX_ENTITY_COUNT};
+use crate::{
+    audit::{self, ComponentWrite},
+    bitset::BitSet,
+    EntityId, MAX_ENTITY_COUNT,
+};
 
 pub struct ComponentStore {
+    type_name: &'static str,
     component_layout: Layout,
     data: UnsafeCell<NonNull<u8>>,
     cap: usize,
-    entities_bitset: [u8; MAX_ENTITY_COUNT / 8],
-    dirty_bitset: RefCell<[u8; MAX_ENTITY_COUNT / 8]>,
+    entities_bitset: Box<[u8]>,
+    dirty_bitset: RefCell<Box<[u8]>>,
+    added_bitset: Box<[u8]>,
+    last_writes: RefCell<HashMap<EntityId, ComponentWrite>>,
     drop_fn: unsafe fn(*mut u8),
 }
 
@@ -27,17 +35,76 @@ impl ComponentStore {
         };
 
         Self {
+            type_name: "unknown",
             component_layout,
             data: UnsafeCell::new(NonNull::dangling()),
             cap,
-            entities_bitset: [0u8; MAX_ENTITY_COUNT / 8],
-            dirty_bitset: RefCell::new([0u8; MAX_ENTITY_COUNT / 8]),
+            entities_bitset: empty_bitset(),
+            dirty_bitset: RefCell::new(empty_bitset()),
+            added_bitset: empty_bitset(),
+            last_writes: RefCell::new(HashMap::new()),
             drop_fn,
         }
     }
 
+    /// Creates a store for components of type `C`
+    pub fn of<C: 'static>() -> Self {
+        let mut store = Self::new(Layout::new::<C>(), drop_fn_of::<C>);
+        store.type_name = std::any::type_name::<C>();
+        store
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    pub fn component_count(&self) -> usize {
+        self.entities_bitset
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns the number of bytes allocated by the store for the component
+    /// data and its bookkeeping
+    pub fn allocated_bytes(&self) -> usize {
+        let data_bytes = if self.component_layout.size() == 0 {
+            0
+        } else {
+            self.cap * self.component_layout.size()
+        };
+        data_bytes + 3 * MAX_ENTITY_COUNT / 8
+    }
+
+    /// Returns the ids of the entities having a component in this store
+    pub fn entity_ids(&self) -> impl Iterator<Item = EntityId> + '_ {
+        (0..self.cap.min(MAX_ENTITY_COUNT)).filter(|&i| self.entities_bitset.bit(i))
+    }
+
+    /// Unsets the bits of the entities without a component in the `entities`
+    /// bitset
+    pub fn filter_entities(&self, entities: &mut [u8]) {
+        intersect_bitsets(entities, &self.entities_bitset);
+    }
+
+    /// Unsets the bits of the entities without a dirty component in the
+    /// `entities` bitset
+    pub fn filter_dirty_entities(&self, entities: &mut [u8]) {
+        intersect_bitsets(entities, &self.entities_bitset);
+        intersect_bitsets(entities, &self.dirty_bitset.borrow());
+    }
+
+    /// Unsets the bits of the entities without an added component in the
+    /// `entities` bitset
+    pub fn filter_added_entities(&self, entities: &mut [u8]) {
+        intersect_bitsets(entities, &self.entities_bitset);
+        intersect_bitsets(entities, &self.added_bitset);
+    }
+
+    /// Clears the dirty and added flags of the components
     pub fn clear_dirty_bitset(&mut self) {
         self.dirty_bitset.borrow_mut().clear_bits();
+        self.added_bitset.clear_bits();
     }
 
     pub fn set_dirty(&self, entity_id: EntityId) {
@@ -48,8 +115,35 @@ impl ComponentStore {
         self.dirty_bitset.borrow_mut().bit(entity_id)
     }
 
+    /// Returns true if the component has been added to the entity since the
+    /// flags have last been cleared
+    pub fn added(&self, entity_id: EntityId) -> bool {
+        self.added_bitset.bit(entity_id)
+    }
+
+    /// Records the running system as the last writer of the component of the
+    /// entity, if the component audit is enabled
+    pub fn record_write(&self, entity_id: EntityId) {
+        if let Some(write) = audit::current_writer() {
+            self.last_writes.borrow_mut().insert(entity_id, write);
+        }
+    }
+
+    pub fn last_write(&self, entity_id: EntityId) -> Option<ComponentWrite> {
+        self.last_writes.borrow().get(&entity_id).copied()
+    }
+
+    /// Allocates the data of the components of the entities with an id lower
+    /// than `capacity`
+    pub fn reserve(&mut self, capacity: usize) {
+        self.ensure_capacity(capacity.min(MAX_ENTITY_COUNT));
+    }
+
     pub fn store<C>(&mut self, entity_id: EntityId, mut component: C) {
         assert!(entity_id < MAX_ENTITY_COUNT, "The component store is full");
+        if !self.entities_bitset.bit(entity_id) {
+            self.added_bitset.set_bit(entity_id);
+        }
         self.entities_bitset.set_bit(entity_id);
         self.dirty_bitset.borrow_mut().set_bit(entity_id);
         self.ensure_capacity(entity_id + 1);
@@ -65,12 +159,59 @@ impl ComponentStore {
         }
     }
 
+    /// Creates an empty store for the same component type
+    pub fn empty_like(&self) -> Self {
+        let mut store = Self::new(self.component_layout, self.drop_fn);
+        store.type_name = self.type_name;
+        store
+    }
+
+    /// Moves the component of an entity to the entity `destination_id` of
+    /// `destination`, a store of the same component type
+    pub fn move_component(
+        &mut self,
+        entity_id: EntityId,
+        destination: &mut ComponentStore,
+        destination_id: EntityId,
+    ) {
+        assert_eq!(self.component_layout, destination.component_layout);
+        assert!(
+            destination_id < MAX_ENTITY_COUNT,
+            "The component store is full"
+        );
+        if entity_id >= self.cap || !self.entities_bitset.bit(entity_id) {
+            return;
+        }
+
+        self.entities_bitset.unset_bit(entity_id);
+        self.last_writes.borrow_mut().remove(&entity_id);
+        destination.delete(destination_id);
+        destination.added_bitset.set_bit(destination_id);
+        destination.entities_bitset.set_bit(destination_id);
+        destination
+            .dirty_bitset
+            .borrow_mut()
+            .set_bit(destination_id);
+        if self.component_layout.size() > 0 {
+            destination.ensure_capacity(destination_id + 1);
+            // SAFETY:
+            // The component of the entity is valid as its bit was set, and
+            // the capacity of the destination has been ensured. The bit of
+            // the entity being unset, the component won't be dropped by this
+            // store.
+            unsafe {
+                destination.write(destination_id, self.ptr_at(entity_id));
+            }
+        }
+    }
+
     pub fn delete(&mut self, entity_id: EntityId) {
         if entity_id >= self.cap || !self.entities_bitset.bit(entity_id) {
             return;
         }
 
         self.entities_bitset.unset_bit(entity_id);
+        self.last_writes.borrow_mut().remove(&entity_id);
         unsafe {
             (self.drop_fn)(self.ptr_at(entity_id));
         }
@@ -100,6 +241,7 @@ impl ComponentStore {
         unsafe { Some(&*ptr.cast::<C>()) }
     }
 
+    #[allow(clippy::mut_from_ref)]
     pub fn get_mut<C>(&self, entity_id: EntityId) -> Option<&mut C> {
         if entity_id >= MAX_ENTITY_COUNT {
             return None;
@@ -218,6 +360,18 @@ pub unsafe fn drop_fn_of<T>(ptr: *mut u8) {
     ptr.cast::<T>().drop_in_place();
 }
 
+fn empty_bitset() -> Box<[u8]> {
+    vec![0u8; MAX_ENTITY_COUNT / 8].into_boxed_slice()
+}
+
+/// Unsets the bits of `bitset` that aren't set in `other`, the bits beyond the
+/// length of `other` being unset
+fn intersect_bitsets(bitset: &mut [u8], other: &[u8]) {
+    for (i, byte) in bitset.iter_mut().enumerate() {
+        *byte &= other.get(i).copied().unwrap_or(0);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -251,6 +405,19 @@ mod tests {
         assert_eq!(position.y, 12);
     }
 
+    #[test]
+    fn component_store_move_component() {
+        let mut store = ComponentStore::of::<String>();
+        let mut destination = store.empty_like();
+        store.store(3, String::from("moved"));
+
+        store.move_component(3, &mut destination, 7);
+
+        assert!(store.get::<String>(3).is_none());
+        assert_eq!(destination.get::<String>(7).unwrap(), "moved");
+        assert!(destination.added(7));
+    }
+
     #[test]
     fn component_store_get_mut() {
         let mut store = ComponentStore::new(Layout::new::<Position>(), drop_fn_of::<Position>);
@@ -0,0 +1,184 @@
+use std::cell::{Ref, RefMut};
+
+use crate::commands::CommandQueue;
+use crate::system::Argument;
+use crate::Storage;
+
+/// Resource holding the events of type `T`, registered with
+/// [`crate::Ecs::register_event`].
+///
+/// Events are double buffered: the events sent during a frame are readable
+/// during the next frame only, by every [`EventReader`], whatever the order of
+/// the systems. The readable events are cleared at the start of the following
+/// frame, once every consumer had the chance to read them.
+pub struct Events<T> {
+    readable: Vec<T>,
+    sent: Vec<T>,
+}
+
+impl<T> Events<T> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            readable: vec![],
+            sent: vec![],
+        }
+    }
+
+    /// Sends an event, readable during the next frame
+    pub fn send(&mut self, event: T) {
+        self.sent.push(event);
+    }
+
+    /// Returns the events sent during the previous frame
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.readable.iter()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.readable.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.readable.is_empty()
+    }
+
+    /// Makes the events sent during the frame readable, dropping the events
+    /// of the previous frame
+    pub fn update(&mut self) {
+        self.readable.clear();
+        std::mem::swap(&mut self.readable, &mut self.sent);
+    }
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Updates the [`Events`] resource of type `T`, at the start of each frame
+pub(crate) fn update_events<T: 'static>(storage: &Storage) {
+    if let Some(mut events) = storage.resource_mut::<Events<T>>() {
+        events.update();
+    }
+}
+
+/// System argument sending events of type `T`. The event type must have been
+/// registered with [`crate::Ecs::register_event`].
+///
+/// A system cannot have both an `EventWriter` and an [`EventReader`] of the
+/// same event type.
+pub struct EventWriter<'a, T>(RefMut<'a, Events<T>>);
+
+impl<T> EventWriter<'_, T> {
+    pub fn send(&mut self, event: T) {
+        self.0.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        for event in events {
+            self.0.send(event);
+        }
+    }
+}
+
+impl<T: 'static> Argument for EventWriter<'_, T> {
+    type Type<'a> = EventWriter<'a, T>;
+
+    fn provide<'a>(
+        _command_queue: &'a CommandQueue,
+        storage: &'a Storage,
+    ) -> Option<Self::Type<'a>> {
+        Some(EventWriter(storage.resource_mut::<Events<T>>()?))
+    }
+}
+
+/// System argument reading the events of type `T` sent during the previous
+/// frame. The event type must have been registered with
+/// [`crate::Ecs::register_event`].
+pub struct EventReader<'a, T>(Ref<'a, Events<T>>);
+
+impl<T> EventReader<'_, T> {
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.0.iter()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<T: 'static> Argument for EventReader<'_, T> {
+    type Type<'a> = EventReader<'a, T>;
+
+    fn provide<'a>(
+        _command_queue: &'a CommandQueue,
+        storage: &'a Storage,
+    ) -> Option<Self::Type<'a>> {
+        Some(EventReader(storage.resource::<Events<T>>()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{system::stages, system::ResMut, Ecs};
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Collision(u32);
+
+    #[test]
+    fn events_are_readable_during_the_next_frame() {
+        let mut ecs = Ecs::new();
+        ecs.register_event::<Collision>();
+        ecs.insert_resource(Vec::<Collision>::new());
+        ecs.insert_resource(0u32);
+        // The reader runs before the writer, it still sees every event once
+        ecs.register_system(
+            &stages::Update,
+            |reader: EventReader<Collision>, mut read: ResMut<Vec<Collision>>| {
+                read.extend(reader.iter().copied());
+            },
+        );
+        ecs.register_system(
+            &stages::Update,
+            |mut writer: EventWriter<Collision>, mut frame: ResMut<u32>| {
+                if **frame == 0 {
+                    writer.send_batch([Collision(1), Collision(2)]);
+                }
+                **frame += 1;
+            },
+        );
+
+        ecs.run_systems();
+        assert!(ecs.resource::<Vec<Collision>>().unwrap().is_empty());
+        ecs.run_systems();
+        assert_eq!(
+            *ecs.resource::<Vec<Collision>>().unwrap(),
+            [Collision(1), Collision(2)]
+        );
+        ecs.run_systems();
+        assert_eq!(ecs.resource::<Vec<Collision>>().unwrap().len(), 2);
+        assert!(ecs.resource::<Events<Collision>>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn events_sent_outside_systems_are_read_next_frame() {
+        let mut ecs = Ecs::new();
+        ecs.register_event::<Collision>();
+        ecs.send_event(Collision(3));
+        ecs.run_systems();
+        let events = ecs.resource::<Events<Collision>>().unwrap();
+        assert_eq!(events.iter().collect::<Vec<_>>(), [&Collision(3)]);
+    }
+}
@@ -1,36 +1,69 @@
 #![warn(clippy::pedantic)]
 
 use log::trace;
+use memory::{MemoryUsage, ResourceHeapSizeFn};
 use query::ComponentRefMut;
 use relationship::{Relationship, Relationships};
 use std::{
-    alloc::Layout,
     any::{Any, TypeId},
     cell::{Ref, RefCell, RefMut},
     collections::HashMap,
 };
+use tag::{Tag, Tags};
+/// Derives `EntityDefinition` for a struct grouping components, so that it
+/// can be inserted like a tuple. Fields marked with `#[bundle]` are nested
+/// bundles:
+/// ```ignore
+/// #[derive(Debug, Bundle)]
+/// struct EnemyBundle {
+///     #[bundle]
+///     character: CharacterBundle,
+///     enemy: Enemy,
+/// }
+/// ```
+pub use tubereng_derive::Bundle;
 
 use commands::CommandQueue;
-use component_store::{drop_fn_of, ComponentStore};
+use component_store::ComponentStore;
 
+pub mod audit;
 mod bitset;
+pub mod clone;
 pub mod commands;
 mod component_store;
+pub mod event;
+pub mod memory;
+pub mod non_send;
+pub mod plugin;
 pub mod query;
 pub mod relationship;
+pub mod scene;
+pub mod scene_stack;
+pub mod snapshot;
+pub mod state;
 pub mod system;
+pub mod tag;
+pub mod world;
 
 pub type EntityId = usize;
 pub type ComponentStores = HashMap<TypeId, ComponentStore>;
 pub type Resources = HashMap<TypeId, RefCell<Box<dyn Any>>>;
 
-const MAX_ENTITY_COUNT: usize = 1024;
+const MAX_ENTITY_COUNT: usize = 65536;
 pub struct Storage {
     next_entity_id: EntityId,
     deleted_entities: Vec<EntityId>,
     component_stores: ComponentStores,
     relationships: Relationships,
+    tags: Tags,
     resources: Resources,
+    non_send_resources: Resources,
+    component_heap_size_fns: HashMap<TypeId, fn(&ComponentStore) -> usize>,
+    /// Type name, inline size and heap size function of the resources
+    resource_heap_size_fns: HashMap<TypeId, (&'static str, usize, ResourceHeapSizeFn)>,
+    snapshot_registry: snapshot::Registry,
+    scene_components: HashMap<String, scene::SceneComponentFn>,
+    component_clone_fns: HashMap<TypeId, clone::CloneComponentFn>,
 }
 
 impl Default for Storage {
@@ -47,7 +80,14 @@ impl Storage {
             deleted_entities: vec![],
             component_stores: ComponentStores::new(),
             resources: Resources::new(),
+            non_send_resources: Resources::new(),
             relationships: Relationships::new(),
+            tags: Tags::default(),
+            component_heap_size_fns: HashMap::new(),
+            resource_heap_size_fns: HashMap::new(),
+            snapshot_registry: snapshot::Registry::default(),
+            scene_components: HashMap::new(),
+            component_clone_fns: HashMap::new(),
         }
     }
 
@@ -86,12 +126,42 @@ impl Storage {
         entity_id
     }
 
+    /// Inserts entities sharing the same definition, the storage of their
+    /// components being reserved once for the whole batch. Returns the ids of
+    /// the entities in the order of the batch.
+    pub fn insert_batch<ED, I>(&mut self, entity_definitions: I) -> Vec<EntityId>
+    where
+        ED: EntityDefinition,
+        I: IntoIterator<Item = ED>,
+    {
+        let entity_definitions = entity_definitions.into_iter().collect::<Vec<_>>();
+        let entity_ids = entity_definitions
+            .iter()
+            .map(|_| self.allocate_entity())
+            .collect::<Vec<_>>();
+        trace!("Inserting a batch of {} entities", entity_ids.len());
+        if let Some(&max_entity_id) = entity_ids.iter().max() {
+            ED::reserve_component_stores(&mut self.component_stores, max_entity_id + 1);
+        }
+        for (entity_definition, &entity_id) in entity_definitions.into_iter().zip(&entity_ids) {
+            entity_definition.write_into_component_stores(entity_id, &mut self.component_stores);
+        }
+        entity_ids
+    }
+
+    /// Adds a component to an entity, ignored if the entity doesn't exist so
+    /// that the component isn't inherited by an entity reusing its id
     pub fn insert_component<C: 'static>(&mut self, entity_id: EntityId, component: C) {
-        let component_store = self
-            .component_stores
-            .entry(TypeId::of::<C>())
-            .or_insert(ComponentStore::new(Layout::new::<C>(), drop_fn_of::<C>));
-        component_store.store(entity_id, component);
+        if !self.contains_entity(entity_id) {
+            trace!("Ignoring a component inserted on the missing entity {entity_id}");
+            return;
+        }
+        store_component(&mut self.component_stores, entity_id, component);
+    }
+
+    #[must_use]
+    pub fn contains_entity(&self, entity_id: EntityId) -> bool {
+        entity_id < self.next_entity_id && !self.deleted_entities.contains(&entity_id)
     }
 
     pub fn remove_component<C: 'static>(&mut self, entity_id: EntityId) {
@@ -103,9 +173,15 @@ impl Storage {
     }
 
     pub fn delete(&mut self, entity_id: EntityId) {
+        // Deleting an id twice would put it twice in the free list
+        if !self.contains_entity(entity_id) {
+            return;
+        }
         for component_store in self.component_stores.values_mut() {
             component_store.delete(entity_id);
         }
+        self.tags.remove_entity(entity_id);
+        self.relationships.remove_entity(entity_id);
         self.deleted_entities.push(entity_id);
     }
 
@@ -117,6 +193,20 @@ impl Storage {
             .insert(TypeId::of::<R>(), RefCell::new(Box::new(resource)));
     }
 
+    /// Removes a resource, returning it if it was present
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the resource can't be downcasted to its actual type
+    pub fn remove_resource<R: Any>(&mut self) -> Option<R> {
+        let resource = self.resources.remove(&TypeId::of::<R>())?.into_inner();
+        Some(
+            *resource
+                .downcast::<R>()
+                .expect("Couldn't downcast resource"),
+        )
+    }
+
     /// Fetches a resource from the Ecs
     ///
     /// # Panics
@@ -147,6 +237,10 @@ impl Storage {
         self.relationships.insert::<R>(source, target);
     }
 
+    pub fn remove_relationship<R: 'static>(&mut self, source: EntityId, target: EntityId) {
+        self.relationships.remove::<R>(source, target);
+    }
+
     pub fn define_relationship<R: 'static>(&mut self) {
         self.relationships.define::<R>();
     }
@@ -156,6 +250,28 @@ impl Storage {
         self.relationships.get::<R>()
     }
 
+    pub fn add_tag(&mut self, entity_id: EntityId, tag: Tag) {
+        self.tags.add(entity_id, tag);
+    }
+
+    pub fn remove_tag(&mut self, entity_id: EntityId, tag: Tag) {
+        self.tags.remove(entity_id, tag);
+    }
+
+    #[must_use]
+    pub fn has_tag(&self, entity_id: EntityId, tag: Tag) -> bool {
+        self.tags.has(entity_id, tag)
+    }
+
+    /// Returns the entities tagged with `tag`, in no particular order
+    pub fn tagged(&self, tag: Tag) -> impl Iterator<Item = EntityId> + '_ {
+        self.tags.entities(tag)
+    }
+
+    pub fn tags(&self, entity_id: EntityId) -> impl Iterator<Item = Tag> + '_ {
+        self.tags.tags(entity_id)
+    }
+
     #[must_use]
     pub fn component<C>(&self, entity_id: EntityId) -> Option<&C>
     where
@@ -190,17 +306,114 @@ impl Storage {
     }
 
     #[must_use]
-    pub fn query<QD>(&self) -> query::State<QD>
+    pub fn query<QD>(&self) -> query::State<'_, QD>
     where
         QD: query::Definition,
     {
         query::State::new(
             &self.component_stores,
             &self.deleted_entities,
-            self.next_entity_id - 1,
+            self.next_entity_id,
         )
     }
 
+    /// Returns the system that last mutably accessed the component of the
+    /// entity, and when. Only recorded while the component audit is enabled.
+    #[must_use]
+    pub fn last_component_write<C: 'static>(
+        &self,
+        entity_id: EntityId,
+    ) -> Option<audit::ComponentWrite> {
+        self.component_stores
+            .get(&TypeId::of::<C>())?
+            .last_write(entity_id)
+    }
+
+    /// Returns the last recorded write of every component of the entity,
+    /// along with the type name of the component
+    #[must_use]
+    pub fn component_writes(
+        &self,
+        entity_id: EntityId,
+    ) -> Vec<(&'static str, audit::ComponentWrite)> {
+        let mut writes = self
+            .component_stores
+            .values()
+            .filter_map(|store| Some((store.type_name(), store.last_write(entity_id)?)))
+            .collect::<Vec<_>>();
+        writes.sort_by_key(|(type_name, _)| *type_name);
+        writes
+    }
+
+    /// Registers a component type so that the heap memory owned by its
+    /// instances is included in [`Storage::memory_usage`]
+    pub fn register_component_memory_usage<C: MemoryUsage + 'static>(&mut self) {
+        self.component_heap_size_fns
+            .insert(TypeId::of::<C>(), |component_store| {
+                component_store
+                    .entity_ids()
+                    .filter_map(|entity_id| component_store.get::<C>(entity_id))
+                    .map(MemoryUsage::heap_size)
+                    .sum()
+            });
+    }
+
+    /// Registers a resource type so that it is included in
+    /// [`Storage::memory_usage`]
+    pub fn register_resource_memory_usage<R: MemoryUsage + 'static>(&mut self) {
+        self.resource_heap_size_fns.insert(
+            TypeId::of::<R>(),
+            (
+                std::any::type_name::<R>(),
+                std::mem::size_of::<R>(),
+                memory::resource_heap_size_of::<R>,
+            ),
+        );
+    }
+
+    /// Reports the approximate memory used by every component storage and by
+    /// the registered resources. The heap size of the resources mutably
+    /// borrowed at the time, e.g. by the calling system, is unknown.
+    #[must_use]
+    pub fn memory_usage(&self) -> memory::Report {
+        let mut components = self
+            .component_stores
+            .iter()
+            .map(|(type_id, store)| memory::ComponentStorageUsage {
+                type_name: store.type_name(),
+                component_count: store.component_count(),
+                storage_bytes: store.allocated_bytes(),
+                heap_bytes: self
+                    .component_heap_size_fns
+                    .get(type_id)
+                    .map(|heap_size_fn| heap_size_fn(store)),
+            })
+            .collect::<Vec<_>>();
+        components.sort_by_key(|c| std::cmp::Reverse(c.storage_bytes + c.heap_bytes.unwrap_or(0)));
+
+        let mut resources = self
+            .resource_heap_size_fns
+            .iter()
+            .filter_map(|(type_id, (type_name, inline_bytes, heap_size_fn))| {
+                let resource = self.resources.get(type_id)?;
+                Some(memory::ResourceUsage {
+                    type_name,
+                    inline_bytes: *inline_bytes,
+                    heap_bytes: resource
+                        .try_borrow()
+                        .ok()
+                        .map(|resource| heap_size_fn(&**resource)),
+                })
+            })
+            .collect::<Vec<_>>();
+        resources.sort_by_key(|r| std::cmp::Reverse(r.inline_bytes + r.heap_bytes.unwrap_or(0)));
+
+        memory::Report {
+            components,
+            resources,
+        }
+    }
+
     fn allocate_entity(&mut self) -> EntityId {
         if let Some(entity_id) = self.deleted_entities.pop() {
             return entity_id;
@@ -216,16 +429,48 @@ pub struct Ecs {
     storage: Storage,
     command_queue: CommandQueue,
     system_schedule: system::Schedule,
+    /// Update the registered [`event::Events`] resources at the start of each
+    /// frame
+    event_updaters: Vec<fn(&Storage)>,
+    system_error_handler: system::ErrorHandler,
 }
 
 impl Ecs {
     #[must_use]
     pub fn new() -> Self {
-        Ecs {
+        let mut ecs = Ecs {
             storage: Storage::new(),
             command_queue: CommandQueue::new(0, &[]),
             system_schedule: system::Schedule::new(),
+            event_updaters: vec![],
+            system_error_handler: system::log_error,
+        };
+        ecs.register_event::<system::SystemPanicked>();
+        ecs
+    }
+
+    /// Registers the events of type `T`, sent with [`event::EventWriter`] and
+    /// read with [`event::EventReader`]. Registering an event type twice has
+    /// no effect.
+    pub fn register_event<T: 'static>(&mut self) {
+        if self.storage.resource::<event::Events<T>>().is_some() {
+            return;
         }
+        self.storage.insert_resource(event::Events::<T>::new());
+        self.event_updaters.push(event::update_events::<T>);
+    }
+
+    /// Sends an event from outside of the systems, readable during the next
+    /// frame
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the event type hasn't been registered
+    pub fn send_event<T: 'static>(&mut self, event: T) {
+        self.storage
+            .resource_mut::<event::Events<T>>()
+            .expect("The event type should be registered")
+            .send(event);
     }
 
     #[must_use]
@@ -233,6 +478,12 @@ impl Ecs {
         self.storage.entity_count()
     }
 
+    /// Returns `true` if the entity exists and hasn't been deleted
+    #[must_use]
+    pub fn contains_entity(&self, entity_id: EntityId) -> bool {
+        self.storage.contains_entity(entity_id)
+    }
+
     /// Inserts a new entity with its components into the Ecs
     pub fn insert<ED>(&mut self, entity_definition: ED) -> EntityId
     where
@@ -241,6 +492,17 @@ impl Ecs {
         self.storage.insert(entity_definition)
     }
 
+    /// Inserts entities sharing the same definition, see [`Storage::insert_batch`]
+    pub fn insert_batch<ED, I>(&mut self, entity_definitions: I) -> Vec<EntityId>
+    where
+        ED: EntityDefinition,
+        I: IntoIterator<Item = ED>,
+    {
+        self.storage.insert_batch(entity_definitions)
+    }
+
+    /// Adds a component to an entity, replacing the component of the same
+    /// type if present. Ignored if the entity doesn't exist.
     pub fn insert_component<C: 'static>(&mut self, entity_id: EntityId, component: C) {
         self.storage.insert_component(entity_id, component);
     }
@@ -249,7 +511,8 @@ impl Ecs {
         self.storage.remove_component::<C>(entity_id);
     }
 
-    /// Deletes the entity with the given id
+    /// Deletes the entity with the given id. Ignored if the entity doesn't
+    /// exist.
     pub fn delete(&mut self, entity_id: EntityId) {
         self.storage.delete(entity_id);
     }
@@ -262,10 +525,19 @@ impl Ecs {
         self.storage.insert_resource(resource);
     }
 
+    /// Removes a resource from the Ecs, returning it if it was present
+    pub fn remove_resource<R: Any>(&mut self) -> Option<R> {
+        self.storage.remove_resource::<R>()
+    }
+
     pub fn insert_relationship<R: 'static>(&mut self, source: EntityId, target: EntityId) {
         self.storage.insert_relationship::<R>(source, target);
     }
 
+    pub fn remove_relationship<R: 'static>(&mut self, source: EntityId, target: EntityId) {
+        self.storage.remove_relationship::<R>(source, target);
+    }
+
     pub fn define_relationship<R: 'static>(&mut self) {
         self.storage.define_relationship::<R>();
     }
@@ -274,6 +546,38 @@ impl Ecs {
         self.storage.relationship::<R>()
     }
 
+    pub fn add_tag(&mut self, entity_id: EntityId, tag: Tag) {
+        self.storage.add_tag(entity_id, tag);
+    }
+
+    pub fn remove_tag(&mut self, entity_id: EntityId, tag: Tag) {
+        self.storage.remove_tag(entity_id, tag);
+    }
+
+    #[must_use]
+    pub fn has_tag(&self, entity_id: EntityId, tag: Tag) -> bool {
+        self.storage.has_tag(entity_id, tag)
+    }
+
+    pub fn tagged(&self, tag: Tag) -> impl Iterator<Item = EntityId> + '_ {
+        self.storage.tagged(tag)
+    }
+
+    pub fn register_component_memory_usage<C: MemoryUsage + 'static>(&mut self) {
+        self.storage.register_component_memory_usage::<C>();
+    }
+
+    pub fn register_resource_memory_usage<R: MemoryUsage + 'static>(&mut self) {
+        self.storage.register_resource_memory_usage::<R>();
+    }
+
+    /// Reports the approximate memory used by the component storages and the
+    /// registered resources
+    #[must_use]
+    pub fn memory_usage(&self) -> memory::Report {
+        self.storage.memory_usage()
+    }
+
     pub fn command_queue(&self) -> &CommandQueue {
         &self.command_queue
     }
@@ -314,7 +618,7 @@ impl Ecs {
         self.storage.component_mut(entity_id)
     }
 
-    pub fn query<QD>(&mut self) -> query::State<QD>
+    pub fn query<QD>(&mut self) -> query::State<'_, QD>
     where
         QD: query::Definition,
     {
@@ -322,7 +626,7 @@ impl Ecs {
     }
 
     pub fn run_single_run_system(&mut self, system: &system::System) {
-        system.run(&mut self.storage, &mut self.command_queue);
+        system.run(self);
         self.process_command_queue();
     }
 
@@ -331,11 +635,79 @@ impl Ecs {
     }
 
     pub fn run_systems(&mut self) {
-        self.system_schedule
-            .run_systems(&mut self.storage, &mut self.command_queue);
+        for update_events in &self.event_updaters {
+            update_events(&self.storage);
+        }
+        // The schedule is moved out while it runs so that exclusive systems
+        // can borrow the whole Ecs. The settings they change apply from the
+        // next run.
+        let stand_in = self.system_schedule.stand_in();
+        let mut system_schedule = std::mem::replace(&mut self.system_schedule, stand_in);
+        system_schedule.run_systems(self);
+        let registered_while_running =
+            std::mem::replace(&mut self.system_schedule, system_schedule);
+        self.system_schedule.append(registered_while_running);
         self.process_command_queue();
     }
 
+    /// Enables the component audit: the system and frame of the last mutable
+    /// access of each component instance are recorded, see
+    /// [`Ecs::last_component_write`]. Meant for debugging as it slows down
+    /// mutable component accesses.
+    pub fn set_component_audit_enabled(&mut self, enabled: bool) {
+        self.system_schedule.set_component_audit_enabled(enabled);
+    }
+
+    #[must_use]
+    pub fn last_component_write<C: 'static>(
+        &self,
+        entity_id: EntityId,
+    ) -> Option<audit::ComponentWrite> {
+        self.storage.last_component_write::<C>(entity_id)
+    }
+
+    #[must_use]
+    pub fn component_writes(
+        &self,
+        entity_id: EntityId,
+    ) -> Vec<(&'static str, audit::ComponentWrite)> {
+        self.storage.component_writes(entity_id)
+    }
+
+    /// Enables measuring the time spent running each system, see
+    /// [`Ecs::system_timings`]
+    pub fn set_system_timing_enabled(&mut self, enabled: bool) {
+        self.system_schedule.set_system_timing_enabled(enabled);
+    }
+
+    /// Returns the time spent running each system during the last
+    /// [`Ecs::run_systems`], empty unless the system timing is enabled
+    #[must_use]
+    pub fn system_timings(&self) -> &[system::SystemTiming] {
+        self.system_schedule.system_timings()
+    }
+
+    /// Sets what happens when a system of the schedule panics
+    pub fn set_system_panic_policy(&mut self, panic_policy: system::PanicPolicy) {
+        self.system_schedule.set_panic_policy(panic_policy);
+    }
+
+    /// Sets how the errors returned by the fallible systems are handled, e.g.
+    /// [`system::log_error`], [`system::ignore_error`] or
+    /// [`system::panic_on_error`]
+    pub fn set_system_error_handler(&mut self, error_handler: system::ErrorHandler) {
+        self.system_error_handler = error_handler;
+    }
+
+    /// Makes a stage run as long as a condition holds, e.g. to run
+    /// [`system::stages::FixedUpdate`] once per elapsed fixed time step
+    pub fn repeat_stage_while<S>(&mut self, _stage: &S, condition: system::StageCondition)
+    where
+        S: 'static,
+    {
+        self.system_schedule.repeat_stage_while::<S>(condition);
+    }
+
     pub fn register_system<S, F, A>(&mut self, _stage: &S, system: F)
     where
         S: 'static,
@@ -368,12 +740,43 @@ impl Default for Ecs {
     }
 }
 
+/// Stores a component of an entity, creating the store of its type if
+/// required
+pub fn store_component<C: 'static>(
+    component_stores: &mut ComponentStores,
+    entity_id: EntityId,
+    component: C,
+) {
+    component_stores
+        .entry(TypeId::of::<C>())
+        .or_insert_with(ComponentStore::of::<C>)
+        .store(entity_id, component);
+}
+
+/// Reserves the storage of the components of type `C` for the entities with
+/// an id lower than `capacity`, creating the store of the type if required
+pub fn reserve_component<C: 'static>(component_stores: &mut ComponentStores, capacity: usize) {
+    component_stores
+        .entry(TypeId::of::<C>())
+        .or_insert_with(ComponentStore::of::<C>)
+        .reserve(capacity);
+}
+
 pub trait EntityDefinition: BoxedEntityDefinition + std::fmt::Debug {
     fn write_into_component_stores(
         self,
         entity_id: EntityId,
         component_stores: &mut ComponentStores,
     );
+
+    /// Reserves the storage of the components of the definition for the
+    /// entities with an id lower than `capacity`, so that inserting a batch
+    /// of entities doesn't grow the stores once per entity
+    fn reserve_component_stores(_component_stores: &mut ComponentStores, _capacity: usize)
+    where
+        Self: Sized,
+    {
+    }
 }
 
 pub trait BoxedEntityDefinition {
@@ -411,6 +814,19 @@ impl EntityDefinition for Box<dyn EntityDefinition> {
     }
 }
 
+/// Components only known at runtime, e.g. deserialized from a scene
+impl EntityDefinition for Vec<Box<dyn EntityDefinition>> {
+    fn write_into_component_stores(
+        self,
+        entity_id: EntityId,
+        component_stores: &mut ComponentStores,
+    ) {
+        for entity_definition in self {
+            entity_definition.write_into_component_stores(entity_id, component_stores);
+        }
+    }
+}
+
 impl EntityDefinition for () {
     fn write_into_component_stores(
         self,
@@ -432,14 +848,13 @@ macro_rules! impl_entity_definition_for_tuple {
                 entity_id: EntityId,
                 component_stores: &mut ComponentStores,
             ) {
-                component_stores
-                    .entry(TypeId::of::<$head>())
-                    .or_insert_with(|| ComponentStore::new(Layout::new::<$head>(), drop_fn_of::<$head>))
-                    .store(entity_id, self.$head_i);
-                $(component_stores
-                    .entry(TypeId::of::<$tail>())
-                    .or_insert_with(|| ComponentStore::new(Layout::new::<$tail>(), drop_fn_of::<$tail>))
-                    .store(entity_id, self.$tail_i);)*
+                store_component(component_stores, entity_id, self.$head_i);
+                $(store_component(component_stores, entity_id, self.$tail_i);)*
+            }
+
+            fn reserve_component_stores(component_stores: &mut ComponentStores, capacity: usize) {
+                reserve_component::<$head>(component_stores, capacity);
+                $(reserve_component::<$tail>(component_stores, capacity);)*
             }
         }
     };
@@ -487,6 +902,155 @@ mod tests {
         assert_eq!(ecs.entity_count(), 3);
     }
 
+    #[derive(Debug, Bundle)]
+    struct CharacterBundle {
+        health: Health,
+        position: Position,
+    }
+
+    #[derive(Debug, Bundle)]
+    struct EnemyBundle {
+        #[bundle]
+        character: CharacterBundle,
+        enemy: Enemy,
+    }
+
+    #[test]
+    fn ecs_insert_bundle() {
+        let mut ecs = Ecs::new();
+        let enemy = ecs.insert(EnemyBundle {
+            character: CharacterBundle {
+                health: Health(5),
+                position: Position { x: 5, y: 9 },
+            },
+            enemy: Enemy,
+        });
+
+        assert_eq!(ecs.component::<Health>(enemy), Some(&Health(5)));
+        assert_eq!(
+            ecs.component::<Position>(enemy),
+            Some(&Position { x: 5, y: 9 })
+        );
+        assert!(ecs.component::<Enemy>(enemy).is_some());
+        assert!(ecs.component::<CharacterBundle>(enemy).is_none());
+    }
+
+    #[test]
+    fn ecs_insert_batch() {
+        let mut ecs = Ecs::new();
+        let deleted = ecs.insert((Player,));
+        ecs.delete(deleted);
+
+        let enemies = ecs.insert_batch((0..3).map(|i| EnemyBundle {
+            character: CharacterBundle {
+                health: Health(i),
+                position: Position { x: i, y: 0 },
+            },
+            enemy: Enemy,
+        }));
+
+        assert_eq!(enemies, [deleted, 1, 2]);
+        assert_eq!(ecs.entity_count(), 3);
+        for (i, &enemy) in (0..).zip(&enemies) {
+            assert_eq!(ecs.component::<Health>(enemy), Some(&Health(i)));
+            assert_eq!(
+                ecs.component::<Position>(enemy),
+                Some(&Position { x: i, y: 0 })
+            );
+        }
+    }
+
+    #[test]
+    fn ecs_command_queue_insert_batch() {
+        let mut ecs = Ecs::new();
+        let deleted = ecs.insert((Player,));
+        ecs.insert((Player,));
+        ecs.delete(deleted);
+        // Synchronizes the entity ids allocated by the command queue
+        ecs.run_systems();
+
+        let tiles = ecs
+            .command_queue()
+            .insert_batch((0..4).map(|i| (Position { x: i, y: i },)));
+        let player = ecs.command_queue().insert((Player,));
+        ecs.run_systems();
+
+        assert_eq!(tiles, [deleted, 2, 3, 4]);
+        assert_eq!(player, 5);
+        for (i, &tile) in (0..).zip(&tiles) {
+            assert_eq!(
+                ecs.component::<Position>(tile),
+                Some(&Position { x: i, y: i })
+            );
+        }
+        assert!(ecs.component::<Player>(player).is_some());
+    }
+
+    #[test]
+    fn ecs_command_queue_insert_and_remove_component() {
+        let mut ecs = Ecs::new();
+        let player = ecs.insert((Player, Health(10)));
+
+        ecs.command_queue()
+            .insert_component(player, Position { x: 1, y: 2 });
+        ecs.command_queue().remove_component::<Health>(player);
+        ecs.run_systems();
+
+        assert_eq!(
+            ecs.component::<Position>(player),
+            Some(&Position { x: 1, y: 2 })
+        );
+        assert!(ecs.component::<Health>(player).is_none());
+        assert!(ecs.component::<Player>(player).is_some());
+    }
+
+    #[test]
+    fn ecs_command_queue_insert_component_on_reused_ids() {
+        let mut ecs = Ecs::new();
+        let entities = [(); 3].map(|()| ecs.insert((Enemy,)));
+        ecs.delete(entities[0]);
+        ecs.delete(entities[2]);
+        ecs.run_systems();
+
+        let queue = ecs.command_queue();
+        let first = queue.insert((Player,));
+        queue.insert_component(first, Health(1));
+        let second = queue.insert((Player,));
+        queue.insert_component(second, Health(2));
+        ecs.run_systems();
+
+        assert_eq!(ecs.component::<Health>(first), Some(&Health(1)));
+        assert_eq!(ecs.component::<Health>(second), Some(&Health(2)));
+    }
+
+    #[test]
+    fn ecs_insert_component_on_deleted_entity() {
+        let mut ecs = Ecs::new();
+        let enemy = ecs.insert((Enemy,));
+        ecs.delete(enemy);
+        ecs.insert_component(enemy, Health(5));
+
+        let player = ecs.insert((Player,));
+
+        assert_eq!(player, enemy);
+        assert!(ecs.component::<Health>(player).is_none());
+    }
+
+    #[test]
+    fn ecs_delete_missing_entity() {
+        let mut ecs = Ecs::new();
+        let player = ecs.insert((Player,));
+        ecs.delete(12);
+        assert_eq!(ecs.query::<&Player>().iter().count(), 1);
+
+        ecs.delete(player);
+        ecs.delete(player);
+        let first = ecs.insert((Enemy,));
+        let second = ecs.insert((Enemy,));
+        assert_ne!(first, second);
+        assert_eq!(ecs.query::<&Enemy>().iter().count(), 2);
+    }
+
     #[test]
     fn ecs_component() {
         let mut ecs = Ecs::new();
@@ -590,6 +1154,26 @@ mod tests {
         assert_eq!(&*r, &SomeResource(10));
     }
 
+    #[test]
+    fn ecs_remove_resource() {
+        #[derive(Debug, PartialEq)]
+        struct SomeResource(i32);
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(SomeResource(23));
+
+        assert_eq!(
+            ecs.remove_resource::<SomeResource>(),
+            Some(SomeResource(23))
+        );
+        assert!(ecs.resource::<SomeResource>().is_none());
+        assert_eq!(ecs.remove_resource::<SomeResource>(), None);
+
+        ecs.insert_resource(SomeResource(5));
+        ecs.command_queue().remove_resource::<SomeResource>();
+        ecs.run_systems();
+        assert!(ecs.resource::<SomeResource>().is_none());
+    }
+
     #[test]
     fn ecs_insert_relationship() {
         struct ChildOf;
@@ -605,6 +1189,150 @@ mod tests {
             .contains(&entity_b));
     }
 
+    #[test]
+    fn ecs_user_defined_relationship() {
+        struct Likes;
+        let mut ecs = Ecs::new();
+        let alice = ecs.insert((Player,));
+        let bob = ecs.insert((Player,));
+        let carol = ecs.insert((Player,));
+        ecs.insert_relationship::<Likes>(alice, bob);
+        ecs.insert_relationship::<Likes>(alice, carol);
+        ecs.insert_relationship::<Likes>(carol, bob);
+
+        ecs.command_queue()
+            .remove_relationship::<Likes>(alice, carol);
+        ecs.run_systems();
+        ecs.delete(carol);
+
+        let likes = ecs.relationship::<Likes>().unwrap();
+        assert_eq!(likes.edges().collect::<Vec<_>>(), [(alice, bob)]);
+        assert_eq!(likes.sources(bob).unwrap().len(), 1);
+        assert!(likes.targets(carol).is_none());
+    }
+
+    #[test]
+    fn ecs_memory_usage() {
+        #[derive(Debug, memory::MemoryUsage)]
+        struct Name(String);
+        #[derive(memory::MemoryUsage)]
+        struct Names(Vec<u64>);
+
+        let mut ecs = Ecs::new();
+        ecs.register_component_memory_usage::<Name>();
+        ecs.register_resource_memory_usage::<Names>();
+        ecs.insert((Name(String::with_capacity(10)), Health(3)));
+        ecs.insert((Name(String::with_capacity(6)),));
+        ecs.insert_resource(Names(Vec::with_capacity(2)));
+
+        let report = ecs.memory_usage();
+        let names = report
+            .components
+            .iter()
+            .find(|c| c.type_name.ends_with("Name"))
+            .unwrap();
+        assert_eq!(names.component_count, 2);
+        assert_eq!(names.heap_bytes, Some(16));
+        let health = report
+            .components
+            .iter()
+            .find(|c| c.type_name.ends_with("Health"))
+            .unwrap();
+        assert_eq!(health.heap_bytes, None);
+        assert_eq!(report.resources[0].heap_bytes, Some(16));
+
+        // The resources borrowed while reporting are reported without their
+        // heap size
+        let names = ecs.resource_mut::<Names>().unwrap();
+        let report = ecs.memory_usage();
+        assert_eq!(report.resources[0].heap_bytes, None);
+        assert_eq!(
+            report.resources[0].inline_bytes,
+            std::mem::size_of::<Names>()
+        );
+        std::mem::drop(names);
+    }
+
+    #[test]
+    fn ecs_component_audit() {
+        fn read_health(mut query: system::Q<&Health>) {
+            for _health in query.iter() {}
+        }
+        fn damage(mut query: system::Q<&mut Health>) {
+            for mut health in query.iter() {
+                health.0 -= 1;
+            }
+        }
+
+        let mut ecs = Ecs::new();
+        let entity = ecs.insert((Health(10),));
+        ecs.register_system(&system::stages::Update, damage);
+        ecs.register_system(&system::stages::Update, read_health);
+
+        ecs.run_systems();
+        assert_eq!(ecs.last_component_write::<Health>(entity), None);
+
+        ecs.set_component_audit_enabled(true);
+        ecs.run_systems();
+        ecs.run_systems();
+        let write = ecs.last_component_write::<Health>(entity).unwrap();
+        assert!(write.system_name.ends_with("damage"));
+        assert_eq!(write.frame, 2);
+        assert_eq!(ecs.component_writes(entity).len(), 1);
+
+        ecs.component_mut::<Health>(entity).unwrap().0 = 3;
+        assert_eq!(ecs.last_component_write::<Health>(entity), Some(write));
+    }
+
+    #[test]
+    fn ecs_system_timings() {
+        fn slow_system() {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.register_system(&system::stages::Update, slow_system);
+        ecs.run_systems();
+        assert!(ecs.system_timings().is_empty());
+
+        ecs.set_system_timing_enabled(true);
+        ecs.run_systems();
+
+        let timings = ecs.system_timings();
+        assert_eq!(timings.len(), 1);
+        assert!(timings[0].system_name.ends_with("slow_system"));
+        assert!(timings[0].duration >= std::time::Duration::from_millis(2));
+    }
+
+    #[test]
+    fn ecs_tags() {
+        const SQUAD_A: Tag = Tag::new("squad_a");
+        fn count_squad(storage: &Storage) {
+            let total_health = storage
+                .tagged(SQUAD_A)
+                .filter_map(|id| storage.component::<Health>(id))
+                .map(|health| health.0)
+                .sum::<i32>();
+            assert_eq!(total_health, 30);
+        }
+
+        let mut ecs = Ecs::new();
+        let a = ecs.insert((Health(10),));
+        let b = ecs.insert((Health(20),));
+        let c = ecs.insert((Health(40),));
+        ecs.add_tag(a, SQUAD_A);
+        ecs.command_queue().add_tag(b, Tag::intern("squad_a"));
+        ecs.run_systems();
+        assert!(ecs.has_tag(b, SQUAD_A));
+        assert!(!ecs.has_tag(c, SQUAD_A));
+
+        ecs.register_system(&system::stages::Update, count_squad);
+        ecs.run_systems();
+
+        ecs.delete(a);
+        assert_eq!(ecs.tagged(SQUAD_A).collect::<Vec<_>>(), [b]);
+    }
+
     #[test]
     fn storage_clear_dirty_flags() {
         let mut storage = Storage::new();
@@ -0,0 +1,196 @@
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+};
+
+pub use tubereng_derive::MemoryUsage;
+
+/// Approximation of the heap memory owned by a value, used to report the
+/// memory usage of component storages and resources.
+pub trait MemoryUsage {
+    /// Returns the approximate number of bytes allocated on the heap by this
+    /// value, not counting its own inline size
+    fn heap_size(&self) -> usize;
+}
+
+macro_rules! impl_memory_usage_without_heap {
+    ($($ty:ty),*) => {
+        $(impl MemoryUsage for $ty {
+            fn heap_size(&self) -> usize {
+                0
+            }
+        })*
+    };
+}
+
+impl_memory_usage_without_heap!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    &'static str
+);
+
+impl<T: MemoryUsage, const N: usize> MemoryUsage for [T; N] {
+    fn heap_size(&self) -> usize {
+        self.iter().map(MemoryUsage::heap_size).sum()
+    }
+}
+
+impl MemoryUsage for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: MemoryUsage> MemoryUsage for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * std::mem::size_of::<T>()
+            + self.iter().map(MemoryUsage::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: MemoryUsage> MemoryUsage for Box<T> {
+    fn heap_size(&self) -> usize {
+        std::mem::size_of::<T>() + (**self).heap_size()
+    }
+}
+
+impl<T: MemoryUsage> MemoryUsage for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, MemoryUsage::heap_size)
+    }
+}
+
+impl<K: MemoryUsage, V: MemoryUsage, S> MemoryUsage for HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * (std::mem::size_of::<K>() + std::mem::size_of::<V>())
+            + self
+                .iter()
+                .map(|(k, v)| k.heap_size() + v.heap_size())
+                .sum::<usize>()
+    }
+}
+
+impl<T: MemoryUsage, S> MemoryUsage for HashSet<T, S> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * std::mem::size_of::<T>()
+            + self.iter().map(MemoryUsage::heap_size).sum::<usize>()
+    }
+}
+
+macro_rules! impl_memory_usage_for_tuples {
+    ($head:ident: $head_i:tt, $($tail:ident: $tail_i:tt,)*) => {
+        impl<$head: MemoryUsage, $($tail: MemoryUsage,)*> MemoryUsage for ($head, $($tail,)*) {
+            fn heap_size(&self) -> usize {
+                self.$head_i.heap_size() $(+ self.$tail_i.heap_size())*
+            }
+        }
+    };
+}
+
+impl_memory_usage_for_tuples!(A: 0,);
+impl_memory_usage_for_tuples!(A: 0, B: 1,);
+impl_memory_usage_for_tuples!(A: 0, B: 1, C: 2,);
+impl_memory_usage_for_tuples!(A: 0, B: 1, C: 2, D: 3,);
+
+/// Memory used by the storage of a component type
+#[derive(Debug, Clone)]
+pub struct ComponentStorageUsage {
+    pub type_name: &'static str,
+    pub component_count: usize,
+    /// Bytes allocated by the storage itself for the component data
+    pub storage_bytes: usize,
+    /// Bytes allocated on the heap by the components, only known for
+    /// components registered with `Ecs::register_component_memory_usage`
+    pub heap_bytes: Option<usize>,
+}
+
+/// Memory used by a resource registered with `Ecs::register_resource_memory_usage`
+#[derive(Debug, Clone)]
+pub struct ResourceUsage {
+    pub type_name: &'static str,
+    pub inline_bytes: usize,
+    /// Bytes allocated on the heap by the resource, unknown if the resource
+    /// was mutably borrowed when the report was made
+    pub heap_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub components: Vec<ComponentStorageUsage>,
+    pub resources: Vec<ResourceUsage>,
+}
+
+impl Report {
+    #[must_use]
+    pub fn total_bytes(&self) -> usize {
+        self.components
+            .iter()
+            .map(|c| c.storage_bytes + c.heap_bytes.unwrap_or(0))
+            .sum::<usize>()
+            + self
+                .resources
+                .iter()
+                .map(|r| r.inline_bytes + r.heap_bytes.unwrap_or(0))
+                .sum::<usize>()
+    }
+}
+
+pub(crate) type ResourceHeapSizeFn = fn(&dyn Any) -> usize;
+
+pub(crate) fn resource_heap_size_of<R: MemoryUsage + 'static>(resource: &dyn Any) -> usize {
+    resource
+        .downcast_ref::<R>()
+        .map_or(0, MemoryUsage::heap_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(MemoryUsage)]
+    struct Inventory {
+        items: Vec<u32>,
+        name: String,
+        slot: Option<Box<u64>>,
+    }
+
+    #[derive(MemoryUsage)]
+    enum Shape {
+        Circle(f32),
+        Polygon { points: Vec<(f32, f32)> },
+    }
+
+    #[test]
+    fn derive_memory_usage_struct() {
+        let inventory = Inventory {
+            items: Vec::with_capacity(4),
+            name: String::with_capacity(8),
+            slot: Some(Box::new(2)),
+        };
+        assert_eq!(inventory.heap_size(), 4 * 4 + 8 + 8);
+    }
+
+    #[test]
+    fn derive_memory_usage_enum() {
+        assert_eq!(Shape::Circle(2.0).heap_size(), 0);
+        let polygon = Shape::Polygon {
+            points: Vec::with_capacity(3),
+        };
+        assert_eq!(polygon.heap_size(), 3 * 8);
+    }
+}
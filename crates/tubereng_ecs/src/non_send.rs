@@ -0,0 +1,187 @@
+//! Resources that must stay on the main thread, e.g. audio contexts or
+//! graphics API contexts. They are kept apart from the other resources so
+//! that the systems accessing them can be run on the main thread once systems
+//! run in parallel.
+
+use std::{
+    any::{Any, TypeId},
+    cell::{Ref, RefCell, RefMut},
+    ops::{Deref, DerefMut},
+};
+
+use crate::{commands::CommandQueue, system::Argument, Ecs, Storage};
+
+impl Storage {
+    pub fn insert_non_send_resource<R: Any>(&mut self, resource: R) {
+        self.non_send_resources
+            .insert(TypeId::of::<R>(), RefCell::new(Box::new(resource)));
+    }
+
+    /// Removes a non-send resource, returning it if it was present
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the resource can't be downcasted to its actual type
+    pub fn remove_non_send_resource<R: Any>(&mut self) -> Option<R> {
+        let resource = self
+            .non_send_resources
+            .remove(&TypeId::of::<R>())?
+            .into_inner();
+        Some(
+            *resource
+                .downcast::<R>()
+                .expect("Couldn't downcast non-send resource"),
+        )
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the resource can't be downcasted to its actual type
+    #[must_use]
+    pub fn non_send_resource<R: Any>(&self) -> Option<Ref<'_, R>> {
+        Some(Ref::map(
+            self.non_send_resources.get(&TypeId::of::<R>())?.borrow(),
+            |r| {
+                r.downcast_ref::<R>()
+                    .expect("Couldn't downcast non-send resource")
+            },
+        ))
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the resource can't be downcasted to its actual type
+    #[must_use]
+    pub fn non_send_resource_mut<R: Any>(&self) -> Option<RefMut<'_, R>> {
+        Some(RefMut::map(
+            self.non_send_resources
+                .get(&TypeId::of::<R>())?
+                .borrow_mut(),
+            |r| {
+                r.downcast_mut::<R>()
+                    .expect("Couldn't downcast non-send resource")
+            },
+        ))
+    }
+}
+
+impl Ecs {
+    /// Inserts a resource that must stay on the main thread, accessed by
+    /// systems with [`NonSend`] and [`NonSendMut`]
+    pub fn insert_non_send_resource<R: Any>(&mut self, resource: R) {
+        self.storage.insert_non_send_resource(resource);
+    }
+
+    /// See [`Storage::remove_non_send_resource`]
+    pub fn remove_non_send_resource<R: Any>(&mut self) -> Option<R> {
+        self.storage.remove_non_send_resource()
+    }
+
+    #[must_use]
+    pub fn non_send_resource<R: Any>(&self) -> Option<Ref<'_, R>> {
+        self.storage.non_send_resource()
+    }
+
+    #[must_use]
+    pub fn non_send_resource_mut<R: Any>(&self) -> Option<RefMut<'_, R>> {
+        self.storage.non_send_resource_mut()
+    }
+}
+
+/// System argument accessing a non-send resource, making the system run on
+/// the main thread
+pub struct NonSend<'a, T>(Ref<'a, T>);
+impl<'a, T> Deref for NonSend<'a, T> {
+    type Target = Ref<'a, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T: 'static> Argument for NonSend<'_, T> {
+    type Type<'a> = NonSend<'a, T>;
+    const MAIN_THREAD: bool = true;
+
+    fn provide<'a>(
+        _command_queue: &'a CommandQueue,
+        storage: &'a Storage,
+    ) -> Option<Self::Type<'a>> {
+        storage.non_send_resource().map(NonSend)
+    }
+}
+
+/// System argument mutably accessing a non-send resource, making the system
+/// run on the main thread
+pub struct NonSendMut<'a, T>(RefMut<'a, T>);
+impl<'a, T> Deref for NonSendMut<'a, T> {
+    type Target = RefMut<'a, T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl<T> DerefMut for NonSendMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T: 'static> Argument for NonSendMut<'_, T> {
+    type Type<'a> = NonSendMut<'a, T>;
+    const MAIN_THREAD: bool = true;
+
+    fn provide<'a>(
+        _command_queue: &'a CommandQueue,
+        storage: &'a Storage,
+    ) -> Option<Self::Type<'a>> {
+        storage.non_send_resource_mut().map(NonSendMut)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::system::{self, stages, Res};
+
+    /// Non-send as it holds an `Rc`
+    struct AudioContext {
+        played_sounds: Rc<RefCell<Vec<&'static str>>>,
+    }
+
+    #[test]
+    fn non_send_resource_system() {
+        let played_sounds = Rc::new(RefCell::new(vec![]));
+        let mut ecs = Ecs::new();
+        ecs.insert_non_send_resource(AudioContext {
+            played_sounds: Rc::clone(&played_sounds),
+        });
+        let play_sound = |audio_context: NonSendMut<AudioContext>| {
+            audio_context.played_sounds.borrow_mut().push("jump");
+        };
+        assert!(system::Into::into_system(play_sound).requires_main_thread());
+        ecs.register_system(&stages::Update, play_sound);
+
+        ecs.run_systems();
+
+        assert_eq!(*played_sounds.borrow(), ["jump"]);
+        assert!(ecs.resource::<AudioContext>().is_none());
+        assert!(ecs.remove_non_send_resource::<AudioContext>().is_some());
+        assert!(ecs.non_send_resource::<AudioContext>().is_none());
+    }
+
+    #[test]
+    fn systems_requiring_main_thread() {
+        fn requires_main_thread<A>(system: impl system::Into<A>) -> bool {
+            system.into_system().requires_main_thread()
+        }
+
+        assert!(!requires_main_thread(|_: Res<u32>| {}));
+        assert!(requires_main_thread(
+            |_: Res<u32>, _: Option<NonSend<u32>>| {}
+        ));
+        assert!(requires_main_thread(|_: &mut Ecs| {}));
+    }
+}
@@ -0,0 +1,33 @@
+use crate::Ecs;
+
+/// Group of resources and systems installed together into an [`Ecs`], e.g.
+/// by an audio or a physics crate:
+/// ```
+/// # use tubereng_ecs::{plugin::Plugin, system::stages, Ecs};
+/// struct Gravity(f32);
+///
+/// struct PhysicsPlugin {
+///     gravity: f32,
+/// }
+///
+/// impl Plugin for PhysicsPlugin {
+///     fn build(&self, ecs: &mut Ecs) {
+///         ecs.insert_resource(Gravity(self.gravity));
+///         ecs.register_system(&stages::FixedUpdate, || {});
+///     }
+/// }
+///
+/// let mut ecs = Ecs::new();
+/// ecs.add_plugin(&PhysicsPlugin { gravity: -9.81 });
+/// assert!(ecs.resource::<Gravity>().is_some());
+/// ```
+pub trait Plugin {
+    fn build(&self, ecs: &mut Ecs);
+}
+
+impl Ecs {
+    /// Installs the resources and systems of a plugin
+    pub fn add_plugin<P: Plugin + ?Sized>(&mut self, plugin: &P) {
+        plugin.build(self);
+    }
+}
@@ -6,7 +6,7 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use crate::{ComponentStores, EntityId};
+use crate::{bitset::BitSet, ComponentStores, EntityId};
 
 pub struct ComponentAccesses {
     // TODO: Consider using bitsets instead of HashSet, but we would need to
@@ -31,13 +31,14 @@ impl ComponentAccesses {
     }
 }
 
+/// Prepared query, holding the set of entities that can match the query
+/// definition so that iterating doesn't probe every entity
 pub struct State<'w, QD>
 where
     QD: Definition,
 {
     component_stores: &'w ComponentStores,
-    deleted_entities: &'w [EntityId],
-    max_entity_index: usize,
+    entities: Vec<u8>,
     _accesses: ComponentAccesses,
     _marker: PhantomData<QD>,
 }
@@ -46,39 +47,66 @@ impl<'w, QD> State<'w, QD>
 where
     QD: Definition,
 {
+    /// Prepares a query over the entities with an id lower than
+    /// `next_entity_id`
     #[must_use]
     pub fn new(
         component_stores: &'w ComponentStores,
         deleted_entities: &'w [EntityId],
-        max_entity_index: usize,
+        next_entity_id: EntityId,
     ) -> Self {
         let accesses = ComponentAccesses::new();
         QD::register_component_accesses(&accesses);
+
+        let mut entities = vec![0u8; next_entity_id.div_ceil(8)];
+        for entity_id in 0..next_entity_id {
+            entities.set_bit(entity_id);
+        }
+        for &entity_id in deleted_entities
+            .iter()
+            .filter(|&&entity_id| entity_id < next_entity_id)
+        {
+            entities.unset_bit(entity_id);
+        }
+        QD::filter_entities(component_stores, &mut entities);
+
         Self {
             component_stores,
-            max_entity_index,
+            entities,
             _accesses: accesses,
             _marker: PhantomData,
-            deleted_entities,
         }
     }
 
     pub fn iter<'s>(&'s mut self) -> Iter<'w, 's, QD> {
-        Iter::new(
-            self,
-            self.deleted_entities,
-            self.max_entity_index,
-            self.component_stores,
-        )
+        Iter::new(self)
     }
 
     pub fn iter_with_ids<'s>(&'s mut self) -> IterWithIds<'w, 's, QD> {
-        IterWithIds::new(
-            self,
-            self.deleted_entities,
-            self.max_entity_index,
-            self.component_stores,
-        )
+        IterWithIds::new(self)
+    }
+
+    /// Returns the item of an entity, `None` if the entity doesn't match the
+    /// query. Only available for queries without mutable components, see
+    /// [`State::get_mut`] otherwise.
+    pub fn get(&self, entity_id: EntityId) -> Option<QD::Item<'_>>
+    where
+        QD: ReadOnly,
+    {
+        self.fetch(entity_id)
+    }
+
+    /// Returns the item of an entity, `None` if the entity doesn't match the
+    /// query
+    pub fn get_mut(&mut self, entity_id: EntityId) -> Option<QD::Item<'_>> {
+        self.fetch(entity_id)
+    }
+
+    fn fetch(&self, entity_id: EntityId) -> Option<QD::Item<'_>> {
+        if entity_id >= self.entities.len() * 8 || !self.entities.bit(entity_id) {
+            return None;
+        }
+        QD::fetch(self.component_stores, entity_id)
     }
 }
 
@@ -86,11 +114,12 @@ pub struct IterWithIds<'w, 's, QD>
 where
     QD: Definition,
 {
-    _query_state: &'s State<'w, QD>,
-    max_entity_index: usize,
-    deleted_entities: &'w [EntityId],
+    _query_state: PhantomData<&'s State<'w, QD>>,
     component_stores: &'w ComponentStores,
-    current_entity_index: usize,
+    entities: &'s [u8],
+    current_byte_index: usize,
+    /// Bits of the current byte of `entities` not visited yet
+    current_byte: u8,
 }
 
 impl<'w, 's, QD> IterWithIds<'w, 's, QD>
@@ -98,54 +127,37 @@ where
     QD: Definition,
 {
     #[must_use]
-    pub fn new(
-        query_state: &'s State<'w, QD>,
-        deleted_entities: &'w [EntityId],
-        max_entity_index: usize,
-        component_stores: &'w ComponentStores,
-    ) -> Self {
+    pub fn new(query_state: &'s State<'w, QD>) -> Self {
         Self {
-            _query_state: query_state,
-            max_entity_index,
-            component_stores,
-            current_entity_index: 0,
-            deleted_entities,
+            _query_state: PhantomData,
+            component_stores: query_state.component_stores,
+            entities: &query_state.entities,
+            current_byte_index: 0,
+            current_byte: query_state.entities.first().copied().unwrap_or(0),
         }
     }
 }
 
-impl<'w, 's, QD> Iterator for IterWithIds<'w, 's, QD>
+impl<'w, QD> Iterator for IterWithIds<'w, '_, QD>
 where
     QD: Definition,
 {
     type Item = (EntityId, QD::Item<'w>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.current_entity_index > self.max_entity_index {
-            return None;
-        }
-
-        let mut fetched = None;
-        if !self.deleted_entities.contains(&self.current_entity_index) {
-            fetched = QD::fetch(self.component_stores, self.current_entity_index);
-        }
-
-        while fetched.is_none() {
-            self.current_entity_index += 1;
-            if self.current_entity_index > self.max_entity_index {
-                return None;
+        loop {
+            while self.current_byte == 0 {
+                self.current_byte_index += 1;
+                self.current_byte = *self.entities.get(self.current_byte_index)?;
             }
 
-            if self.deleted_entities.contains(&self.current_entity_index) {
-                continue;
+            let bit = self.current_byte.trailing_zeros() as usize;
+            self.current_byte &= self.current_byte - 1;
+            let entity_id = self.current_byte_index * 8 + bit;
+            if let Some(fetched) = QD::fetch(self.component_stores, entity_id) {
+                return Some((entity_id, fetched));
             }
-
-            fetched = QD::fetch(self.component_stores, self.current_entity_index);
         }
-
-        let entity_id = self.current_entity_index;
-        self.current_entity_index += 1;
-        Some((entity_id, fetched?))
     }
 }
 
@@ -161,24 +173,14 @@ where
     QD: Definition,
 {
     #[must_use]
-    pub fn new(
-        query_state: &'s State<'w, QD>,
-        deleted_entities: &'w [EntityId],
-        entity_count: usize,
-        component_stores: &'w ComponentStores,
-    ) -> Self {
+    pub fn new(query_state: &'s State<'w, QD>) -> Self {
         Self {
-            inner: IterWithIds::new(
-                query_state,
-                deleted_entities,
-                entity_count,
-                component_stores,
-            ),
+            inner: IterWithIds::new(query_state),
         }
     }
 }
 
-impl<'w, 's, QD> Iterator for Iter<'w, 's, QD>
+impl<'w, QD> Iterator for Iter<'w, '_, QD>
 where
     QD: Definition,
 {
@@ -193,11 +195,19 @@ pub trait Definition {
     type Item<'a>;
     fn register_component_accesses(accesses: &ComponentAccesses);
 
+    /// Unsets the bits of the entities that can't match the definition in
+    /// the `entities` bitset, so that their components aren't fetched
+    fn filter_entities(component_stores: &ComponentStores, entities: &mut [u8]);
+
     fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>>
     where
         Self: Sized;
 }
 
+/// Query definition without mutable components, whose items can be fetched
+/// through a shared reference to the query
+pub trait ReadOnly: Definition {}
+
 macro_rules! impl_definition_for_tuples {
     ($head:tt, $($tail:tt,)*) => {
         impl<$head: Definition, $($tail: Definition,)*> Definition for ($head, $($tail,)*) {
@@ -208,6 +218,11 @@ macro_rules! impl_definition_for_tuples {
                 $($tail::register_component_accesses(accesses);)*
             }
 
+            fn filter_entities(component_stores: &ComponentStores, entities: &mut [u8]) {
+                $head::filter_entities(component_stores, entities);
+                $($tail::filter_entities(component_stores, entities);)*
+            }
+
             fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>> {
                 Some((
                     $head::fetch(component_stores, entity_id)?,
@@ -216,6 +231,8 @@ macro_rules! impl_definition_for_tuples {
             }
         }
 
+        impl<$head: ReadOnly, $($tail: ReadOnly,)*> ReadOnly for ($head, $($tail,)*) {}
+
         impl_definition_for_tuples!($($tail,)*);
     };
     () => {};
@@ -224,11 +241,18 @@ macro_rules! impl_definition_for_tuples {
 impl_definition_for_tuples!(A, B, C, D, E, F,);
 
 pub struct DirtyState<C>(PhantomData<C>);
+impl<C: 'static> ReadOnly for DirtyState<C> {}
 impl<C: 'static> Definition for DirtyState<C> {
     type Item<'a> = bool;
 
     fn register_component_accesses(_accesses: &ComponentAccesses) {}
 
+    fn filter_entities(component_stores: &ComponentStores, entities: &mut [u8]) {
+        if !component_stores.contains_key(&TypeId::of::<C>()) {
+            entities.clear_bits();
+        }
+    }
+
     fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>>
     where
         Self: Sized,
@@ -237,6 +261,61 @@ impl<C: 'static> Definition for DirtyState<C> {
     }
 }
 
+/// Query filter matching the entities whose component of type `C` has been
+/// mutably accessed or inserted since the dirty flags have last been cleared,
+/// i.e. at the start of the frame
+pub struct Changed<C>(PhantomData<C>);
+impl<C: 'static> ReadOnly for Changed<C> {}
+impl<C: 'static> Definition for Changed<C> {
+    type Item<'a> = ();
+
+    fn register_component_accesses(_accesses: &ComponentAccesses) {}
+
+    fn filter_entities(component_stores: &ComponentStores, entities: &mut [u8]) {
+        match component_stores.get(&TypeId::of::<C>()) {
+            Some(component_store) => component_store.filter_dirty_entities(entities),
+            None => entities.clear_bits(),
+        }
+    }
+
+    fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>>
+    where
+        Self: Sized,
+    {
+        let component_store = component_stores.get(&TypeId::of::<C>())?;
+        component_store.get::<C>(entity_id)?;
+        component_store.dirty(entity_id).then_some(())
+    }
+}
+
+/// Query filter matching the entities whose component of type `C` has been
+/// inserted since the dirty flags have last been cleared, i.e. at the start of
+/// the frame
+pub struct Added<C>(PhantomData<C>);
+impl<C: 'static> ReadOnly for Added<C> {}
+impl<C: 'static> Definition for Added<C> {
+    type Item<'a> = ();
+
+    fn register_component_accesses(_accesses: &ComponentAccesses) {}
+
+    fn filter_entities(component_stores: &ComponentStores, entities: &mut [u8]) {
+        match component_stores.get(&TypeId::of::<C>()) {
+            Some(component_store) => component_store.filter_added_entities(entities),
+            None => entities.clear_bits(),
+        }
+    }
+
+    fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>>
+    where
+        Self: Sized,
+    {
+        let component_store = component_stores.get(&TypeId::of::<C>())?;
+        component_store.get::<C>(entity_id)?;
+        component_store.added(entity_id).then_some(())
+    }
+}
+
+impl<T: 'static> ReadOnly for &T {}
 impl<T: 'static> Definition for &T {
     type Item<'a> = &'a T;
     fn register_component_accesses(accesses: &ComponentAccesses) {
@@ -248,6 +327,9 @@ impl<T: 'static> Definition for &T {
 
         accesses.read.borrow_mut().insert(component_type_id);
     }
+    fn filter_entities(component_stores: &ComponentStores, entities: &mut [u8]) {
+        filter_entities_with_component::<T>(component_stores, entities);
+    }
     fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>> {
         component_stores.get(&TypeId::of::<T>())?.get(entity_id)
     }
@@ -268,6 +350,9 @@ impl<T: 'static> Definition for &mut T {
 
         accesses.write.borrow_mut().insert(component_type_id);
     }
+    fn filter_entities(component_stores: &ComponentStores, entities: &mut [u8]) {
+        filter_entities_with_component::<T>(component_stores, entities);
+    }
     fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>> {
         Some(ComponentRefMut {
             inner: component_stores
@@ -279,6 +364,30 @@ impl<T: 'static> Definition for &mut T {
     }
 }
 
+/// Optional part of a query, e.g. `Option<&T>` or `Option<&mut T>`, matching
+/// the entities with or without the components of `QD`
+impl<QD: ReadOnly> ReadOnly for Option<QD> {}
+impl<QD: Definition> Definition for Option<QD> {
+    type Item<'a> = Option<QD::Item<'a>>;
+    fn register_component_accesses(accesses: &ComponentAccesses) {
+        QD::register_component_accesses(accesses);
+    }
+    fn filter_entities(_component_stores: &ComponentStores, _entities: &mut [u8]) {}
+    fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>> {
+        Some(QD::fetch(component_stores, entity_id))
+    }
+}
+
+fn filter_entities_with_component<T: 'static>(
+    component_stores: &ComponentStores,
+    entities: &mut [u8],
+) {
+    match component_stores.get(&TypeId::of::<T>()) {
+        Some(component_store) => component_store.filter_entities(entities),
+        None => entities.clear_bits(),
+    }
+}
+
 /// Reference to a component that sets the dirty bit of the component on
 /// deref.
 pub struct ComponentRefMut<'a, T: 'static> {
@@ -287,7 +396,7 @@ pub struct ComponentRefMut<'a, T: 'static> {
     pub(crate) entity_id: usize,
 }
 
-impl<'a, T: 'static> Deref for ComponentRefMut<'a, T> {
+impl<T: 'static> Deref for ComponentRefMut<'_, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -297,10 +406,9 @@ impl<'a, T: 'static> Deref for ComponentRefMut<'a, T> {
 
 impl<T: 'static> DerefMut for ComponentRefMut<'_, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.component_stores
-            .get(&TypeId::of::<T>())
-            .unwrap()
-            .set_dirty(self.entity_id);
+        let component_store = self.component_stores.get(&TypeId::of::<T>()).unwrap();
+        component_store.set_dirty(self.entity_id);
+        component_store.record_write(self.entity_id);
         self.inner
     }
 }
@@ -314,6 +422,17 @@ mod tests {
     #[derive(Debug)]
     struct Name(&'static str);
 
+    #[derive(Debug, PartialEq)]
+    struct ZIndex(i32);
+
+    #[test]
+    fn deleted_entities_out_of_range_are_ignored() {
+        let mut ecs = Ecs::new();
+        ecs.insert((Name("player"),));
+        ecs.storage.deleted_entities.push(12);
+        assert_eq!(ecs.query::<&Name>().iter().count(), 1);
+    }
+
     #[test]
     fn set_component_dirty_flag() {
         let mut ecs = Ecs::new();
@@ -334,4 +453,132 @@ mod tests {
         assert!(ecs.query::<DirtyState<Name>>().iter().next().unwrap());
         assert!(ecs.storage.component_stores[&TypeId::of::<Name>()].dirty(entity));
     }
+
+    #[test]
+    fn changed_and_added_filters_match_modified_components() {
+        let mut ecs = Ecs::new();
+        let background = ecs.insert((Name("background"), ZIndex(-1)));
+        let player = ecs.insert((Name("player"),));
+        let names = |ecs: &mut Ecs| {
+            ecs.query::<(&Name, Changed<ZIndex>)>()
+                .iter()
+                .map(|(name, ())| name.0)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&mut ecs), ["background"]);
+        assert_eq!(ecs.query::<Added<Name>>().iter().count(), 2);
+
+        ecs.clear_dirty_flags();
+        assert!(names(&mut ecs).is_empty());
+        assert_eq!(ecs.query::<Added<Name>>().iter().count(), 0);
+
+        ecs.insert_component(player, ZIndex(2));
+        assert_eq!(names(&mut ecs), ["player"]);
+        ecs.clear_dirty_flags();
+        for (mut z_index, ()) in ecs.query::<(&mut ZIndex, Changed<Name>)>().iter() {
+            z_index.0 = 0;
+        }
+        assert!(names(&mut ecs).is_empty());
+
+        ecs.component_mut::<ZIndex>(background).unwrap().0 = 1;
+        assert_eq!(names(&mut ecs), ["background"]);
+        assert_eq!(ecs.query::<Added<ZIndex>>().iter().count(), 0);
+    }
+
+    #[test]
+    fn optional_components_match_entities_with_and_without_them() {
+        let mut ecs = Ecs::new();
+        ecs.insert((Name("background"), ZIndex(-1)));
+        ecs.insert((Name("player"),));
+        ecs.insert((ZIndex(3),));
+
+        let z_indices = ecs
+            .query::<(&Name, Option<&ZIndex>)>()
+            .iter()
+            .map(|(name, z_index)| (name.0, z_index.map_or(0, |z_index| z_index.0)))
+            .collect::<Vec<_>>();
+        assert_eq!(z_indices, [("background", -1), ("player", 0)]);
+
+        for (_, z_index) in ecs.query::<(&Name, Option<&mut ZIndex>)>().iter() {
+            if let Some(mut z_index) = z_index {
+                z_index.0 += 10;
+            }
+        }
+        let z_indices = ecs
+            .query::<Option<&ZIndex>>()
+            .iter()
+            .map(|z_index| z_index.map(|z_index| z_index.0))
+            .collect::<Vec<_>>();
+        assert_eq!(z_indices, [Some(9), None, Some(3)]);
+    }
+
+    #[test]
+    fn query_after_deleting_entities() {
+        let mut ecs = Ecs::new();
+        let entities = (0..10)
+            .map(|i| ecs.insert((ZIndex(i),)))
+            .collect::<Vec<_>>();
+        for &entity in &entities[..5] {
+            ecs.delete(entity);
+        }
+
+        let z_indices = ecs
+            .query::<&ZIndex>()
+            .iter()
+            .map(|z_index| z_index.0)
+            .collect::<Vec<_>>();
+        assert_eq!(z_indices, [5, 6, 7, 8, 9]);
+        assert_eq!(ecs.query::<Option<&Name>>().iter().count(), 5);
+    }
+
+    #[test]
+    fn query_get_by_entity_id() {
+        let mut ecs = Ecs::new();
+        let background = ecs.insert((Name("background"), ZIndex(-1)));
+        let player = ecs.insert((Name("player"),));
+        let deleted = ecs.insert((Name("deleted"), ZIndex(4)));
+        ecs.delete(deleted);
+
+        let query = ecs.query::<(&Name, &ZIndex)>();
+        assert_eq!(
+            query.get(background).map(|(name, _)| name.0),
+            Some("background")
+        );
+        assert!(query.get(player).is_none());
+        assert!(query.get(deleted).is_none());
+        assert!(query.get(1000).is_none());
+
+        if let Some(mut z_index) = ecs.query::<&mut ZIndex>().get_mut(background) {
+            z_index.0 = 3;
+        }
+        assert_eq!(ecs.component::<ZIndex>(background), Some(&ZIndex(3)));
+    }
+
+    #[test]
+    fn query_without_entities() {
+        let mut ecs = Ecs::new();
+        assert_eq!(ecs.query::<&Name>().iter().count(), 0);
+        assert_eq!(ecs.query::<Option<&Name>>().iter().count(), 0);
+    }
+
+    #[test]
+    fn query_tens_of_thousands_of_entities() {
+        let mut ecs = Ecs::new();
+        for i in 0..30_000 {
+            if i % 3 == 0 {
+                ecs.insert((Name("named"), ZIndex(i)));
+            } else {
+                ecs.insert((ZIndex(i),));
+            }
+        }
+
+        let (count, sum) = ecs
+            .query::<(&Name, &ZIndex)>()
+            .iter()
+            .fold((0, 0i64), |(count, sum), (_, z_index)| {
+                (count + 1, sum + i64::from(z_index.0))
+            });
+        assert_eq!(count, 10_000);
+        assert_eq!(sum, (0..30_000).step_by(3).map(i64::from).sum());
+    }
 }
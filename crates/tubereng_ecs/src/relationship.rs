@@ -5,6 +5,9 @@ use std::{
 
 use crate::EntityId;
 
+/// Hierarchy relationship, the source entity being a child of the target
+/// entity. Any other type can be used as a relationship, e.g.
+/// `struct Likes;` with `insert_relationship::<Likes>(a, b)`.
 pub struct ChildOf;
 
 pub(crate) struct Relationships {
@@ -18,9 +21,10 @@ impl Relationships {
         }
     }
 
+    /// Defines a relationship without edges, keeping its edges if it is
+    /// already defined
     pub fn define<R: 'static>(&mut self) {
-        self.relationships
-            .insert(TypeId::of::<R>(), Relationship::default());
+        self.relationships.entry(TypeId::of::<R>()).or_default();
     }
 
     pub fn insert<R: 'static>(&mut self, source: EntityId, target: EntityId) {
@@ -28,11 +32,53 @@ impl Relationships {
         relationship.add(source, target);
     }
 
+    pub fn remove<R: 'static>(&mut self, source: EntityId, target: EntityId) {
+        if let Some(relationship) = self.relationships.get_mut(&TypeId::of::<R>()) {
+            relationship.remove(source, target);
+        }
+    }
+
+    /// Removes the edges of every relationship from or to an entity
+    pub fn remove_entity(&mut self, entity_id: EntityId) {
+        for relationship in self.relationships.values_mut() {
+            relationship.remove_entity(entity_id);
+        }
+    }
+
     pub fn get<R: 'static>(&self) -> Option<&Relationship> {
         self.relationships.get(&TypeId::of::<R>())
     }
+
+    /// Copies to `destination` the edges of every relationship between the
+    /// entities of `moved_ids`, mapping them to their new ids
+    pub fn copy_edges_between(
+        &self,
+        moved_ids: &HashMap<EntityId, EntityId>,
+        destination: &mut Relationships,
+    ) {
+        for (type_id, relationship) in &self.relationships {
+            let destination_relationship = destination.relationships.entry(*type_id).or_default();
+            for (source, target) in relationship.edges() {
+                if let (Some(&source), Some(&target)) =
+                    (moved_ids.get(&source), moved_ids.get(&target))
+                {
+                    destination_relationship.add(source, target);
+                }
+            }
+        }
+    }
+
+    /// Replaces the edges of every relationship with the edges of
+    /// `relationships`, keeping the relationships defined
+    pub fn replace_edges(&mut self, relationships: Relationships) {
+        for relationship in self.relationships.values_mut() {
+            *relationship = Relationship::default();
+        }
+        self.relationships.extend(relationships.relationships);
+    }
 }
 
+/// Many-to-many edges between source and target entities
 #[derive(Default)]
 pub struct Relationship {
     sources_for_entity: HashMap<EntityId, HashSet<EntityId>>,
@@ -51,6 +97,53 @@ impl Relationship {
             .insert(target);
     }
 
+    pub fn remove(&mut self, source: EntityId, target: EntityId) {
+        if let Some(sources) = self.sources_for_entity.get_mut(&target) {
+            sources.remove(&source);
+            if sources.is_empty() {
+                self.sources_for_entity.remove(&target);
+            }
+        }
+        if let Some(targets) = self.targets_for_entity.get_mut(&source) {
+            targets.remove(&target);
+            if targets.is_empty() {
+                self.targets_for_entity.remove(&source);
+            }
+        }
+    }
+
+    /// Removes the edges from or to an entity
+    pub fn remove_entity(&mut self, entity_id: EntityId) {
+        for target in self
+            .targets_for_entity
+            .remove(&entity_id)
+            .unwrap_or_default()
+        {
+            self.remove(entity_id, target);
+        }
+        for source in self
+            .sources_for_entity
+            .remove(&entity_id)
+            .unwrap_or_default()
+        {
+            self.remove(source, entity_id);
+        }
+    }
+
+    #[must_use]
+    pub fn contains(&self, source: EntityId, target: EntityId) -> bool {
+        self.targets_for_entity
+            .get(&source)
+            .is_some_and(|targets| targets.contains(&target))
+    }
+
+    /// Returns the edges of the relationship as `(source, target)` pairs
+    pub fn edges(&self) -> impl Iterator<Item = (EntityId, EntityId)> + '_ {
+        self.targets_for_entity
+            .iter()
+            .flat_map(|(&source, targets)| targets.iter().map(move |&target| (source, target)))
+    }
+
     #[must_use]
     pub fn sources(&self, target: EntityId) -> Option<&HashSet<EntityId>> {
         self.sources_for_entity.get(&target)
@@ -156,6 +249,37 @@ mod tests {
         assert!(successors.contains(&6));
     }
 
+    #[test]
+    fn remove() {
+        let mut relationship = Relationship::default();
+        relationship.add(1, 0);
+        relationship.add(2, 0);
+
+        relationship.remove(1, 0);
+
+        assert!(!relationship.contains(1, 0));
+        assert!(relationship.contains(2, 0));
+        assert!(relationship.targets(1).is_none());
+        assert_eq!(relationship.sources(0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn remove_entity() {
+        let mut relationship = Relationship::default();
+        relationship.add(1, 0);
+        relationship.add(2, 1);
+        relationship.add(1, 3);
+        relationship.add(4, 3);
+
+        relationship.remove_entity(1);
+
+        let mut edges = relationship.edges().collect::<Vec<_>>();
+        edges.sort_unstable();
+        assert_eq!(edges, [(4, 3)]);
+        assert!(relationship.sources(0).is_none());
+        assert!(relationship.targets(2).is_none());
+    }
+
     #[test]
     fn leaves() {
         let mut relationship = Relationship::default();
@@ -0,0 +1,113 @@
+use serde::de::DeserializeOwned;
+
+use crate::{Ecs, EntityDefinition, Storage};
+
+pub type Result<T> = std::result::Result<T, SceneError>;
+
+#[derive(Debug)]
+pub enum SceneError {
+    SceneNotFound,
+    UnregisteredComponent { name: String },
+    ComponentDeserializationFailed { name: String },
+    ComponentConversionFailed { name: String },
+    UnknownParent { name: String },
+}
+
+/// Deserializes a component of a scene from its RON value
+pub(crate) type SceneComponentFn = Box<dyn Fn(&str, &Storage) -> Result<Box<dyn EntityDefinition>>>;
+
+impl Storage {
+    /// Registers a component type so that it can be used in scenes under
+    /// `name`
+    pub fn register_scene_component<C>(&mut self, name: &str)
+    where
+        C: 'static + std::fmt::Debug + DeserializeOwned,
+    {
+        self.register_scene_component_with::<C, C, _>(name, |component, _| Some(component));
+    }
+
+    /// Registers a component type so that it can be used in scenes under
+    /// `name`, described in the scenes by a `D` converted to the component.
+    /// The conversion can resolve references to assets, e.g. load a texture
+    /// from its path using the resources of the storage. A conversion
+    /// returning `None` fails the spawning of the scene.
+    pub fn register_scene_component_with<D, C, F>(&mut self, name: &str, convert: F)
+    where
+        D: DeserializeOwned,
+        C: 'static + std::fmt::Debug,
+        F: 'static + Fn(D, &Storage) -> Option<C>,
+    {
+        let component_name = name.to_string();
+        self.scene_components.insert(
+            name.to_string(),
+            Box::new(move |value, storage| {
+                let description = ron::from_str::<D>(value).map_err(|_| {
+                    SceneError::ComponentDeserializationFailed {
+                        name: component_name.clone(),
+                    }
+                })?;
+                let component = convert(description, storage).ok_or_else(|| {
+                    SceneError::ComponentConversionFailed {
+                        name: component_name.clone(),
+                    }
+                })?;
+                Ok(Box::new((component,)))
+            }),
+        );
+    }
+
+    /// Deserializes the RON value of a component registered under `name`
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if no component is registered under `name`, or if
+    /// the value fails to deserialize or to be converted to the component
+    pub fn deserialize_scene_component(
+        &self,
+        name: &str,
+        value: &str,
+    ) -> Result<Box<dyn EntityDefinition>> {
+        let deserialize =
+            self.scene_components
+                .get(name)
+                .ok_or_else(|| SceneError::UnregisteredComponent {
+                    name: name.to_string(),
+                })?;
+        deserialize(value, self)
+    }
+}
+
+impl Ecs {
+    /// See [`Storage::register_scene_component`]
+    pub fn register_scene_component<C>(&mut self, name: &str)
+    where
+        C: 'static + std::fmt::Debug + DeserializeOwned,
+    {
+        self.storage.register_scene_component::<C>(name);
+    }
+
+    /// See [`Storage::register_scene_component_with`]
+    pub fn register_scene_component_with<D, C, F>(&mut self, name: &str, convert: F)
+    where
+        D: DeserializeOwned,
+        C: 'static + std::fmt::Debug,
+        F: 'static + Fn(D, &Storage) -> Option<C>,
+    {
+        self.storage
+            .register_scene_component_with::<D, C, F>(name, convert);
+    }
+
+    /// See [`Storage::deserialize_scene_component`]
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if no component is registered under `name`, or if
+    /// the value fails to deserialize or to be converted to the component
+    pub fn deserialize_scene_component(
+        &self,
+        name: &str,
+        value: &str,
+    ) -> Result<Box<dyn EntityDefinition>> {
+        self.storage.deserialize_scene_component(name, value)
+    }
+}
@@ -0,0 +1,356 @@
+use crate::commands::{Command, CommandQueue};
+use crate::system::{self, stages, System};
+use crate::tag::Tag;
+use crate::{Ecs, Storage};
+
+/// Resource holding the stack of the scenes of the application, e.g. a pause
+/// menu pushed over the gameplay, added with [`Ecs::add_scene_stack`].
+///
+/// A scene is identified by the tag of the entities it owns. Its systems,
+/// registered with [`Ecs::register_scene_system`], only run while it is at
+/// the top of the stack, so the scenes below keep their entities and their
+/// state until they are back at the top.
+///
+/// The operations take effect at the start of the next frame, in the order
+/// they were requested: the systems registered with
+/// [`Ecs::register_on_scene_exit`] run for the removed scenes, whose entities
+/// are then deleted, and the systems registered with
+/// [`Ecs::register_on_scene_enter`] run for the added scenes.
+#[derive(Debug, Default)]
+pub struct SceneStack {
+    scenes: Vec<Tag>,
+    pending: Vec<SceneOperation>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SceneOperation {
+    Push(Tag),
+    Pop,
+    Replace(Tag),
+}
+
+impl SceneStack {
+    /// Pushes a scene over the current one, pausing it
+    pub fn push(&mut self, scene: Tag) {
+        self.pending.push(SceneOperation::Push(scene));
+    }
+
+    /// Pops the current scene, resuming the scene below it
+    pub fn pop(&mut self) {
+        self.pending.push(SceneOperation::Pop);
+    }
+
+    /// Replaces the current scene, e.g. to go from the title screen to the
+    /// gameplay
+    pub fn replace(&mut self, scene: Tag) {
+        self.pending.push(SceneOperation::Replace(scene));
+    }
+
+    /// Returns the scene at the top of the stack, whose systems run
+    #[must_use]
+    pub fn top(&self) -> Option<Tag> {
+        self.scenes.last().copied()
+    }
+
+    /// Returns the scenes of the stack, from the bottom to the top
+    #[must_use]
+    pub fn scenes(&self) -> &[Tag] {
+        &self.scenes
+    }
+
+    #[must_use]
+    pub fn contains(&self, scene: Tag) -> bool {
+        self.scenes.contains(&scene)
+    }
+}
+
+/// Resource holding the systems run when scenes are added to and removed
+/// from the stack
+#[derive(Default)]
+struct SceneSystems {
+    on_enter: Vec<(Tag, System)>,
+    on_exit: Vec<(Tag, System)>,
+}
+
+/// Returns a run condition holding while `scene` is at the top of the
+/// [`SceneStack`], see [`System::run_if`]
+pub fn in_scene(scene: Tag) -> impl Fn(&Storage) -> bool {
+    move |storage| {
+        storage
+            .resource::<SceneStack>()
+            .is_some_and(|scene_stack| scene_stack.top() == Some(scene))
+    }
+}
+
+impl Ecs {
+    /// Adds an empty [`SceneStack`]. Adding it twice has no effect.
+    pub fn add_scene_stack(&mut self) {
+        if self.storage.resource::<SceneStack>().is_some() {
+            return;
+        }
+        self.storage.insert_resource(SceneStack::default());
+        self.storage.insert_resource(SceneSystems::default());
+        self.register_system(&stages::StartFrame, apply_scene_operations);
+    }
+
+    /// Registers a system running in a stage only while `scene` is at the
+    /// top of the [`SceneStack`]
+    pub fn register_scene_system<S, F, A>(&mut self, stage: &S, scene: Tag, system: F)
+    where
+        S: 'static,
+        F: system::Into<A>,
+    {
+        self.register_system::<S, _, ()>(stage, system.into_system().run_if(in_scene(scene)));
+    }
+
+    /// Registers a system running once each time `scene` is added to the
+    /// stack, e.g. to spawn its entities
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the scene stack hasn't been added with
+    /// [`Ecs::add_scene_stack`]
+    pub fn register_on_scene_enter<F, A>(&mut self, scene: Tag, system: F)
+    where
+        F: system::Into<A>,
+    {
+        scene_systems_mut(&self.storage)
+            .on_enter
+            .push((scene, system.into_system()));
+    }
+
+    /// Registers a system running once each time `scene` is removed from the
+    /// stack, before its entities are deleted
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the scene stack hasn't been added with
+    /// [`Ecs::add_scene_stack`]
+    pub fn register_on_scene_exit<F, A>(&mut self, scene: Tag, system: F)
+    where
+        F: system::Into<A>,
+    {
+        scene_systems_mut(&self.storage)
+            .on_exit
+            .push((scene, system.into_system()));
+    }
+}
+
+impl CommandQueue {
+    pub fn register_scene_system<S, F, A>(&self, stage: &S, scene: Tag, system: F)
+    where
+        S: 'static,
+        F: system::Into<A>,
+    {
+        self.register_system::<S, _, ()>(stage, system.into_system().run_if(in_scene(scene)));
+    }
+
+    pub fn register_on_scene_enter<F, A>(&self, scene: Tag, system: F)
+    where
+        F: system::Into<A>,
+    {
+        self.push_command(RegisterSceneSystem {
+            system: Some((scene, system.into_system())),
+            on_enter: true,
+        });
+    }
+
+    pub fn register_on_scene_exit<F, A>(&self, scene: Tag, system: F)
+    where
+        F: system::Into<A>,
+    {
+        self.push_command(RegisterSceneSystem {
+            system: Some((scene, system.into_system())),
+            on_enter: false,
+        });
+    }
+}
+
+pub struct RegisterSceneSystem {
+    system: Option<(Tag, System)>,
+    on_enter: bool,
+}
+
+impl Command for RegisterSceneSystem {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        let (scene, system) = self.system.take().unwrap();
+        if self.on_enter {
+            ecs.register_on_scene_enter::<_, ()>(scene, system);
+        } else {
+            ecs.register_on_scene_exit::<_, ()>(scene, system);
+        }
+    }
+}
+
+fn scene_systems_mut(storage: &Storage) -> std::cell::RefMut<'_, SceneSystems> {
+    storage
+        .resource_mut::<SceneSystems>()
+        .expect("The scene stack should have been added with Ecs::add_scene_stack")
+}
+
+/// Applies the operations requested on the [`SceneStack`], running the exit
+/// and enter systems of the scenes
+fn apply_scene_operations(ecs: &mut Ecs) {
+    let operations = match ecs.storage.resource_mut::<SceneStack>() {
+        Some(mut scene_stack) if !scene_stack.pending.is_empty() => {
+            std::mem::take(&mut scene_stack.pending)
+        }
+        _ => return,
+    };
+
+    // The systems are moved out while they run, as they can register other
+    // scene systems
+    let scene_systems = std::mem::take(&mut *scene_systems_mut(&ecs.storage));
+    for operation in operations {
+        match operation {
+            SceneOperation::Push(scene) => enter_scene(ecs, &scene_systems, scene),
+            SceneOperation::Pop => exit_scene(ecs, &scene_systems),
+            SceneOperation::Replace(scene) => {
+                exit_scene(ecs, &scene_systems);
+                enter_scene(ecs, &scene_systems, scene);
+            }
+        }
+    }
+
+    let mut registered_while_running = scene_systems_mut(&ecs.storage);
+    let SceneSystems {
+        mut on_enter,
+        mut on_exit,
+    } = scene_systems;
+    on_enter.append(&mut registered_while_running.on_enter);
+    on_exit.append(&mut registered_while_running.on_exit);
+    *registered_while_running = SceneSystems { on_enter, on_exit };
+}
+
+fn enter_scene(ecs: &mut Ecs, scene_systems: &SceneSystems, scene: Tag) {
+    ecs.storage
+        .resource_mut::<SceneStack>()
+        .expect("SceneStack should be present")
+        .scenes
+        .push(scene);
+    for (_, system) in scene_systems
+        .on_enter
+        .iter()
+        .filter(|(entered, _)| *entered == scene)
+    {
+        ecs.run_single_run_system(system);
+    }
+}
+
+/// Removes the scene at the top of the stack and deletes its entities
+fn exit_scene(ecs: &mut Ecs, scene_systems: &SceneSystems) {
+    let Some(scene) = ecs
+        .storage
+        .resource::<SceneStack>()
+        .expect("SceneStack should be present")
+        .top()
+    else {
+        return;
+    };
+    for (_, system) in scene_systems
+        .on_exit
+        .iter()
+        .filter(|(exited, _)| *exited == scene)
+    {
+        ecs.run_single_run_system(system);
+    }
+    ecs.storage
+        .resource_mut::<SceneStack>()
+        .expect("SceneStack should be present")
+        .scenes
+        .pop();
+    let entities = ecs.tagged(scene).collect::<Vec<_>>();
+    for entity_id in entities {
+        ecs.delete(entity_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::{ResMut, Q};
+
+    const GAMEPLAY: Tag = Tag::new("gameplay");
+    const PAUSE_MENU: Tag = Tag::new("pause_menu");
+
+    #[derive(Debug)]
+    struct Position(i32);
+
+    #[derive(Debug, Default)]
+    struct Log {
+        entries: Vec<&'static str>,
+    }
+
+    fn scene_stack_ecs() -> Ecs {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Log::default());
+        ecs.add_scene_stack();
+        ecs.register_on_scene_enter(GAMEPLAY, |queue: &CommandQueue| {
+            let player = queue.insert((Position(0),));
+            queue.add_tag(player, GAMEPLAY);
+        });
+        ecs.register_on_scene_enter(PAUSE_MENU, |mut log: ResMut<Log>| {
+            log.entries.push("enter pause menu");
+        });
+        ecs.register_on_scene_exit(PAUSE_MENU, |mut log: ResMut<Log>| {
+            log.entries.push("exit pause menu");
+        });
+        ecs.register_scene_system(&stages::Update, GAMEPLAY, |mut query: Q<&mut Position>| {
+            for mut position in query.iter() {
+                position.0 += 1;
+            }
+        });
+        ecs.register_scene_system(&stages::Update, PAUSE_MENU, |mut log: ResMut<Log>| {
+            log.entries.push("pause menu");
+        });
+        ecs.resource_mut::<SceneStack>().unwrap().push(GAMEPLAY);
+        ecs
+    }
+
+    fn player_position(ecs: &Ecs) -> Option<i32> {
+        let player = ecs.tagged(GAMEPLAY).next()?;
+        ecs.component::<Position>(player).map(|position| position.0)
+    }
+
+    #[test]
+    fn scene_stack_push_and_pop() {
+        let mut ecs = scene_stack_ecs();
+        ecs.run_systems();
+        assert_eq!(player_position(&ecs), Some(1));
+
+        ecs.resource_mut::<SceneStack>().unwrap().push(PAUSE_MENU);
+        ecs.run_systems();
+        assert_eq!(
+            ecs.resource::<SceneStack>().unwrap().scenes(),
+            [GAMEPLAY, PAUSE_MENU]
+        );
+        assert_eq!(player_position(&ecs), Some(1));
+
+        ecs.resource_mut::<SceneStack>().unwrap().pop();
+        ecs.run_systems();
+
+        assert_eq!(player_position(&ecs), Some(2));
+        assert_eq!(
+            ecs.resource::<Log>().unwrap().entries,
+            ["enter pause menu", "pause menu", "exit pause menu"]
+        );
+    }
+
+    #[test]
+    fn scene_stack_replace_deletes_entities() {
+        let mut ecs = scene_stack_ecs();
+        ecs.run_systems();
+        assert_eq!(ecs.entity_count(), 1);
+
+        ecs.resource_mut::<SceneStack>()
+            .unwrap()
+            .replace(PAUSE_MENU);
+        ecs.run_systems();
+
+        assert_eq!(ecs.entity_count(), 0);
+        assert_eq!(
+            ecs.resource::<SceneStack>().unwrap().top(),
+            Some(PAUSE_MENU)
+        );
+    }
+}
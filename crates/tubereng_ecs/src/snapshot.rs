@@ -0,0 +1,492 @@
+use std::{
+    any::{Any, TypeId},
+    collections::{BTreeMap, HashMap},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    component_store::ComponentStore,
+    relationship::Relationships,
+    store_component,
+    tag::{Tag, Tags},
+    ComponentStores, Ecs, EntityId, Storage, MAX_ENTITY_COUNT,
+};
+
+pub type Result<T> = std::result::Result<T, SnapshotError>;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    SerializationFailed {
+        type_name: String,
+    },
+    DeserializationFailed {
+        type_name: String,
+    },
+    UnregisteredComponent {
+        type_name: String,
+    },
+    UnregisteredRelationship {
+        type_name: String,
+    },
+    UnregisteredResource {
+        type_name: String,
+    },
+    /// The snapshot refers to an entity id which is out of range or deleted
+    InvalidEntity {
+        entity_id: EntityId,
+    },
+}
+
+type SaveComponentsFn = fn(&ComponentStore) -> Result<Vec<(EntityId, String)>>;
+type LoadComponentFn = fn(&mut ComponentStores, EntityId, &str) -> Result<()>;
+type SaveRelationshipFn = fn(&Relationships) -> Vec<(EntityId, EntityId)>;
+type LoadRelationshipFn = fn(&mut Relationships, EntityId, EntityId);
+type SaveResourceFn = fn(&dyn Any) -> Result<String>;
+type LoadResourceFn = fn(&mut Storage, &str) -> Result<()>;
+
+/// Serialization functions of a type included in the snapshots, which
+/// identify it by the name it has been registered with
+struct Entry<S, L> {
+    type_name: String,
+    save_fn: S,
+    load_fn: L,
+}
+
+/// Types included in the snapshots
+#[derive(Default)]
+pub(crate) struct Registry {
+    components: HashMap<TypeId, Entry<SaveComponentsFn, LoadComponentFn>>,
+    relationships: HashMap<TypeId, Entry<SaveRelationshipFn, LoadRelationshipFn>>,
+    resources: HashMap<TypeId, Entry<SaveResourceFn, LoadResourceFn>>,
+}
+
+fn find_load_fn<S, L: Copy>(entries: &HashMap<TypeId, Entry<S, L>>, type_name: &str) -> Option<L> {
+    entries
+        .values()
+        .find(|entry| entry.type_name == type_name)
+        .map(|entry| entry.load_fn)
+}
+
+/// Serialized state of the entities and of the registered resources. Each
+/// component and resource is stored as its own RON string, as the storage
+/// doesn't know their types.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    next_entity_id: EntityId,
+    deleted_entities: Vec<EntityId>,
+    components: BTreeMap<String, Vec<(EntityId, String)>>,
+    relationships: BTreeMap<String, Vec<(EntityId, EntityId)>>,
+    tags: Vec<(EntityId, String)>,
+    resources: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    /// Checks that the entity ids of the snapshot are valid, so that loading
+    /// it cannot overflow the component stores
+    fn validate_entities(&self) -> Result<()> {
+        if self.next_entity_id > MAX_ENTITY_COUNT {
+            return Err(SnapshotError::InvalidEntity {
+                entity_id: self.next_entity_id,
+            });
+        }
+        let mut deleted = vec![false; self.next_entity_id];
+        for &entity_id in &self.deleted_entities {
+            match deleted.get_mut(entity_id) {
+                Some(is_deleted) if !*is_deleted => *is_deleted = true,
+                _ => return Err(SnapshotError::InvalidEntity { entity_id }),
+            }
+        }
+
+        let check = |entity_id: EntityId| {
+            if deleted.get(entity_id) == Some(&false) {
+                Ok(())
+            } else {
+                Err(SnapshotError::InvalidEntity { entity_id })
+            }
+        };
+        for &(entity_id, _) in self.components.values().flatten() {
+            check(entity_id)?;
+        }
+        for &(source, target) in self.relationships.values().flatten() {
+            check(source)?;
+            check(target)?;
+        }
+        for &(entity_id, _) in &self.tags {
+            check(entity_id)?;
+        }
+        Ok(())
+    }
+}
+
+fn to_ron<T: Serialize>(value: &T) -> Result<String> {
+    ron::to_string(value).map_err(|_| SnapshotError::SerializationFailed {
+        type_name: std::any::type_name::<T>().to_string(),
+    })
+}
+
+fn from_ron<T: DeserializeOwned>(serialized: &str) -> Result<T> {
+    ron::from_str(serialized).map_err(|_| SnapshotError::DeserializationFailed {
+        type_name: std::any::type_name::<T>().to_string(),
+    })
+}
+
+impl Storage {
+    /// Registers a component type so that it is included in the snapshots
+    /// under `name`, which identifies it when the snapshots are loaded
+    pub fn register_snapshot_component<C>(&mut self, name: &str)
+    where
+        C: 'static + Serialize + DeserializeOwned,
+    {
+        self.snapshot_registry.components.insert(
+            TypeId::of::<C>(),
+            Entry {
+                type_name: name.to_string(),
+                save_fn: |component_store| {
+                    component_store
+                        .entity_ids()
+                        .filter_map(|entity_id| {
+                            Some((entity_id, component_store.get::<C>(entity_id)?))
+                        })
+                        .map(|(entity_id, component)| Ok((entity_id, to_ron(component)?)))
+                        .collect()
+                },
+                load_fn: |component_stores, entity_id, serialized| {
+                    store_component(component_stores, entity_id, from_ron::<C>(serialized)?);
+                    Ok(())
+                },
+            },
+        );
+    }
+
+    /// Registers a relationship type so that its edges are included in the
+    /// snapshots under `name`
+    pub fn register_snapshot_relationship<R: 'static>(&mut self, name: &str) {
+        self.snapshot_registry.relationships.insert(
+            TypeId::of::<R>(),
+            Entry {
+                type_name: name.to_string(),
+                save_fn: |relationships| {
+                    let mut edges = relationships
+                        .get::<R>()
+                        .map(|relationship| relationship.edges().collect::<Vec<_>>())
+                        .unwrap_or_default();
+                    edges.sort_unstable();
+                    edges
+                },
+                load_fn: Relationships::insert::<R>,
+            },
+        );
+    }
+
+    /// Registers a resource type so that it is included in the snapshots
+    /// under `name`
+    pub fn register_snapshot_resource<R>(&mut self, name: &str)
+    where
+        R: 'static + Serialize + DeserializeOwned,
+    {
+        self.snapshot_registry.resources.insert(
+            TypeId::of::<R>(),
+            Entry {
+                type_name: name.to_string(),
+                save_fn: |resource| {
+                    resource.downcast_ref::<R>().map_or_else(
+                        || {
+                            Err(SnapshotError::SerializationFailed {
+                                type_name: std::any::type_name::<R>().to_string(),
+                            })
+                        },
+                        to_ron,
+                    )
+                },
+                load_fn: |storage, serialized| {
+                    storage.insert_resource(from_ron::<R>(serialized)?);
+                    Ok(())
+                },
+            },
+        );
+    }
+
+    /// Serializes the entities with their registered components,
+    /// relationships and tags, and the registered resources, to RON
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if a component or a resource fails to serialize
+    pub fn save_snapshot(&self) -> Result<String> {
+        let mut components = BTreeMap::new();
+        for (type_id, entry) in &self.snapshot_registry.components {
+            if let Some(component_store) = self.component_stores.get(type_id) {
+                components.insert(entry.type_name.clone(), (entry.save_fn)(component_store)?);
+            }
+        }
+
+        let relationships = self
+            .snapshot_registry
+            .relationships
+            .values()
+            .map(|entry| {
+                (
+                    entry.type_name.clone(),
+                    (entry.save_fn)(&self.relationships),
+                )
+            })
+            .collect();
+
+        let mut tags = (0..self.next_entity_id)
+            .filter(|&entity_id| self.contains_entity(entity_id))
+            .flat_map(|entity_id| {
+                self.tags(entity_id)
+                    .map(move |tag| (entity_id, tag.name().to_string()))
+            })
+            .collect::<Vec<_>>();
+        tags.sort();
+
+        let mut resources = BTreeMap::new();
+        for (type_id, entry) in &self.snapshot_registry.resources {
+            if let Some(resource) = self.resources.get(type_id) {
+                resources.insert(
+                    entry.type_name.clone(),
+                    (entry.save_fn)(&**resource.borrow())?,
+                );
+            }
+        }
+
+        let snapshot = Snapshot {
+            next_entity_id: self.next_entity_id,
+            deleted_entities: self.deleted_entities.clone(),
+            components,
+            relationships,
+            tags,
+            resources,
+        };
+        ron::ser::to_string_pretty(&snapshot, ron::ser::PrettyConfig::default()).map_err(|_| {
+            SnapshotError::SerializationFailed {
+                type_name: std::any::type_name::<Snapshot>().to_string(),
+            }
+        })
+    }
+
+    /// Replaces every entity with the entities of a snapshot made with
+    /// [`Storage::save_snapshot`] and replaces the resources it contains.
+    /// Components of types not registered for snapshots are dropped.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the snapshot is malformed, refers to invalid
+    /// entities or contains a type that hasn't been registered, in which case
+    /// the storage is left unchanged
+    pub fn load_snapshot(&mut self, snapshot: &str) -> Result<()> {
+        let snapshot: Snapshot = from_ron(snapshot)?;
+        snapshot.validate_entities()?;
+        let registry = &self.snapshot_registry;
+
+        // Everything is deserialized before the storage is modified
+        let mut component_stores = ComponentStores::new();
+        for (type_name, components) in &snapshot.components {
+            let load_fn = find_load_fn(&registry.components, type_name).ok_or_else(|| {
+                SnapshotError::UnregisteredComponent {
+                    type_name: type_name.clone(),
+                }
+            })?;
+            for (entity_id, serialized) in components {
+                load_fn(&mut component_stores, *entity_id, serialized)?;
+            }
+        }
+        let mut relationships = Relationships::new();
+        for (type_name, edges) in &snapshot.relationships {
+            let load_fn = find_load_fn(&registry.relationships, type_name).ok_or_else(|| {
+                SnapshotError::UnregisteredRelationship {
+                    type_name: type_name.clone(),
+                }
+            })?;
+            for &(source, target) in edges {
+                load_fn(&mut relationships, source, target);
+            }
+        }
+        let mut resources = Storage::new();
+        for (type_name, serialized) in &snapshot.resources {
+            let load_fn = find_load_fn(&registry.resources, type_name).ok_or_else(|| {
+                SnapshotError::UnregisteredResource {
+                    type_name: type_name.clone(),
+                }
+            })?;
+            load_fn(&mut resources, serialized)?;
+        }
+
+        self.next_entity_id = snapshot.next_entity_id;
+        self.deleted_entities = snapshot.deleted_entities;
+        self.component_stores = component_stores;
+        self.relationships.replace_edges(relationships);
+        self.tags = Tags::default();
+        for (entity_id, tag) in snapshot.tags {
+            self.tags.add(entity_id, Tag::intern(&tag));
+        }
+        self.resources.extend(resources.resources);
+        Ok(())
+    }
+}
+
+impl Ecs {
+    /// See [`Storage::register_snapshot_component`]
+    pub fn register_snapshot_component<C>(&mut self, name: &str)
+    where
+        C: 'static + Serialize + DeserializeOwned,
+    {
+        self.storage.register_snapshot_component::<C>(name);
+    }
+
+    /// See [`Storage::register_snapshot_relationship`]
+    pub fn register_snapshot_relationship<R: 'static>(&mut self, name: &str) {
+        self.storage.register_snapshot_relationship::<R>(name);
+    }
+
+    /// See [`Storage::register_snapshot_resource`]
+    pub fn register_snapshot_resource<R>(&mut self, name: &str)
+    where
+        R: 'static + Serialize + DeserializeOwned,
+    {
+        self.storage.register_snapshot_resource::<R>(name);
+    }
+
+    /// See [`Storage::save_snapshot`]
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if a component or a resource fails to serialize
+    pub fn save_snapshot(&self) -> Result<String> {
+        self.storage.save_snapshot()
+    }
+
+    /// See [`Storage::load_snapshot`]
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the snapshot is malformed, refers to invalid
+    /// entities or contains a type that hasn't been registered
+    pub fn load_snapshot(&mut self, snapshot: &str) -> Result<()> {
+        self.storage.load_snapshot(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relationship::ChildOf;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Position {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Faction {
+        Player,
+        Enemy { aggressive: bool },
+    }
+
+    #[derive(Debug)]
+    struct Transient;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Score {
+        points: u32,
+    }
+
+    fn snapshot_ecs() -> Ecs {
+        let mut ecs = Ecs::new();
+        ecs.register_snapshot_component::<Position>("Position");
+        ecs.register_snapshot_component::<Faction>("Faction");
+        ecs.register_snapshot_relationship::<ChildOf>("ChildOf");
+        ecs.register_snapshot_resource::<Score>("Score");
+        ecs
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let mut ecs = snapshot_ecs();
+        let player = ecs.insert((Position { x: 1, y: 2 }, Faction::Player, Transient));
+        let deleted = ecs.insert((Position { x: 0, y: 0 },));
+        let enemy = ecs.insert((Position { x: 3, y: 4 }, Faction::Enemy { aggressive: true }));
+        ecs.delete(deleted);
+        ecs.insert_relationship::<ChildOf>(enemy, player);
+        ecs.add_tag(enemy, Tag::new("boss"));
+        ecs.insert_resource(Score { points: 12 });
+        let snapshot = ecs.save_snapshot().unwrap();
+
+        let mut loaded = snapshot_ecs();
+        loaded.insert((Position { x: 9, y: 9 },));
+        loaded.insert_resource(Score { points: 0 });
+        loaded.load_snapshot(&snapshot).unwrap();
+
+        assert_eq!(loaded.save_snapshot().unwrap(), snapshot);
+        assert_eq!(loaded.entity_count(), 2);
+        assert_eq!(
+            loaded.component::<Position>(player),
+            Some(&Position { x: 1, y: 2 })
+        );
+        assert_eq!(
+            loaded.component::<Faction>(enemy),
+            Some(&Faction::Enemy { aggressive: true })
+        );
+        assert!(loaded.component::<Position>(deleted).is_none());
+        assert!(loaded.component::<Transient>(player).is_none());
+        assert!(loaded
+            .relationship::<ChildOf>()
+            .unwrap()
+            .contains(enemy, player));
+        assert!(loaded.has_tag(enemy, Tag::new("boss")));
+        assert_eq!(*loaded.resource::<Score>().unwrap(), Score { points: 12 });
+        assert_eq!(loaded.insert((Position { x: 0, y: 0 },)), deleted);
+    }
+
+    #[test]
+    fn snapshot_unregistered_component() {
+        let mut ecs = snapshot_ecs();
+        ecs.insert((Position { x: 1, y: 2 },));
+        let snapshot = ecs.save_snapshot().unwrap();
+
+        let mut loaded = Ecs::new();
+        let entity = loaded.insert((Score { points: 1 },));
+        let result = loaded.load_snapshot(&snapshot);
+
+        assert!(matches!(
+            result,
+            Err(SnapshotError::UnregisteredComponent { .. })
+        ));
+        assert_eq!(
+            loaded.component::<Score>(entity),
+            Some(&Score { points: 1 })
+        );
+    }
+
+    #[test]
+    fn snapshot_invalid_entities() {
+        let mut ecs = snapshot_ecs();
+        let entity = ecs.insert((Position { x: 1, y: 2 },));
+        let snapshot = ecs.save_snapshot().unwrap();
+        assert!(snapshot.contains("\"Position\""));
+
+        let invalid_snapshots = [
+            snapshot.replace("next_entity_id: 1", "next_entity_id: 100000"),
+            snapshot.replace("deleted_entities: []", "deleted_entities: [0, 0]"),
+            snapshot.replace("deleted_entities: []", "deleted_entities: [0]"),
+            snapshot.replace("next_entity_id: 1", "next_entity_id: 0"),
+        ];
+        for invalid_snapshot in invalid_snapshots {
+            assert_ne!(invalid_snapshot, snapshot);
+            let mut loaded = snapshot_ecs();
+            let loaded_entity = loaded.insert((Position { x: 5, y: 5 },));
+            assert!(matches!(
+                loaded.load_snapshot(&invalid_snapshot),
+                Err(SnapshotError::InvalidEntity { .. })
+            ));
+            assert_eq!(
+                loaded.component::<Position>(loaded_entity),
+                Some(&Position { x: 5, y: 5 })
+            );
+        }
+        assert_eq!(entity, 0);
+    }
+}
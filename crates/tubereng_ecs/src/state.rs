@@ -0,0 +1,373 @@
+use crate::commands::{Command, CommandQueue};
+use crate::system::{self, stages, System};
+use crate::{Ecs, Storage};
+
+/// Resource holding the current state of type `T` of the application, e.g.
+/// `Menu`, `Playing` or `Paused`, added with [`Ecs::add_state`].
+///
+/// Changing the state with [`State::set`] takes effect at the start of the
+/// next frame: the systems registered with [`Ecs::register_on_exit`] for the
+/// previous state run, then the ones registered with
+/// [`Ecs::register_on_enter`] for the new state.
+#[derive(Debug)]
+pub struct State<T> {
+    current: T,
+    next: Option<T>,
+    entered: bool,
+}
+
+impl<T> State<T> {
+    #[must_use]
+    pub fn get(&self) -> &T {
+        &self.current
+    }
+
+    /// Requests a transition to another state at the start of the next frame,
+    /// replacing the previous request if any
+    pub fn set(&mut self, next: T) {
+        self.next = Some(next);
+    }
+
+    /// Returns the state requested with [`State::set`] and not entered yet
+    #[must_use]
+    pub fn next(&self) -> Option<&T> {
+        self.next.as_ref()
+    }
+}
+
+/// Resource holding the systems run on state transitions
+struct StateSystems<T> {
+    on_enter: Vec<(T, System)>,
+    on_exit: Vec<(T, System)>,
+}
+
+impl<T> Default for StateSystems<T> {
+    fn default() -> Self {
+        Self {
+            on_enter: vec![],
+            on_exit: vec![],
+        }
+    }
+}
+
+/// Returns a run condition holding while the state of type `T` is `state`,
+/// see [`System::run_if`]
+pub fn in_state<T>(state: T) -> impl Fn(&Storage) -> bool
+where
+    T: 'static + PartialEq,
+{
+    move |storage| {
+        storage
+            .resource::<State<T>>()
+            .is_some_and(|current| current.current == state)
+    }
+}
+
+impl Ecs {
+    /// Adds a state of type `T`, entered at the start of the next frame.
+    /// Adding a state type twice has no effect.
+    pub fn add_state<T>(&mut self, initial: T)
+    where
+        T: 'static + Clone + PartialEq + std::fmt::Debug,
+    {
+        if self.storage.resource::<State<T>>().is_some() {
+            return;
+        }
+        self.storage.insert_resource(State {
+            current: initial,
+            next: None,
+            entered: false,
+        });
+        self.storage.insert_resource(StateSystems::<T>::default());
+        self.register_system(&stages::StartFrame, apply_state_transition::<T>);
+    }
+
+    /// Registers a system running in a stage only while the state of type
+    /// `T` is `required_state`
+    pub fn register_system_in_state<S, T, F, A>(&mut self, stage: &S, required_state: T, system: F)
+    where
+        S: 'static,
+        T: 'static + PartialEq,
+        F: system::Into<A>,
+    {
+        self.register_system::<S, _, ()>(
+            stage,
+            system.into_system().run_if(in_state(required_state)),
+        );
+    }
+
+    /// Registers a system running once each time `state` is entered
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the state type hasn't been added with [`Ecs::add_state`]
+    pub fn register_on_enter<T, F, A>(&mut self, state: T, system: F)
+    where
+        T: 'static,
+        F: system::Into<A>,
+    {
+        self.storage
+            .resource_mut::<StateSystems<T>>()
+            .expect("The state type should have been added with Ecs::add_state")
+            .on_enter
+            .push((state, system.into_system()));
+    }
+
+    /// Registers a system running once each time `state` is exited
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the state type hasn't been added with [`Ecs::add_state`]
+    pub fn register_on_exit<T, F, A>(&mut self, state: T, system: F)
+    where
+        T: 'static,
+        F: system::Into<A>,
+    {
+        self.storage
+            .resource_mut::<StateSystems<T>>()
+            .expect("The state type should have been added with Ecs::add_state")
+            .on_exit
+            .push((state, system.into_system()));
+    }
+}
+
+impl CommandQueue {
+    pub fn add_state<T>(&self, initial: T)
+    where
+        T: 'static + Clone + PartialEq + std::fmt::Debug,
+    {
+        self.push_command(AddState {
+            initial: Some(initial),
+        });
+    }
+
+    pub fn register_system_in_state<S, T, F, A>(&self, stage: &S, required_state: T, system: F)
+    where
+        S: 'static,
+        T: 'static + PartialEq,
+        F: system::Into<A>,
+    {
+        self.register_system::<S, _, ()>(
+            stage,
+            system.into_system().run_if(in_state(required_state)),
+        );
+    }
+
+    pub fn register_on_enter<T, F, A>(&self, state: T, system: F)
+    where
+        T: 'static,
+        F: system::Into<A>,
+    {
+        self.push_command(RegisterStateSystem {
+            system: Some((state, system.into_system())),
+            on_enter: true,
+        });
+    }
+
+    pub fn register_on_exit<T, F, A>(&self, state: T, system: F)
+    where
+        T: 'static,
+        F: system::Into<A>,
+    {
+        self.push_command(RegisterStateSystem {
+            system: Some((state, system.into_system())),
+            on_enter: false,
+        });
+    }
+}
+
+pub struct AddState<T> {
+    initial: Option<T>,
+}
+
+impl<T> Command for AddState<T>
+where
+    T: 'static + Clone + PartialEq + std::fmt::Debug,
+{
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.add_state(self.initial.take().unwrap());
+    }
+}
+
+pub struct RegisterStateSystem<T> {
+    system: Option<(T, System)>,
+    on_enter: bool,
+}
+
+impl<T: 'static> Command for RegisterStateSystem<T> {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        let (state, system) = self.system.take().unwrap();
+        if self.on_enter {
+            ecs.register_on_enter::<T, _, ()>(state, system);
+        } else {
+            ecs.register_on_exit::<T, _, ()>(state, system);
+        }
+    }
+}
+
+/// Enters the initial state or the state requested with [`State::set`],
+/// running the exit and enter systems. Requesting the current state has no
+/// effect.
+fn apply_state_transition<T>(ecs: &mut Ecs)
+where
+    T: 'static + Clone + PartialEq + std::fmt::Debug,
+{
+    let (previous, entered) = {
+        let Some(mut state) = ecs.storage.resource_mut::<State<T>>() else {
+            return;
+        };
+        if state.entered {
+            match state.next.take() {
+                Some(next) if next != state.current => (Some(state.current.clone()), next),
+                _ => return,
+            }
+        } else {
+            state.entered = true;
+            if let Some(next) = state.next.take() {
+                state.current = next;
+            }
+            (None, state.current.clone())
+        }
+    };
+
+    // The systems are moved out while they run, as they can register other
+    // state systems
+    let mut state_systems = std::mem::take(
+        &mut *ecs
+            .storage
+            .resource_mut::<StateSystems<T>>()
+            .expect("StateSystems should be present"),
+    );
+    if let Some(previous) = previous {
+        for (_, system) in state_systems
+            .on_exit
+            .iter()
+            .filter(|(state, _)| *state == previous)
+        {
+            ecs.run_single_run_system(system);
+        }
+        ecs.storage
+            .resource_mut::<State<T>>()
+            .expect("State should be present")
+            .current = entered.clone();
+    }
+    for (_, system) in state_systems
+        .on_enter
+        .iter()
+        .filter(|(state, _)| *state == entered)
+    {
+        ecs.run_single_run_system(system);
+    }
+
+    let mut registered_while_running = ecs
+        .storage
+        .resource_mut::<StateSystems<T>>()
+        .expect("StateSystems should be present");
+    state_systems
+        .on_enter
+        .append(&mut registered_while_running.on_enter);
+    state_systems
+        .on_exit
+        .append(&mut registered_while_running.on_exit);
+    *registered_while_running = state_systems;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::ResMut;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum GameState {
+        Menu,
+        Playing,
+    }
+
+    #[derive(Debug, Default)]
+    struct Log {
+        entries: Vec<&'static str>,
+    }
+
+    fn state_ecs() -> Ecs {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Log::default());
+        ecs.add_state(GameState::Menu);
+        ecs.register_on_enter(GameState::Menu, |mut log: ResMut<Log>| {
+            log.entries.push("enter menu");
+        });
+        ecs.register_on_exit(GameState::Menu, |mut log: ResMut<Log>| {
+            log.entries.push("exit menu");
+        });
+        ecs.register_on_enter(GameState::Playing, |mut log: ResMut<Log>| {
+            log.entries.push("enter playing");
+        });
+        ecs.register_system_in_state(&stages::Update, GameState::Menu, |mut log: ResMut<Log>| {
+            log.entries.push("menu");
+        });
+        ecs.register_system_in_state(
+            &stages::Update,
+            GameState::Playing,
+            |mut log: ResMut<Log>| log.entries.push("playing"),
+        );
+        ecs
+    }
+
+    #[test]
+    fn state_initial_enter() {
+        let mut ecs = state_ecs();
+
+        ecs.run_systems();
+        ecs.run_systems();
+
+        assert_eq!(
+            ecs.resource::<Log>().unwrap().entries,
+            ["enter menu", "menu", "menu"]
+        );
+    }
+
+    #[test]
+    fn state_transition() {
+        let mut ecs = state_ecs();
+        ecs.run_systems();
+
+        ecs.resource_mut::<State<GameState>>()
+            .unwrap()
+            .set(GameState::Playing);
+        assert_eq!(
+            *ecs.resource::<State<GameState>>().unwrap().get(),
+            GameState::Menu
+        );
+        ecs.run_systems();
+
+        assert_eq!(
+            ecs.resource::<Log>().unwrap().entries,
+            [
+                "enter menu",
+                "menu",
+                "exit menu",
+                "enter playing",
+                "playing"
+            ]
+        );
+        assert_eq!(
+            *ecs.resource::<State<GameState>>().unwrap().get(),
+            GameState::Playing
+        );
+    }
+
+    #[test]
+    fn state_transition_to_current_state() {
+        let mut ecs = state_ecs();
+        ecs.run_systems();
+
+        ecs.resource_mut::<State<GameState>>()
+            .unwrap()
+            .set(GameState::Menu);
+        ecs.run_systems();
+
+        assert_eq!(
+            ecs.resource::<Log>().unwrap().entries,
+            ["enter menu", "menu", "menu"]
+        );
+    }
+}
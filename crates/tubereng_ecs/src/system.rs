@@ -1,24 +1,106 @@
+use std::any::Any;
 use std::any::TypeId;
-use std::cell::{Ref, RefMut};
+use std::backtrace::Backtrace;
+use std::cell::{Ref, RefCell, RefMut};
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
+use std::sync::Once;
+use std::time::Duration;
 
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use log::error;
+
+use crate::audit::{ComponentWrite, WriterGuard};
 use crate::commands::CommandQueue;
 use crate::relationship::Relationship;
-use crate::{query, ComponentStores, EntityId, Storage};
+use crate::{query, ComponentStores, Ecs, EntityId, Storage};
 
 pub mod stages {
     pub struct StartFrame;
+    /// Stage running zero or more times per frame with a fixed time step,
+    /// when repeated with [`crate::Ecs::repeat_stage_while`]
+    pub struct FixedUpdate;
     pub struct Update;
     pub struct Render;
     pub struct FinalizeRender;
 }
 
+/// What the schedule should do when one of its systems panics
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// The panic is propagated, taking the whole application down
+    #[default]
+    Propagate,
+    /// The panic is caught, the offending system is disabled and a
+    /// [`SystemPanicked`] event is emitted. Panics cannot be caught on wasm32
+    /// so this behaves like [`PanicPolicy::Propagate`] there.
+    DisableSystem,
+}
+
+/// Event emitted when a system panicked and has been disabled, read through
+/// the [`crate::event::Events`] resource registered by [`Ecs::new`]
+#[derive(Debug, Clone)]
+pub struct SystemPanicked {
+    pub system_name: &'static str,
+    pub message: String,
+    pub backtrace: String,
+}
+
+/// Error returned by a fallible system, i.e. a system returning
+/// `Result<(), SystemError>`. Any error can be converted to it with `?`.
+pub type SystemError = Box<dyn std::error::Error>;
+
+/// Handles the errors returned by the fallible systems, see
+/// [`crate::Ecs::set_system_error_handler`]
+pub type ErrorHandler = fn(system_name: &'static str, error: &SystemError);
+
+/// Logs the error of a system, the default [`ErrorHandler`]
+pub fn log_error(system_name: &'static str, error: &SystemError) {
+    error!("System {system_name} failed: {error}");
+}
+
+/// Ignores the error of a system
+pub fn ignore_error(_system_name: &'static str, _error: &SystemError) {}
+
+/// Panics on the error of a system, the panic being handled according to the
+/// [`PanicPolicy`] of the schedule
+///
+/// # Panics
+///
+/// Always panics
+pub fn panic_on_error(system_name: &'static str, error: &SystemError) {
+    panic!("System {system_name} failed: {error}");
+}
+
+/// Condition checked before each run of a stage, see
+/// [`Schedule::repeat_stage_while`]
+pub type StageCondition = fn(&Storage) -> bool;
+
+/// Time spent running a system during the last run of the schedule, see
+/// [`Schedule::set_system_timing_enabled`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemTiming {
+    pub system_name: &'static str,
+    /// Total duration of the runs of the system, a system of a repeated stage
+    /// running several times per frame
+    pub duration: Duration,
+}
+
 pub struct Schedule {
     stages: Vec<TypeId>,
     stages_systems: HashMap<TypeId, Vec<System>>,
+    stage_repeat_conditions: HashMap<TypeId, StageCondition>,
+    panic_policy: PanicPolicy,
+    component_audit_enabled: bool,
+    system_timing_enabled: bool,
+    system_timings: Vec<SystemTiming>,
+    frame: u64,
 }
 
 impl Schedule {
@@ -26,6 +108,7 @@ impl Schedule {
     pub fn new() -> Self {
         let stages = vec![
             TypeId::of::<stages::StartFrame>(),
+            TypeId::of::<stages::FixedUpdate>(),
             TypeId::of::<stages::Update>(),
             TypeId::of::<stages::Render>(),
             TypeId::of::<stages::FinalizeRender>(),
@@ -41,23 +124,110 @@ impl Schedule {
         Self {
             stages,
             stages_systems,
+            stage_repeat_conditions: HashMap::new(),
+            panic_policy: PanicPolicy::default(),
+            component_audit_enabled: false,
+            system_timing_enabled: false,
+            system_timings: vec![],
+            frame: 0,
+        }
+    }
+
+    pub fn set_panic_policy(&mut self, panic_policy: PanicPolicy) {
+        if panic_policy == PanicPolicy::DisableSystem {
+            install_backtrace_capturing_panic_hook();
         }
+        self.panic_policy = panic_policy;
     }
 
-    /// Run the systems registered in the schedule
+    #[must_use]
+    pub fn panic_policy(&self) -> PanicPolicy {
+        self.panic_policy
+    }
+
+    /// Enables recording which system last mutably accessed each component,
+    /// see [`crate::Storage::last_component_write`]
+    pub fn set_component_audit_enabled(&mut self, enabled: bool) {
+        self.component_audit_enabled = enabled;
+    }
+
+    #[must_use]
+    pub fn component_audit_enabled(&self) -> bool {
+        self.component_audit_enabled
+    }
+
+    /// Enables measuring the time spent running each system, see
+    /// [`Schedule::system_timings`]
+    pub fn set_system_timing_enabled(&mut self, enabled: bool) {
+        self.system_timing_enabled = enabled;
+        self.system_timings.clear();
+    }
+
+    #[must_use]
+    pub fn system_timing_enabled(&self) -> bool {
+        self.system_timing_enabled
+    }
+
+    /// Returns the time spent running each system during the last run of
+    /// the schedule, in the order of their first run. Empty unless the
+    /// system timing is enabled.
+    #[must_use]
+    pub fn system_timings(&self) -> &[SystemTiming] {
+        &self.system_timings
+    }
+
+    /// Returns the number of times the systems of the schedule have been run
+    #[must_use]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Run the systems registered in the schedule. The commands queued before
+    /// an exclusive system are applied before it runs.
     ///
     /// # Panics
     ///
-    /// Will panic if the systems of a stage cannot be found
-    pub fn run_systems(&mut self, storage: &mut Storage, command_queue: &mut CommandQueue) {
+    /// Will panic if the systems of a stage cannot be found, or if a system
+    /// panics and the panic policy is [`PanicPolicy::Propagate`]
+    pub fn run_systems(&mut self, ecs: &mut Ecs) {
+        let frame = self.frame;
+        self.frame += 1;
+        self.system_timings.clear();
         for stage in &self.stages {
             let systems = self.stages_systems.get_mut(stage).unwrap();
-            for system in systems.iter_mut() {
-                system.run(storage, command_queue);
+            let system_timings = &mut self.system_timings;
+            let mut run_stage = |ecs: &mut Ecs| {
+                run_stage_systems(
+                    systems,
+                    ecs,
+                    frame,
+                    self.panic_policy,
+                    self.component_audit_enabled,
+                    self.system_timing_enabled.then_some(&mut *system_timings),
+                );
+            };
+            match self.stage_repeat_conditions.get(stage) {
+                Some(condition) => {
+                    while condition(&ecs.storage) {
+                        run_stage(ecs);
+                    }
+                }
+                None => run_stage(ecs),
             }
         }
     }
 
+    /// Makes a stage run as long as a condition holds, the condition being
+    /// checked before each run. A stage without a condition runs once per
+    /// frame.
+    pub fn repeat_stage_while<S>(&mut self, condition: StageCondition)
+    where
+        S: 'static,
+    {
+        self.stage_repeat_conditions
+            .insert(TypeId::of::<S>(), condition);
+    }
+
     pub fn add_stage<S>(&mut self)
     where
         S: 'static,
@@ -67,6 +237,53 @@ impl Schedule {
         self.stages_systems.insert(stage_id, vec![]);
     }
 
+    /// Returns a schedule without systems but with the settings and the
+    /// system timings of this one, standing in for it while it runs so that
+    /// exclusive systems can read and change them, see [`Schedule::append`]
+    pub(crate) fn stand_in(&self) -> Schedule {
+        Schedule {
+            panic_policy: self.panic_policy,
+            component_audit_enabled: self.component_audit_enabled,
+            system_timing_enabled: self.system_timing_enabled,
+            system_timings: self.system_timings.clone(),
+            frame: self.frame,
+            ..Schedule::new()
+        }
+    }
+
+    /// Moves the systems of another schedule to the end of their stages in
+    /// this one and takes its settings, e.g. the systems registered and the
+    /// settings changed by exclusive systems while this schedule was running
+    pub(crate) fn append(&mut self, other: Schedule) {
+        let Schedule {
+            stages,
+            mut stages_systems,
+            stage_repeat_conditions,
+            panic_policy,
+            component_audit_enabled,
+            system_timing_enabled,
+            ..
+        } = other;
+        self.panic_policy = panic_policy;
+        self.component_audit_enabled = component_audit_enabled;
+        if !system_timing_enabled {
+            self.system_timings.clear();
+        }
+        self.system_timing_enabled = system_timing_enabled;
+        self.stage_repeat_conditions.extend(stage_repeat_conditions);
+        for stage_id in stages {
+            let systems = stages_systems.remove(&stage_id).unwrap_or_default();
+            if let Entry::Vacant(entry) = self.stages_systems.entry(stage_id) {
+                entry.insert(vec![]);
+                self.stages.push(stage_id);
+            }
+            self.stages_systems
+                .get_mut(&stage_id)
+                .expect("The stage should be present")
+                .extend(systems);
+        }
+    }
+
     /// Registers a system to the schedule for a given stage.
     /// If the stage doesn't exist, it is created and will run
     /// after the already registered stages.
@@ -96,24 +313,215 @@ impl Default for Schedule {
     }
 }
 
-type SystemFn = Box<dyn Fn(&mut CommandQueue, &Storage)>;
+fn run_stage_systems(
+    systems: &mut [System],
+    ecs: &mut Ecs,
+    frame: u64,
+    panic_policy: PanicPolicy,
+    component_audit_enabled: bool,
+    mut system_timings: Option<&mut Vec<SystemTiming>>,
+) {
+    for system in systems.iter_mut().filter(|system| system.enabled) {
+        if !system.should_run(&ecs.storage) {
+            continue;
+        }
+        let _writer_guard = component_audit_enabled.then(|| {
+            WriterGuard::new(ComponentWrite {
+                system_name: system.name,
+                frame,
+            })
+        });
+        let run_start = system_timings.is_some().then(Instant::now);
+        run_system(system, ecs, panic_policy);
+        if let (Some(system_timings), Some(run_start)) = (system_timings.as_deref_mut(), run_start)
+        {
+            record_system_timing(system_timings, system.name, run_start.elapsed());
+        }
+    }
+}
+
+fn record_system_timing(
+    system_timings: &mut Vec<SystemTiming>,
+    system_name: &'static str,
+    duration: Duration,
+) {
+    match system_timings
+        .iter_mut()
+        .find(|timing| timing.system_name == system_name)
+    {
+        Some(timing) => timing.duration += duration,
+        None => system_timings.push(SystemTiming {
+            system_name,
+            duration,
+        }),
+    }
+}
+
+/// Runs a system, disabling it if it panics and the panic policy is
+/// [`PanicPolicy::DisableSystem`]
+fn run_system(system: &mut System, ecs: &mut Ecs, panic_policy: PanicPolicy) {
+    if cfg!(target_arch = "wasm32") || panic_policy == PanicPolicy::Propagate {
+        system.run(ecs);
+        return;
+    }
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        system.run(ecs);
+    }));
+
+    if let Err(payload) = result {
+        let event = SystemPanicked {
+            system_name: system.name,
+            message: panic_message(payload.as_ref()),
+            backtrace: LAST_PANIC_BACKTRACE
+                .with(|backtrace| backtrace.borrow_mut().take())
+                .unwrap_or_default(),
+        };
+        error!(
+            "System {} panicked and has been disabled: {}",
+            event.system_name, event.message
+        );
+        system.enabled = false;
+
+        ecs.send_event(event);
+    }
+}
+
+thread_local! {
+    static LAST_PANIC_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Chains a panic hook capturing the backtrace of the panicking thread, so it
+/// can be attached to the [`SystemPanicked`] event once the panic is caught.
+fn install_backtrace_capturing_panic_hook() {
+    static INSTALL_HOOK: Once = Once::new();
+    INSTALL_HOOK.call_once(|| {
+        let previous_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |panic_info| {
+            LAST_PANIC_BACKTRACE.with(|backtrace| {
+                *backtrace.borrow_mut() = Some(Backtrace::force_capture().to_string());
+            });
+            previous_hook(panic_info);
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic payload".to_string()
+    }
+}
+
+type SharedSystemFn = Box<dyn Fn(&mut CommandQueue, &Storage) -> Result<(), SystemError>>;
+type ExclusiveSystemFn = Box<dyn Fn(&mut Ecs) -> Result<(), SystemError>>;
+
+enum SystemFn {
+    Shared(SharedSystemFn),
+    /// Runs with mutable access to the whole [`Ecs`], e.g. for structural
+    /// changes that can't be expressed with commands
+    Exclusive(ExclusiveSystemFn),
+}
+
+/// Condition a system of the schedule is run under, see [`System::run_if`]
+pub type RunCondition = Box<dyn Fn(&Storage) -> bool>;
 
 pub struct System {
+    #[allow(clippy::struct_field_names)]
     system_fn: SystemFn,
+    name: &'static str,
+    enabled: bool,
+    run_condition: Option<RunCondition>,
+    main_thread: bool,
 }
 
 impl System {
-    pub fn run(&self, storage: &mut Storage, command_queue: &mut CommandQueue) {
-        (self.system_fn)(command_queue, storage);
+    fn new<F>(system_fn: SystemFn) -> Self {
+        let main_thread = matches!(system_fn, SystemFn::Exclusive(_));
+        Self {
+            system_fn,
+            name: std::any::type_name::<F>(),
+            enabled: true,
+            run_condition: None,
+            main_thread,
+        }
+    }
+
+    fn with_main_thread(mut self, main_thread: bool) -> Self {
+        self.main_thread |= main_thread;
+        self
+    }
+
+    /// Makes the schedule skip the system when the condition doesn't hold,
+    /// e.g. [`crate::state::in_state`]. Conditions added to a system are
+    /// combined.
+    #[must_use]
+    pub fn run_if<C>(mut self, condition: C) -> Self
+    where
+        C: 'static + Fn(&Storage) -> bool,
+    {
+        self.run_condition = Some(match self.run_condition.take() {
+            Some(previous) => Box::new(move |storage| previous(storage) && condition(storage)),
+            None => Box::new(condition),
+        });
+        self
+    }
+
+    /// Returns whether the run condition of the system holds, `true` if it
+    /// has none
+    #[must_use]
+    pub fn should_run(&self, storage: &Storage) -> bool {
+        self.run_condition
+            .as_ref()
+            .is_none_or(|condition| condition(storage))
+    }
+
+    /// Runs the system, passing its error to the error handler of the
+    /// [`Ecs`] if it fails
+    pub fn run(&self, ecs: &mut Ecs) {
+        let result = match &self.system_fn {
+            SystemFn::Shared(system_fn) => (system_fn)(&mut ecs.command_queue, &ecs.storage),
+            SystemFn::Exclusive(system_fn) => {
+                ecs.process_command_queue();
+                (system_fn)(ecs)
+            }
+        };
+        if let Err(error) = result {
+            (ecs.system_error_handler)(self.name, &error);
+        }
+    }
+
+    /// Returns whether the system must run on the main thread, i.e. if it is
+    /// exclusive or takes a [`crate::non_send::NonSend`] or
+    /// [`crate::non_send::NonSendMut`] argument
+    #[must_use]
+    pub fn requires_main_thread(&self) -> bool {
+        self.main_thread
+    }
+
+    #[must_use]
+    pub fn is_exclusive(&self) -> bool {
+        matches!(self.system_fn, SystemFn::Exclusive(_))
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[must_use]
+    pub fn enabled(&self) -> bool {
+        self.enabled
     }
 }
 
 pub struct Noop;
 impl<A> Into<A> for Noop {
     fn into_system(self) -> System {
-        System {
-            system_fn: Box::new(|_, _| {}),
-        }
+        System::new::<Self>(SystemFn::Shared(Box::new(|_, _| Ok(()))))
     }
 }
 
@@ -146,14 +554,53 @@ impl<A> Into<A> for Box<dyn Into<A>> {
     }
 }
 
+/// Marker of the systems returning `Result<(), SystemError>`, their errors
+/// being passed to the error handler of the [`Ecs`]
+pub struct Fallible<A>(PhantomData<A>);
+
 impl<F> Into<()> for F
 where
     F: 'static + Fn(),
 {
     fn into_system(self) -> System {
-        System {
-            system_fn: Box::new(move |_, _| (self)()),
-        }
+        System::new::<F>(SystemFn::Shared(Box::new(move |_, _| {
+            (self)();
+            Ok(())
+        })))
+    }
+}
+
+impl<F> Into<Fallible<()>> for F
+where
+    F: 'static + Fn() -> Result<(), SystemError>,
+{
+    fn into_system(self) -> System {
+        System::new::<F>(SystemFn::Shared(Box::new(move |_, _| (self)())))
+    }
+}
+
+/// Marker of the systems taking `&mut Ecs` as their only argument, see
+/// [`Schedule::run_systems`]
+pub struct Exclusive;
+
+impl<F> Into<Exclusive> for F
+where
+    F: 'static + Fn(&mut Ecs),
+{
+    fn into_system(self) -> System {
+        System::new::<F>(SystemFn::Exclusive(Box::new(move |ecs| {
+            (self)(ecs);
+            Ok(())
+        })))
+    }
+}
+
+impl<F> Into<Fallible<Exclusive>> for F
+where
+    F: 'static + Fn(&mut Ecs) -> Result<(), SystemError>,
+{
+    fn into_system(self) -> System {
+        System::new::<F>(SystemFn::Exclusive(Box::new(self)))
     }
 }
 
@@ -166,9 +613,23 @@ macro_rules! impl_into_for_tuples {
             $($tail: Argument,)*
         {
             fn into_system(self) -> System {
-                System {
-                    system_fn: Box::new(move |command_queue, storage| (self)($head::provide(command_queue, storage).unwrap(), $($tail::provide(command_queue, storage).unwrap(),)*)),
-                }
+                System::new::<FN>(SystemFn::Shared(Box::new(move |command_queue, storage| {
+                    (self)(provide::<$head>(command_queue, storage), $(provide::<$tail>(command_queue, storage),)*);
+                    Ok(())
+                })))
+                .with_main_thread($head::MAIN_THREAD $(|| $tail::MAIN_THREAD)*)
+            }
+        }
+
+        impl<FN, $head, $($tail,)*> Into<Fallible<($head, $($tail,)*)>> for FN
+        where
+            for<'a> FN: 'static + Fn($head, $($tail,)*) -> Result<(), SystemError> + Fn($head::Type<'a>, $($tail::Type<'a>,)*) -> Result<(), SystemError>,
+            $head: Argument,
+            $($tail: Argument,)*
+        {
+            fn into_system(self) -> System {
+                System::new::<FN>(SystemFn::Shared(Box::new(move |command_queue, storage| (self)(provide::<$head>(command_queue, storage), $(provide::<$tail>(command_queue, storage),)*))))
+                    .with_main_thread($head::MAIN_THREAD $(|| $tail::MAIN_THREAD)*)
             }
         }
 
@@ -181,10 +642,27 @@ impl_into_for_tuples!(F, E, D, C, B, A,);
 
 pub trait Argument {
     type Type<'a>;
+    /// Whether the systems taking the argument must run on the main thread
+    const MAIN_THREAD: bool = false;
     fn provide<'a>(command_queue: &'a CommandQueue, storage: &'a Storage)
         -> Option<Self::Type<'a>>;
 }
 
+/// Provides an argument of a system
+///
+/// # Panics
+///
+/// Will panic if the argument isn't available, e.g. if a resource is
+/// missing, in which case the argument should be wrapped in an `Option`
+fn provide<'a, A: Argument>(command_queue: &'a CommandQueue, storage: &'a Storage) -> A::Type<'a> {
+    A::provide(command_queue, storage).unwrap_or_else(|| {
+        panic!(
+            "The system argument {} isn't available",
+            std::any::type_name::<A>()
+        )
+    })
+}
+
 impl Argument for () {
     type Type<'a> = ();
 
@@ -207,11 +685,14 @@ impl Argument for &Storage {
     }
 }
 
+/// Optional argument, e.g. `Option<Res<T>>` or `Option<ResMut<T>>`, being
+/// `None` when the argument isn't available
 impl<A> Argument for Option<A>
 where
     A: Argument,
 {
     type Type<'a> = Option<A::Type<'a>>;
+    const MAIN_THREAD: bool = A::MAIN_THREAD;
 
     fn provide<'a>(
         command_queue: &'a CommandQueue,
@@ -237,9 +718,9 @@ where
     pub fn new(
         component_stores: &'ecs ComponentStores,
         deleted_entities: &'ecs [EntityId],
-        entity_count: usize,
+        next_entity_id: EntityId,
     ) -> Self {
-        let state = query::State::new(component_stores, deleted_entities, entity_count);
+        let state = query::State::new(component_stores, deleted_entities, next_entity_id);
         Self {
             state,
             _marker: PhantomData,
@@ -259,9 +740,22 @@ where
     pub fn iter_with_ids<'a>(&'a mut self) -> query::IterWithIds<'ecs, 'a, QD> {
         self.state.iter_with_ids()
     }
+
+    /// See [`query::State::get`]
+    pub fn get(&self, entity_id: EntityId) -> Option<QD::Item<'_>>
+    where
+        QD: query::ReadOnly,
+    {
+        self.state.get(entity_id)
+    }
+
+    /// See [`query::State::get_mut`]
+    pub fn get_mut(&mut self, entity_id: EntityId) -> Option<QD::Item<'_>> {
+        self.state.get_mut(entity_id)
+    }
 }
 
-impl<'ecs, QD> Argument for Q<'ecs, QD>
+impl<QD> Argument for Q<'_, QD>
 where
     QD: query::Definition,
 {
@@ -274,7 +768,7 @@ where
         Some(Q::new(
             &storage.component_stores,
             &storage.deleted_entities,
-            storage.entity_count(),
+            storage.next_entity_id(),
         ))
     }
 }
@@ -342,7 +836,7 @@ impl<'a, T> Deref for ResMut<'a, T> {
         &self.0
     }
 }
-impl<'a, T> DerefMut for ResMut<'a, T> {
+impl<T> DerefMut for ResMut<'_, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
@@ -368,7 +862,7 @@ impl<T: 'static> Argument for ResMut<'_, T> {
 
 #[cfg(test)]
 mod tests {
-    use crate::{relationship::ChildOf, Ecs};
+    use crate::{event::Events, relationship::ChildOf, Ecs};
 
     use super::*;
 
@@ -411,6 +905,240 @@ mod tests {
         ecs.run_single_run_system(
             &(|res: Option<Res<MyResource>>| assert!(res.is_some())).into_system(),
         );
+
+        ecs.run_single_run_system(
+            &(|queue: &CommandQueue, res: Option<ResMut<MyResource>>| {
+                assert!(res.is_some());
+                queue.remove_resource::<MyResource>();
+            })
+            .into_system(),
+        );
+        ecs.run_single_run_system(
+            &(|res: Option<ResMut<MyResource>>| assert!(res.is_none())).into_system(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't available")]
+    fn ecs_missing_resource_arg() {
+        let mut ecs = Ecs::new();
+        ecs.run_single_run_system(&(|_res: Res<MyResource>| {}).into_system());
+    }
+
+    #[test]
+    fn ecs_disable_panicking_system() {
+        #[derive(Debug, Default)]
+        struct RunCount {
+            count: u32,
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.set_system_panic_policy(PanicPolicy::DisableSystem);
+        ecs.insert_resource(RunCount::default());
+        // The marker is explicit as the system diverges
+        ecs.register_system::<_, _, (ResMut<RunCount>,)>(
+            &stages::Update,
+            |mut run_count: ResMut<RunCount>| {
+                run_count.count += 1;
+                panic!("Something went wrong");
+            },
+        );
+
+        ecs.run_systems();
+        ecs.run_systems();
+
+        assert_eq!(ecs.resource::<RunCount>().unwrap().count, 1);
+        {
+            let events = ecs.resource::<Events<SystemPanicked>>().unwrap();
+            let event = events.iter().next().unwrap();
+            assert_eq!(event.message, "Something went wrong");
+            assert_eq!(events.len(), 1);
+        }
+        ecs.run_systems();
+
+        assert!(ecs.resource::<Events<SystemPanicked>>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn ecs_fallible_system() {
+        thread_local! {
+            static ERRORS: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+        }
+        fn record_error(_system_name: &'static str, error: &SystemError) {
+            ERRORS.with(|errors| errors.borrow_mut().push(error.to_string()));
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.set_system_error_handler(record_error);
+        ecs.register_system(&stages::Update, |queue: &CommandQueue| {
+            queue.insert((Player,));
+            Ok(())
+        });
+        ecs.register_system(&stages::Update, |_queue: &CommandQueue| {
+            "not a number".parse::<i32>()?;
+            Ok(())
+        });
+        ecs.register_system(&stages::Update, |_ecs: &mut Ecs| {
+            Err("Level not found".into())
+        });
+
+        ecs.run_systems();
+
+        assert_eq!(ecs.entity_count(), 1);
+        assert_eq!(
+            ERRORS.with(RefCell::take),
+            ["invalid digit found in string", "Level not found"]
+        );
+    }
+
+    #[test]
+    fn ecs_fallible_system_panic_on_error() {
+        let mut ecs = Ecs::new();
+        ecs.set_system_panic_policy(PanicPolicy::DisableSystem);
+        ecs.set_system_error_handler(panic_on_error);
+        ecs.register_system(&stages::Update, || Err("Level not found".into()));
+
+        ecs.run_systems();
+        ecs.run_systems();
+
+        let events = ecs.resource::<Events<SystemPanicked>>().unwrap();
+        let event = events.iter().next().unwrap();
+        assert!(event.message.ends_with("failed: Level not found"));
+    }
+
+    #[test]
+    fn ecs_exclusive_system() {
+        let mut ecs = Ecs::new();
+        ecs.register_system(&stages::Update, |queue: &CommandQueue| {
+            queue.insert((Enemy, Health(5)));
+        });
+        ecs.register_system(&stages::Update, |ecs: &mut Ecs| {
+            // The entity inserted by the previous system is already present
+            let enemy_ids = ecs
+                .query::<&Enemy>()
+                .iter_with_ids()
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
+            for id in enemy_ids {
+                ecs.delete(id);
+            }
+            ecs.insert((Player,));
+        });
+
+        ecs.run_systems();
+
+        assert_eq!(ecs.entity_count(), 1);
+        assert_eq!(ecs.query::<&Player>().iter().count(), 1);
+        assert_eq!(ecs.query::<&Enemy>().iter().count(), 0);
+    }
+
+    #[test]
+    fn ecs_exclusive_system_registering_system() {
+        #[derive(Debug, Default)]
+        struct RunCount {
+            count: u32,
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.register_system(&stages::Update, |ecs: &mut Ecs| {
+            if ecs.resource::<RunCount>().is_none() {
+                ecs.insert_resource(RunCount::default());
+                ecs.register_system(&stages::Update, |mut run_count: ResMut<RunCount>| {
+                    run_count.count += 1;
+                });
+            }
+        });
+
+        ecs.run_systems();
+        ecs.run_systems();
+
+        assert_eq!(ecs.resource::<RunCount>().unwrap().count, 1);
+    }
+
+    #[test]
+    fn ecs_exclusive_system_changing_settings() {
+        #[derive(Debug)]
+        struct TimingCount(usize);
+        fn timed_system() {}
+
+        let mut ecs = Ecs::new();
+        ecs.register_system(&stages::Update, timed_system);
+        ecs.register_system(&stages::Update, |ecs: &mut Ecs| {
+            if ecs.system_schedule.frame() == 0 {
+                ecs.set_system_timing_enabled(true);
+                ecs.set_component_audit_enabled(true);
+                ecs.set_system_panic_policy(PanicPolicy::DisableSystem);
+            }
+        });
+
+        ecs.run_systems();
+        ecs.run_systems();
+
+        assert_eq!(
+            ecs.system_schedule.panic_policy(),
+            PanicPolicy::DisableSystem
+        );
+        assert!(ecs.system_schedule.component_audit_enabled());
+        assert!(ecs
+            .system_timings()
+            .iter()
+            .any(|timing| timing.system_name.ends_with("timed_system")));
+
+        // The timings of the last run are readable while the schedule runs
+        ecs.register_system(&stages::Update, |ecs: &mut Ecs| {
+            let timing_count = ecs.system_timings().len();
+            ecs.insert_resource(TimingCount(timing_count));
+        });
+        ecs.run_systems();
+        assert_ne!(ecs.resource::<TimingCount>().unwrap().0, 0);
+    }
+
+    #[test]
+    fn ecs_repeat_stage_while() {
+        #[derive(Debug)]
+        struct RemainingSteps {
+            count: u32,
+        }
+        #[derive(Debug, Default)]
+        struct RunCount {
+            count: u32,
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(RemainingSteps { count: 3 });
+        ecs.insert_resource(RunCount::default());
+        ecs.repeat_stage_while(&stages::FixedUpdate, |storage| {
+            let mut remaining_steps = storage.resource_mut::<RemainingSteps>().unwrap();
+            let has_remaining_steps = remaining_steps.count > 0;
+            remaining_steps.count = remaining_steps.count.saturating_sub(1);
+            has_remaining_steps
+        });
+        ecs.register_system(&stages::FixedUpdate, |mut run_count: ResMut<RunCount>| {
+            run_count.count += 1;
+        });
+
+        ecs.run_systems();
+        assert_eq!(ecs.resource::<RunCount>().unwrap().count, 3);
+        ecs.run_systems();
+        assert_eq!(ecs.resource::<RunCount>().unwrap().count, 3);
+    }
+
+    #[test]
+    fn ecs_query_get_related_entity() {
+        let mut ecs = Ecs::new();
+        let parent = ecs.insert((Health(10),));
+        let child = ecs.insert((Health(3), Position { x: 1, y: 2 }));
+        ecs.insert_relationship::<ChildOf>(child, parent);
+
+        ecs.run_single_run_system(
+            &(move |rel: Rel<ChildOf>, mut healths: Q<&mut Health>| {
+                let parent = *rel.targets(child).unwrap().iter().next().unwrap();
+                healths.get_mut(parent).unwrap().0 += 5;
+            })
+            .into_system(),
+        );
+
+        assert_eq!(ecs.component::<Health>(parent), Some(&Health(15)));
     }
 
     #[test]
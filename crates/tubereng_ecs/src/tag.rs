@@ -0,0 +1,153 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Mutex, OnceLock},
+};
+
+use crate::EntityId;
+
+/// A label attached to entities to group them without defining a marker
+/// component per group.
+///
+/// Tags known at compile time can be declared as constants:
+/// ```
+/// # use tubereng_ecs::tag::Tag;
+/// const ENEMY: Tag = Tag::new("enemy");
+/// ```
+/// while tags built at runtime are interned with [`Tag::intern`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tag(&'static str);
+
+impl Tag {
+    #[must_use]
+    pub const fn new(name: &'static str) -> Self {
+        Self(name)
+    }
+
+    /// Returns the tag named `name`, the name is only allocated the first time
+    /// it is interned
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the interner lock has been poisoned
+    #[must_use]
+    pub fn intern(name: &str) -> Self {
+        static INTERNED_NAMES: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+        let mut interned_names = INTERNED_NAMES
+            .get_or_init(|| Mutex::new(HashSet::new()))
+            .lock()
+            .unwrap();
+        if let Some(&interned_name) = interned_names.get(name) {
+            return Self(interned_name);
+        }
+
+        let interned_name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+        interned_names.insert(interned_name);
+        Self(interned_name)
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl From<&'static str> for Tag {
+    fn from(name: &'static str) -> Self {
+        Self::new(name)
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Tags {
+    entities_for_tag: HashMap<Tag, HashSet<EntityId>>,
+    tags_for_entity: HashMap<EntityId, HashSet<Tag>>,
+}
+
+impl Tags {
+    pub fn add(&mut self, entity_id: EntityId, tag: Tag) {
+        self.entities_for_tag
+            .entry(tag)
+            .or_default()
+            .insert(entity_id);
+        self.tags_for_entity
+            .entry(entity_id)
+            .or_default()
+            .insert(tag);
+    }
+
+    pub fn remove(&mut self, entity_id: EntityId, tag: Tag) {
+        if let Some(entities) = self.entities_for_tag.get_mut(&tag) {
+            entities.remove(&entity_id);
+        }
+        if let Some(tags) = self.tags_for_entity.get_mut(&entity_id) {
+            tags.remove(&tag);
+        }
+    }
+
+    pub fn remove_entity(&mut self, entity_id: EntityId) {
+        let Some(tags) = self.tags_for_entity.remove(&entity_id) else {
+            return;
+        };
+        for tag in tags {
+            if let Some(entities) = self.entities_for_tag.get_mut(&tag) {
+                entities.remove(&entity_id);
+            }
+        }
+    }
+
+    pub fn has(&self, entity_id: EntityId, tag: Tag) -> bool {
+        self.entities_for_tag
+            .get(&tag)
+            .is_some_and(|entities| entities.contains(&entity_id))
+    }
+
+    pub fn entities(&self, tag: Tag) -> impl Iterator<Item = EntityId> + '_ {
+        self.entities_for_tag
+            .get(&tag)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    pub fn tags(&self, entity_id: EntityId) -> impl Iterator<Item = Tag> + '_ {
+        self.tags_for_entity
+            .get(&entity_id)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned_tags_are_equal() {
+        let squad = Tag::intern(&format!("squad_{}", 1));
+        assert_eq!(squad, Tag::new("squad_1"));
+        assert!(std::ptr::eq(squad.name(), Tag::intern("squad_1").name()));
+    }
+
+    #[test]
+    fn tags_membership() {
+        const ENEMY: Tag = Tag::new("enemy");
+        const SQUAD_A: Tag = Tag::new("squad_a");
+        let mut tags = Tags::default();
+        tags.add(1, ENEMY);
+        tags.add(1, SQUAD_A);
+        tags.add(2, ENEMY);
+
+        assert!(tags.has(1, SQUAD_A));
+        assert!(!tags.has(2, SQUAD_A));
+        let mut enemies = tags.entities(ENEMY).collect::<Vec<_>>();
+        enemies.sort_unstable();
+        assert_eq!(enemies, [1, 2]);
+
+        tags.remove(1, ENEMY);
+        assert!(!tags.has(1, ENEMY));
+        tags.remove_entity(1);
+        assert_eq!(tags.tags(1).count(), 0);
+        assert_eq!(tags.entities(SQUAD_A).count(), 0);
+    }
+}
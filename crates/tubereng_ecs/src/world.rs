@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use crate::{relationship::ChildOf, Ecs, EntityId, Storage};
+
+impl Storage {
+    /// Moves an entity to another storage, e.g. from a loading world to the
+    /// simulation world, see [`Storage::move_entities`]. Returns the id of
+    /// the entity in `destination`, or `None` if the entity doesn't exist.
+    pub fn move_entity(
+        &mut self,
+        entity_id: EntityId,
+        destination: &mut Storage,
+    ) -> Option<EntityId> {
+        self.move_entities(&[entity_id], destination).pop()
+    }
+
+    /// Moves an entity along with its descendants to another storage, see
+    /// [`Storage::move_entities`]. Returns the id of the entity in
+    /// `destination`, or `None` if the entity doesn't exist.
+    pub fn move_entity_tree(
+        &mut self,
+        entity_id: EntityId,
+        destination: &mut Storage,
+    ) -> Option<EntityId> {
+        if !self.contains_entity(entity_id) {
+            return None;
+        }
+
+        let mut entity_ids = vec![entity_id];
+        let mut index = 0;
+        while let Some(&current) = entity_ids.get(index) {
+            if let Some(children) = self
+                .relationships
+                .get::<ChildOf>()
+                .and_then(|child_of| child_of.sources(current))
+            {
+                let mut children = children.iter().copied().collect::<Vec<_>>();
+                children.sort_unstable();
+                entity_ids.extend(children);
+            }
+            index += 1;
+        }
+        self.move_entities(&entity_ids, destination)
+            .first()
+            .copied()
+    }
+
+    /// Moves entities with their components and tags to another storage,
+    /// deleting them from this storage. The relationships between the moved
+    /// entities are kept, the ones with the other entities are removed.
+    /// Returns the ids of the entities in `destination`, in the order of
+    /// `entity_ids`, the missing entities being skipped.
+    pub fn move_entities(
+        &mut self,
+        entity_ids: &[EntityId],
+        destination: &mut Storage,
+    ) -> Vec<EntityId> {
+        let mut moved_ids = HashMap::new();
+        let mut destination_ids = Vec::with_capacity(entity_ids.len());
+        for &entity_id in entity_ids {
+            if !self.contains_entity(entity_id) || moved_ids.contains_key(&entity_id) {
+                continue;
+            }
+
+            let destination_id = destination.allocate_entity();
+            for (type_id, component_store) in &mut self.component_stores {
+                let destination_store = destination
+                    .component_stores
+                    .entry(*type_id)
+                    .or_insert_with(|| component_store.empty_like());
+                component_store.move_component(entity_id, destination_store, destination_id);
+            }
+            for tag in self.tags.tags(entity_id) {
+                destination.tags.add(destination_id, tag);
+            }
+            moved_ids.insert(entity_id, destination_id);
+            destination_ids.push(destination_id);
+        }
+
+        self.relationships
+            .copy_edges_between(&moved_ids, &mut destination.relationships);
+        for &entity_id in moved_ids.keys() {
+            self.delete(entity_id);
+        }
+        destination_ids
+    }
+}
+
+impl Ecs {
+    /// See [`Storage::move_entity`]
+    pub fn move_entity(&mut self, entity_id: EntityId, destination: &mut Ecs) -> Option<EntityId> {
+        self.storage
+            .move_entity(entity_id, &mut destination.storage)
+    }
+
+    /// See [`Storage::move_entity_tree`]
+    pub fn move_entity_tree(
+        &mut self,
+        entity_id: EntityId,
+        destination: &mut Ecs,
+    ) -> Option<EntityId> {
+        self.storage
+            .move_entity_tree(entity_id, &mut destination.storage)
+    }
+
+    /// See [`Storage::move_entities`]
+    pub fn move_entities(
+        &mut self,
+        entity_ids: &[EntityId],
+        destination: &mut Ecs,
+    ) -> Vec<EntityId> {
+        self.storage
+            .move_entities(entity_ids, &mut destination.storage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag::Tag;
+
+    #[derive(Debug, PartialEq)]
+    struct Name(String);
+
+    #[test]
+    fn move_entity() {
+        let mut loading_world = Ecs::new();
+        let mut world = Ecs::new();
+        world.insert((Name("existing".into()),));
+        let entity = loading_world.insert((Name("moved".into()),));
+        loading_world.add_tag(entity, Tag::new("level"));
+
+        let moved = loading_world.move_entity(entity, &mut world);
+
+        assert_eq!(moved, Some(1));
+        assert_eq!(loading_world.entity_count(), 0);
+        assert!(loading_world.component::<Name>(entity).is_none());
+        assert_eq!(world.component::<Name>(1), Some(&Name("moved".into())));
+        assert!(world.has_tag(1, Tag::new("level")));
+        assert_eq!(loading_world.move_entity(entity, &mut world), None);
+    }
+
+    #[test]
+    fn move_entity_tree() {
+        let mut loading_world = Ecs::new();
+        let mut world = Ecs::new();
+        let level = loading_world.insert(());
+        let root = loading_world.insert((Name("root".into()),));
+        let child = loading_world.insert((Name("child".into()),));
+        loading_world.insert_relationship::<ChildOf>(root, level);
+        loading_world.insert_relationship::<ChildOf>(child, root);
+
+        let moved = loading_world.move_entity_tree(root, &mut world).unwrap();
+
+        assert_eq!(loading_world.entity_count(), 1);
+        assert!(loading_world
+            .relationship::<ChildOf>()
+            .unwrap()
+            .sources(level)
+            .is_none());
+        assert_eq!(world.entity_count(), 2);
+        assert_eq!(world.component::<Name>(moved), Some(&Name("root".into())));
+        let child_of = world.relationship::<ChildOf>().unwrap();
+        let moved_child = *child_of.sources(moved).unwrap().iter().next().unwrap();
+        assert_eq!(
+            world.component::<Name>(moved_child),
+            Some(&Name("child".into()))
+        );
+        assert!(child_of.targets(moved).is_none());
+    }
+}
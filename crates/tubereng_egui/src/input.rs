@@ -0,0 +1,207 @@
+use tubereng_input::{
+    keyboard::{Key, Modifier},
+    mouse::Button,
+    InputState,
+};
+
+const BUTTONS: [(Button, egui::PointerButton); 3] = [
+    (Button::Left, egui::PointerButton::Primary),
+    (Button::Middle, egui::PointerButton::Middle),
+    (Button::Right, egui::PointerButton::Secondary),
+];
+
+const KEYS: [(Key, egui::Key); 34] = [
+    (Key::Escape, egui::Key::Escape),
+    (Key::Return, egui::Key::Enter),
+    (Key::Backspace, egui::Key::Backspace),
+    (Key::Space, egui::Key::Space),
+    (Key::ArrowUp, egui::Key::ArrowUp),
+    (Key::ArrowDown, egui::Key::ArrowDown),
+    (Key::ArrowLeft, egui::Key::ArrowLeft),
+    (Key::ArrowRight, egui::Key::ArrowRight),
+    (Key::A, egui::Key::A),
+    (Key::B, egui::Key::B),
+    (Key::C, egui::Key::C),
+    (Key::D, egui::Key::D),
+    (Key::E, egui::Key::E),
+    (Key::F, egui::Key::F),
+    (Key::G, egui::Key::G),
+    (Key::H, egui::Key::H),
+    (Key::I, egui::Key::I),
+    (Key::J, egui::Key::J),
+    (Key::K, egui::Key::K),
+    (Key::L, egui::Key::L),
+    (Key::M, egui::Key::M),
+    (Key::N, egui::Key::N),
+    (Key::O, egui::Key::O),
+    (Key::P, egui::Key::P),
+    (Key::Q, egui::Key::Q),
+    (Key::R, egui::Key::R),
+    (Key::S, egui::Key::S),
+    (Key::T, egui::Key::T),
+    (Key::U, egui::Key::U),
+    (Key::V, egui::Key::V),
+    (Key::W, egui::Key::W),
+    (Key::X, egui::Key::X),
+    (Key::Y, egui::Key::Y),
+    (Key::Z, egui::Key::Z),
+];
+
+/// Turns the changes of the [`InputState`] since the previous frame into
+/// egui events
+pub(crate) struct InputEvents {
+    pointer_position: Option<(f64, f64)>,
+    buttons_down: [bool; BUTTONS.len()],
+    keys_down: [bool; KEYS.len()],
+}
+
+impl Default for InputEvents {
+    fn default() -> Self {
+        Self {
+            pointer_position: None,
+            buttons_down: [false; BUTTONS.len()],
+            keys_down: [false; KEYS.len()],
+        }
+    }
+}
+
+impl InputEvents {
+    /// Collects the events since the last call, with positions in points of
+    /// `pixels_per_point` pixels
+    pub(crate) fn collect(
+        &mut self,
+        input: &InputState,
+        pixels_per_point: f32,
+    ) -> (egui::Modifiers, Vec<egui::Event>) {
+        let keyboard = &input.keyboard;
+        let modifiers = egui::Modifiers {
+            alt: keyboard.is_modifier_down(Modifier::Alt),
+            shift: keyboard.is_modifier_down(Modifier::Shift),
+            ctrl: keyboard.is_modifier_down(Modifier::Control),
+            command: keyboard.is_modifier_down(Modifier::Control),
+            ..Default::default()
+        };
+        let mut events = vec![];
+
+        let position = *input.mouse.position();
+        // Positions are in pixels
+        #[allow(clippy::cast_possible_truncation)]
+        let pos = egui::pos2(position.0 as f32, position.1 as f32) / pixels_per_point;
+        if self.pointer_position != Some(position) {
+            self.pointer_position = Some(position);
+            events.push(egui::Event::PointerMoved(pos));
+        }
+
+        for ((button, pointer_button), was_down) in BUTTONS.iter().zip(&mut self.buttons_down) {
+            let is_down = input.mouse.is_button_down(*button);
+            if is_down != *was_down {
+                *was_down = is_down;
+                events.push(egui::Event::PointerButton {
+                    pos,
+                    button: *pointer_button,
+                    pressed: is_down,
+                    modifiers,
+                });
+            }
+        }
+
+        for ((key, egui_key), was_down) in KEYS.iter().zip(&mut self.keys_down) {
+            let is_down = keyboard.is_key_down(*key);
+            if is_down == *was_down {
+                continue;
+            }
+            *was_down = is_down;
+            events.push(egui::Event::Key {
+                key: *egui_key,
+                physical_key: None,
+                pressed: is_down,
+                repeat: false,
+                modifiers,
+            });
+        }
+
+        if !keyboard.text().is_empty() {
+            events.push(egui::Event::Text(keyboard.text().to_string()));
+        }
+
+        (modifiers, events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_input::Input;
+
+    use super::*;
+
+    #[test]
+    fn key_presses_produce_key_and_text_events() {
+        let mut input = InputState::new();
+        let mut input_events = InputEvents::default();
+        input_events.collect(&input, 1.0);
+
+        input.on_input(&Input::KeyDown(Key::LShift));
+        input.on_input(&Input::KeyDown(Key::A));
+        input.on_input(&Input::Text("A".into()));
+        let (modifiers, events) = input_events.collect(&input, 1.0);
+        assert!(modifiers.shift);
+        assert!(matches!(
+            events[..],
+            [
+                egui::Event::Key {
+                    key: egui::Key::A,
+                    pressed: true,
+                    ..
+                },
+                egui::Event::Text(ref text),
+            ] if text == "A"
+        ));
+
+        input.clear_last_frame_inputs();
+        input.on_input(&Input::KeyUp(Key::A));
+        let (_, events) = input_events.collect(&input, 1.0);
+        assert!(matches!(
+            events[..],
+            [egui::Event::Key {
+                key: egui::Key::A,
+                pressed: false,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn pointer_events_are_emitted_on_changes() {
+        let mut input = InputState::new();
+        let mut input_events = InputEvents::default();
+        input.on_input(&Input::CursorMoved((10.0, 20.0)));
+        let (_, events) = input_events.collect(&input, 1.0);
+        assert!(
+            matches!(events[..], [egui::Event::PointerMoved(pos)] if pos == egui::pos2(10.0, 20.0))
+        );
+
+        input.on_input(&Input::MouseButtonDown(Button::Left));
+        let (_, events) = input_events.collect(&input, 1.0);
+        assert!(matches!(
+            events[..],
+            [egui::Event::PointerButton {
+                button: egui::PointerButton::Primary,
+                pressed: true,
+                ..
+            }]
+        ));
+
+        assert!(input_events.collect(&input, 1.0).1.is_empty());
+    }
+
+    #[test]
+    fn pointer_positions_are_scaled_to_points() {
+        let mut input = InputState::new();
+        let mut input_events = InputEvents::default();
+        input.on_input(&Input::CursorMoved((10.0, 20.0)));
+        let (_, events) = input_events.collect(&input, 2.0);
+        assert!(
+            matches!(events[..], [egui::Event::PointerMoved(pos)] if pos == egui::pos2(5.0, 10.0))
+        );
+    }
+}
@@ -0,0 +1,216 @@
+#![warn(clippy::pedantic)]
+
+//! Immediate mode debug UIs with [egui](https://docs.rs/egui).
+//!
+//! Register the [`EguiPlugin`] with the renderer and call [`setup`] from the
+//! init system, then build the UI from any Update system with the
+//! [`EguiContext`] resource:
+//! ```no_run
+//! # use tubereng_ecs::system::Res;
+//! # use tubereng_egui::EguiContext;
+//! fn debug_ui_system(egui_context: Res<EguiContext>) {
+//!     egui::Window::new("Debug").show(egui_context.get(), |ui| {
+//!         ui.label("Hello");
+//!     });
+//! }
+//! ```
+
+use tubereng_core::{accessibility::Accessibility, time::Time};
+use tubereng_ecs::{
+    commands::CommandQueue,
+    system::{stages, Res, ResMut},
+};
+use tubereng_input::InputState;
+use tubereng_renderer::GraphicsState;
+
+mod input;
+mod pass;
+
+pub use egui;
+pub use pass::EguiPlugin;
+
+/// Resource holding the egui context of the current frame
+pub struct EguiContext {
+    context: egui::Context,
+    input_events: input::InputEvents,
+    time: f64,
+    frame_started: bool,
+    /// Textures changes of the frames which have been ended without being
+    /// rendered, to upload with the next rendered frame
+    pending_textures_delta: egui::TexturesDelta,
+}
+
+impl EguiContext {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            context: egui::Context::default(),
+            input_events: input::InputEvents::default(),
+            time: 0.0,
+            frame_started: false,
+            pending_textures_delta: egui::TexturesDelta::default(),
+        }
+    }
+
+    /// Returns the egui context to build the UI of the current frame with
+    #[must_use]
+    pub fn get(&self) -> &egui::Context {
+        &self.context
+    }
+
+    /// Begins a frame drawn on a `size` pixels surface, each UI point being
+    /// drawn with `pixels_per_point` pixels
+    pub(crate) fn begin_frame(
+        &mut self,
+        input: &InputState,
+        size: (u32, u32),
+        delta_time: f32,
+        pixels_per_point: f32,
+    ) {
+        if let Some(output) = self.end_frame() {
+            // The previous frame hasn't been rendered
+            self.pending_textures_delta = output.textures_delta;
+        }
+
+        let (modifiers, events) = self.input_events.collect(input, pixels_per_point);
+        self.time += f64::from(delta_time);
+        #[allow(clippy::cast_precision_loss)]
+        let mut raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(size.0 as f32, size.1 as f32) / pixels_per_point,
+            )),
+            time: Some(self.time),
+            predicted_dt: delta_time,
+            modifiers,
+            events,
+            ..Default::default()
+        };
+        raw_input
+            .viewports
+            .entry(egui::ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(pixels_per_point);
+        self.context.begin_frame(raw_input);
+        self.frame_started = true;
+    }
+
+    /// Ends the current frame, returns `None` if no frame has been started
+    pub(crate) fn end_frame(&mut self) -> Option<egui::FullOutput> {
+        if !std::mem::take(&mut self.frame_started) {
+            return None;
+        }
+        let mut output = self.context.end_frame();
+        let mut textures_delta = std::mem::take(&mut self.pending_textures_delta);
+        textures_delta.append(output.textures_delta);
+        output.textures_delta = textures_delta;
+        Some(output)
+    }
+}
+
+impl Default for EguiContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Input context of egui, kept at the top of the input contexts. The mouse
+/// and the keyboard are consumed while egui uses them, e.g. while the cursor
+/// is over one of its windows, see [`tubereng_input::context::Stack`].
+pub const INPUT_CONTEXT: &str = "egui";
+
+/// Inserts the [`EguiContext`] resource and registers the system feeding the
+/// input to egui at the start of every frame. The UI is scaled by the UI scale
+/// factor of the [`Accessibility`] resource if there is one.
+pub fn setup(queue: &CommandQueue) {
+    queue.insert_resource(EguiContext::new());
+    queue.register_system(&stages::StartFrame, begin_egui_frame_system);
+}
+
+fn begin_egui_frame_system(
+    mut egui_context: ResMut<EguiContext>,
+    mut input: ResMut<InputState>,
+    gfx: Res<GraphicsState>,
+    time: Res<Time>,
+    accessibility: Option<Res<Accessibility>>,
+) {
+    let window_size = gfx.window_size();
+    let pixels_per_point = accessibility
+        .as_ref()
+        .map_or(1.0, |accessibility| accessibility.ui_scale());
+    egui_context.begin_frame(
+        &input,
+        (window_size.width, window_size.height),
+        time.raw_delta(),
+        pixels_per_point,
+    );
+    if input.contexts.contexts().last() != Some(&INPUT_CONTEXT) {
+        input.contexts.push(INPUT_CONTEXT);
+    }
+    if egui_context.get().wants_pointer_input() {
+        input.contexts.consume_mouse(INPUT_CONTEXT);
+    }
+    if egui_context.get().wants_keyboard_input() {
+        input.contexts.consume_keyboard(INPUT_CONTEXT);
+    }
+    std::mem::drop(gfx);
+    std::mem::drop(time);
+    std::mem::drop(accessibility);
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_ecs::{system::Into as _, Ecs};
+    use tubereng_renderer::{stats::RenderStats, texture, RendererBuilder};
+
+    use super::*;
+
+    fn debug_ui_system(egui_context: Res<EguiContext>) {
+        egui::Window::new("Debug").show(egui_context.get(), |ui| {
+            ui.label("Hello");
+        });
+        std::mem::drop(egui_context);
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_ui_is_rendered() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Time::with_delta(0.016));
+        ecs.insert_resource(InputState::new());
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        let initialized = pollster::block_on(
+            RendererBuilder::new()
+                .with_plugin(EguiPlugin::new())
+                .init_headless(
+                    &mut ecs,
+                    64,
+                    64,
+                    &texture::Descriptor {
+                        data: &[255; 4],
+                        width: 1,
+                        height: 1,
+                        premultiplied_alpha: true,
+                        format: texture::Format::Rgba8,
+                    },
+                ),
+        );
+        assert!(initialized, "No graphics adapter is available");
+
+        ecs.run_single_run_system(&setup.into_system());
+        ecs.register_system(&stages::Update, debug_ui_system);
+        // Windows are laid out during their first frame before being shown
+        for _ in 0..3 {
+            ecs.run_systems();
+        }
+
+        let render_stats = ecs.resource::<RenderStats>().unwrap();
+        let egui_stats = render_stats
+            .pass_draw_stats()
+            .iter()
+            .find(|stats| stats.pass_name.ends_with("EguiPass"))
+            .unwrap();
+        assert!(egui_stats.draw_calls > 0);
+        assert!(egui_stats.vertex_count > 0);
+    }
+}
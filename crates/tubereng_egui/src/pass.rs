@@ -0,0 +1,170 @@
+use std::{cell::RefCell, rc::Rc};
+
+use tubereng_ecs::Storage;
+use tubereng_renderer::{
+    plugin::RendererPlugin,
+    render_graph::{RenderGraph, RenderPass},
+    stats::DrawStats,
+    GraphicsState, PipelineCache,
+};
+
+use crate::EguiContext;
+
+#[derive(Default)]
+struct SharedRenderer {
+    renderer: Option<egui_wgpu::Renderer>,
+    /// Set when the renderer has been created again after the device has been
+    /// lost, the textures of egui must then be uploaded again
+    recreated: bool,
+}
+
+/// Renderer plugin drawing the UI built with the [`EguiContext`] on top of
+/// the other passes
+#[derive(Default)]
+pub struct EguiPlugin {
+    renderer: Rc<RefCell<SharedRenderer>>,
+}
+
+impl EguiPlugin {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RendererPlugin for EguiPlugin {
+    fn setup(&self, gfx: &mut GraphicsState, _pipelines: &mut PipelineCache) {
+        let mut shared = self.renderer.borrow_mut();
+        shared.recreated = shared.renderer.is_some();
+        shared.renderer = Some(egui_wgpu::Renderer::new(
+            gfx.device(),
+            gfx.surface_texture_format(),
+            None,
+            1,
+        ));
+    }
+
+    fn add_passes(&self, _gfx: &GraphicsState, graph: &mut RenderGraph, _storage: &Storage) {
+        graph.add_pass(EguiPass {
+            renderer: Rc::clone(&self.renderer),
+            paint_jobs: vec![],
+            textures_to_free: vec![],
+            screen_descriptor: egui_wgpu::ScreenDescriptor {
+                size_in_pixels: [0, 0],
+                pixels_per_point: 1.0,
+            },
+        });
+    }
+}
+
+struct EguiPass {
+    renderer: Rc<RefCell<SharedRenderer>>,
+    paint_jobs: Vec<egui::ClippedPrimitive>,
+    textures_to_free: Vec<egui::TextureId>,
+    screen_descriptor: egui_wgpu::ScreenDescriptor,
+}
+
+impl RenderPass for EguiPass {
+    fn draw_stats(&self) -> DrawStats {
+        DrawStats {
+            draw_calls: u32::try_from(self.paint_jobs.len()).unwrap_or(u32::MAX),
+            vertex_count: self
+                .paint_jobs
+                .iter()
+                .map(|paint_job| match &paint_job.primitive {
+                    egui::epaint::Primitive::Mesh(mesh) => {
+                        u32::try_from(mesh.indices.len()).unwrap_or(u32::MAX)
+                    }
+                    egui::epaint::Primitive::Callback(_) => 0,
+                })
+                .sum(),
+        }
+    }
+
+    fn prepare(&mut self, storage: &Storage) {
+        let Some(mut egui_context) = storage.resource_mut::<EguiContext>() else {
+            return;
+        };
+        let mut shared = self.renderer.borrow_mut();
+        if std::mem::take(&mut shared.recreated) {
+            // Setting the fonts again makes egui upload its font atlas again
+            egui_context
+                .get()
+                .set_fonts(egui::FontDefinitions::default());
+        }
+        let Some(output) = egui_context.end_frame() else {
+            return;
+        };
+        let Some(renderer) = &mut shared.renderer else {
+            return;
+        };
+        let gfx = storage
+            .resource::<GraphicsState>()
+            .expect("Graphics state should be present");
+
+        self.paint_jobs = egui_context
+            .get()
+            .tessellate(output.shapes, output.pixels_per_point);
+        self.screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [gfx.window_size().width, gfx.window_size().height],
+            pixels_per_point: output.pixels_per_point,
+        };
+        for (id, image_delta) in &output.textures_delta.set {
+            renderer.update_texture(gfx.device(), gfx.queue(), *id, image_delta);
+        }
+        self.textures_to_free = output.textures_delta.free;
+
+        let mut encoder = gfx
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("egui_upload_encoder"),
+            });
+        let callback_command_buffers = renderer.update_buffers(
+            gfx.device(),
+            gfx.queue(),
+            &mut encoder,
+            &self.paint_jobs,
+            &self.screen_descriptor,
+        );
+        gfx.queue().submit(
+            callback_command_buffers
+                .into_iter()
+                .chain(std::iter::once(encoder.finish())),
+        );
+    }
+
+    fn execute(
+        &self,
+        _gfx: &mut GraphicsState,
+        encoder: &mut wgpu::CommandEncoder,
+        surface_texture_view: &wgpu::TextureView,
+        _storage: &Storage,
+    ) {
+        let mut shared = self.renderer.borrow_mut();
+        let Some(renderer) = &mut shared.renderer else {
+            return;
+        };
+
+        if !self.paint_jobs.is_empty() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("egui_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: surface_texture_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            renderer.render(&mut render_pass, &self.paint_jobs, &self.screen_descriptor);
+        }
+
+        for id in &self.textures_to_free {
+            renderer.free_texture(id);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+
+use tubereng_ecs::{system::SystemTiming, Ecs};
+use tubereng_renderer::stats::RenderStats;
+
+/// Number of frames the frame time statistics are computed over
+pub const DIAGNOSTICS_FRAME_WINDOW: usize = 120;
+
+/// Resource holding the performance statistics of the engine, updated at the
+/// end of each frame once enabled with `EngineBuilder::with_diagnostics`.
+///
+/// The frame time statistics are computed over the last
+/// [`DIAGNOSTICS_FRAME_WINDOW`] frames, the other statistics are the ones of
+/// the last frame. They are logged periodically if a log interval is set.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    frame_times: VecDeque<f32>,
+    entity_count: usize,
+    system_timings: Vec<SystemTiming>,
+    draw_calls: u32,
+    log_interval: Option<f32>,
+    time_since_log: f32,
+}
+
+impl Diagnostics {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the statistics of a frame lasting `frame_time` seconds
+    pub fn record_frame(
+        &mut self,
+        frame_time: f32,
+        entity_count: usize,
+        system_timings: &[SystemTiming],
+        draw_calls: u32,
+    ) {
+        if self.frame_times.len() == DIAGNOSTICS_FRAME_WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+        self.entity_count = entity_count;
+        self.system_timings.clear();
+        self.system_timings.extend_from_slice(system_timings);
+        self.draw_calls = draw_calls;
+
+        let Some(log_interval) = self.log_interval else {
+            return;
+        };
+        self.time_since_log += frame_time;
+        if self.time_since_log >= log_interval {
+            self.time_since_log = 0.0;
+            self.log();
+        }
+    }
+
+    /// Logs the statistics every `log_interval` seconds, or never if `None`
+    pub fn set_log_interval(&mut self, log_interval: Option<f32>) {
+        self.log_interval = log_interval;
+        self.time_since_log = 0.0;
+    }
+
+    #[must_use]
+    pub fn log_interval(&self) -> Option<f32> {
+        self.log_interval
+    }
+
+    /// Frames per second computed from the average frame time, 0 before the
+    /// first frame
+    #[must_use]
+    pub fn fps(&self) -> f32 {
+        let average_frame_time = self.average_frame_time();
+        if average_frame_time > 0.0 {
+            1.0 / average_frame_time
+        } else {
+            0.0
+        }
+    }
+
+    /// Shortest frame time in seconds
+    #[must_use]
+    pub fn min_frame_time(&self) -> f32 {
+        self.frame_times
+            .iter()
+            .copied()
+            .reduce(f32::min)
+            .unwrap_or_default()
+    }
+
+    /// Average frame time in seconds
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn average_frame_time(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
+    }
+
+    /// Longest frame time in seconds
+    #[must_use]
+    pub fn max_frame_time(&self) -> f32 {
+        self.frame_times
+            .iter()
+            .copied()
+            .reduce(f32::max)
+            .unwrap_or_default()
+    }
+
+    /// Number of entities of the main world
+    #[must_use]
+    pub fn entity_count(&self) -> usize {
+        self.entity_count
+    }
+
+    /// Time spent running each system of the main world
+    #[must_use]
+    pub fn system_timings(&self) -> &[SystemTiming] {
+        &self.system_timings
+    }
+
+    /// Draw calls issued by the renderer, 0 without graphics
+    #[must_use]
+    pub fn draw_calls(&self) -> u32 {
+        self.draw_calls
+    }
+
+    fn log(&self) {
+        let slowest_system = self
+            .system_timings
+            .iter()
+            .max_by_key(|timing| timing.duration);
+        log::info!(
+            "{:.1} FPS, frame time {:.2}/{:.2}/{:.2} ms (min/avg/max), {} entities, {} draw calls{}",
+            self.fps(),
+            self.min_frame_time() * 1000.0,
+            self.average_frame_time() * 1000.0,
+            self.max_frame_time() * 1000.0,
+            self.entity_count,
+            self.draw_calls,
+            slowest_system.map_or_else(String::new, |timing| format!(
+                ", slowest system {} ({:.2} ms)",
+                timing.system_name,
+                timing.duration.as_secs_f32() * 1000.0
+            )),
+        );
+    }
+}
+
+/// Records the statistics of the frame that just ran in the [`Diagnostics`]
+/// resource of a world, if present
+pub(crate) fn update_diagnostics(ecs: &Ecs, frame_time: f32) {
+    let Some(mut diagnostics) = ecs.resource_mut::<Diagnostics>() else {
+        return;
+    };
+    let draw_calls = ecs
+        .resource::<RenderStats>()
+        .map_or(0, |render_stats| render_stats.total_draw_calls());
+    diagnostics.record_frame(
+        frame_time,
+        ecs.entity_count(),
+        ecs.system_timings(),
+        draw_calls,
+    );
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use tubereng_asset::{AssetHandle, AssetStore};
+use tubereng_image::Image;
+use tubereng_renderer::{texture, GraphicsState};
+
+use crate::texture_descriptor;
+
+/// Resource associating the textures with the image assets they have been
+/// uploaded from, so that they are reloaded in place when the images are
+/// modified with the `hot-reload` feature enabled, e.g. while an artist
+/// tweaks them, and unloaded along with the images stored in the
+/// [`AssetStore`].
+#[derive(Debug, Default)]
+pub struct TextureAssets {
+    textures: HashMap<String, Vec<texture::Id>>,
+    /// Textures uploaded from the images stored in the asset store, by asset
+    /// id
+    stored_textures: HashMap<usize, Vec<texture::Id>>,
+}
+
+impl TextureAssets {
+    /// Loads an image asset and uploads it as a texture reloaded with the
+    /// image
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the image can't be loaded
+    pub fn load(
+        &mut self,
+        asset_store: &AssetStore,
+        gfx: &mut GraphicsState,
+        asset_path: &str,
+    ) -> tubereng_asset::Result<texture::Id> {
+        let image = asset_store.load_without_storing::<Image>(asset_path)?;
+        let texture_id = gfx.load_texture(&texture_descriptor(&image));
+        self.watch(asset_path, texture_id);
+        Ok(texture_id)
+    }
+
+    /// Uploads an image stored in the asset store as a texture, which is
+    /// unloaded once the last handle to the image is dropped. Returns `None`
+    /// if the image has been unloaded.
+    pub fn upload(
+        &mut self,
+        asset_store: &AssetStore,
+        gfx: &mut GraphicsState,
+        image: &AssetHandle<Image>,
+    ) -> Option<texture::Id> {
+        let texture_id = gfx.load_texture(&texture_descriptor(asset_store.get(image)?));
+        self.stored_textures
+            .entry(image.id())
+            .or_default()
+            .push(texture_id);
+        if let Some(asset_path) = asset_store.path(image) {
+            self.watch(asset_path, texture_id);
+        }
+        Some(texture_id)
+    }
+
+    /// Unloads the textures uploaded from unloaded assets, see
+    /// [`AssetStore::free_unused`]
+    pub fn on_assets_unloaded(&mut self, gfx: &mut GraphicsState, unloaded_asset_ids: &[usize]) {
+        for asset_id in unloaded_asset_ids {
+            let Some(texture_ids) = self.stored_textures.remove(asset_id) else {
+                continue;
+            };
+            for texture_id in texture_ids {
+                gfx.unload_texture(texture_id);
+                // The id may be reused by an unrelated texture
+                for textures in self.textures.values_mut() {
+                    textures.retain(|&watched| watched != texture_id);
+                }
+            }
+        }
+    }
+
+    /// Reloads a texture already uploaded from the image at `asset_path` when
+    /// the image is modified
+    pub fn watch(&mut self, asset_path: &str, texture_id: texture::Id) {
+        let textures = self.textures.entry(asset_path.to_string()).or_default();
+        if !textures.contains(&texture_id) {
+            textures.push(texture_id);
+        }
+    }
+
+    /// Returns the textures uploaded from the image at `asset_path`
+    pub fn textures(&self, asset_path: &str) -> impl Iterator<Item = texture::Id> + '_ {
+        self.textures.get(asset_path).into_iter().flatten().copied()
+    }
+
+    /// Uploads again the textures of an image, returning the number of
+    /// reloaded textures. The unloaded textures are forgotten.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the image can't be loaded, the textures then
+    /// keeping their previous content
+    pub fn reload(
+        &mut self,
+        asset_store: &AssetStore,
+        gfx: &mut GraphicsState,
+        asset_path: &str,
+    ) -> tubereng_asset::Result<usize> {
+        let Some(textures) = self.textures.get_mut(asset_path) else {
+            return Ok(0);
+        };
+        let image = asset_store.load_without_storing::<Image>(asset_path)?;
+        let descriptor = texture_descriptor(&image);
+        textures.retain(|&texture_id| gfx.reload_texture(texture_id, &descriptor));
+        Ok(textures.len())
+    }
+}
+
+/// Reloads the modified assets and the textures uploaded from them
+#[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+pub(crate) fn reload_modified_assets_system(
+    mut asset_store: tubereng_ecs::system::ResMut<AssetStore>,
+    mut texture_assets: tubereng_ecs::system::ResMut<TextureAssets>,
+    gfx: Option<tubereng_ecs::system::ResMut<GraphicsState>>,
+) {
+    let modified_paths = asset_store.reload_modified();
+    let Some(mut gfx) = gfx else {
+        return;
+    };
+    for asset_path in modified_paths {
+        if let Err(error) = texture_assets.reload(&asset_store, &mut gfx, &asset_path) {
+            log::warn!("Failed to reload the textures of {asset_path}: {error:?}");
+        }
+    }
+}
+
+/// Loads the assets whose files were still being fetched, see
+/// [`AssetStore::load_pending`]
+pub(crate) fn load_pending_assets_system(
+    mut asset_store: tubereng_ecs::system::ResMut<AssetStore>,
+) {
+    let loaded_asset_ids = asset_store.load_pending();
+    if !loaded_asset_ids.is_empty() {
+        log::debug!("Loaded {} pending assets", loaded_asset_ids.len());
+    }
+}
+
+/// Unloads the assets whose handles have all been dropped and the textures
+/// uploaded from them
+pub(crate) fn free_unused_assets_system(
+    mut asset_store: tubereng_ecs::system::ResMut<AssetStore>,
+    mut texture_assets: tubereng_ecs::system::ResMut<TextureAssets>,
+    gfx: Option<tubereng_ecs::system::ResMut<GraphicsState>>,
+) {
+    let unloaded_asset_ids = asset_store.free_unused();
+    if unloaded_asset_ids.is_empty() {
+        return;
+    }
+    log::debug!("Unloaded {} unused assets", unloaded_asset_ids.len());
+    if let Some(mut gfx) = gfx {
+        texture_assets.on_assets_unloaded(&mut gfx, &unloaded_asset_ids);
+    }
+}
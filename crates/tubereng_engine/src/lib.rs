@@ -2,36 +2,69 @@
 
 use std::sync::Arc;
 use tubereng_asset::vfs::VirtualFileSystem;
-use tubereng_asset::AssetLoader;
-use tubereng_asset::AssetStore;
-use tubereng_core::TransformCache;
-
-use tubereng_ecs::system::stages;
-
-use tubereng_math::matrix::Identity;
-use tubereng_math::matrix::Matrix4f;
+use tubereng_asset::{AssetLoader, AssetStore};
 
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
-use tubereng_core::DeltaTime;
-use tubereng_core::Transform;
-
-use tubereng_ecs::relationship::ChildOf;
+use tubereng_core::accessibility::{Accessibility, Announcement};
+use tubereng_core::clipboard::{Clipboard, ClipboardBackend};
+use tubereng_core::exit::AppExit;
+use tubereng_core::time::{FixedTime, FrameTiming, Time};
 
-use tubereng_ecs::Storage;
-use tubereng_image::ImageLoader;
-use tubereng_input::{Input, InputState};
+use tubereng_image::{Image, ImageFormat, ImageLoader};
+use tubereng_input::{
+    haptics::{Haptics, HapticsBackend},
+    Input, InputState,
+};
 
 use tubereng_ecs::{
+    plugin::Plugin,
     system::{self, System},
-    Ecs,
+    Ecs, EntityId,
+};
+use tubereng_renderer::{
+    adapter::RendererSettings, plugin::RendererPlugin, texture, GraphicsState, RendererBuilder,
+    WindowSize,
 };
-use tubereng_renderer::texture;
+use window::{Window, WindowCommand, WindowSettings};
+
+pub mod diagnostics;
+pub mod hot_reload;
+pub mod plugins;
+pub mod scene;
+pub mod window;
+
+/// Name of the world created by the engine, holding the renderer and input
+/// resources
+pub const MAIN_WORLD: &str = "main";
+
+/// Phase of the startup of the engine a startup system runs in, see
+/// [`EngineBuilder::with_startup_system`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupPhase {
+    /// When the engine is built, before the window and the graphics are
+    /// initialized, e.g. to load the assets and set up the world
+    Startup,
+    /// At the first update, once the graphics are initialized, e.g. to
+    /// upload textures or set up the UI
+    PostStartup,
+}
+
+/// Additional world added with [`Engine::add_world`]
+struct World {
+    name: &'static str,
+    ecs: Ecs,
+    active: bool,
+}
 
 pub struct Engine {
     application_title: &'static str,
     ecs: Ecs,
-    init_system: System,
-    init_system_ran: bool,
+    main_world_active: bool,
+    worlds: Vec<World>,
+    post_startup_systems: Vec<System>,
+    post_startup_ran: bool,
+    window_settings: WindowSettings,
+    frame_rate_limit: Option<f32>,
 }
 
 impl Engine {
@@ -40,32 +73,203 @@ impl Engine {
         EngineBuilder::default()
     }
 
+    /// Initializes the renderer installed by the [`plugins::GraphicsPlugin`]
+    /// for the window
     pub async fn init_graphics<W>(&mut self, window: Arc<W>)
     where
         W: HasWindowHandle + HasDisplayHandle + std::marker::Send + std::marker::Sync,
     {
-        // SAFETY: The placeholder image is a valid PNG file that is loaded at compile time
-        let placeholder_texture_image = unsafe {
-            ImageLoader::load(include_bytes!("../res/placeholder.png")).unwrap_unchecked()
-        };
-        let placeholder_texture_descriptor = texture::Descriptor {
-            data: placeholder_texture_image.data(),
-            width: placeholder_texture_image.width(),
-            height: placeholder_texture_image.height(),
-        };
-        tubereng_renderer::renderer_init(&mut self.ecs, window, &placeholder_texture_descriptor)
+        let placeholder_texture_image = placeholder_texture_image();
+        let placeholder_texture_descriptor = texture_descriptor(&placeholder_texture_image);
+        self.take_renderer_builder()
+            .init(&mut self.ecs, window, &placeholder_texture_descriptor)
             .await;
     }
 
-    /// Updates the state of the engine
+    /// Resizes the render target to the new inner size of the window
+    pub fn on_window_resized(&mut self, width: u32, height: u32) {
+        if let Some(mut window) = self.ecs.resource_mut::<Window>() {
+            window.on_resized(width, height);
+        }
+        if let Some(mut gfx) = self.ecs.resource_mut::<GraphicsState>() {
+            gfx.resize(width, height);
+        }
+    }
+
+    /// Initializes the renderer without a window, rendering frames into an
+    /// offscreen texture of the given size.
+    ///
+    /// Returns `false` if no graphics adapter is available.
+    pub async fn init_headless_graphics(&mut self, width: u32, height: u32) -> bool {
+        let placeholder_texture_image = placeholder_texture_image();
+        let placeholder_texture_descriptor = texture_descriptor(&placeholder_texture_image);
+        self.take_renderer_builder()
+            .init_headless(
+                &mut self.ecs,
+                width,
+                height,
+                &placeholder_texture_descriptor,
+            )
+            .await
+    }
+
+    fn take_renderer_builder(&mut self) -> RendererBuilder {
+        self.ecs
+            .remove_resource::<RendererBuilder>()
+            .unwrap_or_default()
+    }
+
+    /// Initializes the engine without a window nor graphics device, e.g. to
+    /// test the gameplay in CI. The systems of the renderer that don't need
+    /// the graphics device run, the rendering being skipped: the systems
+    /// using the `GraphicsState` resource must take it as an `Option`.
+    pub fn init_headless(&mut self) {
+        tubereng_renderer::register_simulation_systems(&mut self.ecs);
+    }
+
+    /// Reads back the last frame rendered without a window, as its width,
+    /// its height and its tightly packed RGBA8 rows. Returns `None` if the
+    /// graphics haven't been initialized with
+    /// [`Engine::init_headless_graphics`].
+    #[must_use]
+    pub fn read_headless_frame(&self) -> Option<(u32, u32, Vec<u8>)> {
+        let gfx = self.ecs.resource::<GraphicsState>()?;
+        let window_size = gfx.window_size();
+        let pixels = gfx.read_offscreen_target()?;
+        Some((window_size.width, window_size.height, pixels))
+    }
+
+    /// Updates the state of the engine, running the systems of the active
+    /// worlds
     pub fn update(&mut self, delta_time: f32) {
-        self.ecs.insert_resource(DeltaTime(delta_time));
-        self.ecs.clear_dirty_flags();
-        if !self.init_system_ran {
-            self.ecs.run_single_run_system(&self.init_system);
-            self.init_system_ran = true;
+        if self.main_world_active {
+            begin_world_update(&mut self.ecs, delta_time);
+        }
+        if !self.post_startup_ran {
+            for system in &self.post_startup_systems {
+                self.ecs.run_single_run_system(system);
+            }
+            self.post_startup_ran = true;
+        }
+        if self.main_world_active {
+            self.ecs.run_systems();
+            diagnostics::update_diagnostics(&self.ecs, delta_time);
+        }
+        for world in self.worlds.iter_mut().filter(|world| world.active) {
+            begin_world_update(&mut world.ecs, delta_time);
+            world.ecs.run_systems();
+        }
+    }
+
+    /// Updates the engine `frame_count` times with a constant delta time,
+    /// e.g. to simulate a few seconds of gameplay in a test
+    pub fn run_frames(&mut self, frame_count: u32, delta_time: f32) {
+        for _ in 0..frame_count {
+            self.update(delta_time);
+        }
+    }
+
+    /// Adds a world ticked by the engine after the main world while it is
+    /// active, e.g. a loading world or an isolated menu. The world doesn't
+    /// share the resources of the main world. Replaces the world with the
+    /// same name if any.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `name` is [`MAIN_WORLD`]
+    pub fn add_world(&mut self, name: &'static str, ecs: Ecs) {
+        assert_ne!(name, MAIN_WORLD, "The main world can't be replaced");
+        self.remove_world(name);
+        self.worlds.push(World {
+            name,
+            ecs,
+            active: true,
+        });
+    }
+
+    /// Removes a world added with [`Engine::add_world`]
+    pub fn remove_world(&mut self, name: &str) -> Option<Ecs> {
+        let index = self.worlds.iter().position(|world| world.name == name)?;
+        Some(self.worlds.remove(index).ecs)
+    }
+
+    #[must_use]
+    pub fn world(&self, name: &str) -> Option<&Ecs> {
+        if name == MAIN_WORLD {
+            return Some(&self.ecs);
+        }
+        self.worlds
+            .iter()
+            .find(|world| world.name == name)
+            .map(|world| &world.ecs)
+    }
+
+    pub fn world_mut(&mut self, name: &str) -> Option<&mut Ecs> {
+        if name == MAIN_WORLD {
+            return Some(&mut self.ecs);
+        }
+        self.worlds
+            .iter_mut()
+            .find(|world| world.name == name)
+            .map(|world| &mut world.ecs)
+    }
+
+    /// Sets whether the systems of a world run on update. Returns `false` if
+    /// the world doesn't exist.
+    pub fn set_world_active(&mut self, name: &str, active: bool) -> bool {
+        if name == MAIN_WORLD {
+            self.main_world_active = active;
+            return true;
+        }
+        let Some(world) = self.worlds.iter_mut().find(|world| world.name == name) else {
+            return false;
+        };
+        world.active = active;
+        true
+    }
+
+    #[must_use]
+    pub fn is_world_active(&self, name: &str) -> bool {
+        if name == MAIN_WORLD {
+            return self.main_world_active;
+        }
+        self.worlds
+            .iter()
+            .any(|world| world.name == name && world.active)
+    }
+
+    /// Moves an entity and its descendants from a world to another, see
+    /// `Storage::move_entities`. Returns the id of the entity in the
+    /// destination world, or `None` if a world or the entity doesn't exist.
+    pub fn move_entity_tree(
+        &mut self,
+        entity_id: EntityId,
+        from: &str,
+        to: &str,
+    ) -> Option<EntityId> {
+        let (source, destination) = self.world_pair_mut(from, to)?;
+        source.move_entity_tree(entity_id, destination)
+    }
+
+    fn world_pair_mut(&mut self, first: &str, second: &str) -> Option<(&mut Ecs, &mut Ecs)> {
+        if first == second {
+            return None;
+        }
+        let mut first_world = None;
+        let mut second_world = None;
+        let worlds = std::iter::once((MAIN_WORLD, &mut self.ecs)).chain(
+            self.worlds
+                .iter_mut()
+                .map(|world| (world.name, &mut world.ecs)),
+        );
+        for (name, ecs) in worlds {
+            if name == first {
+                first_world = Some(ecs);
+            } else if name == second {
+                second_world = Some(ecs);
+            }
         }
-        self.ecs.run_systems();
+        Some((first_world?, second_world?))
     }
 
     /// Handles the input
@@ -75,6 +279,7 @@ impl Engine {
     /// Will panic if
     /// - the ``InputState`` is missing from the engine resources
     /// - the ``gui::Context`` is missing from the engine resources
+    #[allow(clippy::needless_pass_by_value)] // The inputs are built by the runners
     pub fn on_input(&mut self, input: Input) {
         let mut input_state = self
             .ecs
@@ -83,15 +288,154 @@ impl Engine {
         input_state.on_input(&input);
     }
 
+    /// Starts capturing the default microphone, the captured samples are
+    /// available each frame in `InputState::microphone`.
+    ///
+    /// Returns `false` if the capture couldn't be started, in which case the
+    /// microphone permission is set to denied. On wasm the permission stays
+    /// pending until the user answers the browser prompt.
+    #[cfg(feature = "microphone")]
+    pub fn start_microphone_capture(&mut self) -> bool {
+        use tubereng_input::microphone::{Capture, Permission};
+
+        let capture = Capture::start();
+        if let Err(error) = &capture {
+            log::warn!("Failed to start the microphone capture: {error:?}");
+        }
+        if let Some(mut input_state) = self.ecs.resource_mut::<InputState>() {
+            input_state.microphone.on_permission_changed(
+                capture
+                    .as_ref()
+                    .map_or(Permission::Denied, Capture::permission),
+            );
+        }
+        let Ok(capture) = capture else {
+            return false;
+        };
+
+        self.ecs.insert_non_send_resource(capture);
+        self.ecs
+            .register_system(&system::stages::StartFrame, poll_microphone_capture_system);
+        true
+    }
+
     #[must_use]
     pub fn application_title(&self) -> &'static str {
         self.application_title
     }
+
+    #[must_use]
+    pub fn window_settings(&self) -> &WindowSettings {
+        &self.window_settings
+    }
+
+    /// Returns the title the window is created with, the application title
+    /// unless set in the [`WindowSettings`]. It can be changed at runtime
+    /// through the [`Window`] resource.
+    #[must_use]
+    pub fn window_title(&self) -> &str {
+        self.window_settings
+            .title
+            .as_deref()
+            .unwrap_or(self.application_title)
+    }
+
+    /// Loads the icon of the window set in the [`WindowSettings`], for the
+    /// runner to apply when it creates the window
+    #[must_use]
+    pub fn load_window_icon(&self) -> Option<Image> {
+        let icon_path = self.window_settings.icon.as_deref()?;
+        let asset_store = self.ecs.resource::<AssetStore>()?;
+        asset_store
+            .load_without_storing::<Image>(icon_path)
+            .inspect_err(|error| log::warn!("Failed to load the window icon: {error:?}"))
+            .ok()
+    }
+
+    /// Maximum number of frames per second the runner updates the engine
+    /// at, `None` if the frame rate isn't limited
+    #[must_use]
+    pub fn frame_rate_limit(&self) -> Option<f32> {
+        self.frame_rate_limit
+    }
+
+    /// Records the time the runner spent updating the engine during the last
+    /// frame and waiting before it to honor the frame rate limit, in seconds,
+    /// see [`FrameTiming`]
+    pub fn on_frame_paced(&mut self, work_time: f32, wait_time: f32) {
+        if let Some(mut frame_timing) = self.ecs.resource_mut::<FrameTiming>() {
+            frame_timing.record_pacing(work_time, wait_time);
+        }
+    }
+
+    #[must_use]
+    pub fn ecs(&self) -> &Ecs {
+        &self.ecs
+    }
+
+    pub fn ecs_mut(&mut self) -> &mut Ecs {
+        &mut self.ecs
+    }
+
+    /// Returns whether a system of the main world requested the application
+    /// to exit through the [`AppExit`] resource
+    #[must_use]
+    pub fn exit_requested(&self) -> bool {
+        self.ecs
+            .resource::<AppExit>()
+            .is_some_and(|app_exit| app_exit.is_requested())
+    }
+
+    /// Returns the announcements made through the [`Accessibility`] resource
+    /// since the last call, for the runner to forward to screen readers
+    pub fn drain_announcements(&mut self) -> Vec<Announcement> {
+        self.ecs
+            .resource_mut::<Accessibility>()
+            .map(|mut accessibility| accessibility.drain_announcements().collect())
+            .unwrap_or_default()
+    }
+
+    /// Backs the [`Clipboard`] resource of the main world with the clipboard
+    /// of the platform
+    pub fn set_clipboard_backend(&mut self, backend: impl ClipboardBackend + 'static) {
+        if let Some(mut clipboard) = self.ecs.resource_mut::<Clipboard>() {
+            clipboard.set_backend(backend);
+        }
+    }
+
+    /// Plays the rumbles of the [`Haptics`] resource of the main world on the
+    /// gamepads of the platform
+    pub fn set_haptics_backend(&mut self, backend: impl HapticsBackend + 'static) {
+        if let Some(mut haptics) = self.ecs.resource_mut::<Haptics>() {
+            haptics.set_backend(backend);
+        }
+    }
+
+    /// Returns the changes of the window requested through the [`Window`]
+    /// resource since the last call, for the runner to apply
+    pub fn drain_window_commands(&mut self) -> Vec<WindowCommand> {
+        self.ecs
+            .resource_mut::<Window>()
+            .map(|mut window| window.drain_commands().collect())
+            .unwrap_or_default()
+    }
 }
 
 pub struct EngineBuilder {
     application_title: &'static str,
-    init_system: Option<system::System>,
+    startup_systems: Vec<System>,
+    post_startup_systems: Vec<System>,
+    plugins: Vec<Box<dyn Plugin>>,
+    system_panic_policy: system::PanicPolicy,
+    system_error_handler: system::ErrorHandler,
+    component_audit_enabled: bool,
+    diagnostics_enabled: bool,
+    renderer_plugins: Vec<Box<dyn RendererPlugin>>,
+    renderer_settings: RendererSettings,
+    window_settings: WindowSettings,
+    job_frame_budget: std::time::Duration,
+    fixed_timestep: f32,
+    frame_rate_limit: Option<f32>,
 }
 
 impl EngineBuilder {
@@ -100,11 +444,120 @@ impl EngineBuilder {
         self
     }
 
+    /// Registers a plugin installing resources and systems, built after the
+    /// plugins of the engine in the order of registration
+    pub fn with_plugin<P>(&mut self, plugin: P) -> &mut Self
+    where
+        P: 'static + Plugin,
+    {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// Registers a system running once in the [`StartupPhase::PostStartup`]
+    /// phase, see [`EngineBuilder::with_startup_system`]
     pub fn with_init_system<F, A>(&mut self, init_system: F) -> &mut Self
     where
         F: 'static + system::Into<A>,
     {
-        self.init_system = Some(init_system.into_system());
+        self.with_startup_system(StartupPhase::PostStartup, init_system)
+    }
+
+    /// Registers a system running once during a phase of the startup, the
+    /// systems of a phase running in the order of registration. The commands
+    /// queued by a startup system are applied before the next one runs.
+    pub fn with_startup_system<F, A>(&mut self, phase: StartupPhase, system: F) -> &mut Self
+    where
+        F: 'static + system::Into<A>,
+    {
+        let system = system.into_system();
+        match phase {
+            StartupPhase::Startup => self.startup_systems.push(system),
+            StartupPhase::PostStartup => self.post_startup_systems.push(system),
+        }
+        self
+    }
+
+    /// Sets what happens when a system panics, see [`system::PanicPolicy`]
+    pub fn with_system_panic_policy(&mut self, panic_policy: system::PanicPolicy) -> &mut Self {
+        self.system_panic_policy = panic_policy;
+        self
+    }
+
+    /// Sets how the errors returned by the fallible systems are handled, e.g.
+    /// [`system::log_error`], [`system::ignore_error`] or
+    /// [`system::panic_on_error`]
+    pub fn with_system_error_handler(&mut self, error_handler: system::ErrorHandler) -> &mut Self {
+        self.system_error_handler = error_handler;
+        self
+    }
+
+    /// Records which system last mutably accessed each component, for
+    /// debugging, see [`Ecs::set_component_audit_enabled`]
+    pub fn with_component_audit(&mut self, enabled: bool) -> &mut Self {
+        self.component_audit_enabled = enabled;
+        self
+    }
+
+    /// Measures the performance of the engine in the
+    /// [`diagnostics::Diagnostics`] resource, timing every system of the main
+    /// world
+    pub fn with_diagnostics(&mut self, enabled: bool) -> &mut Self {
+        self.diagnostics_enabled = enabled;
+        self
+    }
+
+    /// Registers a plugin adding custom passes and pipelines to the renderer
+    /// once the graphics are initialized
+    pub fn with_renderer_plugin<P>(&mut self, plugin: P) -> &mut Self
+    where
+        P: 'static + RendererPlugin,
+    {
+        self.renderer_plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// Sets the settings used to pick the graphics adapter, see
+    /// [`RendererSettings`]
+    pub fn with_renderer_settings(&mut self, renderer_settings: RendererSettings) -> &mut Self {
+        self.renderer_settings = renderer_settings;
+        self
+    }
+
+    /// Sets the size, title and mode of the window created by the runner, see
+    /// [`WindowSettings`]
+    pub fn with_window_settings(&mut self, window_settings: WindowSettings) -> &mut Self {
+        self.window_settings = window_settings;
+        self
+    }
+
+    /// Sets the time the [`tubereng_core::scheduler::JobScheduler`] spends running background jobs each
+    /// frame
+    pub fn with_job_frame_budget(&mut self, frame_budget: std::time::Duration) -> &mut Self {
+        self.job_frame_budget = frame_budget;
+        self
+    }
+
+    /// Sets the time step of the [`system::stages::FixedUpdate`] stage in seconds,
+    /// see [`FixedTime`]
+    pub fn with_fixed_timestep(&mut self, fixed_timestep: f32) -> &mut Self {
+        self.fixed_timestep = fixed_timestep;
+        self
+    }
+
+    /// Limits the number of frames per second the runner updates and renders
+    /// the engine at, e.g. to save power. The frame rate isn't limited by
+    /// default, besides vertical synchronization.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the frame rate limit isn't positive
+    pub fn with_frame_rate_limit(&mut self, max_frame_rate: f32) -> &mut Self {
+        assert!(
+            max_frame_rate > 0.0,
+            "The frame rate limit must be positive"
+        );
+        self.frame_rate_limit = Some(max_frame_rate);
         self
     }
 
@@ -113,21 +566,47 @@ impl EngineBuilder {
         VFS: 'static + VirtualFileSystem,
     {
         let mut ecs = Ecs::new();
-        ecs.insert_resource(InputState::new());
-        ecs.insert_resource(TransformCache::new());
-        ecs.define_relationship::<ChildOf>();
-        ecs.insert_resource(AssetStore::new(fs));
-        ecs.register_system(&stages::Render, compute_effective_transforms_system);
-
-        let init_system = self
-            .init_system
-            .take()
-            .unwrap_or(system::Into::<()>::into_system(system::Noop));
+        ecs.set_system_panic_policy(self.system_panic_policy);
+        ecs.set_system_error_handler(self.system_error_handler);
+        ecs.set_component_audit_enabled(self.component_audit_enabled);
+        if self.diagnostics_enabled {
+            ecs.set_system_timing_enabled(true);
+            ecs.insert_resource(diagnostics::Diagnostics::new());
+        }
+        ecs.add_plugin(&plugins::CorePlugin {
+            job_frame_budget: self.job_frame_budget,
+            fixed_timestep: self.fixed_timestep,
+        });
+        ecs.add_plugin(&plugins::InputPlugin);
+        ecs.add_plugin(&plugins::WindowPlugin {
+            settings: &self.window_settings,
+            application_title: self.application_title,
+        });
+        ecs.add_plugin(&plugins::AssetPlugin::new(fs, self.application_title));
+        ecs.add_plugin(&plugins::GraphicsPlugin::new(
+            std::mem::take(&mut self.renderer_plugins),
+            self.renderer_settings.clone(),
+            WindowSize {
+                width: self.window_settings.width,
+                height: self.window_settings.height,
+            },
+        ));
+        for plugin in &self.plugins {
+            ecs.add_plugin(plugin.as_ref());
+        }
+
+        for system in std::mem::take(&mut self.startup_systems) {
+            ecs.run_single_run_system(&system);
+        }
         Engine {
             application_title: self.application_title,
             ecs,
-            init_system,
-            init_system_ran: false,
+            main_world_active: true,
+            worlds: vec![],
+            post_startup_systems: std::mem::take(&mut self.post_startup_systems),
+            post_startup_ran: false,
+            window_settings: self.window_settings.clone(),
+            frame_rate_limit: self.frame_rate_limit,
         }
     }
 }
@@ -136,53 +615,72 @@ impl Default for EngineBuilder {
     fn default() -> Self {
         Self {
             application_title: "Tuber application",
-            init_system: None,
+            startup_systems: vec![],
+            post_startup_systems: vec![],
+            plugins: vec![],
+            system_panic_policy: system::PanicPolicy::default(),
+            system_error_handler: system::log_error,
+            component_audit_enabled: false,
+            diagnostics_enabled: false,
+            renderer_plugins: vec![],
+            renderer_settings: RendererSettings::default(),
+            window_settings: WindowSettings::default(),
+            job_frame_budget: tubereng_core::scheduler::DEFAULT_FRAME_BUDGET,
+            fixed_timestep: tubereng_core::time::DEFAULT_FIXED_TIMESTEP,
+            frame_rate_limit: None,
         }
     }
 }
 
-fn compute_effective_transforms_system(storage: &Storage) {
-    let Some(child_of_relationship) = storage.relationship::<ChildOf>() else {
-        return;
+/// Describes an image loaded by the image loader as a texture to upload with
+/// `GraphicsState::load_texture`
+///
+/// # Panics
+///
+/// Will panic if the image format isn't supported by the renderer
+#[must_use]
+pub fn texture_descriptor(image: &Image) -> texture::Descriptor<'_> {
+    let format = match image.format() {
+        ImageFormat::RGBA8 => texture::Format::Rgba8,
+        ImageFormat::BC1 => texture::Format::Bc1,
+        ImageFormat::BC3 => texture::Format::Bc3,
+        ImageFormat::BC7 => texture::Format::Bc7,
+        format => unimplemented!("Unsupported image format {format:?}"),
     };
-
-    let mut dirty_transform_entities = vec![];
-    let mut to_visit = child_of_relationship.leaves(storage.next_entity_id());
-
-    while let Some(entity_to_visit) = to_visit.pop() {
-        if storage.dirty_state::<Transform>(entity_to_visit) {
-            dirty_transform_entities.push(entity_to_visit);
-            dirty_transform_entities
-                .extend(child_of_relationship.ancestors(entity_to_visit).iter());
-        } else {
-            let children = child_of_relationship.sources(entity_to_visit);
-            to_visit.extend(children.iter().flat_map(|i| i.iter()));
-        }
+    texture::Descriptor {
+        data: image.data(),
+        width: image.width(),
+        height: image.height(),
+        premultiplied_alpha: image.premultiplied_alpha(),
+        format,
     }
+}
 
-    let mut transform_cache = storage
-        .resource_mut::<TransformCache>()
-        .expect("A TransformCache resource should be present");
-    while let Some(entity_id) = dirty_transform_entities.pop() {
-        let parents = child_of_relationship.successors(entity_id);
-
-        let mut matrix = storage
-            .component::<Transform>(entity_id)
-            .unwrap()
-            .as_matrix4();
-
-        for parent in parents {
-            let parent_matrix = storage
-                .component::<Transform>(parent)
-                .map_or_else(Matrix4f::identity, Transform::as_matrix4);
-
-            matrix = parent_matrix * matrix;
-        }
+fn placeholder_texture_image() -> Image {
+    // SAFETY: The placeholder image is a valid PNG file that is loaded at compile time
+    unsafe { ImageLoader::load(include_bytes!("../res/placeholder.png")).unwrap_unchecked() }
+}
 
-        transform_cache.set(entity_id, matrix);
+#[cfg(feature = "microphone")]
+fn poll_microphone_capture_system(
+    capture: tubereng_ecs::non_send::NonSend<tubereng_input::microphone::Capture>,
+    mut input_state: system::ResMut<InputState>,
+) {
+    capture.poll(&mut input_state.microphone);
+    std::mem::drop(capture);
+}
 
-        if let Some(children) = child_of_relationship.sources(entity_id) {
-            dirty_transform_entities.extend(children.iter());
-        }
+/// Advances the time of a world and clears its dirty flags before its
+/// systems run
+fn begin_world_update(ecs: &mut Ecs, delta_time: f32) {
+    let mut time = ecs.remove_resource::<Time>().unwrap_or_default();
+    time.advance(delta_time);
+    if let Some(mut fixed_time) = ecs.resource_mut::<FixedTime>() {
+        fixed_time.accumulate(time.delta());
+    }
+    ecs.insert_resource(time);
+    if let Some(mut frame_timing) = ecs.resource_mut::<FrameTiming>() {
+        frame_timing.record_frame(delta_time);
     }
+    ecs.clear_dirty_flags();
 }
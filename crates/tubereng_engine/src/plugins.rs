@@ -0,0 +1,316 @@
+use std::cell::Cell;
+
+use tubereng_asset::{settings::SettingsStore, vfs::VirtualFileSystem, AssetStore};
+use tubereng_core::{
+    accessibility::Accessibility,
+    arena::FrameArena,
+    clipboard::Clipboard,
+    exit::AppExit,
+    interpolation::InterpolatedTransform,
+    scheduler::JobScheduler,
+    time::{FixedTime, FrameTiming, Time},
+    verlet::VerletBody,
+    Transform, TransformCache,
+};
+use tubereng_ecs::{
+    plugin::Plugin,
+    relationship::ChildOf,
+    system::{self, stages},
+    Ecs, EntityId, Storage,
+};
+use tubereng_input::{haptics::Haptics, InputState};
+use tubereng_math::matrix::{Identity, Matrix4f};
+use tubereng_renderer::{
+    adapter::RendererSettings,
+    plugin::RendererPlugin,
+    sprite::{AnimatedSprite, Sprite},
+    RendererBuilder, WindowSize,
+};
+
+use crate::{
+    hot_reload::TextureAssets,
+    window::{Window, WindowSettings},
+};
+
+/// Installs the exit request, the clipboard, the scene stack, the frame
+/// arena, the background jobs, the time, the fixed time step, the frame
+/// timing, the transform hierarchy and its interpolation, and the verlet
+/// bodies
+pub struct CorePlugin {
+    pub job_frame_budget: std::time::Duration,
+    pub fixed_timestep: f32,
+}
+
+impl Plugin for CorePlugin {
+    fn build(&self, ecs: &mut Ecs) {
+        ecs.insert_resource(AppExit::new());
+        ecs.insert_resource(Clipboard::new());
+        ecs.add_scene_stack();
+        ecs.insert_resource(TransformCache::new());
+        ecs.insert_resource(FrameArena::new());
+        ecs.insert_resource(JobScheduler::new(self.job_frame_budget));
+        ecs.insert_resource(Accessibility::new());
+        ecs.insert_resource(Time::new());
+        ecs.insert_resource(FixedTime::new(self.fixed_timestep));
+        ecs.insert_resource(FrameTiming::new());
+        ecs.repeat_stage_while(&stages::FixedUpdate, expend_fixed_step);
+        ecs.define_relationship::<ChildOf>();
+        ecs.register_scene_component::<Transform>("Transform");
+        ecs.register_cloneable_component::<Transform>();
+        ecs.register_cloneable_component::<VerletBody>();
+        ecs.register_system(&stages::StartFrame, reset_frame_arena_system);
+        ecs.register_system(&stages::StartFrame, run_background_jobs_system);
+        // Registered first so that the transforms are recorded before the
+        // other systems of the fixed step move the entities
+        ecs.register_system(&stages::FixedUpdate, record_interpolated_transforms_system);
+        ecs.register_system(&stages::FixedUpdate, step_verlet_bodies_system);
+        ecs.register_system(&stages::Render, compute_effective_transforms_system);
+    }
+}
+
+/// Installs the [`InputState`] fed by `Engine::on_input`, whose clock is
+/// advanced at the start of each frame and whose inputs of the frame are
+/// cleared at its end, and the [`Haptics`] whose rumbles are played at the
+/// end of each frame
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, ecs: &mut Ecs) {
+        ecs.insert_resource(InputState::new());
+        ecs.register_system(&stages::StartFrame, advance_input_time_system);
+        ecs.register_system(&stages::FinalizeRender, clear_last_frame_inputs_system);
+        ecs.insert_resource(Haptics::new());
+        ecs.register_system(&stages::FinalizeRender, flush_haptics_system);
+    }
+}
+
+/// Installs the [`Window`] resource controlling the window created by the
+/// runner
+pub struct WindowPlugin<'a> {
+    pub settings: &'a WindowSettings,
+    pub application_title: &'a str,
+}
+
+impl Plugin for WindowPlugin<'_> {
+    fn build(&self, ecs: &mut Ecs) {
+        ecs.insert_resource(Window::new(self.settings, self.application_title));
+        ecs.register_system(&stages::StartFrame, reset_cursor_icon_system);
+    }
+}
+
+/// Installs the [`AssetStore`] reading from a virtual file system, the
+/// [`TextureAssets`] and the [`SettingsStore`] of the application. The
+/// pending assets are loaded at the start of each frame once fetched, and
+/// the assets whose handles have all been dropped are unloaded at the end of
+/// each frame. With the `hot-reload` feature, the modified assets are
+/// reloaded at the start of each frame.
+pub struct AssetPlugin<VFS> {
+    // The file system is moved into the asset store, so the plugin can only
+    // be built once
+    fs: Cell<Option<VFS>>,
+    application_title: &'static str,
+}
+
+impl<VFS> AssetPlugin<VFS> {
+    pub fn new(fs: VFS, application_title: &'static str) -> Self {
+        Self {
+            fs: Cell::new(Some(fs)),
+            application_title,
+        }
+    }
+}
+
+impl<VFS> Plugin for AssetPlugin<VFS>
+where
+    VFS: 'static + VirtualFileSystem,
+{
+    /// # Panics
+    ///
+    /// Will panic if the plugin has already been built
+    fn build(&self, ecs: &mut Ecs) {
+        let fs = self
+            .fs
+            .take()
+            .expect("The asset plugin should only be built once");
+        ecs.insert_resource(AssetStore::new(fs));
+        ecs.insert_resource(TextureAssets::default());
+        ecs.insert_resource(SettingsStore::new(self.application_title));
+        ecs.register_system(
+            &stages::StartFrame,
+            crate::hot_reload::load_pending_assets_system,
+        );
+        ecs.register_system(
+            &stages::FinalizeRender,
+            crate::hot_reload::free_unused_assets_system,
+        );
+        #[cfg(all(feature = "hot-reload", not(target_arch = "wasm32")))]
+        ecs.register_system(
+            &stages::StartFrame,
+            crate::hot_reload::reload_modified_assets_system,
+        );
+    }
+}
+
+/// Installs the rendering: registers the cloneable sprite components and
+/// inserts the [`RendererBuilder`] configured with the renderer plugins and
+/// settings of the application. The graphics device being created
+/// asynchronously once the window exists, the builder is consumed by
+/// `Engine::init_graphics` or `Engine::init_headless_graphics`.
+pub struct GraphicsPlugin {
+    // The renderer plugins are moved into the builder, so the plugin can only
+    // be built once
+    renderer_plugins: Cell<Vec<Box<dyn RendererPlugin>>>,
+    settings: RendererSettings,
+    window_size: WindowSize,
+}
+
+impl GraphicsPlugin {
+    #[must_use]
+    pub fn new(
+        renderer_plugins: Vec<Box<dyn RendererPlugin>>,
+        settings: RendererSettings,
+        window_size: WindowSize,
+    ) -> Self {
+        Self {
+            renderer_plugins: Cell::new(renderer_plugins),
+            settings,
+            window_size,
+        }
+    }
+}
+
+impl Plugin for GraphicsPlugin {
+    fn build(&self, ecs: &mut Ecs) {
+        ecs.register_cloneable_component::<Sprite>();
+        ecs.register_cloneable_component::<AnimatedSprite>();
+        ecs.insert_resource(
+            RendererBuilder::new()
+                .with_boxed_plugins(self.renderer_plugins.take())
+                .with_settings(self.settings.clone())
+                .with_window_size(self.window_size),
+        );
+    }
+}
+
+fn reset_frame_arena_system(mut arena: system::ResMut<FrameArena>) {
+    arena.reset();
+}
+
+fn reset_cursor_icon_system(mut window: system::ResMut<Window>) {
+    window.reset_cursor_icon();
+}
+
+fn advance_input_time_system(mut input_state: system::ResMut<InputState>, time: system::Res<Time>) {
+    input_state.advance_time(std::time::Duration::from_secs_f32(time.raw_delta()));
+    std::mem::drop(time);
+}
+
+fn clear_last_frame_inputs_system(mut input_state: system::ResMut<InputState>) {
+    input_state.clear_last_frame_inputs();
+}
+
+fn flush_haptics_system(mut haptics: system::ResMut<Haptics>) {
+    haptics.flush();
+}
+
+fn run_background_jobs_system(mut scheduler: system::ResMut<JobScheduler>) {
+    scheduler.run();
+}
+
+/// Runs the [`stages::FixedUpdate`] stage once per fixed step accumulated
+/// in [`FixedTime`]
+fn expend_fixed_step(storage: &Storage) -> bool {
+    storage
+        .resource_mut::<FixedTime>()
+        .is_some_and(|mut fixed_time| fixed_time.expend())
+}
+
+fn step_verlet_bodies_system(
+    fixed_time: system::Res<FixedTime>,
+    mut query_bodies: system::Q<&mut VerletBody>,
+) {
+    for mut body in query_bodies.iter() {
+        body.step(fixed_time.timestep());
+    }
+    std::mem::drop(fixed_time);
+}
+
+fn record_interpolated_transforms_system(
+    mut query_interpolated: system::Q<(&Transform, &mut InterpolatedTransform)>,
+) {
+    for (transform, mut interpolated) in query_interpolated.iter() {
+        interpolated.record(transform);
+    }
+}
+
+/// Returns the matrix of the transform of an entity relative to its parent,
+/// interpolated between the last fixed steps if it has an
+/// [`InterpolatedTransform`]
+fn local_matrix(
+    storage: &Storage,
+    query_interpolated: &system::Q<(&Transform, &InterpolatedTransform)>,
+    entity_id: EntityId,
+    alpha: f32,
+) -> Option<Matrix4f> {
+    if let Some((transform, interpolated)) = query_interpolated.get(entity_id) {
+        return Some(interpolated.interpolate(transform, alpha).as_matrix4());
+    }
+    storage
+        .component::<Transform>(entity_id)
+        .map(Transform::as_matrix4)
+}
+
+fn compute_effective_transforms_system(
+    mut query_interpolated: system::Q<(&Transform, &InterpolatedTransform)>,
+    storage: &Storage,
+) {
+    let Some(child_of_relationship) = storage.relationship::<ChildOf>() else {
+        return;
+    };
+
+    let arena = storage
+        .resource::<FrameArena>()
+        .expect("A FrameArena resource should be present");
+    let mut dirty_transform_entities = arena.vec();
+    // The interpolated transforms change every frame
+    dirty_transform_entities.extend(query_interpolated.iter_with_ids().map(|(id, _)| id));
+    let alpha = storage
+        .resource::<FixedTime>()
+        .map_or(1.0, |fixed_time| fixed_time.alpha());
+    let mut to_visit = child_of_relationship.leaves(storage.next_entity_id());
+
+    while let Some(entity_to_visit) = to_visit.pop() {
+        if storage.dirty_state::<Transform>(entity_to_visit) {
+            dirty_transform_entities.push(entity_to_visit);
+            dirty_transform_entities
+                .extend(child_of_relationship.ancestors(entity_to_visit).iter());
+        } else {
+            let children = child_of_relationship.sources(entity_to_visit);
+            to_visit.extend(children.iter().flat_map(|i| i.iter()));
+        }
+    }
+
+    let mut transform_cache = storage
+        .resource_mut::<TransformCache>()
+        .expect("A TransformCache resource should be present");
+    while let Some(entity_id) = dirty_transform_entities.pop() {
+        let parents = child_of_relationship.successors(entity_id);
+
+        let Some(mut matrix) = local_matrix(storage, &query_interpolated, entity_id, alpha) else {
+            continue;
+        };
+
+        for parent in parents {
+            let parent_matrix = local_matrix(storage, &query_interpolated, parent, alpha)
+                .unwrap_or_else(Matrix4f::identity);
+
+            matrix = parent_matrix * matrix;
+        }
+
+        transform_cache.set(entity_id, matrix);
+
+        if let Some(children) = child_of_relationship.sources(entity_id) {
+            dirty_transform_entities.extend(children.iter());
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use tubereng_asset::{scene::Scene, AssetHandle, AssetStore};
+use tubereng_ecs::{
+    commands::{Command, CommandQueue},
+    relationship::ChildOf,
+    scene::{Result, SceneError},
+    Ecs, EntityId,
+};
+
+/// Spawns the [`Scene`] assets in an ECS whose scene components have been
+/// registered with `Ecs::register_scene_component`
+pub trait SceneSpawner {
+    /// Inserts the entities of a scene, the entities having a parent in the
+    /// scene being linked to it with a [`ChildOf`] relationship. Returns the
+    /// ids of the entities in the order of the scene.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if a component of the scene isn't registered or
+    /// fails to deserialize, or if a parent isn't in the scene, in which case
+    /// no entity is inserted
+    fn spawn_scene(&mut self, scene: &Scene) -> Result<Vec<EntityId>>;
+}
+
+impl SceneSpawner for Ecs {
+    fn spawn_scene(&mut self, scene: &Scene) -> Result<Vec<EntityId>> {
+        let index_for_name = scene
+            .entities
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entity)| Some((entity.name.as_deref()?, index)))
+            .collect::<HashMap<_, _>>();
+        let parents = scene
+            .entities
+            .iter()
+            .map(|entity| {
+                entity
+                    .parent
+                    .as_deref()
+                    .map(|parent| {
+                        index_for_name.get(parent).copied().ok_or_else(|| {
+                            SceneError::UnknownParent {
+                                name: parent.to_string(),
+                            }
+                        })
+                    })
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Every component is deserialized before the entities are inserted
+        let mut entity_components = Vec::with_capacity(scene.entities.len());
+        for entity in &scene.entities {
+            let components = entity
+                .components
+                .iter()
+                .map(|(name, value)| self.deserialize_scene_component(name, value))
+                .collect::<Result<Vec<_>>>()?;
+            entity_components.push(components);
+        }
+
+        let entity_ids = entity_components
+            .into_iter()
+            .map(|components| self.insert(components))
+            .collect::<Vec<_>>();
+        for (&entity_id, parent) in entity_ids.iter().zip(parents) {
+            if let Some(parent) = parent {
+                self.insert_relationship::<ChildOf>(entity_id, entity_ids[parent]);
+            }
+        }
+        Ok(entity_ids)
+    }
+}
+
+/// Spawns the scenes loaded in the [`AssetStore`] resource from a
+/// [`CommandQueue`]
+pub trait SceneCommands {
+    /// Inserts the entities of a scene loaded in the [`AssetStore`]
+    /// resource after the other commands of the queue, see
+    /// [`SceneSpawner::spawn_scene`]. Errors are logged.
+    fn spawn_scene(&self, scene: AssetHandle<Scene>);
+}
+
+impl SceneCommands for CommandQueue {
+    fn spawn_scene(&self, scene: AssetHandle<Scene>) {
+        self.push_deferred_command(SpawnScene { scene });
+    }
+}
+
+pub struct SpawnScene {
+    scene: AssetHandle<Scene>,
+}
+
+impl Command for SpawnScene {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        // The scene is cloned as the asset store is borrowed from the storage
+        let scene = ecs
+            .resource::<AssetStore>()
+            .and_then(|asset_store| asset_store.get(&self.scene).cloned());
+        let result = scene
+            .ok_or(SceneError::SceneNotFound)
+            .and_then(|scene| ecs.spawn_scene(&scene));
+        if let Err(e) = result {
+            log::error!("Couldn't spawn scene {}: {e:?}", self.scene.id());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use tubereng_asset::vfs::VirtualFileSystem;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Position {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Faction {
+        Player,
+        Enemy { aggressive: bool },
+    }
+
+    /// Component referring to an asset, described in scenes by its path
+    #[derive(Debug, PartialEq)]
+    struct Label {
+        text: String,
+    }
+
+    #[derive(Deserialize)]
+    struct LabelDescription {
+        path: String,
+    }
+
+    struct Text(String);
+    impl tubereng_asset::Asset for Text {
+        type Loader = TextLoader;
+    }
+
+    struct TextLoader;
+    impl tubereng_asset::AssetLoader<Text> for TextLoader {
+        fn load(file_content: &[u8]) -> tubereng_asset::Result<Text> {
+            Ok(Text(String::from_utf8_lossy(file_content).into_owned()))
+        }
+    }
+
+    struct MockFS;
+    impl VirtualFileSystem for MockFS {
+        fn read_bytes(&self, path: &str) -> tubereng_asset::Result<Vec<u8>> {
+            Ok(path
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .as_bytes()
+                .to_vec())
+        }
+    }
+
+    const SCENE: &str = r#"
+        Scene(
+            entities: [
+                (
+                    name: "player",
+                    components: {
+                        "Position": (x: 1, y: 2),
+                        "Faction": Player,
+                    },
+                ),
+                (
+                    parent: "player",
+                    components: {
+                        "Faction": Enemy(aggressive: true),
+                        "Label": (path: "greeting.txt"),
+                    },
+                ),
+            ],
+        )
+    "#;
+
+    fn scene_ecs() -> Ecs {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(AssetStore::new(MockFS));
+        ecs.register_scene_component::<Position>("Position");
+        ecs.register_scene_component::<Faction>("Faction");
+        ecs.register_scene_component_with("Label", |label: LabelDescription, storage| {
+            let asset_store = storage.resource::<AssetStore>()?;
+            let text = asset_store.load_without_storing::<Text>(&label.path).ok()?;
+            Some(Label { text: text.0 })
+        });
+        ecs
+    }
+
+    #[test]
+    fn spawn_scene() {
+        let mut ecs = scene_ecs();
+        let scene = ecs
+            .resource_mut::<AssetStore>()
+            .unwrap()
+            .store(Scene::from_ron(SCENE).unwrap());
+
+        ecs.command_queue().spawn_scene(scene);
+        ecs.run_systems();
+
+        assert_eq!(ecs.entity_count(), 2);
+        assert_eq!(ecs.component::<Position>(0), Some(&Position { x: 1, y: 2 }));
+        assert_eq!(ecs.component::<Faction>(0), Some(&Faction::Player));
+        assert_eq!(
+            ecs.component::<Faction>(1),
+            Some(&Faction::Enemy { aggressive: true })
+        );
+        assert_eq!(
+            ecs.component::<Label>(1),
+            Some(&Label {
+                text: "greeting.txt".into()
+            })
+        );
+        assert!(ecs.relationship::<ChildOf>().unwrap().contains(1, 0));
+    }
+
+    #[test]
+    fn spawn_scene_unregistered_component() {
+        let mut ecs = Ecs::new();
+        ecs.register_scene_component::<Position>("Position");
+
+        let result = ecs.spawn_scene(&Scene::from_ron(SCENE).unwrap());
+
+        assert!(matches!(
+            result,
+            Err(SceneError::UnregisteredComponent { name }) if name == "Faction"
+        ));
+        assert_eq!(ecs.entity_count(), 0);
+    }
+}
@@ -0,0 +1,218 @@
+/// Settings of the window the application runs in, applied by the runner
+/// when it creates the window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowSettings {
+    /// Title of the window, the application title if `None`
+    pub title: Option<String>,
+    /// Inner width of the window in physical pixels
+    pub width: u32,
+    /// Inner height of the window in physical pixels
+    pub height: u32,
+    pub resizable: bool,
+    pub fullscreen: FullscreenMode,
+    /// Path of the image asset shown as the icon of the window, e.g. in the
+    /// taskbar
+    pub icon: Option<String>,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            title: None,
+            width: 800,
+            height: 600,
+            resizable: false,
+            fullscreen: FullscreenMode::Windowed,
+            icon: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FullscreenMode {
+    #[default]
+    Windowed,
+    /// The window covers the whole monitor, without borders, keeping the
+    /// video mode of the monitor
+    Borderless,
+    /// The application takes exclusive control of the monitor, switching to
+    /// its best video mode. Falls back to borderless where unsupported, e.g.
+    /// on the web.
+    Exclusive,
+}
+
+/// Icon of the mouse cursor over the window, drawn by the platform
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CursorIcon {
+    /// Usually an arrow
+    #[default]
+    Default,
+    /// Usually a hand, shown over clickable elements
+    Pointer,
+    Crosshair,
+    /// Shown over editable text
+    Text,
+    Move,
+    Grab,
+    Grabbing,
+    NotAllowed,
+    Wait,
+    ResizeHorizontal,
+    ResizeVertical,
+    /// The cursor isn't shown, e.g. to draw it with a sprite instead
+    Hidden,
+}
+
+/// Change of the window requested by a system, applied by the runner at the
+/// end of the frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowCommand {
+    SetFullscreen(FullscreenMode),
+    SetTitle(String),
+    /// Resizes the inside of the window, in physical pixels
+    SetInnerSize {
+        width: u32,
+        height: u32,
+    },
+    /// Sets the size the window can't be resized below, in physical pixels,
+    /// or removes it if `None`
+    SetMinSize(Option<(u32, u32)>),
+    /// Moves the window to the center of its monitor
+    Center,
+    SetCursorIcon(CursorIcon),
+}
+
+/// Resource through which systems control the window of the application,
+/// e.g. to toggle the fullscreen from the options menu or to show the level
+/// in the title. The requests are applied by the runner once the frame is
+/// complete.
+///
+/// The cursor icon is reset at the start of each frame, so systems set it
+/// every frame it applies, e.g. while a button is hovered.
+#[derive(Debug, Default)]
+pub struct Window {
+    title: String,
+    inner_size: (u32, u32),
+    min_size: Option<(u32, u32)>,
+    fullscreen: FullscreenMode,
+    cursor_icon: CursorIcon,
+    applied_cursor_icon: CursorIcon,
+    commands: Vec<WindowCommand>,
+}
+
+impl Window {
+    /// Creates the window resource, titled `title` unless set in the
+    /// settings
+    #[must_use]
+    pub fn new(settings: &WindowSettings, title: &str) -> Self {
+        Self {
+            title: settings.title.as_deref().unwrap_or(title).to_string(),
+            inner_size: (settings.width, settings.height),
+            min_size: None,
+            fullscreen: settings.fullscreen,
+            cursor_icon: CursorIcon::Default,
+            applied_cursor_icon: CursorIcon::Default,
+            commands: vec![],
+        }
+    }
+
+    #[must_use]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        let title = title.into();
+        if title != self.title {
+            self.title.clone_from(&title);
+            self.commands.push(WindowCommand::SetTitle(title));
+        }
+    }
+
+    /// Returns the size of the inside of the window in physical pixels, as
+    /// last reported by the runner or requested
+    #[must_use]
+    pub fn inner_size(&self) -> (u32, u32) {
+        self.inner_size
+    }
+
+    /// Resizes the inside of the window, the render target following once
+    /// the runner reports the new size
+    pub fn set_inner_size(&mut self, width: u32, height: u32) {
+        self.inner_size = (width, height);
+        self.commands
+            .push(WindowCommand::SetInnerSize { width, height });
+    }
+
+    /// Records the size of the window reported by the runner, e.g. after the
+    /// user resized it
+    pub fn on_resized(&mut self, width: u32, height: u32) {
+        self.inner_size = (width, height);
+    }
+
+    #[must_use]
+    pub fn min_size(&self) -> Option<(u32, u32)> {
+        self.min_size
+    }
+
+    pub fn set_min_size(&mut self, min_size: Option<(u32, u32)>) {
+        if min_size != self.min_size {
+            self.min_size = min_size;
+            self.commands.push(WindowCommand::SetMinSize(min_size));
+        }
+    }
+
+    /// Moves the window to the center of its monitor. Has no effect in
+    /// fullscreen or on the web.
+    pub fn center(&mut self) {
+        self.commands.push(WindowCommand::Center);
+    }
+
+    /// Returns the fullscreen mode of the window, including the requested
+    /// changes not applied yet
+    #[must_use]
+    pub fn fullscreen(&self) -> FullscreenMode {
+        self.fullscreen
+    }
+
+    pub fn set_fullscreen(&mut self, fullscreen: FullscreenMode) {
+        if fullscreen != self.fullscreen {
+            self.fullscreen = fullscreen;
+            self.commands.push(WindowCommand::SetFullscreen(fullscreen));
+        }
+    }
+
+    /// Switches between windowed and borderless fullscreen
+    pub fn toggle_fullscreen(&mut self) {
+        self.set_fullscreen(match self.fullscreen {
+            FullscreenMode::Windowed => FullscreenMode::Borderless,
+            FullscreenMode::Borderless | FullscreenMode::Exclusive => FullscreenMode::Windowed,
+        });
+    }
+
+    /// Returns the cursor icon requested during the frame
+    #[must_use]
+    pub fn cursor_icon(&self) -> CursorIcon {
+        self.cursor_icon
+    }
+
+    pub fn set_cursor_icon(&mut self, cursor_icon: CursorIcon) {
+        self.cursor_icon = cursor_icon;
+    }
+
+    /// Resets the cursor icon before the systems of a frame run
+    pub fn reset_cursor_icon(&mut self) {
+        self.cursor_icon = CursorIcon::Default;
+    }
+
+    /// Returns the requested changes, in the order they have been made, the
+    /// change of cursor icon coming last
+    pub fn drain_commands(&mut self) -> impl Iterator<Item = WindowCommand> + '_ {
+        if self.cursor_icon != self.applied_cursor_icon {
+            self.applied_cursor_icon = self.cursor_icon;
+            self.commands
+                .push(WindowCommand::SetCursorIcon(self.cursor_icon));
+        }
+        self.commands.drain(..)
+    }
+}
@@ -0,0 +1,235 @@
+#![warn(clippy::pedantic)]
+
+//! TrueType and OpenType fonts, exposing the metrics of the glyphs and their
+//! rasterization, for the text rendering and the UI.
+//!
+//! Sizes are given in pixels per em, and the vertical offsets are relative
+//! to the baseline with y pointing down, as in the 2D pass.
+
+use ab_glyph::{Font as _, FontVec, GlyphId, PxScale};
+use tubereng_asset::{Asset, AssetError, AssetLoader};
+
+pub struct Font {
+    font: FontVec,
+    units_per_em: f32,
+}
+
+/// Vertical metrics of the lines of a font at a given size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    /// Distance from the baseline to the top of the highest glyphs
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of the lowest glyphs,
+    /// usually negative
+    pub descent: f32,
+    /// Space between the descent of a line and the ascent of the next one
+    pub line_gap: f32,
+}
+
+impl LineMetrics {
+    /// Distance between the baselines of two consecutive lines
+    #[must_use]
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
+
+/// Metrics of a glyph at a given size
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphMetrics {
+    /// Horizontal distance from the pen position of the glyph to the one of
+    /// the next glyph
+    pub advance_width: f32,
+    /// Horizontal distance from the pen position to the left of the outline
+    pub left_side_bearing: f32,
+}
+
+/// Coverage bitmap of a glyph
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RasterizedGlyph {
+    pub width: u32,
+    pub height: u32,
+    /// Horizontal offset of the left of the bitmap from the pen position
+    pub offset_x: i32,
+    /// Vertical offset of the top of the bitmap from the baseline
+    pub offset_y: i32,
+    /// Coverage of the pixels, from 0 to 255, row by row
+    pub coverage: Vec<u8>,
+}
+
+impl Font {
+    #[must_use]
+    pub fn units_per_em(&self) -> f32 {
+        self.units_per_em
+    }
+
+    #[must_use]
+    pub fn glyph_count(&self) -> usize {
+        self.font.glyph_count()
+    }
+
+    /// Returns true if the font has a glyph for the character. The metrics
+    /// and the rasterization of the missing characters are the ones of the
+    /// fallback glyph of the font.
+    #[must_use]
+    pub fn has_glyph(&self, character: char) -> bool {
+        self.font.glyph_id(character) != GlyphId(0)
+    }
+
+    /// Returns the line metrics at `size` pixels per em
+    #[must_use]
+    pub fn line_metrics(&self, size: f32) -> LineMetrics {
+        let scale = self.scale_factor(size);
+        LineMetrics {
+            ascent: self.font.ascent_unscaled() * scale,
+            descent: self.font.descent_unscaled() * scale,
+            line_gap: self.font.line_gap_unscaled() * scale,
+        }
+    }
+
+    /// Returns the metrics of the glyph of a character at `size` pixels per
+    /// em
+    #[must_use]
+    pub fn glyph_metrics(&self, character: char, size: f32) -> GlyphMetrics {
+        let scale = self.scale_factor(size);
+        let glyph_id = self.font.glyph_id(character);
+        GlyphMetrics {
+            advance_width: self.font.h_advance_unscaled(glyph_id) * scale,
+            left_side_bearing: self.font.h_side_bearing_unscaled(glyph_id) * scale,
+        }
+    }
+
+    /// Returns the adjustment of the advance width of `left` when followed by
+    /// `right`, at `size` pixels per em
+    #[must_use]
+    pub fn kerning(&self, left: char, right: char, size: f32) -> f32 {
+        self.font
+            .kern_unscaled(self.font.glyph_id(left), self.font.glyph_id(right))
+            * self.scale_factor(size)
+    }
+
+    /// Rasterizes the glyph of a character at `size` pixels per em, the pen
+    /// being at the origin. Returns `None` for the glyphs without outline,
+    /// e.g. spaces.
+    #[must_use]
+    pub fn rasterize(&self, character: char, size: f32) -> Option<RasterizedGlyph> {
+        let glyph = self.font.glyph_id(character).with_scale(PxScale::from(
+            size * self.font.height_unscaled() / self.units_per_em,
+        ));
+        let outlined_glyph = self.font.outline_glyph(glyph)?;
+        let bounds = outlined_glyph.px_bounds();
+        // The bounds are rounded to whole pixels
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let (width, height, offset_x, offset_y) = (
+            bounds.width() as u32,
+            bounds.height() as u32,
+            bounds.min.x as i32,
+            bounds.min.y as i32,
+        );
+        let mut coverage = vec![0; width as usize * height as usize];
+        outlined_glyph.draw(|x, y, pixel_coverage| {
+            if let Some(pixel) = coverage.get_mut(y as usize * width as usize + x as usize) {
+                // The coverage is clamped to [0, 1]
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let value = (pixel_coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+                *pixel = value;
+            }
+        });
+        Some(RasterizedGlyph {
+            width,
+            height,
+            offset_x,
+            offset_y,
+            coverage,
+        })
+    }
+
+    fn scale_factor(&self, size: f32) -> f32 {
+        size / self.units_per_em
+    }
+}
+
+impl Asset for Font {
+    type Loader = FontLoader;
+}
+
+/// Loads `.ttf` and `.otf` files, and the first font of `.ttc` collections
+pub struct FontLoader;
+impl AssetLoader<Font> for FontLoader {
+    fn load(file_content: &[u8]) -> tubereng_asset::Result<Font> {
+        let font = FontVec::try_from_vec(file_content.to_vec())
+            .map_err(|_| AssetError::FontDecodingFailed)?;
+        let units_per_em = font.units_per_em().ok_or(AssetError::FontDecodingFailed)?;
+        Ok(Font { font, units_per_em })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn demo_font() -> Font {
+        FontLoader::load(include_bytes!("../res/demo.ttf")).unwrap()
+    }
+
+    #[test]
+    fn load_font() {
+        let font = demo_font();
+        assert_eq!(font.glyph_count(), 2);
+        assert!(font.has_glyph('A'));
+        assert!(!font.has_glyph('B'));
+        assert!(matches!(
+            FontLoader::load(b"not a font"),
+            Err(AssetError::FontDecodingFailed)
+        ));
+    }
+
+    #[test]
+    fn metrics_are_scaled_to_the_size() {
+        let font = demo_font();
+        let units_per_em = font.units_per_em();
+        let line_metrics = font.line_metrics(units_per_em / 10.0);
+        assert_eq!(
+            line_metrics,
+            LineMetrics {
+                ascent: 102.4,
+                descent: -40.0,
+                line_gap: 0.0,
+            }
+        );
+        assert!((line_metrics.line_height() - 142.4).abs() < 1e-4);
+
+        assert_eq!(
+            font.glyph_metrics('A', units_per_em / 10.0),
+            GlyphMetrics {
+                advance_width: 54.0,
+                left_side_bearing: 0.6,
+            }
+        );
+        // Missing characters have the metrics of the fallback glyph
+        assert_eq!(
+            font.glyph_metrics('B', units_per_em),
+            GlyphMetrics {
+                advance_width: 600.0,
+                left_side_bearing: 100.0,
+            }
+        );
+    }
+
+    #[test]
+    fn rasterize_glyph() {
+        let font = demo_font();
+        let glyph = font.rasterize('A', 100.0).unwrap();
+        // The outline of A spans from (6, 0) to (541, 656) font units, the
+        // bounds being rounded outwards
+        assert_eq!((glyph.width, glyph.height), (55, 66));
+        assert_eq!((glyph.offset_x, glyph.offset_y), (0, -66));
+        assert_eq!(glyph.coverage.len(), 55 * 66);
+        let covered_pixels = glyph.coverage.iter().filter(|&&value| value > 128).count();
+        assert!(covered_pixels > 0 && covered_pixels < glyph.coverage.len() / 2);
+        // The bottom of the left leg is covered, the top corners aren't
+        assert!(glyph.coverage[65 * 55 + 2] > 128);
+        assert_eq!(glyph.coverage[0], 0);
+        assert_eq!(glyph.coverage[54], 0);
+    }
+}
@@ -0,0 +1,994 @@
+#![warn(clippy::pedantic)]
+
+//! glTF 2.0 loader, importing the meshes, materials, images and node
+//! hierarchy of 3D models, e.g. exported from Blender as `.glb` files.
+//!
+//! The buffers must be embedded in the asset, either in the binary chunk of a
+//! `.glb` file or as base64 data URIs in a `.gltf` file. Images can also be
+//! referenced by a path relative to the glTF file, see [`resolve_uri`].
+//!
+//! The nodes of a loaded [`Gltf`] are spawned with [`spawn_gltf`].
+
+use std::collections::HashSet;
+
+use base64::Engine as _;
+use log::warn;
+use tubereng_asset::{
+    json::Value, Asset, AssetError, AssetHandle, AssetLoader, AssetStore, Result,
+};
+use tubereng_core::Transform;
+use tubereng_ecs::{relationship::ChildOf, Ecs, EntityId};
+use tubereng_image::{Image, ImageLoader};
+use tubereng_math::{matrix::Matrix4f, quaternion::Quaternion, vector::Vector3f};
+use tubereng_renderer::{material, mesh, sampler, texture};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_HEADER_SIZE: usize = 12;
+const GLB_JSON_CHUNK: u32 = 0x4E4F_534A;
+const GLB_BIN_CHUNK: u32 = 0x004E_4942;
+const TRIANGLES_MODE: usize = 4;
+const NEAREST_FILTER: usize = 9728;
+const CLAMP_TO_EDGE_WRAPPING: usize = 33071;
+const MIRRORED_REPEAT_WRAPPING: usize = 33648;
+
+/// 3D model loaded from a `.gltf` or `.glb` file
+pub struct Gltf {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    pub images: Vec<ImageSource>,
+    pub nodes: Vec<Node>,
+    pub scenes: Vec<Scene>,
+    /// Scene to display when the model is loaded
+    pub default_scene: Option<usize>,
+}
+
+pub struct Mesh {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+}
+
+/// Part of a mesh drawn with a single material
+pub struct Primitive {
+    pub geometry: mesh::Descriptor,
+    /// Index of the material in [`Gltf::materials`], the default material
+    /// being used if `None`
+    pub material: Option<usize>,
+}
+
+pub enum ImageSource {
+    /// Image embedded in the glTF asset
+    Embedded(Image),
+    /// URI of the image relative to the glTF file, see [`resolve_uri`]
+    Uri(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    /// Fragments with an alpha below the cutoff are discarded, the other ones
+    /// being opaque
+    Mask {
+        cutoff: f32,
+    },
+    Blend,
+}
+
+/// Metallic-roughness material of a glTF asset, the texture indices being
+/// indices in [`Gltf::images`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub emissive_factor: [f32; 3],
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+    /// Sampler of the base color texture
+    pub sampler: sampler::Descriptor,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: None,
+            base_color_factor: [1.0; 4],
+            base_color_texture: None,
+            normal_texture: None,
+            emissive_factor: [0.0; 3],
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+            sampler: default_sampler(),
+        }
+    }
+}
+
+impl Material {
+    /// Returns the descriptor of the renderer material, given the textures
+    /// uploaded from the images of the material. A white texture should be
+    /// given as the base color if the material has no base color texture.
+    #[must_use]
+    pub fn descriptor(
+        &self,
+        base_color: texture::Id,
+        normal: Option<texture::Id>,
+        emissive: Option<texture::Id>,
+    ) -> material::Descriptor {
+        let [red, green, blue] = self.emissive_factor;
+        material::Descriptor {
+            base_color,
+            normal,
+            emissive,
+            region: texture::Rect::new(0.0, 0.0, 1.0, 1.0),
+            blend_mode: match self.alpha_mode {
+                AlphaMode::Opaque => material::BlendMode::Opaque,
+                AlphaMode::Mask { .. } | AlphaMode::Blend => material::BlendMode::Alpha,
+            },
+            sampler: self.sampler,
+            parameters: material::Parameters {
+                base_color_factor: self.base_color_factor,
+                emissive: [red, green, blue, 0.0],
+                ..material::Parameters::default()
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: Option<String>,
+    /// Transform relative to the parent node
+    pub transform: Transform,
+    /// Index of the mesh of the node in [`Gltf::meshes`]
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scene {
+    pub name: Option<String>,
+    /// Root nodes of the scene
+    pub nodes: Vec<usize>,
+}
+
+impl Gltf {
+    /// Returns the root nodes of the default scene, or of the first scene if
+    /// there is no default scene
+    #[must_use]
+    pub fn root_nodes(&self) -> &[usize] {
+        self.default_scene
+            .or((!self.scenes.is_empty()).then_some(0))
+            .map_or(&[], |scene| &self.scenes[scene].nodes)
+    }
+}
+
+/// Resolves the URI of an image relative to the path of its glTF asset, e.g.
+/// `textures/wood.png` in `models/table.gltf` is `models/textures/wood.png`
+#[must_use]
+pub fn resolve_uri(gltf_path: &str, uri: &str) -> String {
+    match gltf_path.rfind('/') {
+        Some(separator) => format!("{}{uri}", &gltf_path[..=separator]),
+        None => uri.to_string(),
+    }
+}
+
+/// Entity spawned from a node of a glTF asset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    /// Index of the node in [`Gltf::nodes`]
+    pub node: usize,
+}
+
+/// Mesh of a glTF asset drawn at the transform of the entity
+#[derive(Debug, Clone)]
+pub struct GltfMesh {
+    pub gltf: AssetHandle<Gltf>,
+    /// Index of the mesh in [`Gltf::meshes`]
+    pub mesh: usize,
+}
+
+/// Inserts the nodes of the root scene of a glTF asset stored in the
+/// [`AssetStore`] resource, see [`Gltf::root_nodes`]. Each node is spawned
+/// with its [`Transform`] and a [`GltfNode`], the nodes with a mesh with a
+/// [`GltfMesh`], and the child nodes are linked to their parent with a
+/// [`ChildOf`] relationship. Returns the ids of the entities, parents first,
+/// or `None` if the asset isn't loaded.
+pub fn spawn_gltf(ecs: &mut Ecs, gltf: &AssetHandle<Gltf>) -> Option<Vec<EntityId>> {
+    // The nodes are collected first as the asset store is borrowed from the
+    // ECS
+    let nodes = {
+        let asset_store = ecs.resource::<AssetStore>()?;
+        let asset = asset_store.get(gltf)?;
+        let mut visited = HashSet::new();
+        let mut nodes = vec![];
+        let mut stack = asset
+            .root_nodes()
+            .iter()
+            .rev()
+            .map(|&node| (node, None))
+            .collect::<Vec<_>>();
+        while let Some((node_index, parent)) = stack.pop() {
+            if !visited.insert(node_index) {
+                warn!(
+                    "Node {node_index} of glTF asset {} is visited twice",
+                    gltf.id()
+                );
+                continue;
+            }
+            let node = &asset.nodes[node_index];
+            let spawned_index = nodes.len();
+            stack.extend(
+                node.children
+                    .iter()
+                    .rev()
+                    .map(|&child| (child, Some(spawned_index))),
+            );
+            nodes.push((node_index, node.clone(), parent));
+        }
+        nodes
+    };
+
+    let mut entity_ids = Vec::with_capacity(nodes.len());
+    for (node_index, node, parent) in nodes {
+        let gltf_node = GltfNode {
+            name: node.name,
+            node: node_index,
+        };
+        let entity_id = match node.mesh {
+            Some(mesh) => ecs.insert((
+                node.transform,
+                gltf_node,
+                GltfMesh {
+                    gltf: gltf.clone(),
+                    mesh,
+                },
+            )),
+            None => ecs.insert((node.transform, gltf_node)),
+        };
+        if let Some(parent) = parent {
+            ecs.insert_relationship::<ChildOf>(entity_id, entity_ids[parent]);
+        }
+        entity_ids.push(entity_id);
+    }
+    Some(entity_ids)
+}
+
+impl Asset for Gltf {
+    type Loader = GltfLoader;
+}
+
+pub struct GltfLoader;
+impl AssetLoader<Gltf> for GltfLoader {
+    fn load(file_content: &[u8]) -> Result<Gltf> {
+        let (json, binary_chunk) = if file_content.starts_with(GLB_MAGIC) {
+            parse_glb(file_content)?
+        } else {
+            (file_content, None)
+        };
+        let json = std::str::from_utf8(json).map_err(|_| AssetError::GltfDeserializationFailed)?;
+        let document = Value::parse(json)?;
+        let buffers = document
+            .get("buffers")
+            .map_or(&[][..], Value::items)
+            .iter()
+            .enumerate()
+            .map(
+                |(index, buffer)| match buffer.get("uri").and_then(Value::as_str) {
+                    Some(uri) => {
+                        decode_data_uri(uri).ok_or_else(|| AssetError::UnsupportedGltfBuffer {
+                            uri: uri.to_string(),
+                        })
+                    }
+                    None if index == 0 => binary_chunk
+                        .map(<[u8]>::to_vec)
+                        .ok_or(AssetError::GltfDeserializationFailed),
+                    None => Err(AssetError::GltfDeserializationFailed),
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
+        Document {
+            root: &document,
+            buffers: &buffers,
+        }
+        .gltf()
+    }
+}
+
+/// Returns the JSON chunk and the binary chunk of a `.glb` file
+fn parse_glb(file_content: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    let read_u32 = |offset: usize| {
+        file_content
+            .get(offset..offset + 4)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u32::from_le_bytes)
+            .ok_or(AssetError::GltfDeserializationFailed)
+    };
+    if read_u32(4)? != 2 {
+        return Err(AssetError::GltfDeserializationFailed);
+    }
+    let mut json = None;
+    let mut binary = None;
+    let mut offset = GLB_HEADER_SIZE;
+    while offset < file_content.len() {
+        let length = usize::try_from(read_u32(offset)?)
+            .map_err(|_| AssetError::GltfDeserializationFailed)?;
+        let chunk_type = read_u32(offset + 4)?;
+        let start = offset + 8;
+        let chunk = start
+            .checked_add(length)
+            .and_then(|end| file_content.get(start..end))
+            .ok_or(AssetError::GltfDeserializationFailed)?;
+        match chunk_type {
+            GLB_JSON_CHUNK if json.is_none() => json = Some(chunk),
+            GLB_BIN_CHUNK if binary.is_none() => binary = Some(chunk),
+            // Unknown chunks are ignored as required by the specification
+            _ => {}
+        }
+        offset = start + length;
+    }
+    Ok((json.ok_or(AssetError::GltfDeserializationFailed)?, binary))
+}
+
+/// Decodes a `data:<media type>;base64,<data>` URI
+fn decode_data_uri(uri: &str) -> Option<Vec<u8>> {
+    let (_, data) = uri.strip_prefix("data:")?.split_once(";base64,")?;
+    base64::engine::general_purpose::STANDARD.decode(data).ok()
+}
+
+fn default_sampler() -> sampler::Descriptor {
+    sampler::Descriptor {
+        filter_mode: sampler::FilterMode::Linear,
+        address_mode: sampler::AddressMode::Repeat,
+        ..sampler::Descriptor::default()
+    }
+}
+
+/// Elements of an accessor, with their component type
+struct AccessorData<'a> {
+    component_type: usize,
+    normalized: bool,
+    elements: Vec<&'a [u8]>,
+}
+
+/// Parsed glTF document, with its buffers
+struct Document<'a> {
+    root: &'a Value,
+    buffers: &'a [Vec<u8>],
+}
+
+impl Document<'_> {
+    fn gltf(&self) -> Result<Gltf> {
+        let images = self
+            .array("images")
+            .iter()
+            .map(|image| self.image(image))
+            .collect::<Result<Vec<_>>>()?;
+        let materials = self
+            .array("materials")
+            .iter()
+            .map(|material| self.material(material, images.len()))
+            .collect::<Result<Vec<_>>>()?;
+        let meshes = self
+            .array("meshes")
+            .iter()
+            .map(|mesh| self.mesh(mesh, materials.len()))
+            .collect::<Result<Vec<_>>>()?;
+        let node_count = self.array("nodes").len();
+        let nodes = self
+            .array("nodes")
+            .iter()
+            .map(|node| parse_node(node, meshes.len(), node_count))
+            .collect::<Result<Vec<_>>>()?;
+        let scenes = self
+            .array("scenes")
+            .iter()
+            .map(|scene| {
+                Ok(Scene {
+                    name: name(scene),
+                    nodes: indices(scene.get("nodes"), node_count)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let default_scene = optional_index(self.root.get("scene"), scenes.len())?;
+        Ok(Gltf {
+            meshes,
+            materials,
+            images,
+            nodes,
+            scenes,
+            default_scene,
+        })
+    }
+
+    fn array(&self, name: &str) -> &[Value] {
+        self.root.get(name).map_or(&[], Value::items)
+    }
+
+    fn image(&self, image: &Value) -> Result<ImageSource> {
+        let bytes = if let Some(uri) = image.get("uri").and_then(Value::as_str) {
+            if !uri.starts_with("data:") {
+                return Ok(ImageSource::Uri(uri.to_string()));
+            }
+            decode_data_uri(uri).ok_or(AssetError::GltfDeserializationFailed)?
+        } else {
+            let buffer_view =
+                required_index(image.get("bufferView"), self.array("bufferViews").len())?;
+            self.buffer_view(buffer_view)?.0.to_vec()
+        };
+        Ok(ImageSource::Embedded(ImageLoader::load(&bytes)?))
+    }
+
+    fn material(&self, material: &Value, image_count: usize) -> Result<Material> {
+        let pbr = material.get("pbrMetallicRoughness");
+        let base_color_texture = pbr.and_then(|pbr| pbr.get("baseColorTexture"));
+        let alpha_mode = match material.get("alphaMode").and_then(Value::as_str) {
+            None | Some("OPAQUE") => AlphaMode::Opaque,
+            Some("MASK") => AlphaMode::Mask {
+                cutoff: number(material.get("alphaCutoff"), 0.5)?,
+            },
+            Some("BLEND") => AlphaMode::Blend,
+            Some(_) => return Err(AssetError::GltfDeserializationFailed),
+        };
+        Ok(Material {
+            name: name(material),
+            base_color_factor: pbr
+                .and_then(|pbr| pbr.get("baseColorFactor"))
+                .map_or(Some([1.0; 4]), Value::as_f32_array)
+                .ok_or(AssetError::GltfDeserializationFailed)?,
+            base_color_texture: self.texture_image(base_color_texture, image_count)?,
+            normal_texture: self.texture_image(material.get("normalTexture"), image_count)?,
+            emissive_factor: material
+                .get("emissiveFactor")
+                .map_or(Some([0.0; 3]), Value::as_f32_array)
+                .ok_or(AssetError::GltfDeserializationFailed)?,
+            emissive_texture: self.texture_image(material.get("emissiveTexture"), image_count)?,
+            alpha_mode,
+            double_sided: material
+                .get("doubleSided")
+                .map_or(Some(false), Value::as_bool)
+                .ok_or(AssetError::GltfDeserializationFailed)?,
+            sampler: self.texture_sampler(base_color_texture)?,
+        })
+    }
+
+    /// Returns the image of a texture referenced by a material
+    fn texture_image(
+        &self,
+        texture_info: Option<&Value>,
+        image_count: usize,
+    ) -> Result<Option<usize>> {
+        let Some(texture_info) = texture_info else {
+            return Ok(None);
+        };
+        let texture = required_index(texture_info.get("index"), self.array("textures").len())?;
+        optional_index(self.array("textures")[texture].get("source"), image_count)
+    }
+
+    fn texture_sampler(&self, texture_info: Option<&Value>) -> Result<sampler::Descriptor> {
+        let Some(texture_info) = texture_info else {
+            return Ok(default_sampler());
+        };
+        let texture = required_index(texture_info.get("index"), self.array("textures").len())?;
+        let Some(sampler) = self.array("textures")[texture].get("sampler") else {
+            return Ok(default_sampler());
+        };
+        let sampler =
+            &self.array("samplers")[required_index(Some(sampler), self.array("samplers").len())?];
+        // The renderer samples with a single filter and address mode
+        Ok(sampler::Descriptor {
+            filter_mode: match sampler.get("magFilter").and_then(Value::as_usize) {
+                Some(NEAREST_FILTER) => sampler::FilterMode::Nearest,
+                _ => sampler::FilterMode::Linear,
+            },
+            address_mode: match sampler.get("wrapS").and_then(Value::as_usize) {
+                Some(CLAMP_TO_EDGE_WRAPPING) => sampler::AddressMode::ClampToEdge,
+                Some(MIRRORED_REPEAT_WRAPPING) => sampler::AddressMode::MirrorRepeat,
+                _ => sampler::AddressMode::Repeat,
+            },
+            ..sampler::Descriptor::default()
+        })
+    }
+
+    fn mesh(&self, mesh: &Value, material_count: usize) -> Result<Mesh> {
+        let mut primitives = vec![];
+        for primitive in mesh.get("primitives").map_or(&[][..], Value::items) {
+            let mode = optional_usize(primitive.get("mode"))?.unwrap_or(TRIANGLES_MODE);
+            if mode != TRIANGLES_MODE {
+                warn!("Skipping glTF primitive with unsupported mode {mode}");
+                continue;
+            }
+            let attributes = primitive
+                .get("attributes")
+                .ok_or(AssetError::GltfDeserializationFailed)?;
+            let attribute =
+                |name: &str| optional_index(attributes.get(name), self.array("accessors").len());
+            let position_accessor =
+                attribute("POSITION")?.ok_or(AssetError::GltfDeserializationFailed)?;
+            let positions = self.read_floats::<3>(position_accessor)?;
+            let normals = attribute("NORMAL")?
+                .map(|accessor| self.read_floats::<3>(accessor))
+                .transpose()?
+                .unwrap_or_default();
+            let texture_coordinates = attribute("TEXCOORD_0")?
+                .map(|accessor| self.read_floats::<2>(accessor))
+                .transpose()?
+                .unwrap_or_default();
+            let vertex_count = u32::try_from(positions.len())
+                .map_err(|_| AssetError::GltfDeserializationFailed)?;
+            let indices =
+                match optional_index(primitive.get("indices"), self.array("accessors").len())? {
+                    Some(accessor) => self.read_indices(accessor)?,
+                    None => (0..vertex_count).collect(),
+                };
+            if (!normals.is_empty() && normals.len() != positions.len())
+                || (!texture_coordinates.is_empty() && texture_coordinates.len() != positions.len())
+                || indices.iter().any(|&index| index >= vertex_count)
+            {
+                return Err(AssetError::GltfDeserializationFailed);
+            }
+            primitives.push(Primitive {
+                geometry: mesh::Descriptor {
+                    positions,
+                    normals,
+                    texture_coordinates,
+                    indices,
+                },
+                material: optional_index(primitive.get("material"), material_count)?,
+            });
+        }
+        Ok(Mesh {
+            name: name(mesh),
+            primitives,
+        })
+    }
+
+    /// Returns the bytes of a buffer view and its stride, if any
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>)> {
+        let buffer_view = &self.array("bufferViews")[index];
+        let buffer = required_index(buffer_view.get("buffer"), self.buffers.len())?;
+        let offset = optional_usize(buffer_view.get("byteOffset"))?.unwrap_or(0);
+        let length = optional_usize(buffer_view.get("byteLength"))?
+            .ok_or(AssetError::GltfDeserializationFailed)?;
+        let bytes = offset
+            .checked_add(length)
+            .and_then(|end| self.buffers[buffer].get(offset..end))
+            .ok_or(AssetError::GltfDeserializationFailed)?;
+        Ok((bytes, optional_usize(buffer_view.get("byteStride"))?))
+    }
+
+    fn accessor(&self, index: usize) -> Result<AccessorData<'_>> {
+        /// Zeroed elements of the accessors without buffer view
+        const ZEROS: [u8; 64] = [0; 64];
+
+        let accessor = &self.array("accessors")[index];
+        if accessor.get("sparse").is_some() {
+            warn!("Sparse glTF accessors aren't supported");
+            return Err(AssetError::GltfDeserializationFailed);
+        }
+        let component_type = optional_usize(accessor.get("componentType"))?
+            .ok_or(AssetError::GltfDeserializationFailed)?;
+        let component_size =
+            component_size(component_type).ok_or(AssetError::GltfDeserializationFailed)?;
+        let component_count = match accessor.get("type").and_then(Value::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4" | "MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(AssetError::GltfDeserializationFailed),
+        };
+        let element_size = component_size * component_count;
+        let count =
+            optional_usize(accessor.get("count"))?.ok_or(AssetError::GltfDeserializationFailed)?;
+
+        let elements =
+            match optional_index(accessor.get("bufferView"), self.array("bufferViews").len())? {
+                Some(buffer_view) => {
+                    let (bytes, stride) = self.buffer_view(buffer_view)?;
+                    let offset = optional_usize(accessor.get("byteOffset"))?.unwrap_or(0);
+                    let stride = stride.unwrap_or(element_size);
+                    (0..count)
+                        .map(|element| {
+                            let start = offset.checked_add(element.checked_mul(stride)?)?;
+                            bytes.get(start..start.checked_add(element_size)?)
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or(AssetError::GltfDeserializationFailed)?
+                }
+                None => vec![&ZEROS[..element_size]; count],
+            };
+        Ok(AccessorData {
+            component_type,
+            normalized: accessor
+                .get("normalized")
+                .and_then(Value::as_bool)
+                .unwrap_or(false),
+            elements,
+        })
+    }
+
+    /// Reads the elements of an accessor of `N` components, e.g. positions
+    fn read_floats<const N: usize>(&self, accessor: usize) -> Result<Vec<[f32; N]>> {
+        let accessor = self.accessor(accessor)?;
+        let component_size =
+            component_size(accessor.component_type).ok_or(AssetError::GltfDeserializationFailed)?;
+        accessor
+            .elements
+            .iter()
+            .map(|element| {
+                if element.len() != N * component_size {
+                    return None;
+                }
+                let mut values = [0.0; N];
+                for (value, bytes) in values.iter_mut().zip(element.chunks_exact(component_size)) {
+                    *value = component_to_f32(accessor.component_type, accessor.normalized, bytes)?;
+                }
+                Some(values)
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(AssetError::GltfDeserializationFailed)
+    }
+
+    fn read_indices(&self, accessor: usize) -> Result<Vec<u32>> {
+        let accessor = self.accessor(accessor)?;
+        accessor
+            .elements
+            .iter()
+            .map(|element| match (accessor.component_type, *element) {
+                (5121, &[index]) => Some(u32::from(index)),
+                (5123, &[low, high]) => Some(u32::from(u16::from_le_bytes([low, high]))),
+                (5125, bytes) => Some(u32::from_le_bytes(bytes.try_into().ok()?)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(AssetError::GltfDeserializationFailed)
+    }
+}
+
+fn parse_node(node: &Value, mesh_count: usize, node_count: usize) -> Result<Node> {
+    let transform = if let Some(matrix) = node.get("matrix") {
+        let matrix = matrix
+            .as_f32_array::<16>()
+            .ok_or(AssetError::GltfDeserializationFailed)?;
+        // glTF matrices are stored in column-major order
+        let mut values = [0.0; 16];
+        for (index, value) in values.iter_mut().enumerate() {
+            *value = matrix[(index % 4) * 4 + index / 4];
+        }
+        Transform::from(Matrix4f::with_values(values))
+    } else {
+        let vector = |name: &str, default: [f32; 3]| {
+            node.get(name)
+                .map_or(Some(default), Value::as_f32_array)
+                .map(|[x, y, z]| Vector3f::new(x, y, z))
+                .ok_or(AssetError::GltfDeserializationFailed)
+        };
+        let [x, y, z, w] = node
+            .get("rotation")
+            .map_or(Some([0.0, 0.0, 0.0, 1.0]), Value::as_f32_array)
+            .ok_or(AssetError::GltfDeserializationFailed)?;
+        Transform {
+            translation: vector("translation", [0.0; 3])?,
+            scale: vector("scale", [1.0; 3])?,
+            rotation: Quaternion::new(w, Vector3f::new(x, y, z)),
+        }
+    };
+    Ok(Node {
+        name: name(node),
+        transform,
+        mesh: optional_index(node.get("mesh"), mesh_count)?,
+        children: indices(node.get("children"), node_count)?,
+    })
+}
+
+fn component_size(component_type: usize) -> Option<usize> {
+    match component_type {
+        5120 | 5121 => Some(1),
+        5122 | 5123 => Some(2),
+        5125 | 5126 => Some(4),
+        _ => None,
+    }
+}
+
+/// Converts a component to a float, normalized integers being mapped to
+/// [0, 1] or [-1, 1]
+fn component_to_f32(component_type: usize, normalized: bool, bytes: &[u8]) -> Option<f32> {
+    let value = match component_type {
+        5126 => return Some(f32::from_le_bytes(bytes.try_into().ok()?)),
+        5120 => f32::from(i8::from_le_bytes(bytes.try_into().ok()?)),
+        5121 => f32::from(bytes[0]),
+        5122 => f32::from(i16::from_le_bytes(bytes.try_into().ok()?)),
+        5123 => f32::from(u16::from_le_bytes(bytes.try_into().ok()?)),
+        _ => return None,
+    };
+    if !normalized {
+        return Some(value);
+    }
+    Some(match component_type {
+        5120 => (value / f32::from(i8::MAX)).max(-1.0),
+        5121 => value / f32::from(u8::MAX),
+        5122 => (value / f32::from(i16::MAX)).max(-1.0),
+        _ => value / f32::from(u16::MAX),
+    })
+}
+
+fn name(value: &Value) -> Option<String> {
+    value
+        .get("name")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+fn number(value: Option<&Value>, default: f32) -> Result<f32> {
+    value
+        .map_or(Some(default), Value::as_f32)
+        .ok_or(AssetError::GltfDeserializationFailed)
+}
+
+fn optional_usize(value: Option<&Value>) -> Result<Option<usize>> {
+    value
+        .map(|value| {
+            value
+                .as_usize()
+                .ok_or(AssetError::GltfDeserializationFailed)
+        })
+        .transpose()
+}
+
+/// Reads an optional index into an array of `len` elements
+fn optional_index(value: Option<&Value>, len: usize) -> Result<Option<usize>> {
+    match optional_usize(value)? {
+        Some(index) if index >= len => Err(AssetError::GltfDeserializationFailed),
+        index => Ok(index),
+    }
+}
+
+fn required_index(value: Option<&Value>, len: usize) -> Result<usize> {
+    optional_index(value, len)?.ok_or(AssetError::GltfDeserializationFailed)
+}
+
+fn indices(value: Option<&Value>, len: usize) -> Result<Vec<usize>> {
+    value
+        .map_or(&[][..], Value::items)
+        .iter()
+        .map(|index| required_index(Some(index), len))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a `.glb` file with the given JSON chunk and binary chunk
+    fn glb(json: &str, binary: &[u8]) -> Vec<u8> {
+        let mut json = json.as_bytes().to_vec();
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let mut binary = binary.to_vec();
+        binary.resize(binary.len().next_multiple_of(4), 0);
+        let length = GLB_HEADER_SIZE + 8 + json.len() + 8 + binary.len();
+
+        let mut glb = GLB_MAGIC.to_vec();
+        for value in [2, length] {
+            glb.extend_from_slice(&u32::try_from(value).unwrap().to_le_bytes());
+        }
+        for (chunk_type, chunk) in [(GLB_JSON_CHUNK, json), (GLB_BIN_CHUNK, binary)] {
+            glb.extend_from_slice(&u32::try_from(chunk.len()).unwrap().to_le_bytes());
+            glb.extend_from_slice(&chunk_type.to_le_bytes());
+            glb.extend_from_slice(&chunk);
+        }
+        glb
+    }
+
+    /// A triangle with interleaved normalized u8 texture coordinates, indexed
+    /// by u16
+    fn triangle_binary() -> Vec<u8> {
+        let mut binary = vec![];
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            binary.extend_from_slice(&value.to_le_bytes());
+        }
+        binary.extend_from_slice(&[0, 0, 9, 9, 255, 0, 9, 9, 0, 255]);
+        for index in [0u16, 1, 2] {
+            binary.extend_from_slice(&index.to_le_bytes());
+        }
+        binary
+    }
+
+    const TRIANGLE_JSON: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "name": "Scene", "nodes": [0] }],
+        "nodes": [
+            { "name": "Root", "translation": [1, 2, 3], "children": [1] },
+            { "name": "Triangle", "mesh": 0, "rotation": [0, 0, 0.7071068, 0.7071068] }
+        ],
+        "meshes": [{
+            "name": "Triangle",
+            "primitives": [{
+                "attributes": { "POSITION": 0, "TEXCOORD_0": 1 },
+                "indices": 2,
+                "material": 0
+            }]
+        }],
+        "materials": [{
+            "name": "Red",
+            "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1] },
+            "alphaMode": "MASK",
+            "doubleSided": true
+        }],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+            { "bufferView": 1, "componentType": 5121, "normalized": true, "count": 3, "type": "VEC2" },
+            { "bufferView": 2, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 10, "byteStride": 4 },
+            { "buffer": 0, "byteOffset": 46, "byteLength": 6 }
+        ],
+        "buffers": [{ "byteLength": 52 }]
+    }"#;
+
+    #[test]
+    fn load_glb() {
+        let gltf = GltfLoader::load(&glb(TRIANGLE_JSON, &triangle_binary())).unwrap();
+        assert_eq!(gltf.root_nodes(), &[0]);
+        assert_eq!(gltf.scenes[0].name.as_deref(), Some("Scene"));
+
+        let root = &gltf.nodes[0];
+        assert_eq!(root.children, [1]);
+        assert_eq!(root.transform.translation, Vector3f::new(1.0, 2.0, 3.0));
+        let triangle = &gltf.nodes[1];
+        assert_eq!(triangle.mesh, Some(0));
+        assert!(
+            (triangle.transform.rotation.scalar_part() - std::f32::consts::FRAC_1_SQRT_2).abs()
+                < 1e-6
+        );
+
+        let primitive = &gltf.meshes[0].primitives[0];
+        assert_eq!(primitive.material, Some(0));
+        assert_eq!(
+            primitive.geometry,
+            mesh::Descriptor {
+                positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+                normals: vec![],
+                texture_coordinates: vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]],
+                indices: vec![0, 1, 2],
+            }
+        );
+
+        assert_eq!(
+            gltf.materials[0],
+            Material {
+                name: Some("Red".to_string()),
+                base_color_factor: [1.0, 0.0, 0.0, 1.0],
+                alpha_mode: AlphaMode::Mask { cutoff: 0.5 },
+                double_sided: true,
+                ..Material::default()
+            }
+        );
+    }
+
+    #[test]
+    fn load_gltf_with_data_uri() {
+        let uri = format!(
+            "data:application/octet-stream;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(triangle_binary())
+        );
+        let json = TRIANGLE_JSON.replace(
+            r#"{ "byteLength": 52 }"#,
+            &format!(r#"{{ "byteLength": 52, "uri": "{uri}" }}"#),
+        );
+        let gltf = GltfLoader::load(json.as_bytes()).unwrap();
+        assert_eq!(gltf.meshes[0].primitives[0].geometry.triangle_count(), 1);
+
+        let json = TRIANGLE_JSON.replace(
+            r#"{ "byteLength": 52 }"#,
+            r#"{ "byteLength": 52, "uri": "triangle.bin" }"#,
+        );
+        assert!(matches!(
+            GltfLoader::load(json.as_bytes()),
+            Err(AssetError::UnsupportedGltfBuffer { uri }) if uri == "triangle.bin"
+        ));
+    }
+
+    #[test]
+    fn invalid_references_are_rejected() {
+        let binary = triangle_binary();
+        for (from, to) in [
+            (r#""mesh": 0"#, r#""mesh": 1"#),
+            (r#""children": [1]"#, r#""children": [2]"#),
+            (
+                r#""byteOffset": 46, "byteLength": 6"#,
+                r#""byteOffset": 48, "byteLength": 6"#,
+            ),
+            (r#""indices": 2"#, r#""indices": 0"#),
+        ] {
+            let json = TRIANGLE_JSON.replace(from, to);
+            assert!(GltfLoader::load(&glb(&json, &binary)).is_err(), "{to}");
+        }
+    }
+
+    #[test]
+    fn node_matrices_are_column_major() {
+        let json = r#"{
+            "nodes": [{ "matrix": [2, 0, 0, 0, 0, 2, 0, 0, 0, 0, 2, 0, 4, 5, 6, 1] }]
+        }"#;
+        let gltf = GltfLoader::load(json.as_bytes()).unwrap();
+        let transform = &gltf.nodes[0].transform;
+        assert_eq!(transform.translation, Vector3f::new(4.0, 5.0, 6.0));
+        assert_eq!(transform.scale, Vector3f::new(2.0, 2.0, 2.0));
+        assert!(gltf.root_nodes().is_empty());
+    }
+
+    #[test]
+    fn images_are_embedded_or_referenced() {
+        let json = r#"{
+            "images": [{ "uri": "textures/wood.png" }],
+            "textures": [{ "source": 0, "sampler": 0 }],
+            "samplers": [{ "magFilter": 9728, "wrapS": 33071 }],
+            "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0 } } }]
+        }"#;
+        let gltf = GltfLoader::load(json.as_bytes()).unwrap();
+        assert!(matches!(&gltf.images[0], ImageSource::Uri(uri) if uri == "textures/wood.png"));
+        let material = &gltf.materials[0];
+        assert_eq!(material.base_color_texture, Some(0));
+        assert_eq!(material.sampler.filter_mode, sampler::FilterMode::Nearest);
+        assert_eq!(
+            material.sampler.address_mode,
+            sampler::AddressMode::ClampToEdge
+        );
+        assert_eq!(
+            resolve_uri("models/table.gltf", "textures/wood.png"),
+            "models/textures/wood.png"
+        );
+        assert_eq!(resolve_uri("table.gltf", "wood.png"), "wood.png");
+    }
+
+    #[test]
+    fn spawn_gltf_hierarchy() {
+        struct NoFileSystem;
+        impl tubereng_asset::vfs::VirtualFileSystem for NoFileSystem {
+            fn read_bytes(&self, _path: &str) -> Result<Vec<u8>> {
+                Err(AssetError::ReadFailed)
+            }
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.define_relationship::<ChildOf>();
+        let mut asset_store = AssetStore::new(NoFileSystem);
+        let gltf =
+            asset_store.store(GltfLoader::load(&glb(TRIANGLE_JSON, &triangle_binary())).unwrap());
+        ecs.insert_resource(asset_store);
+
+        let entities = spawn_gltf(&mut ecs, &gltf).unwrap();
+        assert_eq!(entities.len(), 2);
+        let [root, triangle] = entities[..] else {
+            unreachable!()
+        };
+        let child_of = ecs.relationship::<ChildOf>().unwrap();
+        assert!(child_of.contains(triangle, root));
+        assert!(child_of.targets(root).is_none());
+        assert_eq!(
+            ecs.component::<GltfNode>(triangle),
+            Some(&GltfNode {
+                name: Some("Triangle".to_string()),
+                node: 1,
+            })
+        );
+        assert_eq!(ecs.component::<GltfMesh>(triangle).unwrap().gltf, gltf);
+        assert!(ecs.component::<GltfMesh>(root).is_none());
+        assert_eq!(
+            ecs.component::<Transform>(root).unwrap().translation,
+            Vector3f::new(1.0, 2.0, 3.0)
+        );
+
+        // The mesh components keep the asset loaded
+        drop(gltf);
+        let mut asset_store = ecs.resource_mut::<AssetStore>().unwrap();
+        assert!(asset_store.free_unused().is_empty());
+    }
+}
@@ -2,6 +2,7 @@
 
 use std::io::Cursor;
 
+use serde::Deserialize;
 use tubereng_asset::{Asset, AssetError, AssetLoader};
 
 #[derive(Debug)]
@@ -14,6 +15,12 @@ pub enum ImageError {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageFormat {
     RGBA8,
+    /// BC1 compressed blocks, loaded from KTX2 containers
+    BC1,
+    /// BC3 compressed blocks, loaded from KTX2 containers
+    BC3,
+    /// BC7 compressed blocks, loaded from KTX2 containers
+    BC7,
 }
 
 pub struct Image {
@@ -21,9 +28,60 @@ pub struct Image {
     width: u32,
     height: u32,
     format: ImageFormat,
+    premultiplied_alpha: bool,
+}
+
+/// Import settings of an image, read from the `.meta` file next to it
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ImageMeta {
+    /// Multiply the color channels by the alpha channel at load time, which
+    /// avoids dark fringes on the anti-aliased edges of filtered sprites.
+    /// Disabled by default.
+    pub premultiply_alpha: bool,
+}
+
+impl ImageMeta {
+    /// Parses the content of a `.meta` file, the default settings being used
+    /// without one
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AssetError::MetaDeserializationFailed`] if the meta file is
+    /// invalid
+    pub fn parse(meta: Option<&[u8]>) -> tubereng_asset::Result<Self> {
+        match meta {
+            Some(meta) => std::str::from_utf8(meta)
+                .ok()
+                .and_then(|meta| ron::from_str::<ImageMeta>(meta).ok())
+                .ok_or(AssetError::MetaDeserializationFailed),
+            None => Ok(ImageMeta::default()),
+        }
+    }
 }
 
 impl Image {
+    /// Creates an RGBA8 image from its pixels, row by row, e.g. for images
+    /// decoded or composited by other loaders
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` isn't `width * height * 4` bytes long
+    #[must_use]
+    pub fn from_rgba8(width: u32, height: u32, mut data: Vec<u8>, premultiply: bool) -> Self {
+        assert_eq!(data.len(), width as usize * height as usize * 4);
+        if premultiply {
+            premultiply_alpha(&mut data);
+        }
+        Self {
+            data,
+            width,
+            height,
+            format: ImageFormat::RGBA8,
+            premultiplied_alpha: premultiply,
+        }
+    }
+
     #[must_use]
     pub fn data(&self) -> &[u8] {
         &self.data
@@ -43,6 +101,48 @@ impl Image {
     pub fn format(&self) -> ImageFormat {
         self.format
     }
+
+    /// Returns true if the color channels of the image data are
+    /// premultiplied by its alpha channel
+    #[must_use]
+    pub fn premultiplied_alpha(&self) -> bool {
+        self.premultiplied_alpha
+    }
+}
+
+/// Multiplies the color channels by the alpha channel. The color channels are
+/// sRGB encoded while the alpha channel is linear, so the multiplication is
+/// done on the linear values to match the blending done by the GPU on sRGB
+/// textures.
+fn premultiply_alpha(rgba_data: &mut [u8]) {
+    let linear_values: Vec<f32> = (0..=255u8).map(srgb_to_linear).collect();
+    for pixel in rgba_data.chunks_exact_mut(4) {
+        let alpha = f32::from(pixel[3]) / 255.0;
+        for channel in &mut pixel[..3] {
+            *channel = linear_to_srgb(linear_values[usize::from(*channel)] * alpha);
+        }
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = f32::from(value) / 255.0;
+    if value <= 0.040_45 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    // The value is clamped to [0, 255] before the conversion
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let value = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+    value
 }
 
 impl Asset for Image {
@@ -52,8 +152,18 @@ impl Asset for Image {
 pub struct ImageLoader;
 impl AssetLoader<Image> for ImageLoader {
     fn load(file_content: &[u8]) -> tubereng_asset::Result<Image> {
+        Self::load_with_meta(file_content, None)
+    }
+
+    fn load_with_meta(file_content: &[u8], meta: Option<&[u8]>) -> tubereng_asset::Result<Image> {
+        if file_content.starts_with(&KTX2_IDENTIFIER) {
+            return load_ktx2(file_content);
+        }
+
+        let meta = ImageMeta::parse(meta)?;
+
         let cursor = Cursor::new(file_content);
-        let image_reader = image::io::Reader::new(cursor);
+        let image_reader = image::ImageReader::new(cursor);
         let image = image_reader
             .with_guessed_format()
             .map_err(|_| AssetError::ImageDecodingFailed)?
@@ -63,15 +173,72 @@ impl AssetLoader<Image> for ImageLoader {
         let width = image.width();
         let height = image.height();
 
+        let mut data = image.into_rgba8().into_vec();
+        if meta.premultiply_alpha {
+            premultiply_alpha(&mut data);
+        }
+
         Ok(Image {
-            data: image.into_rgba8().into_vec(),
+            data,
             width,
             height,
             format: ImageFormat::RGBA8,
+            premultiplied_alpha: meta.premultiply_alpha,
         })
     }
 }
 
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// Loads the first mip level of a KTX2 container of BC1, BC3 or BC7 blocks.
+/// The compressed data is kept as is, so `.meta` import settings don't apply.
+fn load_ktx2(file_content: &[u8]) -> tubereng_asset::Result<Image> {
+    let reader = ktx2::Reader::new(file_content).map_err(|_| AssetError::ImageDecodingFailed)?;
+    let header = reader.header();
+    if header.supercompression_scheme.is_some()
+        || header.pixel_depth > 1
+        || header.layer_count > 1
+        || header.face_count > 1
+    {
+        return Err(AssetError::UnsupportedTextureContainer);
+    }
+
+    let format = match header.format {
+        Some(ktx2::Format::BC1_RGBA_UNORM_BLOCK | ktx2::Format::BC1_RGBA_SRGB_BLOCK) => {
+            ImageFormat::BC1
+        }
+        Some(ktx2::Format::BC3_UNORM_BLOCK | ktx2::Format::BC3_SRGB_BLOCK) => ImageFormat::BC3,
+        Some(ktx2::Format::BC7_UNORM_BLOCK | ktx2::Format::BC7_SRGB_BLOCK) => ImageFormat::BC7,
+        _ => return Err(AssetError::UnsupportedTextureContainer),
+    };
+
+    let premultiplied_alpha = reader
+        .data_format_descriptors()
+        .filter(|descriptor| descriptor.header == ktx2::DataFormatDescriptorHeader::BASIC)
+        .filter_map(|descriptor| ktx2::BasicDataFormatDescriptor::parse(descriptor.data).ok())
+        .any(|descriptor| {
+            descriptor
+                .flags
+                .contains(ktx2::DataFormatFlags::ALPHA_PREMULTIPLIED)
+        });
+
+    let data = reader
+        .levels()
+        .next()
+        .ok_or(AssetError::ImageDecodingFailed)?
+        .to_vec();
+
+    Ok(Image {
+        data,
+        width: header.pixel_width,
+        height: header.pixel_height.max(1),
+        format,
+        premultiplied_alpha,
+    })
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -88,5 +255,71 @@ mod tests {
             image.data().len(),
             image.width() as usize * image.height() as usize * 4usize
         );
+        assert!(!image.premultiplied_alpha());
+    }
+
+    #[test]
+    fn load_image_with_meta() {
+        let image_data = include_bytes!("../res/logo.png");
+        let straight = ImageLoader::load(image_data).unwrap();
+        let premultiplied =
+            ImageLoader::load_with_meta(image_data, Some(b"(premultiply_alpha: true)")).unwrap();
+        assert!(premultiplied.premultiplied_alpha());
+
+        let mut expected = straight.data().to_vec();
+        premultiply_alpha(&mut expected);
+        assert_eq!(premultiplied.data(), &expected[..]);
+
+        assert!(ImageLoader::load_with_meta(image_data, Some(b"(premultiply_alpha: 3)")).is_err());
+    }
+
+    fn ktx2_file(vk_format: u32, width: u32, height: u32, level_data: &[u8]) -> Vec<u8> {
+        const HEADER_LENGTH: u32 = 80;
+        const LEVEL_INDEX_LENGTH: u32 = 24;
+        let dfd_offset = HEADER_LENGTH + LEVEL_INDEX_LENGTH;
+        let level_offset = dfd_offset + 4;
+
+        let mut file = KTX2_IDENTIFIER.to_vec();
+        for value in [
+            vk_format, 1, width, height, 0, 0, 1, 1, 0, dfd_offset, 4, 0, 0,
+        ] {
+            file.extend_from_slice(&value.to_le_bytes());
+        }
+        file.extend_from_slice(&0u64.to_le_bytes());
+        file.extend_from_slice(&0u64.to_le_bytes());
+        file.extend_from_slice(&u64::from(level_offset).to_le_bytes());
+        file.extend_from_slice(&(level_data.len() as u64).to_le_bytes());
+        file.extend_from_slice(&(level_data.len() as u64).to_le_bytes());
+        file.extend_from_slice(&4u32.to_le_bytes());
+        file.extend_from_slice(level_data);
+        file
+    }
+
+    #[test]
+    fn load_ktx2_image() {
+        const VK_FORMAT_BC7_SRGB_BLOCK: u32 = 146;
+        let blocks = [7u8; 32];
+        let image = ImageLoader::load(&ktx2_file(VK_FORMAT_BC7_SRGB_BLOCK, 8, 4, &blocks)).unwrap();
+        assert_eq!(image.format(), ImageFormat::BC7);
+        assert_eq!((image.width(), image.height()), (8, 4));
+        assert_eq!(image.data(), &blocks[..]);
+        assert!(!image.premultiplied_alpha());
+    }
+
+    #[test]
+    fn load_ktx2_image_unsupported_format() {
+        const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+        let result = ImageLoader::load(&ktx2_file(VK_FORMAT_R8G8B8A8_SRGB, 1, 1, &[0; 4]));
+        assert!(matches!(
+            result,
+            Err(AssetError::UnsupportedTextureContainer)
+        ));
+    }
+
+    #[test]
+    fn premultiply_alpha_pixels() {
+        let mut pixels = [255, 128, 0, 128, 200, 100, 50, 0, 10, 20, 30, 255];
+        premultiply_alpha(&mut pixels);
+        assert_eq!(pixels, [188, 93, 0, 128, 0, 0, 0, 0, 10, 20, 30, 255]);
     }
 }
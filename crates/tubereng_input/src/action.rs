@@ -0,0 +1,211 @@
+//! Logical actions of a game bound to physical inputs, so that the gameplay
+//! checks whether the player jumps rather than whether the space bar is
+//! down, and the players can rebind the controls
+
+use std::{collections::HashMap, hash::Hash};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{keyboard::Key, mouse::Button, InputState};
+
+/// Physical input an action can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(Key),
+    MouseButton(Button),
+}
+
+impl Binding {
+    #[must_use]
+    pub fn is_down(self, input: &InputState) -> bool {
+        match self {
+            Binding::Key(key) => input.keyboard.is_key_down(key),
+            Binding::MouseButton(button) => input.mouse.is_button_down(button),
+        }
+    }
+
+    /// Returns whether the input was down in the previous frame
+    #[must_use]
+    pub fn was_down(self, input: &InputState) -> bool {
+        match self {
+            Binding::Key(key) => input.keyboard.was_key_down(key),
+            Binding::MouseButton(button) => input.mouse.was_button_down(button),
+        }
+    }
+}
+
+impl From<Key> for Binding {
+    fn from(key: Key) -> Self {
+        Binding::Key(key)
+    }
+}
+
+impl From<Button> for Binding {
+    fn from(button: Button) -> Self {
+        Binding::MouseButton(button)
+    }
+}
+
+/// Resource binding the actions of a game, usually an enum, to sets of
+/// inputs. An action is pressed while any of its inputs is down.
+///
+/// The bindings can be serialized, e.g. as part of the settings of the game
+/// once the player rebound them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "A: Serialize",
+    deserialize = "A: Deserialize<'de> + Eq + Hash"
+))]
+pub struct ActionMap<A> {
+    bindings: HashMap<A, Vec<Binding>>,
+}
+
+impl<A> ActionMap<A>
+where
+    A: Copy + Eq + Hash,
+{
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Binds an input to an action, in addition to its other inputs
+    #[must_use]
+    pub fn with_binding(mut self, action: A, binding: impl Into<Binding>) -> Self {
+        self.bind(action, binding);
+        self
+    }
+
+    /// Binds an input to an action, in addition to its other inputs
+    pub fn bind(&mut self, action: A, binding: impl Into<Binding>) {
+        let binding = binding.into();
+        let bindings = self.bindings.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: A, binding: impl Into<Binding>) {
+        let binding = binding.into();
+        if let Some(bindings) = self.bindings.get_mut(&action) {
+            bindings.retain(|bound| *bound != binding);
+        }
+    }
+
+    /// Replaces the inputs of an action, e.g. from a rebinding screen
+    pub fn set_bindings(&mut self, action: A, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+
+    /// Returns the inputs bound to an action
+    #[must_use]
+    pub fn bindings(&self, action: A) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the actions bound to an input, e.g. to warn the player about
+    /// conflicting bindings
+    pub fn actions_bound_to(&self, binding: Binding) -> impl Iterator<Item = A> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| *action)
+    }
+
+    /// Returns whether any of the inputs of an action is down
+    #[must_use]
+    pub fn is_action_pressed(&self, action: A, input: &InputState) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|binding| binding.is_down(input))
+    }
+
+    /// Returns whether an action has been pressed this frame
+    #[must_use]
+    pub fn is_action_just_pressed(&self, action: A, input: &InputState) -> bool {
+        let bindings = self.bindings(action);
+        bindings.iter().any(|binding| binding.is_down(input))
+            && !bindings.iter().any(|binding| binding.was_down(input))
+    }
+
+    /// Returns whether an action has been released this frame
+    #[must_use]
+    pub fn is_action_just_released(&self, action: A, input: &InputState) -> bool {
+        let bindings = self.bindings(action);
+        !bindings.iter().any(|binding| binding.is_down(input))
+            && bindings.iter().any(|binding| binding.was_down(input))
+    }
+}
+
+impl<A> Default for ActionMap<A>
+where
+    A: Copy + Eq + Hash,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Input;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    enum Action {
+        Jump,
+        Fire,
+    }
+
+    fn action_map() -> ActionMap<Action> {
+        ActionMap::new()
+            .with_binding(Action::Jump, Key::Space)
+            .with_binding(Action::Jump, Key::W)
+            .with_binding(Action::Fire, Button::Left)
+    }
+
+    #[test]
+    fn actions_are_pressed_by_any_of_their_inputs() {
+        let action_map = action_map();
+        let mut input = InputState::new();
+        assert!(!action_map.is_action_pressed(Action::Jump, &input));
+
+        input.on_input(&Input::KeyDown(Key::W));
+        assert!(action_map.is_action_pressed(Action::Jump, &input));
+        assert!(action_map.is_action_just_pressed(Action::Jump, &input));
+        assert!(!action_map.is_action_pressed(Action::Fire, &input));
+
+        input.clear_last_frame_inputs();
+        input.on_input(&Input::KeyDown(Key::Space));
+        assert!(!action_map.is_action_just_pressed(Action::Jump, &input));
+
+        input.clear_last_frame_inputs();
+        input.on_input(&Input::KeyUp(Key::W));
+        input.on_input(&Input::KeyUp(Key::Space));
+        assert!(action_map.is_action_just_released(Action::Jump, &input));
+    }
+
+    #[test]
+    fn actions_can_be_rebound_and_serialized() {
+        let mut action_map = action_map();
+        action_map.unbind(Action::Jump, Key::W);
+        action_map.set_bindings(Action::Fire, vec![Binding::Key(Key::F)]);
+        assert_eq!(
+            action_map
+                .actions_bound_to(Binding::Key(Key::F))
+                .collect::<Vec<_>>(),
+            [Action::Fire]
+        );
+
+        let serialized = ron::to_string(&action_map).unwrap();
+        let deserialized: ActionMap<Action> = ron::from_str(&serialized).unwrap();
+
+        assert_eq!(
+            deserialized.bindings(Action::Jump),
+            [Binding::Key(Key::Space)]
+        );
+        assert_eq!(deserialized.bindings(Action::Fire), [Binding::Key(Key::F)]);
+    }
+}
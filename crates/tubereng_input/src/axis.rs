@@ -0,0 +1,137 @@
+//! Axes combining opposing inputs into values between -1 and 1, e.g. to move
+//! a character with the arrow keys
+
+use tubereng_math::vector::Vector2f;
+
+use crate::keyboard::{self, Key};
+
+impl keyboard::State {
+    /// Returns -1 while `negative` is down, 1 while `positive` is down, and 0
+    /// while both or none are down
+    #[must_use]
+    pub fn axis(&self, negative: Key, positive: Key) -> f32 {
+        match (self.is_key_down(negative), self.is_key_down(positive)) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    /// Combines two axes into a direction, with x pointing to the right and y
+    /// pointing up. The direction is normalized so that moving diagonally
+    /// isn't faster.
+    pub fn axis_2d(&self, left: Key, right: Key, down: Key, up: Key) -> Vector2f {
+        let direction = Vector2f::new(self.axis(left, right), self.axis(down, up));
+        if direction.x != 0.0 && direction.y != 0.0 {
+            direction.normalized()
+        } else {
+            direction
+        }
+    }
+}
+
+/// Axis value moving gradually towards the value of its inputs, e.g. so that
+/// a character accelerates instead of reaching its full speed instantly
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmoothedAxis {
+    value: f32,
+    /// Change of the value per second
+    sensitivity: f32,
+}
+
+impl SmoothedAxis {
+    /// Creates an axis going from 0 to 1 in `1 / sensitivity` seconds
+    #[must_use]
+    pub fn new(sensitivity: f32) -> Self {
+        Self {
+            value: 0.0,
+            sensitivity,
+        }
+    }
+
+    /// Moves the value towards `target` for a frame lasting `delta_time`
+    /// seconds, returning the new value
+    pub fn update(&mut self, target: f32, delta_time: f32) -> f32 {
+        let max_change = self.sensitivity * delta_time;
+        self.value += (target - self.value).clamp(-max_change, max_change);
+        self.value
+    }
+
+    #[must_use]
+    pub fn value(&self) -> f32 {
+        self.value
+    }
+
+    /// Sets the value right away, e.g. to stop a character hitting a wall
+    pub fn reset(&mut self, value: f32) {
+        self.value = value;
+    }
+}
+
+/// Direction moving gradually towards the direction of its inputs, see
+/// [`SmoothedAxis`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmoothedAxis2d {
+    x: SmoothedAxis,
+    y: SmoothedAxis,
+}
+
+impl SmoothedAxis2d {
+    #[must_use]
+    pub fn new(sensitivity: f32) -> Self {
+        Self {
+            x: SmoothedAxis::new(sensitivity),
+            y: SmoothedAxis::new(sensitivity),
+        }
+    }
+
+    /// Moves the direction towards `target` for a frame lasting `delta_time`
+    /// seconds, returning the new direction
+    pub fn update(&mut self, target: Vector2f, delta_time: f32) -> Vector2f {
+        Vector2f::new(
+            self.x.update(target.x, delta_time),
+            self.y.update(target.y, delta_time),
+        )
+    }
+
+    pub fn value(&self) -> Vector2f {
+        Vector2f::new(self.x.value(), self.y.value())
+    }
+
+    pub fn reset(&mut self, value: Vector2f) {
+        self.x.reset(value.x);
+        self.y.reset(value.y);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Input, InputState};
+
+    #[test]
+    fn axis_2d_is_normalized() {
+        let mut input = InputState::new();
+        input.on_input(&Input::KeyDown(Key::D));
+        assert_eq!(
+            input.keyboard.axis_2d(Key::A, Key::D, Key::S, Key::W),
+            Vector2f::new(1.0, 0.0)
+        );
+
+        input.on_input(&Input::KeyDown(Key::A));
+        input.on_input(&Input::KeyDown(Key::W));
+        assert!(input.keyboard.axis(Key::A, Key::D).abs() < 1e-6);
+        input.on_input(&Input::KeyUp(Key::A));
+        let direction = input.keyboard.axis_2d(Key::A, Key::D, Key::S, Key::W);
+        assert!((direction.norm() - 1.0).abs() < 1e-6);
+        assert!(direction.x > 0.0 && direction.y > 0.0);
+    }
+
+    #[test]
+    fn smoothed_axis() {
+        let mut axis = SmoothedAxis::new(4.0);
+        assert!((axis.update(1.0, 0.1) - 0.4).abs() < 1e-6);
+        assert!((axis.update(1.0, 0.2) - 1.0).abs() < 1e-6);
+        assert!((axis.update(0.0, 0.1) - 0.6).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,171 @@
+//! Stacked input contexts, e.g. the UI over the gameplay, so that an input
+//! handled by a context isn't also handled by the contexts below it
+
+use std::collections::HashMap;
+
+use crate::{action::Binding, keyboard::Key, mouse::Button, InputState};
+
+/// Stack of the input contexts of the application, from the lowest priority
+/// to the highest, e.g. `["gameplay", "ui"]`.
+///
+/// A context consumes the inputs it handles for the rest of the frame: the
+/// contexts below it don't see them through [`InputState::context`], so that
+/// clicking a button doesn't also fire the weapon of the player. The systems
+/// of a context must run before the systems of the contexts below it to
+/// consume the inputs in time.
+#[derive(Debug, Default)]
+pub struct Stack {
+    contexts: Vec<&'static str>,
+    /// Level of the highest context that consumed each input
+    consumed: HashMap<Binding, usize>,
+    keyboard_consumed: Option<usize>,
+    mouse_consumed: Option<usize>,
+}
+
+impl Stack {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes a context above the other ones. Pushing a context already on
+    /// the stack moves it to the top.
+    pub fn push(&mut self, context: &'static str) {
+        self.remove(context);
+        self.contexts.push(context);
+    }
+
+    pub fn pop(&mut self) -> Option<&'static str> {
+        self.contexts.pop()
+    }
+
+    pub fn remove(&mut self, context: &'static str) {
+        self.contexts.retain(|pushed| *pushed != context);
+    }
+
+    /// Returns the contexts from the lowest priority to the highest
+    #[must_use]
+    pub fn contexts(&self) -> &[&'static str] {
+        &self.contexts
+    }
+
+    /// Hides an input from the contexts below `context` for the rest of the
+    /// frame. Has no effect if `context` isn't on the stack.
+    pub fn consume(&mut self, context: &'static str, binding: impl Into<Binding>) {
+        if let Some(level) = self.level(context) {
+            let consumed = self.consumed.entry(binding.into()).or_insert(level);
+            *consumed = (*consumed).max(level);
+        }
+    }
+
+    /// Hides every key from the contexts below `context` for the rest of the
+    /// frame, e.g. while a text field has the focus
+    pub fn consume_keyboard(&mut self, context: &'static str) {
+        if let Some(level) = self.level(context) {
+            self.keyboard_consumed = self.keyboard_consumed.max(Some(level));
+        }
+    }
+
+    /// Hides every mouse button from the contexts below `context` for the
+    /// rest of the frame, e.g. while the cursor is over a window of the UI
+    pub fn consume_mouse(&mut self, context: &'static str) {
+        if let Some(level) = self.level(context) {
+            self.mouse_consumed = self.mouse_consumed.max(Some(level));
+        }
+    }
+
+    /// Returns whether an input is hidden from `context`. Every consumed
+    /// input is hidden from the contexts that aren't on the stack.
+    #[must_use]
+    pub fn is_consumed(&self, context: &'static str, binding: Binding) -> bool {
+        let consumed_level = match binding {
+            Binding::Key(_) => self.keyboard_consumed,
+            Binding::MouseButton(_) => self.mouse_consumed,
+        }
+        .max(self.consumed.get(&binding).copied());
+        match (consumed_level, self.level(context)) {
+            (Some(consumed_level), Some(level)) => consumed_level > level,
+            (consumed_level, None) => consumed_level.is_some(),
+            (None, Some(_)) => false,
+        }
+    }
+
+    pub(crate) fn clear_consumed_inputs(&mut self) {
+        self.consumed.clear();
+        self.keyboard_consumed = None;
+        self.mouse_consumed = None;
+    }
+
+    fn level(&self, context: &'static str) -> Option<usize> {
+        self.contexts.iter().position(|pushed| *pushed == context)
+    }
+}
+
+/// Inputs seen by the systems of a context, the inputs consumed by the
+/// contexts above it being up
+pub struct ContextInput<'a> {
+    input: &'a InputState,
+    context: &'static str,
+}
+
+impl<'a> ContextInput<'a> {
+    pub(crate) fn new(input: &'a InputState, context: &'static str) -> Self {
+        Self { input, context }
+    }
+
+    #[must_use]
+    pub fn is_down(&self, binding: Binding) -> bool {
+        binding.is_down(self.input) && !self.input.contexts.is_consumed(self.context, binding)
+    }
+
+    #[must_use]
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.is_down(Binding::Key(key))
+    }
+
+    #[must_use]
+    pub fn is_button_down(&self, button: Button) -> bool {
+        self.is_down(Binding::MouseButton(button))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Input;
+
+    #[test]
+    fn consumed_inputs_are_hidden_from_lower_contexts() {
+        let mut input = InputState::new();
+        input.contexts.push("gameplay");
+        input.contexts.push("ui");
+        input.on_input(&Input::MouseButtonDown(Button::Left));
+        input.on_input(&Input::KeyDown(Key::Space));
+
+        input.contexts.consume("ui", Button::Left);
+        assert!(input.context("ui").is_button_down(Button::Left));
+        assert!(!input.context("gameplay").is_button_down(Button::Left));
+        assert!(input.context("gameplay").is_key_down(Key::Space));
+
+        input.contexts.consume_keyboard("ui");
+        assert!(!input.context("gameplay").is_key_down(Key::Space));
+
+        input.clear_last_frame_inputs();
+        assert!(input.context("gameplay").is_button_down(Button::Left));
+    }
+
+    #[test]
+    fn consuming_from_the_lowest_context_hides_nothing_on_the_stack() {
+        let mut input = InputState::new();
+        input.contexts.push("gameplay");
+        input.contexts.push("ui");
+        input.on_input(&Input::KeyDown(Key::Escape));
+
+        input.contexts.consume("gameplay", Key::Escape);
+
+        assert!(input.context("ui").is_key_down(Key::Escape));
+        assert!(!input.context("unknown").is_key_down(Key::Escape));
+        input.contexts.push("gameplay");
+        assert_eq!(input.contexts.contexts(), ["ui", "gameplay"]);
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+/// Index of a gamepad, in the order they were connected
+pub type GamepadId = usize;
+
+/// Vibration of the motors of a gamepad
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rumble {
+    /// Gamepad vibrating, `None` for all the connected gamepads
+    pub gamepad: Option<GamepadId>,
+    /// Strength of the vibration, from 0 (none) to 1 (the strongest)
+    pub strength: f32,
+    pub duration: Duration,
+}
+
+/// Access to the vibration motors of the gamepads, installed by the gamepad
+/// backend of the runner
+pub trait HapticsBackend {
+    /// Starts a rumble, replacing the one playing on the same gamepads
+    fn play(&mut self, rumble: &Rumble);
+    /// Stops the rumble of a gamepad, or of all the gamepads if `None`
+    fn stop(&mut self, gamepad: Option<GamepadId>);
+}
+
+/// Backend ignoring the rumbles, used when no gamepad backend is available
+#[derive(Debug, Default)]
+pub struct NoHaptics;
+
+impl HapticsBackend for NoHaptics {
+    fn play(&mut self, _rumble: &Rumble) {}
+
+    fn stop(&mut self, _gamepad: Option<GamepadId>) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Command {
+    Play(Rumble),
+    Stop(Option<GamepadId>),
+}
+
+/// Resource through which systems make the gamepads vibrate, e.g. when the
+/// player is hit. The rumbles are queued during the frame and handed to the
+/// [`HapticsBackend`] at its end, so they can be turned off in the settings
+/// with [`Haptics::set_enabled`].
+///
+/// The engine has no gamepad backend yet: the default [`NoHaptics`] backend
+/// drops the rumbles until a runner installs one with
+/// `Engine::set_haptics_backend`.
+pub struct Haptics {
+    backend: Box<dyn HapticsBackend>,
+    commands: Vec<Command>,
+    enabled: bool,
+}
+
+impl Haptics {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_backend(NoHaptics)
+    }
+
+    #[must_use]
+    pub fn with_backend(backend: impl HapticsBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+            commands: vec![],
+            enabled: true,
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: impl HapticsBackend + 'static) {
+        self.backend = Box::new(backend);
+    }
+
+    /// Makes a gamepad vibrate with a strength from 0 to 1 for `duration`
+    pub fn rumble(&mut self, gamepad: GamepadId, strength: f32, duration: Duration) {
+        self.play(Rumble {
+            gamepad: Some(gamepad),
+            strength,
+            duration,
+        });
+    }
+
+    /// Makes all the gamepads vibrate with a strength from 0 to 1 for
+    /// `duration`
+    pub fn rumble_all(&mut self, strength: f32, duration: Duration) {
+        self.play(Rumble {
+            gamepad: None,
+            strength,
+            duration,
+        });
+    }
+
+    pub fn play(&mut self, rumble: Rumble) {
+        if !self.enabled {
+            return;
+        }
+        self.commands.push(Command::Play(Rumble {
+            strength: rumble.strength.clamp(0.0, 1.0),
+            ..rumble
+        }));
+    }
+
+    pub fn stop(&mut self, gamepad: GamepadId) {
+        self.commands.push(Command::Stop(Some(gamepad)));
+    }
+
+    pub fn stop_all(&mut self) {
+        self.commands.push(Command::Stop(None));
+    }
+
+    /// Enables or disables the vibrations, stopping the current ones when
+    /// disabled
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled && !enabled {
+            self.commands
+                .retain(|command| matches!(command, Command::Stop(_)));
+            self.stop_all();
+        }
+        self.enabled = enabled;
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Hands the rumbles queued since the last call to the backend
+    pub fn flush(&mut self) {
+        for command in self.commands.drain(..) {
+            match command {
+                Command::Play(rumble) => self.backend.play(&rumble),
+                Command::Stop(gamepad) => self.backend.stop(gamepad),
+            }
+        }
+    }
+}
+
+impl Default for Haptics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingHaptics(Rc<RefCell<Vec<Command>>>);
+
+    impl HapticsBackend for RecordingHaptics {
+        fn play(&mut self, rumble: &Rumble) {
+            self.0.borrow_mut().push(Command::Play(*rumble));
+        }
+
+        fn stop(&mut self, gamepad: Option<GamepadId>) {
+            self.0.borrow_mut().push(Command::Stop(gamepad));
+        }
+    }
+
+    #[test]
+    fn rumbles_are_played_by_the_backend() {
+        let played = Rc::new(RefCell::new(vec![]));
+        let mut haptics = Haptics::with_backend(RecordingHaptics(Rc::clone(&played)));
+        haptics.rumble(1, 2.0, Duration::from_millis(200));
+        assert!(played.borrow().is_empty());
+
+        haptics.flush();
+        assert_eq!(
+            *played.borrow(),
+            [Command::Play(Rumble {
+                gamepad: Some(1),
+                strength: 1.0,
+                duration: Duration::from_millis(200),
+            })]
+        );
+
+        played.borrow_mut().clear();
+        haptics.rumble_all(0.5, Duration::from_secs(1));
+        haptics.set_enabled(false);
+        haptics.rumble(0, 0.5, Duration::from_secs(1));
+        haptics.flush();
+        assert_eq!(*played.borrow(), [Command::Stop(None)]);
+    }
+}
@@ -1,6 +1,14 @@
 #![warn(clippy::pedantic)]
 
-#[derive(Debug, Clone, Copy)]
+pub mod action;
+pub mod axis;
+pub mod context;
+pub mod haptics;
+pub mod microphone;
+
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
 pub enum Input {
     MouseButtonDown(mouse::Button),
     MouseButtonUp(mouse::Button),
@@ -8,11 +16,17 @@ pub enum Input {
     KeyUp(keyboard::Key),
     MouseMotion((f64, f64)),
     CursorMoved((f64, f64)),
+    /// Text typed by the user, either characters produced by key presses
+    /// with the keyboard layout applied or text committed by an input
+    /// method editor
+    Text(String),
 }
 
 pub struct InputState {
     pub keyboard: keyboard::State,
     pub mouse: mouse::State,
+    pub microphone: microphone::State,
+    pub contexts: context::Stack,
 }
 
 impl InputState {
@@ -21,12 +35,30 @@ impl InputState {
         Self {
             keyboard: keyboard::State::new(),
             mouse: mouse::State::new(),
+            microphone: microphone::State::new(),
+            contexts: context::Stack::new(),
         }
     }
 
     pub fn clear_last_frame_inputs(&mut self) {
         self.mouse.clear_last_frame_inputs();
         self.keyboard.clear_last_frame_inputs();
+        self.contexts.clear_consumed_inputs();
+    }
+
+    /// Advances the clock of the input state at the start of a frame lasting
+    /// `delta_time`, which times the double clicks, the held keys and the key
+    /// repeats
+    pub fn advance_time(&mut self, delta_time: Duration) {
+        self.keyboard.advance_time(delta_time);
+        self.mouse.advance_time(delta_time);
+    }
+
+    /// Returns the inputs seen by the systems of a context, see
+    /// [`context::Stack`]
+    #[must_use]
+    pub fn context(&self, context: &'static str) -> context::ContextInput<'_> {
+        context::ContextInput::new(self, context)
     }
 
     pub fn on_input(&mut self, input: &Input) {
@@ -37,6 +69,7 @@ impl InputState {
             Input::KeyUp(key) => self.keyboard.on_key_up(*key),
             Input::MouseMotion(motion) => self.mouse.on_motion(*motion),
             Input::CursorMoved(position) => self.mouse.on_move(*position),
+            Input::Text(text) => self.keyboard.on_text(text),
         }
     }
 }
@@ -48,18 +81,40 @@ impl Default for InputState {
 }
 
 pub mod mouse {
+    use std::time::Duration;
+
     use log::trace;
+    use serde::{Deserialize, Serialize};
+
+    /// Default maximum time between the clicks of a double click
+    pub const DEFAULT_DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+    /// Default maximum distance in pixels between the clicks of a double
+    /// click
+    pub const DEFAULT_DOUBLE_CLICK_DISTANCE: f64 = 4.0;
 
     #[derive(Default, Debug, Clone, Copy)]
     pub(crate) struct ButtonState {
         pub current: bool,
         pub previous: bool,
+        pub double_clicked: bool,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    struct Click {
+        button: Button,
+        time: Duration,
+        position: (f64, f64),
     }
 
     pub struct State {
+        #[allow(clippy::struct_field_names)]
         pub(super) button_state: [ButtonState; BUTTON_COUNT],
         last_motion: (f64, f64),
         position: (f64, f64),
+        now: Duration,
+        last_click: Option<Click>,
+        double_click_interval: Duration,
+        double_click_distance: f64,
     }
 
     impl State {
@@ -69,9 +124,27 @@ pub mod mouse {
                 button_state: [ButtonState::default(); BUTTON_COUNT],
                 last_motion: (0.0, 0.0),
                 position: (0.0, 0.0),
+                now: Duration::ZERO,
+                last_click: None,
+                double_click_interval: DEFAULT_DOUBLE_CLICK_INTERVAL,
+                double_click_distance: DEFAULT_DOUBLE_CLICK_DISTANCE,
             }
         }
 
+        /// Sets the maximum time and distance in pixels between the clicks
+        /// of a double click
+        pub fn set_double_click(&mut self, interval: Duration, distance: f64) {
+            self.double_click_interval = interval;
+            self.double_click_distance = distance;
+        }
+
+        /// Returns whether a button has been pressed a second time this
+        /// frame, shortly after the first time and close to it
+        #[must_use]
+        pub fn double_clicked(&self, button: Button) -> bool {
+            self.button_state[button as usize].double_clicked
+        }
+
         #[must_use]
         pub fn motion(&self) -> &(f64, f64) {
             &self.last_motion
@@ -112,13 +185,38 @@ pub mod mouse {
 
         pub(crate) fn on_button_down(&mut self, button: Button) {
             trace!("Button down: {button:?}");
-            self.button_state[button as usize].current = true;
+            let button_state = &mut self.button_state[button as usize];
+            if button_state.current {
+                return;
+            }
+            button_state.current = true;
+
+            let click = Click {
+                button,
+                time: self.now,
+                position: self.position,
+            };
+            let is_double_click = self.last_click.is_some_and(|last_click| {
+                last_click.button == button
+                    && click.time.saturating_sub(last_click.time) <= self.double_click_interval
+                    && (click.position.0 - last_click.position.0)
+                        .hypot(click.position.1 - last_click.position.1)
+                        <= self.double_click_distance
+            });
+            button_state.double_clicked |= is_double_click;
+            // A third click starts a new double click
+            self.last_click = (!is_double_click).then_some(click);
+        }
+
+        pub(crate) fn advance_time(&mut self, delta_time: Duration) {
+            self.now += delta_time;
         }
 
         pub(crate) fn clear_last_frame_inputs(&mut self) {
             self.last_motion = (0.0, 0.0);
             for button_state in &mut self.button_state {
                 button_state.previous = button_state.current;
+                button_state.double_clicked = false;
             }
         }
     }
@@ -130,26 +228,60 @@ pub mod mouse {
     }
 
     const BUTTON_COUNT: usize = 4;
-    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
     pub enum Button {
         Left = 0,
         Middle,
         Right,
         Unknown,
     }
+
+    impl Button {
+        /// Every button, indexed by its discriminant
+        pub const ALL: [Button; BUTTON_COUNT] =
+            [Button::Left, Button::Middle, Button::Right, Button::Unknown];
+    }
 }
 
 pub mod keyboard {
+    use std::time::Duration;
+
     use log::trace;
+    use serde::{Deserialize, Serialize};
 
     #[derive(Debug, Default, Copy, Clone)]
     pub(crate) struct KeyState {
         pub current: bool,
         pub previous: bool,
+        pub pressed_at: Duration,
+    }
+
+    /// Timing of the repeats of a held key, e.g. to move the selection of a
+    /// menu while an arrow key is held
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct KeyRepeat {
+        /// Time between the press of the key and its first repeat
+        pub delay: Duration,
+        /// Time between the following repeats
+        pub interval: Duration,
+    }
+
+    impl Default for KeyRepeat {
+        fn default() -> Self {
+            Self {
+                delay: Duration::from_millis(500),
+                interval: Duration::from_millis(50),
+            }
+        }
     }
 
     pub struct State {
+        #[allow(clippy::struct_field_names)]
         pub(super) key_state: [KeyState; KEY_COUNT],
+        text: String,
+        now: Duration,
+        frame_start: Duration,
+        key_repeat: KeyRepeat,
     }
 
     impl State {
@@ -157,13 +289,85 @@ pub mod keyboard {
         pub fn new() -> Self {
             Self {
                 key_state: [KeyState::default(); KEY_COUNT],
+                text: String::new(),
+                now: Duration::ZERO,
+                frame_start: Duration::ZERO,
+                key_repeat: KeyRepeat::default(),
+            }
+        }
+
+        /// Returns how long a key has been held, `None` if it's up
+        #[must_use]
+        pub fn held_duration(&self, key: Key) -> Option<Duration> {
+            let key_state = &self.key_state[key as usize];
+            key_state
+                .current
+                .then(|| self.now.saturating_sub(key_state.pressed_at))
+        }
+
+        /// Returns whether a key has been held for at least `duration`, e.g.
+        /// to release a charged attack
+        #[must_use]
+        pub fn held_for(&self, key: Key, duration: Duration) -> bool {
+            self.held_duration(key)
+                .is_some_and(|held_duration| held_duration >= duration)
+        }
+
+        pub fn set_key_repeat(&mut self, key_repeat: KeyRepeat) {
+            self.key_repeat = key_repeat;
+        }
+
+        #[must_use]
+        pub fn key_repeat(&self) -> KeyRepeat {
+            self.key_repeat
+        }
+
+        /// Returns whether a key has been pressed this frame, or is held and
+        /// repeats this frame according to the [`KeyRepeat`] settings
+        #[must_use]
+        pub fn is_key_repeated(&self, key: Key) -> bool {
+            let key_state = &self.key_state[key as usize];
+            if !key_state.current {
+                return false;
             }
+            if !key_state.previous {
+                return true;
+            }
+            let Some(first_repeat) = key_state.pressed_at.checked_add(self.key_repeat.delay) else {
+                return false;
+            };
+            // Whether the time of a repeat falls within the frame
+            let repeats_until = |time: Duration| {
+                time.checked_sub(first_repeat)
+                    .map_or(0, |since_first_repeat| {
+                        1 + since_first_repeat.as_nanos()
+                            / self.key_repeat.interval.as_nanos().max(1)
+                    })
+            };
+            repeats_until(self.now) > repeats_until(self.frame_start)
+        }
+
+        pub(crate) fn advance_time(&mut self, delta_time: Duration) {
+            self.frame_start = self.now;
+            self.now += delta_time;
         }
 
         pub fn clear_last_frame_inputs(&mut self) {
             for key_state in &mut self.key_state {
                 key_state.previous = key_state.current;
             }
+            self.text.clear();
+        }
+
+        /// Text typed by the user since the last frame
+        #[must_use]
+        pub fn text(&self) -> &str {
+            &self.text
+        }
+
+        pub(crate) fn on_text(&mut self, text: &str) {
+            trace!("Text: {text:?}");
+            self.text.push_str(text);
         }
 
         #[must_use]
@@ -181,6 +385,30 @@ pub mod keyboard {
             !self.key_state[key as usize].current
         }
 
+        /// Returns whether either side of a modifier is down
+        #[must_use]
+        pub fn is_modifier_down(&self, modifier: Modifier) -> bool {
+            modifier.keys().into_iter().any(|key| self.is_key_down(key))
+        }
+
+        /// Returns whether all the keys of a key combination are down, e.g.
+        /// `[Key::LControl, Key::S]` for a save shortcut. The modifier keys
+        /// match both sides of the keyboard, and the modifiers that aren't
+        /// part of the combination must be up, so that control+S isn't
+        /// pressed while control+shift+S is.
+        #[must_use]
+        pub fn is_chord_pressed(&self, keys: &[Key]) -> bool {
+            is_chord_down(keys, |key| self.key_state[key as usize].current)
+        }
+
+        /// Returns whether a key combination has been pressed this frame, see
+        /// [`State::is_chord_pressed`]
+        #[must_use]
+        pub fn is_chord_just_pressed(&self, keys: &[Key]) -> bool {
+            self.is_chord_pressed(keys)
+                && !is_chord_down(keys, |key| self.key_state[key as usize].previous)
+        }
+
         pub(crate) fn on_key_up(&mut self, key: Key) {
             trace!("Key up: {key:?}");
             self.key_state[key as usize].current = false;
@@ -188,7 +416,12 @@ pub mod keyboard {
 
         pub(crate) fn on_key_down(&mut self, key: Key) {
             trace!("Key down: {key:?}");
-            self.key_state[key as usize].current = true;
+            let key_state = &mut self.key_state[key as usize];
+            // The platform repeats the presses of a held key
+            if !key_state.current {
+                key_state.current = true;
+                key_state.pressed_at = self.now;
+            }
         }
     }
 
@@ -198,11 +431,30 @@ pub mod keyboard {
         }
     }
 
+    /// Returns whether a key combination is down according to `is_key_down`,
+    /// see [`State::is_chord_pressed`]
+    fn is_chord_down(keys: &[Key], is_key_down: impl Fn(Key) -> bool) -> bool {
+        let chord_modifiers = keys
+            .iter()
+            .filter_map(|key| Modifier::of_key(*key))
+            .collect::<Vec<_>>();
+        let is_modifier_down = |modifier: Modifier| modifier.keys().into_iter().any(&is_key_down);
+        !keys.is_empty()
+            && keys.iter().all(|key| match Modifier::of_key(*key) {
+                Some(modifier) => is_modifier_down(modifier),
+                None => is_key_down(*key),
+            })
+            && Modifier::ALL
+                .into_iter()
+                .filter(|modifier| !chord_modifiers.contains(modifier))
+                .all(|modifier| !is_modifier_down(modifier))
+    }
+
     // TODO:
     // Use https://doc.rust-lang.org/std/mem/fn.variant_count.html when it stabilizes
     // In the meantime a proc_macro could be made to generate this constant.
-    const KEY_COUNT: usize = 39;
-    #[derive(Debug, Copy, Clone)]
+    const KEY_COUNT: usize = 43;
+    #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
     pub enum Key {
         Escape = 0,
         Return,
@@ -243,12 +495,98 @@ pub mod keyboard {
         Y,
         Z,
         Unknown,
+        // Added after `Unknown` to keep the values of the C API
+        LAlt,
+        RAlt,
+        LSuper,
+        RSuper,
+    }
+
+    impl Key {
+        /// Every key, indexed by its discriminant
+        pub const ALL: [Key; KEY_COUNT] = [
+            Key::Escape,
+            Key::Return,
+            Key::LShift,
+            Key::RShift,
+            Key::LControl,
+            Key::RControl,
+            Key::Backspace,
+            Key::Space,
+            Key::ArrowUp,
+            Key::ArrowDown,
+            Key::ArrowLeft,
+            Key::ArrowRight,
+            Key::A,
+            Key::B,
+            Key::C,
+            Key::D,
+            Key::E,
+            Key::F,
+            Key::G,
+            Key::H,
+            Key::I,
+            Key::J,
+            Key::K,
+            Key::L,
+            Key::M,
+            Key::N,
+            Key::O,
+            Key::P,
+            Key::Q,
+            Key::R,
+            Key::S,
+            Key::T,
+            Key::U,
+            Key::V,
+            Key::W,
+            Key::X,
+            Key::Y,
+            Key::Z,
+            Key::Unknown,
+            Key::LAlt,
+            Key::RAlt,
+            Key::LSuper,
+            Key::RSuper,
+        ];
     }
 
+    /// Modifier key, which can be pressed on either side of the keyboard
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub enum Modifier {
         Shift,
-        LControl,
-        RControl,
+        Control,
+        Alt,
+        /// The Windows key, or the command key on macOS
+        Super,
+    }
+
+    impl Modifier {
+        pub const ALL: [Modifier; 4] = [
+            Modifier::Shift,
+            Modifier::Control,
+            Modifier::Alt,
+            Modifier::Super,
+        ];
+
+        /// Returns the left and right keys of the modifier
+        #[must_use]
+        pub fn keys(self) -> [Key; 2] {
+            match self {
+                Modifier::Shift => [Key::LShift, Key::RShift],
+                Modifier::Control => [Key::LControl, Key::RControl],
+                Modifier::Alt => [Key::LAlt, Key::RAlt],
+                Modifier::Super => [Key::LSuper, Key::RSuper],
+            }
+        }
+
+        /// Returns the modifier a key is one of the sides of
+        #[must_use]
+        pub fn of_key(key: Key) -> Option<Modifier> {
+            Modifier::ALL
+                .into_iter()
+                .find(|modifier| modifier.keys().contains(&key))
+        }
     }
 }
 
@@ -258,6 +596,18 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn keys_and_buttons_are_indexed_by_their_discriminant() {
+        assert!(Key::ALL
+            .iter()
+            .enumerate()
+            .all(|(index, key)| *key as usize == index));
+        assert!(mouse::Button::ALL
+            .iter()
+            .enumerate()
+            .all(|(index, button)| *button as usize == index));
+    }
+
     #[test]
     fn input_state_initial_key_state_is_false() {
         let input = InputState::new();
@@ -286,4 +636,98 @@ mod tests {
         input.on_input(&Input::KeyDown(Key::A));
         assert!(input.keyboard.is_key_down(Key::A));
     }
+
+    #[test]
+    fn chords_match_either_side_of_their_modifiers() {
+        use crate::keyboard::Modifier;
+
+        let mut input = InputState::new();
+        input.on_input(&Input::KeyDown(Key::RControl));
+        input.on_input(&Input::KeyDown(Key::S));
+        assert!(input.keyboard.is_modifier_down(Modifier::Control));
+        assert!(input.keyboard.is_chord_pressed(&[Key::LControl, Key::S]));
+        assert!(input
+            .keyboard
+            .is_chord_just_pressed(&[Key::LControl, Key::S]));
+
+        input.clear_last_frame_inputs();
+        assert!(!input
+            .keyboard
+            .is_chord_just_pressed(&[Key::LControl, Key::S]));
+
+        input.on_input(&Input::KeyDown(Key::LShift));
+        assert!(!input.keyboard.is_chord_pressed(&[Key::LControl, Key::S]));
+        assert!(input
+            .keyboard
+            .is_chord_just_pressed(&[Key::LControl, Key::LShift, Key::S]));
+    }
+
+    #[test]
+    fn double_clicks_are_close_in_time_and_space() {
+        use std::time::Duration;
+
+        use crate::mouse::Button;
+
+        let click = |input: &mut InputState| {
+            input.on_input(&Input::MouseButtonDown(Button::Left));
+            let double_clicked = input.mouse.double_clicked(Button::Left);
+            input.on_input(&Input::MouseButtonUp(Button::Left));
+            input.clear_last_frame_inputs();
+            input.advance_time(Duration::from_millis(100));
+            double_clicked
+        };
+        let mut input = InputState::new();
+        assert!(!click(&mut input));
+        assert!(click(&mut input));
+        // The third click starts a new double click
+        assert!(!click(&mut input));
+
+        input.advance_time(Duration::from_secs(1));
+        assert!(!click(&mut input));
+        input.on_input(&Input::CursorMoved((50.0, 0.0)));
+        assert!(!click(&mut input));
+    }
+
+    #[test]
+    fn held_keys_are_timed_and_repeated() {
+        use std::time::Duration;
+
+        let mut input = InputState::new();
+        input.on_input(&Input::KeyDown(Key::Space));
+        input.advance_time(Duration::from_millis(16));
+        assert!(input.keyboard.is_key_repeated(Key::Space));
+        assert!(!input
+            .keyboard
+            .held_for(Key::Space, Duration::from_millis(500)));
+
+        let mut repeats = 0;
+        for _ in 0..35 {
+            input.clear_last_frame_inputs();
+            // Repeated presses from the platform don't restart the hold
+            input.on_input(&Input::KeyDown(Key::Space));
+            input.advance_time(Duration::from_millis(20));
+            if input.keyboard.is_key_repeated(Key::Space) {
+                repeats += 1;
+            }
+        }
+
+        // Held for 716ms, repeated at 500ms then every 50ms
+        assert!(input
+            .keyboard
+            .held_for(Key::Space, Duration::from_millis(700)));
+        assert_eq!(repeats, 5);
+        input.on_input(&Input::KeyUp(Key::Space));
+        assert_eq!(input.keyboard.held_duration(Key::Space), None);
+    }
+
+    #[test]
+    fn input_state_text_is_accumulated_until_cleared() {
+        let mut input = InputState::new();
+        input.on_input(&Input::Text("é".into()));
+        input.on_input(&Input::Text("日本".into()));
+        assert_eq!(input.keyboard.text(), "é日本");
+
+        input.clear_last_frame_inputs();
+        assert_eq!(input.keyboard.text(), "");
+    }
 }
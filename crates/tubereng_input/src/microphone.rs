@@ -0,0 +1,344 @@
+/// State of the permission to capture the microphone
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// No capture has been started yet
+    #[default]
+    NotRequested,
+    /// The user is being asked for the permission
+    Pending,
+    Granted,
+    /// The permission has been refused, or no input device is available
+    Denied,
+}
+
+/// Microphone input of the current frame. Samples are downmixed to mono and
+/// normalized in `[-1.0, 1.0]`.
+#[derive(Debug, Default)]
+pub struct State {
+    permission: Permission,
+    sample_rate: u32,
+    samples: Vec<f32>,
+    loudness: f32,
+}
+
+impl State {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn permission(&self) -> Permission {
+        self.permission
+    }
+
+    /// Sample rate of the captured samples, 0 if nothing has been captured
+    #[must_use]
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Samples captured since the previous frame
+    #[must_use]
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// Root mean square of the samples captured since the previous frame,
+    /// from 0 (silence) to 1
+    #[must_use]
+    pub fn loudness(&self) -> f32 {
+        self.loudness
+    }
+
+    pub fn on_permission_changed(&mut self, permission: Permission) {
+        self.permission = permission;
+    }
+
+    /// Replaces the samples of the previous frame with the samples captured
+    /// since then
+    pub fn on_frame_samples(&mut self, sample_rate: u32, samples: impl IntoIterator<Item = f32>) {
+        self.sample_rate = sample_rate;
+        self.samples.clear();
+        self.samples.extend(samples);
+
+        #[allow(clippy::cast_precision_loss)]
+        let mean_square = if self.samples.is_empty() {
+            0.0
+        } else {
+            self.samples
+                .iter()
+                .map(|sample| sample * sample)
+                .sum::<f32>()
+                / self.samples.len() as f32
+        };
+        self.loudness = mean_square.sqrt().min(1.0);
+    }
+}
+
+/// Captures the default input device of the system
+///
+/// The stream is opened by [`Capture::start`], which is when the operating
+/// system asks for the permission if it requires it. Captured samples are
+/// handed to the [`State`] by [`Capture::poll`], once per frame.
+#[cfg(all(feature = "microphone", not(target_arch = "wasm32")))]
+pub struct Capture {
+    _stream: cpal::Stream,
+    receiver: std::sync::mpsc::Receiver<Vec<f32>>,
+    sample_rate: u32,
+}
+
+#[cfg(feature = "microphone")]
+#[derive(Debug)]
+pub enum CaptureError {
+    NoInputDevice,
+    UnsupportedSampleFormat,
+    StreamCreationFailed,
+}
+
+#[cfg(all(feature = "microphone", not(target_arch = "wasm32")))]
+impl Capture {
+    /// Starts capturing the default input device
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if there is no input device, or if the capture
+    /// stream cannot be opened, for instance because the permission was denied
+    pub fn start() -> Result<Self, CaptureError> {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or(CaptureError::NoInputDevice)?;
+        let config = device
+            .default_input_config()
+            .map_err(|_| CaptureError::NoInputDevice)?;
+        let channels = usize::from(config.channels());
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let on_error = |error| log::warn!("Microphone capture error: {error}");
+
+        let stream = match config.sample_format() {
+            cpal::SampleFormat::F32 => device.build_input_stream(
+                &config.config(),
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    let _ = sender.send(downmix(data.iter().copied(), channels));
+                },
+                on_error,
+                None,
+            ),
+            cpal::SampleFormat::I16 => device.build_input_stream(
+                &config.config(),
+                move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                    let samples = data.iter().map(|&sample| f32::from(sample) / 32768.0);
+                    let _ = sender.send(downmix(samples, channels));
+                },
+                on_error,
+                None,
+            ),
+            _ => return Err(CaptureError::UnsupportedSampleFormat),
+        }
+        .map_err(|_| CaptureError::StreamCreationFailed)?;
+        stream
+            .play()
+            .map_err(|_| CaptureError::StreamCreationFailed)?;
+
+        Ok(Self {
+            _stream: stream,
+            receiver,
+            sample_rate: config.sample_rate().0,
+        })
+    }
+
+    /// Permission state of the capture, the stream being opened once the
+    /// capture is started
+    #[must_use]
+    pub fn permission(&self) -> Permission {
+        Permission::Granted
+    }
+
+    /// Hands the samples captured since the last call to the state
+    pub fn poll(&self, state: &mut State) {
+        state.on_frame_samples(self.sample_rate, self.receiver.try_iter().flatten());
+    }
+}
+
+/// Captures the microphone of the browser
+///
+/// [`Capture::start`] asks for the permission with `getUserMedia`, the
+/// permission stays [`Permission::Pending`] until the user answers. Captured
+/// samples and permission changes are handed to the [`State`] by
+/// [`Capture::poll`], once per frame.
+#[cfg(all(feature = "microphone", target_arch = "wasm32"))]
+pub struct Capture {
+    shared: std::rc::Rc<std::cell::RefCell<WebCapture>>,
+}
+
+/// State shared between the [`Capture`] and the browser callbacks
+#[cfg(all(feature = "microphone", target_arch = "wasm32"))]
+struct WebCapture {
+    permission: Permission,
+    sample_rate: u32,
+    samples: Vec<f32>,
+    /// Nodes processing the microphone stream, kept alive while capturing
+    audio_context: Option<web_sys::AudioContext>,
+    on_audio_process:
+        Option<wasm_bindgen::closure::Closure<dyn FnMut(web_sys::AudioProcessingEvent)>>,
+}
+
+#[cfg(all(feature = "microphone", target_arch = "wasm32"))]
+impl Capture {
+    /// Asks the browser for the permission to capture the microphone
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the browser doesn't expose media devices, e.g.
+    /// outside of a secure context
+    pub fn start() -> Result<Self, CaptureError> {
+        use std::{cell::RefCell, rc::Rc};
+        use wasm_bindgen::{JsCast, JsValue};
+
+        let media_devices = web_sys::window()
+            .ok_or(CaptureError::NoInputDevice)?
+            .navigator()
+            .media_devices()
+            .map_err(|_| CaptureError::NoInputDevice)?;
+        let constraints = web_sys::MediaStreamConstraints::new();
+        constraints.set_audio(&JsValue::TRUE);
+        let user_media = media_devices
+            .get_user_media_with_constraints(&constraints)
+            .map_err(|_| CaptureError::StreamCreationFailed)?;
+
+        let shared = Rc::new(RefCell::new(WebCapture {
+            permission: Permission::Pending,
+            sample_rate: 0,
+            samples: vec![],
+            audio_context: None,
+            on_audio_process: None,
+        }));
+        let capture = Rc::downgrade(&shared);
+        wasm_bindgen_futures::spawn_local(async move {
+            let stream = wasm_bindgen_futures::JsFuture::from(user_media)
+                .await
+                .ok()
+                .and_then(|stream| stream.dyn_into::<web_sys::MediaStream>().ok());
+            // The capture may have been dropped while the user was asked
+            let Some(capture) = capture.upgrade() else {
+                return;
+            };
+            let started = stream.map(|stream| start_web_capture(&capture, &stream));
+            if let Some(Err(error)) = &started {
+                log::warn!("Microphone capture error: {error:?}");
+            }
+            if !matches!(started, Some(Ok(()))) {
+                capture.borrow_mut().permission = Permission::Denied;
+            }
+        });
+
+        Ok(Self { shared })
+    }
+
+    #[must_use]
+    pub fn permission(&self) -> Permission {
+        self.shared.borrow().permission
+    }
+
+    /// Hands the permission state and the samples captured since the last
+    /// call to the state
+    pub fn poll(&self, state: &mut State) {
+        let mut capture = self.shared.borrow_mut();
+        if state.permission() != capture.permission {
+            state.on_permission_changed(capture.permission);
+        }
+        let samples = std::mem::take(&mut capture.samples);
+        state.on_frame_samples(capture.sample_rate, samples);
+    }
+}
+
+#[cfg(all(feature = "microphone", target_arch = "wasm32"))]
+impl Drop for Capture {
+    fn drop(&mut self) {
+        if let Some(audio_context) = self.shared.borrow_mut().audio_context.take() {
+            let _ = audio_context.close();
+        }
+    }
+}
+
+/// Routes the microphone stream through a script processor node which copies
+/// the samples of the first channel into the shared state
+#[cfg(all(feature = "microphone", target_arch = "wasm32"))]
+fn start_web_capture(
+    capture: &std::rc::Rc<std::cell::RefCell<WebCapture>>,
+    stream: &web_sys::MediaStream,
+) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    const BUFFER_SIZE: u32 = 4096;
+
+    let audio_context = web_sys::AudioContext::new()?;
+    let source = audio_context.create_media_stream_source(stream)?;
+    let processor = audio_context
+        .create_script_processor_with_buffer_size_and_number_of_input_channels_and_number_of_output_channels(
+            BUFFER_SIZE,
+            1,
+            1,
+        )?;
+
+    let samples_capture = std::rc::Rc::downgrade(capture);
+    let on_audio_process =
+        Closure::<dyn FnMut(_)>::new(move |event: web_sys::AudioProcessingEvent| {
+            let Some(capture) = samples_capture.upgrade() else {
+                return;
+            };
+            if let Ok(samples) = event
+                .input_buffer()
+                .and_then(|buffer| buffer.get_channel_data(0))
+            {
+                capture.borrow_mut().samples.extend(samples);
+            }
+        });
+    processor.set_onaudioprocess(Some(on_audio_process.as_ref().unchecked_ref()));
+    source.connect_with_audio_node(&processor)?;
+    // Script processors only run while connected to the destination, their
+    // output is silent as nothing is written to it
+    processor.connect_with_audio_node(&audio_context.destination())?;
+
+    let mut capture = capture.borrow_mut();
+    // Sample rates are positive integers stored as floats
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let sample_rate = audio_context.sample_rate() as u32;
+    capture.sample_rate = sample_rate;
+    capture.permission = Permission::Granted;
+    capture.audio_context = Some(audio_context);
+    capture.on_audio_process = Some(on_audio_process);
+    Ok(())
+}
+
+#[cfg(all(feature = "microphone", not(target_arch = "wasm32")))]
+fn downmix(samples: impl Iterator<Item = f32>, channels: usize) -> Vec<f32> {
+    let samples = samples.collect::<Vec<_>>();
+    #[allow(clippy::cast_precision_loss)]
+    samples
+        .chunks(channels.max(1))
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_loudness() {
+        let mut state = State::new();
+        assert!(state.loudness() < f32::EPSILON);
+
+        state.on_frame_samples(48000, [0.5, -0.5, 0.5, -0.5]);
+        assert_eq!(state.sample_rate(), 48000);
+        assert_eq!(state.samples().len(), 4);
+        assert!((state.loudness() - 0.5).abs() < f32::EPSILON);
+
+        state.on_frame_samples(48000, []);
+        assert!(state.samples().is_empty());
+        assert!(state.loudness() < f32::EPSILON);
+    }
+}
@@ -0,0 +1,571 @@
+#![warn(clippy::pedantic)]
+
+//! `LDtk` importer, loading the tile layers and the entity layers of the levels
+//! of `.ldtk` projects.
+//!
+//! The levels of a loaded [`Ldtk`] are spawned with [`spawn_level`], the tiles
+//! being drawn as sprites of the textures of their tilesets, loaded from the
+//! paths returned by [`resolve_path`].
+//!
+//! The levels must be saved in the project file, the "Save levels to separate
+//! files" option of `LDtk` isn't supported.
+
+use std::{collections::HashMap, hash::BuildHasher};
+
+use log::warn;
+use tubereng_asset::{
+    json::Value, Asset, AssetError, AssetHandle, AssetLoader, AssetStore, Result,
+};
+use tubereng_core::Transform;
+use tubereng_ecs::{relationship::ChildOf, Ecs, EntityId};
+use tubereng_math::vector::Vector3f;
+use tubereng_renderer::{
+    sprite::{Sprite, SpriteBundle},
+    texture,
+};
+
+const FLIP_X: usize = 1;
+const FLIP_Y: usize = 2;
+
+/// `LDtk` project loaded from a `.ldtk` file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ldtk {
+    pub tilesets: Vec<Tileset>,
+    pub levels: Vec<Level>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tileset {
+    pub uid: usize,
+    pub identifier: String,
+    /// Path of the image of the tileset, relative to the project file, `None`
+    /// for the internal icons of `LDtk`
+    pub path: Option<String>,
+    pub tile_size: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+    pub identifier: String,
+    /// Position of the level in the world, in pixels
+    pub position: [f32; 2],
+    pub width: f32,
+    pub height: f32,
+    /// Layers of the level, from the top-most to the bottom-most as in `LDtk`
+    pub layers: Vec<Layer>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub identifier: String,
+    /// Offset of the layer in the level, in pixels
+    pub offset: [f32; 2],
+    pub visible: bool,
+    /// Uid of the tileset of the tiles, see [`Ldtk::tileset`]
+    pub tileset: Option<usize>,
+    /// Tiles of a tile layer, or of the auto-layer rules of an `IntGrid` or
+    /// auto-layer
+    pub tiles: Vec<Tile>,
+    /// Entities of an entity layer
+    pub entities: Vec<Entity>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tile {
+    /// Position of the top left corner of the tile in the layer, in pixels
+    pub position: [f32; 2],
+    /// Area of the tile in the tileset, in pixels
+    pub source: texture::Rect,
+    pub flip_x: bool,
+    pub flip_y: bool,
+}
+
+/// Instance of an `LDtk` entity, e.g. a spawn point or a chest
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    pub identifier: String,
+    pub iid: String,
+    /// Position of the pivot of the entity in the layer, in pixels
+    pub position: [f32; 2],
+    pub width: f32,
+    pub height: f32,
+    /// Custom fields of the entity, by identifier
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Ldtk {
+    #[must_use]
+    pub fn level(&self, identifier: &str) -> Option<&Level> {
+        self.levels
+            .iter()
+            .find(|level| level.identifier == identifier)
+    }
+
+    #[must_use]
+    pub fn tileset(&self, uid: usize) -> Option<&Tileset> {
+        self.tilesets.iter().find(|tileset| tileset.uid == uid)
+    }
+}
+
+impl Entity {
+    /// Returns the value of the custom field `identifier`, `None` if the
+    /// entity doesn't have it
+    #[must_use]
+    pub fn field(&self, identifier: &str) -> Option<&Value> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == identifier)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Resolves a path relative to the path of an `.ldtk` file, e.g.
+/// `tiles.png` next to `maps/world.ldtk` is `maps/tiles.png`
+#[must_use]
+pub fn resolve_path(file_path: &str, relative_path: &str) -> String {
+    match file_path.rfind('/') {
+        Some(separator) => format!("{}{relative_path}", &file_path[..=separator]),
+        None => relative_path.to_string(),
+    }
+}
+
+/// Level spawned by [`spawn_level`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdtkLevel {
+    pub identifier: String,
+}
+
+/// Layer spawned by [`spawn_level`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LdtkLayer {
+    pub identifier: String,
+}
+
+/// Inserts a level of an `LDtk` project stored in the [`AssetStore`]
+/// resource. The level is spawned with a [`Transform`] at its position in the
+/// world and an [`LdtkLevel`], with its visible layers as children with an
+/// [`LdtkLayer`]. The tiles are spawned as children of their layer with a
+/// [`SpriteBundle`] of the texture of their tileset in `tileset_textures`, by
+/// tileset uid, and the entities with their [`Transform`] and their
+/// [`Entity`] as component. The bottom-most layers are spawned first to be
+/// drawn beneath the others. Returns the ids of the entities, parents first,
+/// or `None` if the asset isn't loaded or has no such level.
+pub fn spawn_level<S: BuildHasher>(
+    ecs: &mut Ecs,
+    ldtk: &AssetHandle<Ldtk>,
+    level: &str,
+    tileset_textures: &HashMap<usize, texture::Id, S>,
+) -> Option<Vec<EntityId>> {
+    // The level is cloned as the asset store is borrowed from the ECS
+    let level = {
+        let asset_store = ecs.resource::<AssetStore>()?;
+        asset_store.get(ldtk)?.level(level)?.clone()
+    };
+
+    let level_id = ecs.insert((
+        translation(level.position),
+        LdtkLevel {
+            identifier: level.identifier,
+        },
+    ));
+    let mut entity_ids = vec![level_id];
+    for layer in level.layers.into_iter().rev().filter(|layer| layer.visible) {
+        let layer_id = ecs.insert((
+            translation(layer.offset),
+            LdtkLayer {
+                identifier: layer.identifier.clone(),
+            },
+        ));
+        ecs.insert_relationship::<ChildOf>(layer_id, level_id);
+        entity_ids.push(layer_id);
+
+        let texture = layer
+            .tileset
+            .and_then(|tileset| tileset_textures.get(&tileset));
+        match texture {
+            Some(&texture) => {
+                for tile in layer.tiles {
+                    let tile_id = ecs.insert(SpriteBundle {
+                        transform: tile_transform(&tile),
+                        sprite: Sprite {
+                            texture,
+                            texture_rect: Some(tile.source),
+                        },
+                    });
+                    ecs.insert_relationship::<ChildOf>(tile_id, layer_id);
+                    entity_ids.push(tile_id);
+                }
+            }
+            None if !layer.tiles.is_empty() => warn!(
+                "Skipping the tiles of layer {}, the texture of its tileset is missing",
+                layer.identifier
+            ),
+            None => {}
+        }
+
+        for entity in layer.entities {
+            let entity_id = ecs.insert((translation(entity.position), entity));
+            ecs.insert_relationship::<ChildOf>(entity_id, layer_id);
+            entity_ids.push(entity_id);
+        }
+    }
+    Some(entity_ids)
+}
+
+fn translation([x, y]: [f32; 2]) -> Transform {
+    Transform {
+        translation: Vector3f::new(x, y, 0.0),
+        ..Default::default()
+    }
+}
+
+/// Returns the transform of a tile, the flipped tiles being mirrored around
+/// their center
+fn tile_transform(tile: &Tile) -> Transform {
+    let mut transform = translation(tile.position);
+    if tile.flip_x {
+        transform.scale.x = -1.0;
+        transform.translation.x += tile.source.width;
+    }
+    if tile.flip_y {
+        transform.scale.y = -1.0;
+        transform.translation.y += tile.source.height;
+    }
+    transform
+}
+
+impl Asset for Ldtk {
+    type Loader = LdtkLoader;
+}
+
+pub struct LdtkLoader;
+impl AssetLoader<Ldtk> for LdtkLoader {
+    fn load(file_content: &[u8]) -> Result<Ldtk> {
+        let source =
+            std::str::from_utf8(file_content).map_err(|_| AssetError::LdtkDeserializationFailed)?;
+        let document = Value::parse(source)?;
+        let tilesets = document
+            .get("defs")
+            .and_then(|defs| defs.get("tilesets"))
+            .map_or(&[][..], Value::items)
+            .iter()
+            .map(parse_tileset)
+            .collect::<Result<_>>()?;
+
+        // Multi-world projects have their levels in their worlds
+        let mut levels = document
+            .get("levels")
+            .map_or(&[][..], Value::items)
+            .iter()
+            .collect::<Vec<_>>();
+        for world in document.get("worlds").map_or(&[][..], Value::items) {
+            levels.extend(world.get("levels").map_or(&[][..], Value::items));
+        }
+        let levels = levels.into_iter().map(parse_level).collect::<Result<_>>()?;
+        Ok(Ldtk { tilesets, levels })
+    }
+}
+
+fn string(value: &Value, key: &str) -> Result<String> {
+    value
+        .get(key)
+        .and_then(Value::as_str)
+        .map(ToString::to_string)
+        .ok_or(AssetError::LdtkDeserializationFailed)
+}
+
+fn number(value: &Value, key: &str) -> Result<f32> {
+    value
+        .get(key)
+        .and_then(Value::as_f32)
+        .ok_or(AssetError::LdtkDeserializationFailed)
+}
+
+fn index(value: &Value, key: &str) -> Result<usize> {
+    value
+        .get(key)
+        .and_then(Value::as_usize)
+        .ok_or(AssetError::LdtkDeserializationFailed)
+}
+
+fn point(value: &Value, key: &str) -> Result<[f32; 2]> {
+    value
+        .get(key)
+        .and_then(Value::as_f32_array)
+        .ok_or(AssetError::LdtkDeserializationFailed)
+}
+
+fn parse_tileset(tileset: &Value) -> Result<Tileset> {
+    Ok(Tileset {
+        uid: index(tileset, "uid")?,
+        identifier: string(tileset, "identifier")?,
+        path: tileset
+            .get("relPath")
+            .and_then(Value::as_str)
+            .map(ToString::to_string),
+        tile_size: number(tileset, "tileGridSize")?,
+    })
+}
+
+fn parse_level(level: &Value) -> Result<Level> {
+    let identifier = string(level, "identifier")?;
+    let layers = if let Some(Value::Array(layers)) = level.get("layerInstances") {
+        layers.iter().map(parse_layer).collect::<Result<_>>()?
+    } else {
+        warn!("Level {identifier} is saved in a separate file, its layers are not loaded");
+        vec![]
+    };
+    Ok(Level {
+        position: [number(level, "worldX")?, number(level, "worldY")?],
+        width: number(level, "pxWid")?,
+        height: number(level, "pxHei")?,
+        identifier,
+        layers,
+    })
+}
+
+fn parse_layer(layer: &Value) -> Result<Layer> {
+    let grid_size = number(layer, "__gridSize")?;
+    let tiles = ["gridTiles", "autoLayerTiles"]
+        .into_iter()
+        .flat_map(|key| layer.get(key).map_or(&[][..], Value::items))
+        .map(|tile| parse_tile(tile, grid_size))
+        .collect::<Result<_>>()?;
+    let entities = layer
+        .get("entityInstances")
+        .map_or(&[][..], Value::items)
+        .iter()
+        .map(parse_entity)
+        .collect::<Result<_>>()?;
+    Ok(Layer {
+        identifier: string(layer, "__identifier")?,
+        offset: [
+            number(layer, "__pxTotalOffsetX")?,
+            number(layer, "__pxTotalOffsetY")?,
+        ],
+        visible: layer
+            .get("visible")
+            .and_then(Value::as_bool)
+            .unwrap_or(true),
+        tileset: layer.get("__tilesetDefUid").and_then(Value::as_usize),
+        tiles,
+        entities,
+    })
+}
+
+fn parse_tile(tile: &Value, grid_size: f32) -> Result<Tile> {
+    let [source_x, source_y] = point(tile, "src")?;
+    let flags = index(tile, "f")?;
+    Ok(Tile {
+        position: point(tile, "px")?,
+        source: texture::Rect::new(source_x, source_y, grid_size, grid_size),
+        flip_x: flags & FLIP_X != 0,
+        flip_y: flags & FLIP_Y != 0,
+    })
+}
+
+fn parse_entity(entity: &Value) -> Result<Entity> {
+    let fields = entity
+        .get("fieldInstances")
+        .map_or(&[][..], Value::items)
+        .iter()
+        .map(|field| {
+            Ok((
+                string(field, "__identifier")?,
+                field.get("__value").cloned().unwrap_or(Value::Null),
+            ))
+        })
+        .collect::<Result<_>>()?;
+    Ok(Entity {
+        identifier: string(entity, "__identifier")?,
+        iid: string(entity, "iid")?,
+        position: point(entity, "px")?,
+        width: number(entity, "width")?,
+        height: number(entity, "height")?,
+        fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &str = r#"{
+        "jsonVersion": "1.5.3",
+        "defs": {
+            "tilesets": [
+                { "uid": 7, "identifier": "Dungeon", "relPath": "tiles/dungeon.png", "tileGridSize": 16 },
+                { "uid": 8, "identifier": "Internal_Icons", "relPath": null, "tileGridSize": 16 }
+            ]
+        },
+        "levels": [{
+            "identifier": "Entrance",
+            "worldX": 256,
+            "worldY": 0,
+            "pxWid": 64,
+            "pxHei": 32,
+            "layerInstances": [
+                {
+                    "__identifier": "Objects",
+                    "__type": "Entities",
+                    "__gridSize": 16,
+                    "__pxTotalOffsetX": 0,
+                    "__pxTotalOffsetY": 0,
+                    "__tilesetDefUid": null,
+                    "visible": true,
+                    "gridTiles": [],
+                    "autoLayerTiles": [],
+                    "entityInstances": [{
+                        "__identifier": "Chest",
+                        "iid": "a1b2",
+                        "px": [24, 16],
+                        "width": 16,
+                        "height": 16,
+                        "fieldInstances": [
+                            { "__identifier": "loot", "__type": "String", "__value": "key" },
+                            { "__identifier": "locked", "__type": "Bool", "__value": true }
+                        ]
+                    }]
+                },
+                {
+                    "__identifier": "Ground",
+                    "__type": "Tiles",
+                    "__gridSize": 16,
+                    "__pxTotalOffsetX": 0,
+                    "__pxTotalOffsetY": 8,
+                    "__tilesetDefUid": 7,
+                    "visible": true,
+                    "gridTiles": [
+                        { "px": [0, 0], "src": [32, 0], "f": 0, "t": 2, "a": 1 },
+                        { "px": [16, 0], "src": [32, 16], "f": 1, "t": 18, "a": 1 }
+                    ],
+                    "autoLayerTiles": [],
+                    "entityInstances": []
+                },
+                {
+                    "__identifier": "Hidden",
+                    "__type": "Tiles",
+                    "__gridSize": 16,
+                    "__pxTotalOffsetX": 0,
+                    "__pxTotalOffsetY": 0,
+                    "__tilesetDefUid": 7,
+                    "visible": false,
+                    "gridTiles": [{ "px": [0, 0], "src": [0, 0], "f": 3, "t": 0, "a": 1 }],
+                    "autoLayerTiles": [],
+                    "entityInstances": []
+                }
+            ]
+        }]
+    }"#;
+
+    #[test]
+    fn load_project() {
+        let ldtk = LdtkLoader::load(PROJECT.as_bytes()).unwrap();
+        assert_eq!(ldtk.tilesets.len(), 2);
+        let tileset = ldtk.tileset(7).unwrap();
+        assert_eq!(tileset.path.as_deref(), Some("tiles/dungeon.png"));
+        assert!(ldtk.tileset(8).unwrap().path.is_none());
+        assert_eq!(
+            resolve_path("maps/world.ldtk", "tiles/dungeon.png"),
+            "maps/tiles/dungeon.png"
+        );
+
+        let level = ldtk.level("Entrance").unwrap();
+        assert_eq!(level.layers.len(), 3);
+        let ground = &level.layers[1];
+        assert_eq!(ground.tileset, Some(7));
+        assert_eq!(
+            ground.tiles[1],
+            Tile {
+                position: [16.0, 0.0],
+                source: texture::Rect::new(32.0, 16.0, 16.0, 16.0),
+                flip_x: true,
+                flip_y: false,
+            }
+        );
+        assert!(!level.layers[2].visible);
+
+        let chest = &level.layers[0].entities[0];
+        assert_eq!(chest.identifier, "Chest");
+        assert_eq!(chest.field("loot").and_then(Value::as_str), Some("key"));
+        assert_eq!(chest.field("locked").and_then(Value::as_bool), Some(true));
+        assert!(chest.field("gold").is_none());
+    }
+
+    #[test]
+    fn invalid_projects_are_rejected() {
+        let project = PROJECT.replace(r#""iid": "a1b2","#, "");
+        assert!(matches!(
+            LdtkLoader::load(project.as_bytes()),
+            Err(AssetError::LdtkDeserializationFailed)
+        ));
+    }
+
+    #[test]
+    fn flipped_tiles_are_mirrored_in_place() {
+        let ldtk = LdtkLoader::load(PROJECT.as_bytes()).unwrap();
+        let level = ldtk.level("Entrance").unwrap();
+        let transform = tile_transform(&level.layers[1].tiles[1]);
+        assert_eq!(transform.translation, Vector3f::new(32.0, 0.0, 0.0));
+        assert_eq!(transform.scale, Vector3f::new(-1.0, 1.0, 1.0));
+        let transform = tile_transform(&level.layers[2].tiles[0]);
+        assert_eq!(transform.translation, Vector3f::new(16.0, 16.0, 0.0));
+        assert_eq!(transform.scale, Vector3f::new(-1.0, -1.0, 1.0));
+    }
+
+    #[test]
+    fn spawn_level_hierarchy() {
+        struct NoFileSystem;
+        impl tubereng_asset::vfs::VirtualFileSystem for NoFileSystem {
+            fn read_bytes(&self, _path: &str) -> Result<Vec<u8>> {
+                Err(AssetError::ReadFailed)
+            }
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.define_relationship::<ChildOf>();
+        let mut asset_store = AssetStore::new(NoFileSystem);
+        let ldtk = asset_store.store(LdtkLoader::load(PROJECT.as_bytes()).unwrap());
+        ecs.insert_resource(asset_store);
+
+        // The textures of the tilesets are uploaded by the renderer, the
+        // tiles are skipped without them
+        let textures = HashMap::new();
+        assert!(spawn_level(&mut ecs, &ldtk, "Exit", &textures).is_none());
+        let entities = spawn_level(&mut ecs, &ldtk, "Entrance", &textures).unwrap();
+        // The hidden layer is skipped, and the bottom-most layer comes first
+        let [level, ground, objects, chest] = entities[..] else {
+            panic!("unexpected entities {entities:?}");
+        };
+        assert_eq!(
+            ecs.component::<LdtkLevel>(level),
+            Some(&LdtkLevel {
+                identifier: "Entrance".into()
+            })
+        );
+        assert_eq!(
+            ecs.component::<LdtkLayer>(ground),
+            Some(&LdtkLayer {
+                identifier: "Ground".into()
+            })
+        );
+        assert_eq!(
+            ecs.component::<Transform>(level).unwrap().translation,
+            Vector3f::new(256.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            ecs.component::<Transform>(ground).unwrap().translation,
+            Vector3f::new(0.0, 8.0, 0.0)
+        );
+        let child_of = ecs.relationship::<ChildOf>().unwrap();
+        assert!(child_of.contains(ground, level));
+        assert!(child_of.contains(objects, level));
+        assert!(child_of.contains(chest, objects));
+
+        assert_eq!(
+            ecs.component::<Transform>(chest).unwrap().translation,
+            Vector3f::new(24.0, 16.0, 0.0)
+        );
+        assert_eq!(ecs.component::<Entity>(chest).unwrap().iid, "a1b2");
+    }
+}
@@ -457,4 +457,20 @@ mod tests {
         assert_float_absolute_eq!(inverse[3][2], -1.0, 0.1);
         assert_float_absolute_eq!(inverse[3][3], -0.5, 0.1);
     }
+
+    #[test]
+    fn try_inverse_small_determinant() {
+        // The determinant of an 800x600 orthographic projection is about 8e-9
+        let projection = Matrix4f::new_orthographic(0.0, 800.0, 600.0, 0.0, -1000.0, 1000.0);
+
+        let inverse = projection.try_inverse().unwrap();
+
+        let identity = projection * inverse;
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert_float_absolute_eq!(identity[i][j], expected, 0.001);
+            }
+        }
+    }
 }
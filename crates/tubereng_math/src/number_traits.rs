@@ -95,13 +95,13 @@ impl IsZero for i32 {
 
 impl IsZero for f32 {
     fn is_zero(&self) -> bool {
-        self.abs() < 0.000_000_01
+        *self == 0.0
     }
 }
 
 impl IsZero for f64 {
     fn is_zero(&self) -> bool {
-        self.abs() < 0.000_000_01
+        *self == 0.0
     }
 }
 
@@ -1,11 +1,13 @@
 use std::fmt::{Debug, Display, Formatter};
 use std::ops::Mul;
 
+use serde::{Deserialize, Serialize};
+
 use crate::matrix::Matrix4;
 use crate::number_traits::Float;
 use crate::vector::Vector3;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Quaternion<T = f32>
 where
     T: Debug,
@@ -95,6 +97,14 @@ where
         ])
     }
 
+    pub fn scalar_part(&self) -> T {
+        self.scalar_part
+    }
+
+    pub fn vector_part(&self) -> Vector3<T> {
+        self.vector_part
+    }
+
     pub fn normalize(&mut self) {
         let norm = self.norm();
         self.vector_part /= norm;
@@ -120,6 +130,21 @@ where
 
         (ww + xx + yy + zz).sqrt()
     }
+
+    /// Interpolates linearly between two unit quaternions, taking the
+    /// shortest path, and normalizes the result. Close to a spherical
+    /// interpolation for close rotations.
+    #[must_use]
+    pub fn nlerp(&self, other: &Self, t: T) -> Self {
+        let dot = self.scalar_part * other.scalar_part + self.vector_part.dot(&other.vector_part);
+        let other_weight = if dot < T::zero() { -t } else { t };
+        let self_weight = T::one() - t;
+        Self::new(
+            self.scalar_part * self_weight + other.scalar_part * other_weight,
+            self.vector_part * self_weight + other.vector_part * other_weight,
+        )
+        .normalized()
+    }
 }
 
 impl<T> Display for Quaternion<T>
@@ -251,6 +276,25 @@ mod tests {
         assert_float_absolute_eq!(matrix[3][3], 1.0, 0.02);
     }
 
+    #[test]
+    fn nlerp() {
+        let from = Quaternion::from_axis_angle(&Vector3f::new(0.0, 0.0, 1.0), 0.0);
+        let to = Quaternion::from_axis_angle(&Vector3f::new(0.0, 0.0, 1.0), PI / 2.0);
+        let expected = Quaternion::from_axis_angle(&Vector3f::new(0.0, 0.0, 1.0), PI / 4.0);
+
+        let halfway = from.nlerp(&to, 0.5);
+        // The opposite quaternion represents the same rotation
+        let halfway_from_opposite = from.nlerp(
+            &Quaternion::new(-to.scalar_part, to.vector_part * -1.0),
+            0.5,
+        );
+
+        for result in [halfway, halfway_from_opposite] {
+            assert_float_absolute_eq!(result.scalar_part, expected.scalar_part, 1e-5);
+            assert_float_absolute_eq!(result.vector_part.z, expected.vector_part.z, 1e-5);
+        }
+    }
+
     #[test]
     fn norm() {
         let quaternion = Quaternion::new(23.0, Vector3::new(12.0, 34.0, 56.0));
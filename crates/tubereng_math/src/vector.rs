@@ -1,6 +1,8 @@
 use std::fmt::{Display, Formatter};
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
+use serde::{Deserialize, Serialize};
+
 use crate::number_traits::{Float, Zero};
 
 pub type Vector2f = Vector2<f32>;
@@ -10,7 +12,7 @@ pub type Vector4f = Vector4<f32>;
 macro_rules! struct_vec {
     ($name:ident : $display_fmt:literal, ($($dim:ident : $TY:ty => $idx:tt,)*)) => {
         #[must_use]
-        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        #[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
         pub struct $name<T = f32> {
             $(pub $dim: T,)*
         }
@@ -0,0 +1,494 @@
+#![warn(clippy::pedantic)]
+
+//! Wavefront OBJ loader, a lightweight alternative to glTF for simple 3D
+//! props.
+//!
+//! The materials of an [`Obj`] are defined in the `.mtl` files named by
+//! [`Obj::material_libraries`], loaded as [`MaterialLibrary`] assets from the
+//! paths returned by [`resolve_path`].
+
+use std::collections::{BTreeMap, HashMap};
+
+use log::warn;
+use tubereng_asset::{Asset, AssetError, AssetLoader, Result};
+use tubereng_renderer::{material, mesh, sampler, texture};
+
+/// 3D model loaded from a `.obj` file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Obj {
+    pub meshes: Vec<ObjMesh>,
+    /// Paths of the `.mtl` files defining the materials, relative to the
+    /// `.obj` file
+    pub material_libraries: Vec<String>,
+}
+
+/// Faces of an object or group sharing a material
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjMesh {
+    /// Name of the object or group of the faces
+    pub name: Option<String>,
+    /// Name of the material in the material libraries
+    pub material: Option<String>,
+    pub geometry: mesh::Descriptor,
+}
+
+/// Resolves a path relative to the path of an `.obj` or `.mtl` file, e.g.
+/// `crate.mtl` next to `props/crate.obj` is `props/crate.mtl`
+#[must_use]
+pub fn resolve_path(file_path: &str, relative_path: &str) -> String {
+    match file_path.rfind('/') {
+        Some(separator) => format!("{}{relative_path}", &file_path[..=separator]),
+        None => relative_path.to_string(),
+    }
+}
+
+impl Asset for Obj {
+    type Loader = ObjLoader;
+}
+
+pub struct ObjLoader;
+impl AssetLoader<Obj> for ObjLoader {
+    fn load(file_content: &[u8]) -> Result<Obj> {
+        let source = std::str::from_utf8(file_content)
+            .map_err(|_| AssetError::ObjDeserializationFailed { line: 0 })?;
+        let mut builder = ObjBuilder::default();
+        for (line_index, line) in source.lines().enumerate() {
+            builder
+                .statement(line)
+                .ok_or(AssetError::ObjDeserializationFailed {
+                    line: line_index + 1,
+                })?;
+        }
+        Ok(builder.finish())
+    }
+}
+
+#[derive(Default)]
+struct ObjBuilder {
+    positions: Vec<[f32; 3]>,
+    texture_coordinates: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    obj: Obj,
+    current: ObjMesh,
+    /// Index of the vertex of the current mesh for each combination of
+    /// position, texture coordinates and normal indices
+    vertex_indices: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    /// Whether a vertex of the current mesh has texture coordinates
+    has_texture_coordinates: bool,
+    /// Whether a vertex of the current mesh has a normal
+    has_normals: bool,
+}
+
+impl ObjBuilder {
+    /// Parses a statement, returning `None` if it is invalid
+    fn statement(&mut self, line: &str) -> Option<()> {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut arguments = line.split_whitespace();
+        let Some(keyword) = arguments.next() else {
+            return Some(());
+        };
+        match keyword {
+            "v" => self.positions.push(floats(arguments)?),
+            "vt" => {
+                let [u, v] = floats(arguments.take(2))?;
+                // The texture coordinates of OBJ files start at the bottom
+                self.texture_coordinates.push([u, 1.0 - v]);
+            }
+            "vn" => self.normals.push(floats(arguments)?),
+            "f" => self.face(arguments)?,
+            "o" | "g" => {
+                let name = rest_of_line(line, keyword);
+                self.start_mesh(name, self.current.material.clone());
+            }
+            "usemtl" => {
+                let material = rest_of_line(line, keyword);
+                if material != self.current.material {
+                    self.start_mesh(self.current.name.clone(), material);
+                }
+            }
+            "mtllib" => self
+                .obj
+                .material_libraries
+                .extend(arguments.map(str::to_string)),
+            // Smoothing groups, lines and points aren't used
+            "s" | "l" | "p" => {}
+            _ => warn!("Ignoring unsupported OBJ statement {keyword}"),
+        }
+        Some(())
+    }
+
+    fn face<'a>(&mut self, vertices: impl Iterator<Item = &'a str>) -> Option<()> {
+        let indices = vertices
+            .map(|vertex| self.vertex(vertex))
+            .collect::<Option<Vec<_>>>()?;
+        if indices.len() < 3 {
+            return None;
+        }
+        // Polygons are triangulated as fans
+        for corner in 1..indices.len() - 1 {
+            self.current.geometry.indices.extend([
+                indices[0],
+                indices[corner],
+                indices[corner + 1],
+            ]);
+        }
+        Some(())
+    }
+
+    /// Returns the index of a `v`, `v/vt`, `v//vn` or `v/vt/vn` vertex in the
+    /// current mesh, adding it if needed
+    fn vertex(&mut self, vertex: &str) -> Option<u32> {
+        let mut references = vertex.split('/');
+        let position = resolve_index(references.next()?, self.positions.len())?;
+        let texture_coordinates = references
+            .next()
+            .filter(|reference| !reference.is_empty())
+            .map(|reference| resolve_index(reference, self.texture_coordinates.len()))
+            .map_or(Some(None), |index| index.map(Some))?;
+        let normal = references
+            .next()
+            .map(|reference| resolve_index(reference, self.normals.len()))
+            .map_or(Some(None), |index| index.map(Some))?;
+
+        self.has_texture_coordinates |= texture_coordinates.is_some();
+        self.has_normals |= normal.is_some();
+        let key = (position, texture_coordinates, normal);
+        if let Some(&index) = self.vertex_indices.get(&key) {
+            return Some(index);
+        }
+        let geometry = &mut self.current.geometry;
+        let index = u32::try_from(geometry.positions.len()).ok()?;
+        geometry.positions.push(self.positions[position]);
+        geometry
+            .texture_coordinates
+            .push(texture_coordinates.map_or([0.0; 2], |index| self.texture_coordinates[index]));
+        geometry
+            .normals
+            .push(normal.map_or([0.0; 3], |index| self.normals[index]));
+        self.vertex_indices.insert(key, index);
+        Some(index)
+    }
+
+    fn start_mesh(&mut self, name: Option<String>, material: Option<String>) {
+        let mesh = std::mem::replace(
+            &mut self.current,
+            ObjMesh {
+                name,
+                material,
+                geometry: mesh::Descriptor::default(),
+            },
+        );
+        self.push_mesh(mesh);
+    }
+
+    /// Adds a mesh to the model, the attributes none of its vertices have
+    /// being dropped
+    fn push_mesh(&mut self, mut mesh: ObjMesh) {
+        self.vertex_indices.clear();
+        let has_texture_coordinates = std::mem::take(&mut self.has_texture_coordinates);
+        let has_normals = std::mem::take(&mut self.has_normals);
+        if mesh.geometry.indices.is_empty() {
+            return;
+        }
+        if !has_texture_coordinates {
+            mesh.geometry.texture_coordinates.clear();
+        }
+        if !has_normals {
+            mesh.geometry.normals.clear();
+        }
+        self.obj.meshes.push(mesh);
+    }
+
+    fn finish(mut self) -> Obj {
+        let mesh = std::mem::take(&mut self.current);
+        self.push_mesh(mesh);
+        self.obj
+    }
+}
+
+/// Material of a `.mtl` file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    /// `Kd` diffuse color with the `d` dissolve as alpha
+    pub diffuse_color: [f32; 4],
+    /// `Ke` emissive color
+    pub emissive_color: [f32; 3],
+    /// `map_Kd` texture path, relative to the `.mtl` file
+    pub diffuse_texture: Option<String>,
+    /// `norm` or `map_Bump` texture path, relative to the `.mtl` file
+    pub normal_texture: Option<String>,
+    /// `map_Ke` texture path, relative to the `.mtl` file
+    pub emissive_texture: Option<String>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            diffuse_color: [1.0; 4],
+            emissive_color: [0.0; 3],
+            diffuse_texture: None,
+            normal_texture: None,
+            emissive_texture: None,
+        }
+    }
+}
+
+impl Material {
+    /// Returns the descriptor of the renderer material, given the textures
+    /// uploaded from the maps of the material. A white texture should be
+    /// given as the base color if the material has no diffuse texture.
+    #[must_use]
+    pub fn descriptor(
+        &self,
+        base_color: texture::Id,
+        normal: Option<texture::Id>,
+        emissive: Option<texture::Id>,
+    ) -> material::Descriptor {
+        let [red, green, blue] = self.emissive_color;
+        material::Descriptor {
+            base_color,
+            normal,
+            emissive,
+            region: texture::Rect::new(0.0, 0.0, 1.0, 1.0),
+            blend_mode: if self.diffuse_color[3] < 1.0 {
+                material::BlendMode::Alpha
+            } else {
+                material::BlendMode::Opaque
+            },
+            sampler: sampler::Descriptor {
+                filter_mode: sampler::FilterMode::Linear,
+                address_mode: sampler::AddressMode::Repeat,
+                ..sampler::Descriptor::default()
+            },
+            parameters: material::Parameters {
+                base_color_factor: self.diffuse_color,
+                emissive: [red, green, blue, 0.0],
+                ..material::Parameters::default()
+            },
+        }
+    }
+}
+
+/// Materials of a `.mtl` file, by name
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaterialLibrary {
+    pub materials: BTreeMap<String, Material>,
+}
+
+impl Asset for MaterialLibrary {
+    type Loader = MaterialLibraryLoader;
+}
+
+pub struct MaterialLibraryLoader;
+impl AssetLoader<MaterialLibrary> for MaterialLibraryLoader {
+    fn load(file_content: &[u8]) -> Result<MaterialLibrary> {
+        let source = std::str::from_utf8(file_content)
+            .map_err(|_| AssetError::ObjDeserializationFailed { line: 0 })?;
+        let mut library = MaterialLibrary::default();
+        let mut current = None;
+        for (line_index, line) in source.lines().enumerate() {
+            material_statement(&mut library, &mut current, line).ok_or(
+                AssetError::ObjDeserializationFailed {
+                    line: line_index + 1,
+                },
+            )?;
+        }
+        Ok(library)
+    }
+}
+
+/// Parses a statement of a `.mtl` file, `current` being the name of the
+/// material being defined. Returns `None` if the statement is invalid.
+fn material_statement(
+    library: &mut MaterialLibrary,
+    current: &mut Option<String>,
+    line: &str,
+) -> Option<()> {
+    let line = line.split('#').next().unwrap_or_default();
+    let mut arguments = line.split_whitespace();
+    let Some(keyword) = arguments.next() else {
+        return Some(());
+    };
+    if keyword == "newmtl" {
+        let name = rest_of_line(line, keyword)?;
+        library.materials.insert(name.clone(), Material::default());
+        *current = Some(name);
+        return Some(());
+    }
+    let material = library.materials.get_mut(current.as_ref()?)?;
+    match keyword {
+        "Kd" => {
+            let [red, green, blue] = floats(arguments)?;
+            material.diffuse_color[..3].copy_from_slice(&[red, green, blue]);
+        }
+        "d" => material.diffuse_color[3] = floats::<1>(arguments)?[0],
+        "Tr" => material.diffuse_color[3] = 1.0 - floats::<1>(arguments)?[0],
+        "Ke" => material.emissive_color = floats(arguments)?,
+        "map_Kd" => material.diffuse_texture = Some(texture_path(arguments)?),
+        "norm" | "map_Bump" | "bump" => {
+            material.normal_texture = Some(texture_path(arguments)?);
+        }
+        "map_Ke" => material.emissive_texture = Some(texture_path(arguments)?),
+        // Only the diffuse, emissive and normal maps are used
+        _ => {}
+    }
+    Some(())
+}
+
+/// Returns the path of a texture statement, the last argument, the options
+/// preceding it being ignored
+fn texture_path<'a>(arguments: impl Iterator<Item = &'a str>) -> Option<String> {
+    arguments.last().map(str::to_string)
+}
+
+/// Returns the text following a keyword, e.g. a name containing spaces
+fn rest_of_line(line: &str, keyword: &str) -> Option<String> {
+    let rest = line.trim_start().strip_prefix(keyword)?.trim();
+    (!rest.is_empty()).then(|| rest.to_string())
+}
+
+/// Parses the first `N` arguments as floats, the extra ones, e.g. the `w`
+/// coordinate of a position, being ignored
+fn floats<'a, const N: usize>(mut arguments: impl Iterator<Item = &'a str>) -> Option<[f32; N]> {
+    let mut values = [0.0; N];
+    for value in &mut values {
+        *value = arguments.next()?.parse().ok()?;
+    }
+    Some(values)
+}
+
+/// Resolves a 1-based index, or a negative index relative to the end of the
+/// `len` elements defined so far
+fn resolve_index(reference: &str, len: usize) -> Option<usize> {
+    let index = reference.parse::<isize>().ok()?;
+    let index = match index {
+        1.. => index.unsigned_abs() - 1,
+        ..=-1 => len.checked_sub(index.unsigned_abs())?,
+        0 => return None,
+    };
+    (index < len).then_some(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUBE_FACES: &str = "
+        # Two faces of a cube
+        mtllib crate.mtl
+        o Crate
+        v 0 0 0
+        v 1 0 0
+        v 1 1 0
+        v 0 1 0
+        v 0 0 1
+        vt 0 0
+        vt 1 0
+        vt 1 1
+        vt 0 1
+        vn 0 0 -1
+        vn -1 0 0
+        usemtl Wood
+        f 1/1/1 2/2/1 3/3/1 4/4/1
+        usemtl Metal Plate
+        f -5//2 -1//2 -2//2
+    ";
+
+    #[test]
+    fn load_obj() {
+        let obj = ObjLoader::load(CUBE_FACES.as_bytes()).unwrap();
+        assert_eq!(obj.material_libraries, ["crate.mtl"]);
+        assert_eq!(obj.meshes.len(), 2);
+
+        let wood = &obj.meshes[0];
+        assert_eq!(wood.name.as_deref(), Some("Crate"));
+        assert_eq!(wood.material.as_deref(), Some("Wood"));
+        assert_eq!(
+            wood.geometry,
+            mesh::Descriptor {
+                positions: vec![
+                    [0.0, 0.0, 0.0],
+                    [1.0, 0.0, 0.0],
+                    [1.0, 1.0, 0.0],
+                    [0.0, 1.0, 0.0]
+                ],
+                normals: vec![[0.0, 0.0, -1.0]; 4],
+                texture_coordinates: vec![[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]],
+                indices: vec![0, 1, 2, 0, 2, 3],
+            }
+        );
+
+        let metal = &obj.meshes[1];
+        assert_eq!(metal.name.as_deref(), Some("Crate"));
+        assert_eq!(metal.material.as_deref(), Some("Metal Plate"));
+        assert_eq!(
+            metal.geometry,
+            mesh::Descriptor {
+                positions: vec![[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+                normals: vec![[-1.0, 0.0, 0.0]; 3],
+                texture_coordinates: vec![],
+                indices: vec![0, 1, 2],
+            }
+        );
+    }
+
+    #[test]
+    fn shared_vertices_are_indexed_once() {
+        let obj =
+            ObjLoader::load(b"v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf 1 3 4\n").unwrap();
+        let geometry = &obj.meshes[0].geometry;
+        assert_eq!(geometry.vertex_count(), 4);
+        assert_eq!(geometry.indices, [0, 1, 2, 0, 2, 3]);
+        assert!(geometry.normals.is_empty());
+        assert!(geometry.texture_coordinates.is_empty());
+    }
+
+    #[test]
+    fn invalid_statements_are_rejected() {
+        for (source, line) in [
+            ("v 0 0 0\nf 1 2 3\n", 2),
+            ("v 0 0\n", 1),
+            ("v 0 0 0\nv 0 0 0\nf 1 2\n", 3),
+            ("v 0 0 0\nf 0 1 1\n", 2),
+        ] {
+            assert!(
+                matches!(
+                    ObjLoader::load(source.as_bytes()),
+                    Err(AssetError::ObjDeserializationFailed { line: error_line }) if error_line == line
+                ),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn load_material_library() {
+        let library = MaterialLibraryLoader::load(
+            b"newmtl Wood\nKd 0.5 0.25 0\nmap_Kd -s 2 2 1 textures/wood.png\n\n\
+              newmtl Glass\nd 0.5\nKe 0 0 1\nnorm glass_normal.png\n",
+        )
+        .unwrap();
+        assert_eq!(
+            library.materials["Wood"],
+            Material {
+                diffuse_color: [0.5, 0.25, 0.0, 1.0],
+                diffuse_texture: Some("textures/wood.png".to_string()),
+                ..Material::default()
+            }
+        );
+        assert_eq!(
+            library.materials["Glass"],
+            Material {
+                diffuse_color: [1.0, 1.0, 1.0, 0.5],
+                emissive_color: [0.0, 0.0, 1.0],
+                normal_texture: Some("glass_normal.png".to_string()),
+                ..Material::default()
+            }
+        );
+        assert!(MaterialLibraryLoader::load(b"Kd 1 1 1\n").is_err());
+        assert_eq!(
+            resolve_path("props/crate.obj", "crate.mtl"),
+            "props/crate.mtl"
+        );
+    }
+}
@@ -0,0 +1,272 @@
+/// Graphics API used to talk to the GPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+    BrowserWebGpu,
+    Empty,
+}
+
+impl Backend {
+    pub(crate) fn to_wgpu(self) -> wgpu::Backends {
+        match self {
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Gl => wgpu::Backends::GL,
+            Backend::BrowserWebGpu => wgpu::Backends::BROWSER_WEBGPU,
+            Backend::Empty => wgpu::Backends::empty(),
+        }
+    }
+}
+
+impl From<wgpu::Backend> for Backend {
+    fn from(backend: wgpu::Backend) -> Self {
+        match backend {
+            wgpu::Backend::Vulkan => Backend::Vulkan,
+            wgpu::Backend::Metal => Backend::Metal,
+            wgpu::Backend::Dx12 => Backend::Dx12,
+            wgpu::Backend::Gl => Backend::Gl,
+            wgpu::Backend::BrowserWebGpu => Backend::BrowserWebGpu,
+            wgpu::Backend::Empty => Backend::Empty,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceType {
+    Other,
+    IntegratedGpu,
+    DiscreteGpu,
+    VirtualGpu,
+    Cpu,
+}
+
+impl From<wgpu::DeviceType> for DeviceType {
+    fn from(device_type: wgpu::DeviceType) -> Self {
+        match device_type {
+            wgpu::DeviceType::Other => DeviceType::Other,
+            wgpu::DeviceType::IntegratedGpu => DeviceType::IntegratedGpu,
+            wgpu::DeviceType::DiscreteGpu => DeviceType::DiscreteGpu,
+            wgpu::DeviceType::VirtualGpu => DeviceType::VirtualGpu,
+            wgpu::DeviceType::Cpu => DeviceType::Cpu,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PowerPreference {
+    /// Lets the platform pick the adapter
+    #[default]
+    Default,
+    /// Prefers integrated GPUs, e.g. to save battery
+    LowPower,
+    /// Prefers discrete GPUs
+    HighPerformance,
+}
+
+impl PowerPreference {
+    pub(crate) fn to_wgpu(self) -> wgpu::PowerPreference {
+        match self {
+            PowerPreference::Default => wgpu::PowerPreference::None,
+            PowerPreference::LowPower => wgpu::PowerPreference::LowPower,
+            PowerPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+
+    fn preferred_device_type(self) -> Option<DeviceType> {
+        match self {
+            PowerPreference::Default => None,
+            PowerPreference::LowPower => Some(DeviceType::IntegratedGpu),
+            PowerPreference::HighPerformance => Some(DeviceType::DiscreteGpu),
+        }
+    }
+}
+
+/// Settings used to pick the adapter the renderer is initialized with.
+///
+/// The platform picks the adapter if none matches the settings.
+#[derive(Debug, Clone, Default)]
+pub struct RendererSettings {
+    pub preferred_backend: Option<Backend>,
+    pub power_preference: PowerPreference,
+    /// Case insensitive part of the name of the adapter to use, e.g. "nvidia"
+    pub adapter_name: Option<String>,
+}
+
+impl RendererSettings {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_preferred_backend(mut self, backend: Backend) -> Self {
+        self.preferred_backend = Some(backend);
+        self
+    }
+
+    #[must_use]
+    pub fn with_power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    #[must_use]
+    pub fn with_adapter_name(mut self, adapter_name: &str) -> Self {
+        self.adapter_name = Some(adapter_name.to_string());
+        self
+    }
+
+    /// Backends the instance must be created with to find the adapters
+    pub(crate) fn backends(&self, default_backends: wgpu::Backends) -> wgpu::Backends {
+        default_backends
+            | self
+                .preferred_backend
+                .map_or(wgpu::Backends::empty(), Backend::to_wgpu)
+    }
+
+    /// Returns how well an adapter matches the settings, higher is better
+    fn score(&self, info: &AdapterInfo) -> u32 {
+        let name_matches = self
+            .adapter_name
+            .as_ref()
+            .is_some_and(|name| info.name.to_lowercase().contains(&name.to_lowercase()));
+        let backend_matches = self.preferred_backend == Some(info.backend);
+        let device_type_matches =
+            self.power_preference.preferred_device_type() == Some(info.device_type);
+        u32::from(name_matches) * 4
+            + u32::from(backend_matches) * 2
+            + u32::from(device_type_matches)
+    }
+
+    /// Returns the index of the adapter matching the settings best, the first
+    /// one on ties, `None` if no adapter matches any of the settings
+    pub(crate) fn pick_adapter(&self, adapters: &[AdapterInfo]) -> Option<usize> {
+        adapters
+            .iter()
+            .map(|info| self.score(info))
+            .enumerate()
+            .rev()
+            .max_by_key(|&(_, score)| score)
+            .filter(|&(_, score)| score > 0)
+            .map(|(index, _)| index)
+    }
+}
+
+/// Resource describing the adapter the renderer has been initialized with,
+/// e.g. to display the GPU in use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdapterInfo {
+    pub name: String,
+    pub vendor: u32,
+    pub device: u32,
+    pub device_type: DeviceType,
+    pub driver: String,
+    pub driver_info: String,
+    pub backend: Backend,
+}
+
+impl From<wgpu::AdapterInfo> for AdapterInfo {
+    fn from(info: wgpu::AdapterInfo) -> Self {
+        Self {
+            name: info.name,
+            vendor: info.vendor,
+            device: info.device,
+            device_type: info.device_type.into(),
+            driver: info.driver,
+            driver_info: info.driver_info,
+            backend: info.backend.into(),
+        }
+    }
+}
+
+/// Requests the adapter matching the settings best, among the ones
+/// compatible with `compatible_surface`
+pub(crate) async fn request_adapter(
+    instance: &wgpu::Instance,
+    settings: &RendererSettings,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+) -> Option<wgpu::Adapter> {
+    // Adapters can't be enumerated on the web
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut adapters = instance
+            .enumerate_adapters(wgpu::Backends::all())
+            .into_iter()
+            .filter(|adapter| {
+                compatible_surface.is_none_or(|surface| adapter.is_surface_supported(surface))
+            })
+            .collect::<Vec<_>>();
+        let infos = adapters
+            .iter()
+            .map(|adapter| AdapterInfo::from(adapter.get_info()))
+            .collect::<Vec<_>>();
+        if let Some(index) = settings.pick_adapter(&infos) {
+            return Some(adapters.swap_remove(index));
+        }
+    }
+
+    instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: settings.power_preference.to_wgpu(),
+            compatible_surface,
+            force_fallback_adapter: false,
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adapter(name: &str, device_type: DeviceType, backend: Backend) -> AdapterInfo {
+        AdapterInfo {
+            name: name.to_string(),
+            vendor: 0,
+            device: 0,
+            device_type,
+            driver: String::new(),
+            driver_info: String::new(),
+            backend,
+        }
+    }
+
+    fn adapters() -> Vec<AdapterInfo> {
+        vec![
+            adapter("Intel UHD", DeviceType::IntegratedGpu, Backend::Vulkan),
+            adapter("NVIDIA GeForce", DeviceType::DiscreteGpu, Backend::Vulkan),
+            adapter("NVIDIA GeForce", DeviceType::DiscreteGpu, Backend::Gl),
+        ]
+    }
+
+    #[test]
+    fn no_adapter_is_picked_by_default() {
+        assert_eq!(RendererSettings::new().pick_adapter(&adapters()), None);
+        let settings =
+            RendererSettings::new().with_power_preference(PowerPreference::HighPerformance);
+        assert_eq!(settings.pick_adapter(&[]), None);
+    }
+
+    #[test]
+    fn adapter_is_picked_by_power_preference() {
+        let settings =
+            RendererSettings::new().with_power_preference(PowerPreference::HighPerformance);
+        assert_eq!(settings.pick_adapter(&adapters()), Some(1));
+    }
+
+    #[test]
+    fn adapter_is_picked_by_name_then_backend() {
+        let settings = RendererSettings::new()
+            .with_adapter_name("nvidia")
+            .with_preferred_backend(Backend::Gl);
+        assert_eq!(settings.pick_adapter(&adapters()), Some(2));
+
+        let settings = RendererSettings::new()
+            .with_adapter_name("intel")
+            .with_preferred_backend(Backend::Gl);
+        assert_eq!(settings.pick_adapter(&adapters()), Some(0));
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt::Write;
+
+use crate::texture;
+
+/// Fixed-width glyph atlas, laid out as a grid of glyphs in the order of
+/// `characters`, starting at `origin_x`/`origin_y` in the texture
+#[derive(Debug, Clone)]
+pub struct BitmapFont {
+    pub texture: texture::Id,
+    pub characters: &'static str,
+    pub glyph_width: f32,
+    pub glyph_height: f32,
+    pub columns: u32,
+    pub origin_x: f32,
+    pub origin_y: f32,
+}
+
+impl BitmapFont {
+    /// Font made of the 10 digits laid out on a single row
+    #[must_use]
+    pub fn digits(texture: texture::Id, glyph_width: f32, glyph_height: f32) -> Self {
+        Self {
+            texture,
+            characters: "0123456789",
+            glyph_width,
+            glyph_height,
+            columns: 10,
+            origin_x: 0.0,
+            origin_y: 0.0,
+        }
+    }
+
+    /// Returns the region of the atlas containing the glyph of the character,
+    /// or `None` if the font doesn't contain it
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn glyph_rect(&self, character: char) -> Option<texture::Rect> {
+        let index = u32::try_from(self.characters.chars().position(|c| c == character)?).ok()?;
+        let column = index % self.columns;
+        let row = index / self.columns;
+        Some(texture::Rect::new(
+            self.origin_x + column as f32 * self.glyph_width,
+            self.origin_y + row as f32 * self.glyph_height,
+            self.glyph_width,
+            self.glyph_height,
+        ))
+    }
+}
+
+/// Text drawn with a [`BitmapFont`], meant for frequently updated HUD
+/// elements such as score counters. Glyphs are laid out on a single line with
+/// no shaping, characters missing from the font are rendered as blank space.
+#[derive(Debug, Clone)]
+pub struct BitmapText {
+    pub font: BitmapFont,
+    pub text: String,
+    pub letter_spacing: f32,
+}
+
+impl BitmapText {
+    #[must_use]
+    pub fn new(font: BitmapFont) -> Self {
+        Self {
+            font,
+            text: String::new(),
+            letter_spacing: 0.0,
+        }
+    }
+
+    /// Replaces the text with the given number, reusing the text allocation
+    pub fn set_number(&mut self, number: i64) {
+        self.text.clear();
+        // Writing into a String cannot fail
+        let _ = write!(self.text, "{number}");
+    }
+
+    /// Returns the horizontal offset and atlas region of every glyph to draw
+    pub(crate) fn glyphs(&self) -> impl Iterator<Item = (f32, texture::Rect)> + '_ {
+        let advance = self.font.glyph_width + self.letter_spacing;
+        let mut offset = -advance;
+        self.text.chars().filter_map(move |character| {
+            offset += advance;
+            Some((offset, self.font.glyph_rect(character)?))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> BitmapFont {
+        BitmapFont {
+            texture: texture::Id(0),
+            characters: "0123456789:",
+            glyph_width: 8.0,
+            glyph_height: 10.0,
+            columns: 4,
+            origin_x: 2.0,
+            origin_y: 0.0,
+        }
+    }
+
+    #[test]
+    fn glyph_rect() {
+        let font = font();
+        let rect = font.glyph_rect('5').unwrap();
+        assert_eq!((rect.x, rect.y), (2.0 + 8.0, 10.0));
+        let rect = font.glyph_rect(':').unwrap();
+        assert_eq!((rect.x, rect.y), (2.0 + 16.0, 20.0));
+        assert!(font.glyph_rect('a').is_none());
+    }
+
+    #[test]
+    fn glyphs_skip_missing_characters() {
+        let mut text = BitmapText::new(font());
+        text.letter_spacing = 1.0;
+        text.set_number(-12);
+        let offsets = text.glyphs().map(|(offset, _)| offset).collect::<Vec<_>>();
+        assert_eq!(text.text, "-12");
+        assert_eq!(offsets, vec![9.0, 18.0]);
+    }
+}
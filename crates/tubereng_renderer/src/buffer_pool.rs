@@ -0,0 +1,195 @@
+use std::num::NonZeroU64;
+
+use tubereng_ecs::system::ResMut;
+
+/// Space allocated in a [`FrameBufferPool`], valid until the end of the frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocation {
+    chunk: usize,
+    offset: wgpu::BufferAddress,
+    size: wgpu::BufferAddress,
+}
+
+impl Allocation {
+    #[must_use]
+    pub fn size(&self) -> wgpu::BufferAddress {
+        self.size
+    }
+}
+
+struct Chunk {
+    buffer: wgpu::Buffer,
+    used: wgpu::BufferAddress,
+}
+
+/// Pool of GPU buffers in which passes write their per-frame vertex and
+/// uniform data.
+///
+/// Buffers are allocated in chunks which are kept from one frame to the next,
+/// the space of every chunk is reclaimed at the beginning of each frame.
+pub struct FrameBufferPool {
+    chunks: Vec<Chunk>,
+    uniform_alignment: wgpu::BufferAddress,
+}
+
+impl FrameBufferPool {
+    pub const CHUNK_SIZE: wgpu::BufferAddress = 1 << 20;
+    const USAGES: wgpu::BufferUsages = wgpu::BufferUsages::VERTEX
+        .union(wgpu::BufferUsages::INDEX)
+        .union(wgpu::BufferUsages::UNIFORM)
+        .union(wgpu::BufferUsages::COPY_DST);
+
+    #[must_use]
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            chunks: vec![],
+            uniform_alignment: wgpu::BufferAddress::from(
+                device.limits().min_uniform_buffer_offset_alignment,
+            ),
+        }
+    }
+
+    /// Writes vertex or index data for the current frame
+    pub fn write_vertices(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[u8],
+    ) -> Allocation {
+        self.write(device, queue, data, wgpu::COPY_BUFFER_ALIGNMENT)
+    }
+
+    /// Writes uniform data for the current frame, the allocation can be bound
+    /// with [`FrameBufferPool::binding`]
+    pub fn write_uniform(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[u8],
+    ) -> Allocation {
+        self.write(device, queue, data, self.uniform_alignment)
+    }
+
+    fn write(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        data: &[u8],
+        alignment: wgpu::BufferAddress,
+    ) -> Allocation {
+        let size =
+            (data.len() as wgpu::BufferAddress).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        let allocation = if let Some(allocation) = self.find_space(size, alignment) {
+            allocation
+        } else {
+            self.chunks.push(Chunk {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("frame_buffer_pool_chunk"),
+                    size: size.max(Self::CHUNK_SIZE).next_power_of_two(),
+                    usage: Self::USAGES,
+                    mapped_at_creation: false,
+                }),
+                used: 0,
+            });
+            Allocation {
+                chunk: self.chunks.len() - 1,
+                offset: 0,
+                size,
+            }
+        };
+
+        let chunk = &mut self.chunks[allocation.chunk];
+        chunk.used = allocation.offset + size;
+        if size > 0 {
+            // Writes must be a multiple of COPY_BUFFER_ALIGNMENT
+            let mut padded_data;
+            let data = if data.len() as wgpu::BufferAddress == size {
+                data
+            } else {
+                padded_data = data.to_vec();
+                padded_data.resize(usize::try_from(size).unwrap(), 0);
+                &padded_data
+            };
+            queue.write_buffer(&chunk.buffer, allocation.offset, data);
+        }
+        allocation
+    }
+
+    /// Finds space for `size` bytes in the chunks allocated so far
+    fn find_space(
+        &self,
+        size: wgpu::BufferAddress,
+        alignment: wgpu::BufferAddress,
+    ) -> Option<Allocation> {
+        self.chunks.iter().enumerate().find_map(|(index, chunk)| {
+            let offset = chunk.used.next_multiple_of(alignment);
+            (offset + size <= chunk.buffer.size()).then_some(Allocation {
+                chunk: index,
+                offset,
+                size,
+            })
+        })
+    }
+
+    #[must_use]
+    pub fn slice(&self, allocation: Allocation) -> wgpu::BufferSlice<'_> {
+        self.chunks[allocation.chunk]
+            .buffer
+            .slice(allocation.offset..allocation.offset + allocation.size)
+    }
+
+    #[must_use]
+    pub fn binding(&self, allocation: Allocation) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: &self.chunks[allocation.chunk].buffer,
+            offset: allocation.offset,
+            size: NonZeroU64::new(allocation.size),
+        })
+    }
+
+    /// Total size of the buffers owned by the pool
+    #[must_use]
+    pub fn capacity(&self) -> wgpu::BufferAddress {
+        self.chunks.iter().map(|chunk| chunk.buffer.size()).sum()
+    }
+
+    pub(crate) fn reset(&mut self) {
+        for chunk in &mut self.chunks {
+            chunk.used = 0;
+        }
+    }
+}
+
+pub(crate) fn reset_frame_buffer_pool_system(mut pool: ResMut<FrameBufferPool>) {
+    pool.reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn allocations_reuse_chunks_across_frames() {
+        let gfx = testing::headless_graphics_state(4, 4);
+        let (device, queue) = (gfx.device(), gfx.queue());
+        let mut pool = FrameBufferPool::new(device);
+
+        let vertices = pool.write_vertices(device, queue, &[1; 6]);
+        assert_eq!(vertices.size(), 8);
+        let uniform = pool.write_uniform(device, queue, &[2; 64]);
+        assert_eq!(uniform.chunk, vertices.chunk);
+        assert_eq!(uniform.offset % pool.uniform_alignment, 0);
+        assert!(uniform.offset >= vertices.size());
+
+        let large = pool.write_vertices(device, queue, &vec![0; 3 << 20]);
+        assert_eq!(large.chunk, 1);
+        let capacity = pool.capacity();
+
+        pool.reset();
+        let vertices = pool.write_vertices(device, queue, &[1; 16]);
+        assert_eq!(vertices.offset, 0);
+        assert_eq!(pool.capacity(), capacity);
+    }
+}
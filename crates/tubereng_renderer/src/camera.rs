@@ -1,4 +1,14 @@
-use tubereng_math::matrix::Matrix4f;
+use tubereng_core::{time::Time, Transform};
+use tubereng_ecs::{
+    system::{Res, Q},
+    EntityId, Storage,
+};
+use tubereng_math::{
+    matrix::{Identity, Matrix4f},
+    vector::{Vector2f, Vector3f},
+};
+
+use crate::{GraphicsState, WindowSize};
 
 #[derive(Debug)]
 pub struct Active;
@@ -6,12 +16,16 @@ pub struct Active;
 #[derive(Debug)]
 pub struct D2 {
     projection: Matrix4f,
+    viewport_width: f32,
+    viewport_height: f32,
 }
 
 impl D2 {
     #[must_use]
     pub fn new(viewport_width: f32, viewport_height: f32) -> Self {
         Self {
+            viewport_width,
+            viewport_height,
             projection: Matrix4f::new_orthographic(
                 0.0,
                 viewport_width,
@@ -26,4 +40,283 @@ impl D2 {
     pub(crate) fn projection(&self) -> &Matrix4f {
         &self.projection
     }
+
+    /// Size of the view of the camera in world units
+    #[must_use]
+    pub fn viewport_size(&self) -> (f32, f32) {
+        (self.viewport_width, self.viewport_height)
+    }
+
+    /// Converts a position on a render target of `target_size` pixels, in
+    /// pixels from its top left corner, into world coordinates for this
+    /// camera placed by `camera_transform` and rendering to `viewport`
+    #[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+    pub fn screen_to_world(
+        &self,
+        screen_position: (f64, f64),
+        camera_transform: &Matrix4f,
+        viewport: &Viewport,
+        target_size: (u32, u32),
+    ) -> Vector2f {
+        let (x, y, width, height) = viewport.to_physical(target_size.0, target_size.1);
+        let view_x =
+            (screen_position.0 as f32 - x as f32) / width.max(1) as f32 * self.viewport_width;
+        let view_y =
+            (screen_position.1 as f32 - y as f32) / height.max(1) as f32 * self.viewport_height;
+        let world_position = camera_transform.transform_vec3(&Vector3f::new(view_x, view_y, 0.0));
+        Vector2f::new(world_position.x, world_position.y)
+    }
+}
+
+/// Converts a position on the window, in physical pixels from its top left
+/// corner like the cursor position of the input state, into world
+/// coordinates, e.g. to select the entity under the cursor.
+///
+/// The position is converted by the active 2D camera whose viewport contains
+/// it, the cameras being expected to have no parent. Returns `None` if no
+/// viewport contains the position, or without graphics.
+#[must_use]
+pub fn screen_to_world(storage: &Storage, screen_position: (f64, f64)) -> Option<Vector2f> {
+    let WindowSize { width, height } = *storage.resource::<GraphicsState>()?.window_size();
+    let mut query_camera = storage.query::<(&D2, &Active)>();
+    for (camera_id, (camera, _)) in query_camera.iter_with_ids() {
+        let viewport = storage
+            .component::<Viewport>(camera_id)
+            .copied()
+            .unwrap_or_default();
+        if !viewport.contains(screen_position, (width, height)) {
+            continue;
+        }
+        let camera_transform = storage
+            .component::<Transform>(camera_id)
+            .map_or_else(Matrix4f::identity, Transform::as_matrix4);
+        return Some(camera.screen_to_world(
+            screen_position,
+            &camera_transform,
+            &viewport,
+            (width, height),
+        ));
+    }
+    None
+}
+
+/// Moves a 2D camera so that its view is centered on a target entity, e.g. a
+/// player of a split-screen game with one camera per [`Viewport`]
+#[derive(Debug, Clone, Copy)]
+pub struct Follow {
+    pub target: EntityId,
+    /// Offset of the center of the view from the target, in world units
+    pub offset: Vector2f,
+    /// Time in seconds the camera takes to cover about two thirds of the
+    /// distance to the target, 0.0 snapping the camera to it
+    pub smoothing: f32,
+}
+
+impl Follow {
+    #[must_use]
+    pub fn new(target: EntityId) -> Self {
+        Self {
+            target,
+            offset: Vector2f::new(0.0, 0.0),
+            smoothing: 0.0,
+        }
+    }
+
+    #[must_use]
+    pub fn with_offset(mut self, offset: Vector2f) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    #[must_use]
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Returns the fraction of the distance to the target covered during a
+    /// frame
+    fn blend_factor(&self, delta_time: f32) -> f32 {
+        if self.smoothing <= 0.0 {
+            1.0
+        } else {
+            1.0 - (-delta_time / self.smoothing).exp()
+        }
+    }
+}
+
+/// Moves the cameras with a [`Follow`] component towards their target. The
+/// translation of the target is read from its `Transform`, the targets are
+/// expected to have no parent.
+pub(crate) fn follow_target_system(
+    time: Res<Time>,
+    mut query_follow: Q<(&D2, &Follow)>,
+    storage: &Storage,
+) {
+    let mut moves = vec![];
+    for (camera_id, (camera, follow)) in query_follow.iter_with_ids() {
+        let Some(target) = storage.component::<Transform>(follow.target) else {
+            continue;
+        };
+        let (width, height) = camera.viewport_size();
+        let x = target.translation.x + follow.offset.x - width / 2.0;
+        let y = target.translation.y + follow.offset.y - height / 2.0;
+        moves.push((camera_id, x, y, follow.blend_factor(time.delta())));
+    }
+
+    for (camera_id, x, y, blend_factor) in moves {
+        let Some(mut transform) = storage.component_mut::<Transform>(camera_id) else {
+            continue;
+        };
+        transform.translation.x += (x - transform.translation.x) * blend_factor;
+        transform.translation.y += (y - transform.translation.y) * blend_factor;
+    }
+    std::mem::drop(time);
+}
+
+/// Region of the render target a camera renders to, in normalized
+/// coordinates (0.0 to 1.0) with the origin at the top left corner.
+///
+/// Cameras without a `Viewport` render to the whole target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Viewport {
+    #[must_use]
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the viewport in pixels as `(x, y, width, height)` for a target
+    /// of the given size, clamped to the bounds of the target
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn to_physical(&self, target_width: u32, target_height: u32) -> (u32, u32, u32, u32) {
+        let target_w = target_width as f32;
+        let target_h = target_height as f32;
+        let x = (self.x.clamp(0.0, 1.0) * target_w).round() as u32;
+        let y = (self.y.clamp(0.0, 1.0) * target_h).round() as u32;
+        let width = ((self.width.max(0.0) * target_w).round() as u32).min(target_width - x);
+        let height = ((self.height.max(0.0) * target_h).round() as u32).min(target_height - y);
+        (x, y, width, height)
+    }
+}
+
+impl Viewport {
+    /// Returns whether a position in pixels is within the viewport on a
+    /// target of the given size
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn contains(&self, position: (f64, f64), target_size: (u32, u32)) -> bool {
+        let (x, y, width, height) = self.to_physical(target_size.0, target_size.1);
+        let (x, y) = (f64::from(x), f64::from(y));
+        (x..x + f64::from(width)).contains(&position.0)
+            && (y..y + f64::from(height)).contains(&position.1)
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(0.0, 0.0, 1.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_ecs::{system::stages, Ecs};
+    use tubereng_math::vector::Vector3f;
+
+    use super::*;
+
+    fn at(x: f32, y: f32) -> Transform {
+        Transform {
+            translation: Vector3f::new(x, y, 0.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn split_screen_cameras_follow_their_target() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Time::with_delta(0.016));
+        ecs.register_system(&stages::Update, follow_target_system);
+        let first_player = ecs.insert((at(100.0, 50.0),));
+        let second_player = ecs.insert((at(-20.0, 10.0),));
+        let first_camera = ecs.insert((
+            D2::new(40.0, 20.0),
+            Viewport::new(0.0, 0.0, 0.5, 1.0),
+            Transform::default(),
+            Follow::new(first_player),
+        ));
+        let second_camera = ecs.insert((
+            D2::new(40.0, 20.0),
+            Viewport::new(0.5, 0.0, 0.5, 1.0),
+            Transform::default(),
+            Follow::new(second_player).with_offset(Vector2f::new(0.0, -5.0)),
+        ));
+        ecs.run_systems();
+
+        let translation = &ecs
+            .component::<Transform>(first_camera)
+            .unwrap()
+            .translation;
+        assert_eq!((translation.x, translation.y), (80.0, 40.0));
+        let translation = &ecs
+            .component::<Transform>(second_camera)
+            .unwrap()
+            .translation;
+        assert_eq!((translation.x, translation.y), (-40.0, -5.0));
+    }
+
+    #[test]
+    fn smoothed_cameras_move_part_of_the_way() {
+        let follow = Follow::new(0).with_smoothing(0.5);
+        let blend_factor = follow.blend_factor(0.5);
+        assert!((blend_factor - (1.0 - (-1.0f32).exp())).abs() < 1e-6);
+        assert!((Follow::new(0).blend_factor(0.016) - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn viewport_to_physical() {
+        let left_half = Viewport::new(0.0, 0.0, 0.5, 1.0);
+        assert_eq!(left_half.to_physical(800, 600), (0, 0, 400, 600));
+        let right_half = Viewport::new(0.5, 0.0, 0.5, 1.0);
+        assert_eq!(right_half.to_physical(800, 600), (400, 0, 400, 600));
+    }
+
+    #[test]
+    fn screen_to_world() {
+        let camera = D2::new(400.0, 300.0);
+        let camera_transform = at(100.0, 50.0).as_matrix4();
+        // The camera renders to the right half of an 800x300 window
+        let viewport = Viewport::new(0.5, 0.0, 0.5, 1.0);
+        assert!(viewport.contains((600.0, 150.0), (800, 300)));
+        assert!(!viewport.contains((200.0, 150.0), (800, 300)));
+
+        let world_position =
+            camera.screen_to_world((600.0, 150.0), &camera_transform, &viewport, (800, 300));
+
+        assert!((world_position.x - 300.0).abs() < 1e-4);
+        assert!((world_position.y - 200.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn viewport_to_physical_is_clamped() {
+        let viewport = Viewport::new(0.75, 0.5, 1.0, 1.0);
+        assert_eq!(viewport.to_physical(800, 600), (600, 300, 200, 300));
+    }
 }
@@ -0,0 +1,563 @@
+use tubereng_ecs::{
+    memory,
+    system::{Res, ResMut},
+    Storage,
+};
+use tubereng_math::matrix::Matrix4f;
+
+use crate::{
+    bitmap_text::{BitmapFont, BitmapText},
+    buffer_pool::{Allocation, FrameBufferPool},
+    material::BlendMode,
+    mesh::Vertex,
+    pass_2d::{self, PassUniform, MAX_BATCH_TEXTURES},
+    render_graph::{RenderGraph, RenderPass},
+    sampler,
+    stats::{DrawStats, RenderStats},
+    texture, GraphicsState, PipelineCache,
+};
+
+/// Features required to render wireframes
+pub(crate) const WIREFRAME_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE;
+
+/// Debug rendering options, read by the passes every frame
+#[derive(Debug, Default, Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RenderDebugMode {
+    /// Renders the geometry as wireframe when the device supports it, see
+    /// `GraphicsState::wireframe_supported`
+    pub wireframe: bool,
+    /// Logs the draw calls and vertices of every pass each frame, they are
+    /// available in the [`crate::stats::RenderStats`] resource in any case
+    pub log_draw_stats: bool,
+    /// Draws the draw calls and vertices of every pass of the previous frame
+    /// in the top left corner of the window, over everything else
+    pub draw_stats_overlay: bool,
+    /// Draws the memory used by the largest component storages and
+    /// resources, see `Ecs::memory_usage`, below the draw stats
+    pub memory_usage_overlay: bool,
+}
+
+impl RenderDebugMode {
+    pub(crate) fn polygon_mode(self, wireframe_supported: bool) -> wgpu::PolygonMode {
+        if self.wireframe && wireframe_supported {
+            wgpu::PolygonMode::Line
+        } else {
+            wgpu::PolygonMode::Fill
+        }
+    }
+}
+
+/// Characters of the built-in debug font, the last glyph being a full cell
+/// drawn behind the text. Lowercase letters are drawn in uppercase.
+const DEBUG_FONT_CHARACTERS: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ:.,_-/()%\u{2588}";
+const DEBUG_FONT_BACKGROUND: char = '\u{2588}';
+/// Rows of the 3x5 glyphs of the debug font, the leftmost pixel being the
+/// third bit
+#[rustfmt::skip]
+const DEBUG_FONT_GLYPHS: [[u8; 5]; 45] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111], [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111], [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001], [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111], [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111], [0b111, 0b101, 0b111, 0b001, 0b111],
+    [0b010, 0b101, 0b111, 0b101, 0b101], [0b110, 0b101, 0b110, 0b101, 0b110],
+    [0b011, 0b100, 0b100, 0b100, 0b011], [0b110, 0b101, 0b101, 0b101, 0b110],
+    [0b111, 0b100, 0b110, 0b100, 0b111], [0b111, 0b100, 0b110, 0b100, 0b100],
+    [0b011, 0b100, 0b101, 0b101, 0b011], [0b101, 0b101, 0b111, 0b101, 0b101],
+    [0b111, 0b010, 0b010, 0b010, 0b111], [0b001, 0b001, 0b001, 0b101, 0b010],
+    [0b101, 0b101, 0b110, 0b101, 0b101], [0b100, 0b100, 0b100, 0b100, 0b111],
+    [0b101, 0b111, 0b111, 0b101, 0b101], [0b110, 0b101, 0b101, 0b101, 0b101],
+    [0b010, 0b101, 0b101, 0b101, 0b010], [0b110, 0b101, 0b110, 0b100, 0b100],
+    [0b010, 0b101, 0b101, 0b110, 0b011], [0b110, 0b101, 0b110, 0b101, 0b101],
+    [0b011, 0b100, 0b010, 0b001, 0b110], [0b111, 0b010, 0b010, 0b010, 0b010],
+    [0b101, 0b101, 0b101, 0b101, 0b111], [0b101, 0b101, 0b101, 0b101, 0b010],
+    [0b101, 0b101, 0b111, 0b111, 0b101], [0b101, 0b101, 0b010, 0b101, 0b101],
+    [0b101, 0b101, 0b010, 0b010, 0b010], [0b111, 0b001, 0b010, 0b100, 0b111],
+    [0b000, 0b010, 0b000, 0b010, 0b000], [0b000, 0b000, 0b000, 0b000, 0b010],
+    [0b000, 0b000, 0b000, 0b010, 0b100], [0b000, 0b000, 0b000, 0b000, 0b111],
+    [0b000, 0b000, 0b111, 0b000, 0b000], [0b001, 0b001, 0b010, 0b100, 0b100],
+    [0b010, 0b100, 0b100, 0b100, 0b010], [0b010, 0b001, 0b001, 0b001, 0b010],
+    [0b101, 0b001, 0b010, 0b100, 0b101],
+];
+/// Size of a glyph cell of the debug font atlas, a 3x5 glyph and its spacing
+const DEBUG_FONT_CELL_WIDTH: u32 = 4;
+const DEBUG_FONT_CELL_HEIGHT: u32 = 6;
+const DEBUG_FONT_COLUMNS: u32 = 16;
+/// Number of window pixels per pixel of the debug font
+const OVERLAY_SCALE: f32 = 2.0;
+const OVERLAY_MARGIN: f32 = 4.0;
+
+fn debug_font(texture: texture::Id) -> BitmapFont {
+    #[allow(clippy::cast_precision_loss)]
+    BitmapFont {
+        texture,
+        characters: DEBUG_FONT_CHARACTERS,
+        glyph_width: DEBUG_FONT_CELL_WIDTH as f32,
+        glyph_height: DEBUG_FONT_CELL_HEIGHT as f32,
+        columns: DEBUG_FONT_COLUMNS,
+        origin_x: 0.0,
+        origin_y: 0.0,
+    }
+}
+
+/// Returns the width, height and RGBA8 data of the debug font atlas
+fn debug_font_atlas() -> (u32, u32, Vec<u8>) {
+    let glyph_count = u32::try_from(DEBUG_FONT_CHARACTERS.chars().count()).unwrap();
+    let width = DEBUG_FONT_COLUMNS * DEBUG_FONT_CELL_WIDTH;
+    let height = glyph_count.div_ceil(DEBUG_FONT_COLUMNS) * DEBUG_FONT_CELL_HEIGHT;
+    let mut data = vec![0; (width * height * 4) as usize];
+    let mut set_pixel = |x: u32, y: u32| {
+        let offset = ((y * width + x) * 4) as usize;
+        data[offset..offset + 4].copy_from_slice(&[255; 4]);
+    };
+    for index in 0..glyph_count {
+        let cell_x = (index % DEBUG_FONT_COLUMNS) * DEBUG_FONT_CELL_WIDTH;
+        let cell_y = (index / DEBUG_FONT_COLUMNS) * DEBUG_FONT_CELL_HEIGHT;
+        match DEBUG_FONT_GLYPHS.get(index as usize) {
+            Some(rows) => {
+                for (y, row) in (0..).zip(rows) {
+                    for x in (0..3).filter(|x| row & (0b100 >> x) != 0) {
+                        set_pixel(cell_x + x, cell_y + y);
+                    }
+                }
+            }
+            // The background glyph
+            None => {
+                for y in 0..DEBUG_FONT_CELL_HEIGHT {
+                    for x in 0..DEBUG_FONT_CELL_WIDTH {
+                        set_pixel(cell_x + x, cell_y + y);
+                    }
+                }
+            }
+        }
+    }
+    (width, height, data)
+}
+
+/// Returns the lines of the draw stats overlay
+fn draw_stats_lines(render_stats: &RenderStats) -> Vec<String> {
+    let mut lines = render_stats
+        .pass_draw_stats()
+        .iter()
+        .map(|stats| {
+            format!(
+                "{}: {} draw calls, {} vertices",
+                stats.pass_name, stats.draw_calls, stats.vertex_count
+            )
+        })
+        .collect::<Vec<_>>();
+    lines.push(format!(
+        "total: {} draw calls",
+        render_stats.total_draw_calls()
+    ));
+    lines
+}
+
+/// Number of component storages and resources listed by the memory usage
+/// overlay, the largest ones being listed first
+const MEMORY_USAGE_OVERLAY_ENTRIES: usize = 8;
+
+/// Returns the lines of the memory usage overlay
+fn memory_usage_lines(report: &memory::Report) -> Vec<String> {
+    let components = report
+        .components
+        .iter()
+        .take(MEMORY_USAGE_OVERLAY_ENTRIES)
+        .map(|usage| {
+            format!(
+                "{}: {} components, {}",
+                short_type_name(usage.type_name),
+                usage.component_count,
+                format_bytes(usage.storage_bytes + usage.heap_bytes.unwrap_or(0))
+            )
+        });
+    let resources = report
+        .resources
+        .iter()
+        .take(MEMORY_USAGE_OVERLAY_ENTRIES)
+        .map(|usage| match usage.heap_bytes {
+            Some(heap_bytes) => format!(
+                "{}: {}",
+                short_type_name(usage.type_name),
+                format_bytes(usage.inline_bytes + heap_bytes)
+            ),
+            None => format!("{}: borrowed", short_type_name(usage.type_name)),
+        });
+    std::iter::once(format!("memory: {}", format_bytes(report.total_bytes())))
+        .chain(components)
+        .chain(resources)
+        .collect()
+}
+
+/// Returns the type name without the module paths, e.g. `Events<Jumped>` for
+/// `tubereng_ecs::event::Events<game::Jumped>`
+fn short_type_name(type_name: &str) -> String {
+    let mut short = String::with_capacity(type_name.len());
+    let mut segment_start = 0;
+    let mut characters = type_name.chars().peekable();
+    while let Some(character) = characters.next() {
+        if character == ':' && characters.peek() == Some(&':') {
+            characters.next();
+            short.truncate(segment_start);
+            continue;
+        }
+        short.push(character);
+        if !(character.is_alphanumeric() || character == '_') {
+            segment_start = short.len();
+        }
+    }
+    short
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f32 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f32 / 1_048_576.0),
+    }
+}
+
+/// Returns the vertices of the lines of text, each line being drawn over a
+/// translucent background. Positions are in window pixels.
+fn overlay_vertices(font: &BitmapFont, atlas_size: (f32, f32), lines: &[String]) -> Vec<Vertex> {
+    let (atlas_width, atlas_height) = atlas_size;
+    let mut vertices = vec![];
+    let mut push_quad = |x: f32, y: f32, rect: &texture::Rect, color: [f32; 4]| {
+        let (left, top) = (x, y);
+        let (right, bottom) = (
+            x + rect.width * OVERLAY_SCALE,
+            y + rect.height * OVERLAY_SCALE,
+        );
+        let (u0, v0) = (rect.x / atlas_width, rect.y / atlas_height);
+        let (u1, v1) = (
+            (rect.x + rect.width) / atlas_width,
+            (rect.y + rect.height) / atlas_height,
+        );
+        let vertex = |x: f32, y: f32, u: f32, v: f32| Vertex {
+            position: [x, y, 0.0],
+            texture_coordinates: [u, v],
+            color,
+            shadow: [0.0; 4],
+            texture_index: 0,
+        };
+        vertices.extend_from_slice(&[
+            vertex(left, top, u0, v0),
+            vertex(left, bottom, u0, v1),
+            vertex(right, bottom, u1, v1),
+            vertex(right, bottom, u1, v1),
+            vertex(right, top, u1, v0),
+            vertex(left, top, u0, v0),
+        ]);
+    };
+
+    let background_rect = font
+        .glyph_rect(DEBUG_FONT_BACKGROUND)
+        .expect("The debug font should have a background glyph");
+    let line_height = font.glyph_height * OVERLAY_SCALE;
+    let mut text = BitmapText::new(font.clone());
+    for (index, line) in (0u16..).zip(lines) {
+        let y = OVERLAY_MARGIN + f32::from(index) * line_height;
+        text.text = line.to_uppercase();
+        #[allow(clippy::cast_precision_loss)]
+        let character_count = text.text.chars().count() as f32;
+        let background = texture::Rect {
+            width: background_rect.width * character_count,
+            ..background_rect.clone()
+        };
+        push_quad(OVERLAY_MARGIN, y, &background, [0.0, 0.0, 0.0, 0.6]);
+        for (offset, glyph_rect) in text.glyphs() {
+            push_quad(
+                OVERLAY_MARGIN + offset * OVERLAY_SCALE,
+                y,
+                &glyph_rect,
+                [1.0; 4],
+            );
+        }
+    }
+    vertices
+}
+
+/// Render pass drawing the debug overlays enabled in the [`RenderDebugMode`]
+pub(crate) struct DebugOverlayPass {
+    font: BitmapFont,
+    pass_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    bind_groups: Option<(wgpu::BindGroup, wgpu::BindGroup)>,
+    vertex_allocation: Option<Allocation>,
+    vertex_count: u32,
+    debug_mode: RenderDebugMode,
+}
+
+impl DebugOverlayPass {
+    fn new(device: &wgpu::Device, font_texture: texture::Id, debug_mode: RenderDebugMode) -> Self {
+        Self {
+            font: debug_font(font_texture),
+            debug_mode,
+            pass_uniform_bind_group_layout: pass_2d::create_pass_uniform_bind_group_layout(device),
+            texture_bind_group_layout: pass_2d::create_texture_bind_group_layout(device),
+            bind_groups: None,
+            vertex_allocation: None,
+            vertex_count: 0,
+        }
+    }
+}
+
+impl RenderPass for DebugOverlayPass {
+    fn draw_stats(&self) -> DrawStats {
+        DrawStats {
+            draw_calls: u32::from(self.vertex_count > 0),
+            vertex_count: self.vertex_count,
+        }
+    }
+
+    fn prepare(&mut self, storage: &Storage) {
+        let gfx = storage
+            .resource::<GraphicsState>()
+            .expect("Graphics state should be present");
+        let Some(sampler) = gfx.sampler_cache.get(sampler::Descriptor::default()) else {
+            return;
+        };
+        if !gfx.texture_cache.contains(self.font.texture) {
+            return;
+        }
+
+        let mut lines = vec![];
+        if self.debug_mode.draw_stats_overlay {
+            if let Some(render_stats) = storage.resource::<RenderStats>() {
+                lines.extend(draw_stats_lines(&render_stats));
+            }
+        }
+        if self.debug_mode.memory_usage_overlay {
+            lines.extend(memory_usage_lines(&storage.memory_usage()));
+        }
+
+        let font_info = gfx.texture_cache.info(self.font.texture);
+        #[allow(clippy::cast_precision_loss)]
+        let vertices = overlay_vertices(
+            &self.font,
+            (font_info.width as f32, font_info.height as f32),
+            &lines,
+        );
+        self.vertex_count = u32::try_from(vertices.len()).unwrap();
+        let mut buffer_pool = storage
+            .resource_mut::<FrameBufferPool>()
+            .expect("FrameBufferPool resource should be present");
+        self.vertex_allocation = Some(buffer_pool.write_vertices(
+            gfx.device(),
+            gfx.queue(),
+            bytemuck::cast_slice(&vertices),
+        ));
+
+        let window_size = gfx.window_size();
+        #[allow(clippy::cast_precision_loss)]
+        let view_proj = Matrix4f::new_orthographic(
+            0.0,
+            window_size.width as f32,
+            window_size.height as f32,
+            0.0,
+            -1.0,
+            1.0,
+        );
+        let pass_uniform = buffer_pool.write_uniform(
+            gfx.device(),
+            gfx.queue(),
+            bytemuck::cast_slice(&[PassUniform::new(&view_proj)]),
+        );
+        let pass_uniform_bind_group = gfx.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("debug_overlay_pass_uniform_bind_group"),
+            layout: &self.pass_uniform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer_pool.binding(pass_uniform),
+            }],
+        });
+        let font_view = gfx
+            .texture_cache
+            .get(self.font.texture)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        // Every texture slot is bound to the font
+        let mut entries = (0..MAX_BATCH_TEXTURES)
+            .map(|slot| wgpu::BindGroupEntry {
+                binding: u32::try_from(slot).unwrap(),
+                resource: wgpu::BindingResource::TextureView(&font_view),
+            })
+            .collect::<Vec<_>>();
+        entries.push(wgpu::BindGroupEntry {
+            binding: u32::try_from(MAX_BATCH_TEXTURES).unwrap(),
+            resource: wgpu::BindingResource::Sampler(sampler),
+        });
+        let texture_bind_group = gfx.device().create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("debug_overlay_texture_bind_group"),
+            layout: &self.texture_bind_group_layout,
+            entries: &entries,
+        });
+        self.bind_groups = Some((pass_uniform_bind_group, texture_bind_group));
+    }
+
+    fn execute(
+        &self,
+        gfx: &mut GraphicsState,
+        encoder: &mut wgpu::CommandEncoder,
+        surface_texture_view: &wgpu::TextureView,
+        storage: &Storage,
+    ) {
+        let (Some((pass_uniform_bind_group, texture_bind_group)), Some(vertex_allocation)) =
+            (&self.bind_groups, self.vertex_allocation)
+        else {
+            return;
+        };
+        let surface_texture_format = gfx.surface_texture_format();
+        let identifier = format!("debug_overlay_pipeline_{surface_texture_format:?}");
+        let mut pipeline_cache = storage.resource_mut::<PipelineCache>().unwrap();
+        if !pipeline_cache.has(&identifier) {
+            pipeline_cache.insert(
+                &identifier,
+                pass_2d::Pass::create_pass_2d_pipeline(
+                    gfx.device(),
+                    &[
+                        &self.pass_uniform_bind_group_layout,
+                        &self.texture_bind_group_layout,
+                    ],
+                    surface_texture_format,
+                    BlendMode::Alpha,
+                    false,
+                    wgpu::PolygonMode::Fill,
+                ),
+            );
+        }
+        let buffer_pool = storage.resource::<FrameBufferPool>().unwrap();
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("debug_overlay"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface_texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(pipeline_cache.get(&identifier).unwrap());
+        rpass.set_bind_group(0, pass_uniform_bind_group, &[]);
+        rpass.set_bind_group(1, texture_bind_group, &[]);
+        rpass.set_vertex_buffer(0, buffer_pool.slice(vertex_allocation));
+        rpass.draw(0..self.vertex_count, 0..1);
+    }
+}
+
+/// Adds the [`DebugOverlayPass`] once the other passes have been added, if
+/// an overlay is enabled. The debug font is loaded the first time.
+pub(crate) fn add_debug_overlay_pass_system(
+    mut gfx: ResMut<GraphicsState>,
+    mut graph: ResMut<RenderGraph>,
+    debug_mode: Res<RenderDebugMode>,
+) {
+    if !(debug_mode.draw_stats_overlay || debug_mode.memory_usage_overlay) {
+        return;
+    }
+    let font_texture = if let Some(font_texture) = gfx.debug_font_texture_id {
+        font_texture
+    } else {
+        let (width, height, data) = debug_font_atlas();
+        let font_texture = gfx.load_texture(&texture::Descriptor {
+            data: &data,
+            width,
+            height,
+            premultiplied_alpha: false,
+            format: texture::Format::Rgba8,
+        });
+        gfx.debug_font_texture_id = Some(font_texture);
+        font_texture
+    };
+    let gfx: &mut GraphicsState = &mut gfx;
+    gfx.sampler_cache
+        .get_or_create(&gfx.wgpu_state.device, sampler::Descriptor::default());
+    graph.add_pass(DebugOverlayPass::new(
+        gfx.device(),
+        font_texture,
+        **debug_mode,
+    ));
+    std::mem::drop(debug_mode);
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_math::vector::Vector3f;
+
+    use super::*;
+
+    #[test]
+    fn debug_font_has_a_glyph_per_character() {
+        assert_eq!(
+            DEBUG_FONT_CHARACTERS.chars().count(),
+            DEBUG_FONT_GLYPHS.len() + 1
+        );
+        let (width, height, data) = debug_font_atlas();
+        assert_eq!((width, height), (64, 18));
+        // Top left pixel of the "0" glyph
+        assert_eq!(data[..4], [255; 4]);
+        // Spacing column of the "0" glyph
+        assert_eq!(data[12..16], [0; 4]);
+    }
+
+    #[test]
+    fn memory_usage_lines() {
+        let report = memory::Report {
+            components: vec![memory::ComponentStorageUsage {
+                type_name: "game::Health",
+                component_count: 3,
+                storage_bytes: 2048,
+                heap_bytes: None,
+            }],
+            resources: vec![
+                memory::ResourceUsage {
+                    type_name: "tubereng_ecs::event::Events<game::Jumped>",
+                    inline_bytes: 48,
+                    heap_bytes: Some(16),
+                },
+                memory::ResourceUsage {
+                    type_name: "game::Level",
+                    inline_bytes: 8,
+                    heap_bytes: None,
+                },
+            ],
+        };
+        assert_eq!(
+            super::memory_usage_lines(&report),
+            [
+                "memory: 2.1 KB",
+                "Health: 3 components, 2.0 KB",
+                "Events<Jumped>: 64 B",
+                "Level: borrowed"
+            ]
+        );
+    }
+
+    #[test]
+    fn overlay_lines_are_drawn_over_a_background() {
+        let font = debug_font(texture::Id(0));
+        let lines = ["ab".to_string(), "c d".to_string()];
+        let vertices = overlay_vertices(&font, (64.0, 18.0), &lines);
+        // A background quad and a quad per glyph, spaces aren't drawn
+        assert_eq!(vertices.len(), (1 + 2 + 1 + 2) * 6);
+        let position = |vertex: &Vertex| {
+            let [x, y, z] = vertex.position;
+            Vector3f::new(x, y, z)
+        };
+        let second_line_background = &vertices[3 * 6..4 * 6];
+        assert_eq!(
+            position(&second_line_background[0]),
+            Vector3f::new(OVERLAY_MARGIN, OVERLAY_MARGIN + 12.0, 0.0)
+        );
+        assert_eq!(
+            position(&second_line_background[2]),
+            Vector3f::new(OVERLAY_MARGIN + 24.0, OVERLAY_MARGIN + 24.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+
+use tubereng_ecs::system::ResMut;
+
+use crate::{texture, GraphicsState};
+
+/// Least recently used eviction policy of the loaded textures.
+///
+/// When the textures use more GPU memory than the budget, the textures that
+/// haven't been used for the longest time are unloaded until the budget is
+/// met. Textures used by a loaded material, pinned textures and textures used
+/// during the last `min_idle_frames` frames are never evicted.
+///
+/// Eviction is disabled until a budget is set, evicted textures must be
+/// loaded again before being drawn.
+pub struct TextureEviction {
+    budget_bytes: Option<usize>,
+    min_idle_frames: u64,
+    pinned: HashSet<texture::Id>,
+}
+
+impl TextureEviction {
+    pub const DEFAULT_MIN_IDLE_FRAMES: u64 = 60;
+
+    #[must_use]
+    pub fn new(budget_bytes: Option<usize>) -> Self {
+        Self {
+            budget_bytes,
+            min_idle_frames: Self::DEFAULT_MIN_IDLE_FRAMES,
+            pinned: HashSet::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn budget_bytes(&self) -> Option<usize> {
+        self.budget_bytes
+    }
+
+    pub fn set_budget_bytes(&mut self, budget_bytes: Option<usize>) {
+        self.budget_bytes = budget_bytes;
+    }
+
+    #[must_use]
+    pub fn min_idle_frames(&self) -> u64 {
+        self.min_idle_frames
+    }
+
+    pub fn set_min_idle_frames(&mut self, min_idle_frames: u64) {
+        self.min_idle_frames = min_idle_frames;
+    }
+
+    /// Prevents a texture from being evicted
+    pub fn pin(&mut self, texture_id: texture::Id) {
+        self.pinned.insert(texture_id);
+    }
+
+    pub fn unpin(&mut self, texture_id: texture::Id) {
+        self.pinned.remove(&texture_id);
+    }
+
+    #[must_use]
+    pub fn is_pinned(&self, texture_id: texture::Id) -> bool {
+        self.pinned.contains(&texture_id)
+    }
+}
+
+impl Default for TextureEviction {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+pub(crate) struct EvictionCandidate {
+    pub(crate) texture_id: texture::Id,
+    pub(crate) size_bytes: usize,
+    pub(crate) last_used_frame: u64,
+}
+
+/// Returns the textures to evict so that `total_bytes` fits in
+/// `budget_bytes`, least recently used first
+pub(crate) fn plan_eviction(
+    mut candidates: Vec<EvictionCandidate>,
+    total_bytes: usize,
+    budget_bytes: usize,
+    frame: u64,
+    min_idle_frames: u64,
+) -> Vec<texture::Id> {
+    candidates
+        .retain(|candidate| frame.saturating_sub(candidate.last_used_frame) >= min_idle_frames);
+    candidates.sort_by_key(|candidate| candidate.last_used_frame);
+
+    let mut total_bytes = total_bytes;
+    candidates
+        .into_iter()
+        .take_while(|candidate| {
+            let over_budget = total_bytes > budget_bytes;
+            total_bytes = total_bytes.saturating_sub(candidate.size_bytes);
+            over_budget
+        })
+        .map(|candidate| candidate.texture_id)
+        .collect()
+}
+
+/// Unloads the least recently used textures while the loaded textures exceed
+/// the [`TextureEviction`] budget
+pub(crate) fn evict_textures_system(
+    mut gfx: ResMut<GraphicsState>,
+    mut eviction: ResMut<TextureEviction>,
+) {
+    gfx.texture_cache.advance_frame();
+    let Some(budget_bytes) = eviction.budget_bytes else {
+        return;
+    };
+
+    let cache = &gfx.texture_cache;
+    let total_bytes = cache
+        .ids()
+        .filter_map(|texture_id| cache.size_bytes(texture_id))
+        .sum();
+    if total_bytes <= budget_bytes {
+        return;
+    }
+
+    let material_textures = gfx
+        .material_cache
+        .iter()
+        .flat_map(|(_, material)| material.descriptor.textures())
+        .collect::<HashSet<_>>();
+    let candidates = cache
+        .ids()
+        .filter(|texture_id| {
+            !material_textures.contains(texture_id)
+                && !eviction.pinned.contains(texture_id)
+                && Some(*texture_id) != gfx.missing_texture_id
+                && Some(*texture_id) != gfx.debug_font_texture_id
+                && !gfx.is_material_fallback_texture(*texture_id)
+        })
+        .filter_map(|texture_id| {
+            Some(EvictionCandidate {
+                texture_id,
+                size_bytes: cache.size_bytes(texture_id)?,
+                last_used_frame: cache.last_used_frame(texture_id)?,
+            })
+        })
+        .collect();
+
+    let evicted = plan_eviction(
+        candidates,
+        total_bytes,
+        budget_bytes,
+        cache.frame(),
+        eviction.min_idle_frames,
+    );
+    for texture_id in evicted {
+        log::debug!("Evicting texture {}", *texture_id);
+        gfx.texture_cache.remove(texture_id);
+        eviction.pinned.remove(&texture_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn candidate(id: usize, size_bytes: usize, last_used_frame: u64) -> EvictionCandidate {
+        EvictionCandidate {
+            texture_id: texture::Id(id),
+            size_bytes,
+            last_used_frame,
+        }
+    }
+
+    #[test]
+    fn least_recently_used_textures_are_evicted_first() {
+        let candidates = vec![
+            candidate(0, 100, 50),
+            candidate(1, 100, 10),
+            candidate(2, 100, 30),
+        ];
+        let evicted = plan_eviction(candidates, 300, 150, 100, 10);
+        assert_eq!(evicted, [texture::Id(1), texture::Id(2)]);
+    }
+
+    #[test]
+    fn recently_used_textures_are_kept_over_budget() {
+        let candidates = vec![candidate(0, 100, 95), candidate(1, 100, 20)];
+        let evicted = plan_eviction(candidates, 200, 0, 100, 10);
+        assert_eq!(evicted, [texture::Id(1)]);
+    }
+
+    #[test]
+    fn nothing_is_evicted_within_budget() {
+        let candidates = vec![candidate(0, 100, 0)];
+        assert!(plan_eviction(candidates, 100, 100, 100, 0).is_empty());
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_unloaded_ids_are_reused() {
+        let mut gfx = testing::headless_graphics_state(4, 4);
+        let descriptor = texture::Descriptor {
+            data: &[255; 4],
+            width: 1,
+            height: 1,
+            premultiplied_alpha: true,
+            format: texture::Format::Rgba8,
+        };
+        let first = gfx.load_texture(&descriptor);
+        let second = gfx.load_texture(&descriptor);
+
+        assert!(gfx.unload_texture(first));
+        assert!(!gfx.unload_texture(first));
+        assert!(!gfx.texture_cache.contains(first));
+        assert_eq!(gfx.texture_cache.ids().collect::<Vec<_>>(), [second]);
+        assert_eq!(gfx.load_texture(&descriptor), first);
+    }
+}
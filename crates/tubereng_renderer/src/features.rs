@@ -0,0 +1,175 @@
+use tubereng_ecs::Storage;
+
+/// Optional features of the device used by float textures
+pub(crate) const FLOAT_TEXTURE_FEATURES: wgpu::Features = wgpu::Features::FLOAT32_FILTERABLE;
+
+/// Capability of the device which may be missing, e.g. on WebGL2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// BC compressed textures are uploaded without being decompressed
+    CompressedTextures,
+    ComputeShaders,
+    /// Storage buffers can be bound in vertex shaders
+    VertexStorageBuffers,
+    /// 32 bit float textures can be sampled with filtering
+    Float32Filterable,
+    /// 16 bit float textures can be rendered to
+    FloatRenderTargets,
+}
+
+/// Resource describing what the device the renderer has been initialized with
+/// supports, so that the same game runs on WebGL2 and on desktop backends.
+///
+/// Systems and passes needing a feature are registered with
+/// [`register_system_with_features!`](crate::register_system_with_features)
+/// and [`requires_features!`](crate::requires_features).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RendererFeatures {
+    pub compressed_textures: bool,
+    pub compute_shaders: bool,
+    pub vertex_storage_buffers: bool,
+    pub float32_filterable: bool,
+    pub float_render_targets: bool,
+}
+
+impl RendererFeatures {
+    pub(crate) fn new(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Self {
+        let features = device.features();
+        let limits = device.limits();
+        let downlevel_flags = adapter.get_downlevel_capabilities().flags;
+        Self {
+            compressed_textures: features.contains(crate::texture::COMPRESSED_TEXTURE_FEATURES),
+            compute_shaders: downlevel_flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+                && limits.max_compute_workgroups_per_dimension > 0,
+            vertex_storage_buffers: downlevel_flags.contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
+                && limits.max_storage_buffers_per_shader_stage > 0,
+            float32_filterable: features.contains(FLOAT_TEXTURE_FEATURES),
+            float_render_targets: adapter
+                .get_texture_format_features(wgpu::TextureFormat::Rgba16Float)
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT),
+        }
+    }
+
+    #[must_use]
+    pub fn supports(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::CompressedTextures => self.compressed_textures,
+            Feature::ComputeShaders => self.compute_shaders,
+            Feature::VertexStorageBuffers => self.vertex_storage_buffers,
+            Feature::Float32Filterable => self.float32_filterable,
+            Feature::FloatRenderTargets => self.float_render_targets,
+        }
+    }
+
+    #[must_use]
+    pub fn supports_all(&self, features: &[Feature]) -> bool {
+        features.iter().all(|&feature| self.supports(feature))
+    }
+}
+
+/// Returns a condition for
+/// [`RenderGraph::add_conditional_pass`](crate::render_graph::RenderGraph::add_conditional_pass)
+/// which is true when the device supports every feature of `features`
+pub fn condition(features: &'static [Feature]) -> impl Fn(&Storage) -> bool {
+    |storage| {
+        storage
+            .resource::<RendererFeatures>()
+            .is_some_and(|renderer_features| renderer_features.supports_all(features))
+    }
+}
+
+/// Returns a [`condition`] which is true when the device supports every given
+/// [`Feature`]
+///
+/// ```ignore
+/// graph.add_conditional_pass(
+///     BloomPass::new(),
+///     requires_features!(ComputeShaders, FloatRenderTargets),
+/// );
+/// ```
+#[macro_export]
+macro_rules! requires_features {
+    ($($feature:ident),+ $(,)?) => {
+        $crate::features::condition(&[$($crate::features::Feature::$feature),+])
+    };
+}
+
+/// Registers a system only if the device supports every given [`Feature`],
+/// evaluating to true if the system has been registered. The renderer must be
+/// initialized beforehand.
+///
+/// ```ignore
+/// register_system_with_features!(ecs, &stages::Update, simulate_particles_system, [ComputeShaders]);
+/// ```
+#[macro_export]
+macro_rules! register_system_with_features {
+    ($ecs:expr, $stage:expr, $system:expr, [$($feature:ident),+ $(,)?]) => {{
+        let supported = $ecs
+            .resource::<$crate::features::RendererFeatures>()
+            .is_some_and(|features| {
+                features.supports_all(&[$($crate::features::Feature::$feature),+])
+            });
+        if supported {
+            $ecs.register_system($stage, $system);
+        }
+        supported
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_ecs::{system::stages, Ecs};
+
+    use super::*;
+
+    fn webgl2_features() -> RendererFeatures {
+        RendererFeatures {
+            float_render_targets: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn features_are_checked_together() {
+        let features = webgl2_features();
+        assert!(features.supports_all(&[Feature::FloatRenderTargets]));
+        assert!(!features.supports_all(&[Feature::FloatRenderTargets, Feature::ComputeShaders]));
+    }
+
+    #[test]
+    fn systems_are_registered_when_features_are_supported() {
+        fn count_frames_system(mut frames: tubereng_ecs::system::ResMut<u32>) {
+            **frames += 1;
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(0u32);
+        assert!(!register_system_with_features!(
+            ecs,
+            &stages::Update,
+            count_frames_system,
+            [FloatRenderTargets]
+        ));
+        ecs.insert_resource(webgl2_features());
+        assert!(!register_system_with_features!(
+            ecs,
+            &stages::Update,
+            count_frames_system,
+            [ComputeShaders]
+        ));
+        assert!(register_system_with_features!(
+            ecs,
+            &stages::Update,
+            count_frames_system,
+            [FloatRenderTargets]
+        ));
+        ecs.run_systems();
+        assert_eq!(*ecs.resource::<u32>().unwrap(), 1);
+
+        let condition = requires_features!(ComputeShaders);
+        let storage = tubereng_ecs::Storage::new();
+        assert!(!condition(&storage));
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+/// A pair of textures alternating every frame, allowing a pass to read what
+/// has been rendered to the target during the previous frame, e.g. for motion
+/// trails, temporal smoothing or feedback effects.
+///
+/// Passes render to [`HistoryTarget::current`] and sample
+/// [`HistoryTarget::previous`], the textures are swapped by the render graph
+/// once every pass has been executed.
+pub struct HistoryTarget {
+    textures: [wgpu::Texture; 2],
+    views: [wgpu::TextureView; 2],
+    current: usize,
+    format: wgpu::TextureFormat,
+}
+
+impl HistoryTarget {
+    const USAGES: wgpu::TextureUsages = wgpu::TextureUsages::RENDER_ATTACHMENT
+        .union(wgpu::TextureUsages::TEXTURE_BINDING)
+        .union(wgpu::TextureUsages::COPY_SRC)
+        .union(wgpu::TextureUsages::COPY_DST);
+
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        name: &str,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let create_texture = || {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(name),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: Self::USAGES,
+                view_formats: &[],
+            })
+        };
+        let textures = [create_texture(), create_texture()];
+        let views = [
+            textures[0].create_view(&wgpu::TextureViewDescriptor::default()),
+            textures[1].create_view(&wgpu::TextureViewDescriptor::default()),
+        ];
+        Self {
+            textures,
+            views,
+            current: 0,
+            format,
+        }
+    }
+
+    /// Texture rendered to during the current frame
+    #[must_use]
+    pub fn current(&self) -> &wgpu::Texture {
+        &self.textures[self.current]
+    }
+
+    #[must_use]
+    pub fn current_view(&self) -> &wgpu::TextureView {
+        &self.views[self.current]
+    }
+
+    /// Texture rendered to during the previous frame
+    #[must_use]
+    pub fn previous(&self) -> &wgpu::Texture {
+        &self.textures[1 - self.current]
+    }
+
+    #[must_use]
+    pub fn previous_view(&self) -> &wgpu::TextureView {
+        &self.views[1 - self.current]
+    }
+
+    #[must_use]
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    #[must_use]
+    pub fn width(&self) -> u32 {
+        self.textures[0].width()
+    }
+
+    #[must_use]
+    pub fn height(&self) -> u32 {
+        self.textures[0].height()
+    }
+
+    pub(crate) fn swap(&mut self) {
+        self.current = 1 - self.current;
+    }
+}
+
+/// History targets of the render graph, by name
+#[derive(Default)]
+pub(crate) struct HistoryTargets {
+    targets: HashMap<&'static str, HistoryTarget>,
+}
+
+impl HistoryTargets {
+    pub(crate) fn insert(&mut self, name: &'static str, target: HistoryTarget) {
+        self.targets.insert(name, target);
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) -> bool {
+        self.targets.remove(name).is_some()
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&HistoryTarget> {
+        self.targets.get(name)
+    }
+
+    pub(crate) fn swap_all(&mut self) {
+        for target in self.targets.values_mut() {
+            target.swap();
+        }
+    }
+
+    /// Creates every target again with a new device, their content is lost
+    pub(crate) fn recreate(&mut self, device: &wgpu::Device) {
+        for (name, target) in &mut self.targets {
+            *target =
+                HistoryTarget::new(device, name, target.format, target.width(), target.height());
+        }
+    }
+}
@@ -1,6 +1,10 @@
 #![warn(clippy::pedantic)]
 
-use std::{borrow::BorrowMut, collections::HashMap, sync::Arc};
+use std::{
+    borrow::BorrowMut,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawWindowHandle};
 use render_graph::{RenderGraph, RenderPass};
@@ -8,28 +12,178 @@ use tubereng_ecs::{
     system::{stages, Res, ResMut},
     Ecs, Storage,
 };
-use wgpu::SurfaceTargetUnsafe;
+use wgpu::{util::DeviceExt, SurfaceTargetUnsafe};
 
+pub mod adapter;
+pub mod bitmap_text;
+pub mod buffer_pool;
 pub mod camera;
+pub mod debug;
+pub mod eviction;
+pub mod features;
+pub mod history;
+pub mod lod;
 pub mod material;
-mod mesh;
+pub mod mesh;
+pub mod nine_slice;
 mod pass_2d;
+pub mod plugin;
+pub mod recovery;
 pub mod render_graph;
+pub mod sampler;
+pub mod shadow;
 pub mod sprite;
+pub mod stats;
+pub mod streaming;
+#[cfg(test)]
+mod testing;
 pub mod texture;
+pub mod validation;
+
+/// Requests a device with the optional features used by the renderer that
+/// the adapter supports. The returned reason is set when the device is lost.
+///
+/// # Panics
+///
+/// Will panic if the device cannot be set up
+async fn request_device(
+    adapter: &wgpu::Adapter,
+    limits: &wgpu::Limits,
+) -> (wgpu::Device, wgpu::Queue, Arc<Mutex<Option<String>>>) {
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                required_features: adapter.features()
+                    & (stats::GPU_TIMING_FEATURES
+                        | texture::COMPRESSED_TEXTURE_FEATURES
+                        | features::FLOAT_TEXTURE_FEATURES
+                        | debug::WIREFRAME_FEATURES),
+                required_limits: limits.clone(),
+                label: None,
+            },
+            None,
+        )
+        .await
+        .expect("Couldn't setup device");
+
+    let device_lost = Arc::new(Mutex::new(None));
+    let lost_reason = device_lost.clone();
+    device.set_device_lost_callback(move |reason, message| {
+        log::error!("The GPU device has been lost ({reason:?}): {message}");
+        if let Ok(mut lost_reason) = lost_reason.lock() {
+            *lost_reason = Some(message);
+        }
+    });
+    (device, queue, device_lost)
+}
+
+fn create_offscreen_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("offscreen_render_target"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+fn create_material_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("material_bind_group_layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            // Normal map
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            // Emissive map
+            wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            },
+        ],
+    })
+}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct WindowSize {
     pub width: u32,
     pub height: u32,
 }
 
+/// Size of the surface when the renderer isn't given the size of the window
+pub const DEFAULT_WINDOW_SIZE: WindowSize = WindowSize {
+    width: 800,
+    height: 600,
+};
+
+/// What the renderer draws into
+pub enum RenderTarget<'w> {
+    Surface {
+        surface: wgpu::Surface<'w>,
+        _window: RawWindowHandle,
+    },
+    /// Offscreen texture used when rendering without a window
+    Offscreen(wgpu::Texture),
+}
+
 pub struct WgpuState<'w> {
-    surface: wgpu::Surface<'w>,
+    render_target: RenderTarget<'w>,
+    /// Adapter and limits the device has been requested with, kept to request
+    /// a new device if it is lost
+    adapter: wgpu::Adapter,
+    limits: wgpu::Limits,
     device: wgpu::Device,
+    /// Reason of the loss of the device, set by the device lost callback
+    device_lost: Arc<Mutex<Option<String>>>,
     queue: wgpu::Queue,
     surface_configuration: wgpu::SurfaceConfiguration,
     window_size: WindowSize,
-    _window: RawWindowHandle,
 }
 
 pub struct GraphicsState<'w> {
@@ -37,11 +191,21 @@ pub struct GraphicsState<'w> {
     pub(crate) texture_cache: texture::Cache,
     material_bind_group_layout: wgpu::BindGroupLayout,
     placeholder_material_id: Option<material::Id>,
+    /// Magenta checker drawn in place of invalid sprites
+    pub(crate) missing_texture_id: Option<texture::Id>,
+    /// Font of the debug overlays, loaded the first time one is drawn
+    pub(crate) debug_font_texture_id: Option<texture::Id>,
+    /// Textures bound in place of the missing maps of materials, loaded with
+    /// the first material missing them
+    material_fallback_textures: HashMap<material::Map, texture::Id>,
     pub(crate) material_cache: material::Cache,
+    pub(crate) sampler_cache: sampler::Cache,
+    pub(crate) gpu_timer: Option<stats::GpuTimer>,
+    pub(crate) history_targets: history::HistoryTargets,
 }
 
 impl<'w> GraphicsState<'w> {
-    /// Creates a new `WGPUState`
+    /// Creates a new `WGPUState` with the adapter matching `settings` best
     ///
     /// # Panics
     ///
@@ -50,46 +214,31 @@ impl<'w> GraphicsState<'w> {
     ///  - No adapter is found
     ///  - The device cannot be set up
     ///  - The handle of the window cannot be obtained
-    pub async fn new<W>(window: W) -> Self
+    pub async fn new<W>(
+        window: W,
+        window_size: WindowSize,
+        settings: &adapter::RendererSettings,
+    ) -> Self
     where
         W: HasWindowHandle + HasDisplayHandle + std::marker::Send + std::marker::Sync,
     {
-        const WINDOW_SIZE: WindowSize = WindowSize {
-            width: 800,
-            height: 600,
-        };
-
         let mut instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::PRIMARY,
+            backends: settings.backends(wgpu::Backends::PRIMARY),
             ..Default::default()
         });
 
         let surface = Self::create_surface(&mut instance, &window);
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
+        let adapter = adapter::request_adapter(&instance, settings, Some(&surface))
             .await
             .expect("No adapter found");
 
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    required_limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .expect("Couldn't setup device");
+        let limits = if cfg!(target_arch = "wasm32") {
+            wgpu::Limits::downlevel_webgl2_defaults()
+        } else {
+            wgpu::Limits::default()
+        };
+        let (device, queue, device_lost) = request_device(&adapter, &limits).await;
         let surface_capabilities = surface.get_capabilities(&adapter);
         let surface_format = surface_capabilities
             .formats
@@ -98,7 +247,6 @@ impl<'w> GraphicsState<'w> {
             .find(wgpu::TextureFormat::is_srgb)
             .unwrap_or(surface_capabilities.formats[0]);
 
-        let window_size = WINDOW_SIZE;
         let surface_configuration = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
@@ -111,45 +259,106 @@ impl<'w> GraphicsState<'w> {
         };
         surface.configure(&device, &surface_configuration);
 
-        let material_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("material_bind_group_layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            });
+        let render_target = RenderTarget::Surface {
+            surface,
+            _window: window
+                .window_handle()
+                .expect("Couldn't obtain window handle")
+                .into(),
+        };
+        Self::with_wgpu_state(WgpuState {
+            render_target,
+            adapter,
+            limits,
+            device,
+            device_lost,
+            queue,
+            surface_configuration,
+            window_size,
+        })
+    }
+
+    /// Creates a `GraphicsState` rendering into an offscreen texture of the
+    /// given size instead of a window surface, using any available adapter.
+    ///
+    /// Returns `None` if no adapter is available.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the device cannot be set up
+    pub async fn new_headless(width: u32, height: u32) -> Option<Self> {
+        Self::new_headless_with_settings(width, height, &adapter::RendererSettings::default()).await
+    }
+
+    /// Creates a headless `GraphicsState`, see [`GraphicsState::new_headless`],
+    /// with the adapter matching `settings` best
+    ///
+    /// Returns `None` if no adapter is available.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the device cannot be set up
+    pub async fn new_headless_with_settings(
+        width: u32,
+        height: u32,
+        settings: &adapter::RendererSettings,
+    ) -> Option<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: settings.backends(wgpu::InstanceDescriptor::default().backends),
+            ..Default::default()
+        });
+        let adapter = adapter::request_adapter(&instance, settings, None).await?;
+
+        let limits = if adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            wgpu::Limits::downlevel_defaults()
+        } else {
+            wgpu::Limits::downlevel_webgl2_defaults()
+        }
+        .using_resolution(adapter.limits());
+        let (device, queue, device_lost) = request_device(&adapter, &limits).await;
+        let offscreen_texture = create_offscreen_texture(&device, width, height);
+
+        let surface_configuration = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: offscreen_texture.format(),
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
 
+        Some(Self::with_wgpu_state(WgpuState {
+            render_target: RenderTarget::Offscreen(offscreen_texture),
+            adapter,
+            limits,
+            device,
+            device_lost,
+            queue,
+            surface_configuration,
+            window_size: WindowSize { width, height },
+        }))
+    }
+
+    fn with_wgpu_state(wgpu_state: WgpuState<'w>) -> Self {
+        let material_bind_group_layout = create_material_bind_group_layout(&wgpu_state.device);
         GraphicsState {
-            wgpu_state: WgpuState {
-                surface,
-                device,
-                queue,
-                surface_configuration,
-                window_size,
-                _window: window
-                    .window_handle()
-                    .expect("Couldn't obtain window handle")
-                    .into(),
-            },
+            wgpu_state,
             texture_cache: texture::Cache::new(),
             material_cache: material::Cache::new(),
+            sampler_cache: sampler::Cache::new(),
             placeholder_material_id: None,
+            missing_texture_id: None,
+            debug_font_texture_id: None,
+            material_fallback_textures: HashMap::new(),
             material_bind_group_layout,
+            gpu_timer: None,
+            history_targets: history::HistoryTargets::default(),
         }
     }
 
@@ -157,10 +366,42 @@ impl<'w> GraphicsState<'w> {
         &self.wgpu_state.window_size
     }
 
+    /// Resizes the render target, e.g. when the window has been resized. A
+    /// null size, e.g. of a minimized window, is ignored.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0
+            || height == 0
+            || self.wgpu_state.window_size == (WindowSize { width, height })
+        {
+            return;
+        }
+
+        self.wgpu_state.window_size = WindowSize { width, height };
+        self.wgpu_state.surface_configuration.width = width;
+        self.wgpu_state.surface_configuration.height = height;
+        match &mut self.wgpu_state.render_target {
+            RenderTarget::Surface { surface, .. } => {
+                surface.configure(
+                    &self.wgpu_state.device,
+                    &self.wgpu_state.surface_configuration,
+                );
+            }
+            RenderTarget::Offscreen(texture) => {
+                *texture = create_offscreen_texture(&self.wgpu_state.device, width, height);
+            }
+        }
+    }
+
     pub fn device(&self) -> &wgpu::Device {
         &self.wgpu_state.device
     }
 
+    /// Describes the adapter the device has been requested from
+    #[must_use]
+    pub fn adapter_info(&self) -> adapter::AdapterInfo {
+        self.wgpu_state.adapter.get_info().into()
+    }
+
     pub fn queue(&self) -> &wgpu::Queue {
         &self.wgpu_state.queue
     }
@@ -169,6 +410,192 @@ impl<'w> GraphicsState<'w> {
         self.wgpu_state.surface_configuration.format
     }
 
+    /// Returns true if the device can render wireframes, see
+    /// [`debug::RenderDebugMode`]
+    #[must_use]
+    pub fn wireframe_supported(&self) -> bool {
+        self.device().features().contains(debug::WIREFRAME_FEATURES)
+    }
+
+    /// Enables or disables the timing of the render passes on the GPU, the
+    /// results are available in the [`stats::RenderStats`] resource.
+    ///
+    /// Returns `false` if timestamp queries aren't supported by the device.
+    pub fn set_gpu_timing_enabled(&mut self, enabled: bool) -> bool {
+        if !enabled {
+            self.gpu_timer = None;
+            return true;
+        }
+
+        if !self
+            .device()
+            .features()
+            .contains(stats::GPU_TIMING_FEATURES)
+        {
+            return false;
+        }
+
+        if self.gpu_timer.is_none() {
+            self.gpu_timer = Some(stats::GpuTimer::new(self.device()));
+        }
+        true
+    }
+
+    /// Returns true if the device has been lost, in which case nothing is
+    /// rendered until [`GraphicsState::recover_lost_device`] is called
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the device lost reason lock has been poisoned
+    #[must_use]
+    pub fn is_device_lost(&self) -> bool {
+        self.wgpu_state.device_lost.lock().unwrap().is_some()
+    }
+
+    /// Requests a new device after the device has been lost, and uploads the
+    /// textures and materials again from their CPU side data. Pipelines
+    /// created with the lost device must be created again.
+    ///
+    /// Returns the reason of the loss of the device.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the new device cannot be set up
+    pub async fn recover_lost_device(&mut self) -> String {
+        let lost_reason = self
+            .wgpu_state
+            .device_lost
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap_or_default();
+        let (device, queue, device_lost) =
+            request_device(&self.wgpu_state.adapter, &self.wgpu_state.limits).await;
+        match &mut self.wgpu_state.render_target {
+            RenderTarget::Surface { surface, .. } => {
+                surface.configure(&device, &self.wgpu_state.surface_configuration);
+            }
+            RenderTarget::Offscreen(texture) => {
+                *texture = create_offscreen_texture(&device, texture.width(), texture.height());
+            }
+        }
+        self.wgpu_state.device = device;
+        self.wgpu_state.queue = queue;
+        self.wgpu_state.device_lost = device_lost;
+
+        self.material_bind_group_layout = create_material_bind_group_layout(self.device());
+        self.sampler_cache = sampler::Cache::new();
+        if self.gpu_timer.is_some() {
+            self.gpu_timer = Some(stats::GpuTimer::new(self.device()));
+        }
+        self.history_targets.recreate(&self.wgpu_state.device);
+
+        for texture_id in self.texture_cache.ids().collect::<Vec<_>>() {
+            let info = self.texture_cache.info(texture_id);
+            let (width, height) = (info.width, info.height);
+            let texture = if let Some(streamed) =
+                self.texture_cache.streamed_textures().get(&texture_id)
+            {
+                self.create_streamed_texture(width, height, &streamed.mips, streamed.resident_mip)
+            } else if let Some(retained) = self.texture_cache.retained_texture(texture_id) {
+                self.create_texture(retained.format, width, height, &[&retained.data])
+            } else {
+                continue;
+            };
+            self.texture_cache.replace(texture_id, texture);
+        }
+
+        for material_id in self.material_cache.ids().collect::<Vec<_>>() {
+            let Some(material) = self.material_cache.get(material_id) else {
+                continue;
+            };
+            if !material
+                .descriptor
+                .textures()
+                .all(|texture_id| self.texture_cache.contains(texture_id))
+            {
+                log::warn!(
+                    "Material {} can't be recovered, its texture has been unloaded",
+                    *material_id
+                );
+                continue;
+            }
+            let descriptor = material::Descriptor {
+                parameters: material.parameters,
+                ..material.descriptor.clone()
+            };
+            let material = self.create_material(&descriptor);
+            self.material_cache.replace(material_id, material);
+        }
+
+        lost_reason
+    }
+
+    #[must_use]
+    pub fn is_headless(&self) -> bool {
+        matches!(self.wgpu_state.render_target, RenderTarget::Offscreen(_))
+    }
+
+    /// Reads back the content of the offscreen render target as tightly packed
+    /// RGBA8 rows. Returns `None` when rendering to a window surface.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the readback buffer cannot be mapped
+    #[must_use]
+    pub fn read_offscreen_target(&self) -> Option<Vec<u8>> {
+        let RenderTarget::Offscreen(texture) = &self.wgpu_state.render_target else {
+            return None;
+        };
+
+        let WindowSize { width, height } = self.wgpu_state.window_size;
+        let unpadded_bytes_per_row = 4 * width;
+        let padded_bytes_per_row = unpadded_bytes_per_row
+            .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let device = &self.wgpu_state.device;
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("offscreen_readback_buffer"),
+            size: u64::from(padded_bytes_per_row * height),
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("offscreen_readback_encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.wgpu_state
+            .queue
+            .submit(std::iter::once(encoder.finish()));
+
+        let buffer_slice = readback_buffer.slice(..);
+        buffer_slice.map_async(wgpu::MapMode::Read, |result| {
+            result.expect("Couldn't map the readback buffer");
+        });
+        device.poll(wgpu::Maintain::Wait);
+
+        let padded_data = buffer_slice.get_mapped_range();
+        let mut data = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        for row in padded_data.chunks(padded_bytes_per_row as usize) {
+            data.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        std::mem::drop(padded_data);
+        readback_buffer.unmap();
+        Some(data)
+    }
+
     fn create_surface<W>(instance: &mut wgpu::Instance, window: &W) -> wgpu::Surface<'w>
     where
         W: HasWindowHandle + HasDisplayHandle + std::marker::Send + std::marker::Sync,
@@ -197,66 +624,230 @@ impl<'w> GraphicsState<'w> {
         surface.unwrap()
     }
 
+    /// Uploads a texture to the GPU. Compressed texture data is uploaded as
+    /// is if the device supports its format, otherwise it is decompressed to
+    /// RGBA8 first.
     pub fn load_texture(&mut self, descriptor: &texture::Descriptor) -> texture::Id {
-        let texture_size = wgpu::Extent3d {
+        let decompressed_data;
+        let (format, data) = if descriptor.format.is_compressed()
+            && !texture::compressed_upload_supported(
+                self.wgpu_state.device.features(),
+                descriptor.width,
+                descriptor.height,
+            ) {
+            decompressed_data = texture::decompress_to_rgba8(
+                descriptor.format,
+                descriptor.data,
+                descriptor.width,
+                descriptor.height,
+            );
+            (texture::Format::Rgba8, &decompressed_data[..])
+        } else {
+            (descriptor.format, descriptor.data)
+        };
+
+        let texture = self.create_texture(format, descriptor.width, descriptor.height, &[data]);
+        let texture_info = texture::Info {
             width: descriptor.width,
             height: descriptor.height,
-            depth_or_array_layers: 1,
+            premultiplied_alpha: descriptor.premultiplied_alpha,
         };
 
+        self.texture_cache.insert_retained(
+            texture_info,
+            texture,
+            texture::RetainedTexture {
+                format,
+                data: data.to_vec(),
+            },
+        )
+    }
+
+    /// Loads a texture whose mip levels are streamed: only its low resolution
+    /// mips are uploaded at first, higher resolution mips are uploaded when
+    /// the texture is drawn large enough on screen and dropped when the
+    /// [`streaming::TextureStreaming`] memory budget is exceeded.
+    ///
+    /// Streaming only applies to sprites, and to RGBA8 textures: compressed
+    /// textures are loaded as with [`GraphicsState::load_texture`].
+    pub fn load_streamed_texture(&mut self, descriptor: &texture::Descriptor) -> texture::Id {
+        if descriptor.format != texture::Format::Rgba8 {
+            return self.load_texture(descriptor);
+        }
+
+        let mips =
+            texture::generate_mip_chain(descriptor.data, descriptor.width, descriptor.height);
+        let resident_mip =
+            streaming::initial_resident_mip(descriptor.width, descriptor.height, mips.len());
+        let texture =
+            self.create_streamed_texture(descriptor.width, descriptor.height, &mips, resident_mip);
+        let texture_info = texture::Info {
+            width: descriptor.width,
+            height: descriptor.height,
+            premultiplied_alpha: descriptor.premultiplied_alpha,
+        };
+
+        self.texture_cache.insert_streamed(
+            texture_info,
+            texture,
+            texture::StreamedTexture { mips, resident_mip },
+        )
+    }
+
+    /// Creates a texture with the mip levels of `mips` starting at
+    /// `resident_mip`
+    pub(crate) fn create_streamed_texture(
+        &self,
+        width: u32,
+        height: u32,
+        mips: &[Vec<u8>],
+        resident_mip: usize,
+    ) -> wgpu::Texture {
+        let (width, height) = texture::mip_size(width, height, resident_mip);
+        let resident_mips = mips[resident_mip..]
+            .iter()
+            .map(Vec::as_slice)
+            .collect::<Vec<_>>();
+        self.create_texture(texture::Format::Rgba8, width, height, &resident_mips)
+    }
+
+    /// Creates a texture and uploads its mip levels, starting with the level
+    /// of the given size
+    fn create_texture(
+        &self,
+        format: texture::Format,
+        width: u32,
+        height: u32,
+        mips: &[&[u8]],
+    ) -> wgpu::Texture {
         // TODO add texture path as label
         let texture = self
             .wgpu_state
             .device
             .create_texture(&wgpu::TextureDescriptor {
                 label: None,
-                size: texture_size,
-                mip_level_count: 1,
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: u32::try_from(mips.len()).unwrap(),
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                format: format.wgpu_format(),
                 usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
                 view_formats: &[],
             });
 
-        self.wgpu_state.queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            descriptor.data,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * descriptor.width),
-                rows_per_image: Some(descriptor.height),
-            },
-            texture_size,
-        );
+        for (mip_level, data) in mips.iter().enumerate() {
+            let (mip_width, mip_height) = texture::mip_size(width, height, mip_level);
+            self.wgpu_state.queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: u32::try_from(mip_level).unwrap(),
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(format.bytes_per_row(mip_width)),
+                    rows_per_image: Some(format.rows_per_image(mip_height)),
+                },
+                wgpu::Extent3d {
+                    width: mip_width,
+                    height: mip_height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
 
-        let texture_info = texture::Info {
-            width: descriptor.width,
-            height: descriptor.height,
+        texture
+    }
+
+    /// Replaces the content of a loaded texture while keeping its id, e.g.
+    /// when its file has been modified, so that the sprites and the materials
+    /// using it show the new content. A streamed texture stays streamed.
+    /// Returns false if the texture wasn't loaded.
+    pub fn reload_texture(&mut self, id: texture::Id, descriptor: &texture::Descriptor) -> bool {
+        if !self.texture_cache.contains(id) {
+            return false;
+        }
+        let reloaded_id = if self.texture_cache.resident_mip(id).is_some() {
+            self.load_streamed_texture(descriptor)
+        } else {
+            self.load_texture(descriptor)
         };
+        self.texture_cache.swap(id, reloaded_id);
+        self.texture_cache.remove(reloaded_id);
 
-        self.texture_cache.insert(texture_info, texture)
+        // The bind groups of the materials reference the replaced texture
+        for material_id in self.material_cache.ids().collect::<Vec<_>>() {
+            let Some(material) = self.material_cache.get(material_id) else {
+                continue;
+            };
+            if !material
+                .descriptor
+                .textures()
+                .any(|texture_id| texture_id == id)
+            {
+                continue;
+            }
+            let descriptor = material::Descriptor {
+                parameters: material.parameters,
+                ..material.descriptor.clone()
+            };
+            let material = self.create_material(&descriptor);
+            self.material_cache.replace(material_id, material);
+        }
+        true
     }
 
     pub fn load_material(&mut self, descriptor: &material::Descriptor) -> material::Id {
+        let material = self.create_material(descriptor);
+        self.material_cache.insert(material)
+    }
+
+    /// Returns the texture bound in place of a missing map, loading it if
+    /// required
+    fn material_fallback_texture(&mut self, map: material::Map) -> texture::Id {
+        if let Some(texture_id) = self.material_fallback_textures.get(&map) {
+            return *texture_id;
+        }
+        let texture_id = self.load_texture(&map.fallback_texture());
+        self.material_fallback_textures.insert(map, texture_id);
+        texture_id
+    }
+
+    pub(crate) fn is_material_fallback_texture(&self, id: texture::Id) -> bool {
+        self.material_fallback_textures.values().any(|&t| t == id)
+    }
+
+    fn create_material(&mut self, descriptor: &material::Descriptor) -> material::Material {
+        let normal_texture_id = match descriptor.normal {
+            Some(texture_id) => texture_id,
+            None => self.material_fallback_texture(material::Map::Normal),
+        };
+        let emissive_texture_id = match descriptor.emissive {
+            Some(texture_id) => texture_id,
+            None => self.material_fallback_texture(material::Map::Emissive),
+        };
+        let create_view = |texture_id| {
+            self.texture_cache
+                .get(texture_id)
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let base_color_texture_view = create_view(descriptor.base_color);
+        let normal_texture_view = create_view(normal_texture_id);
+        let emissive_texture_view = create_view(emissive_texture_id);
+
         let device = &self.wgpu_state.device;
-        let base_color_texture = self.texture_cache.get(descriptor.base_color);
-        let base_color_texture_view =
-            base_color_texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let base_color_texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            label: None,
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
+        let base_color_texture_sampler =
+            self.sampler_cache.get_or_create(device, descriptor.sampler);
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("material_uniform_buffer"),
+            contents: bytemuck::cast_slice(&[descriptor.parameters]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -269,19 +860,108 @@ impl<'w> GraphicsState<'w> {
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&base_color_texture_sampler),
+                    resource: wgpu::BindingResource::Sampler(base_color_texture_sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&normal_texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(&emissive_texture_view),
                 },
             ],
         });
 
-        self.material_cache
-            .insert(material::Material { bind_group })
+        material::Material {
+            bind_group,
+            blend_mode: descriptor.blend_mode,
+            uniform_buffer,
+            parameters: descriptor.parameters,
+            descriptor: descriptor.clone(),
+        }
+    }
+
+    /// Replaces the uniform parameters of a material
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the material doesn't exist
+    pub fn update_material(&mut self, id: material::Id, parameters: &material::Parameters) {
+        let material = self
+            .material_cache
+            .get_mut(id)
+            .expect("The material to update should exist");
+        material.parameters = *parameters;
+        self.wgpu_state.queue.write_buffer(
+            &material.uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[*parameters]),
+        );
+    }
+
+    /// Creates a history target named `name`, replacing the existing one with
+    /// the same name. Both of its textures start cleared.
+    pub fn create_history_target(
+        &mut self,
+        name: &'static str,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) {
+        let target = history::HistoryTarget::new(self.device(), name, format, width, height);
+        self.history_targets.insert(name, target);
+    }
+
+    #[must_use]
+    pub fn history_target(&self, name: &str) -> Option<&history::HistoryTarget> {
+        self.history_targets.get(name)
+    }
+
+    /// Returns false if there was no history target with this name
+    pub fn remove_history_target(&mut self, name: &str) -> bool {
+        self.history_targets.remove(name)
+    }
+
+    /// Unloads a texture, its id may be reused by a texture loaded later.
+    /// Sprites still referencing the texture are no longer drawn. Returns
+    /// false if the texture wasn't loaded.
+    pub fn unload_texture(&mut self, id: texture::Id) -> bool {
+        if Some(id) == self.missing_texture_id {
+            log::warn!("The missing texture can't be unloaded");
+            return false;
+        }
+        if Some(id) == self.debug_font_texture_id {
+            log::warn!("The debug font can't be unloaded");
+            return false;
+        }
+        if self.is_material_fallback_texture(id) {
+            log::warn!("The fallback textures of materials can't be unloaded");
+            return false;
+        }
+        self.texture_cache.remove(id)
+    }
+
+    /// Unloads a material, its id may be reused by a material loaded later.
+    /// The textures of the material stay loaded. Returns false if the material
+    /// wasn't loaded.
+    pub fn unload_material(&mut self, id: material::Id) -> bool {
+        if Some(id) == self.placeholder_material_id {
+            log::warn!("The placeholder material can't be unloaded");
+            return false;
+        }
+        self.material_cache.remove(id)
     }
 }
 
 #[derive(Default)]
 pub struct PipelineCache {
     pipelines: HashMap<String, wgpu::RenderPipeline>,
+    compute_pipelines: HashMap<String, wgpu::ComputePipeline>,
 }
 
 impl PipelineCache {
@@ -298,6 +978,26 @@ impl PipelineCache {
     pub fn get(&self, identifier: &str) -> Option<&wgpu::RenderPipeline> {
         self.pipelines.get(identifier)
     }
+
+    pub fn insert_compute(&mut self, identifier: &str, pipeline: wgpu::ComputePipeline) {
+        self.compute_pipelines
+            .insert(identifier.to_string(), pipeline);
+    }
+
+    #[must_use]
+    pub fn has_compute(&self, identifier: &str) -> bool {
+        self.compute_pipelines.contains_key(identifier)
+    }
+
+    #[must_use]
+    pub fn get_compute(&self, identifier: &str) -> Option<&wgpu::ComputePipeline> {
+        self.compute_pipelines.get(identifier)
+    }
+
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+        self.compute_pipelines.clear();
+    }
 }
 
 pub struct FrameRenderingContext {
@@ -306,6 +1006,86 @@ pub struct FrameRenderingContext {
     pub encoder: Option<wgpu::CommandEncoder>,
 }
 
+/// Initializes the renderer with its built-in passes and the registered
+/// plugins
+#[derive(Default)]
+pub struct RendererBuilder {
+    plugins: Vec<Box<dyn plugin::RendererPlugin>>,
+    settings: adapter::RendererSettings,
+    window_size: Option<WindowSize>,
+}
+
+impl RendererBuilder {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a plugin adding its own passes and pipelines to the renderer
+    #[must_use]
+    pub fn with_plugin<P>(mut self, plugin: P) -> Self
+    where
+        P: 'static + plugin::RendererPlugin,
+    {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    /// Sets the settings used to pick the adapter
+    #[must_use]
+    pub fn with_settings(mut self, settings: adapter::RendererSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Sets the size of the window surface, [`DEFAULT_WINDOW_SIZE`] by
+    /// default
+    #[must_use]
+    pub fn with_window_size(mut self, window_size: WindowSize) -> Self {
+        self.window_size = Some(window_size);
+        self
+    }
+
+    #[must_use]
+    pub fn with_boxed_plugins(mut self, plugins: Vec<Box<dyn plugin::RendererPlugin>>) -> Self {
+        self.plugins.extend(plugins);
+        self
+    }
+
+    pub async fn init<W>(
+        self,
+        ecs: &mut Ecs,
+        window: Arc<W>,
+        placeholder_texture: &texture::Descriptor<'_>,
+    ) where
+        W: HasWindowHandle + HasDisplayHandle + std::marker::Send + std::marker::Sync,
+    {
+        let window_size = self.window_size.unwrap_or(DEFAULT_WINDOW_SIZE);
+        let gfx = GraphicsState::new(window, window_size, &self.settings).await;
+        setup_renderer(ecs, gfx, placeholder_texture, self.plugins);
+    }
+
+    /// Initializes the renderer without a window, see
+    /// [`renderer_init_headless`]
+    ///
+    /// Returns `false` if no adapter is available.
+    pub async fn init_headless(
+        self,
+        ecs: &mut Ecs,
+        width: u32,
+        height: u32,
+        placeholder_texture: &texture::Descriptor<'_>,
+    ) -> bool {
+        let Some(gfx) =
+            GraphicsState::new_headless_with_settings(width, height, &self.settings).await
+        else {
+            return false;
+        };
+        setup_renderer(ecs, gfx, placeholder_texture, self.plugins);
+        true
+    }
+}
+
 pub async fn renderer_init<W>(
     ecs: &mut Ecs,
     window: Arc<W>,
@@ -313,34 +1093,120 @@ pub async fn renderer_init<W>(
 ) where
     W: HasWindowHandle + HasDisplayHandle + std::marker::Send + std::marker::Sync,
 {
-    let mut gfx = GraphicsState::new(window).await;
+    RendererBuilder::new()
+        .init(ecs, window, placeholder_texture)
+        .await;
+}
+
+/// Initializes the renderer without a window, rendering into an offscreen
+/// texture of the given size. See [`GraphicsState::new_headless`].
+///
+/// Returns `false` if no adapter is available.
+pub async fn renderer_init_headless(
+    ecs: &mut Ecs,
+    width: u32,
+    height: u32,
+    placeholder_texture: &texture::Descriptor<'_>,
+) -> bool {
+    RendererBuilder::new()
+        .init_headless(ecs, width, height, placeholder_texture)
+        .await
+}
+
+fn setup_renderer(
+    ecs: &mut Ecs,
+    mut gfx: GraphicsState<'static>,
+    placeholder_texture: &texture::Descriptor<'_>,
+    plugins: Vec<Box<dyn plugin::RendererPlugin>>,
+) {
     let placeholder_texture_id = gfx.load_texture(placeholder_texture);
     let placeholder_material_id = gfx.load_material(&material::Descriptor {
         base_color: placeholder_texture_id,
+        normal: None,
+        emissive: None,
         region: texture::Rect {
             x: 0.0,
             y: 0.0,
             width: 16.0,
             height: 16.0,
         },
+        blend_mode: material::BlendMode::Alpha,
+        sampler: sampler::Descriptor::default(),
+        parameters: material::Parameters::default(),
     });
     gfx.placeholder_material_id = Some(placeholder_material_id);
+    gfx.missing_texture_id = Some(gfx.load_texture(&texture::Descriptor {
+        data: &validation::missing_texture_data(),
+        width: validation::MISSING_TEXTURE_SIZE,
+        height: validation::MISSING_TEXTURE_SIZE,
+        premultiplied_alpha: false,
+        format: texture::Format::Rgba8,
+    }));
 
+    let mut pipeline_cache = PipelineCache::default();
+    for plugin in &plugins {
+        plugin.setup(&mut gfx, &mut pipeline_cache);
+    }
+
+    let adapter_info = gfx.adapter_info();
+    log::info!(
+        "Rendering with {} ({:?}, {:?})",
+        adapter_info.name,
+        adapter_info.device_type,
+        adapter_info.backend
+    );
+    ecs.insert_resource(adapter_info);
+    ecs.insert_resource(features::RendererFeatures::new(
+        &gfx.wgpu_state.adapter,
+        gfx.device(),
+    ));
+    ecs.insert_resource(buffer_pool::FrameBufferPool::new(gfx.device()));
     ecs.insert_resource(gfx);
     ecs.insert_resource(RenderGraph::new());
-    ecs.insert_resource(PipelineCache::default());
+    ecs.insert_resource(pipeline_cache);
+    ecs.insert_resource(plugin::RendererPlugins { plugins });
+    ecs.insert_resource(stats::RenderStats::default());
+    ecs.insert_resource(debug::RenderDebugMode::default());
+    ecs.insert_resource(render_graph::PassToggles::default());
+    ecs.register_event::<recovery::DeviceRecovered>();
+    ecs.insert_resource(streaming::TextureStreaming::default());
+    ecs.insert_resource(eviction::TextureEviction::default());
+    ecs.register_event::<validation::InvalidSprite>();
+    ecs.insert_resource(validation::ReportedInvalidSprites::default());
     ecs.insert_resource(FrameRenderingContext {
         surface_texture: None,
         surface_texture_view: None,
         encoder: None,
     });
 
-    ecs.register_system(&stages::Update, sprite::animate_sprite_system);
+    register_simulation_systems(ecs);
+    ecs.register_system(&stages::Render, recovery::recover_lost_device_system);
+    ecs.register_system(&stages::Render, buffer_pool::reset_frame_buffer_pool_system);
     ecs.register_system(&stages::Render, begin_frame_system);
     ecs.register_system(&stages::Render, add_clear_pass_system);
+    ecs.register_system(
+        &stages::Render,
+        validation::prune_reported_invalid_sprites_system,
+    );
     ecs.register_system(&stages::Render, pass_2d::add_pass_system);
+    ecs.register_system(&stages::Render, plugin::add_plugin_passes_system);
+    ecs.register_system(&stages::Render, debug::add_debug_overlay_pass_system);
     ecs.register_system(&stages::FinalizeRender, prepare_passes_system);
     ecs.register_system(&stages::FinalizeRender, finish_frame_system);
+    ecs.register_system(
+        &stages::FinalizeRender,
+        streaming::update_texture_residency_system,
+    );
+    ecs.register_system(&stages::FinalizeRender, eviction::evict_textures_system);
+}
+
+/// Registers the systems of the renderer that don't need the graphics
+/// device, animating the sprites and moving the cameras. They are registered
+/// when the renderer is initialized, and can be registered alone to run the
+/// gameplay without graphics.
+pub fn register_simulation_systems(ecs: &mut Ecs) {
+    ecs.register_system(&stages::Update, sprite::animate_sprite_system);
+    ecs.register_system(&stages::Update, camera::follow_target_system);
 }
 
 fn begin_frame_system(
@@ -349,10 +1215,19 @@ fn begin_frame_system(
     mut graph: ResMut<RenderGraph>,
 ) {
     let graphics = graphics.borrow_mut();
-    let surface_texture = graphics.wgpu_state.surface.get_current_texture().unwrap();
-    let surface_texture_view = surface_texture
-        .texture
-        .create_view(&wgpu::TextureViewDescriptor::default());
+    let (surface_texture, surface_texture_view) = match &graphics.wgpu_state.render_target {
+        RenderTarget::Surface { surface, .. } => {
+            let surface_texture = surface.get_current_texture().unwrap();
+            let surface_texture_view = surface_texture
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            (Some(surface_texture), surface_texture_view)
+        }
+        RenderTarget::Offscreen(texture) => (
+            None,
+            texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        ),
+    };
     let encoder =
         graphics
             .wgpu_state
@@ -361,7 +1236,7 @@ fn begin_frame_system(
                 label: Some("encoder"),
             });
 
-    frame_ctx.surface_texture = Some(surface_texture);
+    frame_ctx.surface_texture = surface_texture;
     frame_ctx.surface_texture_view = Some(surface_texture_view);
     frame_ctx.encoder = Some(encoder);
 
@@ -380,6 +1255,8 @@ fn prepare_passes_system(mut graph: ResMut<RenderGraph>, storage: &Storage) {
 fn finish_frame_system(
     mut graphics: ResMut<GraphicsState>,
     mut frame_ctx: ResMut<FrameRenderingContext>,
+    mut render_stats: ResMut<stats::RenderStats>,
+    debug_mode: Res<debug::RenderDebugMode>,
     graph: Res<RenderGraph>,
     storage: &Storage,
 ) {
@@ -391,9 +1268,37 @@ fn finish_frame_system(
         .queue
         .submit(std::iter::once(encoder.finish()));
 
-    let surface_texture = frame_ctx.surface_texture.take().unwrap();
-    surface_texture.present();
+    render_stats.pass_draw_stats.clear();
+    render_stats.pass_draw_stats.extend(graph.pass_draw_stats());
+    if debug_mode.log_draw_stats {
+        for pass_draw_stats in &render_stats.pass_draw_stats {
+            log::info!(
+                "{}: {} draw calls, {} vertices",
+                pass_draw_stats.pass_name,
+                pass_draw_stats.draw_calls,
+                pass_draw_stats.vertex_count
+            );
+        }
+    }
+
+    let gfx: &mut GraphicsState = &mut graphics;
+    if let Some(gpu_timer) = &mut gfx.gpu_timer {
+        gpu_timer.map_readbacks();
+        let timestamp_period = gfx.wgpu_state.queue.get_timestamp_period();
+        if let Some(pass_timings) =
+            gpu_timer.read_pass_timings(&gfx.wgpu_state.device, timestamp_period)
+        {
+            render_stats.pass_timings = pass_timings;
+        }
+    } else {
+        render_stats.pass_timings.clear();
+    }
+
+    if let Some(surface_texture) = frame_ctx.surface_texture.take() {
+        surface_texture.present();
+    }
     std::mem::drop(graphics);
+    std::mem::drop(debug_mode);
     std::mem::drop(graph);
 }
 
@@ -428,6 +1333,7 @@ impl RenderPass for ClearPass {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     r: f32,
     g: f32,
@@ -457,3 +1363,569 @@ impl From<&Color> for [f32; 3] {
         [value.r, value.g, value.b]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_clear_pass() {
+        let mut gfx = testing::headless_graphics_state(4, 2);
+
+        let storage = Storage::new();
+        let target_view = match &gfx.wgpu_state.render_target {
+            RenderTarget::Offscreen(texture) => {
+                texture.create_view(&wgpu::TextureViewDescriptor::default())
+            }
+            RenderTarget::Surface { .. } => unreachable!(),
+        };
+        let mut encoder = gfx
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        ClearPass.execute(&mut gfx, &mut encoder, &target_view, &storage);
+        gfx.queue().submit(std::iter::once(encoder.finish()));
+
+        let pixels = gfx.read_offscreen_target().unwrap();
+        assert_eq!(pixels.len(), 4 * 2 * 4);
+        assert!(pixels.chunks(4).all(|pixel| pixel == [0, 0, 0, 255]));
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_history_targets_are_swapped_after_execution() {
+        let mut gfx = testing::headless_graphics_state(4, 4);
+        gfx.create_history_target("trails", wgpu::TextureFormat::Rgba8UnormSrgb, 4, 4);
+        let target = gfx.history_target("trails").unwrap();
+        let (current, previous) = (target.current().global_id(), target.previous().global_id());
+        assert_ne!(current, previous);
+
+        let storage = Storage::new();
+        let target_view = gfx
+            .history_target("trails")
+            .unwrap()
+            .current()
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = gfx
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        RenderGraph::new().execute(&mut gfx, &mut encoder, &target_view, &storage);
+        gfx.queue().submit(std::iter::once(encoder.finish()));
+
+        let target = gfx.history_target("trails").unwrap();
+        assert_eq!(target.previous().global_id(), current);
+        assert_eq!(target.current().global_id(), previous);
+        assert!(gfx.remove_history_target("trails"));
+        assert!(gfx.history_target("trails").is_none());
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_device_loss_recovery() {
+        let mut gfx = testing::headless_graphics_state(4, 4);
+        let texture_id = gfx.load_texture(&texture::Descriptor {
+            data: &[255; 4],
+            width: 1,
+            height: 1,
+            premultiplied_alpha: true,
+            format: texture::Format::Rgba8,
+        });
+        let material_id = gfx.load_material(&material::Descriptor {
+            base_color: texture_id,
+            normal: None,
+            emissive: None,
+            region: texture::Rect::new(0.0, 0.0, 1.0, 1.0),
+            blend_mode: material::BlendMode::Alpha,
+            sampler: sampler::Descriptor::default(),
+            parameters: material::Parameters::default(),
+        });
+        let parameters = material::Parameters {
+            emissive: [1.0; 4],
+            ..Default::default()
+        };
+        gfx.update_material(material_id, &parameters);
+
+        gfx.device().destroy();
+        gfx.device().poll(wgpu::Maintain::Poll);
+        assert!(gfx.is_device_lost());
+
+        pollster::block_on(gfx.recover_lost_device());
+        assert!(!gfx.is_device_lost());
+        assert_eq!(gfx.texture_cache.info(texture_id).width(), 1);
+        assert_eq!(
+            gfx.material_cache.get(material_id).unwrap().parameters(),
+            &parameters
+        );
+        assert_eq!(gfx.read_offscreen_target().unwrap().len(), 4 * 4 * 4);
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_reloaded_texture_keeps_its_id() {
+        let mut gfx = testing::headless_graphics_state(4, 4);
+        let texture_id = gfx.load_texture(&texture::Descriptor {
+            data: &[255; 4],
+            width: 1,
+            height: 1,
+            premultiplied_alpha: true,
+            format: texture::Format::Rgba8,
+        });
+        let material_id = gfx.load_material(&material::Descriptor {
+            base_color: texture_id,
+            normal: None,
+            emissive: None,
+            region: texture::Rect::new(0.0, 0.0, 1.0, 1.0),
+            blend_mode: material::BlendMode::Alpha,
+            sampler: sampler::Descriptor::default(),
+            parameters: material::Parameters::default(),
+        });
+        let texture_count = gfx.texture_cache.ids().count();
+
+        assert!(gfx.reload_texture(
+            texture_id,
+            &texture::Descriptor {
+                data: &[0; 16],
+                width: 2,
+                height: 2,
+                premultiplied_alpha: true,
+                format: texture::Format::Rgba8,
+            },
+        ));
+
+        assert_eq!(gfx.texture_cache.info(texture_id).width(), 2);
+        assert_eq!(gfx.texture_cache.ids().count(), texture_count);
+        assert!(gfx.material_cache.get(material_id).is_some());
+        assert!(gfx.unload_texture(texture_id));
+        assert!(!gfx.reload_texture(
+            texture_id,
+            &texture::Descriptor {
+                data: &[0; 4],
+                width: 1,
+                height: 1,
+                premultiplied_alpha: true,
+                format: texture::Format::Rgba8,
+            },
+        ));
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_material_maps_fall_back_to_1x1_textures() {
+        let mut gfx = testing::headless_graphics_state(4, 4);
+        let base_color = gfx.load_texture(&texture::Descriptor {
+            data: &[255; 4],
+            width: 1,
+            height: 1,
+            premultiplied_alpha: true,
+            format: texture::Format::Rgba8,
+        });
+        let normal = gfx.load_texture(&texture::Descriptor {
+            data: &[128, 128, 255, 255],
+            width: 1,
+            height: 1,
+            premultiplied_alpha: false,
+            format: texture::Format::Rgba8Linear,
+        });
+        let descriptor = material::Descriptor {
+            base_color,
+            normal: Some(normal),
+            emissive: None,
+            region: texture::Rect::new(0.0, 0.0, 1.0, 1.0),
+            blend_mode: material::BlendMode::Opaque,
+            sampler: sampler::Descriptor::default(),
+            parameters: material::Parameters::default(),
+        };
+        assert_eq!(
+            descriptor.textures().collect::<Vec<_>>(),
+            vec![base_color, normal]
+        );
+        gfx.load_material(&descriptor);
+        gfx.load_material(&material::Descriptor {
+            normal: None,
+            ..descriptor
+        });
+
+        // Only the missing maps are created, once
+        assert_eq!(gfx.material_fallback_textures.len(), 2);
+        assert_eq!(gfx.texture_cache.ids().count(), 4);
+        let emissive_fallback = gfx.material_fallback_textures[&material::Map::Emissive];
+        assert!(!gfx.unload_texture(emissive_fallback));
+        assert!(gfx.unload_texture(normal));
+    }
+
+    struct OverlayPass;
+    impl RenderPass for OverlayPass {
+        fn prepare(&mut self, _storage: &Storage) {}
+        fn execute(
+            &self,
+            _gfx: &mut GraphicsState,
+            _encoder: &mut wgpu::CommandEncoder,
+            _surface_texture_view: &wgpu::TextureView,
+            _storage: &Storage,
+        ) {
+        }
+    }
+
+    struct OverlayPlugin {
+        setup_ran: std::rc::Rc<std::cell::Cell<bool>>,
+    }
+
+    impl plugin::RendererPlugin for OverlayPlugin {
+        fn setup(&self, _gfx: &mut GraphicsState, _pipelines: &mut PipelineCache) {
+            self.setup_ran.set(true);
+        }
+
+        fn add_passes(&self, _gfx: &GraphicsState, graph: &mut RenderGraph, _storage: &Storage) {
+            graph.add_pass(OverlayPass);
+        }
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_renderer_plugin() {
+        let setup_ran = std::rc::Rc::new(std::cell::Cell::new(false));
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(tubereng_core::time::Time::with_delta(0.016));
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        testing::init_headless_renderer(
+            &mut ecs,
+            RendererBuilder::new().with_plugin(OverlayPlugin {
+                setup_ran: setup_ran.clone(),
+            }),
+            4,
+            4,
+        );
+        assert!(setup_ran.get());
+        let renderer_features = *ecs.resource::<features::RendererFeatures>().unwrap();
+        let device_features = ecs.resource::<GraphicsState>().unwrap().device().features();
+        assert_eq!(
+            renderer_features.supports(features::Feature::CompressedTextures),
+            device_features.contains(texture::COMPRESSED_TEXTURE_FEATURES)
+        );
+
+        ecs.run_systems();
+        let graph = ecs.resource::<RenderGraph>().unwrap();
+        assert_eq!(
+            graph.pass_names().last(),
+            Some(std::any::type_name::<OverlayPass>())
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_resize() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(tubereng_core::time::Time::with_delta(0.016));
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        testing::init_headless_renderer(&mut ecs, RendererBuilder::new(), 4, 4);
+
+        let mut gfx = ecs.resource_mut::<GraphicsState>().unwrap();
+        gfx.resize(8, 2);
+        gfx.resize(0, 0);
+
+        assert_eq!(
+            *gfx.window_size(),
+            WindowSize {
+                width: 8,
+                height: 2
+            }
+        );
+        assert_eq!(gfx.read_offscreen_target().unwrap().len(), 8 * 2 * 4);
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_draw_stats_in_wireframe_mode() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(tubereng_core::time::Time::with_delta(0.016));
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        testing::init_headless_renderer(&mut ecs, RendererBuilder::new(), 4, 4);
+
+        ecs.resource_mut::<debug::RenderDebugMode>()
+            .unwrap()
+            .wireframe = true;
+        ecs.insert((camera::D2::new(4.0, 4.0), camera::Active));
+        ecs.insert((sprite::Sprite {
+            texture: texture::Id(0),
+            texture_rect: None,
+        },));
+        ecs.run_systems();
+
+        let render_stats = ecs.resource::<stats::RenderStats>().unwrap();
+        let pass_2d_stats = render_stats
+            .pass_draw_stats()
+            .iter()
+            .find(|stats| stats.pass_name == std::any::type_name::<pass_2d::Pass>())
+            .unwrap();
+        assert_eq!(pass_2d_stats.draw_calls, 1);
+        assert_eq!(pass_2d_stats.vertex_count, 6);
+        assert_eq!(render_stats.total_draw_calls(), 1);
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_draw_stats_overlay() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(tubereng_core::time::Time::with_delta(0.016));
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        testing::init_headless_renderer(&mut ecs, RendererBuilder::new(), 64, 64);
+
+        ecs.resource_mut::<debug::RenderDebugMode>()
+            .unwrap()
+            .draw_stats_overlay = true;
+        ecs.run_systems();
+        ecs.run_systems();
+
+        let render_stats = ecs.resource::<stats::RenderStats>().unwrap();
+        let overlay_stats = render_stats
+            .pass_draw_stats()
+            .iter()
+            .find(|stats| stats.pass_name == std::any::type_name::<debug::DebugOverlayPass>())
+            .unwrap();
+        assert_eq!(overlay_stats.draw_calls, 1);
+        assert!(overlay_stats.vertex_count > 0);
+        let gfx = ecs.resource::<GraphicsState>().unwrap();
+        assert!(gfx.debug_font_texture_id.is_some());
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_drop_shadow_is_batched_with_its_sprite() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(tubereng_core::time::Time::with_delta(0.016));
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        testing::init_headless_renderer(&mut ecs, RendererBuilder::new(), 4, 4);
+
+        ecs.insert((camera::D2::new(4.0, 4.0), camera::Active));
+        ecs.insert((
+            sprite::Sprite {
+                texture: texture::Id(0),
+                texture_rect: None,
+            },
+            shadow::DropShadow {
+                blur: 1.0,
+                ..shadow::DropShadow::new(tubereng_math::vector::Vector2f::new(1.0, 1.0))
+            },
+        ));
+        ecs.run_systems();
+
+        let render_stats = ecs.resource::<stats::RenderStats>().unwrap();
+        let pass_2d_stats = render_stats
+            .pass_draw_stats()
+            .iter()
+            .find(|stats| stats.pass_name == std::any::type_name::<pass_2d::Pass>())
+            .unwrap();
+        assert_eq!(pass_2d_stats.draw_calls, 1);
+        assert_eq!(pass_2d_stats.vertex_count, 12);
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_sprites_with_different_textures_are_batched_together() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(tubereng_core::time::Time::with_delta(0.016));
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        testing::init_headless_renderer(&mut ecs, RendererBuilder::new(), 4, 4);
+
+        let other_texture =
+            ecs.resource_mut::<GraphicsState>()
+                .unwrap()
+                .load_texture(&texture::Descriptor {
+                    data: &[0, 0, 255, 255],
+                    width: 1,
+                    height: 1,
+                    premultiplied_alpha: true,
+                    format: texture::Format::Rgba8,
+                });
+        ecs.insert((camera::D2::new(4.0, 4.0), camera::Active));
+        ecs.insert((sprite::Sprite {
+            texture: texture::Id(0),
+            texture_rect: None,
+        },));
+        ecs.insert((sprite::Sprite {
+            texture: other_texture,
+            texture_rect: None,
+        },));
+        ecs.run_systems();
+
+        let render_stats = ecs.resource::<stats::RenderStats>().unwrap();
+        let pass_2d_stats = render_stats
+            .pass_draw_stats()
+            .iter()
+            .find(|stats| stats.pass_name == std::any::type_name::<pass_2d::Pass>())
+            .unwrap();
+        assert_eq!(pass_2d_stats.draw_calls, 1);
+        assert_eq!(pass_2d_stats.vertex_count, 12);
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_sprites_are_drawn_with_the_blend_mode_of_their_material() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(tubereng_core::time::Time::with_delta(0.016));
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        testing::init_headless_renderer(&mut ecs, RendererBuilder::new(), 4, 4);
+
+        let additive_material =
+            ecs.resource_mut::<GraphicsState>()
+                .unwrap()
+                .load_material(&material::Descriptor {
+                    base_color: texture::Id(0),
+                    normal: None,
+                    emissive: None,
+                    region: texture::Rect::new(0.0, 0.0, 1.0, 1.0),
+                    blend_mode: material::BlendMode::Additive,
+                    sampler: sampler::Descriptor::default(),
+                    parameters: material::Parameters::default(),
+                });
+        ecs.insert((camera::D2::new(4.0, 4.0), camera::Active));
+        let sprite = || sprite::Sprite {
+            texture: texture::Id(0),
+            texture_rect: None,
+        };
+        ecs.insert((sprite(),));
+        ecs.insert((sprite(), additive_material));
+        // The blend mode component overrides the blend mode of the material
+        ecs.insert((sprite(), additive_material, material::BlendMode::Alpha));
+        ecs.run_systems();
+
+        let render_stats = ecs.resource::<stats::RenderStats>().unwrap();
+        let pass_2d_stats = render_stats
+            .pass_draw_stats()
+            .iter()
+            .find(|stats| stats.pass_name == std::any::type_name::<pass_2d::Pass>())
+            .unwrap();
+        assert_eq!(pass_2d_stats.draw_calls, 3);
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_sprites_are_drawn_with_the_sampler_of_their_material() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(tubereng_core::time::Time::with_delta(0.016));
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        testing::init_headless_renderer(&mut ecs, RendererBuilder::new(), 4, 4);
+
+        let linear_sampler = sampler::Descriptor {
+            filter_mode: sampler::FilterMode::Linear,
+            ..Default::default()
+        };
+        let linear_material =
+            ecs.resource_mut::<GraphicsState>()
+                .unwrap()
+                .load_material(&material::Descriptor {
+                    base_color: texture::Id(0),
+                    normal: None,
+                    emissive: None,
+                    region: texture::Rect::new(0.0, 0.0, 1.0, 1.0),
+                    blend_mode: material::BlendMode::Alpha,
+                    sampler: linear_sampler,
+                    parameters: material::Parameters::default(),
+                });
+        ecs.insert((camera::D2::new(4.0, 4.0), camera::Active));
+        let sprite = || sprite::Sprite {
+            texture: texture::Id(0),
+            texture_rect: None,
+        };
+        ecs.insert((sprite(),));
+        ecs.insert((sprite(), linear_material));
+        // The sampler component overrides the sampler of the material
+        let repeat_sampler = sampler::Descriptor {
+            address_mode: sampler::AddressMode::Repeat,
+            ..Default::default()
+        };
+        ecs.insert((sprite(), linear_material, repeat_sampler));
+        ecs.run_systems();
+
+        let render_stats = ecs.resource::<stats::RenderStats>().unwrap();
+        let pass_2d_stats = render_stats
+            .pass_draw_stats()
+            .iter()
+            .find(|stats| stats.pass_name == std::any::type_name::<pass_2d::Pass>())
+            .unwrap();
+        assert_eq!(pass_2d_stats.draw_calls, 3);
+        let gfx = ecs.resource::<GraphicsState>().unwrap();
+        assert!(gfx.sampler_cache.get(repeat_sampler).is_some());
+        assert!(gfx
+            .sampler_cache
+            .get(sampler::Descriptor::default())
+            .is_some());
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_invalid_sprites_are_drawn_with_the_missing_texture() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(tubereng_core::time::Time::with_delta(0.016));
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        testing::init_headless_renderer(&mut ecs, RendererBuilder::new(), 4, 4);
+
+        ecs.insert((camera::D2::new(4.0, 4.0), camera::Active));
+        let missing_texture_sprite = ecs.insert((sprite::Sprite {
+            texture: texture::Id(99),
+            texture_rect: None,
+        },));
+        ecs.insert((sprite::Sprite {
+            texture: texture::Id(0),
+            texture_rect: Some(texture::Rect::new(0.0, 0.0, 2.0, 2.0)),
+        },));
+        ecs.run_systems();
+        ecs.run_systems();
+
+        let render_stats = ecs.resource::<stats::RenderStats>().unwrap();
+        let pass_2d_stats = render_stats
+            .pass_draw_stats()
+            .iter()
+            .find(|stats| stats.pass_name == std::any::type_name::<pass_2d::Pass>())
+            .unwrap();
+        assert_eq!(pass_2d_stats.vertex_count, 12);
+
+        let events = ecs
+            .resource::<tubereng_ecs::event::Events<validation::InvalidSprite>>()
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .any(|event| event.entity == missing_texture_sprite
+                && matches!(
+                    event.reason,
+                    validation::InvalidSpriteReason::MissingTexture(texture::Id(99))
+                )));
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_lod_level_is_selected_by_screen_size() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(tubereng_core::time::Time::with_delta(0.016));
+        ecs.insert_resource(tubereng_core::TransformCache::new());
+        testing::init_headless_renderer(&mut ecs, RendererBuilder::new(), 4, 4);
+
+        let camera = ecs.insert((camera::D2::new(4.0, 4.0), camera::Active));
+        let level = |threshold| {
+            lod::LodLevel::new(
+                sprite::Sprite {
+                    texture: texture::Id(0),
+                    texture_rect: None,
+                },
+                threshold,
+            )
+        };
+        let entity = ecs.insert((lod::Lod::new(
+            lod::LodMetric::ScreenSize,
+            vec![level(2.0), level(0.5)],
+        ),));
+        ecs.run_systems();
+        ecs.run_systems();
+
+        let render_stats = ecs.resource::<stats::RenderStats>().unwrap();
+        let pass_2d_stats = render_stats
+            .pass_draw_stats()
+            .iter()
+            .find(|stats| stats.pass_name == std::any::type_name::<pass_2d::Pass>())
+            .unwrap();
+        assert_eq!(pass_2d_stats.vertex_count, 6);
+        let lod = ecs.component::<lod::Lod>(entity).unwrap();
+        assert_eq!(lod.current_level(camera), Some(1));
+    }
+}
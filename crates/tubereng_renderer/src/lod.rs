@@ -0,0 +1,196 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use tubereng_ecs::EntityId;
+
+use crate::sprite::Sprite;
+
+const DEFAULT_HYSTERESIS: f32 = 0.1;
+
+/// What the thresholds of the levels of a [`Lod`] are compared with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LodMetric {
+    /// Distance in world units between the origin of the entity and the
+    /// center of the view of the camera, in the plane of the view. A level is
+    /// used while the distance is below its threshold.
+    #[default]
+    Distance,
+    /// Largest side in pixels of the entity on screen. A level is used while
+    /// the size is above its threshold.
+    ScreenSize,
+}
+
+#[derive(Debug)]
+pub struct LodLevel {
+    pub sprite: Sprite,
+    pub threshold: f32,
+}
+
+impl LodLevel {
+    #[must_use]
+    pub fn new(sprite: Sprite, threshold: f32) -> Self {
+        Self { sprite, threshold }
+    }
+}
+
+/// Representations of an entity from the most detailed to the least detailed
+/// one, the 2D pass of every camera draws the one matching the distance or
+/// the screen size of the entity.
+///
+/// Every level is drawn with the size of the first one, so that lower
+/// resolution textures can be used for the far away levels. Nothing is drawn
+/// past the threshold of the last level.
+#[derive(Debug)]
+pub struct Lod {
+    levels: Vec<LodLevel>,
+    metric: LodMetric,
+    /// Fraction of a threshold the metric must go past to switch back to the
+    /// level on the other side, avoiding popping around the thresholds
+    hysteresis: f32,
+    /// Index of the level used by each camera, equal to the level count when
+    /// the entity is hidden
+    current_levels: RefCell<HashMap<EntityId, usize>>,
+}
+
+impl Lod {
+    /// Creates a `Lod` from levels ordered from the most detailed to the least
+    /// detailed one
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `levels` is empty
+    #[must_use]
+    pub fn new(metric: LodMetric, levels: Vec<LodLevel>) -> Self {
+        assert!(!levels.is_empty(), "Lod should have at least one level");
+        Self {
+            levels,
+            metric,
+            hysteresis: DEFAULT_HYSTERESIS,
+            current_levels: RefCell::new(HashMap::new()),
+        }
+    }
+
+    #[must_use]
+    pub fn with_hysteresis(mut self, hysteresis: f32) -> Self {
+        self.hysteresis = hysteresis;
+        self
+    }
+
+    #[must_use]
+    pub fn levels(&self) -> &[LodLevel] {
+        &self.levels
+    }
+
+    #[must_use]
+    pub fn metric(&self) -> LodMetric {
+        self.metric
+    }
+
+    /// Returns the index of the level last drawn by a camera, `None` if the
+    /// entity hasn't been drawn by that camera or was hidden
+    #[must_use]
+    pub fn current_level(&self, camera_id: EntityId) -> Option<usize> {
+        self.current_levels
+            .borrow()
+            .get(&camera_id)
+            .copied()
+            .filter(|&level| level < self.levels.len())
+    }
+
+    /// Selects the level a camera draws for the given value of the metric,
+    /// `None` if the entity is hidden
+    pub(crate) fn select_level(&self, camera_id: EntityId, value: f32) -> Option<&LodLevel> {
+        let mut current_levels = self.current_levels.borrow_mut();
+        let current = current_levels.get(&camera_id).copied();
+        let level = self
+            .levels
+            .iter()
+            .enumerate()
+            .position(|(index, level)| self.is_within(index, level.threshold, value, current))
+            .unwrap_or(self.levels.len());
+        current_levels.insert(camera_id, level);
+        self.levels.get(level)
+    }
+
+    /// Returns true if `value` is on the detailed side of the threshold of the
+    /// level `index`, the threshold being moved away from the current level
+    fn is_within(&self, index: usize, threshold: f32, value: f32, current: Option<usize>) -> bool {
+        let margin = match current {
+            Some(current) if current <= index => threshold.abs() * self.hysteresis,
+            Some(_) => -threshold.abs() * self.hysteresis,
+            None => 0.0,
+        };
+        match self.metric {
+            LodMetric::Distance => value <= threshold + margin,
+            LodMetric::ScreenSize => value >= threshold - margin,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::texture;
+
+    use super::*;
+
+    fn lod(metric: LodMetric, thresholds: &[f32]) -> Lod {
+        Lod::new(
+            metric,
+            thresholds
+                .iter()
+                .map(|&threshold| {
+                    LodLevel::new(
+                        Sprite {
+                            texture: texture::Id(0),
+                            texture_rect: None,
+                        },
+                        threshold,
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn levels_are_selected_by_distance() {
+        let lod = lod(LodMetric::Distance, &[100.0, 200.0]);
+        lod.select_level(0, 50.0);
+        assert_eq!(lod.current_level(0), Some(0));
+        lod.select_level(0, 150.0);
+        assert_eq!(lod.current_level(0), Some(1));
+        assert!(lod.select_level(0, 250.0).is_none());
+        assert_eq!(lod.current_level(0), None);
+    }
+
+    #[test]
+    fn levels_are_selected_by_screen_size() {
+        let lod = lod(LodMetric::ScreenSize, &[64.0, 8.0]);
+        lod.select_level(0, 128.0);
+        assert_eq!(lod.current_level(0), Some(0));
+        lod.select_level(0, 32.0);
+        assert_eq!(lod.current_level(0), Some(1));
+        assert!(lod.select_level(0, 4.0).is_none());
+    }
+
+    #[test]
+    fn hysteresis_avoids_popping_around_thresholds() {
+        let lod = lod(LodMetric::Distance, &[100.0, 200.0]);
+        lod.select_level(0, 95.0);
+        lod.select_level(0, 105.0);
+        assert_eq!(lod.current_level(0), Some(0));
+        lod.select_level(0, 111.0);
+        assert_eq!(lod.current_level(0), Some(1));
+        lod.select_level(0, 95.0);
+        assert_eq!(lod.current_level(0), Some(1));
+        lod.select_level(0, 89.0);
+        assert_eq!(lod.current_level(0), Some(0));
+    }
+
+    #[test]
+    fn levels_are_selected_per_camera() {
+        let lod = lod(LodMetric::Distance, &[100.0, 200.0]);
+        lod.select_level(0, 50.0);
+        lod.select_level(1, 150.0);
+        assert_eq!(lod.current_level(0), Some(0));
+        assert_eq!(lod.current_level(1), Some(1));
+    }
+}
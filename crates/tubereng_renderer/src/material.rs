@@ -1,9 +1,9 @@
 use std::ops::Deref;
 
-use crate::texture;
+use crate::{sampler, texture};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Id(usize);
+pub struct Id(pub(crate) usize);
 impl Deref for Id {
     type Target = usize;
 
@@ -12,8 +12,107 @@ impl Deref for Id {
     }
 }
 
+/// How the fragments of a material are blended with the render target.
+///
+/// The 2D pass draws an entity with the blend mode of the material whose
+/// [`Id`] is attached to it as a component. A `BlendMode` can also be attached
+/// as a component to a sprite entity, overriding the blend mode of its
+/// material.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    #[default]
+    Alpha,
+    Additive,
+    Multiply,
+    Opaque,
+}
+
+impl BlendMode {
+    /// Returns the blend state of the blend mode, for source colors that
+    /// are premultiplied by alpha or not
+    #[must_use]
+    pub fn blend_state(self, premultiplied_alpha: bool) -> Option<wgpu::BlendState> {
+        match self {
+            BlendMode::Alpha if premultiplied_alpha => {
+                Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING)
+            }
+            BlendMode::Alpha => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::default(),
+            }),
+            BlendMode::Additive => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: if premultiplied_alpha {
+                        wgpu::BlendFactor::One
+                    } else {
+                        wgpu::BlendFactor::SrcAlpha
+                    },
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            BlendMode::Multiply => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::Dst,
+                    // Fully transparent premultiplied texels are black, so the
+                    // destination must be kept where the source is transparent
+                    dst_factor: if premultiplied_alpha {
+                        wgpu::BlendFactor::OneMinusSrcAlpha
+                    } else {
+                        wgpu::BlendFactor::Zero
+                    },
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::DstAlpha,
+                    dst_factor: wgpu::BlendFactor::Zero,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            }),
+            BlendMode::Opaque => None,
+        }
+    }
+}
+
+/// Uniform parameters of a material, bound next to its base color texture
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Parameters {
+    /// Factor multiplied with the base color texture
+    pub base_color_factor: [f32; 4],
+    /// Color added to the shaded color, the alpha channel is unused
+    pub emissive: [f32; 4],
+    /// Parameters free for custom shaders to use
+    pub custom: [[f32; 4]; 4],
+}
+
+impl Default for Parameters {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0; 4],
+            emissive: [0.0; 4],
+            custom: [[0.0; 4]; 4],
+        }
+    }
+}
+
 pub struct Material {
     pub(crate) bind_group: wgpu::BindGroup,
+    pub(crate) blend_mode: BlendMode,
+    pub(crate) uniform_buffer: wgpu::Buffer,
+    pub(crate) parameters: Parameters,
+    /// Descriptor the material has been created with, kept to create it again
+    /// if the device is lost
+    pub(crate) descriptor: Descriptor,
 }
 
 impl Material {
@@ -21,31 +120,139 @@ impl Material {
     pub fn bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
+
+    #[must_use]
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    #[must_use]
+    pub fn sampler(&self) -> sampler::Descriptor {
+        self.descriptor.sampler
+    }
+
+    #[must_use]
+    pub fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
 }
 
+#[derive(Clone)]
 pub struct Descriptor {
     pub base_color: texture::Id,
+    /// Tangent space normal map, expected to be loaded as
+    /// [`texture::Format::Rgba8Linear`]. A flat normal is bound if `None`.
+    pub normal: Option<texture::Id>,
+    /// Map multiplied with [`Parameters::emissive`]. A white texture is bound
+    /// if `None`, the emissive color then applying uniformly.
+    pub emissive: Option<texture::Id>,
     pub region: texture::Rect,
+    pub blend_mode: BlendMode,
+    pub sampler: sampler::Descriptor,
+    pub parameters: Parameters,
+}
+
+impl Descriptor {
+    /// Returns the textures the material samples, the missing maps excluded
+    pub fn textures(&self) -> impl Iterator<Item = texture::Id> {
+        std::iter::once(self.base_color)
+            .chain(self.normal)
+            .chain(self.emissive)
+    }
+}
+
+/// Optional maps of a material, bound to a 1x1 fallback texture when missing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Map {
+    Normal,
+    Emissive,
+}
+
+impl Map {
+    pub(crate) fn fallback_texture(self) -> texture::Descriptor<'static> {
+        match self {
+            Map::Normal => texture::Descriptor {
+                // (0, 0, 1) in tangent space
+                data: &[128, 128, 255, 255],
+                width: 1,
+                height: 1,
+                premultiplied_alpha: false,
+                format: texture::Format::Rgba8Linear,
+            },
+            Map::Emissive => texture::Descriptor {
+                data: &[255; 4],
+                width: 1,
+                height: 1,
+                premultiplied_alpha: false,
+                format: texture::Format::Rgba8,
+            },
+        }
+    }
 }
 
 pub struct Cache {
-    material: Vec<Material>,
+    material: Vec<Option<Material>>,
+    /// Ids of the unloaded materials, reused by the next loaded materials
+    free_ids: Vec<Id>,
 }
 
 impl Cache {
     #[must_use]
     pub fn new() -> Self {
-        Self { material: vec![] }
+        Self {
+            material: vec![],
+            free_ids: vec![],
+        }
     }
 
+    /// Inserts a material, the ids of unloaded materials are reused
     pub fn insert(&mut self, material: Material) -> Id {
-        self.material.push(material);
-        Id(self.material.len() - 1)
+        if let Some(id) = self.free_ids.pop() {
+            self.material[*id] = Some(material);
+            id
+        } else {
+            self.material.push(Some(material));
+            Id(self.material.len() - 1)
+        }
+    }
+
+    /// Removes a material from the cache. Returns false if the material wasn't
+    /// loaded.
+    pub fn remove(&mut self, id: Id) -> bool {
+        let Some(material) = self.material.get_mut(*id) else {
+            return false;
+        };
+        if material.take().is_none() {
+            return false;
+        }
+        self.free_ids.push(id);
+        true
     }
 
     #[must_use]
     pub fn get(&self, id: Id) -> Option<&Material> {
-        self.material.get(*id)
+        self.material.get(*id).and_then(Option::as_ref)
+    }
+
+    #[must_use]
+    pub fn get_mut(&mut self, id: Id) -> Option<&mut Material> {
+        self.material.get_mut(*id).and_then(Option::as_mut)
+    }
+
+    /// Returns the loaded materials
+    pub fn iter(&self) -> impl Iterator<Item = (Id, &Material)> {
+        self.material
+            .iter()
+            .enumerate()
+            .filter_map(|(index, material)| Some((Id(index), material.as_ref()?)))
+    }
+
+    pub(crate) fn ids(&self) -> impl Iterator<Item = Id> + '_ {
+        self.iter().map(|(id, _)| id)
+    }
+
+    pub(crate) fn replace(&mut self, id: Id, material: Material) {
+        self.material[*id] = Some(material);
     }
 }
 
@@ -54,3 +261,18 @@ impl Default for Cache {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parameters_uniform_layout() {
+        // Uniform buffers are laid out with a 16 bytes alignment
+        assert_eq!(std::mem::size_of::<Parameters>() % 16, 0);
+        assert_eq!(
+            bytemuck::cast_slice::<Parameters, f32>(&[Parameters::default()])[..4],
+            [1.0; 4]
+        );
+    }
+}
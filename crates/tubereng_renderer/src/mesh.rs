@@ -1,5 +1,6 @@
 use std::ops::Deref;
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Id(usize);
 impl Deref for Id {
@@ -12,14 +13,28 @@ impl Deref for Id {
 
 #[repr(C)]
 #[derive(bytemuck::Zeroable, bytemuck::Pod, Debug, Copy, Clone)]
-pub struct Vertex {
+pub(crate) struct Vertex {
     pub(crate) position: [f32; 3],
     pub(crate) texture_coordinates: [f32; 2],
+    /// Color multiplied with the sampled texel, or the color of the
+    /// silhouette for drop shadows
+    pub(crate) color: [f32; 4],
+    /// Drop shadow parameters: `x` is 1 to draw the silhouette of the texture,
+    /// `y` is 1 if the texture has premultiplied alpha and `zw` is the blur
+    /// radius in texture coordinates
+    pub(crate) shadow: [f32; 4],
+    /// Index of the texture of the quad among the textures of its batch
+    pub(crate) texture_index: u32,
 }
 
 impl Vertex {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Uint32
+    ];
 
     pub fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
@@ -29,3 +44,27 @@ impl Vertex {
         }
     }
 }
+
+/// Geometry of an indexed triangle mesh, e.g. imported from a 3D model. The
+/// normals and texture coordinates are either empty or have one element per
+/// position.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Descriptor {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub texture_coordinates: Vec<[f32; 2]>,
+    /// Indices of the vertices of the triangles
+    pub indices: Vec<u32>,
+}
+
+impl Descriptor {
+    #[must_use]
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    #[must_use]
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
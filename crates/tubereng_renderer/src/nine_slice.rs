@@ -0,0 +1,194 @@
+use crate::texture;
+
+/// Size of the borders of a [`NineSlice`], in texels
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Insets {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl Insets {
+    #[must_use]
+    pub fn uniform(inset: f32) -> Self {
+        Self {
+            left: inset,
+            right: inset,
+            top: inset,
+            bottom: inset,
+        }
+    }
+}
+
+/// Sprite drawn at an arbitrary size by splitting its texture in 9 slices:
+/// the corners are drawn as is, the edges are stretched along one axis and
+/// the center is stretched along both, so that panels and buttons keep crisp
+/// borders. If the target size is smaller than the borders, the borders are
+/// shrunk.
+#[derive(Debug, Clone)]
+pub struct NineSlice {
+    pub texture: texture::Id,
+    /// Region of the texture to slice, the whole texture if `None`
+    pub texture_rect: Option<texture::Rect>,
+    pub insets: Insets,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Part of a nine slice sprite: region of the texture and where it is drawn
+#[derive(Debug, Clone)]
+pub(crate) struct Slice {
+    pub(crate) texture_rect: texture::Rect,
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+}
+
+/// Splits an axis in 3 slices, returns the source offset and length, and the
+/// destination offset and length of each one
+fn axis_slices(
+    start: f32,
+    length: f32,
+    inset_start: f32,
+    inset_end: f32,
+    target_length: f32,
+) -> [(f32, f32, f32, f32); 3] {
+    let inset_start = inset_start.clamp(0.0, length);
+    let inset_end = inset_end.clamp(0.0, length - inset_start);
+    let borders = inset_start + inset_end;
+    let border_scale = if borders > target_length && borders > 0.0 {
+        target_length / borders
+    } else {
+        1.0
+    };
+    let target_start = inset_start * border_scale;
+    let target_end = inset_end * border_scale;
+    let target_center = (target_length - target_start - target_end).max(0.0);
+    [
+        (start, inset_start, 0.0, target_start),
+        (
+            start + inset_start,
+            length - borders,
+            target_start,
+            target_center,
+        ),
+        (
+            start + length - inset_end,
+            inset_end,
+            target_start + target_center,
+            target_end,
+        ),
+    ]
+}
+
+impl NineSlice {
+    #[must_use]
+    pub fn new(texture: texture::Id, insets: Insets, width: f32, height: f32) -> Self {
+        Self {
+            texture,
+            texture_rect: None,
+            insets,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the slices to draw, slices with an empty area are skipped
+    pub(crate) fn slices(&self, texture_info: &texture::Info) -> Vec<Slice> {
+        #[allow(clippy::cast_precision_loss)]
+        let source = self.texture_rect.clone().unwrap_or(texture::Rect::new(
+            0.0,
+            0.0,
+            texture_info.width() as f32,
+            texture_info.height() as f32,
+        ));
+        let columns = axis_slices(
+            source.x,
+            source.width,
+            self.insets.left,
+            self.insets.right,
+            self.width,
+        );
+        let rows = axis_slices(
+            source.y,
+            source.height,
+            self.insets.top,
+            self.insets.bottom,
+            self.height,
+        );
+
+        let mut slices = Vec::with_capacity(9);
+        for &(source_y, source_height, y, height) in &rows {
+            for &(source_x, source_width, x, width) in &columns {
+                if source_width <= 0.0 || source_height <= 0.0 || width <= 0.0 || height <= 0.0 {
+                    continue;
+                }
+                slices.push(Slice {
+                    texture_rect: texture::Rect::new(
+                        source_x,
+                        source_y,
+                        source_width,
+                        source_height,
+                    ),
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+        }
+        slices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture_info() -> texture::Info {
+        texture::Info {
+            width: 16,
+            height: 16,
+            premultiplied_alpha: true,
+        }
+    }
+
+    #[test]
+    fn corners_keep_their_size() {
+        let nine_slice = NineSlice::new(texture::Id(0), Insets::uniform(4.0), 100.0, 40.0);
+        let slices = nine_slice.slices(&texture_info());
+        assert_eq!(slices.len(), 9);
+
+        let bottom_right = &slices[8];
+        assert_eq!(
+            (
+                bottom_right.x,
+                bottom_right.y,
+                bottom_right.width,
+                bottom_right.height
+            ),
+            (96.0, 36.0, 4.0, 4.0)
+        );
+        let center = &slices[4];
+        assert_eq!((center.x, center.width, center.height), (4.0, 92.0, 32.0));
+        assert_eq!(
+            (bottom_right.texture_rect.x, center.texture_rect.width),
+            (12.0, 8.0)
+        );
+    }
+
+    #[test]
+    fn borders_shrink_when_target_is_too_small() {
+        let mut nine_slice = NineSlice::new(texture::Id(0), Insets::uniform(4.0), 4.0, 40.0);
+        nine_slice.texture_rect = Some(texture::Rect::new(16.0, 0.0, 16.0, 16.0));
+        let slices = nine_slice.slices(&texture_info());
+        // The center column is empty
+        assert_eq!(slices.len(), 6);
+        assert!(slices
+            .iter()
+            .all(|slice| (slice.width - 2.0).abs() < f32::EPSILON));
+        assert_eq!((slices[1].x, slices[1].texture_rect.x), (2.0, 28.0));
+    }
+}
@@ -2,43 +2,119 @@ use std::collections::HashMap;
 
 use tubereng_core::TransformCache;
 use tubereng_ecs::{
+    event::Events,
     system::{Res, ResMut, Q},
-    Storage,
+    EntityId, Storage,
+};
+use tubereng_math::{
+    matrix::{Identity, Matrix4f},
+    vector::Vector3f,
 };
-use tubereng_math::{matrix::Matrix4f, vector::Vector3f};
 use wgpu::include_wgsl;
 
 use crate::{
+    bitmap_text::BitmapText,
+    buffer_pool::{Allocation, FrameBufferPool},
     camera,
+    debug::RenderDebugMode,
+    lod::{Lod, LodMetric},
+    material::{self, BlendMode},
     mesh::Vertex,
+    nine_slice::NineSlice,
     render_graph::{RenderGraph, RenderPass},
+    sampler,
+    shadow::DropShadow,
     sprite::{AnimatedSprite, Sprite},
-    texture, GraphicsState, PipelineCache,
+    stats::DrawStats,
+    streaming::{self, TextureStreaming},
+    texture,
+    validation::{self, InvalidSprite, ReportedInvalidSprites},
+    GraphicsState, PipelineCache,
 };
 
 struct Quad2d {
     pub(crate) transform: Matrix4f,
     texture_id: texture::Id,
     texture_rect: texture::Rect,
+    blend_mode: BlendMode,
+    sampler: sampler::Descriptor,
+    /// Draws the silhouette of the quad instead of its texels
+    shadow: Option<QuadShadow>,
+}
+
+struct QuadShadow {
+    color: [f32; 4],
+    /// Blur radius, in texels
+    blur: f32,
 }
+
+/// Number of textures a batch can draw from, quads with different textures
+/// being merged into the same draw call
+pub(crate) const MAX_BATCH_TEXTURES: usize = 8;
+
 struct PendingBatch {
     pub(crate) vertices: Vec<Vertex>,
-    pub(crate) texture_id: texture::Id,
+    pub(crate) texture_ids: Vec<texture::Id>,
+    pub(crate) blend_mode: BlendMode,
+    pub(crate) premultiplied_alpha: bool,
+    pub(crate) sampler: sampler::Descriptor,
 }
 
 impl PendingBatch {
-    pub fn new(texture_id: texture::Id) -> Self {
+    pub fn new(
+        blend_mode: BlendMode,
+        premultiplied_alpha: bool,
+        sampler: sampler::Descriptor,
+    ) -> Self {
         Self {
             vertices: vec![],
-            texture_id,
+            texture_ids: vec![],
+            blend_mode,
+            premultiplied_alpha,
+            sampler,
         }
     }
+
+    /// Returns the index of the texture in the batch, adding it if there is
+    /// room left
+    fn texture_index(&mut self, texture_id: texture::Id) -> Option<u32> {
+        let index = match self.texture_ids.iter().position(|id| *id == texture_id) {
+            Some(index) => index,
+            None if self.texture_ids.len() < MAX_BATCH_TEXTURES => {
+                self.texture_ids.push(texture_id);
+                self.texture_ids.len() - 1
+            }
+            None => return None,
+        };
+        u32::try_from(index).ok()
+    }
 }
 
 struct BatchMetadata {
     start_vertex_index: u32,
     end_vertex_index: u32,
-    texture_id: texture::Id,
+    /// Index of the bind group of the textures of the batch
+    texture_bind_group: usize,
+    blend_mode: BlendMode,
+    premultiplied_alpha: bool,
+}
+
+/// Identifier of a 2D pipeline in the pipeline cache, pipelines being created
+/// for the format of the surface they render to
+fn pipeline_identifier(
+    blend_mode: BlendMode,
+    premultiplied_alpha: bool,
+    polygon_mode: wgpu::PolygonMode,
+    surface_texture_format: wgpu::TextureFormat,
+) -> String {
+    let mut identifier = format!("pass_2d_pipeline_{blend_mode:?}_{surface_texture_format:?}");
+    if premultiplied_alpha {
+        identifier.push_str("_premultiplied");
+    }
+    if polygon_mode == wgpu::PolygonMode::Line {
+        identifier.push_str("_wireframe");
+    }
+    identifier
 }
 
 #[repr(C)]
@@ -47,93 +123,146 @@ pub struct PassUniform {
     view_proj: [[f32; 4]; 4],
 }
 
+impl PassUniform {
+    pub(crate) fn new(view_proj: &Matrix4f) -> Self {
+        Self {
+            view_proj: (*view_proj).into(),
+        }
+    }
+}
+
+/// Layout of the textures of a batch, followed by the sampler they are
+/// sampled with
+pub(crate) fn create_texture_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("texture_bind_group_layout"),
+        entries: &(0..=MAX_BATCH_TEXTURES)
+            .map(|binding| wgpu::BindGroupLayoutEntry {
+                binding: u32::try_from(binding).unwrap(),
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                // The sampler follows the textures
+                ty: if binding < MAX_BATCH_TEXTURES {
+                    wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    }
+                } else {
+                    wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+                },
+                count: None,
+            })
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// Layout of the [`PassUniform`]
+pub(crate) fn create_pass_uniform_bind_group_layout(
+    device: &wgpu::Device,
+) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("pass_uniform_bind_group_layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        }],
+    })
+}
+
 pub struct Pass {
+    camera_id: EntityId,
     pending_batches: Vec<PendingBatch>,
     batches_metadata: Vec<BatchMetadata>,
     #[allow(clippy::struct_field_names)]
-    pass_uniform_buffer: wgpu::Buffer,
     #[allow(clippy::struct_field_names)]
     pass_uniform_bind_group_layout: wgpu::BindGroupLayout,
     #[allow(clippy::struct_field_names)]
-    pass_uniform_bind_group: wgpu::BindGroup,
+    pass_uniform_bind_group: Option<wgpu::BindGroup>,
     texture_bind_group_layout: wgpu::BindGroupLayout,
-    texture_bind_groups: HashMap<texture::Id, wgpu::BindGroup>,
-    vertex_buffer: wgpu::Buffer,
+    texture_views: HashMap<texture::Id, wgpu::TextureView>,
+    texture_bind_groups: Vec<wgpu::BindGroup>,
+    vertex_allocation: Option<Allocation>,
+    view_proj: Matrix4f,
+    /// Center of the view of the camera, in world space
+    view_center: Vector3f,
+    target_size: (f32, f32),
+    requested_mips: HashMap<texture::Id, usize>,
 }
 
-impl Pass {
-    const MAX_VERTICES: usize = 10_000;
-    pub fn new(device: &wgpu::Device) -> Self {
-        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("pass_2d_vertex_buffer"),
-            size: (Self::MAX_VERTICES * std::mem::size_of::<Vertex>()) as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+/// Returns the blend mode of an entity: its [`BlendMode`] component if any,
+/// else the blend mode of its material, else the default blend mode
+fn entity_blend_mode(storage: &Storage, gfx: &GraphicsState, id: EntityId) -> BlendMode {
+    if let Some(blend_mode) = storage.component::<BlendMode>(id) {
+        return *blend_mode;
+    }
+    storage
+        .component::<material::Id>(id)
+        .and_then(|material_id| gfx.material_cache.get(*material_id))
+        .map_or_else(BlendMode::default, material::Material::blend_mode)
+}
 
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("texture_bind_group_layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            });
+/// Returns the sampler settings of an entity: its [`sampler::Descriptor`]
+/// component if any, else the sampler of its material, else the default
+/// sampler
+fn entity_sampler(storage: &Storage, gfx: &GraphicsState, id: EntityId) -> sampler::Descriptor {
+    if let Some(sampler) = storage.component::<sampler::Descriptor>(id) {
+        return *sampler;
+    }
+    storage
+        .component::<material::Id>(id)
+        .and_then(|material_id| gfx.material_cache.get(*material_id))
+        .map_or_else(sampler::Descriptor::default, material::Material::sampler)
+}
 
-        let pass_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("pass_uniform"),
-            size: std::mem::size_of::<PassUniform>() as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+/// Returns true if a quad whose corners are given in clip space is entirely
+/// outside of the view volume of the camera
+fn is_outside_view(clip_corners: &[Vector3f; 4]) -> bool {
+    clip_corners.iter().all(|corner| corner.x < -1.0)
+        || clip_corners.iter().all(|corner| corner.x > 1.0)
+        || clip_corners.iter().all(|corner| corner.y < -1.0)
+        || clip_corners.iter().all(|corner| corner.y > 1.0)
+}
 
-        let pass_uniform_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("pass_uniform_bind_group_layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
-            });
+/// Returns the size in pixels of the area the camera renders to
+#[allow(clippy::cast_precision_loss)]
+fn target_size(gfx: &GraphicsState, viewport: Option<&camera::Viewport>) -> (f32, f32) {
+    let window_size = gfx.window_size();
+    let (width, height) = match viewport {
+        Some(viewport) => {
+            let (_, _, width, height) = viewport.to_physical(window_size.width, window_size.height);
+            (width, height)
+        }
+        None => (window_size.width, window_size.height),
+    };
+    (width as f32, height as f32)
+}
 
-        let pass_uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("pass_uniform_bind_group"),
-            layout: &pass_uniform_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: pass_uniform_buffer.as_entire_binding(),
-            }],
-        });
+impl Pass {
+    pub fn new(device: &wgpu::Device, camera_id: EntityId) -> Self {
+        let texture_bind_group_layout = create_texture_bind_group_layout(device);
+
+        let pass_uniform_bind_group_layout = create_pass_uniform_bind_group_layout(device);
 
         Self {
+            camera_id,
             pending_batches: vec![],
             batches_metadata: vec![],
             texture_bind_group_layout,
-            texture_bind_groups: HashMap::new(),
-            vertex_buffer,
-            pass_uniform_buffer,
-            pass_uniform_bind_group,
+            texture_views: HashMap::new(),
+            texture_bind_groups: vec![],
+            vertex_allocation: None,
+            pass_uniform_bind_group: None,
             pass_uniform_bind_group_layout,
+            view_proj: Matrix4f::identity(),
+            view_center: Vector3f::new(0.0, 0.0, 0.0),
+            target_size: (0.0, 0.0),
+            requested_mips: HashMap::new(),
         }
     }
 
@@ -148,74 +277,184 @@ impl Pass {
         let quad_texture_w = quad.texture_rect.width;
         let quad_texture_h = quad.texture_rect.height;
 
-        let top_left = local_to_world_matrix
-            .transform_vec3(&Vector3f::new(0.0, 0.0, 0.0))
-            .into();
-        let bottom_left = local_to_world_matrix
-            .transform_vec3(&Vector3f::new(0.0, quad_texture_h, 0.0))
-            .into();
-        let bottom_right = local_to_world_matrix
-            .transform_vec3(&Vector3f::new(quad_texture_w, quad_texture_h, 0.0))
-            .into();
-        let top_right = local_to_world_matrix
-            .transform_vec3(&Vector3f::new(quad_texture_w, 0.0, 0.0))
-            .into();
+        let corners = [
+            Vector3f::new(0.0, 0.0, 0.0),
+            Vector3f::new(0.0, quad_texture_h, 0.0),
+            Vector3f::new(quad_texture_w, quad_texture_h, 0.0),
+            Vector3f::new(quad_texture_w, 0.0, 0.0),
+        ]
+        .map(|corner| local_to_world_matrix.transform_vec3(&corner));
+        let clip_corners = corners.map(|corner| self.view_proj.transform_vec3(&corner));
+        if is_outside_view(&clip_corners) {
+            return;
+        }
+        self.request_mip_for_quad(quad, &clip_corners);
+        let [top_left, bottom_left, bottom_right, top_right] = corners.map(Into::into);
         let texture_id = quad.texture_id;
+        let blend_mode = quad.blend_mode;
+        let sampler = quad.sampler;
 
-        let batch = match self.pending_batches.last_mut() {
-            Some(batch) if batch.texture_id == texture_id => batch,
-            _ => {
-                self.pending_batches.push(PendingBatch::new(texture_id));
-                // SAFETY: We just added a batch to the pending batch list
-                unsafe { self.pending_batches.last_mut().unwrap_unchecked() }
+        let premultiplied_alpha = texture_info.premultiplied_alpha;
+        let (batch, texture_index) = match self.pending_batches.last_mut() {
+            Some(batch)
+                if batch.blend_mode == blend_mode
+                    && batch.premultiplied_alpha == premultiplied_alpha
+                    && batch.sampler == sampler =>
+            {
+                match batch.texture_index(texture_id) {
+                    Some(texture_index) => (batch, texture_index),
+                    None => self.push_pending_batch(
+                        texture_id,
+                        blend_mode,
+                        premultiplied_alpha,
+                        sampler,
+                    ),
+                }
             }
+            _ => self.push_pending_batch(texture_id, blend_mode, premultiplied_alpha, sampler),
         };
 
-        #[allow(clippy::cast_precision_loss)]
-        batch.vertices.extend_from_slice(&[
-            Vertex {
-                position: top_left,
-                texture_coordinates: [quad_texture_u / texture_w, quad_texture_v / texture_h],
-            },
-            Vertex {
-                position: bottom_left,
-                texture_coordinates: [
-                    quad_texture_u / texture_w,
-                    (quad_texture_v + quad_texture_h) / texture_h,
-                ],
-            },
-            Vertex {
-                position: bottom_right,
-                texture_coordinates: [
-                    (quad_texture_u + quad_texture_w) / texture_w,
-                    (quad_texture_v + quad_texture_h) / texture_h,
-                ],
-            },
-            Vertex {
-                position: bottom_right,
-                texture_coordinates: [
-                    (quad_texture_u + quad_texture_w) / texture_w,
-                    (quad_texture_v + quad_texture_h) / texture_h,
-                ],
-            },
-            Vertex {
-                position: top_right,
-                texture_coordinates: [
-                    (quad_texture_u + quad_texture_w) / texture_w,
-                    quad_texture_v / texture_h,
+        let (color, shadow) = match &quad.shadow {
+            Some(shadow) => (
+                shadow.color,
+                [
+                    1.0,
+                    if texture_info.premultiplied_alpha {
+                        1.0
+                    } else {
+                        0.0
+                    },
+                    shadow.blur / texture_w,
+                    shadow.blur / texture_h,
                 ],
-            },
-            Vertex {
-                position: top_left,
-                texture_coordinates: [quad_texture_u / texture_w, quad_texture_v / texture_h],
-            },
+            ),
+            None => ([1.0; 4], [0.0; 4]),
+        };
+        let vertex = |position, u: f32, v: f32| Vertex {
+            position,
+            texture_coordinates: [u / texture_w, v / texture_h],
+            color,
+            shadow,
+            texture_index,
+        };
+        let (left, top) = (quad_texture_u, quad_texture_v);
+        let (right, bottom) = (left + quad_texture_w, top + quad_texture_h);
+        batch.vertices.extend_from_slice(&[
+            vertex(top_left, left, top),
+            vertex(bottom_left, left, bottom),
+            vertex(bottom_right, right, bottom),
+            vertex(bottom_right, right, bottom),
+            vertex(top_right, right, top),
+            vertex(top_left, left, top),
         ]);
     }
 
+    fn push_pending_batch(
+        &mut self,
+        texture_id: texture::Id,
+        blend_mode: BlendMode,
+        premultiplied_alpha: bool,
+        sampler: sampler::Descriptor,
+    ) -> (&mut PendingBatch, u32) {
+        let mut batch = PendingBatch::new(blend_mode, premultiplied_alpha, sampler);
+        let texture_index = batch.texture_index(texture_id).unwrap_or_default();
+        self.pending_batches.push(batch);
+        // SAFETY: We just added a batch to the pending batch list
+        let batch = unsafe { self.pending_batches.last_mut().unwrap_unchecked() };
+        (batch, texture_index)
+    }
+
+    /// Requests the mip level of the quad texture matching the size of the
+    /// quad on screen
+    fn request_mip_for_quad(&mut self, quad: &Quad2d, clip_corners: &[Vector3f; 4]) {
+        let (target_width, target_height) = self.target_size;
+        let screen_length = |from: &Vector3f, to: &Vector3f| {
+            Vector3f::new(
+                (to.x - from.x) * target_width / 2.0,
+                (to.y - from.y) * target_height / 2.0,
+                0.0,
+            )
+            .norm()
+        };
+        let [top_left, bottom_left, _, top_right] = clip_corners;
+        let mip_level = streaming::mip_level_for_screen_size(
+            quad.texture_rect.width,
+            screen_length(top_left, top_right),
+        )
+        .min(streaming::mip_level_for_screen_size(
+            quad.texture_rect.height,
+            screen_length(top_left, bottom_left),
+        ));
+        self.requested_mips
+            .entry(quad.texture_id)
+            .and_modify(|requested| *requested = (*requested).min(mip_level))
+            .or_insert(mip_level);
+    }
+
+    fn write_pass_uniform(&mut self, gfx: &GraphicsState, buffer_pool: &mut FrameBufferPool) {
+        let pass_uniform = buffer_pool.write_uniform(
+            gfx.device(),
+            gfx.queue(),
+            bytemuck::cast_slice(&[PassUniform::new(&self.view_proj)]),
+        );
+        self.pass_uniform_bind_group =
+            Some(gfx.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("pass_uniform_bind_group"),
+                layout: &self.pass_uniform_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer_pool.binding(pass_uniform),
+                }],
+            }));
+    }
+
+    fn upload_pending_batches(
+        &mut self,
+        gfx: &mut GraphicsState,
+        buffer_pool: &mut FrameBufferPool,
+    ) {
+        let mut vertices = vec![];
+        let mut vertex_count = 0u32;
+        self.batches_metadata.clear();
+        self.texture_bind_groups.clear();
+        let mut bind_group_indices = HashMap::new();
+        for batch in std::mem::take(&mut self.pending_batches) {
+            let start_vertex_index = vertex_count;
+            vertices.extend_from_slice(&batch.vertices);
+            vertex_count += u32::try_from(batch.vertices.len()).unwrap();
+
+            let end_vertex_index = vertex_count;
+            let texture_bind_group = *bind_group_indices
+                .entry((batch.texture_ids.clone(), batch.sampler))
+                .or_insert_with(|| {
+                    self.texture_bind_groups
+                        .push(self.create_texture_bind_group(
+                            gfx,
+                            &batch.texture_ids,
+                            batch.sampler,
+                        ));
+                    self.texture_bind_groups.len() - 1
+                });
+            self.batches_metadata.push(BatchMetadata {
+                start_vertex_index,
+                end_vertex_index,
+                texture_bind_group,
+                blend_mode: batch.blend_mode,
+                premultiplied_alpha: batch.premultiplied_alpha,
+            });
+        }
+        self.vertex_allocation = (!vertices.is_empty()).then(|| {
+            buffer_pool.write_vertices(gfx.device(), gfx.queue(), bytemuck::cast_slice(&vertices))
+        });
+    }
+
     pub fn create_pass_2d_pipeline(
         device: &wgpu::Device,
         bind_group_layouts: &[&wgpu::BindGroupLayout],
         surface_texture_format: wgpu::TextureFormat,
+        blend_mode: BlendMode,
+        premultiplied_alpha: bool,
+        polygon_mode: wgpu::PolygonMode,
     ) -> wgpu::RenderPipeline {
         let shader_module = device.create_shader_module(include_wgsl!("./pass_2d.wgsl"));
 
@@ -240,7 +479,7 @@ impl Pass {
                 front_face: wgpu::FrontFace::Ccw,
                 cull_mode: None,
                 unclipped_depth: false,
-                polygon_mode: wgpu::PolygonMode::Fill,
+                polygon_mode,
                 conservative: false,
             },
             depth_stencil: None,
@@ -254,14 +493,7 @@ impl Pass {
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
                     format: surface_texture_format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::SrcAlpha,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                        alpha: wgpu::BlendComponent::default(),
-                    }),
+                    blend: blend_mode.blend_state(premultiplied_alpha),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -269,128 +501,416 @@ impl Pass {
         })
     }
 
-    fn create_texture_bind_group_for_texture_if_required(
+    fn queue_bitmap_text(
         &mut self,
-        texture: texture::Id,
+        bitmap_text: &BitmapText,
+        transform: &Matrix4f,
+        blend_mode: BlendMode,
+        sampler: sampler::Descriptor,
         gfx: &std::cell::Ref<'_, GraphicsState<'_>>,
     ) {
-        if let std::collections::hash_map::Entry::Vacant(e) =
-            self.texture_bind_groups.entry(texture)
-        {
-            let texture = gfx.texture_cache.get(texture);
-            let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-            let texture_sampler = gfx.device().create_sampler(&wgpu::SamplerDescriptor {
-                label: None,
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Nearest,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Linear,
-                ..Default::default()
-            });
+        let texture_id = bitmap_text.font.texture;
+        if !self.create_texture_view_for_texture_if_required(texture_id, gfx) {
+            return;
+        }
+        let texture_info = gfx.texture_cache.info(texture_id);
+        for (offset, glyph_rect) in bitmap_text.glyphs() {
+            self.queue_quad_2d(
+                &Quad2d {
+                    transform: *transform
+                        * Matrix4f::new_translation(&Vector3f::new(offset, 0.0, 0.0)),
+                    texture_id,
+                    texture_rect: glyph_rect,
+                    blend_mode,
+                    sampler,
+                    shadow: None,
+                },
+                texture_info,
+            );
+        }
+    }
 
-            let texture_bind_group = gfx.device().create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &self.texture_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&texture_view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&texture_sampler),
-                    },
-                ],
-            });
+    fn queue_nine_slice(
+        &mut self,
+        nine_slice: &NineSlice,
+        transform: &Matrix4f,
+        blend_mode: BlendMode,
+        sampler: sampler::Descriptor,
+        gfx: &std::cell::Ref<'_, GraphicsState<'_>>,
+    ) {
+        let texture_id = nine_slice.texture;
+        if !self.create_texture_view_for_texture_if_required(texture_id, gfx) {
+            return;
+        }
+        let texture_info = gfx.texture_cache.info(texture_id);
+        for slice in nine_slice.slices(texture_info) {
+            let scale = Vector3f::new(
+                slice.width / slice.texture_rect.width,
+                slice.height / slice.texture_rect.height,
+                1.0,
+            );
+            self.queue_quad_2d(
+                &Quad2d {
+                    transform: *transform
+                        * Matrix4f::new_translation(&Vector3f::new(slice.x, slice.y, 0.0))
+                        * Matrix4f::new_scale(&scale),
+                    texture_id,
+                    texture_rect: slice.texture_rect,
+                    blend_mode,
+                    sampler,
+                    shadow: None,
+                },
+                texture_info,
+            );
+        }
+    }
 
-            e.insert(texture_bind_group);
+    /// Queues the quad of a sprite, or a magenta checker of the same size if
+    /// the texture of the sprite is missing or its texture rect is invalid
+    fn queue_sprite(
+        &mut self,
+        id: EntityId,
+        texture_id: texture::Id,
+        texture_rect: Option<&texture::Rect>,
+        storage: &Storage,
+        transform: &Matrix4f,
+        gfx: &std::cell::Ref<'_, GraphicsState<'_>>,
+    ) {
+        let validated_rect =
+            validation::validate_texture_rect(&gfx.texture_cache, texture_id, texture_rect);
+        let (texture_id, texture_rect, transform) = match validated_rect {
+            Ok(texture_rect) => {
+                if let Some(mut reported) = storage.resource_mut::<ReportedInvalidSprites>() {
+                    reported.forget(id);
+                }
+                (texture_id, texture_rect, *transform)
+            }
+            Err(reason) => {
+                if let (Some(mut reported), Some(mut events)) = (
+                    storage.resource_mut::<ReportedInvalidSprites>(),
+                    storage.resource_mut::<Events<InvalidSprite>>(),
+                ) {
+                    reported.report(&mut events, id, reason);
+                }
+                let Some(missing_texture_id) = gfx.missing_texture_id else {
+                    return;
+                };
+                #[allow(clippy::cast_precision_loss)]
+                let missing_size = validation::MISSING_TEXTURE_SIZE as f32;
+                let (width, height) = texture_rect
+                    .filter(|rect| rect.width > 0.0 && rect.height > 0.0)
+                    .map_or((missing_size, missing_size), |rect| {
+                        (rect.width, rect.height)
+                    });
+                let scale = Vector3f::new(width / missing_size, height / missing_size, 1.0);
+                (
+                    missing_texture_id,
+                    texture::Rect::new(0.0, 0.0, missing_size, missing_size),
+                    *transform * Matrix4f::new_scale(&scale),
+                )
+            }
+        };
+
+        if !self.create_texture_view_for_texture_if_required(texture_id, gfx) {
+            return;
+        }
+        let texture_info = gfx.texture_cache.info(texture_id);
+        self.queue_quad_2d(
+            &Quad2d {
+                transform,
+                texture_id,
+                texture_rect,
+                blend_mode: entity_blend_mode(storage, gfx, id),
+                sampler: entity_sampler(storage, gfx, id),
+                shadow: None,
+            },
+            texture_info,
+        );
+    }
+
+    /// Queues the level of detail of an entity selected for the camera of the
+    /// pass, drawn with the size of the first level
+    fn queue_lod(
+        &mut self,
+        id: EntityId,
+        lod: &Lod,
+        storage: &Storage,
+        transform: &Matrix4f,
+        gfx: &std::cell::Ref<'_, GraphicsState<'_>>,
+    ) {
+        let sprite_size = |sprite: &Sprite| match &sprite.texture_rect {
+            Some(rect) => (rect.width, rect.height),
+            None if gfx.texture_cache.contains(sprite.texture) => {
+                let info = gfx.texture_cache.info(sprite.texture);
+                #[allow(clippy::cast_precision_loss)]
+                (info.width as f32, info.height as f32)
+            }
+            None => {
+                #[allow(clippy::cast_precision_loss)]
+                let missing_size = validation::MISSING_TEXTURE_SIZE as f32;
+                (missing_size, missing_size)
+            }
+        };
+        let (width, height) = sprite_size(&lod.levels()[0].sprite);
+
+        let value = match lod.metric() {
+            LodMetric::Distance => {
+                let position = transform.transform_vec3(&Vector3f::new(0.0, 0.0, 0.0));
+                Vector3f::new(
+                    position.x - self.view_center.x,
+                    position.y - self.view_center.y,
+                    0.0,
+                )
+                .norm()
+            }
+            LodMetric::ScreenSize => self.screen_size(transform, width, height),
+        };
+        let Some(level) = lod.select_level(self.camera_id, value) else {
+            return;
+        };
+
+        let (level_width, level_height) = sprite_size(&level.sprite);
+        let scale = if level_width > 0.0 && level_height > 0.0 {
+            Vector3f::new(width / level_width, height / level_height, 1.0)
+        } else {
+            Vector3f::new(1.0, 1.0, 1.0)
+        };
+        self.queue_sprite(
+            id,
+            level.sprite.texture,
+            level.sprite.texture_rect.as_ref(),
+            storage,
+            &(*transform * Matrix4f::new_scale(&scale)),
+            gfx,
+        );
+    }
+
+    /// Returns the largest side in pixels of a quad of the given size once
+    /// drawn by the camera of the pass
+    fn screen_size(&self, transform: &Matrix4f, width: f32, height: f32) -> f32 {
+        let (target_width, target_height) = self.target_size;
+        let [top_left, bottom_left, top_right] = [
+            Vector3f::new(0.0, 0.0, 0.0),
+            Vector3f::new(0.0, height, 0.0),
+            Vector3f::new(width, 0.0, 0.0),
+        ]
+        .map(|corner| {
+            self.view_proj
+                .transform_vec3(&transform.transform_vec3(&corner))
+        });
+        let screen_length = |to: &Vector3f| {
+            Vector3f::new(
+                (to.x - top_left.x) * target_width / 2.0,
+                (to.y - top_left.y) * target_height / 2.0,
+                0.0,
+            )
+            .norm()
+        };
+        screen_length(&top_right).max(screen_length(&bottom_left))
+    }
+
+    fn queue_drop_shadows(
+        &mut self,
+        storage: &Storage,
+        transform_cache: &TransformCache,
+        gfx: &std::cell::Ref<'_, GraphicsState<'_>>,
+    ) {
+        let sprites = storage
+            .query::<(&DropShadow, &Sprite)>()
+            .iter_with_ids()
+            .map(|(id, (shadow, sprite))| (id, shadow, sprite.texture, sprite.texture_rect.clone()))
+            .collect::<Vec<_>>();
+        let animated_sprites = storage
+            .query::<(&DropShadow, &AnimatedSprite)>()
+            .iter_with_ids()
+            .map(|(id, (shadow, animated_sprite))| {
+                let animation = &animated_sprite.animation;
+                let rect = animation.animations[animation.current_animation]
+                    [animation.current_frame]
+                    .clone();
+                (id, shadow, animated_sprite.texture_atlas, Some(rect))
+            })
+            .collect::<Vec<_>>();
+
+        for (id, shadow, texture_id, texture_rect) in sprites.into_iter().chain(animated_sprites) {
+            // Invalid sprites are drawn with the missing texture, without shadow
+            let Ok(texture_rect) = validation::validate_texture_rect(
+                &gfx.texture_cache,
+                texture_id,
+                texture_rect.as_ref(),
+            ) else {
+                continue;
+            };
+            if !self.create_texture_view_for_texture_if_required(texture_id, gfx) {
+                continue;
+            }
+            let texture_info = gfx.texture_cache.info(texture_id);
+            self.queue_quad_2d(
+                &Quad2d {
+                    transform: shadow.transform(&transform_cache.get(id), texture_rect.height),
+                    texture_id,
+                    texture_rect,
+                    blend_mode: entity_blend_mode(storage, gfx, id),
+                    sampler: entity_sampler(storage, gfx, id),
+                    shadow: Some(QuadShadow {
+                        color: shadow.rgba(),
+                        blur: shadow.blur,
+                    }),
+                },
+                texture_info,
+            );
+        }
+    }
+
+    /// Returns false if the texture isn't loaded, e.g. after it has been
+    /// evicted, in which case nothing must be drawn with it
+    fn create_texture_view_for_texture_if_required(
+        &mut self,
+        texture: texture::Id,
+        gfx: &std::cell::Ref<'_, GraphicsState<'_>>,
+    ) -> bool {
+        if !gfx.texture_cache.contains(texture) {
+            return false;
         }
+        self.texture_views.entry(texture).or_insert_with(|| {
+            gfx.texture_cache
+                .get(texture)
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+        true
+    }
+
+    /// Creates the bind group of the textures of a batch, the unused slots
+    /// being bound to its first texture. The sampler is shared through the
+    /// sampler cache.
+    fn create_texture_bind_group(
+        &self,
+        gfx: &mut GraphicsState,
+        texture_ids: &[texture::Id],
+        sampler: sampler::Descriptor,
+    ) -> wgpu::BindGroup {
+        let device = &gfx.wgpu_state.device;
+        let sampler = gfx.sampler_cache.get_or_create(device, sampler);
+        let mut entries = (0..MAX_BATCH_TEXTURES)
+            .map(|slot| {
+                let texture_id = texture_ids.get(slot).unwrap_or(&texture_ids[0]);
+                wgpu::BindGroupEntry {
+                    binding: u32::try_from(slot).unwrap(),
+                    resource: wgpu::BindingResource::TextureView(&self.texture_views[texture_id]),
+                }
+            })
+            .collect::<Vec<_>>();
+        entries.push(wgpu::BindGroupEntry {
+            binding: u32::try_from(MAX_BATCH_TEXTURES).unwrap(),
+            resource: wgpu::BindingResource::Sampler(sampler),
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("pass_2d_texture_bind_group"),
+            layout: &self.texture_bind_group_layout,
+            entries: &entries,
+        })
     }
 }
 
 impl RenderPass for Pass {
+    fn draw_stats(&self) -> DrawStats {
+        DrawStats {
+            draw_calls: u32::try_from(self.batches_metadata.len()).unwrap(),
+            vertex_count: self
+                .batches_metadata
+                .last()
+                .map_or(0, |batch| batch.end_vertex_index),
+        }
+    }
+
     fn prepare(&mut self, storage: &Storage) {
         let gfx = storage
             .resource::<GraphicsState>()
             .expect("Graphics state should be present");
 
-        let (camera_id, (camera, _)) = storage
-            .query::<(&camera::D2, &camera::Active)>()
-            .iter_with_ids()
-            .next()
-            .expect("An active 2d camera should be present in the scene");
+        let camera_id = self.camera_id;
+        let camera = storage
+            .component::<camera::D2>(camera_id)
+            .expect("The camera of the 2d pass should be present in the scene");
 
         let transform_cache = storage
             .resource::<TransformCache>()
             .expect("TransformCache resource should be present");
         let camera_transform = transform_cache.get(camera_id);
         let inverse_transform = camera_transform.try_inverse().unwrap();
-        gfx.queue().write_buffer(
-            &self.pass_uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[PassUniform {
-                view_proj: (*camera.projection() * inverse_transform).into(),
-            }]),
-        );
+        self.view_proj = *camera.projection() * inverse_transform;
+        self.view_center = self
+            .view_proj
+            .try_inverse()
+            .unwrap()
+            .transform_vec3(&Vector3f::new(0.0, 0.0, 0.0));
+        self.target_size = target_size(&gfx, storage.component::<camera::Viewport>(camera_id));
+        let mut buffer_pool = storage
+            .resource_mut::<FrameBufferPool>()
+            .expect("FrameBufferPool resource should be present");
+        self.write_pass_uniform(&gfx, &mut buffer_pool);
+        // Shadows are queued first to be drawn beneath every sprite
+        self.queue_drop_shadows(storage, &transform_cache, &gfx);
 
         for (id, sprite) in storage.query::<&Sprite>().iter_with_ids() {
-            self.create_texture_bind_group_for_texture_if_required(sprite.texture, &gfx);
-            let texture_info = gfx.texture_cache.info(sprite.texture);
-            #[allow(clippy::cast_precision_loss)]
-            self.queue_quad_2d(
-                &Quad2d {
-                    transform: transform_cache.get(id),
-                    texture_id: sprite.texture,
-                    texture_rect: sprite.texture_rect.clone().unwrap_or(texture::Rect {
-                        x: 0.0,
-                        y: 0.0,
-                        width: texture_info.width as f32,
-                        height: texture_info.height as f32,
-                    }),
-                },
-                texture_info,
+            self.queue_sprite(
+                id,
+                sprite.texture,
+                sprite.texture_rect.as_ref(),
+                storage,
+                &transform_cache.get(id),
+                &gfx,
             );
         }
 
         for (id, animated_sprite) in storage.query::<&AnimatedSprite>().iter_with_ids() {
-            self.create_texture_bind_group_for_texture_if_required(
+            let animation = &animated_sprite.animation;
+            let rect = &animation.animations[animation.current_animation][animation.current_frame];
+            self.queue_sprite(
+                id,
                 animated_sprite.texture_atlas,
+                Some(rect),
+                storage,
+                &transform_cache.get(id),
                 &gfx,
             );
-            let texture_info = gfx.texture_cache.info(animated_sprite.texture_atlas);
-            let animation = &animated_sprite.animation;
-            let rect =
-                animation.animations[animation.current_animation][animation.current_frame].clone();
-            #[allow(clippy::cast_precision_loss)]
-            self.queue_quad_2d(
-                &Quad2d {
-                    transform: transform_cache.get(id),
-                    texture_id: animated_sprite.texture_atlas,
-                    texture_rect: rect,
-                },
-                texture_info,
+        }
+
+        for (id, lod) in storage.query::<&Lod>().iter_with_ids() {
+            self.queue_lod(id, lod, storage, &transform_cache.get(id), &gfx);
+        }
+
+        for (id, nine_slice) in storage.query::<&NineSlice>().iter_with_ids() {
+            self.queue_nine_slice(
+                nine_slice,
+                &transform_cache.get(id),
+                entity_blend_mode(storage, &gfx, id),
+                entity_sampler(storage, &gfx, id),
+                &gfx,
             );
         }
 
-        let mut vertex_count = 0u32;
-        self.batches_metadata.clear();
-        for batch in self.pending_batches.drain(..) {
-            let start_vertex_index = vertex_count;
-            gfx.wgpu_state.queue.write_buffer(
-                &self.vertex_buffer,
-                (vertex_count as usize * std::mem::size_of::<Vertex>()) as wgpu::BufferAddress,
-                bytemuck::cast_slice(&batch.vertices),
+        for (id, bitmap_text) in storage.query::<&BitmapText>().iter_with_ids() {
+            self.queue_bitmap_text(
+                bitmap_text,
+                &transform_cache.get(id),
+                entity_blend_mode(storage, &gfx, id),
+                entity_sampler(storage, &gfx, id),
+                &gfx,
             );
-            vertex_count += u32::try_from(batch.vertices.len()).unwrap();
+        }
 
-            let end_vertex_index = vertex_count;
-            self.batches_metadata.push(BatchMetadata {
-                start_vertex_index,
-                end_vertex_index,
-                texture_id: batch.texture_id,
-            });
+        // The samplers of the batches may have to be created
+        std::mem::drop(gfx);
+        let mut gfx = storage
+            .resource_mut::<GraphicsState>()
+            .expect("Graphics state should be present");
+        self.upload_pending_batches(&mut gfx, &mut buffer_pool);
+
+        if let Some(mut texture_streaming) = storage.resource_mut::<TextureStreaming>() {
+            for (texture_id, mip_level) in self.requested_mips.drain() {
+                texture_streaming.request_mip(texture_id, mip_level);
+            }
         }
     }
 
@@ -401,19 +921,42 @@ impl RenderPass for Pass {
         surface_texture_view: &wgpu::TextureView,
         storage: &Storage,
     ) {
+        let (Some(pass_uniform_bind_group), Some(vertex_allocation)) =
+            (&self.pass_uniform_bind_group, self.vertex_allocation)
+        else {
+            return;
+        };
+        let buffer_pool = storage.resource::<FrameBufferPool>().unwrap();
+        let polygon_mode = storage
+            .resource::<RenderDebugMode>()
+            .map_or(wgpu::PolygonMode::Fill, |debug_mode| {
+                debug_mode.polygon_mode(gfx.wireframe_supported())
+            });
+        let surface_texture_format = gfx.surface_texture_format();
         let mut pipeline_cache = storage.resource_mut::<PipelineCache>().unwrap();
-        if !pipeline_cache.has("pass_2d_pipeline") {
-            pipeline_cache.insert(
-                "pass_2d_pipeline",
-                Self::create_pass_2d_pipeline(
-                    gfx.device(),
-                    &[
-                        &self.pass_uniform_bind_group_layout,
-                        &self.texture_bind_group_layout,
-                    ],
-                    gfx.surface_texture_format(),
-                ),
+        for batch in &self.batches_metadata {
+            let identifier = pipeline_identifier(
+                batch.blend_mode,
+                batch.premultiplied_alpha,
+                polygon_mode,
+                surface_texture_format,
             );
+            if !pipeline_cache.has(&identifier) {
+                pipeline_cache.insert(
+                    &identifier,
+                    Self::create_pass_2d_pipeline(
+                        gfx.device(),
+                        &[
+                            &self.pass_uniform_bind_group_layout,
+                            &self.texture_bind_group_layout,
+                        ],
+                        surface_texture_format,
+                        batch.blend_mode,
+                        batch.premultiplied_alpha,
+                        polygon_mode,
+                    ),
+                );
+            }
         }
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("pass_2d"),
@@ -430,11 +973,36 @@ impl RenderPass for Pass {
             occlusion_query_set: None,
         });
 
-        rpass.set_pipeline(pipeline_cache.get("pass_2d_pipeline").unwrap());
-        rpass.set_bind_group(0, &self.pass_uniform_bind_group, &[]);
+        if let Some(viewport) = storage.component::<camera::Viewport>(self.camera_id) {
+            let window_size = gfx.window_size();
+            let (x, y, width, height) = viewport.to_physical(window_size.width, window_size.height);
+            if width == 0 || height == 0 {
+                return;
+            }
+            #[allow(clippy::cast_precision_loss)]
+            rpass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            rpass.set_scissor_rect(x, y, width, height);
+        }
+
+        rpass.set_bind_group(0, pass_uniform_bind_group, &[]);
+        rpass.set_vertex_buffer(0, buffer_pool.slice(vertex_allocation));
+        let mut current_pipeline = None;
         for batch in &self.batches_metadata {
-            rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            let texture_bind_group = &self.texture_bind_groups[&batch.texture_id];
+            let pipeline = Some((batch.blend_mode, batch.premultiplied_alpha));
+            if current_pipeline != pipeline {
+                rpass.set_pipeline(
+                    pipeline_cache
+                        .get(&pipeline_identifier(
+                            batch.blend_mode,
+                            batch.premultiplied_alpha,
+                            polygon_mode,
+                            surface_texture_format,
+                        ))
+                        .unwrap(),
+                );
+                current_pipeline = pipeline;
+            }
+            let texture_bind_group = &self.texture_bind_groups[batch.texture_bind_group];
             rpass.set_bind_group(1, texture_bind_group, &[]);
             rpass.draw(batch.start_vertex_index..batch.end_vertex_index, 0..1);
         }
@@ -446,11 +1014,45 @@ pub(crate) fn add_pass_system(
     mut graph: ResMut<RenderGraph>,
     mut query_camera: Q<(&camera::D2, &camera::Active)>,
 ) {
-    // Don't add a 2D pass if there is no 2D camera in the scene
-    if query_camera.iter().next().is_none() {
-        return;
+    // A 2D pass is added for every active 2D camera in the scene, each one
+    // rendering to the viewport of its camera
+    for (camera_id, _) in query_camera.iter_with_ids() {
+        graph.add_pass(Pass::new(&gfx.wgpu_state.device, camera_id));
     }
-
-    graph.add_pass(Pass::new(&gfx.wgpu_state.device));
     std::mem::drop(gfx);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad_corners(x: f32, y: f32, size: f32) -> [Vector3f; 4] {
+        [
+            Vector3f::new(x, y, 0.0),
+            Vector3f::new(x, y + size, 0.0),
+            Vector3f::new(x + size, y + size, 0.0),
+            Vector3f::new(x + size, y, 0.0),
+        ]
+    }
+
+    #[test]
+    fn quads_outside_view_are_culled() {
+        assert!(!is_outside_view(&quad_corners(-0.5, -0.5, 1.0)));
+        // Partially visible quads are kept
+        assert!(!is_outside_view(&quad_corners(0.5, 0.5, 1.0)));
+        // Quads straddling the view without any corner inside are kept
+        assert!(!is_outside_view(&quad_corners(-2.0, -2.0, 4.0)));
+        assert!(is_outside_view(&quad_corners(1.5, 0.0, 1.0)));
+        assert!(is_outside_view(&quad_corners(0.0, -3.0, 1.0)));
+    }
+
+    #[test]
+    fn pipelines_are_cached_per_surface_format() {
+        let identifier =
+            |format| pipeline_identifier(BlendMode::Alpha, false, wgpu::PolygonMode::Fill, format);
+        assert_ne!(
+            identifier(wgpu::TextureFormat::Bgra8UnormSrgb),
+            identifier(wgpu::TextureFormat::Rgba8UnormSrgb)
+        );
+    }
+}
@@ -3,11 +3,17 @@
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) texture_coordinates: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) shadow: vec4<f32>,
+    @location(4) texture_index: u32,
 }
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
-    @location(0) texture_coordinates: vec2<f32>
+    @location(0) texture_coordinates: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) shadow: vec4<f32>,
+    @location(3) @interpolate(flat) texture_index: u32,
 }
 
 struct PassUniform {
@@ -18,20 +24,85 @@ struct PassUniform {
 var<uniform> u_pass: PassUniform;
 
 @group(1) @binding(0)
-var t_base_color: texture_2d<f32>;
+var t_base_color_0: texture_2d<f32>;
 @group(1) @binding(1)
+var t_base_color_1: texture_2d<f32>;
+@group(1) @binding(2)
+var t_base_color_2: texture_2d<f32>;
+@group(1) @binding(3)
+var t_base_color_3: texture_2d<f32>;
+@group(1) @binding(4)
+var t_base_color_4: texture_2d<f32>;
+@group(1) @binding(5)
+var t_base_color_5: texture_2d<f32>;
+@group(1) @binding(6)
+var t_base_color_6: texture_2d<f32>;
+@group(1) @binding(7)
+var t_base_color_7: texture_2d<f32>;
+@group(1) @binding(8)
 var s_base_color: sampler;
 
+// Samples the texture of the batch at `index`, with explicit gradients as the
+// index varies between the quads of a batch
+fn sample_base_color(index: u32, uv: vec2<f32>, ddx: vec2<f32>, ddy: vec2<f32>) -> vec4<f32> {
+    switch index {
+        case 1u: { return textureSampleGrad(t_base_color_1, s_base_color, uv, ddx, ddy); }
+        case 2u: { return textureSampleGrad(t_base_color_2, s_base_color, uv, ddx, ddy); }
+        case 3u: { return textureSampleGrad(t_base_color_3, s_base_color, uv, ddx, ddy); }
+        case 4u: { return textureSampleGrad(t_base_color_4, s_base_color, uv, ddx, ddy); }
+        case 5u: { return textureSampleGrad(t_base_color_5, s_base_color, uv, ddx, ddy); }
+        case 6u: { return textureSampleGrad(t_base_color_6, s_base_color, uv, ddx, ddy); }
+        case 7u: { return textureSampleGrad(t_base_color_7, s_base_color, uv, ddx, ddy); }
+        default: { return textureSampleGrad(t_base_color_0, s_base_color, uv, ddx, ddy); }
+    }
+}
+
+fn sample_base_color_level(index: u32, uv: vec2<f32>) -> vec4<f32> {
+    switch index {
+        case 1u: { return textureSampleLevel(t_base_color_1, s_base_color, uv, 0.0); }
+        case 2u: { return textureSampleLevel(t_base_color_2, s_base_color, uv, 0.0); }
+        case 3u: { return textureSampleLevel(t_base_color_3, s_base_color, uv, 0.0); }
+        case 4u: { return textureSampleLevel(t_base_color_4, s_base_color, uv, 0.0); }
+        case 5u: { return textureSampleLevel(t_base_color_5, s_base_color, uv, 0.0); }
+        case 6u: { return textureSampleLevel(t_base_color_6, s_base_color, uv, 0.0); }
+        case 7u: { return textureSampleLevel(t_base_color_7, s_base_color, uv, 0.0); }
+        default: { return textureSampleLevel(t_base_color_0, s_base_color, uv, 0.0); }
+    }
+}
+
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
     out.position = u_pass.view_proj * vec4<f32>(in.position, 1.0);
     out.texture_coordinates = in.texture_coordinates;
+    out.color = in.color;
+    out.shadow = in.shadow;
+    out.texture_index = in.texture_index;
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let sample = textureSample(t_base_color, s_base_color, in.texture_coordinates);
-    return sample;
+    let ddx = dpdx(in.texture_coordinates);
+    let ddy = dpdy(in.texture_coordinates);
+    var sample = sample_base_color(in.texture_index, in.texture_coordinates, ddx, ddy);
+    let blur_radius = in.shadow.zw;
+    if blur_radius.x > 0.0 || blur_radius.y > 0.0 {
+        // 3x3 box blur, sampled explicitly as the control flow isn't uniform
+        var sum = vec4<f32>(0.0);
+        for (var x = -1; x <= 1; x++) {
+            for (var y = -1; y <= 1; y++) {
+                let offset = vec2<f32>(f32(x), f32(y)) * blur_radius;
+                sum += sample_base_color_level(in.texture_index, in.texture_coordinates + offset);
+            }
+        }
+        sample = sum / 9.0;
+    }
+
+    if in.shadow.x > 0.0 {
+        let alpha = sample.a * in.color.a;
+        let premultiplied = in.shadow.y > 0.0;
+        return vec4<f32>(in.color.rgb * select(1.0, alpha, premultiplied), alpha);
+    }
+    return sample * in.color;
 }
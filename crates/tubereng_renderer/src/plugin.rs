@@ -0,0 +1,38 @@
+use tubereng_ecs::{
+    system::{Res, ResMut},
+    Storage,
+};
+
+use crate::{render_graph::RenderGraph, GraphicsState, PipelineCache};
+
+/// Extends the renderer with custom render passes and pipelines, registered
+/// with [`crate::RendererBuilder::with_plugin`]
+pub trait RendererPlugin {
+    /// Called when the renderer is initialized, typically to create the
+    /// pipelines used by the passes of the plugin. Called again after the
+    /// device has been lost, once the pipeline cache has been cleared.
+    fn setup(&self, _gfx: &mut GraphicsState, _pipelines: &mut PipelineCache) {}
+
+    /// Called every frame to add the passes of the plugin to the render graph,
+    /// after the built-in passes
+    fn add_passes(&self, gfx: &GraphicsState, graph: &mut RenderGraph, storage: &Storage);
+}
+
+/// Resource holding the plugins registered to the renderer
+#[derive(Default)]
+pub(crate) struct RendererPlugins {
+    pub(crate) plugins: Vec<Box<dyn RendererPlugin>>,
+}
+
+pub(crate) fn add_plugin_passes_system(
+    gfx: Res<GraphicsState>,
+    mut graph: ResMut<RenderGraph>,
+    plugins: Res<RendererPlugins>,
+    storage: &Storage,
+) {
+    for plugin in &plugins.plugins {
+        plugin.add_passes(&gfx, &mut graph, storage);
+    }
+    std::mem::drop(gfx);
+    std::mem::drop(plugins);
+}
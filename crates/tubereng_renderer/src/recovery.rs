@@ -0,0 +1,44 @@
+use tubereng_ecs::event::EventWriter;
+use tubereng_ecs::system::{Res, ResMut};
+
+use crate::{buffer_pool::FrameBufferPool, plugin::RendererPlugins, GraphicsState, PipelineCache};
+
+/// Event emitted when the GPU device has been lost and recreated. Textures
+/// and materials loaded with the `GraphicsState` are restored, GPU resources
+/// created directly with the lost device must be created again.
+#[derive(Debug, Clone)]
+pub struct DeviceRecovered {
+    pub reason: String,
+}
+
+/// Recreates the device and the resources of the renderer when the device
+/// has been lost, e.g. after a driver update or a GPU reset
+pub(crate) fn recover_lost_device_system(
+    mut gfx: ResMut<GraphicsState>,
+    mut pipeline_cache: ResMut<PipelineCache>,
+    mut buffer_pool: ResMut<FrameBufferPool>,
+    plugins: Res<RendererPlugins>,
+    mut events: EventWriter<DeviceRecovered>,
+) {
+    if !gfx.is_device_lost() {
+        return;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let reason = pollster::block_on(gfx.recover_lost_device());
+    #[cfg(target_arch = "wasm32")]
+    let reason = {
+        // The device can't be requested synchronously on the web
+        log::error!("The GPU device has been lost and cannot be recovered");
+        return;
+    };
+    log::info!("The GPU device has been recovered");
+
+    pipeline_cache.clear();
+    **buffer_pool = FrameBufferPool::new(gfx.device());
+    for plugin in &plugins.plugins {
+        plugin.setup(&mut gfx, &mut pipeline_cache);
+    }
+    events.send(DeviceRecovered { reason });
+    std::mem::drop(plugins);
+}
@@ -1,9 +1,91 @@
+use std::collections::HashSet;
+
 use tubereng_ecs::Storage;
 
-use crate::GraphicsState;
+use crate::{
+    stats::{DrawStats, GpuTimer, PassDrawStats},
+    GraphicsState,
+};
+
+/// Pass of the render graph, render and compute passes are executed in the
+/// order they have been added
+enum NodePass {
+    Render(Box<dyn RenderPass>),
+    Compute(Box<dyn ComputePass>),
+}
+
+type Condition = Box<dyn Fn(&Storage) -> bool>;
+
+struct Node {
+    pass: NodePass,
+    condition: Option<Condition>,
+    /// Result of the enable conditions evaluated by the last `prepare`
+    enabled: bool,
+}
+
+impl Node {
+    fn new(pass: NodePass, condition: Option<Condition>) -> Self {
+        Self {
+            pass,
+            condition,
+            enabled: true,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match &self.pass {
+            NodePass::Render(pass) => pass.name(),
+            NodePass::Compute(pass) => pass.name(),
+        }
+    }
+
+    fn is_enabled(&self, storage: &Storage, toggles: Option<&PassToggles>) -> bool {
+        toggles.is_none_or(|toggles| toggles.is_enabled(self.name()))
+            && self
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition(storage))
+            && match &self.pass {
+                NodePass::Render(pass) => pass.is_enabled(storage),
+                NodePass::Compute(pass) => pass.is_enabled(storage),
+            }
+    }
+}
+
+/// Resource enabling and disabling the passes of the render graph by name at
+/// runtime, e.g. to toggle debug overlays or post effects.
+///
+/// Pass names default to the type name of the pass, see [`RenderPass::name`].
+#[derive(Debug, Default)]
+pub struct PassToggles {
+    disabled: HashSet<String>,
+}
+
+impl PassToggles {
+    pub fn enable(&mut self, pass_name: &str) {
+        self.disabled.remove(pass_name);
+    }
+
+    pub fn disable(&mut self, pass_name: &str) {
+        self.disabled.insert(pass_name.to_string());
+    }
+
+    pub fn set_enabled(&mut self, pass_name: &str, enabled: bool) {
+        if enabled {
+            self.enable(pass_name);
+        } else {
+            self.disable(pass_name);
+        }
+    }
+
+    #[must_use]
+    pub fn is_enabled(&self, pass_name: &str) -> bool {
+        !self.disabled.contains(pass_name)
+    }
+}
 
 pub struct RenderGraph {
-    passes: Vec<Box<dyn RenderPass>>,
+    passes: Vec<Node>,
 }
 
 impl RenderGraph {
@@ -20,15 +102,56 @@ impl RenderGraph {
     where
         P: 'static + RenderPass,
     {
-        self.passes.push(Box::new(pass));
+        self.passes
+            .push(Node::new(NodePass::Render(Box::new(pass)), None));
     }
 
+    /// Adds a pass which is only prepared and executed in the frames where
+    /// `condition` returns true, e.g. when a resource flag is set
+    pub fn add_conditional_pass<P, C>(&mut self, pass: P, condition: C)
+    where
+        P: 'static + RenderPass,
+        C: 'static + Fn(&Storage) -> bool,
+    {
+        self.passes.push(Node::new(
+            NodePass::Render(Box::new(pass)),
+            Some(Box::new(condition)),
+        ));
+    }
+
+    pub fn add_compute_pass<P>(&mut self, pass: P)
+    where
+        P: 'static + ComputePass,
+    {
+        self.passes
+            .push(Node::new(NodePass::Compute(Box::new(pass)), None));
+    }
+
+    /// Evaluates the enable conditions of the passes and prepares the enabled
+    /// ones, see [`PassToggles`], [`RenderGraph::add_conditional_pass`] and
+    /// [`RenderPass::is_enabled`]
     pub fn prepare(&mut self, storage: &Storage) {
-        for pass in &mut self.passes {
-            pass.prepare(storage);
+        {
+            let toggles = storage.resource::<PassToggles>();
+            for node in &mut self.passes {
+                node.enabled = node.is_enabled(storage, toggles.as_deref());
+            }
+        }
+
+        for node in self.passes.iter_mut().filter(|node| node.enabled) {
+            match &mut node.pass {
+                NodePass::Render(pass) => pass.prepare(storage),
+                NodePass::Compute(pass) => pass.prepare(storage),
+            }
         }
     }
 
+    /// Executes the passes of the graph. When GPU timing is enabled, each pass
+    /// is surrounded by timestamp queries which are copied into a readback
+    /// buffer of the timer.
+    ///
+    /// Passes are executed in the order they have been added, the history
+    /// targets are swapped once every pass has been executed.
     pub fn execute(
         &self,
         graphics: &mut GraphicsState,
@@ -36,9 +159,64 @@ impl RenderGraph {
         surface_texture_view: &wgpu::TextureView,
         storage: &Storage,
     ) {
-        for pass in &self.passes {
-            pass.execute(graphics, encoder, surface_texture_view, storage);
+        let mut gpu_timer = graphics.gpu_timer.take();
+        let mut timed_pass_count = 0;
+        for node in self.passes.iter().filter(|node| node.enabled) {
+            let timer = gpu_timer
+                .as_ref()
+                .filter(|_| timed_pass_count < GpuTimer::MAX_TIMED_PASSES);
+            if let Some(timer) = timer {
+                timer.begin_pass(encoder, timed_pass_count);
+            }
+
+            match &node.pass {
+                NodePass::Render(pass) => {
+                    pass.execute(graphics, encoder, surface_texture_view, storage);
+                }
+                NodePass::Compute(pass) => pass.execute(graphics, encoder, storage),
+            }
+
+            if let Some(timer) = timer {
+                timer.end_pass(encoder, timed_pass_count);
+                timed_pass_count += 1;
+            }
+        }
+
+        if let Some(timer) = &mut gpu_timer {
+            timer.resolve(
+                encoder,
+                self.pass_names().take(timed_pass_count as usize).collect(),
+            );
         }
+        graphics.gpu_timer = gpu_timer;
+        graphics.history_targets.swap_all();
+    }
+
+    /// Names of the enabled passes, in execution order
+    pub fn pass_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.passes
+            .iter()
+            .filter(|node| node.enabled)
+            .map(Node::name)
+    }
+
+    /// Draw statistics of the enabled render passes, compute passes don't
+    /// draw
+    pub fn pass_draw_stats(&self) -> impl Iterator<Item = PassDrawStats> + '_ {
+        self.passes
+            .iter()
+            .filter(|node| node.enabled)
+            .filter_map(|node| {
+                let NodePass::Render(pass) = &node.pass else {
+                    return None;
+                };
+                let draw_stats = pass.draw_stats();
+                Some(PassDrawStats {
+                    pass_name: pass.name(),
+                    draw_calls: draw_stats.draw_calls,
+                    vertex_count: draw_stats.vertex_count,
+                })
+            })
     }
 }
 
@@ -49,6 +227,23 @@ impl Default for RenderGraph {
 }
 
 pub trait RenderPass {
+    /// Name of the pass, used in the rendering statistics
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Draw calls recorded by the pass during the last `prepare`, reported in
+    /// the rendering statistics
+    fn draw_stats(&self) -> DrawStats {
+        DrawStats::default()
+    }
+
+    /// Condition evaluated every frame before `prepare`, a disabled pass is
+    /// neither prepared nor executed in that frame
+    fn is_enabled(&self, _storage: &Storage) -> bool {
+        true
+    }
+
     fn prepare(&mut self, storage: &Storage);
     fn execute(
         &self,
@@ -59,10 +254,34 @@ pub trait RenderPass {
     );
 }
 
+/// Pass dispatching compute shaders, e.g. to simulate particles or cull
+/// objects on the GPU before the render passes using the results
+pub trait ComputePass {
+    /// Name of the pass, used in the rendering statistics
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Condition evaluated every frame before `prepare`, see
+    /// [`RenderPass::is_enabled`]
+    fn is_enabled(&self, _storage: &Storage) -> bool {
+        true
+    }
+
+    fn prepare(&mut self, storage: &Storage);
+    fn execute(
+        &self,
+        gfx: &mut GraphicsState,
+        encoder: &mut wgpu::CommandEncoder,
+        storage: &Storage,
+    );
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::testing;
 
     struct SomePass;
     impl RenderPass for SomePass {
@@ -83,4 +302,171 @@ mod tests {
         graph.add_pass(SomePass);
         assert_eq!(graph.passes.len(), 1);
     }
+
+    struct OverlayEnabled(bool);
+
+    struct ConditionalPass;
+    impl RenderPass for ConditionalPass {
+        fn is_enabled(&self, storage: &Storage) -> bool {
+            storage
+                .resource::<OverlayEnabled>()
+                .is_some_and(|enabled| enabled.0)
+        }
+
+        fn prepare(&mut self, _storage: &Storage) {}
+        fn execute(
+            &self,
+            _gfx: &mut GraphicsState,
+            _encoder: &mut wgpu::CommandEncoder,
+            _surface_texture_view: &wgpu::TextureView,
+            _storage: &Storage,
+        ) {
+        }
+    }
+
+    #[test]
+    fn disabled_passes_are_skipped() {
+        let mut storage = Storage::new();
+        storage.insert_resource(PassToggles::default());
+        storage.insert_resource(OverlayEnabled(false));
+        let mut graph = RenderGraph::new();
+        graph.add_pass(SomePass);
+        graph.add_pass(ConditionalPass);
+        graph.add_conditional_pass(SomePass, |storage| {
+            storage
+                .resource::<OverlayEnabled>()
+                .is_some_and(|enabled| enabled.0)
+        });
+
+        graph.prepare(&storage);
+        assert_eq!(
+            graph.pass_names().collect::<Vec<_>>(),
+            [std::any::type_name::<SomePass>()]
+        );
+
+        storage.resource_mut::<OverlayEnabled>().unwrap().0 = true;
+        graph.prepare(&storage);
+        assert_eq!(graph.pass_names().count(), 3);
+        assert_eq!(graph.pass_draw_stats().count(), 3);
+
+        storage
+            .resource_mut::<PassToggles>()
+            .unwrap()
+            .disable(std::any::type_name::<SomePass>());
+        graph.prepare(&storage);
+        assert_eq!(
+            graph.pass_names().collect::<Vec<_>>(),
+            [std::any::type_name::<ConditionalPass>()]
+        );
+    }
+
+    struct DoublePass {
+        pipeline: wgpu::ComputePipeline,
+        bind_group: wgpu::BindGroup,
+    }
+    impl ComputePass for DoublePass {
+        fn prepare(&mut self, _storage: &Storage) {}
+        fn execute(
+            &self,
+            _gfx: &mut GraphicsState,
+            encoder: &mut wgpu::CommandEncoder,
+            _storage: &Storage,
+        ) {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("double_pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            compute_pass.dispatch_workgroups(4, 1, 1);
+        }
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_compute_pass() {
+        let mut gfx = testing::headless_graphics_state(4, 4);
+        if gfx.device().limits().max_compute_workgroups_per_dimension == 0 {
+            // Compute shaders aren't supported by the adapter
+            return;
+        }
+        let device = gfx.device();
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(
+                "@group(0) @binding(0) var<storage, read_write> values: array<u32>;
+                @compute @workgroup_size(1)
+                fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+                    values[id.x] = values[id.x] * 2u;
+                }"
+                .into(),
+            ),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &shader_module,
+            entry_point: "cs_main",
+        });
+
+        let values = wgpu::util::DeviceExt::create_buffer_init(
+            device,
+            &wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: bytemuck::cast_slice(&[1u32, 2, 3, 4]),
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            },
+        );
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: values.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: values.as_entire_binding(),
+            }],
+        });
+
+        let mut graph = RenderGraph::new();
+        graph.add_compute_pass(DoublePass {
+            pipeline,
+            bind_group,
+        });
+        let storage = Storage::new();
+        graph.prepare(&storage);
+        let target_texture = gfx.device().create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let target_view = target_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = gfx
+            .device()
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        graph.execute(&mut gfx, &mut encoder, &target_view, &storage);
+        encoder.copy_buffer_to_buffer(&values, 0, &readback, 0, values.size());
+        gfx.queue().submit(std::iter::once(encoder.finish()));
+
+        readback.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        gfx.device().poll(wgpu::Maintain::Wait);
+        let doubled =
+            bytemuck::cast_slice::<u8, u32>(&readback.slice(..).get_mapped_range()).to_vec();
+        assert_eq!(doubled, [2, 4, 6, 8]);
+        assert_eq!(graph.pass_draw_stats().count(), 0);
+        assert_eq!(graph.pass_names().count(), 1);
+    }
 }
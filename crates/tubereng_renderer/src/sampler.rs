@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FilterMode {
+    /// Sharp texels, suited for pixel art
+    #[default]
+    Nearest,
+    /// Smoothly interpolated texels, suited for high resolution art
+    Linear,
+}
+
+impl FilterMode {
+    fn wgpu_filter_mode(self) -> wgpu::FilterMode {
+        match self {
+            FilterMode::Nearest => wgpu::FilterMode::Nearest,
+            FilterMode::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressMode {
+    #[default]
+    ClampToEdge,
+    Repeat,
+    MirrorRepeat,
+}
+
+impl AddressMode {
+    fn wgpu_address_mode(self) -> wgpu::AddressMode {
+        match self {
+            AddressMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            AddressMode::Repeat => wgpu::AddressMode::Repeat,
+            AddressMode::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+/// Describes how a texture is sampled.
+///
+/// The 2D pass samples the textures of an entity with the sampler of its
+/// material, a `Descriptor` can also be attached as a component to override
+/// it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Descriptor {
+    pub filter_mode: FilterMode,
+    pub address_mode: AddressMode,
+    /// Maximum anisotropy level, from 1 (disabled) to 16. Anisotropic
+    /// filtering is only applied with [`FilterMode::Linear`].
+    pub anisotropy: u16,
+}
+
+impl Default for Descriptor {
+    fn default() -> Self {
+        Self {
+            filter_mode: FilterMode::default(),
+            address_mode: AddressMode::default(),
+            anisotropy: 1,
+        }
+    }
+}
+
+impl Descriptor {
+    /// Anisotropy level actually used, wgpu requires every filter to be
+    /// linear when anisotropic filtering is enabled
+    fn effective_anisotropy(self) -> u16 {
+        match self.filter_mode {
+            FilterMode::Nearest => 1,
+            FilterMode::Linear => self.anisotropy.clamp(1, 16),
+        }
+    }
+}
+
+/// Samplers created by the renderer, shared by every material using the same
+/// sampler settings
+#[derive(Default)]
+pub struct Cache {
+    samplers: HashMap<Descriptor, wgpu::Sampler>,
+}
+
+impl Cache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_or_create(
+        &mut self,
+        device: &wgpu::Device,
+        descriptor: Descriptor,
+    ) -> &wgpu::Sampler {
+        self.samplers.entry(descriptor).or_insert_with(|| {
+            let filter_mode = descriptor.filter_mode.wgpu_filter_mode();
+            let address_mode = descriptor.address_mode.wgpu_address_mode();
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                label: None,
+                address_mode_u: address_mode,
+                address_mode_v: address_mode,
+                address_mode_w: address_mode,
+                mag_filter: filter_mode,
+                min_filter: filter_mode,
+                mipmap_filter: filter_mode,
+                anisotropy_clamp: descriptor.effective_anisotropy(),
+                ..Default::default()
+            })
+        })
+    }
+
+    /// Returns the sampler created with these settings, if any
+    #[must_use]
+    pub fn get(&self, descriptor: Descriptor) -> Option<&wgpu::Sampler> {
+        self.samplers.get(&descriptor)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_anisotropy() {
+        let mut descriptor = Descriptor {
+            anisotropy: 8,
+            ..Default::default()
+        };
+        assert_eq!(descriptor.effective_anisotropy(), 1);
+        descriptor.filter_mode = FilterMode::Linear;
+        assert_eq!(descriptor.effective_anisotropy(), 8);
+        descriptor.anisotropy = 64;
+        assert_eq!(descriptor.effective_anisotropy(), 16);
+        descriptor.anisotropy = 0;
+        assert_eq!(descriptor.effective_anisotropy(), 1);
+    }
+}
@@ -0,0 +1,83 @@
+use tubereng_math::{
+    matrix::Matrix4f,
+    vector::{Vector2f, Vector3f},
+};
+
+use crate::Color;
+
+/// Draws a tinted silhouette of the sprite of the entity beneath every sprite
+/// of the scene, giving depth to 2D scenes without a lighting system.
+///
+/// Applies to [`crate::sprite::Sprite`] and [`crate::sprite::AnimatedSprite`].
+/// Shadow blobs under characters can be made by flattening the shadow with
+/// `scale` and softening it with `blur`.
+#[derive(Debug, Clone)]
+pub struct DropShadow {
+    /// Offset of the shadow from the sprite, in world units
+    pub offset: Vector2f,
+    /// Scale of the shadow, anchored at the bottom edge of the sprite
+    pub scale: Vector2f,
+    pub color: Color,
+    pub opacity: f32,
+    /// Blur radius, in texels
+    pub blur: f32,
+}
+
+impl DropShadow {
+    #[must_use]
+    pub fn new(offset: Vector2f) -> Self {
+        Self {
+            offset,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the transform of the shadow of a sprite drawn with `transform`
+    /// whose texture region is `height` texels high
+    pub(crate) fn transform(&self, transform: &Matrix4f, height: f32) -> Matrix4f {
+        Matrix4f::new_translation(&Vector3f::new(self.offset.x, self.offset.y, 0.0))
+            * *transform
+            * Matrix4f::new_translation(&Vector3f::new(0.0, height, 0.0))
+            * Matrix4f::new_scale(&Vector3f::new(self.scale.x, self.scale.y, 1.0))
+            * Matrix4f::new_translation(&Vector3f::new(0.0, -height, 0.0))
+    }
+
+    /// Color of the silhouette, with the opacity as alpha
+    pub(crate) fn rgba(&self) -> [f32; 4] {
+        let [r, g, b]: [f32; 3] = (&self.color).into();
+        [r, g, b, self.opacity]
+    }
+}
+
+impl Default for DropShadow {
+    fn default() -> Self {
+        Self {
+            offset: Vector2f::new(4.0, 4.0),
+            scale: Vector2f::new(1.0, 1.0),
+            color: Color::BLACK,
+            opacity: 0.5,
+            blur: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadow_is_offset_and_scaled_from_the_bottom_edge() {
+        let shadow = DropShadow {
+            offset: Vector2f::new(2.0, 3.0),
+            scale: Vector2f::new(1.0, 0.5),
+            ..Default::default()
+        };
+        let transform = Matrix4f::new_translation(&Vector3f::new(10.0, 20.0, 0.0));
+        let shadow_transform = shadow.transform(&transform, 8.0);
+
+        let top_left = shadow_transform.transform_vec3(&Vector3f::new(0.0, 0.0, 0.0));
+        let bottom_left = shadow_transform.transform_vec3(&Vector3f::new(0.0, 8.0, 0.0));
+        assert_eq!(bottom_left, Vector3f::new(12.0, 31.0, 0.0));
+        assert_eq!(top_left, Vector3f::new(12.0, 27.0, 0.0));
+    }
+}
@@ -1,17 +1,42 @@
-use tubereng_core::DeltaTime;
-use tubereng_ecs::system::{Res, Q};
+use tubereng_core::{time::Time, Transform};
+use tubereng_ecs::{
+    system::{Res, Q},
+    Bundle,
+};
 
 use crate::texture;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Sprite {
     pub texture: texture::Id,
     pub texture_rect: Option<texture::Rect>,
 }
 
-#[derive(Debug)]
+/// Components of an entity drawn with a sprite
+#[derive(Debug, Bundle)]
+#[allow(clippy::module_name_repetitions)]
+pub struct SpriteBundle {
+    pub transform: Transform,
+    pub sprite: Sprite,
+}
+
+/// Components of an entity drawn with an animated sprite
+#[derive(Debug, Bundle)]
+#[allow(clippy::module_name_repetitions)]
+pub struct AnimatedSpriteBundle {
+    pub transform: Transform,
+    pub animated_sprite: AnimatedSprite,
+}
+
+#[derive(Debug, Clone)]
 pub struct AnimationState {
     pub animations: Vec<Vec<texture::Rect>>,
+    /// Names of the animations, e.g. the tags of an Aseprite file, see
+    /// [`AnimationState::play`]
+    pub animation_names: Vec<String>,
+    /// Duration in seconds of each frame of each animation, `secs_per_frame`
+    /// being used for the animations without durations
+    pub frame_durations: Vec<Vec<f32>>,
     pub current_animation: usize,
     pub current_frame: usize,
     pub secs_per_frame: f32,
@@ -22,6 +47,8 @@ impl Default for AnimationState {
     fn default() -> Self {
         Self {
             animations: vec![],
+            animation_names: vec![],
+            frame_durations: vec![],
             current_animation: 0,
             current_frame: 0,
             secs_per_frame: 1.0,
@@ -30,28 +57,102 @@ impl Default for AnimationState {
     }
 }
 
-#[derive(Debug)]
+impl AnimationState {
+    /// Returns the duration in seconds of the current frame
+    #[must_use]
+    pub fn frame_duration(&self) -> f32 {
+        self.frame_durations
+            .get(self.current_animation)
+            .and_then(|durations| durations.get(self.current_frame))
+            .copied()
+            .unwrap_or(self.secs_per_frame)
+    }
+
+    /// Plays the animation named `name` from its first frame, unless it is
+    /// already playing. Returns false if there is no animation with this
+    /// name.
+    pub fn play(&mut self, name: &str) -> bool {
+        let Some(animation) = self.animation_names.iter().position(|n| n == name) else {
+            return false;
+        };
+        if animation != self.current_animation {
+            self.current_animation = animation;
+            self.current_frame = 0;
+            self.ticks = 0.0;
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone)]
 #[allow(clippy::module_name_repetitions)]
 pub struct AnimatedSprite {
     pub texture_atlas: texture::Id,
     pub animation: AnimationState,
 }
 
-pub fn animate_sprite_system(
-    delta_time: Res<DeltaTime>,
-    mut query_animated_sprite: Q<&mut AnimatedSprite>,
-) {
-    let now = delta_time.0;
+pub fn animate_sprite_system(time: Res<Time>, mut query_animated_sprite: Q<&mut AnimatedSprite>) {
+    let now = time.delta();
     for mut sprite in query_animated_sprite.iter() {
         sprite.animation.ticks += now;
-        if sprite.animation.ticks > sprite.animation.secs_per_frame {
+        let frame_duration = sprite.animation.frame_duration();
+        if sprite.animation.ticks > frame_duration {
             let animation_frame_count =
                 sprite.animation.animations[sprite.animation.current_animation].len();
-            sprite.animation.ticks -= sprite.animation.secs_per_frame;
+            sprite.animation.ticks -= frame_duration;
             sprite.animation.current_frame =
                 (sprite.animation.current_frame + 1) % animation_frame_count;
         }
     }
 
-    std::mem::drop(delta_time);
+    std::mem::drop(time);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk_and_jump() -> AnimationState {
+        AnimationState {
+            animations: vec![
+                vec![
+                    texture::Rect::new(0.0, 0.0, 16.0, 16.0),
+                    texture::Rect::new(16.0, 0.0, 16.0, 16.0),
+                ],
+                vec![texture::Rect::new(32.0, 0.0, 16.0, 16.0)],
+            ],
+            animation_names: vec!["walk".into(), "jump".into()],
+            frame_durations: vec![vec![0.1, 0.25]],
+            secs_per_frame: 0.5,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn frame_duration_falls_back_to_secs_per_frame() {
+        let mut animation = walk_and_jump();
+        assert!((animation.frame_duration() - 0.1).abs() < f32::EPSILON);
+        animation.current_frame = 1;
+        assert!((animation.frame_duration() - 0.25).abs() < f32::EPSILON);
+        animation.current_animation = 1;
+        animation.current_frame = 0;
+        assert!((animation.frame_duration() - 0.5).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn play_animation_by_name() {
+        let mut animation = walk_and_jump();
+        animation.current_frame = 1;
+        animation.ticks = 0.05;
+        assert!(animation.play("walk"));
+        assert_eq!(animation.current_frame, 1);
+
+        assert!(animation.play("jump"));
+        assert_eq!(animation.current_animation, 1);
+        assert_eq!(animation.current_frame, 0);
+        assert!(animation.ticks.abs() < f32::EPSILON);
+
+        assert!(!animation.play("run"));
+        assert_eq!(animation.current_animation, 1);
+    }
 }
@@ -0,0 +1,265 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// GPU time spent executing a render pass
+#[derive(Debug, Clone)]
+pub struct PassTiming {
+    pub pass_name: &'static str,
+    pub gpu_time_ms: f32,
+}
+
+/// Draw calls recorded by a render pass
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DrawStats {
+    pub draw_calls: u32,
+    pub vertex_count: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PassDrawStats {
+    pub pass_name: &'static str,
+    pub draw_calls: u32,
+    pub vertex_count: u32,
+}
+
+/// Rendering statistics of the last rendered frame
+#[derive(Debug, Default)]
+pub struct RenderStats {
+    pub(crate) pass_timings: Vec<PassTiming>,
+    pub(crate) pass_draw_stats: Vec<PassDrawStats>,
+}
+
+impl RenderStats {
+    /// GPU time of each render pass of a recent frame, empty unless GPU timing
+    /// has been enabled with `GraphicsState::set_gpu_timing_enabled`.
+    ///
+    /// The timestamps are read back without waiting for the GPU, so the
+    /// timings lag a few frames behind the draw statistics.
+    #[must_use]
+    pub fn pass_timings(&self) -> &[PassTiming] {
+        &self.pass_timings
+    }
+
+    #[must_use]
+    pub fn total_gpu_time_ms(&self) -> f32 {
+        self.pass_timings.iter().map(|t| t.gpu_time_ms).sum()
+    }
+
+    /// Draw calls and vertices of each render pass of the last frame
+    #[must_use]
+    pub fn pass_draw_stats(&self) -> &[PassDrawStats] {
+        &self.pass_draw_stats
+    }
+
+    #[must_use]
+    pub fn total_draw_calls(&self) -> u32 {
+        self.pass_draw_stats.iter().map(|s| s.draw_calls).sum()
+    }
+}
+
+/// Features required to time render passes with timestamp queries
+pub(crate) const GPU_TIMING_FEATURES: wgpu::Features = wgpu::Features::TIMESTAMP_QUERY;
+
+/// Writes timestamps before and after each render pass of the graph
+///
+/// The timestamps are copied into one of several readback buffers which are
+/// mapped asynchronously, so the timings of a frame are only available a few
+/// frames later and reading them never stalls on the GPU.
+pub(crate) struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readbacks: Vec<Readback>,
+    frame: u64,
+}
+
+struct Readback {
+    buffer: wgpu::Buffer,
+    state: ReadbackState,
+}
+
+enum ReadbackState {
+    Free,
+    /// The timestamps of a frame have been copied by a command encoder which
+    /// hasn't been submitted yet
+    Copied {
+        frame: u64,
+        pass_names: Vec<&'static str>,
+    },
+    /// The buffer is being mapped, `mapped` is set once it can be read
+    Mapping {
+        frame: u64,
+        pass_names: Vec<&'static str>,
+        mapped: Arc<AtomicBool>,
+    },
+}
+
+impl GpuTimer {
+    pub(crate) const MAX_TIMED_PASSES: u32 = 64;
+    const QUERY_COUNT: u32 = Self::MAX_TIMED_PASSES * 2;
+    const BUFFER_SIZE: u64 = Self::QUERY_COUNT as u64 * std::mem::size_of::<u64>() as u64;
+    const READBACK_BUFFER_COUNT: usize = 3;
+
+    pub(crate) fn new(device: &wgpu::Device) -> Self {
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("gpu_timer_query_set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: Self::QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu_timer_resolve_buffer"),
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readbacks = (0..Self::READBACK_BUFFER_COUNT)
+            .map(|_| Readback {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("gpu_timer_readback_buffer"),
+                    size: Self::BUFFER_SIZE,
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                }),
+                state: ReadbackState::Free,
+            })
+            .collect();
+
+        Self {
+            query_set,
+            resolve_buffer,
+            readbacks,
+            frame: 0,
+        }
+    }
+
+    pub(crate) fn begin_pass(&self, encoder: &mut wgpu::CommandEncoder, pass_index: u32) {
+        encoder.write_timestamp(&self.query_set, pass_index * 2);
+    }
+
+    pub(crate) fn end_pass(&self, encoder: &mut wgpu::CommandEncoder, pass_index: u32) {
+        encoder.write_timestamp(&self.query_set, pass_index * 2 + 1);
+    }
+
+    /// Copies the timestamps of the timed passes into a free readback buffer.
+    /// The timings of the frame are dropped if every readback buffer is still
+    /// waiting to be read.
+    pub(crate) fn resolve(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        pass_names: Vec<&'static str>,
+    ) {
+        self.frame += 1;
+        if pass_names.is_empty() {
+            return;
+        }
+        let Some(readback) = self
+            .readbacks
+            .iter_mut()
+            .find(|readback| matches!(readback.state, ReadbackState::Free))
+        else {
+            return;
+        };
+
+        let pass_count = u32::try_from(pass_names.len()).unwrap();
+        encoder.resolve_query_set(&self.query_set, 0..pass_count * 2, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &readback.buffer,
+            0,
+            Self::timestamps_size(pass_names.len()),
+        );
+        readback.state = ReadbackState::Copied {
+            frame: self.frame,
+            pass_names,
+        };
+    }
+
+    /// Starts mapping the readback buffers written by the last frame. Must be
+    /// called after the encoder passed to [`GpuTimer::resolve`] has been
+    /// submitted.
+    pub(crate) fn map_readbacks(&mut self) {
+        for readback in &mut self.readbacks {
+            let ReadbackState::Copied { frame, pass_names } =
+                std::mem::replace(&mut readback.state, ReadbackState::Free)
+            else {
+                continue;
+            };
+
+            let mapped = Arc::new(AtomicBool::new(false));
+            let callback_mapped = Arc::clone(&mapped);
+            readback
+                .buffer
+                .slice(..Self::timestamps_size(pass_names.len()))
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    if result.is_ok() {
+                        callback_mapped.store(true, Ordering::Release);
+                    }
+                });
+            readback.state = ReadbackState::Mapping {
+                frame,
+                pass_names,
+                mapped,
+            };
+        }
+    }
+
+    /// Returns the GPU time of each timed pass of the most recent frame whose
+    /// timestamps are readable, in milliseconds. Returns `None` if no frame
+    /// has finished since the last call. Doesn't wait for the GPU.
+    pub(crate) fn read_pass_timings(
+        &mut self,
+        device: &wgpu::Device,
+        timestamp_period: f32,
+    ) -> Option<Vec<PassTiming>> {
+        device.poll(wgpu::Maintain::Poll);
+
+        let mut latest: Option<(u64, Vec<PassTiming>)> = None;
+        for readback in &mut self.readbacks {
+            let ReadbackState::Mapping { mapped, .. } = &readback.state else {
+                continue;
+            };
+            if !mapped.load(Ordering::Acquire) {
+                continue;
+            }
+            let ReadbackState::Mapping {
+                frame, pass_names, ..
+            } = std::mem::replace(&mut readback.state, ReadbackState::Free)
+            else {
+                unreachable!();
+            };
+
+            let buffer_slice = readback
+                .buffer
+                .slice(..Self::timestamps_size(pass_names.len()));
+            let timestamps: Vec<u64> =
+                bytemuck::cast_slice(&buffer_slice.get_mapped_range()).to_vec();
+            readback.buffer.unmap();
+
+            if latest
+                .as_ref()
+                .is_some_and(|(latest_frame, _)| *latest_frame > frame)
+            {
+                continue;
+            }
+            #[allow(clippy::cast_precision_loss)]
+            let timings = pass_names
+                .into_iter()
+                .zip(timestamps.chunks_exact(2))
+                .map(|(pass_name, pair)| PassTiming {
+                    pass_name,
+                    gpu_time_ms: pair[1].saturating_sub(pair[0]) as f32 * timestamp_period
+                        / 1_000_000.0,
+                })
+                .collect();
+            latest = Some((frame, timings));
+        }
+
+        latest.map(|(_, timings)| timings)
+    }
+
+    fn timestamps_size(pass_count: usize) -> u64 {
+        (pass_count * 2 * std::mem::size_of::<u64>()) as u64
+    }
+}
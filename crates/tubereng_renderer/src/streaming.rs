@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use tubereng_ecs::system::ResMut;
+
+use crate::{texture, GraphicsState};
+
+/// Largest dimension of the mip levels uploaded when a streamed texture is
+/// loaded, before it is known how large it is drawn on screen
+const INITIAL_RESIDENT_SIZE: u32 = 64;
+
+/// Memory budget of the GPU memory used by streamed textures, and mip levels
+/// requested by the passes for the current frame
+pub struct TextureStreaming {
+    budget_bytes: usize,
+    requested_mips: HashMap<texture::Id, usize>,
+}
+
+impl TextureStreaming {
+    pub const DEFAULT_BUDGET_BYTES: usize = 256 * 1024 * 1024;
+
+    #[must_use]
+    pub fn new(budget_bytes: usize) -> Self {
+        Self {
+            budget_bytes,
+            requested_mips: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn budget_bytes(&self) -> usize {
+        self.budget_bytes
+    }
+
+    pub fn set_budget_bytes(&mut self, budget_bytes: usize) {
+        self.budget_bytes = budget_bytes;
+    }
+
+    /// Requests a mip level of a texture to be resident, the most detailed
+    /// level requested during a frame wins
+    pub fn request_mip(&mut self, texture_id: texture::Id, mip_level: usize) {
+        self.requested_mips
+            .entry(texture_id)
+            .and_modify(|requested| *requested = (*requested).min(mip_level))
+            .or_insert(mip_level);
+    }
+}
+
+impl Default for TextureStreaming {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BUDGET_BYTES)
+    }
+}
+
+/// Returns the mip level to sample to draw `texel_count` texels over
+/// `pixel_count` screen pixels
+pub(crate) fn mip_level_for_screen_size(texel_count: f32, pixel_count: f32) -> usize {
+    if pixel_count <= 0.0 {
+        return usize::MAX;
+    }
+
+    let ratio = texel_count / pixel_count;
+    if ratio <= 1.0 {
+        return 0;
+    }
+    // The ratio is finite and greater than 1, so its logarithm is positive
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let mip_level = ratio.log2().floor() as usize;
+    mip_level
+}
+
+pub(crate) fn initial_resident_mip(width: u32, height: u32, mip_count: usize) -> usize {
+    (0..mip_count)
+        .find(|&mip_level| {
+            let (mip_width, mip_height) = texture::mip_size(width, height, mip_level);
+            mip_width.max(mip_height) <= INITIAL_RESIDENT_SIZE
+        })
+        .unwrap_or(mip_count - 1)
+}
+
+/// Residency of a streamed texture, as seen by [`plan_residency`]
+struct ResidencyState {
+    texture_id: texture::Id,
+    /// Size in bytes of every mip level
+    mip_bytes: Vec<usize>,
+    resident_mip: usize,
+    requested_mip: Option<usize>,
+}
+
+impl ResidencyState {
+    fn bytes(&self, resident_mip: usize) -> usize {
+        self.mip_bytes[resident_mip..].iter().sum()
+    }
+}
+
+/// Computes the resident mip of every streamed texture. Requested mips are
+/// made resident, then while the budget is exceeded the highest resolution
+/// mip of the largest texture is dropped, textures that haven't been drawn
+/// during the frame first.
+fn plan_residency(states: &[ResidencyState], budget_bytes: usize) -> HashMap<texture::Id, usize> {
+    let mut planned_mips = states
+        .iter()
+        .map(|state| {
+            let last_mip = state.mip_bytes.len() - 1;
+            state
+                .requested_mip
+                .map_or(state.resident_mip, |requested_mip| {
+                    requested_mip.min(state.resident_mip)
+                })
+                .min(last_mip)
+        })
+        .collect::<Vec<_>>();
+
+    let mut total_bytes = states
+        .iter()
+        .zip(&planned_mips)
+        .map(|(state, &mip)| state.bytes(mip))
+        .sum::<usize>();
+    while total_bytes > budget_bytes {
+        let texture_to_degrade = states
+            .iter()
+            .zip(&planned_mips)
+            .enumerate()
+            .filter(|(_, (state, &mip))| mip + 1 < state.mip_bytes.len())
+            .max_by_key(|(_, (state, &mip))| (state.requested_mip.is_none(), state.bytes(mip)))
+            .map(|(index, _)| index);
+        let Some(index) = texture_to_degrade else {
+            break;
+        };
+
+        total_bytes -= states[index].mip_bytes[planned_mips[index]];
+        planned_mips[index] += 1;
+    }
+
+    states
+        .iter()
+        .zip(planned_mips)
+        .map(|(state, mip)| (state.texture_id, mip))
+        .collect()
+}
+
+/// Uploads or drops mip levels of the streamed textures according to the
+/// mips requested during the frame and the memory budget
+pub(crate) fn update_texture_residency_system(
+    mut gfx: ResMut<GraphicsState>,
+    mut streaming: ResMut<TextureStreaming>,
+) {
+    let states = gfx
+        .texture_cache
+        .streamed_textures()
+        .iter()
+        .map(|(&texture_id, streamed)| ResidencyState {
+            texture_id,
+            mip_bytes: streamed.mips.iter().map(Vec::len).collect(),
+            resident_mip: streamed.resident_mip,
+            requested_mip: streaming.requested_mips.get(&texture_id).copied(),
+        })
+        .collect::<Vec<_>>();
+    streaming.requested_mips.clear();
+
+    for (texture_id, resident_mip) in plan_residency(&states, streaming.budget_bytes) {
+        let info = gfx.texture_cache.info(texture_id);
+        let (width, height) = (info.width, info.height);
+        let Some(streamed) = gfx.texture_cache.streamed_texture_mut(texture_id) else {
+            continue;
+        };
+        if streamed.resident_mip == resident_mip {
+            continue;
+        }
+        streamed.resident_mip = resident_mip;
+
+        let streamed = &gfx.texture_cache.streamed_textures()[&texture_id];
+        let texture = gfx.create_streamed_texture(width, height, &streamed.mips, resident_mip);
+        gfx.texture_cache.replace(texture_id, texture);
+    }
+}
+
+/// Returns the GPU memory used by the resident mip levels of the streamed
+/// textures
+#[must_use]
+pub fn resident_bytes(gfx: &GraphicsState) -> usize {
+    gfx.texture_cache
+        .streamed_textures()
+        .values()
+        .map(|streamed| streamed.resident_bytes(streamed.resident_mip))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(
+        id: usize,
+        mip_bytes: &[usize],
+        resident_mip: usize,
+        requested_mip: Option<usize>,
+    ) -> ResidencyState {
+        ResidencyState {
+            texture_id: texture::Id(id),
+            mip_bytes: mip_bytes.to_vec(),
+            resident_mip,
+            requested_mip,
+        }
+    }
+
+    #[test]
+    fn mip_level_for_screen_size_selection() {
+        assert_eq!(mip_level_for_screen_size(64.0, 128.0), 0);
+        assert_eq!(mip_level_for_screen_size(64.0, 64.0), 0);
+        assert_eq!(mip_level_for_screen_size(64.0, 32.0), 1);
+        assert_eq!(mip_level_for_screen_size(64.0, 20.0), 1);
+        assert_eq!(mip_level_for_screen_size(64.0, 16.0), 2);
+        assert_eq!(mip_level_for_screen_size(64.0, 0.0), usize::MAX);
+    }
+
+    #[test]
+    fn initial_residency_keeps_low_mips() {
+        assert_eq!(initial_resident_mip(32, 32, 6), 0);
+        assert_eq!(initial_resident_mip(256, 128, 9), 2);
+    }
+
+    #[test]
+    fn plan_residency_streams_requested_mips() {
+        let states = [state(0, &[64, 16, 4, 1], 2, Some(0))];
+        assert_eq!(plan_residency(&states, 1000)[&texture::Id(0)], 0);
+        // A coarser request doesn't drop mips when the budget isn't exceeded
+        let states = [state(0, &[64, 16, 4, 1], 1, Some(3))];
+        assert_eq!(plan_residency(&states, 1000)[&texture::Id(0)], 1);
+    }
+
+    #[test]
+    fn plan_residency_drops_unused_mips_first_under_pressure() {
+        let states = [
+            state(0, &[64, 16, 4, 1], 0, None),
+            state(1, &[64, 16, 4, 1], 2, Some(0)),
+        ];
+        let plan = plan_residency(&states, 100);
+        assert_eq!(plan[&texture::Id(1)], 0);
+        assert_eq!(plan[&texture::Id(0)], 2);
+
+        let plan = plan_residency(&states, 10);
+        assert_eq!(plan[&texture::Id(0)], 3);
+        assert_eq!(plan[&texture::Id(1)], 2);
+    }
+}
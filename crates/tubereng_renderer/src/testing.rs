@@ -0,0 +1,45 @@
+//! Helpers of the GPU tests. They need a graphics adapter, which isn't
+//! available everywhere, so they are ignored by default and run with
+//! `cargo test -- --ignored`.
+
+use tubereng_ecs::Ecs;
+
+use crate::{texture, GraphicsState, RendererBuilder};
+
+const NO_ADAPTER: &str =
+    "No graphics adapter is available, the GPU tests can't run in this environment";
+
+/// Creates a headless `GraphicsState`
+///
+/// # Panics
+///
+/// Will panic if no adapter is available
+pub(crate) fn headless_graphics_state(width: u32, height: u32) -> GraphicsState<'static> {
+    pollster::block_on(GraphicsState::new_headless(width, height)).expect(NO_ADAPTER)
+}
+
+/// Initializes the renderer headlessly with a white 1x1 placeholder texture
+///
+/// # Panics
+///
+/// Will panic if no adapter is available
+pub(crate) fn init_headless_renderer(
+    ecs: &mut Ecs,
+    builder: RendererBuilder,
+    width: u32,
+    height: u32,
+) {
+    let initialized = pollster::block_on(builder.init_headless(
+        ecs,
+        width,
+        height,
+        &texture::Descriptor {
+            data: &[255; 4],
+            width: 1,
+            height: 1,
+            premultiplied_alpha: true,
+            format: texture::Format::Rgba8,
+        },
+    ));
+    assert!(initialized, "{NO_ADAPTER}");
+}
@@ -1,7 +1,7 @@
-use std::ops::Deref;
+use std::{cell::Cell, collections::HashMap, ops::Deref};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Id(usize);
+pub struct Id(pub(crate) usize);
 impl Deref for Id {
     type Target = usize;
 
@@ -10,34 +10,231 @@ impl Deref for Id {
     }
 }
 
+/// CPU side mip chain of a streamed texture, see
+/// `GraphicsState::load_streamed_texture`
+pub(crate) struct StreamedTexture {
+    /// Every mip level, from the full resolution one to the 1x1 one
+    pub(crate) mips: Vec<Vec<u8>>,
+    /// Index of the highest resolution mip level uploaded to the GPU
+    pub(crate) resident_mip: usize,
+}
+
+impl StreamedTexture {
+    /// Size in bytes of the mip levels uploaded to the GPU when the given mip
+    /// level is the highest resolution resident one
+    pub(crate) fn resident_bytes(&self, resident_mip: usize) -> usize {
+        self.mips[resident_mip..].iter().map(Vec::len).sum()
+    }
+}
+
+/// CPU side copy of the data uploaded for a texture, kept to upload it again
+/// if the device is lost
+pub(crate) struct RetainedTexture {
+    pub(crate) format: Format,
+    pub(crate) data: Vec<u8>,
+}
+
+struct Entry {
+    info: Info,
+    texture: wgpu::Texture,
+    /// Frame during which the texture has last been accessed with
+    /// [`Cache::get`]
+    last_used_frame: Cell<u64>,
+}
+
 pub struct Cache {
-    infos: Vec<Info>,
-    textures: Vec<wgpu::Texture>,
+    entries: Vec<Option<Entry>>,
+    /// Ids of the unloaded textures, reused by the next loaded textures
+    free_ids: Vec<Id>,
+    frame: u64,
+    streamed: HashMap<Id, StreamedTexture>,
+    retained: HashMap<Id, RetainedTexture>,
 }
 
 impl Cache {
     #[must_use]
     pub fn new() -> Self {
         Self {
-            infos: vec![],
-            textures: vec![],
+            entries: vec![],
+            free_ids: vec![],
+            frame: 0,
+            streamed: HashMap::new(),
+            retained: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn insert_retained(
+        &mut self,
+        texture_info: Info,
+        texture: wgpu::Texture,
+        retained_texture: RetainedTexture,
+    ) -> Id {
+        let id = self.insert(texture_info, texture);
+        self.retained.insert(id, retained_texture);
+        id
+    }
+
+    pub(crate) fn retained_texture(&self, id: Id) -> Option<&RetainedTexture> {
+        self.retained.get(&id)
+    }
+
+    /// Returns the ids of the loaded textures
+    pub fn ids(&self) -> impl Iterator<Item = Id> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.is_some())
+            .map(|(index, _)| Id(index))
+    }
+
+    pub(crate) fn insert_streamed(
+        &mut self,
+        texture_info: Info,
+        texture: wgpu::Texture,
+        streamed_texture: StreamedTexture,
+    ) -> Id {
+        let id = self.insert(texture_info, texture);
+        self.streamed.insert(id, streamed_texture);
+        id
+    }
+
+    /// Replaces the GPU texture of a texture, e.g. when its resident mip levels
+    /// change
+    pub(crate) fn replace(&mut self, id: Id, texture: wgpu::Texture) {
+        if let Some(entry) = &mut self.entries[*id] {
+            entry.texture = texture;
         }
     }
 
+    pub(crate) fn streamed_textures(&self) -> &HashMap<Id, StreamedTexture> {
+        &self.streamed
+    }
+
+    pub(crate) fn streamed_texture_mut(&mut self, id: Id) -> Option<&mut StreamedTexture> {
+        self.streamed.get_mut(&id)
+    }
+
+    /// Returns the highest resolution mip level of a streamed texture that
+    /// is uploaded to the GPU, or `None` if the texture isn't streamed
+    #[must_use]
+    pub fn resident_mip(&self, id: Id) -> Option<usize> {
+        self.streamed.get(&id).map(|streamed| streamed.resident_mip)
+    }
+
+    /// Inserts a texture, the ids of unloaded textures are reused
     pub fn insert(&mut self, texture_info: Info, texture: wgpu::Texture) -> Id {
-        self.infos.push(texture_info);
-        self.textures.push(texture);
-        Id(self.textures.len() - 1)
+        let entry = Entry {
+            info: texture_info,
+            texture,
+            last_used_frame: Cell::new(self.frame),
+        };
+        if let Some(id) = self.free_ids.pop() {
+            self.entries[*id] = Some(entry);
+            id
+        } else {
+            self.entries.push(Some(entry));
+            Id(self.entries.len() - 1)
+        }
+    }
+
+    /// Removes a texture from the cache, its GPU memory is released once the
+    /// bind groups using it are dropped. Returns false if the texture wasn't
+    /// loaded.
+    pub fn remove(&mut self, id: Id) -> bool {
+        let Some(entry) = self.entries.get_mut(*id) else {
+            return false;
+        };
+        if entry.take().is_none() {
+            return false;
+        }
+        self.streamed.remove(&id);
+        self.retained.remove(&id);
+        self.free_ids.push(id);
+        true
+    }
+
+    /// Swaps the contents of two loaded textures, keeping their ids
+    pub(crate) fn swap(&mut self, a: Id, b: Id) {
+        fn swap_values<V>(map: &mut HashMap<Id, V>, a: Id, b: Id) {
+            let (value_a, value_b) = (map.remove(&a), map.remove(&b));
+            if let Some(value) = value_a {
+                map.insert(b, value);
+            }
+            if let Some(value) = value_b {
+                map.insert(a, value);
+            }
+        }
+
+        self.entries.swap(*a, *b);
+        swap_values(&mut self.streamed, a, b);
+        swap_values(&mut self.retained, a, b);
     }
 
+    #[must_use]
+    pub fn contains(&self, id: Id) -> bool {
+        self.entries.get(*id).is_some_and(Option::is_some)
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the texture isn't loaded
     #[must_use]
     pub fn info(&self, id: Id) -> &Info {
-        &self.infos[*id]
+        &self.entry(id).info
     }
 
+    /// Returns the GPU texture of a texture and marks it as used during the
+    /// current frame
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the texture isn't loaded
     #[must_use]
     pub fn get(&self, id: Id) -> &wgpu::Texture {
-        &self.textures[*id]
+        let entry = self.entry(id);
+        entry.last_used_frame.set(self.frame);
+        &entry.texture
+    }
+
+    fn entry(&self, id: Id) -> &Entry {
+        self.entries
+            .get(*id)
+            .and_then(Option::as_ref)
+            .expect("texture should be loaded")
+    }
+
+    /// Returns the frame during which a texture has last been used, or `None`
+    /// if the texture isn't loaded
+    #[must_use]
+    pub fn last_used_frame(&self, id: Id) -> Option<u64> {
+        self.entries
+            .get(*id)
+            .and_then(Option::as_ref)
+            .map(|entry| entry.last_used_frame.get())
+    }
+
+    /// Number of frames rendered since the cache has been created
+    #[must_use]
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub(crate) fn advance_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Returns an estimate of the GPU memory used by a texture, or `None` if
+    /// the texture isn't loaded
+    #[must_use]
+    pub fn size_bytes(&self, id: Id) -> Option<usize> {
+        let entry = self.entries.get(*id).and_then(Option::as_ref)?;
+        Some(if let Some(streamed) = self.streamed.get(&id) {
+            streamed.resident_bytes(streamed.resident_mip)
+        } else if let Some(retained) = self.retained.get(&id) {
+            retained.data.len()
+        } else {
+            entry.info.width as usize * entry.info.height as usize * 4
+        })
     }
 }
 
@@ -50,6 +247,7 @@ impl Default for Cache {
 pub struct Info {
     pub(crate) width: u32,
     pub(crate) height: u32,
+    pub(crate) premultiplied_alpha: bool,
 }
 
 impl Info {
@@ -61,15 +259,160 @@ impl Info {
     pub fn height(&self) -> u32 {
         self.height
     }
+    #[must_use]
+    pub fn premultiplied_alpha(&self) -> bool {
+        self.premultiplied_alpha
+    }
+}
+
+/// Features needed to upload compressed textures without decompressing them
+pub(crate) const COMPRESSED_TEXTURE_FEATURES: wgpu::Features =
+    wgpu::Features::TEXTURE_COMPRESSION_BC;
+
+/// Format of the data of a texture. Color data is treated as sRGB, except
+/// for [`Format::Rgba8Linear`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Rgba8,
+    /// RGBA8 data that isn't a color, e.g. a normal map
+    Rgba8Linear,
+    Bc1,
+    Bc3,
+    Bc7,
+}
+
+impl Format {
+    #[must_use]
+    pub fn is_compressed(self) -> bool {
+        !matches!(self, Format::Rgba8 | Format::Rgba8Linear)
+    }
+
+    pub(crate) fn wgpu_format(self) -> wgpu::TextureFormat {
+        match self {
+            Format::Rgba8 => wgpu::TextureFormat::Rgba8UnormSrgb,
+            Format::Rgba8Linear => wgpu::TextureFormat::Rgba8Unorm,
+            Format::Bc1 => wgpu::TextureFormat::Bc1RgbaUnormSrgb,
+            Format::Bc3 => wgpu::TextureFormat::Bc3RgbaUnormSrgb,
+            Format::Bc7 => wgpu::TextureFormat::Bc7RgbaUnormSrgb,
+        }
+    }
+
+    /// Size in bytes of a pixel, or of a 4x4 block for compressed formats
+    fn block_size(self) -> u32 {
+        match self {
+            Format::Rgba8 | Format::Rgba8Linear => 4,
+            Format::Bc1 => 8,
+            Format::Bc3 | Format::Bc7 => 16,
+        }
+    }
+
+    fn block_dimension(self) -> u32 {
+        if self.is_compressed() {
+            4
+        } else {
+            1
+        }
+    }
+
+    pub(crate) fn bytes_per_row(self, width: u32) -> u32 {
+        width.div_ceil(self.block_dimension()) * self.block_size()
+    }
+
+    pub(crate) fn rows_per_image(self, height: u32) -> u32 {
+        height.div_ceil(self.block_dimension())
+    }
+}
+
+/// Size of a mip level of a texture
+pub(crate) fn mip_size(width: u32, height: u32, mip_level: usize) -> (u32, u32) {
+    let mip_level = u32::try_from(mip_level).unwrap_or(u32::MAX);
+    (
+        width.checked_shr(mip_level).unwrap_or(0).max(1),
+        height.checked_shr(mip_level).unwrap_or(0).max(1),
+    )
+}
+
+/// Generates every mip level of an RGBA8 texture by averaging 2x2 texels,
+/// starting with the full resolution data
+pub(crate) fn generate_mip_chain(data: &[u8], width: u32, height: u32) -> Vec<Vec<u8>> {
+    let mut mips = vec![data.to_vec()];
+    let (mut width, mut height) = (width as usize, height as usize);
+    while width > 1 || height > 1 {
+        let previous = mips.last().unwrap();
+        let (mip_width, mip_height) = ((width / 2).max(1), (height / 2).max(1));
+        let mut mip = Vec::with_capacity(mip_width * mip_height * 4);
+        for y in 0..mip_height {
+            for x in 0..mip_width {
+                let texels = [
+                    (2 * x, 2 * y),
+                    ((2 * x + 1).min(width - 1), 2 * y),
+                    (2 * x, (2 * y + 1).min(height - 1)),
+                    ((2 * x + 1).min(width - 1), (2 * y + 1).min(height - 1)),
+                ];
+                for channel in 0..4 {
+                    let sum: u32 = texels
+                        .iter()
+                        .map(|&(x, y)| u32::from(previous[(y * width + x) * 4 + channel]))
+                        .sum();
+                    // The average of 4 bytes always fits in a byte
+                    #[allow(clippy::cast_possible_truncation)]
+                    mip.push(((sum + 2) / 4) as u8);
+                }
+            }
+        }
+        mips.push(mip);
+        (width, height) = (mip_width, mip_height);
+    }
+    mips
+}
+
+/// Returns true if compressed data of a texture of the given size can be
+/// uploaded as is. Compressed textures must be made of whole blocks.
+pub(crate) fn compressed_upload_supported(
+    features: wgpu::Features,
+    width: u32,
+    height: u32,
+) -> bool {
+    features.contains(COMPRESSED_TEXTURE_FEATURES)
+        && width.is_multiple_of(4)
+        && height.is_multiple_of(4)
+}
+
+/// Decompresses block compressed data into RGBA8 pixels. If `data` is too
+/// short for the texture size, the texture is left transparent.
+pub(crate) fn decompress_to_rgba8(format: Format, data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let mut pixels = vec![0u32; width * height];
+    let result = match format {
+        Format::Rgba8 | Format::Rgba8Linear => return data.to_vec(),
+        Format::Bc1 => texture2ddecoder::decode_bc1a(data, width, height, &mut pixels),
+        Format::Bc3 => texture2ddecoder::decode_bc3(data, width, height, &mut pixels),
+        Format::Bc7 => texture2ddecoder::decode_bc7(data, width, height, &mut pixels),
+    };
+    if let Err(error) = result {
+        log::warn!("Failed to decompress {format:?} texture: {error}");
+    }
+
+    pixels
+        .into_iter()
+        .flat_map(|pixel| {
+            let [b, g, r, a] = pixel.to_le_bytes();
+            [r, g, b, a]
+        })
+        .collect()
 }
 
 pub struct Descriptor<'a> {
     pub data: &'a [u8],
     pub width: u32,
     pub height: u32,
+    /// Whether the color channels of `data` are premultiplied by alpha
+    pub premultiplied_alpha: bool,
+    pub format: Format,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Rect {
     pub x: f32,
     pub y: f32,
@@ -88,3 +431,49 @@ impl Rect {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_row_layout() {
+        assert_eq!(Format::Rgba8.bytes_per_row(10), 40);
+        assert_eq!(Format::Rgba8.rows_per_image(10), 10);
+        assert_eq!(Format::Bc1.bytes_per_row(10), 24);
+        assert_eq!(Format::Bc7.bytes_per_row(8), 32);
+        assert_eq!(Format::Bc7.rows_per_image(10), 3);
+    }
+
+    #[test]
+    fn mip_chain() {
+        let data = [
+            [255, 0, 0, 255],
+            [255, 0, 0, 255],
+            [0, 0, 255, 255],
+            [0, 0, 255, 255],
+            [255, 0, 0, 255],
+            [255, 0, 0, 255],
+            [0, 0, 255, 255],
+            [0, 0, 255, 255],
+        ]
+        .concat();
+        let mips = generate_mip_chain(&data, 4, 2);
+        assert_eq!(mips.len(), 3);
+        assert_eq!(mips[1], [[255, 0, 0, 255], [0, 0, 255, 255]].concat());
+        assert_eq!(mips[2], [128, 0, 128, 255]);
+        assert_eq!(mip_size(4, 2, 2), (1, 1));
+        assert_eq!(mip_size(4, 2, 40), (1, 1));
+    }
+
+    #[test]
+    fn decompress_bc1_to_rgba8() {
+        // Single block with a pure red first endpoint, every texel using it
+        let block = [0x00, 0xF8, 0x00, 0x00, 0, 0, 0, 0];
+        let pixels = decompress_to_rgba8(Format::Bc1, &block, 4, 4);
+        assert_eq!(pixels.len(), 4 * 4 * 4);
+        assert!(pixels
+            .chunks_exact(4)
+            .all(|pixel| pixel == [255, 0, 0, 255]));
+    }
+}
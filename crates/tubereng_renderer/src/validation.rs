@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+
+use tubereng_ecs::{event::Events, system::ResMut, EntityId, Storage};
+
+use crate::texture;
+
+/// Size of the checker texture drawn in place of invalid sprites
+pub(crate) const MISSING_TEXTURE_SIZE: u32 = 16;
+const MISSING_TEXTURE_CELL_SIZE: u32 = 4;
+
+/// Why a sprite can't be drawn as is
+#[derive(Debug, Clone)]
+pub enum InvalidSpriteReason {
+    /// The texture of the sprite isn't loaded, e.g. it has been unloaded or
+    /// evicted
+    MissingTexture(texture::Id),
+    /// The texture rect of the sprite is empty or goes beyond the bounds of its
+    /// texture
+    TextureRectOutOfBounds {
+        texture_rect: texture::Rect,
+        texture_width: u32,
+        texture_height: u32,
+    },
+}
+
+/// Event emitted the first time a sprite is found to be invalid, the sprite
+/// is then drawn with a magenta checker
+#[derive(Debug, Clone)]
+pub struct InvalidSprite {
+    pub entity: EntityId,
+    pub reason: InvalidSpriteReason,
+}
+
+/// Resource holding the entities whose invalid sprite has been reported, each
+/// one is reported once until its sprite becomes valid or it is deleted
+#[derive(Debug, Default)]
+pub(crate) struct ReportedInvalidSprites(HashSet<EntityId>);
+
+impl ReportedInvalidSprites {
+    pub(crate) fn report(
+        &mut self,
+        events: &mut Events<InvalidSprite>,
+        entity: EntityId,
+        reason: InvalidSpriteReason,
+    ) {
+        if !self.0.insert(entity) {
+            return;
+        }
+        log::warn!("Sprite of entity {entity} is invalid: {reason:?}");
+        events.send(InvalidSprite { entity, reason });
+    }
+
+    /// Forgets an entity whose sprite is valid, it is reported again if its
+    /// sprite becomes invalid
+    pub(crate) fn forget(&mut self, entity: EntityId) {
+        self.0.remove(&entity);
+    }
+}
+
+/// Forgets the deleted entities, whose ids can be reused by new entities
+pub(crate) fn prune_reported_invalid_sprites_system(
+    mut reported: ResMut<ReportedInvalidSprites>,
+    storage: &Storage,
+) {
+    reported.0.retain(|entity| storage.contains_entity(*entity));
+}
+
+/// Returns the region of the texture to draw for a sprite, the whole texture
+/// if `texture_rect` is `None`
+pub(crate) fn validate_texture_rect(
+    texture_cache: &texture::Cache,
+    texture_id: texture::Id,
+    texture_rect: Option<&texture::Rect>,
+) -> Result<texture::Rect, InvalidSpriteReason> {
+    if !texture_cache.contains(texture_id) {
+        return Err(InvalidSpriteReason::MissingTexture(texture_id));
+    }
+
+    let info = texture_cache.info(texture_id);
+    #[allow(clippy::cast_precision_loss)]
+    let (width, height) = (info.width as f32, info.height as f32);
+    let Some(texture_rect) = texture_rect else {
+        return Ok(texture::Rect::new(0.0, 0.0, width, height));
+    };
+    let in_bounds = texture_rect.x >= 0.0
+        && texture_rect.y >= 0.0
+        && texture_rect.width > 0.0
+        && texture_rect.height > 0.0
+        && texture_rect.x + texture_rect.width <= width
+        && texture_rect.y + texture_rect.height <= height;
+    if in_bounds {
+        Ok(texture_rect.clone())
+    } else {
+        Err(InvalidSpriteReason::TextureRectOutOfBounds {
+            texture_rect: texture_rect.clone(),
+            texture_width: info.width,
+            texture_height: info.height,
+        })
+    }
+}
+
+/// RGBA8 data of the magenta and black checker drawn in place of invalid
+/// sprites
+pub(crate) fn missing_texture_data() -> Vec<u8> {
+    const MAGENTA: [u8; 4] = [255, 0, 255, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+    (0..MISSING_TEXTURE_SIZE)
+        .flat_map(|y| {
+            (0..MISSING_TEXTURE_SIZE).flat_map(move |x| {
+                let cell = x / MISSING_TEXTURE_CELL_SIZE + y / MISSING_TEXTURE_CELL_SIZE;
+                if cell.is_multiple_of(2) {
+                    MAGENTA
+                } else {
+                    BLACK
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offenders_are_reported_once() {
+        let mut reported = ReportedInvalidSprites::default();
+        let mut events = Events::new();
+        let reason = InvalidSpriteReason::MissingTexture(texture::Id(3));
+        reported.report(&mut events, 1, reason.clone());
+        reported.report(&mut events, 1, reason.clone());
+        events.update();
+        assert_eq!(events.len(), 1);
+        events.update();
+        reported.report(&mut events, 1, reason.clone());
+        events.update();
+        assert!(events.is_empty());
+
+        reported.report(&mut events, 2, reason);
+        events.update();
+        assert_eq!(
+            events.iter().map(|event| event.entity).collect::<Vec<_>>(),
+            [2]
+        );
+
+        reported.forget(2);
+        reported.report(
+            &mut events,
+            2,
+            InvalidSpriteReason::MissingTexture(texture::Id(3)),
+        );
+        events.update();
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn deleted_entities_are_forgotten() {
+        let mut ecs = tubereng_ecs::Ecs::new();
+        ecs.register_event::<InvalidSprite>();
+        ecs.insert_resource(ReportedInvalidSprites::default());
+        ecs.register_system(
+            &tubereng_ecs::system::stages::Update,
+            prune_reported_invalid_sprites_system,
+        );
+        let kept = ecs.insert((texture::Id(3),));
+        let deleted = ecs.insert((texture::Id(3),));
+        {
+            let mut reported = ecs.resource_mut::<ReportedInvalidSprites>().unwrap();
+            let mut events = ecs.resource_mut::<Events<InvalidSprite>>().unwrap();
+            for entity in [kept, deleted] {
+                reported.report(
+                    &mut events,
+                    entity,
+                    InvalidSpriteReason::MissingTexture(texture::Id(3)),
+                );
+            }
+        }
+        ecs.delete(deleted);
+        ecs.run_systems();
+
+        let reported = ecs.resource::<ReportedInvalidSprites>().unwrap();
+        assert!(reported.0.contains(&kept));
+        assert!(!reported.0.contains(&deleted));
+    }
+
+    #[test]
+    fn missing_texture_is_a_checker() {
+        let data = missing_texture_data();
+        assert_eq!(
+            data.len(),
+            (MISSING_TEXTURE_SIZE * MISSING_TEXTURE_SIZE * 4) as usize
+        );
+        assert_eq!(data[..4], [255, 0, 255, 255]);
+        let second_cell = (MISSING_TEXTURE_CELL_SIZE * 4) as usize;
+        assert_eq!(data[second_cell..second_cell + 4], [0, 0, 0, 255]);
+    }
+}
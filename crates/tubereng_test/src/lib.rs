@@ -0,0 +1,592 @@
+#![warn(clippy::pedantic)]
+
+//! Visual regression tests for games: frames rendered headlessly are compared
+//! with golden images stored in the `tests/golden` directory of the crate.
+//!
+//! ```no_run
+//! # use tubereng_engine::Engine;
+//! # use tubereng_test::assert_frame_matches;
+//! # fn test(mut engine: Engine) {
+//! if !pollster::block_on(engine.init_headless_graphics(320, 240)) {
+//!     // No adapter is available in this environment
+//!     return;
+//! }
+//! engine.update(0.016);
+//! assert_frame_matches!(engine, "title_screen", tolerance = 2);
+//! # }
+//! ```
+//!
+//! A missing golden image is created from the rendered frame. Setting the
+//! `TUBERENG_UPDATE_GOLDEN` environment variable replaces the existing ones.
+//! When a frame doesn't match, `<scene>.actual.png` and `<scene>.diff.png` are
+//! written next to the golden image, the mismatched pixels being red in the
+//! diff image.
+//!
+//! The gameplay can be tested without a graphics adapter, the engine
+//! running its systems without rendering once initialized with
+//! `Engine::init_headless`.
+
+use std::path::{Path, PathBuf};
+
+use tubereng_engine::Engine;
+
+/// Environment variable replacing the golden images by the rendered frames
+pub const UPDATE_GOLDEN_VARIABLE: &str = "TUBERENG_UPDATE_GOLDEN";
+
+/// Asserts that the last frame rendered by a headless [`Engine`] matches the
+/// golden image of a scene, see the [crate documentation](crate).
+///
+/// The per-channel tolerance defaults to 0.
+#[macro_export]
+macro_rules! assert_frame_matches {
+    ($engine:expr, $scene_name:expr) => {
+        $crate::assert_frame_matches!($engine, $scene_name, tolerance = 0)
+    };
+    ($engine:expr, $scene_name:expr, tolerance = $tolerance:expr) => {
+        $crate::assert_frame_matches(
+            &$crate::Frame::from_engine(&$engine)
+                .expect("The engine should render without a window"),
+            &::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden"),
+            $scene_name,
+            $tolerance,
+        )
+    };
+}
+
+/// RGBA8 image rendered by the engine or loaded from a golden image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed RGBA8 rows
+    pub pixels: Vec<u8>,
+}
+
+impl Frame {
+    /// Reads back the last frame rendered by `engine`, `None` if its graphics
+    /// haven't been initialized headlessly
+    #[must_use]
+    pub fn from_engine(engine: &Engine) -> Option<Self> {
+        let (width, height, pixels) = engine.read_headless_frame()?;
+        Some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// # Errors
+    ///
+    /// An error will be returned if the image cannot be read or decoded
+    pub fn load_png(path: &Path) -> image::ImageResult<Self> {
+        let image = image::open(path)?.into_rgba8();
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.into_raw(),
+        })
+    }
+
+    /// # Errors
+    ///
+    /// An error will be returned if the image cannot be encoded or written
+    pub fn save_png(&self, path: &Path) -> image::ImageResult<()> {
+        image::save_buffer(
+            path,
+            &self.pixels,
+            self.width,
+            self.height,
+            image::ExtendedColorType::Rgba8,
+        )
+    }
+}
+
+/// Differences between a frame and its golden image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrameMismatch {
+    Size {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    Pixels {
+        /// Number of pixels with a channel differing by more than the
+        /// tolerance
+        mismatched_pixels: usize,
+        /// Largest difference of a channel
+        max_difference: u8,
+        /// Mismatched pixels in red over the dimmed frame
+        diff: Frame,
+    },
+}
+
+/// Compares two frames, channels differing by at most `tolerance` being
+/// considered equal
+///
+/// # Errors
+///
+/// The differences are returned if the frames don't match
+pub fn compare(actual: &Frame, expected: &Frame, tolerance: u8) -> Result<(), FrameMismatch> {
+    if (actual.width, actual.height) != (expected.width, expected.height) {
+        return Err(FrameMismatch::Size {
+            expected: (expected.width, expected.height),
+            actual: (actual.width, actual.height),
+        });
+    }
+
+    let mut mismatched_pixels = 0;
+    let mut max_difference = 0;
+    let mut diff_pixels = Vec::with_capacity(actual.pixels.len());
+    for (actual_pixel, expected_pixel) in actual
+        .pixels
+        .chunks_exact(4)
+        .zip(expected.pixels.chunks_exact(4))
+    {
+        let difference = actual_pixel
+            .iter()
+            .zip(expected_pixel)
+            .map(|(a, e)| a.abs_diff(*e))
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+        if difference > tolerance {
+            mismatched_pixels += 1;
+            diff_pixels.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let luminance = u8::try_from(
+                (u16::from(actual_pixel[0])
+                    + u16::from(actual_pixel[1])
+                    + u16::from(actual_pixel[2]))
+                    / 12,
+            )
+            .unwrap_or(u8::MAX);
+            diff_pixels.extend_from_slice(&[luminance, luminance, luminance, 255]);
+        }
+    }
+
+    if mismatched_pixels == 0 {
+        return Ok(());
+    }
+    Err(FrameMismatch::Pixels {
+        mismatched_pixels,
+        max_difference,
+        diff: Frame {
+            width: actual.width,
+            height: actual.height,
+            pixels: diff_pixels,
+        },
+    })
+}
+
+/// Compares a frame with the golden image of `scene_name` in
+/// `golden_directory`, see [`assert_frame_matches!`]
+///
+/// # Panics
+///
+/// Will panic if the frame doesn't match the golden image, or if the images
+/// cannot be read or written
+pub fn assert_frame_matches(
+    frame: &Frame,
+    golden_directory: &Path,
+    scene_name: &str,
+    tolerance: u8,
+) {
+    let path = |suffix: &str| -> PathBuf { golden_directory.join(format!("{scene_name}{suffix}")) };
+    let golden_path = path(".png");
+    if !golden_path.exists() || std::env::var_os(UPDATE_GOLDEN_VARIABLE).is_some() {
+        std::fs::create_dir_all(golden_directory).expect("Couldn't create the golden directory");
+        frame
+            .save_png(&golden_path)
+            .expect("Couldn't write the golden image");
+        return;
+    }
+
+    let golden = Frame::load_png(&golden_path).expect("Couldn't read the golden image");
+    let Err(mismatch) = compare(frame, &golden, tolerance) else {
+        return;
+    };
+    let actual_path = path(".actual.png");
+    frame
+        .save_png(&actual_path)
+        .expect("Couldn't write the actual image");
+    match mismatch {
+        FrameMismatch::Size { expected, actual } => panic!(
+            "Frame of scene {scene_name} is {}x{} instead of {}x{}, see {}",
+            actual.0,
+            actual.1,
+            expected.0,
+            expected.1,
+            actual_path.display()
+        ),
+        FrameMismatch::Pixels {
+            mismatched_pixels,
+            max_difference,
+            diff,
+        } => {
+            let diff_path = path(".diff.png");
+            diff.save_png(&diff_path)
+                .expect("Couldn't write the diff image");
+            panic!(
+                "Frame of scene {scene_name} doesn't match its golden image: \
+                 {mismatched_pixels} pixels differ by up to {max_difference}, \
+                 see {} and {}",
+                actual_path.display(),
+                diff_path.display()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_asset::vfs::filesystem::FileSystem;
+    use tubereng_core::{interpolation::InterpolatedTransform, Transform, TransformCache};
+    use tubereng_engine::{
+        diagnostics::Diagnostics,
+        window::{CursorIcon, FullscreenMode, Window, WindowCommand},
+        StartupPhase,
+    };
+    use tubereng_math::vector::Vector3f;
+    use tubereng_renderer::camera::{Follow, D2};
+
+    use super::*;
+
+    fn frame(pixels: &[[u8; 4]]) -> Frame {
+        Frame {
+            width: u32::try_from(pixels.len()).unwrap(),
+            height: 1,
+            pixels: pixels.concat(),
+        }
+    }
+
+    fn golden_directory(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tubereng_golden_{name}_{}", std::process::id()))
+    }
+
+    #[test]
+    fn differences_within_tolerance_are_ignored() {
+        let expected = frame(&[[10, 20, 30, 255], [0, 0, 0, 255]]);
+        let actual = frame(&[[12, 20, 30, 255], [0, 0, 0, 255]]);
+        assert!(compare(&actual, &expected, 2).is_ok());
+        assert!(matches!(
+            compare(&actual, &expected, 1),
+            Err(FrameMismatch::Pixels {
+                mismatched_pixels: 1,
+                max_difference: 2,
+                ref diff,
+            }) if diff.pixels[..4] == [255, 0, 0, 255]
+        ));
+    }
+
+    #[test]
+    fn frames_of_different_sizes_mismatch() {
+        let expected = frame(&[[0; 4]]);
+        let actual = frame(&[[0; 4], [0; 4]]);
+        assert_eq!(
+            compare(&actual, &expected, 0),
+            Err(FrameMismatch::Size {
+                expected: (1, 1),
+                actual: (2, 1),
+            })
+        );
+    }
+
+    #[test]
+    fn mismatches_write_actual_and_diff_images() {
+        let directory = golden_directory("mismatch");
+        assert_frame_matches(&frame(&[[0, 0, 0, 255]]), &directory, "scene", 0);
+        let result = std::panic::catch_unwind(|| {
+            assert_frame_matches(&frame(&[[255, 255, 255, 255]]), &directory, "scene", 0);
+        });
+        assert!(result.is_err());
+        assert!(directory.join("scene.actual.png").exists());
+        assert_eq!(
+            Frame::load_png(&directory.join("scene.diff.png"))
+                .unwrap()
+                .pixels,
+            [255, 0, 0, 255]
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[derive(Debug)]
+    struct Speed(f32);
+
+    fn move_system(
+        time: tubereng_ecs::system::Res<tubereng_core::time::Time>,
+        mut query: tubereng_ecs::system::Q<(&Speed, &mut Transform)>,
+    ) {
+        for (speed, mut transform) in query.iter() {
+            transform.translation.x += speed.0 * time.delta();
+        }
+        std::mem::drop(time);
+    }
+
+    #[test]
+    fn gameplay_runs_without_graphics() {
+        let mut engine = Engine::builder().build(FileSystem);
+        engine.init_headless();
+        let ecs = engine.ecs_mut();
+        let player = ecs.insert((
+            Speed(10.0),
+            Transform {
+                translation: Vector3f::new(0.0, 50.0, 0.0),
+                ..Default::default()
+            },
+        ));
+        let camera = ecs.insert((
+            D2::new(20.0, 10.0),
+            Follow::new(player),
+            Transform::default(),
+        ));
+        ecs.register_system(&tubereng_ecs::system::stages::Update, move_system);
+
+        engine.run_frames(4, 0.5);
+
+        let ecs = engine.ecs();
+        let player_translation = &ecs.component::<Transform>(player).unwrap().translation;
+        assert!((player_translation.x - 20.0).abs() < 1e-6);
+        // The camera, registered before the movement, follows the position
+        // of the player at the previous frame
+        let camera_translation = &ecs.component::<Transform>(camera).unwrap().translation;
+        assert!((camera_translation.x - 5.0).abs() < 1e-6);
+        assert!((camera_translation.y - 45.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn interpolated_transforms_are_rendered_between_fixed_steps() {
+        let mut engine = Engine::builder().with_fixed_timestep(0.1).build(FileSystem);
+        engine.init_headless();
+        let ecs = engine.ecs_mut();
+        let entity = ecs.insert((
+            Speed(10.0),
+            Transform::default(),
+            InterpolatedTransform::new(),
+        ));
+        ecs.register_system(
+            &tubereng_ecs::system::stages::FixedUpdate,
+            |mut query: tubereng_ecs::system::Q<(&Speed, &mut Transform)>| {
+                for (speed, mut transform) in query.iter() {
+                    transform.translation.x += speed.0 * 0.1;
+                }
+            },
+        );
+
+        engine.update(0.15);
+
+        let ecs = engine.ecs();
+        assert!((ecs.component::<Transform>(entity).unwrap().translation.x - 1.0).abs() < 1e-6);
+        let rendered_matrix = ecs.resource::<TransformCache>().unwrap().get(entity);
+        assert!((Transform::from(rendered_matrix).translation.x - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn diagnostics_are_recorded_every_frame() {
+        let mut engine = Engine::builder().with_diagnostics(true).build(FileSystem);
+        engine.init_headless();
+        engine.ecs_mut().insert((Speed(1.0), Transform::default()));
+        engine
+            .ecs_mut()
+            .register_system(&tubereng_ecs::system::stages::Update, move_system);
+
+        engine.update(0.01);
+        engine.update(0.03);
+
+        let diagnostics = engine.ecs().resource::<Diagnostics>().unwrap();
+        assert!((diagnostics.min_frame_time() - 0.01).abs() < 1e-6);
+        assert!((diagnostics.average_frame_time() - 0.02).abs() < 1e-6);
+        assert!((diagnostics.max_frame_time() - 0.03).abs() < 1e-6);
+        assert!((diagnostics.fps() - 50.0).abs() < 1e-3);
+        assert_eq!(diagnostics.entity_count(), 1);
+        assert_eq!(diagnostics.draw_calls(), 0);
+        assert!(diagnostics
+            .system_timings()
+            .iter()
+            .any(|timing| timing.system_name.ends_with("move_system")));
+    }
+
+    #[derive(Debug, Default)]
+    struct StartupLog(Vec<&'static str>);
+
+    #[test]
+    fn startup_systems_run_in_order() {
+        fn load(queue: &tubereng_ecs::commands::CommandQueue) {
+            queue.insert_resource(StartupLog(vec!["load"]));
+        }
+        fn set_up_world(mut startup_log: tubereng_ecs::system::ResMut<StartupLog>) {
+            startup_log.0.push("world");
+        }
+        fn set_up_ui(mut startup_log: tubereng_ecs::system::ResMut<StartupLog>) {
+            startup_log.0.push("ui");
+        }
+        fn init(mut startup_log: tubereng_ecs::system::ResMut<StartupLog>) {
+            startup_log.0.push("init");
+        }
+
+        let mut engine = Engine::builder()
+            .with_startup_system(StartupPhase::PostStartup, set_up_ui)
+            .with_startup_system(StartupPhase::Startup, load)
+            .with_startup_system(StartupPhase::Startup, set_up_world)
+            .with_init_system(init)
+            .build(FileSystem);
+        assert_eq!(
+            engine.ecs().resource::<StartupLog>().unwrap().0,
+            ["load", "world"]
+        );
+
+        engine.init_headless();
+        engine.run_frames(2, 0.016);
+
+        assert_eq!(
+            engine.ecs().resource::<StartupLog>().unwrap().0,
+            ["load", "world", "ui", "init"]
+        );
+    }
+
+    #[test]
+    fn window_commands_are_drained_by_the_runner() {
+        fn toggle_fullscreen(mut window: tubereng_ecs::system::ResMut<Window>) {
+            window.toggle_fullscreen();
+        }
+
+        let mut engine = Engine::builder().build(FileSystem);
+        engine.init_headless();
+        engine
+            .ecs_mut()
+            .register_system(&tubereng_ecs::system::stages::Update, toggle_fullscreen);
+
+        engine.update(0.016);
+        assert_eq!(
+            engine.drain_window_commands(),
+            [WindowCommand::SetFullscreen(FullscreenMode::Borderless)]
+        );
+        assert!(engine.drain_window_commands().is_empty());
+
+        engine.update(0.016);
+        let window = engine.ecs().resource::<Window>().unwrap();
+        assert_eq!(window.fullscreen(), FullscreenMode::Windowed);
+    }
+
+    #[test]
+    fn window_title_and_size_can_be_changed() {
+        fn show_level(mut window: tubereng_ecs::system::ResMut<Window>) {
+            window.set_title("Game - level 3");
+            window.set_inner_size(1280, 720);
+            window.center();
+        }
+
+        let mut engine = Engine::builder()
+            .with_application_title("Game")
+            .build(FileSystem);
+        engine.init_headless();
+        assert_eq!(engine.ecs().resource::<Window>().unwrap().title(), "Game");
+        engine
+            .ecs_mut()
+            .register_system(&tubereng_ecs::system::stages::Update, show_level);
+
+        engine.update(0.016);
+        assert_eq!(
+            engine.drain_window_commands(),
+            [
+                WindowCommand::SetTitle("Game - level 3".into()),
+                WindowCommand::SetInnerSize {
+                    width: 1280,
+                    height: 720
+                },
+                WindowCommand::Center,
+            ]
+        );
+
+        engine.on_window_resized(1024, 768);
+        let window = engine.ecs().resource::<Window>().unwrap();
+        assert_eq!(window.title(), "Game - level 3");
+        assert_eq!(window.inner_size(), (1024, 768));
+    }
+
+    #[derive(Debug, Default)]
+    struct Hovered(bool);
+
+    #[test]
+    fn cursor_icon_is_set_every_frame() {
+        fn show_pointer(
+            hovered: tubereng_ecs::system::Res<Hovered>,
+            mut window: tubereng_ecs::system::ResMut<Window>,
+        ) {
+            if hovered.0 {
+                window.set_cursor_icon(CursorIcon::Pointer);
+            }
+            std::mem::drop(hovered);
+        }
+
+        let mut engine = Engine::builder().build(FileSystem);
+        engine.init_headless();
+        engine.ecs_mut().insert_resource(Hovered(true));
+        engine
+            .ecs_mut()
+            .register_system(&tubereng_ecs::system::stages::Update, show_pointer);
+
+        engine.run_frames(2, 0.016);
+        assert_eq!(
+            engine.drain_window_commands(),
+            [WindowCommand::SetCursorIcon(CursorIcon::Pointer)]
+        );
+
+        engine.ecs_mut().insert_resource(Hovered(false));
+        engine.update(0.016);
+        assert_eq!(
+            engine.drain_window_commands(),
+            [WindowCommand::SetCursorIcon(CursorIcon::Default)]
+        );
+    }
+
+    #[test]
+    fn unused_assets_are_unloaded_at_the_end_of_the_frame() {
+        use tubereng_asset::{scene::Scene, AssetStore};
+
+        let mut engine = Engine::builder().build(FileSystem);
+        engine.init_headless();
+        let scene = engine
+            .ecs()
+            .resource_mut::<AssetStore>()
+            .unwrap()
+            .store(Scene::default());
+        let other_scene = scene.clone();
+
+        std::mem::drop(scene);
+        engine.update(0.016);
+        assert_eq!(
+            engine.ecs().resource::<AssetStore>().unwrap().asset_count(),
+            1
+        );
+
+        std::mem::drop(other_scene);
+        engine.update(0.016);
+        assert_eq!(
+            engine.ecs().resource::<AssetStore>().unwrap().asset_count(),
+            0
+        );
+    }
+
+    #[test]
+    #[ignore = "requires a graphics adapter"]
+    fn headless_frames_match_their_golden_image() {
+        let mut engine = Engine::builder().build(FileSystem);
+        assert!(
+            pollster::block_on(engine.init_headless_graphics(8, 8)),
+            "No graphics adapter is available"
+        );
+        engine.update(0.016);
+
+        let directory = golden_directory("engine");
+        let frame = Frame::from_engine(&engine).unwrap();
+        assert_eq!(frame.pixels.len(), 8 * 8 * 4);
+        assert_frame_matches(&frame, &directory, "clear", 0);
+        engine.update(0.016);
+        assert_frame_matches(
+            &Frame::from_engine(&engine).unwrap(),
+            &directory,
+            "clear",
+            0,
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
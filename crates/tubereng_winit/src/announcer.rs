@@ -0,0 +1,72 @@
+use tubereng_core::accessibility::Announcement;
+#[cfg(target_arch = "wasm32")]
+use tubereng_core::accessibility::Politeness;
+
+/// Forwards the announcements of the application to the screen readers of the
+/// platform.
+///
+/// On wasm32, announcements are written into visually hidden ARIA live
+/// regions appended to the element holding the canvas. Native platforms have
+/// no screen reader backend: announcements are only logged there until an
+/// AccessKit adapter is added to the window.
+pub(crate) struct Announcer {
+    #[cfg(target_arch = "wasm32")]
+    polite_region: Option<web_sys::Element>,
+    #[cfg(target_arch = "wasm32")]
+    assertive_region: Option<web_sys::Element>,
+}
+
+impl Announcer {
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) fn new(container: &web_sys::Element) -> Self {
+        Self {
+            polite_region: live_region(container, "polite"),
+            assertive_region: live_region(container, "assertive"),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), allow(clippy::unused_self))]
+    pub(crate) fn announce(&self, announcements: &[Announcement]) {
+        for announcement in announcements {
+            log::info!("Announcement: {}", announcement.text);
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        for politeness in [Politeness::Polite, Politeness::Assertive] {
+            let region = match politeness {
+                Politeness::Polite => &self.polite_region,
+                Politeness::Assertive => &self.assertive_region,
+            };
+            let texts = announcements
+                .iter()
+                .filter(|announcement| announcement.politeness == politeness)
+                .map(|announcement| announcement.text.as_str())
+                .collect::<Vec<_>>();
+            if let (Some(region), false) = (region, texts.is_empty()) {
+                // Screen readers read the content of the region when it changes
+                region.set_text_content(Some(&texts.join(". ")));
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn live_region(container: &web_sys::Element, politeness: &str) -> Option<web_sys::Element> {
+    let document = web_sys::window()?.document()?;
+    let region = document.create_element("div").ok()?;
+    region.set_attribute("aria-live", politeness).ok()?;
+    region.set_attribute("aria-atomic", "true").ok()?;
+    region
+        .set_attribute(
+            "style",
+            "position:absolute;width:1px;height:1px;overflow:hidden;clip:rect(0 0 0 0);",
+        )
+        .ok()?;
+    container.append_child(&region).ok()?;
+    Some(region)
+}
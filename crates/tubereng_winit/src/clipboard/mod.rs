@@ -0,0 +1,67 @@
+//! Platform clipboards backing the `Clipboard` resource of the engine.
+//!
+//! Only the X11 and browser clipboards are supported. The other native
+//! platforms would be covered by arboard, which isn't a dependency of the
+//! engine yet.
+
+use tubereng_engine::Engine;
+
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+mod x11;
+
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+/// Text pasted into the application outside of the `Clipboard` resource. The
+/// browser only gives access to the clipboard when the user pastes, so the
+/// pasted text is forwarded to the engine as typed text. It's always empty
+/// on native platforms.
+pub(crate) struct PasteEvents {
+    #[cfg(target_arch = "wasm32")]
+    listener: Option<web::PasteListener>,
+}
+
+impl PasteEvents {
+    /// Returns the texts pasted since the last call
+    #[cfg_attr(not(target_arch = "wasm32"), allow(clippy::unused_self))]
+    pub(crate) fn drain(&self) -> Vec<String> {
+        #[cfg(target_arch = "wasm32")]
+        if let Some(listener) = &self.listener {
+            return listener.drain();
+        }
+        vec![]
+    }
+}
+
+/// Backs the `Clipboard` resource of the engine with the clipboard of the
+/// platform. The clipboard stays local to the application on the platforms
+/// without support.
+pub(crate) fn install_platform_clipboard(engine: &mut Engine) -> PasteEvents {
+    cfg_if::cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            let clipboard = web::WebClipboard::new();
+            let listener = web::PasteListener::new(&clipboard);
+            if listener.is_none() {
+                log::warn!("Couldn't listen to the paste events of the page");
+            }
+            engine.set_clipboard_backend(clipboard);
+            PasteEvents { listener }
+        } else if #[cfg(all(
+            unix,
+            not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+        ))] {
+            match x11::X11Clipboard::new() {
+                Ok(clipboard) => engine.set_clipboard_backend(clipboard),
+                Err(error) => log::warn!("Couldn't access the X11 clipboard: {error}"),
+            }
+            PasteEvents {}
+        } else {
+            let _ = engine;
+            log::warn!("The platform clipboard isn't supported, copied text stays in the application");
+            PasteEvents {}
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::{cell::RefCell, rc::Rc};
+
+use tubereng_core::clipboard::ClipboardBackend;
+use wasm_bindgen::{closure::Closure, JsCast};
+
+/// Clipboard of the browser. Reading it asynchronously would prompt the
+/// user for the permission and return the text of the previous read, so the
+/// text returned is the one last pasted into the page, or the text last
+/// copied by the application.
+pub(crate) struct WebClipboard {
+    text: Rc<RefCell<Option<String>>>,
+}
+
+impl WebClipboard {
+    pub fn new() -> Self {
+        Self {
+            text: Rc::new(RefCell::new(None)),
+        }
+    }
+}
+
+impl ClipboardBackend for WebClipboard {
+    fn get_text(&mut self) -> Option<String> {
+        self.text.borrow().clone()
+    }
+
+    fn set_text(&mut self, text: &str) {
+        *self.text.borrow_mut() = Some(text.to_string());
+        if let Some(clipboard) = browser_clipboard() {
+            let _ = clipboard.write_text(text);
+        }
+    }
+}
+
+fn browser_clipboard() -> Option<web_sys::Clipboard> {
+    Some(web_sys::window()?.navigator().clipboard())
+}
+
+/// Listens to the `paste` events of the page, whose clipboard data can be
+/// read synchronously. The pasted text is kept until drained by the runner,
+/// and becomes the text of the [`WebClipboard`].
+pub(crate) struct PasteListener {
+    document: web_sys::Document,
+    on_paste: Closure<dyn FnMut(web_sys::ClipboardEvent)>,
+    pasted: Rc<RefCell<Vec<String>>>,
+}
+
+impl PasteListener {
+    pub fn new(clipboard: &WebClipboard) -> Option<Self> {
+        let document = web_sys::window()?.document()?;
+        let pasted = Rc::new(RefCell::new(vec![]));
+        let on_paste = {
+            let clipboard_text = Rc::clone(&clipboard.text);
+            let pasted = Rc::clone(&pasted);
+            Closure::<dyn FnMut(_)>::new(move |event: web_sys::ClipboardEvent| {
+                let Some(text) = event
+                    .clipboard_data()
+                    .and_then(|data| data.get_data("text").ok())
+                    .filter(|text| !text.is_empty())
+                else {
+                    return;
+                };
+                event.prevent_default();
+                *clipboard_text.borrow_mut() = Some(text.clone());
+                pasted.borrow_mut().push(text);
+            })
+        };
+        document
+            .add_event_listener_with_callback("paste", on_paste.as_ref().unchecked_ref())
+            .ok()?;
+        Some(Self {
+            document,
+            on_paste,
+            pasted,
+        })
+    }
+
+    /// Returns the texts pasted since the last call
+    pub fn drain(&self) -> Vec<String> {
+        std::mem::take(&mut self.pasted.borrow_mut())
+    }
+}
+
+impl Drop for PasteListener {
+    fn drop(&mut self) {
+        let _ = self
+            .document
+            .remove_event_listener_with_callback("paste", self.on_paste.as_ref().unchecked_ref());
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    error::Error,
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use tubereng_core::clipboard::ClipboardBackend;
+use x11rb::{
+    connection::Connection,
+    errors::{ConnectionError, ReplyError},
+    protocol::{
+        xproto::{
+            Atom, AtomEnum, ConnectionExt as _, CreateWindowAux, EventMask, PropMode,
+            SelectionNotifyEvent, SelectionRequestEvent, Window, WindowClass,
+            SELECTION_NOTIFY_EVENT,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    COPY_DEPTH_FROM_PARENT, COPY_FROM_PARENT, CURRENT_TIME, NONE,
+};
+
+/// Time the owner of the clipboard has to send its text
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy)]
+struct Atoms {
+    clipboard: Atom,
+    targets: Atom,
+    utf8_string: Atom,
+    incr: Atom,
+    /// Property of our windows the text is transferred through
+    transfer: Atom,
+}
+
+/// Clipboard selection of the X11 server.
+///
+/// The copied text is served to the other clients by a thread owning the
+/// selection through its own connection. Large texts transferred
+/// incrementally can't be pasted.
+pub(crate) struct X11Clipboard {
+    connection: RustConnection,
+    window: Window,
+    owner_connection: Arc<RustConnection>,
+    owner_window: Window,
+    atoms: Atoms,
+    text: Arc<Mutex<Option<String>>>,
+}
+
+impl X11Clipboard {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let (connection, window) = connect()?;
+        let atoms = Atoms {
+            clipboard: intern_atom(&connection, b"CLIPBOARD")?,
+            targets: intern_atom(&connection, b"TARGETS")?,
+            utf8_string: intern_atom(&connection, b"UTF8_STRING")?,
+            incr: intern_atom(&connection, b"INCR")?,
+            transfer: intern_atom(&connection, b"TUBERENG_CLIPBOARD")?,
+        };
+        let (owner_connection, owner_window) = connect()?;
+        let owner_connection = Arc::new(owner_connection);
+        let text = Arc::new(Mutex::new(None));
+        thread::Builder::new()
+            .name("clipboard".to_string())
+            .spawn({
+                let owner_connection = Arc::clone(&owner_connection);
+                let text = Arc::clone(&text);
+                move || serve_selection(&owner_connection, atoms, &text)
+            })?;
+        Ok(Self {
+            connection,
+            window,
+            owner_connection,
+            owner_window,
+            atoms,
+            text,
+        })
+    }
+
+    fn read_text(&self) -> Result<Option<String>, ReplyError> {
+        let owner = self
+            .connection
+            .get_selection_owner(self.atoms.clipboard)?
+            .reply()?
+            .owner;
+        if owner == NONE {
+            return Ok(None);
+        }
+        if owner == self.owner_window {
+            return Ok(self
+                .text
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone());
+        }
+
+        self.connection.convert_selection(
+            self.window,
+            self.atoms.clipboard,
+            self.atoms.utf8_string,
+            self.atoms.transfer,
+            CURRENT_TIME,
+        )?;
+        self.connection.flush()?;
+        let deadline = Instant::now() + READ_TIMEOUT;
+        loop {
+            match self.connection.poll_for_event()? {
+                Some(Event::SelectionNotify(notification))
+                    if notification.selection == self.atoms.clipboard =>
+                {
+                    if notification.property == NONE {
+                        return Ok(None);
+                    }
+                    break;
+                }
+                Some(_) => {}
+                None if Instant::now() >= deadline => return Ok(None),
+                None => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+
+        let property = self
+            .connection
+            .get_property(
+                true,
+                self.window,
+                self.atoms.transfer,
+                AtomEnum::ANY,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+        if property.type_ == self.atoms.incr {
+            return Ok(None);
+        }
+        Ok(String::from_utf8(property.value).ok())
+    }
+
+    fn take_selection(&self) -> Result<(), ConnectionError> {
+        self.owner_connection.set_selection_owner(
+            self.owner_window,
+            self.atoms.clipboard,
+            CURRENT_TIME,
+        )?;
+        self.owner_connection.flush()
+    }
+}
+
+impl ClipboardBackend for X11Clipboard {
+    fn get_text(&mut self) -> Option<String> {
+        self.read_text().unwrap_or_else(|error| {
+            log::warn!("Couldn't read the X11 clipboard: {error}");
+            None
+        })
+    }
+
+    fn set_text(&mut self, text: &str) {
+        *self.text.lock().unwrap_or_else(PoisonError::into_inner) = Some(text.to_string());
+        if let Err(error) = self.take_selection() {
+            log::warn!("Couldn't write the X11 clipboard: {error}");
+        }
+    }
+}
+
+/// Connects to the X11 server, creating the hidden window the selection is
+/// transferred through
+fn connect() -> Result<(RustConnection, Window), Box<dyn Error>> {
+    let (connection, screen_index) = x11rb::connect(None)?;
+    let window = connection.generate_id()?;
+    let root = connection.setup().roots[screen_index].root;
+    connection.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        COPY_FROM_PARENT,
+        &CreateWindowAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+    )?;
+    connection.flush()?;
+    Ok((connection, window))
+}
+
+fn intern_atom(connection: &RustConnection, name: &[u8]) -> Result<Atom, ReplyError> {
+    Ok(connection.intern_atom(false, name)?.reply()?.atom)
+}
+
+/// Sends the copied text to the clients pasting it until the connection is
+/// closed
+fn serve_selection(connection: &RustConnection, atoms: Atoms, text: &Mutex<Option<String>>) {
+    while let Ok(event) = connection.wait_for_event() {
+        match event {
+            Event::SelectionRequest(request) => {
+                let text = text.lock().unwrap_or_else(PoisonError::into_inner).clone();
+                if let Err(error) = send_selection(connection, atoms, text.as_deref(), &request) {
+                    log::warn!("Couldn't send the X11 clipboard: {error}");
+                }
+            }
+            // Another client copied some text
+            Event::SelectionClear(_) => {
+                *text.lock().unwrap_or_else(PoisonError::into_inner) = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn send_selection(
+    connection: &RustConnection,
+    atoms: Atoms,
+    text: Option<&str>,
+    request: &SelectionRequestEvent,
+) -> Result<(), ConnectionError> {
+    // Obsolete clients don't set the property to transfer the text through
+    let property = if request.property == NONE {
+        request.target
+    } else {
+        request.property
+    };
+    let sent = match text {
+        Some(_) if request.target == atoms.targets => {
+            connection.change_property32(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                AtomEnum::ATOM,
+                &[atoms.targets, atoms.utf8_string],
+            )?;
+            true
+        }
+        Some(text) if request.target == atoms.utf8_string => {
+            connection.change_property8(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                atoms.utf8_string,
+                text.as_bytes(),
+            )?;
+            true
+        }
+        _ => false,
+    };
+    connection.send_event(
+        false,
+        request.requestor,
+        EventMask::NO_EVENT,
+        SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: request.time,
+            requestor: request.requestor,
+            selection: request.selection,
+            target: request.target,
+            property: if sent { property } else { NONE },
+        },
+    )?;
+    connection.flush()
+}
@@ -1,21 +1,30 @@
 #![warn(clippy::pedantic)]
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 #[cfg(target_arch = "wasm32")]
 use web_time::Instant;
 
-use tubereng_engine::Engine;
+mod announcer;
+mod clipboard;
+mod pacing;
+
+use tubereng_engine::{
+    window::{CursorIcon, FullscreenMode, WindowCommand},
+    Engine,
+};
+use tubereng_image::{Image, ImageFormat};
 use tubereng_input::{keyboard::Key, mouse::Button, Input};
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
     error::{EventLoopError, OsError},
-    event::{DeviceEvent, Event, KeyEvent, MouseButton, WindowEvent},
+    event::{DeviceEvent, ElementState, Event, Ime, KeyEvent, MouseButton, WindowEvent},
     event_loop::EventLoop,
     keyboard::{KeyCode, PhysicalKey},
-    window::WindowBuilder,
+    monitor::MonitorHandle,
+    window::{Fullscreen, Icon, Window, WindowBuilder},
 };
 
 #[derive(Debug)]
@@ -40,18 +49,17 @@ impl WinitTuberRunner {
     /// For wasm32, might panic if the window canvas cannot be added to the page.
     pub async fn run(mut engine: Engine) -> Result<(), WinitError> {
         let event_loop = EventLoop::new().map_err(WinitError::EventLoopCreationFailed)?;
-        let window = Arc::new(
-            WindowBuilder::new()
-                .with_title(engine.application_title())
-                .with_resizable(false)
-                .with_inner_size(PhysicalSize::new(800, 600))
-                .build(&event_loop)
-                .map_err(WinitError::WindowCreationFailed)?,
-        );
+        let window = Arc::new(build_window(&engine, &event_loop)?);
+        // Lets input method editors compose text, e.g. for CJK languages
+        window.set_ime_allowed(true);
         #[cfg(target_arch = "wasm32")]
-        {
+        let announcer = {
             use winit::platform::web::WindowExtWebSys;
-            let _ = window.request_inner_size(PhysicalSize::new(800, 600));
+            let window_settings = engine.window_settings();
+            let _ = window.request_inner_size(PhysicalSize::new(
+                window_settings.width,
+                window_settings.height,
+            ));
 
             web_sys::window()
                 .and_then(|win| win.document())
@@ -59,11 +67,19 @@ impl WinitTuberRunner {
                     let dst = doc.get_element_by_id(engine.application_title())?;
                     let canvas = web_sys::Element::from(window.canvas()?);
                     dst.append_child(&canvas).ok()?;
-                    Some(())
+                    Some(announcer::Announcer::new(&dst))
                 })
-                .expect("Couldn't append canvas to document body.");
-        }
+                .expect("Couldn't append canvas to document body.")
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        let announcer = announcer::Announcer::new();
+        let paste_events = clipboard::install_platform_clipboard(&mut engine);
         engine.init_graphics(window.clone()).await;
+        // The size of the window can differ from the requested one, e.g. in
+        // fullscreen
+        let PhysicalSize { width, height } = window.inner_size();
+        engine.on_window_resized(width, height);
+        let mut frame_pacer = engine.frame_rate_limit().map(pacing::FramePacer::new);
         let mut last_frame_start_instant = Instant::now();
         event_loop
             .run(move |event, elwt| match event {
@@ -73,6 +89,10 @@ impl WinitTuberRunner {
                 } => {
                     elwt.exit();
                 }
+                Event::WindowEvent {
+                    event: WindowEvent::Resized(PhysicalSize { width, height }),
+                    ..
+                } => engine.on_window_resized(width, height),
                 Event::DeviceEvent {
                     event: DeviceEvent::MouseMotion { delta },
                     ..
@@ -90,43 +110,36 @@ impl WinitTuberRunner {
                     ..
                 } => {
                     window.request_redraw();
-                    let frame_start_instant = Instant::now();
-                    let delta_time = (frame_start_instant - last_frame_start_instant).as_secs_f32();
-                    engine.update(delta_time);
-                    last_frame_start_instant = frame_start_instant;
+                    for text in paste_events.drain() {
+                        engine.on_input(Input::Text(text));
+                    }
+                    if update_frame(&mut engine, &mut frame_pacer, &mut last_frame_start_instant) {
+                        announcer.announce(&engine.drain_announcements());
+                        apply_window_commands(&mut engine, &window);
+                        if engine.exit_requested() {
+                            elwt.exit();
+                        }
+                    }
                 }
                 Event::WindowEvent {
                     event: WindowEvent::MouseInput { state, button, .. },
                     ..
                 } => match state {
-                    winit::event::ElementState::Pressed => {
+                    ElementState::Pressed => {
                         engine.on_input(Input::MouseButtonDown(WinitButton(button).into()));
                     }
-                    winit::event::ElementState::Released => {
+                    ElementState::Released => {
                         engine.on_input(Input::MouseButtonUp(WinitButton(button).into()));
                     }
                 },
                 Event::WindowEvent {
-                    event:
-                        WindowEvent::KeyboardInput {
-                            event:
-                                KeyEvent {
-                                    state,
-                                    physical_key: PhysicalKey::Code(virtual_keycode),
-                                    ..
-                                },
-                            ..
-                        },
+                    event: WindowEvent::KeyboardInput { event, .. },
                     ..
-                } => match state {
-                    winit::event::ElementState::Pressed => {
-                        engine.on_input(Input::KeyDown(WinitKeyCode(virtual_keycode).into()));
-                    }
-
-                    winit::event::ElementState::Released => {
-                        engine.on_input(Input::KeyUp(WinitKeyCode(virtual_keycode).into()));
-                    }
-                },
+                } => on_keyboard_input(&mut engine, event),
+                Event::WindowEvent {
+                    event: WindowEvent::Ime(Ime::Commit(text)),
+                    ..
+                } => engine.on_input(Input::Text(text)),
                 _ => {}
             })
             .map_err(WinitError::EventLoopRunningFailed)?;
@@ -135,6 +148,198 @@ impl WinitTuberRunner {
     }
 }
 
+/// Creates the window of the application according to its
+/// [`tubereng_engine::window::WindowSettings`]
+fn build_window(engine: &Engine, event_loop: &EventLoop<()>) -> Result<Window, WinitError> {
+    let window_settings = engine.window_settings();
+    WindowBuilder::new()
+        .with_title(engine.window_title())
+        .with_resizable(window_settings.resizable)
+        .with_inner_size(PhysicalSize::new(
+            window_settings.width,
+            window_settings.height,
+        ))
+        .with_window_icon(engine.load_window_icon().as_ref().and_then(window_icon))
+        .with_fullscreen(winit_fullscreen(
+            window_settings.fullscreen,
+            event_loop.primary_monitor(),
+        ))
+        .build(event_loop)
+        .map_err(WinitError::WindowCreationFailed)
+}
+
+/// Applies the changes of the window requested by the systems during the
+/// frame
+fn apply_window_commands(engine: &mut Engine, window: &Window) {
+    for command in engine.drain_window_commands() {
+        match command {
+            WindowCommand::SetFullscreen(mode) => {
+                window.set_fullscreen(winit_fullscreen(mode, window.current_monitor()));
+                // Not every platform reports the resize caused by the change,
+                // so the surface is reconfigured right away
+                let PhysicalSize { width, height } = window.inner_size();
+                engine.on_window_resized(width, height);
+            }
+            WindowCommand::SetTitle(title) => window.set_title(&title),
+            WindowCommand::SetInnerSize { width, height } => {
+                // The size is returned if the window has been resized
+                // synchronously, otherwise a resize event follows
+                if let Some(PhysicalSize { width, height }) =
+                    window.request_inner_size(PhysicalSize::new(width, height))
+                {
+                    engine.on_window_resized(width, height);
+                }
+            }
+            WindowCommand::SetMinSize(min_size) => window.set_min_inner_size(
+                min_size.map(|(width, height)| PhysicalSize::new(width, height)),
+            ),
+            WindowCommand::Center => center_window(window),
+            WindowCommand::SetCursorIcon(CursorIcon::Hidden) => window.set_cursor_visible(false),
+            WindowCommand::SetCursorIcon(cursor_icon) => {
+                window.set_cursor_visible(true);
+                window.set_cursor_icon(winit_cursor_icon(cursor_icon));
+            }
+        }
+    }
+}
+
+fn winit_cursor_icon(cursor_icon: CursorIcon) -> winit::window::CursorIcon {
+    match cursor_icon {
+        CursorIcon::Default | CursorIcon::Hidden => winit::window::CursorIcon::Default,
+        CursorIcon::Pointer => winit::window::CursorIcon::Pointer,
+        CursorIcon::Crosshair => winit::window::CursorIcon::Crosshair,
+        CursorIcon::Text => winit::window::CursorIcon::Text,
+        CursorIcon::Move => winit::window::CursorIcon::Move,
+        CursorIcon::Grab => winit::window::CursorIcon::Grab,
+        CursorIcon::Grabbing => winit::window::CursorIcon::Grabbing,
+        CursorIcon::NotAllowed => winit::window::CursorIcon::NotAllowed,
+        CursorIcon::Wait => winit::window::CursorIcon::Wait,
+        CursorIcon::ResizeHorizontal => winit::window::CursorIcon::EwResize,
+        CursorIcon::ResizeVertical => winit::window::CursorIcon::NsResize,
+    }
+}
+
+/// Converts an image to a window icon, whose colors mustn't be premultiplied
+/// by the alpha
+fn window_icon(image: &Image) -> Option<Icon> {
+    if image.format() != ImageFormat::RGBA8 {
+        log::warn!("The window icon must be an uncompressed image");
+        return None;
+    }
+    let mut rgba = image.data().to_vec();
+    if image.premultiplied_alpha() {
+        for pixel in rgba.chunks_exact_mut(4) {
+            let alpha = u16::from(pixel[3]);
+            if alpha == 0 {
+                continue;
+            }
+            for channel in &mut pixel[..3] {
+                // The premultiplied channel is at most the alpha, so the
+                // result fits in a u8
+                #[allow(clippy::cast_possible_truncation)]
+                let straight = ((u16::from(*channel) * 255 + alpha / 2) / alpha).min(255) as u8;
+                *channel = straight;
+            }
+        }
+    }
+    Icon::from_rgba(rgba, image.width(), image.height())
+        .inspect_err(|error| log::warn!("Invalid window icon: {error}"))
+        .ok()
+}
+
+/// Moves a window to the center of its monitor
+fn center_window(window: &Window) {
+    let Some(monitor) = window.current_monitor() else {
+        return;
+    };
+    let monitor_position = monitor.position();
+    let monitor_size = monitor.size();
+    let window_size = window.outer_size();
+    let offset = |monitor_length: u32, window_length: u32| {
+        i32::try_from(monitor_length.saturating_sub(window_length) / 2).unwrap_or_default()
+    };
+    window.set_outer_position(PhysicalPosition::new(
+        monitor_position.x + offset(monitor_size.width, window_size.width),
+        monitor_position.y + offset(monitor_size.height, window_size.height),
+    ));
+}
+
+/// Converts a fullscreen mode for a window on `monitor`. The exclusive
+/// fullscreen uses the video mode of the monitor with the highest resolution
+/// and refresh rate, and falls back to the borderless fullscreen if the
+/// monitor has none.
+fn winit_fullscreen(mode: FullscreenMode, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+    match mode {
+        FullscreenMode::Windowed => None,
+        FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        FullscreenMode::Exclusive => {
+            let video_mode = monitor.as_ref().and_then(|monitor| {
+                monitor.video_modes().max_by_key(|video_mode| {
+                    let PhysicalSize { width, height } = video_mode.size();
+                    (
+                        u64::from(width) * u64::from(height),
+                        video_mode.refresh_rate_millihertz(),
+                    )
+                })
+            });
+            Some(video_mode.map_or(Fullscreen::Borderless(monitor), Fullscreen::Exclusive))
+        }
+    }
+}
+
+/// Updates the engine once the frame rate limit allows the frame to start.
+/// Returns `false` if the frame has been skipped.
+fn update_frame(
+    engine: &mut Engine,
+    frame_pacer: &mut Option<pacing::FramePacer>,
+    last_frame_start_instant: &mut Instant,
+) -> bool {
+    let Some(wait_time) = frame_pacer
+        .as_mut()
+        .map_or(Some(Duration::ZERO), pacing::FramePacer::pace)
+    else {
+        return false;
+    };
+    let frame_start_instant = Instant::now();
+    let delta_time = (frame_start_instant - *last_frame_start_instant).as_secs_f32();
+    engine.update(delta_time);
+    engine.on_frame_paced(
+        frame_start_instant.elapsed().as_secs_f32(),
+        wait_time.as_secs_f32(),
+    );
+    *last_frame_start_instant = frame_start_instant;
+    true
+}
+
+/// Forwards a key press or release along with the text it typed, if any,
+/// the characters depending on the keyboard layout
+fn on_keyboard_input(engine: &mut Engine, event: KeyEvent) {
+    let key = match event.physical_key {
+        PhysicalKey::Code(key_code) => Some(WinitKeyCode(key_code).into()),
+        PhysicalKey::Unidentified(_) => None,
+    };
+    match event.state {
+        ElementState::Pressed => {
+            if let Some(key) = key {
+                engine.on_input(Input::KeyDown(key));
+            }
+            // Keys like backspace or enter type control characters, they are
+            // handled through their key instead
+            if let Some(text) = event
+                .text
+                .filter(|text| !text.chars().any(char::is_control))
+            {
+                engine.on_input(Input::Text(text.to_string()));
+            }
+        }
+        ElementState::Released => {
+            if let Some(key) = key {
+                engine.on_input(Input::KeyUp(key));
+            }
+        }
+    }
+}
+
 struct WinitButton(MouseButton);
 impl From<WinitButton> for Button {
     fn from(value: WinitButton) -> Self {
@@ -154,6 +359,14 @@ impl From<WinitKeyCode> for Key {
         let virtual_key_code = value.0;
         match virtual_key_code {
             KeyCode::Escape => Key::Escape,
+            KeyCode::ShiftLeft => Key::LShift,
+            KeyCode::ShiftRight => Key::RShift,
+            KeyCode::ControlLeft => Key::LControl,
+            KeyCode::ControlRight => Key::RControl,
+            KeyCode::AltLeft => Key::LAlt,
+            KeyCode::AltRight => Key::RAlt,
+            KeyCode::SuperLeft => Key::LSuper,
+            KeyCode::SuperRight => Key::RSuper,
             KeyCode::Space => Key::Space,
             KeyCode::ArrowUp => Key::ArrowUp,
             KeyCode::ArrowDown => Key::ArrowDown,
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+/// Time before the start of a frame during which the pacer spins instead of
+/// sleeping, as sleeping can overshoot by the resolution of the OS scheduler
+#[cfg(not(target_arch = "wasm32"))]
+const SPIN_DURATION: Duration = Duration::from_millis(2);
+
+/// Delays the frames so that they start at most `max_frame_rate` times per
+/// second
+pub(crate) struct FramePacer {
+    frame_duration: Duration,
+    next_frame_start: Option<Instant>,
+}
+
+impl FramePacer {
+    pub fn new(max_frame_rate: f32) -> Self {
+        Self {
+            frame_duration: Duration::from_secs_f64(1.0 / f64::from(max_frame_rate)),
+            next_frame_start: None,
+        }
+    }
+
+    /// Returns the time left at `now` before the next frame can start
+    pub fn remaining(&self, now: Instant) -> Duration {
+        self.next_frame_start
+            .map_or(Duration::ZERO, |next_frame_start| {
+                next_frame_start.saturating_duration_since(now)
+            })
+    }
+
+    /// Schedules the frame following a frame starting at `now`. The frames
+    /// keep their cadence when a frame is slightly late, but a frame late by
+    /// more than a frame duration doesn't make the next ones rush.
+    pub fn start_frame(&mut self, now: Instant) {
+        let next_frame_start = match self.next_frame_start {
+            Some(frame_start) if now < frame_start + self.frame_duration => {
+                frame_start + self.frame_duration
+            }
+            _ => now + self.frame_duration,
+        };
+        self.next_frame_start = Some(next_frame_start);
+    }
+
+    /// Waits until the next frame can start and starts it, returning the
+    /// time waited. Sleeps then spins until the start of the frame.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[allow(clippy::unnecessary_wraps)] // The frames are only skipped on wasm32
+    pub fn pace(&mut self) -> Option<Duration> {
+        let wait_start = Instant::now();
+        if let Some(sleep_duration) = self.remaining(wait_start).checked_sub(SPIN_DURATION) {
+            std::thread::sleep(sleep_duration);
+        }
+        while !self.remaining(Instant::now()).is_zero() {
+            std::hint::spin_loop();
+        }
+        let frame_start = Instant::now();
+        self.start_frame(frame_start);
+        Some(frame_start - wait_start)
+    }
+
+    /// Starts the next frame if it can start now, returning `None` if the
+    /// frame must be skipped, the browser not allowing to block until then
+    #[cfg(target_arch = "wasm32")]
+    pub fn pace(&mut self) -> Option<Duration> {
+        let now = Instant::now();
+        if !self.remaining(now).is_zero() {
+            return None;
+        }
+        self.start_frame(now);
+        Some(Duration::ZERO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_pacer_cadence() {
+        let mut pacer = FramePacer::new(50.0);
+        let start = Instant::now();
+        assert_eq!(pacer.remaining(start), Duration::ZERO);
+
+        pacer.start_frame(start);
+        assert_eq!(
+            pacer.remaining(start + Duration::from_millis(5)),
+            Duration::from_millis(15)
+        );
+
+        // A slightly late frame keeps the cadence
+        pacer.start_frame(start + Duration::from_millis(25));
+        assert_eq!(
+            pacer.remaining(start + Duration::from_millis(25)),
+            Duration::from_millis(15)
+        );
+    }
+
+    #[test]
+    fn frame_pacer_late_frame() {
+        let mut pacer = FramePacer::new(50.0);
+        let start = Instant::now();
+        pacer.start_frame(start);
+
+        pacer.start_frame(start + Duration::from_millis(100));
+
+        assert_eq!(
+            pacer.remaining(start + Duration::from_millis(100)),
+            Duration::from_millis(20)
+        );
+    }
+}
@@ -1,27 +1,7 @@
 #![warn(clippy::all)]
 
 use log::warn;
-use tubereng::{
-    asset::AssetStore,
-    core::{DeltaTime, Transform},
-    ecs::{
-        commands::CommandQueue,
-        relationship::ChildOf,
-        system::{stages, Res, ResMut, Q},
-    },
-    engine::Engine,
-    image::Image,
-    input::{keyboard::Key, InputState},
-    math::vector::{Vector2f, Vector3f},
-    renderer::texture,
-    renderer::{
-        camera,
-        sprite::{AnimatedSprite, AnimationState, Sprite},
-        texture::Rect,
-        GraphicsState,
-    },
-    winit::WinitTuberRunner,
-};
+use tubereng::{prelude::*, renderer::texture::Rect};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
@@ -31,19 +11,11 @@ struct Player {
     acceleration: Vector2f,
     velocity: Vector2f,
 }
-#[derive(Debug)]
-struct Enemy;
 
+#[cfg(target_arch = "wasm32")]
+use tubereng::asset::vfs::fetch::WebFs;
 #[cfg(not(target_arch = "wasm32"))]
 use tubereng::asset::vfs::filesystem::FileSystem;
-#[cfg(target_arch = "wasm32")]
-use {
-    include_dir::{include_dir, Dir},
-    tubereng::asset::vfs::web::Web,
-};
-
-#[cfg(target_arch = "wasm32")]
-static ASSETS: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/assets");
 
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
 pub async fn run() {
@@ -56,8 +28,15 @@ pub async fn run() {
         }
     }
 
+    // The assets are served next to the page. The texture is fetched before
+    // the engine starts so that the init system can read it, the assets read
+    // later being fetched asynchronously
+    #[cfg(target_arch = "wasm32")]
+    let vfs = WebFs::new("assets");
     #[cfg(target_arch = "wasm32")]
-    let vfs = Web::new(&ASSETS);
+    vfs.preload(&["texture_atlas.png"])
+        .await
+        .expect("Couldn't fetch the assets");
     #[cfg(not(target_arch = "wasm32"))]
     let vfs = FileSystem;
 
@@ -68,17 +47,17 @@ pub async fn run() {
     WinitTuberRunner::run(engine).await.unwrap();
 }
 
-fn init(queue: &CommandQueue, asset_store: ResMut<AssetStore>, mut gfx: ResMut<GraphicsState>) {
-    let image = asset_store
-        .load_without_storing::<Image>("texture_atlas.png")
+fn init(
+    queue: &CommandQueue,
+    asset_store: ResMut<AssetStore>,
+    mut texture_assets: ResMut<TextureAssets>,
+    mut gfx: ResMut<GraphicsState>,
+) {
+    // Reloaded when the image is modified with the `hot-reload` feature
+    let texture_id = texture_assets
+        .load(&asset_store, &mut gfx, "texture_atlas.png")
         .unwrap();
 
-    let texture_id = gfx.load_texture(&texture::Descriptor {
-        data: image.data(),
-        width: image.width(),
-        height: image.height(),
-    });
-
     let camera = queue.insert((
         camera::D2::new(800.0, 600.0),
         camera::Active,
@@ -89,17 +68,17 @@ fn init(queue: &CommandQueue, asset_store: ResMut<AssetStore>, mut gfx: ResMut<G
         },
     ));
 
-    queue.insert((
-        Transform {
+    queue.insert(SpriteBundle {
+        transform: Transform {
             translation: Vector3f::new(0.0, 0.0, -10.0),
             scale: Vector3f::uniform(12.5),
             ..Default::default()
         },
-        Sprite {
+        sprite: Sprite {
             texture: texture_id,
             texture_rect: Some(Rect::new(48.0, 0.0, 64.0, 48.0)),
         },
-    ));
+    });
 
     let player = queue.insert((
         Player::default(),
@@ -110,45 +89,50 @@ fn init(queue: &CommandQueue, asset_store: ResMut<AssetStore>, mut gfx: ResMut<G
         },
     ));
 
-    let player_sprite = queue.insert((
-        Transform {
+    let player_sprite = queue.insert(AnimatedSpriteBundle {
+        transform: Transform {
             scale: Vector3f::new(4.0, 4.0, 4.0),
             ..Default::default()
         },
-        AnimatedSprite {
+        animated_sprite: AnimatedSprite {
             texture_atlas: texture_id,
             animation: AnimationState {
                 animations: vec![vec![
                     Rect::new(16.0, 0.0, 16.0, 16.0),
                     Rect::new(32.0, 0.0, 16.0, 16.0),
                 ]],
-                current_animation: 0,
-                current_frame: 0,
                 secs_per_frame: 0.5,
-                ticks: 0.0,
+                ..Default::default()
             },
         },
-    ));
+    });
 
     queue.insert_relationship::<ChildOf>(player_sprite, player);
     queue.insert_relationship::<ChildOf>(camera, player);
 
     for i in 0..13 {
-        queue.insert((
-            Transform {
+        queue.insert(SpriteBundle {
+            transform: Transform {
                 translation: Vector3f::new(i as f32 * 16.0, 600.0 / 4.0 - 16.0, 0.0),
                 scale: Vector3f::new(4.0, 4.0, 4.0),
                 ..Default::default()
             },
-            Sprite {
+            sprite: Sprite {
                 texture: texture_id,
                 texture_rect: Some(Rect::new(0.0, 0.0, 16.0, 16.0)),
             },
-        ));
+        });
     }
 
     queue.register_system(&stages::Update, move_player_grounded_system);
     queue.register_system(&stages::Update, move_player_jumping_system);
+    queue.register_system(&stages::Update, exit_on_escape_system);
+}
+
+fn exit_on_escape_system(input_state: Res<InputState>, mut app_exit: ResMut<AppExit>) {
+    if input_state.keyboard.is_key_down(Key::Escape) {
+        app_exit.request();
+    }
 }
 
 #[derive(Debug)]
@@ -159,7 +143,7 @@ pub struct Grounded;
 fn move_player_grounded_system(
     queue: &CommandQueue,
     mut query_player: Q<(&mut Player, &mut Transform, &Grounded)>,
-    delta_time: Res<DeltaTime>,
+    time: Res<Time>,
     input_state: Res<InputState>,
 ) {
     const MAX_PLAYER_VELOCITY_X: f32 = 200.0;
@@ -168,15 +152,9 @@ fn move_player_grounded_system(
     let Some((player_id, (mut player, mut transform, _))) = query_player.first_with_id() else {
         return;
     };
-    let delta_time = delta_time.0;
+    let delta_time = time.delta();
 
-    if input_state.keyboard.is_key_down(Key::D) {
-        player.acceleration.x = 1.0;
-    } else if input_state.keyboard.is_key_down(Key::A) {
-        player.acceleration.x = -1.0;
-    } else {
-        player.acceleration.x = 0.0;
-    }
+    player.acceleration.x = input_state.keyboard.axis(Key::A, Key::D);
 
     if input_state.keyboard.is_key_down(Key::W) {
         player.acceleration.y = -0.2;
@@ -194,18 +172,16 @@ fn move_player_grounded_system(
     }
 
     player.velocity.x += player.acceleration.x;
-    if player.velocity.x > MAX_PLAYER_VELOCITY_X {
-        player.velocity.x = MAX_PLAYER_VELOCITY_X;
-    } else if player.velocity.x < -MAX_PLAYER_VELOCITY_X {
-        player.velocity.x = -MAX_PLAYER_VELOCITY_X;
-    }
+    player.velocity.x = player
+        .velocity
+        .x
+        .clamp(-MAX_PLAYER_VELOCITY_X, MAX_PLAYER_VELOCITY_X);
 
     player.velocity.y += player.acceleration.y;
-    if player.velocity.y > MAX_PLAYER_VELOCITY_Y {
-        player.velocity.y = MAX_PLAYER_VELOCITY_Y;
-    } else if player.velocity.y < -MAX_PLAYER_VELOCITY_Y {
-        player.velocity.y = -MAX_PLAYER_VELOCITY_Y;
-    }
+    player.velocity.y = player
+        .velocity
+        .y
+        .clamp(-MAX_PLAYER_VELOCITY_Y, MAX_PLAYER_VELOCITY_Y);
 
     transform.translation.x += player.velocity.x * delta_time;
     transform.translation.y += player.velocity.y * delta_time;
@@ -222,7 +198,7 @@ fn move_player_jumping_system(
         &Jumping,
         &mut MaxJumpHeightReached,
     )>,
-    delta_time: Res<DeltaTime>,
+    time: Res<Time>,
     input_state: Res<InputState>,
 ) {
     const MAX_PLAYER_VELOCITY_X: f32 = 200.0;
@@ -234,7 +210,7 @@ fn move_player_jumping_system(
     else {
         return;
     };
-    let delta_time = delta_time.0;
+    let delta_time = time.delta();
 
     if !max_jump_height_reached.0 && input_state.keyboard.is_key_down(Key::W) {
         player.acceleration.y -= 0.02;
@@ -243,13 +219,7 @@ fn move_player_jumping_system(
         }
     }
 
-    if input_state.keyboard.is_key_down(Key::D) {
-        player.acceleration.x = 1.0;
-    } else if input_state.keyboard.is_key_down(Key::A) {
-        player.acceleration.x = -1.0;
-    } else {
-        player.acceleration.x = 0.0;
-    }
+    player.acceleration.x = input_state.keyboard.axis(Key::A, Key::D);
 
     if player.velocity.x > 0.0 && player.acceleration.x.abs() < 0.01 {
         player.velocity.x -= FRICTION;
@@ -258,19 +228,17 @@ fn move_player_jumping_system(
     }
 
     player.velocity.x += player.acceleration.x;
-    if player.velocity.x > MAX_PLAYER_VELOCITY_X {
-        player.velocity.x = MAX_PLAYER_VELOCITY_X;
-    } else if player.velocity.x < -MAX_PLAYER_VELOCITY_X {
-        player.velocity.x = -MAX_PLAYER_VELOCITY_X;
-    }
+    player.velocity.x = player
+        .velocity
+        .x
+        .clamp(-MAX_PLAYER_VELOCITY_X, MAX_PLAYER_VELOCITY_X);
 
     player.acceleration.y += GRAVITY;
     player.velocity.y += player.acceleration.y;
-    if player.velocity.y > MAX_PLAYER_VELOCITY_Y {
-        player.velocity.y = MAX_PLAYER_VELOCITY_Y;
-    } else if player.velocity.y < -MAX_PLAYER_VELOCITY_Y {
-        player.velocity.y = -MAX_PLAYER_VELOCITY_Y;
-    }
+    player.velocity.y = player
+        .velocity
+        .y
+        .clamp(-MAX_PLAYER_VELOCITY_Y, MAX_PLAYER_VELOCITY_Y);
 
     transform.translation.x += player.velocity.x * delta_time;
     transform.translation.y += player.velocity.y * delta_time;
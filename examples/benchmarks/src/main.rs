@@ -0,0 +1,111 @@
+//! Stress scenes printing standardized metrics, to measure the impact of
+//! engine changes on realistic workloads:
+//! ```sh
+//! cargo run --release -p benchmarks -- [SCENE...] [--frames N] [--warmup N] [--format csv|json]
+//! ```
+//! The scenes are rendered without a window, all of them running if none is
+//! given. Frames are simulated with a fixed delta time so that runs are
+//! reproducible.
+
+use std::time::Instant;
+
+use metrics::{Format, FrameMetrics, SceneMetrics};
+use scenes::{Scene, HEIGHT, WIDTH};
+
+mod metrics;
+mod scenes;
+
+const DELTA_TIME: f32 = 1.0 / 60.0;
+
+struct Options {
+    scenes: Vec<Scene>,
+    frames: usize,
+    warmup_frames: usize,
+    format: Format,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self {
+            scenes: vec![],
+            frames: 300,
+            warmup_frames: 30,
+            format: Format::Csv,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or(format!("Missing value for {name}"));
+            match arg.as_str() {
+                "--frames" => {
+                    options.frames = value("--frames")?
+                        .parse()
+                        .map_err(|_| "Invalid frame count".to_string())?;
+                }
+                "--warmup" => {
+                    options.warmup_frames = value("--warmup")?
+                        .parse()
+                        .map_err(|_| "Invalid warmup frame count".to_string())?;
+                }
+                "--format" => {
+                    let format = value("--format")?;
+                    options.format =
+                        Format::from_name(&format).ok_or(format!("Unknown format {format}"))?;
+                }
+                name => options
+                    .scenes
+                    .push(Scene::from_name(name).ok_or(format!("Unknown scene {name}"))?),
+            }
+        }
+        if options.frames == 0 {
+            return Err("The frame count must be positive".to_string());
+        }
+        if options.scenes.is_empty() {
+            options.scenes = Scene::ALL.to_vec();
+        }
+        Ok(options)
+    }
+}
+
+/// Runs a scene, returns `None` if no graphics adapter is available
+fn run(scene: Scene, options: &Options) -> Option<SceneMetrics> {
+    let mut engine = scene.engine();
+    if !pollster::block_on(engine.init_headless_graphics(WIDTH, HEIGHT)) {
+        return None;
+    }
+
+    let mut frames = Vec::with_capacity(options.frames);
+    for frame in 0..options.warmup_frames + options.frames {
+        let start = Instant::now();
+        engine.update(DELTA_TIME);
+        let cpu_ms = start.elapsed().as_secs_f32() * 1000.0;
+        if frame >= options.warmup_frames {
+            frames.push(FrameMetrics::collect(&engine, cpu_ms));
+        }
+    }
+    SceneMetrics::summarize(scene.name(), &frames)
+}
+
+fn main() {
+    env_logger::init();
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}");
+            let scene_names = Scene::ALL.map(Scene::name).join("|");
+            eprintln!(
+                "Usage: benchmarks [{scene_names}...] [--frames N] [--warmup N] [--format csv|json]"
+            );
+            std::process::exit(2);
+        }
+    };
+
+    let mut metrics = vec![];
+    for &scene in &options.scenes {
+        log::info!("Running the {} scene", scene.name());
+        let Some(scene_metrics) = run(scene, &options) else {
+            eprintln!("No graphics adapter is available");
+            std::process::exit(1);
+        };
+        metrics.push(scene_metrics);
+    }
+    println!("{}", metrics::format(&metrics, options.format));
+}
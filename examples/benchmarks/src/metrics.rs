@@ -0,0 +1,150 @@
+use std::fmt::Write as _;
+
+use tubereng::{prelude::Engine, renderer::stats::RenderStats};
+
+/// Metrics of a frame of a scene
+#[derive(Debug, Clone, Copy)]
+pub struct FrameMetrics {
+    /// Time spent in `Engine::update`, including the recording and
+    /// submission of the render passes
+    pub cpu_ms: f32,
+    /// GPU time of the render passes, 0 if timestamp queries are unsupported
+    pub gpu_ms: f32,
+    pub draw_calls: u32,
+    pub vertex_count: u32,
+    pub entity_count: usize,
+}
+
+impl FrameMetrics {
+    pub fn collect(engine: &Engine, cpu_ms: f32) -> Self {
+        let ecs = engine.ecs();
+        let (gpu_ms, draw_calls, vertex_count) =
+            ecs.resource::<RenderStats>()
+                .map_or((0.0, 0, 0), |render_stats| {
+                    (
+                        render_stats.total_gpu_time_ms(),
+                        render_stats.total_draw_calls(),
+                        render_stats
+                            .pass_draw_stats()
+                            .iter()
+                            .map(|stats| stats.vertex_count)
+                            .sum(),
+                    )
+                });
+        Self {
+            cpu_ms,
+            gpu_ms,
+            draw_calls,
+            vertex_count,
+            entity_count: ecs.entity_count(),
+        }
+    }
+}
+
+/// Summary of the measured frames of a scene
+#[derive(Debug, Clone)]
+pub struct SceneMetrics {
+    pub scene: &'static str,
+    pub frames: usize,
+    pub mean_cpu_ms: f32,
+    pub p95_cpu_ms: f32,
+    pub mean_gpu_ms: f32,
+    pub draw_calls: u32,
+    pub vertex_count: u32,
+    pub entity_count: usize,
+}
+
+impl SceneMetrics {
+    /// Summarizes the frames of a scene, the counts being the ones of the
+    /// last frame. Returns `None` if there is no frame.
+    pub fn summarize(scene: &'static str, frames: &[FrameMetrics]) -> Option<Self> {
+        let last = frames.last()?;
+        let mut cpu_times = frames.iter().map(|frame| frame.cpu_ms).collect::<Vec<_>>();
+        cpu_times.sort_by(f32::total_cmp);
+        let p95_index = (cpu_times.len() * 95 / 100).min(cpu_times.len() - 1);
+        #[allow(clippy::cast_precision_loss)]
+        let frame_count = frames.len() as f32;
+        Some(Self {
+            scene,
+            frames: frames.len(),
+            mean_cpu_ms: cpu_times.iter().sum::<f32>() / frame_count,
+            p95_cpu_ms: cpu_times[p95_index],
+            // Folded from 0.0 as an empty f32 sum is -0.0
+            mean_gpu_ms: frames.iter().fold(0.0, |sum, frame| sum + frame.gpu_ms) / frame_count,
+            draw_calls: last.draw_calls,
+            vertex_count: last.vertex_count,
+            entity_count: last.entity_count,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+const COLUMNS: [&str; 8] = [
+    "scene",
+    "frames",
+    "mean_cpu_ms",
+    "p95_cpu_ms",
+    "mean_gpu_ms",
+    "draw_calls",
+    "vertices",
+    "entities",
+];
+
+/// Formats the metrics of the scenes, one row or object per scene
+pub fn format(metrics: &[SceneMetrics], format: Format) -> String {
+    let rows = metrics.iter().map(|scene| {
+        [
+            scene.scene.to_string(),
+            scene.frames.to_string(),
+            format!("{:.3}", scene.mean_cpu_ms),
+            format!("{:.3}", scene.p95_cpu_ms),
+            format!("{:.3}", scene.mean_gpu_ms),
+            scene.draw_calls.to_string(),
+            scene.vertex_count.to_string(),
+            scene.entity_count.to_string(),
+        ]
+    });
+
+    let mut output = String::new();
+    match format {
+        Format::Csv => {
+            output.push_str(&COLUMNS.join(","));
+            for row in rows {
+                let _ = write!(output, "\n{}", row.join(","));
+            }
+        }
+        Format::Json => {
+            output.push('[');
+            for (i, row) in rows.enumerate() {
+                output.push_str(if i == 0 { "\n  {" } else { ",\n  {" });
+                for (j, (column, value)) in COLUMNS.iter().zip(row).enumerate() {
+                    let separator = if j == 0 { "" } else { ", " };
+                    // The scene name is the only string value
+                    if j == 0 {
+                        let _ = write!(output, "{separator}\"{column}\": \"{value}\"");
+                    } else {
+                        let _ = write!(output, "{separator}\"{column}\": {value}");
+                    }
+                }
+                output.push('}');
+            }
+            output.push_str("\n]");
+        }
+    }
+    output
+}
@@ -0,0 +1,55 @@
+use tubereng::{math::quaternion::Quaternion, prelude::*};
+
+use super::{HEIGHT, WIDTH};
+
+const CHAIN_COUNT: usize = 10;
+const CHAIN_DEPTH: usize = 100;
+const LINK_SIZE: f32 = 4.0;
+
+/// Root of a chain, rotated every frame so that the effective transforms of
+/// the whole chain are computed again
+#[derive(Debug)]
+struct Spin {
+    angle: f32,
+}
+
+pub fn init(queue: &CommandQueue, mut gfx: ResMut<GraphicsState>) {
+    let texture = super::setup_graphics(&mut gfx);
+    super::insert_camera(queue);
+
+    #[allow(clippy::cast_precision_loss)]
+    for chain in 0..CHAIN_COUNT {
+        let mut parent = queue.insert((
+            Transform {
+                translation: Vector3f::new(
+                    WIDTH as f32 / 2.0,
+                    (chain as f32 + 0.5) * HEIGHT as f32 / CHAIN_COUNT as f32,
+                    0.0,
+                ),
+                ..Default::default()
+            },
+            super::square_sprite(texture, LINK_SIZE),
+            Spin { angle: 0.0 },
+        ));
+        for _ in 1..CHAIN_DEPTH {
+            let link = queue.insert((
+                Transform {
+                    translation: Vector3f::new(4.0, 0.0, 0.0),
+                    rotation: Quaternion::from_axis_angle(&Vector3f::new(0.0, 0.0, 1.0), 0.05),
+                    ..Default::default()
+                },
+                super::square_sprite(texture, LINK_SIZE),
+            ));
+            queue.insert_relationship::<ChildOf>(link, parent);
+            parent = link;
+        }
+    }
+    queue.register_system(&stages::Update, spin_roots_system);
+}
+
+fn spin_roots_system(time: Res<Time>, mut query_roots: Q<(&mut Spin, &mut Transform)>) {
+    for (mut spin, mut transform) in query_roots.iter() {
+        spin.angle += time.delta();
+        transform.rotation = Quaternion::from_axis_angle(&Vector3f::new(0.0, 0.0, 1.0), spin.angle);
+    }
+}
@@ -0,0 +1,115 @@
+use tubereng::{asset::vfs::filesystem::FileSystem, prelude::*, renderer::texture::Rect};
+
+mod hierarchy;
+mod particles;
+mod sprites;
+mod tilemap;
+
+pub const WIDTH: u32 = 800;
+pub const HEIGHT: u32 = 600;
+
+/// Stress scene, sized to stay within the entity limit of the ECS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scene {
+    /// Moving sprites sharing a texture
+    Sprites,
+    /// Long chains of parented sprites, their roots spinning every frame
+    Hierarchy,
+    /// Short lived particles, deleted and respawned through commands
+    Particles,
+    /// Tile grid scrolled under the camera
+    Tilemap,
+}
+
+impl Scene {
+    pub const ALL: [Scene; 4] = [
+        Scene::Sprites,
+        Scene::Hierarchy,
+        Scene::Particles,
+        Scene::Tilemap,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scene::Sprites => "sprites",
+            Scene::Hierarchy => "hierarchy",
+            Scene::Particles => "particles",
+            Scene::Tilemap => "tilemap",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scene| scene.name() == name)
+    }
+
+    /// Builds an engine running the scene
+    pub fn engine(self) -> Engine {
+        let mut builder = Engine::builder();
+        builder.with_application_title("benchmarks");
+        match self {
+            Scene::Sprites => builder.with_init_system(sprites::init),
+            Scene::Hierarchy => builder.with_init_system(hierarchy::init),
+            Scene::Particles => builder.with_init_system(particles::init),
+            Scene::Tilemap => builder.with_init_system(tilemap::init),
+        };
+        builder.build(FileSystem)
+    }
+}
+
+/// Size of the white texture the sprites are drawn with
+const TEXTURE_SIZE: u32 = 32;
+
+/// Loads a white texture and enables GPU timing when it is supported
+fn setup_graphics(gfx: &mut GraphicsState) -> texture::Id {
+    gfx.set_gpu_timing_enabled(true);
+    gfx.load_texture(&texture::Descriptor {
+        data: &[255; (TEXTURE_SIZE * TEXTURE_SIZE * 4) as usize],
+        width: TEXTURE_SIZE,
+        height: TEXTURE_SIZE,
+        premultiplied_alpha: true,
+        format: texture::Format::Rgba8,
+    })
+}
+
+/// Returns a square sprite of the white texture. The sprites are sized with
+/// their texture rect rather than their scale, which would also scale their
+/// translation.
+fn square_sprite(texture: texture::Id, size: f32) -> Sprite {
+    Sprite {
+        texture,
+        texture_rect: Some(Rect::new(0.0, 0.0, size, size)),
+    }
+}
+
+fn insert_camera(queue: &CommandQueue) -> EntityId {
+    #[allow(clippy::cast_precision_loss)]
+    queue.insert((
+        camera::D2::new(WIDTH as f32, HEIGHT as f32),
+        camera::Active,
+        Transform::default(),
+    ))
+}
+
+/// Xorshift generator, so that runs are reproducible
+#[derive(Debug)]
+struct Rng(u32);
+
+impl Rng {
+    fn new() -> Self {
+        Self(0x9E37_79B9)
+    }
+
+    /// Returns a number between 0 and 1
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        #[allow(clippy::cast_precision_loss)]
+        let value = (self.0 >> 8) as f32 / (1 << 24) as f32;
+        value
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next()
+    }
+}
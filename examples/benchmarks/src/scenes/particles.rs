@@ -0,0 +1,68 @@
+use tubereng::prelude::*;
+
+use super::{Rng, HEIGHT, WIDTH};
+
+const PARTICLE_COUNT: usize = 1000;
+const PARTICLE_SIZE: f32 = 4.0;
+const GRAVITY: f32 = 200.0;
+
+#[derive(Debug)]
+struct Particle {
+    velocity: Vector2f,
+    life: f32,
+}
+
+/// Texture the particles are drawn with
+#[derive(Debug, Clone, Copy)]
+struct ParticleTexture(texture::Id);
+
+pub fn init(queue: &CommandQueue, mut gfx: ResMut<GraphicsState>) {
+    let texture = super::setup_graphics(&mut gfx);
+    super::insert_camera(queue);
+
+    let mut rng = Rng::new();
+    for _ in 0..PARTICLE_COUNT {
+        spawn_particle(queue, texture, &mut rng);
+    }
+    queue.insert_resource(rng);
+    queue.insert_resource(ParticleTexture(texture));
+    queue.register_system(&stages::Update, update_particles_system);
+}
+
+/// Spawns a particle at the emitter, in the middle of the bottom edge of the
+/// viewport
+fn spawn_particle(queue: &CommandQueue, texture: texture::Id, rng: &mut Rng) {
+    #[allow(clippy::cast_precision_loss)]
+    queue.insert((
+        Transform {
+            translation: Vector3f::new(WIDTH as f32 / 2.0, HEIGHT as f32, 0.0),
+            ..Default::default()
+        },
+        super::square_sprite(texture, PARTICLE_SIZE),
+        Particle {
+            velocity: Vector2f::new(rng.range(-150.0, 150.0), rng.range(-450.0, -250.0)),
+            life: rng.range(0.5, 2.0),
+        },
+    ));
+}
+
+/// Moves the particles, replacing the dead ones with new particles
+fn update_particles_system(
+    queue: &CommandQueue,
+    time: Res<Time>,
+    particle_texture: Res<ParticleTexture>,
+    mut rng: ResMut<Rng>,
+    mut query_particles: Q<(&mut Particle, &mut Transform)>,
+) {
+    for (entity_id, (mut particle, mut transform)) in query_particles.iter_with_ids() {
+        particle.life -= time.delta();
+        if particle.life <= 0.0 {
+            queue.delete(entity_id);
+            spawn_particle(queue, particle_texture.0, &mut rng);
+            continue;
+        }
+        particle.velocity.y += GRAVITY * time.delta();
+        transform.translation.x += particle.velocity.x * time.delta();
+        transform.translation.y += particle.velocity.y * time.delta();
+    }
+}
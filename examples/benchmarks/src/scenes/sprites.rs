@@ -0,0 +1,51 @@
+use tubereng::prelude::*;
+
+use super::{Rng, HEIGHT, WIDTH};
+
+const SPRITE_COUNT: usize = 1000;
+const SPRITE_SIZE: f32 = 8.0;
+
+#[derive(Debug)]
+struct Velocity(Vector2f);
+
+pub fn init(queue: &CommandQueue, mut gfx: ResMut<GraphicsState>) {
+    let texture = super::setup_graphics(&mut gfx);
+    super::insert_camera(queue);
+
+    let mut rng = Rng::new();
+    #[allow(clippy::cast_precision_loss)]
+    for _ in 0..SPRITE_COUNT {
+        queue.insert((
+            Transform {
+                translation: Vector3f::new(
+                    rng.range(0.0, WIDTH as f32 - SPRITE_SIZE),
+                    rng.range(0.0, HEIGHT as f32 - SPRITE_SIZE),
+                    0.0,
+                ),
+                ..Default::default()
+            },
+            super::square_sprite(texture, SPRITE_SIZE),
+            Velocity(Vector2f::new(
+                rng.range(-100.0, 100.0),
+                rng.range(-100.0, 100.0),
+            )),
+        ));
+    }
+    queue.register_system(&stages::Update, move_sprites_system);
+}
+
+/// Moves the sprites, bouncing on the edges of the viewport
+fn move_sprites_system(time: Res<Time>, mut query_sprites: Q<(&mut Velocity, &mut Transform)>) {
+    #[allow(clippy::cast_precision_loss)]
+    let bounds = (WIDTH as f32 - SPRITE_SIZE, HEIGHT as f32 - SPRITE_SIZE);
+    for (mut velocity, mut transform) in query_sprites.iter() {
+        transform.translation.x += velocity.0.x * time.delta();
+        transform.translation.y += velocity.0.y * time.delta();
+        if !(0.0..bounds.0).contains(&transform.translation.x) {
+            velocity.0.x = -velocity.0.x;
+        }
+        if !(0.0..bounds.1).contains(&transform.translation.y) {
+            velocity.0.y = -velocity.0.y;
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use tubereng::prelude::*;
+
+use super::WIDTH;
+
+const TILE_SIZE: f32 = 32.0;
+const COLUMNS: usize = 40;
+const ROWS: usize = 20;
+const SCROLL_SPEED: f32 = 120.0;
+
+/// Camera scrolling back and forth over the tilemap
+#[derive(Debug)]
+struct Scroll {
+    direction: f32,
+}
+
+pub fn init(queue: &CommandQueue, mut gfx: ResMut<GraphicsState>) {
+    let texture = super::setup_graphics(&mut gfx);
+    let camera = super::insert_camera(queue);
+    queue.insert_component(camera, Scroll { direction: 1.0 });
+
+    #[allow(clippy::cast_precision_loss)]
+    queue.insert_batch((0..ROWS * COLUMNS).map(|i| {
+        let (row, column) = (i / COLUMNS, i % COLUMNS);
+        SpriteBundle {
+            transform: Transform {
+                translation: Vector3f::new(column as f32 * TILE_SIZE, row as f32 * TILE_SIZE, 0.0),
+                ..Default::default()
+            },
+            sprite: super::square_sprite(texture, TILE_SIZE),
+        }
+    }));
+    queue.register_system(&stages::Update, scroll_camera_system);
+}
+
+/// Scrolls the camera, half of the tiles being outside of its view
+fn scroll_camera_system(time: Res<Time>, mut query_camera: Q<(&mut Scroll, &mut Transform)>) {
+    #[allow(clippy::cast_precision_loss)]
+    let max_x = COLUMNS as f32 * TILE_SIZE - WIDTH as f32;
+    for (mut scroll, mut transform) in query_camera.iter() {
+        transform.translation.x += scroll.direction * SCROLL_SPEED * time.delta();
+        if !(0.0..=max_x).contains(&transform.translation.x) {
+            scroll.direction = -scroll.direction;
+            transform.translation.x = transform.translation.x.clamp(0.0, max_x);
+        }
+    }
+}
@@ -1,10 +1,19 @@
+pub use tubereng_aseprite as aseprite;
 pub use tubereng_asset as asset;
 pub use tubereng_core as core;
 pub use tubereng_ecs as ecs;
+#[cfg(feature = "egui")]
+pub use tubereng_egui as egui;
 pub use tubereng_engine as engine;
+pub use tubereng_font as font;
+pub use tubereng_gltf as gltf;
 pub use tubereng_gui as gui;
 pub use tubereng_image as image;
 pub use tubereng_input as input;
+pub use tubereng_ldtk as ldtk;
 pub use tubereng_math as math;
+pub use tubereng_obj as obj;
 pub use tubereng_renderer as renderer;
 pub use tubereng_winit as winit;
+
+pub mod prelude;
@@ -0,0 +1,49 @@
+//! Types used by most projects, to be glob imported:
+//! ```
+//! use tubereng::prelude::*;
+//! ```
+
+pub use tubereng_asset::{scene::Scene, AssetHandle as Handle, AssetStore};
+pub use tubereng_core::{
+    clipboard::Clipboard,
+    exit::AppExit,
+    interpolation::InterpolatedTransform,
+    time::{FixedTime, FrameTiming, Time},
+    Transform,
+};
+pub use tubereng_ecs::{
+    commands::CommandQueue,
+    event::{EventReader, EventWriter},
+    plugin::Plugin,
+    query::{Added, Changed},
+    relationship::ChildOf,
+    scene_stack::SceneStack,
+    state::State,
+    system::{stages, Res, ResMut, Q},
+    tag::Tag,
+    Bundle, EntityId,
+};
+pub use tubereng_engine::{
+    diagnostics::Diagnostics,
+    hot_reload::TextureAssets,
+    scene::{SceneCommands, SceneSpawner},
+    texture_descriptor,
+    window::{CursorIcon, FullscreenMode, Window, WindowSettings},
+    Engine, StartupPhase,
+};
+pub use tubereng_font::Font;
+pub use tubereng_image::Image;
+pub use tubereng_input::{
+    action::{ActionMap, Binding},
+    haptics::Haptics,
+    keyboard::{Key, Modifier},
+    mouse::Button,
+    InputState,
+};
+pub use tubereng_math::vector::{Vector2f, Vector3f};
+pub use tubereng_renderer::{
+    camera,
+    sprite::{AnimatedSprite, AnimatedSpriteBundle, AnimationState, Sprite, SpriteBundle},
+    texture, Color, GraphicsState,
+};
+pub use tubereng_winit::WinitTuberRunner;